@@ -0,0 +1,179 @@
+//! Event and function lookups in contract ABIs
+//!
+//! ABIs often contain several events with the same name, e.g. a `Transfer` from
+//! an ERC-20 interface and one from a library. Events are always matched by their
+//! full signature, so the topic0 the indexer filters on is the one in the ABI.
+
+use crate::artifact;
+use alloy::json_abi::{Event, Function, JsonAbi};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// Read a contract ABI file or build artifact
+pub fn load(path: &Path) -> Result<JsonAbi> {
+    let abi = artifact::read(path)?;
+    serde_json::from_value(abi).context(format!("Failed to parse ABI: {}", path.display()))
+}
+
+/// Parse an ABI that has already been read as JSON
+pub fn from_value(abi: &Value) -> Result<JsonAbi> {
+    serde_json::from_value(abi.clone()).context("Failed to parse ABI")
+}
+
+/// SHA-256 of an ABI, recorded in spec IRs to detect ABI changes
+///
+/// The ABI is re-serialized first, so reformatting the file doesn't count as a change.
+pub fn abi_hash(abi: &Value) -> String {
+    hex::encode(Sha256::digest(abi.to_string().as_bytes()))
+}
+
+/// Full signatures of the events in an ABI, e.g. `Transfer(address,address,uint256)`
+pub fn event_signatures(abi: &JsonAbi) -> Vec<String> {
+    let mut signatures: Vec<String> = abi.events().map(Event::signature).collect();
+    signatures.sort();
+    signatures
+}
+
+/// The event with this signature, or an error listing the candidates
+///
+/// Candidates are the events named `event_name`, or every event if none has that name.
+pub fn resolve_event<'a>(
+    abi: &'a JsonAbi,
+    event_name: &str,
+    event_signature: &str,
+) -> Result<&'a Event> {
+    let signature: String = event_signature
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    if let Some(event) = abi.events().find(|event| event.signature() == signature) {
+        if event.anonymous {
+            anyhow::bail!(
+                "Event {} is anonymous, so its logs have no topic0 to match",
+                signature
+            );
+        }
+        return Ok(event);
+    }
+
+    let name = signature.split('(').next().unwrap_or(event_name);
+    let mut candidates: Vec<&Event> = abi
+        .events()
+        .filter(|event| event.name == name || event.name == event_name)
+        .collect();
+    if candidates.is_empty() {
+        candidates = abi.events().collect();
+    }
+
+    let candidates: Vec<String> = candidates
+        .iter()
+        .map(|event| format!("{} (topic0 {})", event.signature(), event.selector()))
+        .collect();
+    anyhow::bail!(
+        "Event signature '{}' is not in the ABI. Candidates:\n  - {}",
+        event_signature,
+        candidates.join("\n  - ")
+    )
+}
+
+/// The function with this name or full signature, or an error listing the candidates
+///
+/// A name only resolves when the function isn't overloaded.
+pub fn resolve_function<'a>(abi: &'a JsonAbi, function: &str) -> Result<&'a Function> {
+    let function: String = function.chars().filter(|c| !c.is_whitespace()).collect();
+    let name = function.split('(').next().unwrap_or_default();
+    let candidates = abi.function(name).map(Vec::as_slice).unwrap_or_default();
+    let found = if function.contains('(') {
+        candidates.iter().find(|f| f.signature() == function)
+    } else if let [only] = candidates {
+        Some(only)
+    } else {
+        None
+    };
+    if let Some(found) = found {
+        return Ok(found);
+    }
+
+    let candidates: Vec<String> = if candidates.is_empty() {
+        abi.functions().map(Function::signature).collect()
+    } else {
+        candidates.iter().map(Function::signature).collect()
+    };
+    anyhow::bail!(
+        "Function '{}' is not in the ABI, or is overloaded. Candidates:\n  - {}",
+        function,
+        candidates.join("\n  - ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::keccak256;
+    use serde_json::json;
+
+    fn overloaded_abi() -> JsonAbi {
+        from_value(&json!([
+            {
+                "type": "event",
+                "name": "Transfer",
+                "anonymous": false,
+                "inputs": [
+                    {"name": "from", "type": "address", "indexed": true},
+                    {"name": "to", "type": "address", "indexed": true},
+                    {"name": "value", "type": "uint256", "indexed": false}
+                ]
+            },
+            {
+                "type": "event",
+                "name": "Transfer",
+                "anonymous": false,
+                "inputs": [
+                    {"name": "from", "type": "address", "indexed": true},
+                    {"name": "to", "type": "address", "indexed": true},
+                    {"name": "value", "type": "uint256", "indexed": false},
+                    {"name": "data", "type": "bytes", "indexed": false}
+                ]
+            },
+            {
+                "type": "event",
+                "name": "Approval",
+                "anonymous": false,
+                "inputs": [
+                    {"name": "owner", "type": "address", "indexed": true},
+                    {"name": "spender", "type": "address", "indexed": true},
+                    {"name": "value", "type": "uint256", "indexed": false}
+                ]
+            }
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolves_overloaded_events_by_signature() {
+        let abi = overloaded_abi();
+
+        let event = resolve_event(
+            &abi,
+            "Transfer",
+            "Transfer(address, address, uint256, bytes)",
+        )
+        .unwrap();
+        assert_eq!(event.inputs.len(), 4);
+        assert_eq!(
+            event.selector(),
+            keccak256("Transfer(address,address,uint256,bytes)")
+        );
+
+        let err = resolve_event(&abi, "Transfer", "Transfer(address,uint256)")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Transfer(address,address,uint256) (topic0 0xddf252ad"));
+        assert!(err.contains("Transfer(address,address,uint256,bytes)"));
+        assert!(!err.contains("Approval"));
+
+        assert_eq!(event_signatures(&abi).len(), 3);
+    }
+}
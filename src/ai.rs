@@ -1,14 +1,30 @@
+use crate::abi;
+use crate::audit::{self, AuditLog, AuditRecord};
+use crate::chains;
+use crate::config::{AiConfig, AiTaskConfig};
+use crate::error::{self, ResultExt, SmortyError};
+use crate::field_mapping;
+use crate::index_def::IndexDef;
+use crate::json_stream::JsonStreamValidator;
+use crate::prompts::PromptTemplates;
+use crate::sql_guard;
+use crate::state_table::StateFold;
+use crate::transform::FieldFormat;
+use alloy::json_abi::JsonAbi;
 use anyhow::{Context, Result};
 use async_openai::{
     Client,
     config::OpenAIConfig,
     types::{
         ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs,
-        ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs, ResponseFormat,
-        ResponseFormatJsonSchema,
+        ChatCompletionRequestUserMessageArgs, CompletionUsage, CreateChatCompletionRequest,
+        CreateChatCompletionRequestArgs, CreateEmbeddingRequestArgs, ReasoningEffort,
+        ResponseFormat, ResponseFormatJsonSchema,
     },
 };
+use futures::StreamExt;
 use serde_json::{Value, json};
+use std::io::Write;
 
 /// JSON Schema for IrGenerationResult - enforces structured output
 pub fn ir_generation_schema() -> Value {
@@ -28,9 +44,10 @@ pub fn ir_generation_schema() -> Value {
                         "name": { "type": "string" },
                         "solidity_type": { "type": "string" },
                         "rust_type": { "type": "string" },
-                        "indexed": { "type": "boolean" }
+                        "indexed": { "type": "boolean" },
+                        "column": { "type": "string" }
                     },
-                    "required": ["name", "solidity_type", "rust_type", "indexed"],
+                    "required": ["name", "solidity_type", "rust_type", "indexed", "column"],
                     "additionalProperties": false
                 }
             },
@@ -52,10 +69,38 @@ pub fn ir_generation_schema() -> Value {
                     },
                     "indexes": {
                         "type": "array",
-                        "items": { "type": "string" }
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "name": { "type": "string" },
+                                "columns": {
+                                    "type": "array",
+                                    "items": { "type": "string" }
+                                },
+                                "method": { "type": ["string", "null"] },
+                                "predicate": { "type": ["string", "null"] },
+                                "unique": { "type": "boolean" }
+                            },
+                            "required": ["name", "columns", "method", "predicate", "unique"],
+                            "additionalProperties": false
+                        }
+                    },
+                    "computed_columns": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "name": { "type": "string" },
+                                "type": { "type": "string" },
+                                "expression": { "type": "string" },
+                                "on_insert": { "type": "boolean" }
+                            },
+                            "required": ["name", "type", "expression", "on_insert"],
+                            "additionalProperties": false
+                        }
                     }
                 },
-                "required": ["table_name", "columns", "indexes"],
+                "required": ["table_name", "columns", "indexes", "computed_columns"],
                 "additionalProperties": false
             },
             "description": { "type": "string" }
@@ -68,6 +113,43 @@ pub fn ir_generation_schema() -> Value {
     })
 }
 
+/// JSON Schema for StateFold - enforces structured output
+pub fn state_fold_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "table_name": { "type": "string" },
+            "key_columns": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "type": { "type": "string" }
+                    },
+                    "required": ["name", "type"],
+                    "additionalProperties": false
+                }
+            },
+            "value_column": { "type": "string" },
+            "deltas": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "keys": { "type": "array", "items": { "type": "string" } },
+                        "value": { "type": "string" }
+                    },
+                    "required": ["keys", "value"],
+                    "additionalProperties": false
+                }
+            }
+        },
+        "required": ["table_name", "key_columns", "value_column", "deltas"],
+        "additionalProperties": false
+    })
+}
+
 /// JSON Schema for EndpointIrResult - enforces structured output
 pub fn endpoint_ir_schema() -> Value {
     json!({
@@ -96,9 +178,14 @@ pub fn endpoint_ir_schema() -> Value {
                     "properties": {
                         "name": { "type": "string" },
                         "type": { "type": "string" },
-                        "default": { "type": ["string", "number", "integer", "boolean", "null"] }
+                        "default": { "type": ["string", "number", "integer", "boolean", "null"] },
+                        "description": { "type": "string" },
+                        "allowed_values": {
+                            "type": "array",
+                            "items": { "type": ["string", "number", "integer", "boolean"] }
+                        }
                     },
-                    "required": ["name", "type", "default"],
+                    "required": ["name", "type", "default", "description", "allowed_values"],
                     "additionalProperties": false
                 }
             },
@@ -137,6 +224,42 @@ pub fn endpoint_ir_schema() -> Value {
     })
 }
 
+/// JSON Schema for EndpointTestFixture - enforces structured output
+pub fn endpoint_test_schema() -> Value {
+    let param = json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" },
+            "value": { "type": "string" }
+        },
+        "required": ["name", "value"],
+        "additionalProperties": false
+    });
+
+    json!({
+        "type": "object",
+        "properties": {
+            "endpoint_path": { "type": "string" },
+            "cases": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "description": { "type": "string" },
+                        "path_params": { "type": "array", "items": param },
+                        "query_params": { "type": "array", "items": param }
+                    },
+                    "required": ["name", "description", "path_params", "query_params"],
+                    "additionalProperties": false
+                }
+            }
+        },
+        "required": ["endpoint_path", "cases"],
+        "additionalProperties": false
+    })
+}
+
 /// Validates and sanitizes SQL queries to catch common syntax errors
 fn validate_and_sanitize_sql(sql: &str) -> Result<String> {
     let mut sanitized = sql.to_string();
@@ -154,13 +277,15 @@ fn validate_and_sanitize_sql(sql: &str) -> Result<String> {
         sanitized = sanitized.replace(r#"\'"#, "''"); // SQL standard way to escape single quotes
     }
 
-    // Validate basic SQL structure
-    if !sanitized.trim().to_lowercase().starts_with("select")
-        && !sanitized.trim().to_lowercase().starts_with("with")
-    {
-        anyhow::bail!("SQL query must start with SELECT or WITH");
+    // Models often end the statement with a semicolon, which the guard would reject
+    let trimmed = sanitized.trim_end();
+    if let Some(statement) = trimmed.strip_suffix(';') {
+        sanitized = statement.trim_end().to_string();
     }
 
+    // Validate basic SQL structure (single read-only SELECT/WITH statement)
+    sql_guard::check_read_only_sql(&sanitized)?;
+
     // Check for balanced parentheses
     let open_parens = sanitized.matches('(').count();
     let close_parens = sanitized.matches(')').count();
@@ -182,31 +307,318 @@ fn validate_and_sanitize_sql(sql: &str) -> Result<String> {
     Ok(sanitized)
 }
 
+/// Format existing IRs as few-shot examples to append to a user prompt
+///
+/// Returns an empty string when there are no examples so the prompt is unchanged.
+fn format_examples<T: serde::Serialize>(examples: &[T]) -> Result<String> {
+    if examples.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut section = String::from(
+        "\n\nExisting IR from this project (follow the same naming and typing conventions):",
+    );
+
+    for (index, example) in examples.iter().enumerate() {
+        section.push_str(&format!(
+            "\n\nExample {}:\n{}",
+            index + 1,
+            serde_json::to_string_pretty(example).context("Failed to serialize IR example")?
+        ));
+    }
+
+    Ok(section)
+}
+
+/// Model parameters for one kind of generation request
+#[derive(Debug, Clone)]
+pub struct ModelSettings {
+    pub model: String,
+    pub temperature: f32,
+    pub max_tokens: Option<u32>,
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+impl ModelSettings {
+    pub fn new(model: String, temperature: f32) -> Self {
+        Self {
+            model,
+            temperature,
+            max_tokens: None,
+            reasoning_effort: None,
+        }
+    }
+
+    /// Apply per-task overrides on top of these settings
+    pub fn with_overrides(mut self, overrides: &AiTaskConfig) -> Self {
+        if let Some(model) = &overrides.model {
+            self.model = model.clone();
+        }
+        if let Some(temperature) = overrides.temperature {
+            self.temperature = temperature;
+        }
+        self.max_tokens = overrides.max_tokens.or(self.max_tokens);
+        self.reasoning_effort = overrides.reasoning_effort.clone().or(self.reasoning_effort);
+        self
+    }
+
+    /// Start a chat completion request using these settings
+    fn request(&self) -> CreateChatCompletionRequestArgs {
+        let mut request = CreateChatCompletionRequestArgs::default();
+        request.model(&self.model).temperature(self.temperature);
+        if let Some(max_tokens) = self.max_tokens {
+            request.max_completion_tokens(max_tokens);
+        }
+        if let Some(reasoning_effort) = &self.reasoning_effort {
+            request.reasoning_effort(reasoning_effort.clone());
+        }
+        request
+    }
+}
+
 pub struct AiClient {
     client: Client<OpenAIConfig>,
-    model: String,
-    temperature: f32,
+    /// Settings for spec IR generation
+    spec: ModelSettings,
+    /// Settings for endpoint IR and test fixture generation
+    endpoint: ModelSettings,
+    prompts: PromptTemplates,
+    /// Stream responses and echo tokens to stderr as they arrive
+    verbose: bool,
+    /// Model for the embeddings of the search index
+    embedding_model: String,
+    /// Where requests and responses are recorded, if anywhere
+    audit_log: Option<AuditLog>,
 }
 
 impl AiClient {
+    /// Create a client from the `[ai]` config, applying `[ai.spec]` and `[ai.endpoint]` overrides
+    pub fn from_config(config: &AiConfig) -> Self {
+        let base = ModelSettings::new(config.openai.model.clone(), config.openai.temperature);
+
+        let api_key = config.openai.api_key.clone();
+        let model = config.openai.model.clone();
+        let mut client = match &config.openai.base_url {
+            Some(base_url) => {
+                Self::new_with_base_url(api_key, model, config.openai.temperature, base_url.clone())
+            }
+            None => Self::new(api_key, model, config.openai.temperature),
+        };
+        client.spec = base.clone().with_overrides(&config.spec);
+        client.endpoint = base.with_overrides(&config.endpoint);
+        client.embedding_model = config.embedding_model.clone();
+        if config.audit_log {
+            client.audit_log = Some(AuditLog::new(
+                std::path::Path::new(audit::AUDIT_DIR),
+                vec![config.openai.api_key.clone()],
+            ));
+        }
+        client
+    }
+
+    /// Create a client for the OpenAI API, or for `OPENAI_BASE_URL` if it is set
     pub fn new(api_key: String, model: String, temperature: f32) -> Self {
-        let mut config = OpenAIConfig::new().with_api_key(api_key);
+        let config = OpenAIConfig::new().with_api_key(api_key);
+        let config = match std::env::var("OPENAI_BASE_URL") {
+            Ok(base_url) => config.with_api_base(base_url),
+            Err(_) => config,
+        };
+        Self::with_openai_config(config, model, temperature)
+    }
 
-        // Support custom base URL via env var (for testing or Azure OpenAI)
-        if let Ok(base_url) = std::env::var("OPENAI_BASE_URL") {
-            config = config.with_api_base(base_url);
-        }
+    /// Create a client for an OpenAI-compatible API at `base_url`
+    pub fn new_with_base_url(
+        api_key: String,
+        model: String,
+        temperature: f32,
+        base_url: String,
+    ) -> Self {
+        let config = OpenAIConfig::new()
+            .with_api_key(api_key)
+            .with_api_base(base_url);
+        Self::with_openai_config(config, model, temperature)
+    }
 
+    fn with_openai_config(config: OpenAIConfig, model: String, temperature: f32) -> Self {
         let client = Client::with_config(config);
 
+        let settings = ModelSettings::new(model, temperature);
+
         Self {
             client,
-            model,
-            temperature,
+            spec: settings.clone(),
+            endpoint: settings,
+            prompts: PromptTemplates::default(),
+            verbose: false,
+            embedding_model: crate::config::DEFAULT_EMBEDDING_MODEL.to_string(),
+            audit_log: None,
+        }
+    }
+
+    /// Use custom system prompt templates instead of the built-in ones
+    pub fn with_prompts(mut self, prompts: PromptTemplates) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    /// Stream responses, printing tokens as they arrive and validating JSON incrementally
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Model used by `embed`
+    pub fn embedding_model(&self) -> &str {
+        &self.embedding_model
+    }
+
+    /// Embed each input, in one request
+    pub async fn embed(&self, inputs: Vec<String>) -> error::Result<Vec<Vec<f32>>> {
+        let count = inputs.len();
+        let request = CreateEmbeddingRequestArgs::default()
+            .model(&self.embedding_model)
+            .input(inputs)
+            .build()
+            .context("Failed to build embedding request")
+            .kind(SmortyError::Ai)?;
+
+        let mut response = self
+            .client
+            .embeddings()
+            .create(request)
+            .await
+            .context("Failed to call OpenAI embeddings API")
+            .kind(SmortyError::Ai)?;
+
+        if response.data.len() != count {
+            return Err(SmortyError::Ai(anyhow::anyhow!(
+                "Expected {} embeddings, got {}",
+                count,
+                response.data.len()
+            )));
+        }
+        response.data.sort_by_key(|embedding| embedding.index);
+        Ok(response
+            .data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+
+    /// Record requests and responses in an audit log
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Send a chat completion request and return the response content
+    async fn complete(&self, request: CreateChatCompletionRequest) -> error::Result<String> {
+        let started = std::time::Instant::now();
+        let (result, usage) = if self.verbose {
+            (self.complete_streaming(request.clone()).await, None)
+        } else {
+            self.complete_once(request.clone()).await
+        };
+
+        if let Some(audit_log) = &self.audit_log {
+            let outcome = result.as_deref().map_err(|e| format!("{:#}", e));
+            audit_log.record(&AuditRecord::new(
+                &request,
+                outcome,
+                usage.as_ref(),
+                started.elapsed(),
+            ));
         }
+
+        result
+    }
+
+    /// Send a chat completion request without streaming, returning its token usage
+    async fn complete_once(
+        &self,
+        request: CreateChatCompletionRequest,
+    ) -> (error::Result<String>, Option<CompletionUsage>) {
+        let response = match self
+            .client
+            .chat()
+            .create(request)
+            .await
+            .context("Failed to call OpenAI API")
+            .kind(SmortyError::Ai)
+        {
+            Ok(response) => response,
+            Err(e) => return (Err(e), None),
+        };
+
+        let content = response
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .context("No response from AI")
+            .kind(SmortyError::Ai);
+        (content, response.usage)
+    }
+
+    /// Stream a chat completion, stopping as soon as the JSON is complete or malformed
+    async fn complete_streaming(
+        &self,
+        request: CreateChatCompletionRequest,
+    ) -> error::Result<String> {
+        let mut stream = self
+            .client
+            .chat()
+            .create_stream(request)
+            .await
+            .context("Failed to call OpenAI API")
+            .kind(SmortyError::Ai)?;
+
+        let mut content = String::new();
+        let mut validator = JsonStreamValidator::default();
+        let mut stderr = std::io::stderr();
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk
+                .context("Failed to read streamed AI response")
+                .kind(SmortyError::Ai)?;
+            let Some(token) = chunk
+                .choices
+                .into_iter()
+                .next()
+                .and_then(|choice| choice.delta.content)
+            else {
+                continue;
+            };
+
+            let _ = write!(stderr, "{}", token);
+            let _ = stderr.flush();
+            content.push_str(&token);
+
+            // Dropping the stream cancels the request, so a bad response is retried sooner
+            validator
+                .push(&token)
+                .context("Malformed JSON in streamed AI response")
+                .kind(SmortyError::Ai)?;
+            if validator.is_complete() {
+                break;
+            }
+        }
+        let _ = writeln!(stderr);
+
+        if content.is_empty() {
+            return Err(SmortyError::Ai(anyhow::anyhow!("No response from AI")));
+        }
+        if !validator.is_complete() {
+            return Err(SmortyError::Ai(anyhow::anyhow!(
+                "AI response ended before the JSON was complete"
+            )));
+        }
+
+        Ok(content)
     }
 
     /// Generate IR (Intermediate Representation) for an event spec
+    #[allow(clippy::too_many_arguments)]
     pub async fn generate_ir(
         &self,
         contract_name: &str,
@@ -217,80 +629,114 @@ impl AiClient {
         abi: &Value,
         task_description: &str,
     ) -> Result<IrGenerationResult> {
-        let system_prompt = r#"You are an expert Ethereum indexer code generator.
-Given a contract ABI and a natural language task description, you will:
-
-1. Analyze the ABI to find the relevant event
-2. Extract event parameters and their types
-3. Determine which fields to index based on the task
-4. Generate a JSON schema for the database table
-5. Identify query parameters and filters needed for the API endpoint
-
-Return your response in the following JSON format:
-{
-  "event_name": "EventName",
-  "event_signature": "EventName(uint256,address)",
-  "start_block": 12345678,
-  "contract_address: "0xContractAddress",
-  "chain": "chain_name",
-  "indexed_fields": [
-    {"name": "field1", "solidity_type": "uint256", "rust_type": "String", "indexed": false},
-    {"name": "field2", "solidity_type": "address", "rust_type": "String", "indexed": true}
-  ],
-  "table_schema": {
-    "table_name": "event_table_name",
-    "columns": [
-      {"name": "id", "type": "BIGSERIAL PRIMARY KEY"},
-      {"name": "block_number", "type": "BIGINT NOT NULL"},
-      {"name": "block_timestamp", "type": "BIGINT NOT NULL"},
-      {"name": "transaction_hash", "type": "VARCHAR(66) NOT NULL"},
-      {"name": "log_index", "type": "INTEGER NOT NULL"},
-      {"name": "field_1", "type": "NUMERIC(78, 0) NOT NULL"},
-      {"name": "field_2", "type": "VARCHAR(42) NOT NULL"}
-    ],
-    "indexes": [
-      "CREATE INDEX idx_block_number ON {table_name}(block_number)",
-      "CREATE INDEX idx_timestamp ON {table_name}(block_timestamp)"
-    ]
-  },
-  "description": "A brief and concise description of the event to be indexed"
-}
+        self.generate_ir_with_examples(
+            contract_name,
+            spec_name,
+            start_block,
+            contract_address,
+            chain,
+            abi,
+            task_description,
+            &[],
+        )
+        .await
+    }
+
+    /// Generate IR for an event spec, including existing project IRs as few-shot examples
+    #[allow(clippy::too_many_arguments)]
+    pub async fn generate_ir_with_examples(
+        &self,
+        contract_name: &str,
+        spec_name: &str,
+        start_block: Option<u64>,
+        contract_address: &str,
+        chain: &str,
+        abi: &Value,
+        task_description: &str,
+        examples: &[IrGenerationResult],
+    ) -> Result<IrGenerationResult> {
+        const MAX_RETRIES: usize = 3;
 
-Important Solidity to PostgreSQL type mappings:
-- uint8 -> SMALLINT
-- uint16 -> INTEGER
-- uint24 -> INTEGER
-- uint32 -> BIGINT
-- uint40, uint48, uint56, uint64 -> BIGINT
-- uint72, uint80, uint88, uint96, uint104, uint112, uint120, uint128 -> NUMERIC(39, 0)
-- uint136, uint144, uint152, uint160, uint168, uint176, uint184, uint192, uint200, uint208, uint216, uint224, uint232, uint240, uint248, uint256 -> NUMERIC(78, 0)
-- address -> VARCHAR(42)
-- bytes1-bytes32 -> VARCHAR(66)
-- bytes (dynamic) -> TEXT
-- string -> TEXT
-- bool -> BOOLEAN
-
-For indexed event parameters, note them in the response but they don't need special database treatment.
-
-IMPORTANT: Table naming convention (STRICT):
-- Use abbreviated contract names to avoid PostgreSQL 63-character identifier limit
-- Format: {abbreviated_contract}_{spec_name} (lowercase with underscores)
-- Abbreviate contract names by:
-  1. Taking first 2-3 letters of each major component
-  2. Keeping it under 20 characters total for contract abbreviation
-  3. Making it recognizable but concise
-- Examples:
-  * "FeeManagerV3_Beets_Sonic_ETHUSD6h" + "PoolUpdated" = "fmv3_beets_sonic_poolupdated" (30 chars)
-  * "BalancerV2_Vault" + "Swap" = "balv2_vault_swap" (16 chars)
-  * "UniswapV3Pool" + "Mint" = "univ3_pool_mint" (15 chars)
-- Convert camelCase field names to snake_case: swapFeePercentage → swap_fee_percentage
-- Keep spec names concise but descriptive
-- NEVER use generic names like "pool_updated_events"
-- This prevents table collisions and PostgreSQL identifier truncation issues."#;
+        // Events are matched by full signature, since an ABI can have several with one name
+        let json_abi = match abi::from_value(abi) {
+            Ok(json_abi) => Some(json_abi),
+            Err(e) => {
+                tracing::warn!("Not checking the event signature: {:#}", e);
+                None
+            }
+        };
+        let mut last_error = None;
 
+        for attempt in 1..=MAX_RETRIES {
+            let mut ir = self
+                .generate_ir_internal(
+                    contract_name,
+                    spec_name,
+                    start_block,
+                    contract_address,
+                    chain,
+                    abi,
+                    task_description,
+                    examples,
+                    json_abi.as_ref(),
+                    last_error.as_deref(),
+                )
+                .await?;
+
+            let checked = match &json_abi {
+                Some(json_abi) => abi::resolve_event(json_abi, &ir.event_name, &ir.event_signature)
+                    .map(|event| ir.event_signature = event.signature()),
+                None => Ok(()),
+            };
+            match checked.and_then(|()| field_mapping::map_columns(&mut ir)) {
+                Ok(()) => return Ok(ir),
+                Err(e) => {
+                    let error_msg = format!("{:#}", e);
+                    tracing::warn!(
+                        "{} (attempt {}/{}), retrying...",
+                        error_msg,
+                        attempt,
+                        MAX_RETRIES
+                    );
+                    last_error = Some(error_msg);
+                }
+            }
+        }
+
+        Err(anyhow::anyhow!(
+            "Failed to generate a valid IR after {} attempts. Last error: {}",
+            MAX_RETRIES,
+            last_error.unwrap_or_else(|| "Unknown error".to_string())
+        ))
+    }
+
+    /// Single IR generation request, with the events of the ABI and the previous attempt's error
+    #[allow(clippy::too_many_arguments)]
+    async fn generate_ir_internal(
+        &self,
+        contract_name: &str,
+        spec_name: &str,
+        start_block: Option<u64>,
+        contract_address: &str,
+        chain: &str,
+        abi: &Value,
+        task_description: &str,
+        examples: &[IrGenerationResult],
+        json_abi: Option<&JsonAbi>,
+        previous_error: Option<&str>,
+    ) -> Result<IrGenerationResult> {
         let sblock = start_block.unwrap_or(0);
 
-        let user_prompt = format!(
+        let system_prompt = self.prompts.render_spec(&[
+            ("contract_name", contract_name),
+            ("spec_name", spec_name),
+            ("start_block", &sblock.to_string()),
+            ("contract_address", contract_address),
+            ("chain", chain),
+            ("task", task_description),
+        ]);
+
+        let mut user_prompt = format!(
             r#"Contract: {}
 Spec Name:
 {}
@@ -303,7 +749,7 @@ Contract Address:
 
 Chain:
 {}
-
+{}
 ABI:
 {}
 
@@ -316,9 +762,29 @@ Please generate the IR for this indexing specification."#,
             sblock,
             contract_address,
             chain,
+            chains::describe(chain)
+                .map(|details| format!("\nChain Details:\n{}\n", details))
+                .unwrap_or_default(),
             serde_json::to_string_pretty(abi)?,
             task_description,
         );
+        if let Some(json_abi) = json_abi {
+            user_prompt.push_str(&format!(
+                "\n\nEvents in the ABI by full signature. Several events can share a name, use the signature of the one the task describes as event_signature:\n{}",
+                abi::event_signatures(json_abi)
+                    .iter()
+                    .map(|signature| format!("- {}", signature))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+        }
+        if let Some(error) = previous_error {
+            user_prompt.push_str(&format!(
+                "\n\nIMPORTANT - Previous attempt failed with error: {}\nPlease fix this issue in your response.",
+                error
+            ));
+        }
+        user_prompt.push_str(&format_examples(examples)?);
 
         let messages = vec![
             ChatCompletionRequestMessage::System(
@@ -345,29 +811,18 @@ Please generate the IR for this indexing specification."#,
             },
         };
 
-        let request = CreateChatCompletionRequestArgs::default()
-            .model(&self.model)
+        let request = self
+            .spec
+            .request()
             .messages(messages)
-            .temperature(self.temperature)
             .response_format(response_format)
             .build()?;
 
-        let response = self
-            .client
-            .chat()
-            .create(request)
-            .await
-            .context("Failed to call OpenAI API")?;
-
-        let content = response
-            .choices
-            .first()
-            .and_then(|choice| choice.message.content.as_ref())
-            .context("No response from AI")?;
+        let content = self.complete(request).await?;
 
         // With structured outputs, response is guaranteed valid JSON (no markdown)
         let ir: IrGenerationResult =
-            serde_json::from_str(content).context("Failed to parse AI response as JSON")?;
+            serde_json::from_str(&content).context("Failed to parse AI response as JSON")?;
 
         Ok(ir)
     }
@@ -379,9 +834,68 @@ Please generate the IR for this indexing specification."#,
         endpoint_description: &str,
         task_description: &str,
         available_tables: &[IrGenerationResult],
+    ) -> Result<EndpointIrResult> {
+        self.generate_endpoint_ir_with_examples(
+            endpoint_path,
+            endpoint_description,
+            task_description,
+            available_tables,
+            &[],
+        )
+        .await
+    }
+
+    /// Generate IR for an API endpoint, including existing project endpoint IRs as few-shot examples
+    pub async fn generate_endpoint_ir_with_examples(
+        &self,
+        endpoint_path: &str,
+        endpoint_description: &str,
+        task_description: &str,
+        available_tables: &[IrGenerationResult],
+        examples: &[EndpointIrResult],
+    ) -> Result<EndpointIrResult> {
+        self.generate_endpoint_ir_with_retries(
+            endpoint_path,
+            endpoint_description,
+            task_description,
+            available_tables,
+            examples,
+            None,
+        )
+        .await
+    }
+
+    /// Regenerate endpoint IR whose SQL failed, with the failure fed back to the model
+    pub async fn repair_endpoint_ir(
+        &self,
+        endpoint_path: &str,
+        endpoint_description: &str,
+        task_description: &str,
+        available_tables: &[IrGenerationResult],
+        failure: &str,
+    ) -> Result<EndpointIrResult> {
+        self.generate_endpoint_ir_with_retries(
+            endpoint_path,
+            endpoint_description,
+            task_description,
+            available_tables,
+            &[],
+            Some(failure.to_string()),
+        )
+        .await
+    }
+
+    /// Generate endpoint IR, retrying with the previous error until its SQL validates
+    async fn generate_endpoint_ir_with_retries(
+        &self,
+        endpoint_path: &str,
+        endpoint_description: &str,
+        task_description: &str,
+        available_tables: &[IrGenerationResult],
+        examples: &[EndpointIrResult],
+        mut last_error: Option<String>,
     ) -> Result<EndpointIrResult> {
         const MAX_RETRIES: usize = 3;
-        let mut last_error = None;
 
         for attempt in 1..=MAX_RETRIES {
             tracing::info!(
@@ -396,6 +910,7 @@ Please generate the IR for this indexing specification."#,
                     endpoint_description,
                     task_description,
                     available_tables,
+                    examples,
                     last_error.as_deref(),
                 )
                 .await;
@@ -436,6 +951,170 @@ Please generate the IR for this indexing specification."#,
         ))
     }
 
+    /// Generate the fold of a state table from the spec's event table
+    pub async fn generate_state_fold(
+        &self,
+        table_name: &str,
+        task_description: &str,
+        ir: &IrGenerationResult,
+    ) -> Result<StateFold> {
+        const MAX_RETRIES: usize = 3;
+        let mut last_error: Option<String> = None;
+
+        for attempt in 1..=MAX_RETRIES {
+            let mut fold = self
+                .generate_state_fold_internal(
+                    table_name,
+                    task_description,
+                    ir,
+                    last_error.as_deref(),
+                )
+                .await?;
+            fold.table_name = table_name.to_string();
+
+            match fold.validate(&ir.table_schema.table_name) {
+                Ok(()) => return Ok(fold),
+                Err(e) => {
+                    let error_msg = format!("{:#}", e);
+                    tracing::warn!(
+                        "Invalid state fold (attempt {}/{}): {}",
+                        attempt,
+                        MAX_RETRIES,
+                        error_msg
+                    );
+                    last_error = Some(error_msg);
+                }
+            }
+        }
+
+        Err(anyhow::anyhow!(
+            "Failed to generate a valid state fold after {} attempts. Last error: {}",
+            MAX_RETRIES,
+            last_error.unwrap_or_else(|| "Unknown error".to_string())
+        ))
+    }
+
+    async fn generate_state_fold_internal(
+        &self,
+        table_name: &str,
+        task_description: &str,
+        ir: &IrGenerationResult,
+        previous_error: Option<&str>,
+    ) -> Result<StateFold> {
+        let mut user_prompt = format!(
+            r#"Event table: {}
+Event: {}
+Columns: {}
+
+State table: {}
+
+State Description:
+{}
+
+Please generate the fold for this state table."#,
+            ir.table_schema.table_name,
+            ir.event_signature,
+            ir.table_schema
+                .columns
+                .iter()
+                .map(|col| format!("{} ({})", col.name, col.column_type))
+                .collect::<Vec<_>>()
+                .join(", "),
+            table_name,
+            task_description
+        );
+        if let Some(error) = previous_error {
+            user_prompt.push_str(&format!(
+                "\n\nIMPORTANT - Previous attempt failed with error: {}\nPlease fix this issue in your response.",
+                error
+            ));
+        }
+
+        let messages = vec![
+            ChatCompletionRequestMessage::System(
+                ChatCompletionRequestSystemMessageArgs::default()
+                    .content(crate::prompts::STATE_FOLD_PROMPT)
+                    .build()?,
+            ),
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(user_prompt)
+                    .build()?,
+            ),
+        ];
+
+        let response_format = ResponseFormat::JsonSchema {
+            json_schema: ResponseFormatJsonSchema {
+                name: "state_fold".to_string(),
+                description: Some("Fold of events into a current-state table".to_string()),
+                schema: Some(state_fold_schema()),
+                strict: Some(true),
+            },
+        };
+
+        let request = self
+            .spec
+            .request()
+            .messages(messages)
+            .response_format(response_format)
+            .build()?;
+
+        let content = self.complete(request).await?;
+
+        serde_json::from_str(&content).context("Failed to parse AI response as JSON")
+    }
+
+    /// Generate sample request parameters for an endpoint's golden test fixture
+    pub async fn generate_endpoint_test(
+        &self,
+        endpoint_ir: &EndpointIrResult,
+    ) -> Result<EndpointTestFixture> {
+        let user_prompt = format!(
+            r#"Endpoint IR:
+{}
+
+Please generate test cases with sample parameters for this endpoint."#,
+            serde_json::to_string_pretty(endpoint_ir)?
+        );
+
+        let messages = vec![
+            ChatCompletionRequestMessage::System(
+                ChatCompletionRequestSystemMessageArgs::default()
+                    .content(crate::prompts::ENDPOINT_TEST_PROMPT)
+                    .build()?,
+            ),
+            ChatCompletionRequestMessage::User(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(user_prompt)
+                    .build()?,
+            ),
+        ];
+
+        // Use structured outputs for guaranteed JSON schema compliance
+        let response_format = ResponseFormat::JsonSchema {
+            json_schema: ResponseFormatJsonSchema {
+                name: "endpoint_test_fixture".to_string(),
+                description: Some("Sample parameters for testing an API endpoint".to_string()),
+                schema: Some(endpoint_test_schema()),
+                strict: Some(true),
+            },
+        };
+
+        let request = self
+            .endpoint
+            .request()
+            .messages(messages)
+            .response_format(response_format)
+            .build()?;
+
+        let content = self.complete(request).await?;
+
+        let fixture: EndpointTestFixture =
+            serde_json::from_str(&content).context("Failed to parse AI response as JSON")?;
+
+        Ok(fixture)
+    }
+
     /// Internal method to generate endpoint IR
     async fn generate_endpoint_ir_internal(
         &self,
@@ -443,175 +1122,14 @@ Please generate the IR for this indexing specification."#,
         endpoint_description: &str,
         task_description: &str,
         available_tables: &[IrGenerationResult],
+        examples: &[EndpointIrResult],
         previous_error: Option<&str>,
     ) -> Result<EndpointIrResult> {
-        let system_prompt = r#"You are an expert API endpoint generator for an Ethereum indexer with deep knowledge of PostgreSQL and data analytics.
-
-Given an endpoint path, description, task specification, and available database tables, you will:
-
-1. Analyze the endpoint requirements carefully
-2. Extract path parameters from the endpoint (e.g., {pool} in /api/pool/{pool})
-3. Determine all necessary query parameters (filtering, pagination, time ranges, etc.)
-4. Design and generate the appropriate SQL query to satisfy the requirements
-5. Design the response schema that matches the query output
-6. Provide utoipa-compatible type information for Rust code generation
-
-## SQL Query Capabilities
-
-You have full access to PostgreSQL features and should use them when appropriate:
-
-**Basic Operations:**
-- SELECT with WHERE clauses for filtering
-- ORDER BY for sorting (typically by timestamp DESC for time series)
-- LIMIT and OFFSET for pagination
-- Column aliasing with AS
-
-**Advanced Operations:**
-- JOINs (INNER, LEFT, RIGHT, FULL) when combining multiple tables
-- Subqueries and CTEs (WITH clauses) for complex logic
-- Window functions (ROW_NUMBER, LAG, LEAD, FIRST_VALUE, LAST_VALUE) for time series analytics
-- Aggregations (COUNT, SUM, AVG, MIN, MAX) with GROUP BY
-- Date/time functions for timestamp filtering and grouping
-- CASE statements for conditional logic
-- JSON aggregation (json_agg, jsonb_agg) for nested data
-- DISTINCT ON for deduplication
-
-**Query Parameters:**
-Always parameterize your queries using PostgreSQL numbered parameters ($1, $2, $3, etc.)
-Map parameters in the order they appear in the query.
-
-**CRITICAL SQL FORMATTING RULES:**
-1. NEVER use backslash escaping for quotes in SQL queries
-2. Use single quotes (') for string literals in SQL, not escaped quotes (\")
-3. For numeric literals, write them directly without quotes (e.g., numeric '1000000000000000000' is WRONG, use 1000000000000000000::numeric or CAST(1000000000000000000 AS NUMERIC))
-4. When the SQL query will be stored in JSON, use proper single quotes that don't require escaping
-5. Test your SQL mentally - it should be valid PostgreSQL syntax that can be executed directly
-
-**Example Complex Patterns:**
-
-1. Time series with previous value comparison:
-```sql
-SELECT
-  block_timestamp,
-  value,
-  LAG(value) OVER (ORDER BY block_timestamp) as previous_value,
-  value - LAG(value) OVER (ORDER BY block_timestamp) as change
-FROM table_name
-WHERE condition
-ORDER BY block_timestamp DESC
-LIMIT $1
-```
-
-2. Aggregated statistics:
-```sql
-SELECT
-  DATE_TRUNC('hour', to_timestamp(block_timestamp)) as hour,
-  AVG(value) as avg_value,
-  MAX(value) as max_value,
-  MIN(value) as min_value,
-  COUNT(*) as event_count
-FROM table_name
-WHERE block_timestamp >= $1
-GROUP BY hour
-ORDER BY hour DESC
-LIMIT $2
-```
-
-3. Multiple table joins:
-```sql
-SELECT
-  a.block_timestamp,
-  a.pool,
-  a.fee_percentage,
-  b.total_volume
-FROM table_a a
-INNER JOIN table_b b ON a.pool = b.pool AND a.block_number = b.block_number
-WHERE a.pool = $1
-ORDER BY a.block_timestamp DESC
-LIMIT $2
-```
-
-4. Latest state per entity:
-```sql
-WITH latest_per_pool AS (
-  SELECT DISTINCT ON (pool)
-    pool,
-    block_timestamp,
-    fee_percentage
-  FROM table_name
-  WHERE condition
-  ORDER BY pool, block_timestamp DESC
-)
-SELECT * FROM latest_per_pool
-ORDER BY block_timestamp DESC
-```
-
-## Response Format
-
-Return your response in the following JSON format:
-{
-  "endpoint_path": "/api/example/{param}",
-  "description": "Clear description of what this endpoint returns",
-  "method": "GET",
-  "path_params": [
-    {"name": "param", "type": "String", "description": "What this parameter represents"}
-  ],
-  "query_params": [
-    {"name": "limit", "type": "u32", "default": 50},
-    {"name": "startBlockTimestamp", "type": "Option<u64>", "default": "null"}
-  ],
-  "response_schema": {
-    "name": "ExampleResponse",
-    "fields": [
-      {"name": "block_number", "type": "i64", "description": "Block number where event occurred"},
-      {"name": "block_timestamp", "type": "i64", "description": "Unix timestamp of the block"},
-      {"name": "value", "type": "String", "description": "The indexed value"}
-    ]
-  },
-  "sql_query": "SELECT block_number, block_timestamp, value FROM table_name WHERE condition ORDER BY block_timestamp DESC LIMIT $1",
-  "tables_referenced": ["table_name"]
-}
-
-## Type Mappings
-
-**Rust Types for API:**
-- Integer numbers: i64 (for block numbers, timestamps, counts)
-- Unsigned numbers: u32, u64 (for pagination limits, small positive values)
-- Large integers (uint256): String (since they exceed Rust integer limits)
-- Addresses: String (hex format with 0x prefix)
-- Booleans: bool
-- Optional values: Option<T>
-- Arrays: Vec<T>
-- Decimals: String (for precise financial values)
-
-**PostgreSQL to Rust:**
-- BIGINT → i64
-- NUMERIC(78, 0) → String (for uint256)
-- VARCHAR(42) → String (for addresses)
-- TEXT → String
-- BOOLEAN → bool
-- INTEGER → i32
-
-## Important Guidelines
-
-1. **Pagination**: Always include 'limit' query parameter with reasonable defaults (e.g., 50, max 200)
-2. **Time Filtering**: Support startBlockTimestamp and/or endBlockTimestamp when dealing with time series. Use Option<u64> with default: "null" (the string "null") to make it optional. When NULL, the query should return the latest data ordered DESC. When provided, filter from that timestamp onwards.
-3. **Validation**: Cap limit at 200 to prevent abuse
-4. **Ordering**: Default to DESC for time series (newest first) to show most recent data
-5. **Performance**: Create efficient queries with proper WHERE clauses and indexes
-6. **Null Handling**: Use Option<T> for nullable fields in response schemas
-7. **Response Fields**: Must exactly match SQL query columns (name and type)
-8. **Tables Referenced**: List all tables used in the query (including subqueries and CTEs)
-
-## Task Analysis
-
-Carefully read the task description to understand:
-- What data needs to be returned
-- What filtering is required
-- Whether aggregation or analytics are needed
-- Time range requirements
-- Pagination needs
-- Any special computations or transformations"#;
+        let system_prompt = self.prompts.render_endpoint(&[
+            ("endpoint_path", endpoint_path),
+            ("description", endpoint_description),
+            ("task", task_description),
+        ]);
 
         let tables_info = available_tables
             .iter()
@@ -626,10 +1144,31 @@ Carefully read the task description to understand:
                         .columns
                         .iter()
                         .map(|col| format!("{} ({})", col.name, col.column_type))
+                        .chain(ir.table_schema.computed_columns.iter().map(|col| {
+                            format!(
+                                "{} ({}, computed as {})",
+                                col.name, col.column_type, col.expression
+                            )
+                        }))
                         .collect::<Vec<_>>()
                         .join(", "),
                     ir.description
-                )
+                ) + &ir
+                    .state_fold
+                    .as_ref()
+                    .map(|fold| {
+                        format!(
+                            "\n\nTable: {}\nCurrent state folded from {}, one row per key\nColumns: {}",
+                            fold.table_name,
+                            ir.table_schema.table_name,
+                            fold.columns()
+                                .iter()
+                                .map(|col| format!("{} ({})", col.name, col.column_type))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    })
+                    .unwrap_or_default()
             })
             .collect::<Vec<_>>()
             .join("\n\n");
@@ -643,7 +1182,7 @@ Carefully read the task description to understand:
             String::new()
         };
 
-        let user_prompt = format!(
+        let mut user_prompt = format!(
             r#"Endpoint Path:
 {}
 
@@ -666,6 +1205,7 @@ REMINDER - SQL Syntax Requirements:
 4. The SQL must be valid PostgreSQL syntax that can be executed directly"#,
             endpoint_path, endpoint_description, task_description, tables_info, error_context
         );
+        user_prompt.push_str(&format_examples(examples)?);
 
         let messages = vec![
             ChatCompletionRequestMessage::System(
@@ -690,29 +1230,18 @@ REMINDER - SQL Syntax Requirements:
             },
         };
 
-        let request = CreateChatCompletionRequestArgs::default()
-            .model(&self.model)
+        let request = self
+            .endpoint
+            .request()
             .messages(messages)
-            .temperature(self.temperature)
             .response_format(response_format)
             .build()?;
 
-        let response = self
-            .client
-            .chat()
-            .create(request)
-            .await
-            .context("Failed to call OpenAI API")?;
-
-        let content = response
-            .choices
-            .first()
-            .and_then(|choice| choice.message.content.as_ref())
-            .context("No response from AI")?;
+        let content = self.complete(request).await?;
 
         // With structured outputs, response is guaranteed valid JSON (no markdown)
         let endpoint_ir: EndpointIrResult =
-            serde_json::from_str(content).context("Failed to parse AI response as JSON")?;
+            serde_json::from_str(&content).context("Failed to parse AI response as JSON")?;
 
         Ok(endpoint_ir)
     }
@@ -728,6 +1257,12 @@ pub struct IrGenerationResult {
     pub indexed_fields: Vec<EventField>,
     pub table_schema: TableSchema,
     pub description: String,
+    /// Hash of the contract ABI the IR was generated from, see `abi::abi_hash`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abi_hash: Option<String>,
+    /// Generated fold of the spec's state table, see `state_table`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_fold: Option<StateFold>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -736,13 +1271,19 @@ pub struct EventField {
     pub solidity_type: String,
     pub rust_type: String,
     pub indexed: bool,
+    /// Column the field is stored in, see `field_mapping`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TableSchema {
     pub table_name: String,
     pub columns: Vec<ColumnDef>,
-    pub indexes: Vec<String>,
+    pub indexes: Vec<IndexDef>,
+    /// Columns derived from other columns of the row
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub computed_columns: Vec<ComputedColumn>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -752,12 +1293,45 @@ pub struct ColumnDef {
     pub column_type: String,
 }
 
+/// Column computed from a SQL expression over other columns, e.g. `amount / 1e18`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ComputedColumn {
+    pub name: String,
+    /// Column type, without constraints
+    #[serde(rename = "type")]
+    pub column_type: String,
+    pub expression: String,
+    /// Compute the value in the indexer instead of as a generated column, for
+    /// expressions Postgres doesn't allow there, such as ones that aren't immutable
+    #[serde(default)]
+    pub on_insert: bool,
+}
+
+impl ComputedColumn {
+    /// Column type in the table definition
+    pub fn definition(&self) -> String {
+        if self.on_insert {
+            self.column_type.clone()
+        } else {
+            format!(
+                "{} GENERATED ALWAYS AS ({}) STORED",
+                self.column_type, self.expression
+            )
+        }
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct QueryParam {
     pub name: String,
     #[serde(rename = "type")]
     pub param_type: String,
     pub default: Option<serde_json::Value>,
+    #[serde(default)]
+    pub description: String,
+    /// Values the parameter is restricted to, empty for any value of its type
+    #[serde(default)]
+    pub allowed_values: Vec<serde_json::Value>,
 }
 
 /// Represents the generated IR for an API endpoint
@@ -770,7 +1344,39 @@ pub struct EndpointIrResult {
     pub query_params: Vec<QueryParam>,
     pub response_schema: ResponseSchema,
     pub sql_query: String,
+    /// Queries run before `sql_query`, feeding it their outputs, see `query_stages`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<QueryStage>,
     pub tables_referenced: Vec<String>,
+    /// OpenAPI tags, from the endpoint config; grouped by contract when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Scopes an API key needs to call the endpoint, from the endpoint config
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+    /// Seconds clients may cache responses, sent as `Cache-Control: max-age`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_ttl: Option<u64>,
+}
+
+impl EndpointIrResult {
+    /// Method and path identifying the endpoint, e.g. `POST /api/pools`
+    pub fn route(&self) -> String {
+        format!(
+            "{} {}",
+            self.method.to_ascii_uppercase(),
+            self.endpoint_path
+        )
+    }
+}
+
+/// A query whose rows feed the endpoint's later queries
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct QueryStage {
+    pub name: String,
+    pub sql_query: String,
+    /// Columns passed on, each as a `TEXT[]` parameter of the later queries
+    pub outputs: Vec<String>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -793,4 +1399,56 @@ pub struct ResponseField {
     #[serde(rename = "type")]
     pub field_type: String,
     pub description: String,
+    /// Divisor applied to the value, e.g. `1e18` for token amounts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    /// Format the value is rewritten to, see `transform`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<FieldFormat>,
+}
+
+/// Golden test fixture for an endpoint, stored under ir/endpoints/tests/
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EndpointTestFixture {
+    pub endpoint_path: String,
+    /// Method of the endpoint, set from its IR
+    #[serde(default = "default_fixture_method")]
+    pub method: String,
+    pub cases: Vec<EndpointTestCase>,
+}
+
+fn default_fixture_method() -> String {
+    "GET".to_string()
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EndpointTestCase {
+    pub name: String,
+    pub description: String,
+    pub path_params: Vec<TestParam>,
+    pub query_params: Vec<TestParam>,
+    /// Normalized EXPLAIN output recorded against the database (empty until recorded)
+    #[serde(default)]
+    pub query_plan: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TestParam {
+    pub name: String,
+    pub value: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_strips_trailing_semicolon() {
+        assert_eq!(
+            validate_and_sanitize_sql("SELECT * FROM swaps LIMIT $1;\n").unwrap(),
+            "SELECT * FROM swaps LIMIT $1"
+        );
+        assert!(validate_and_sanitize_sql("SELECT * FROM swaps;;").is_err());
+        assert!(validate_and_sanitize_sql("SELECT 1; DELETE FROM swaps;").is_err());
+    }
 }
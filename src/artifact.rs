@@ -0,0 +1,135 @@
+//! Foundry and Hardhat build artifacts as contract ABIs
+//!
+//! `abiPath` can point to a plain ABI, a Foundry artifact
+//! (`out/Pool.sol/Pool.json`), a Hardhat artifact
+//! (`artifacts/contracts/Pool.sol/Pool.json`) or a hardhat-deploy deployment
+//! (`deployments/mainnet/Pool.json`). Artifacts keep the ABI under `abi`, next to
+//! bytecode and metadata that don't affect indexing.
+//!
+//! A contract without an `address` takes it from its deployment file, or from the
+//! Foundry broadcast file set as `broadcast`, e.g.
+//! `broadcast/Deploy.s.sol/1/run-latest.json`.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// The ABI of an ABI file or build artifact
+pub fn abi(json: Value) -> Value {
+    match json {
+        Value::Object(mut artifact) if artifact.get("abi").is_some_and(Value::is_array) => {
+            artifact.remove("abi").unwrap_or_default()
+        }
+        json => json,
+    }
+}
+
+/// Read an ABI file or build artifact as JSON
+pub fn read(path: &Path) -> Result<Value> {
+    let content =
+        fs::read_to_string(path).context(format!("Failed to read ABI file: {}", path.display()))?;
+    let json = serde_json::from_str(&content)
+        .context(format!("Failed to parse ABI JSON: {}", path.display()))?;
+    Ok(abi(json))
+}
+
+/// Address a contract was deployed at, from its deployment or broadcast file
+pub fn deployed_address(abi_path: &Path, broadcast: Option<&Path>) -> Result<Option<String>> {
+    let artifact = read_json(abi_path)?;
+    if let Some(address) = artifact.get("address").and_then(Value::as_str) {
+        return Ok(Some(address.to_string()));
+    }
+
+    let Some(broadcast) = broadcast else {
+        return Ok(None);
+    };
+    let contract_name = artifact
+        .get("contractName")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .or_else(|| {
+            abi_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .context(format!(
+            "Failed to tell the contract name of {}",
+            abi_path.display()
+        ))?;
+    Ok(broadcast_address(&read_json(broadcast)?, &contract_name))
+}
+
+/// Address of the last deployment of `contract_name` in a Foundry broadcast
+fn broadcast_address(broadcast: &Value, contract_name: &str) -> Option<String> {
+    broadcast
+        .get("transactions")?
+        .as_array()?
+        .iter()
+        .rev()
+        .find(|tx| {
+            tx.get("contractName").and_then(Value::as_str) == Some(contract_name)
+                && matches!(
+                    tx.get("transactionType").and_then(Value::as_str),
+                    Some("CREATE" | "CREATE2")
+                )
+        })?
+        .get("contractAddress")?
+        .as_str()
+        .map(str::to_string)
+}
+
+fn read_json(path: &Path) -> Result<Value> {
+    let content = fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).context(format!("Failed to parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_reads_abi_and_address_from_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        let event = json!({"type": "event", "name": "Swap", "inputs": [], "anonymous": false});
+
+        let foundry = dir.path().join("Pool.json");
+        fs::write(
+            &foundry,
+            json!({"abi": [event], "bytecode": {"object": "0x60"}}).to_string(),
+        )
+        .unwrap();
+        let broadcast = dir.path().join("run-latest.json");
+        fs::write(
+            &broadcast,
+            json!({"transactions": [
+                {"transactionType": "CREATE", "contractName": "Pool", "contractAddress": "0x01"},
+                {"transactionType": "CALL", "contractName": "Pool", "contractAddress": "0x01"},
+                {"transactionType": "CREATE", "contractName": "Router", "contractAddress": "0x02"},
+                {"transactionType": "CREATE2", "contractName": "Pool", "contractAddress": "0x03"},
+            ]})
+            .to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(read(&foundry).unwrap(), json!([event]));
+        assert_eq!(
+            deployed_address(&foundry, Some(&broadcast)).unwrap(),
+            Some("0x03".to_string())
+        );
+        assert_eq!(deployed_address(&foundry, None).unwrap(), None);
+
+        let deployment = dir.path().join("Router.json");
+        fs::write(
+            &deployment,
+            json!({"address": "0x02", "abi": []}).to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            deployed_address(&deployment, None).unwrap(),
+            Some("0x02".to_string())
+        );
+        assert_eq!(read(&deployment).unwrap(), json!([]));
+    }
+}
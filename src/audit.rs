@@ -0,0 +1,221 @@
+//! Audit log of AI requests made by `gen-spec` and `gen-endpoint`
+//!
+//! Every chat completion is written to `ir/.audit/` as one JSON file holding the
+//! full request, the raw response (or the error), token counts and timing, so a bad
+//! generation can be reviewed and replayed. API keys are redacted before writing.
+
+use anyhow::{Context, Result};
+use async_openai::types::{CompletionUsage, CreateChatCompletionRequest, ResponseFormat};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Directory of audit records
+pub const AUDIT_DIR: &str = "ir/.audit";
+
+/// Replaces secrets in audit records
+const REDACTED: &str = "[REDACTED]";
+
+/// One request and its outcome
+#[derive(Debug, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: String,
+    /// What was generated, from the name of the response schema
+    pub task: String,
+    pub model: String,
+    pub duration_ms: u128,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub total_tokens: Option<u32>,
+    /// The request as sent to the API
+    pub request: Value,
+    /// Raw response content
+    pub response: Option<String>,
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    /// Record of a request; token counts are only known for non-streamed responses
+    pub fn new(
+        request: &CreateChatCompletionRequest,
+        outcome: std::result::Result<&str, String>,
+        usage: Option<&CompletionUsage>,
+        duration: Duration,
+    ) -> Self {
+        let task = match &request.response_format {
+            Some(ResponseFormat::JsonSchema { json_schema }) => json_schema.name.clone(),
+            _ => "completion".to_string(),
+        };
+        let (response, error) = match outcome {
+            Ok(content) => (Some(content.to_string()), None),
+            Err(e) => (None, Some(e)),
+        };
+
+        Self {
+            timestamp: Utc::now().to_rfc3339(),
+            task,
+            model: request.model.clone(),
+            duration_ms: duration.as_millis(),
+            prompt_tokens: usage.map(|usage| usage.prompt_tokens),
+            completion_tokens: usage.map(|usage| usage.completion_tokens),
+            total_tokens: usage.map(|usage| usage.total_tokens),
+            request: serde_json::to_value(request).unwrap_or(Value::Null),
+            response,
+            error,
+        }
+    }
+}
+
+/// Writes audit records to a directory
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    dir: PathBuf,
+    /// Values to redact, such as the configured API key
+    secrets: Vec<String>,
+}
+
+impl AuditLog {
+    pub fn new(dir: &Path, secrets: Vec<String>) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            // Short values would redact unrelated text
+            secrets: secrets
+                .into_iter()
+                .filter(|secret| secret.len() >= 8)
+                .collect(),
+        }
+    }
+
+    /// Write a record, logging rather than failing the request if it can't be written
+    pub fn record(&self, record: &AuditRecord) {
+        if let Err(e) = self.write(record) {
+            tracing::warn!("Failed to write AI audit record: {:#}", e);
+        }
+    }
+
+    fn write(&self, record: &AuditRecord) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)
+            .context(format!("Failed to create {}", self.dir.display()))?;
+
+        let json =
+            serde_json::to_string_pretty(record).context("Failed to serialize audit record")?;
+        let path = self.dir.join(format!(
+            "{}-{}.json",
+            Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+            record.task
+        ));
+        fs::write(&path, redact(&json, &self.secrets))
+            .context(format!("Failed to write {}", path.display()))?;
+
+        Ok(path)
+    }
+}
+
+/// Replace known secrets and anything shaped like an OpenAI key (`sk-...`)
+pub fn redact(text: &str, secrets: &[String]) -> String {
+    let mut text = text.to_string();
+    for secret in secrets {
+        text = text.replace(secret.as_str(), REDACTED);
+    }
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find("sk-") {
+        let key_len = rest[start + 3..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(rest.len() - start - 3);
+        redacted.push_str(&rest[..start]);
+        let word_start = rest[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_ascii_alphanumeric());
+        if word_start && key_len >= 20 {
+            redacted.push_str(REDACTED);
+        } else {
+            redacted.push_str(&rest[start..start + 3 + key_len]);
+        }
+        rest = &rest[start + 3 + key_len..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_openai::types::{
+        ChatCompletionRequestUserMessageArgs, CreateChatCompletionRequestArgs,
+        ResponseFormatJsonSchema,
+    };
+
+    #[test]
+    fn test_redact() {
+        let secrets = vec!["my-company-key-123".to_string()];
+        let text = "key my-company-key-123, sk-proj-abcdefghijklmnopqrstuvwxyz and risk-adjusted-fee-percentage";
+        assert_eq!(
+            redact(text, &secrets),
+            "key [REDACTED], [REDACTED] and risk-adjusted-fee-percentage"
+        );
+    }
+
+    #[test]
+    fn test_write_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(dir.path(), vec!["super-secret-key".to_string()]);
+
+        let request = CreateChatCompletionRequestArgs::default()
+            .model("gpt-4o")
+            .messages(vec![
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content("Track Transfer, the key is super-secret-key")
+                    .build()
+                    .unwrap()
+                    .into(),
+            ])
+            .response_format(ResponseFormat::JsonSchema {
+                json_schema: ResponseFormatJsonSchema {
+                    name: "ir_generation_result".to_string(),
+                    description: None,
+                    schema: None,
+                    strict: Some(true),
+                },
+            })
+            .build()
+            .unwrap();
+        let usage = CompletionUsage {
+            prompt_tokens: 120,
+            completion_tokens: 30,
+            total_tokens: 150,
+            prompt_tokens_details: None,
+            completion_tokens_details: None,
+        };
+        let record = AuditRecord::new(
+            &request,
+            Ok("{\"event_name\": \"Transfer\"}"),
+            Some(&usage),
+            Duration::from_millis(1500),
+        );
+
+        let path = log.write(&record).unwrap();
+        assert!(
+            path.file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .ends_with("-ir_generation_result.json")
+        );
+
+        let written: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written["task"], "ir_generation_result");
+        assert_eq!(written["total_tokens"], 150);
+        assert_eq!(written["duration_ms"], 1500);
+        assert_eq!(
+            written["request"]["messages"][0]["content"],
+            "Track Transfer, the key is [REDACTED]"
+        );
+        assert_eq!(written["response"], "{\"event_name\": \"Transfer\"}");
+    }
+}
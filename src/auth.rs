@@ -0,0 +1,214 @@
+use crate::config::{ADMIN_SCOPE, ApiKeyConfig, ServerConfig};
+use crate::server::ApiError;
+use axum::http::HeaderMap;
+use axum::http::header::AUTHORIZATION;
+use std::collections::HashMap;
+
+/// Header carrying the API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// API keys accepted by the server
+///
+/// Keys are read from `X-API-Key` or `Authorization: Bearer <key>`. When no keys
+/// are configured, authentication is disabled and every request is allowed.
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeyStore {
+    keys: HashMap<String, ApiKeyConfig>,
+}
+
+impl ApiKeyStore {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        let keys = config
+            .api_keys
+            .iter()
+            .map(|api_key| (api_key.key.clone(), api_key.clone()))
+            .collect();
+
+        Self { keys }
+    }
+
+    /// Check if requests must present an API key
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Authenticate a request from its headers
+    ///
+    /// Returns `Ok(None)` when authentication is disabled.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<Option<&ApiKeyConfig>, ApiError> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+
+        let key = Self::extract_key(headers)
+            .ok_or_else(|| ApiError::Unauthorized("Missing API key".to_string()))?;
+
+        let api_key = self
+            .keys
+            .get(key)
+            .ok_or_else(|| ApiError::Unauthorized("Invalid API key".to_string()))?;
+
+        tracing::debug!("Authenticated API key: {}", api_key.display_name());
+
+        Ok(Some(api_key))
+    }
+
+    /// Authenticate a request and check that its key has every scope in `scopes`
+    pub fn authorize(
+        &self,
+        headers: &HeaderMap,
+        scopes: &[String],
+    ) -> Result<Option<&ApiKeyConfig>, ApiError> {
+        let api_key = self.authenticate(headers)?;
+        if let Some(api_key) = api_key {
+            let missing: Vec<&str> = scopes
+                .iter()
+                .filter(|scope| !api_key.scopes.contains(scope))
+                .map(String::as_str)
+                .collect();
+            if !missing.is_empty() {
+                return Err(ApiError::Forbidden(format!(
+                    "API key lacks required scopes: {}",
+                    missing.join(", ")
+                )));
+            }
+        }
+        Ok(api_key)
+    }
+
+    /// Authenticate a request to an admin route
+    ///
+    /// Without API keys there is no way to tell an operator from anyone else, so
+    /// admin routes are forbidden until keys are configured, and then need a key
+    /// with the [`ADMIN_SCOPE`].
+    pub fn authenticate_admin(&self, headers: &HeaderMap) -> Result<&ApiKeyConfig, ApiError> {
+        match self.authenticate(headers)? {
+            Some(api_key) if api_key.is_admin() => Ok(api_key),
+            Some(_) => Err(ApiError::Forbidden(format!(
+                "Admin routes require an API key with the '{}' scope",
+                ADMIN_SCOPE
+            ))),
+            None => Err(ApiError::Forbidden(
+                "Admin routes require API keys to be configured".to_string(),
+            )),
+        }
+    }
+
+    fn extract_key(headers: &HeaderMap) -> Option<&str> {
+        if let Some(key) = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+            return Some(key.trim());
+        }
+
+        headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(|key| key.trim())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn create_store() -> ApiKeyStore {
+        ApiKeyStore::from_config(&ServerConfig {
+            api_keys: vec![ApiKeyConfig {
+                key: "secret".to_string(),
+                name: Some("acme".to_string()),
+                metadata: HashMap::from([("tenant".to_string(), "acme".to_string())]),
+                scopes: vec!["pools:read".to_string()],
+            }],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_disabled_store_allows_all() {
+        let store = ApiKeyStore::default();
+        assert!(!store.is_enabled());
+        assert!(store.authenticate(&HeaderMap::new()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_authenticate_with_header() {
+        let store = create_store();
+        let mut headers = HeaderMap::new();
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("secret"));
+
+        let api_key = store.authenticate(&headers).unwrap().unwrap();
+        assert_eq!(api_key.display_name(), "acme");
+    }
+
+    #[test]
+    fn test_authenticate_with_bearer_token() {
+        let store = create_store();
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+
+        assert!(store.authenticate(&headers).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_authorize_checks_scopes() {
+        let store = create_store();
+        let mut headers = HeaderMap::new();
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("secret"));
+
+        assert!(store.authorize(&headers, &[]).unwrap().is_some());
+        assert!(
+            store
+                .authorize(&headers, &["pools:read".to_string()])
+                .is_ok()
+        );
+        assert!(matches!(
+            store.authorize(&headers, &["pools:read".to_string(), "admin".to_string()]),
+            Err(ApiError::Forbidden(message)) if message.ends_with(": admin")
+        ));
+    }
+
+    #[test]
+    fn test_admin_routes_need_admin_scope() {
+        let mut headers = HeaderMap::new();
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("secret"));
+        assert!(matches!(
+            ApiKeyStore::default().authenticate_admin(&headers),
+            Err(ApiError::Forbidden(_))
+        ));
+        assert!(matches!(
+            create_store().authenticate_admin(&headers),
+            Err(ApiError::Forbidden(message)) if message.contains("'admin' scope")
+        ));
+
+        let store = ApiKeyStore::from_config(&ServerConfig {
+            api_keys: vec![ApiKeyConfig {
+                key: "secret".to_string(),
+                name: Some("ops".to_string()),
+                metadata: HashMap::new(),
+                scopes: vec![ADMIN_SCOPE.to_string()],
+            }],
+            ..Default::default()
+        });
+        assert_eq!(
+            store.authenticate_admin(&headers).unwrap().display_name(),
+            "ops"
+        );
+    }
+
+    #[test]
+    fn test_authenticate_rejects_missing_and_invalid_keys() {
+        let store = create_store();
+        assert!(matches!(
+            store.authenticate(&HeaderMap::new()),
+            Err(ApiError::Unauthorized(_))
+        ));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("wrong"));
+        assert!(matches!(
+            store.authenticate(&headers),
+            Err(ApiError::Unauthorized(_))
+        ));
+    }
+}
@@ -0,0 +1,233 @@
+use alloy::rpc::types::Log;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Logs per `eth_getLogs` request to aim for; well below common provider caps of 10k
+const TARGET_LOGS_PER_REQUEST: f64 = 2_000.0;
+const MIN_SUGGESTED_CHUNK_SIZE: u64 = 100;
+const MAX_SUGGESTED_CHUNK_SIZE: u64 = 10_000;
+/// Average request latency above which smaller chunks are suggested
+const SLOW_RPC_LATENCY: Duration = Duration::from_secs(5);
+
+/// Logs captured from a block range, replayed by `bench index --fixture`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogFixture {
+    pub chain: String,
+    pub from_block: u64,
+    pub to_block: u64,
+    pub logs: Vec<Log>,
+}
+
+impl LogFixture {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content =
+            fs::read_to_string(path).context(format!("Failed to read log fixture: {:?}", path))?;
+        serde_json::from_str(&content).context(format!("Failed to parse log fixture: {:?}", path))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create directory: {:?}", parent))?;
+        }
+        let json = serde_json::to_string(self).context("Failed to serialize log fixture")?;
+        fs::write(path, json).context(format!("Failed to write log fixture: {:?}", path))?;
+        Ok(())
+    }
+}
+
+/// Timing of a single `eth_getLogs` request
+#[derive(Debug, Clone)]
+pub struct ChunkTiming {
+    pub blocks: u64,
+    pub logs: usize,
+    pub latency: Duration,
+}
+
+/// Throughput measured for one chain by `bench index`
+#[derive(Debug, Clone, Default)]
+pub struct IndexBenchReport {
+    pub chain: String,
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Chunk size used to fetch logs
+    pub chunk_size: u64,
+    /// Logs returned for the configured contracts
+    pub logs: usize,
+    /// Logs matching an indexed event
+    pub matched: usize,
+    /// Logs that failed to decode or insert
+    pub failed: usize,
+    pub decode_time: Duration,
+    pub insert_time: Duration,
+    /// RPC requests, empty when replaying a fixture
+    pub chunks: Vec<ChunkTiming>,
+}
+
+impl IndexBenchReport {
+    pub fn blocks(&self) -> u64 {
+        self.to_block.saturating_sub(self.from_block) + 1
+    }
+
+    pub fn rpc_time(&self) -> Duration {
+        self.chunks.iter().map(|chunk| chunk.latency).sum()
+    }
+
+    /// Matched logs processed per second, excluding RPC time
+    pub fn processing_logs_per_sec(&self) -> f64 {
+        per_sec(self.matched, self.decode_time + self.insert_time)
+    }
+
+    /// Matched logs processed per second, including RPC time
+    pub fn total_logs_per_sec(&self) -> f64 {
+        per_sec(
+            self.matched,
+            self.decode_time + self.insert_time + self.rpc_time(),
+        )
+    }
+
+    /// Suggest a chunk size from the observed log density and RPC latency
+    pub fn suggested_chunk_size(&self) -> u64 {
+        let density = self.logs as f64 / self.blocks() as f64;
+        let mut suggestion = if density > 0.0 {
+            (TARGET_LOGS_PER_REQUEST / density) as u64
+        } else {
+            MAX_SUGGESTED_CHUNK_SIZE
+        };
+
+        if !self.chunks.is_empty() {
+            let average_latency = self.rpc_time() / self.chunks.len() as u32;
+            if average_latency > SLOW_RPC_LATENCY {
+                suggestion = suggestion.min(self.chunk_size / 2);
+            }
+        }
+
+        let suggestion = suggestion.clamp(MIN_SUGGESTED_CHUNK_SIZE, MAX_SUGGESTED_CHUNK_SIZE);
+        suggestion - suggestion % MIN_SUGGESTED_CHUNK_SIZE
+    }
+
+    pub fn render(&self) -> String {
+        let mut lines = vec![
+            format!(
+                "Chain '{}': blocks {}..={} ({} blocks)",
+                self.chain,
+                self.from_block,
+                self.to_block,
+                self.blocks()
+            ),
+            format!(
+                "  logs:        {} fetched, {} matched, {} failed",
+                self.logs, self.matched, self.failed
+            ),
+        ];
+
+        if self.chunks.is_empty() {
+            lines.push("  rpc:         n/a (replayed from fixture)".to_string());
+        } else {
+            lines.push(format!(
+                "  rpc:         {:.2?} over {} requests ({:.2?} avg)",
+                self.rpc_time(),
+                self.chunks.len(),
+                self.rpc_time() / self.chunks.len() as u32
+            ));
+        }
+
+        lines.push(format!(
+            "  decode:      {:.2?} ({:.1}%)",
+            self.decode_time,
+            share(self.decode_time, self.decode_time + self.insert_time)
+        ));
+        lines.push(format!(
+            "  insert:      {:.2?} ({:.1}%)",
+            self.insert_time,
+            share(self.insert_time, self.decode_time + self.insert_time)
+        ));
+        lines.push(format!(
+            "  throughput:  {:.0} logs/sec processing, {:.0} logs/sec including rpc",
+            self.processing_logs_per_sec(),
+            self.total_logs_per_sec()
+        ));
+        lines.push(format!(
+            "  chunk size:  {} (suggested: {})",
+            self.chunk_size,
+            self.suggested_chunk_size()
+        ));
+
+        lines.join("\n")
+    }
+}
+
+fn per_sec(count: usize, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        0.0
+    } else {
+        count as f64 / elapsed.as_secs_f64()
+    }
+}
+
+fn share(part: Duration, total: Duration) -> f64 {
+    if total.is_zero() {
+        0.0
+    } else {
+        part.as_secs_f64() / total.as_secs_f64() * 100.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(blocks: u64, logs: usize) -> IndexBenchReport {
+        IndexBenchReport {
+            chain: "mainnet".to_string(),
+            from_block: 1,
+            to_block: blocks,
+            chunk_size: 1000,
+            logs,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_suggested_chunk_size_follows_log_density() {
+        // 0.5 logs per block -> 4000 blocks per request
+        assert_eq!(report(1000, 500).suggested_chunk_size(), 4000);
+        // 10 logs per block -> 200 blocks per request
+        assert_eq!(report(1000, 10_000).suggested_chunk_size(), 200);
+        // Clamped at both ends
+        assert_eq!(report(1000, 0).suggested_chunk_size(), 10_000);
+        assert_eq!(report(10, 100_000).suggested_chunk_size(), 100);
+    }
+
+    #[test]
+    fn test_suggested_chunk_size_shrinks_for_slow_rpc() {
+        let mut slow = report(1000, 0);
+        slow.chunks = vec![ChunkTiming {
+            blocks: 1000,
+            logs: 0,
+            latency: Duration::from_secs(8),
+        }];
+        assert_eq!(slow.suggested_chunk_size(), 500);
+    }
+
+    #[test]
+    fn test_log_fixture_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fixtures/mainnet.json");
+        let fixture = LogFixture {
+            chain: "mainnet".to_string(),
+            from_block: 100,
+            to_block: 199,
+            logs: vec![Log::default()],
+        };
+
+        fixture.save(&path).unwrap();
+        let loaded = LogFixture::load(&path).unwrap();
+        assert_eq!(loaded.chain, "mainnet");
+        assert_eq!(loaded.to_block, 199);
+        assert_eq!(loaded.logs.len(), 1);
+    }
+}
@@ -0,0 +1,521 @@
+//! Decoded function calls
+//!
+//! A `[[contracts.<name>.calls]]` entry indexes the calls of one of the
+//! contract's functions into a table, with the calldata decoded by the ABI into
+//! a column per argument. With `via = "blocks"` (the default) calls are found by
+//! reading every block in full and keeping the transactions sent straight to the
+//! contract, which any RPC supports. With `via = "traces"` they come from
+//! `trace_filter`, which also finds calls made by other contracts but needs an
+//! RPC with the trace API.
+
+use crate::abi;
+use crate::ai::ColumnDef;
+use crate::config::{CallConfig, CallSource, ContractConfig};
+use crate::decoded::{DecodedField, DecodedValue};
+use crate::identifier;
+use crate::index_def::IndexDef;
+use crate::indexer::{BoundQuery, InsertRow};
+use crate::rpc_logs::{self, BLOCK_FETCH_CONCURRENCY};
+use alloy::dyn_abi::JsonAbiExt;
+use alloy::eips::BlockNumberOrTag;
+use alloy::json_abi::Function;
+use alloy::primitives::{Address, B256, Bytes, Selector, U256};
+use alloy::providers::Provider;
+use anyhow::{Context, Result};
+use futures::{StreamExt, TryStreamExt};
+use serde::de::DeserializeOwned;
+use serde_json::{Value as JsonValue, json};
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Contract name recorded as the source of call tables in schema.json
+pub const CALLS_SOURCE: &str = "calls";
+
+/// Columns of every call table, before the function's arguments
+const BASE_COLUMNS: [(&str, &str); 8] = [
+    ("id", "BIGSERIAL PRIMARY KEY"),
+    ("block_number", "BIGINT NOT NULL"),
+    ("block_timestamp", "BIGINT NOT NULL"),
+    ("transaction_hash", "VARCHAR(66) NOT NULL"),
+    ("trace_address", "TEXT NOT NULL"),
+    ("from_address", "VARCHAR(42) NOT NULL"),
+    ("value", "NUMERIC(78, 0) NOT NULL"),
+    ("success", "BOOLEAN NOT NULL"),
+];
+
+/// A call of a function, with its raw calldata
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub block_number: u64,
+    pub block_timestamp: u64,
+    pub transaction_hash: B256,
+    /// Position in the transaction's call tree, e.g. `0.1`, empty for the
+    /// transaction itself
+    pub trace_address: String,
+    pub from: Address,
+    pub value: U256,
+    /// Whether the call didn't revert
+    pub success: bool,
+    pub input: Bytes,
+}
+
+/// A function whose calls are indexed, on one contract
+#[derive(Debug, Clone)]
+pub struct CallSpec {
+    pub contract_name: String,
+    pub chain: String,
+    pub function: Function,
+    pub address: Address,
+    pub table: String,
+    pub start_block: u64,
+    pub via: CallSource,
+}
+
+impl CallSpec {
+    pub fn from_config(
+        contract_name: &str,
+        contract: &ContractConfig,
+        call: &CallConfig,
+    ) -> Result<Self> {
+        let abi = abi::load(Path::new(&contract.abi_path))?;
+        let function = abi::resolve_function(&abi, &call.function)
+            .context(format!("Invalid calls of contract '{}'", contract_name))?
+            .clone();
+        let address = Address::from_str(contract.address.trim()).context(format!(
+            "Invalid address '{}' of contract '{}'",
+            contract.address, contract_name
+        ))?;
+        Ok(Self {
+            contract_name: contract_name.to_string(),
+            chain: contract.chain.clone(),
+            function,
+            address,
+            table: call.table_name(contract_name),
+            start_block: call.start_block,
+            via: call.via,
+        })
+    }
+
+    /// Name the function's indexed ranges are recorded under
+    pub fn coverage_key(&self) -> String {
+        format!("calls/{}/{}", self.contract_name, self.function.signature())
+    }
+
+    /// Whether calldata is a call of the function
+    pub fn matches(&self, input: &[u8]) -> bool {
+        input.starts_with(self.selector().as_slice())
+    }
+
+    fn selector(&self) -> Selector {
+        self.function.selector()
+    }
+
+    /// Column of each argument: its snake_case name, `arg<n>` when unnamed, and
+    /// `arg_`-prefixed when it would clash with another column
+    fn argument_columns(&self) -> Vec<String> {
+        let mut taken: HashSet<String> = BASE_COLUMNS
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        self.function
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| {
+                let mut name = if input.name.is_empty() {
+                    format!("arg{}", i)
+                } else {
+                    identifier::transliterate(&input.name)
+                };
+                while taken.contains(&name) {
+                    name = format!("arg_{}", name);
+                }
+                taken.insert(name.clone());
+                name
+            })
+            .collect()
+    }
+
+    /// Columns of the call table
+    pub fn columns(&self) -> Vec<ColumnDef> {
+        let arguments = self
+            .argument_columns()
+            .into_iter()
+            .zip(&self.function.inputs)
+            .map(|(name, input)| ColumnDef {
+                name,
+                column_type: crate::subgraph::column_type(&input.selector_type()),
+            });
+        BASE_COLUMNS
+            .iter()
+            .map(|(name, column_type)| ColumnDef {
+                name: name.to_string(),
+                column_type: column_type.to_string(),
+            })
+            .chain(arguments)
+            .collect()
+    }
+
+    /// A row per call, and indexes for a caller's history
+    pub fn indexes(&self) -> Vec<IndexDef> {
+        let mut call_index =
+            IndexDef::new("idx_call_trace", &["transaction_hash", "trace_address"]);
+        call_index.unique = true;
+        vec![
+            call_index,
+            IndexDef::new("idx_call_from", &["from_address", "block_number DESC"]),
+            IndexDef::new("idx_block_number", &["block_number"]),
+        ]
+    }
+
+    /// INSERT for a call, with its arguments decoded from the calldata
+    pub fn insert_sql(&self, call: &Call) -> Result<BoundQuery> {
+        let arguments = call
+            .input
+            .get(4..)
+            .context("Calldata is shorter than a selector")?;
+        let arguments = self.function.abi_decode_input(arguments).context(format!(
+            "Failed to decode calldata of {}",
+            self.function.signature()
+        ))?;
+
+        let mut row = InsertRow::default();
+        for (column, solidity_type, value) in [
+            (
+                "block_number",
+                "int64",
+                DecodedValue::Int(call.block_number as i64),
+            ),
+            (
+                "block_timestamp",
+                "int64",
+                DecodedValue::Int(call.block_timestamp as i64),
+            ),
+            (
+                "transaction_hash",
+                "bytes32",
+                DecodedValue::Topic(call.transaction_hash),
+            ),
+            (
+                "trace_address",
+                "string",
+                DecodedValue::String(call.trace_address.clone()),
+            ),
+            ("from_address", "address", DecodedValue::Address(call.from)),
+            ("value", "uint256", DecodedValue::U256(call.value)),
+            ("success", "bool", DecodedValue::Bool(call.success)),
+        ] {
+            row.push(column, DecodedField::new(column, solidity_type, value));
+        }
+        for ((column, input), value) in self
+            .argument_columns()
+            .into_iter()
+            .zip(&self.function.inputs)
+            .zip(&arguments)
+        {
+            let field = DecodedField::new(
+                &column,
+                &input.selector_type(),
+                DecodedValue::from_sol(value),
+            );
+            row.push(&column, field);
+        }
+
+        Ok(BoundQuery {
+            sql: format!(
+                "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT (transaction_hash, trace_address) DO NOTHING",
+                self.table,
+                row.columns.join(", "),
+                row.expressions.join(", ")
+            ),
+            values: row.values,
+        })
+    }
+
+    /// Calls of the function in blocks `from_block` to `to_block`
+    pub async fn fetch<P: Provider>(
+        &self,
+        provider: &P,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<Call>> {
+        match self.via {
+            CallSource::Blocks => self.block_calls(provider, from_block, to_block).await,
+            CallSource::Traces => self.traced_calls(provider, from_block, to_block).await,
+        }
+    }
+
+    /// Transactions calling the function, from full blocks and their receipts
+    async fn block_calls<P: Provider>(
+        &self,
+        provider: &P,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<Call>> {
+        let blocks: Vec<JsonValue> = futures::stream::iter(from_block..=to_block)
+            .map(|block| async move {
+                provider
+                    .raw_request::<_, JsonValue>(
+                        "eth_getBlockByNumber".into(),
+                        (BlockNumberOrTag::Number(block), true),
+                    )
+                    .await
+                    .context(format!("Failed to fetch block {}", block))
+            })
+            .buffered(BLOCK_FETCH_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        let mut calls = Vec::new();
+        for block in blocks {
+            let block_number = block
+                .get("number")
+                .and_then(rpc_logs::parse_quantity)
+                .context("Block has no number")?;
+            let block_timestamp = block
+                .get("timestamp")
+                .and_then(rpc_logs::parse_quantity)
+                .context(format!("Block {} has no timestamp", block_number))?;
+            let transactions = block
+                .get("transactions")
+                .and_then(JsonValue::as_array)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            for transaction in transactions {
+                // Contract creations have a null `to`
+                let to: Option<Address> = field(transaction, "to")?;
+                let input: Bytes = field(transaction, "input")?;
+                if to != Some(self.address) || !self.matches(&input) {
+                    continue;
+                }
+                calls.push(Call {
+                    block_number,
+                    block_timestamp,
+                    transaction_hash: field(transaction, "hash")?,
+                    trace_address: String::new(),
+                    from: field(transaction, "from")?,
+                    value: field(transaction, "value")?,
+                    success: true,
+                    input,
+                });
+            }
+        }
+
+        for call in &mut calls {
+            let receipt: JsonValue = provider
+                .raw_request("eth_getTransactionReceipt".into(), (call.transaction_hash,))
+                .await
+                .context(format!(
+                    "Failed to fetch receipt of {:#x}",
+                    call.transaction_hash
+                ))?;
+            // Receipts from before Byzantium have no status
+            call.success = receipt.get("status").and_then(rpc_logs::parse_quantity) != Some(0);
+        }
+        Ok(calls)
+    }
+
+    /// Calls of the function, top-level or internal, from `trace_filter`
+    ///
+    /// Static and delegate calls are skipped, since they don't run the contract's
+    /// code on its own storage.
+    async fn traced_calls<P: Provider>(
+        &self,
+        provider: &P,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<Call>> {
+        let traces: Vec<JsonValue> = provider
+            .raw_request(
+                "trace_filter".into(),
+                (json!({
+                    "fromBlock": format!("{:#x}", from_block),
+                    "toBlock": format!("{:#x}", to_block),
+                    "toAddress": [self.address],
+                }),),
+            )
+            .await
+            .context(format!(
+                "Failed to fetch traces of blocks {} to {}, does the RPC support trace_filter?",
+                from_block, to_block
+            ))?;
+
+        let mut calls = Vec::new();
+        for trace in &traces {
+            let action = trace.get("action").unwrap_or(&JsonValue::Null);
+            if trace.get("type").and_then(JsonValue::as_str) != Some("call")
+                || action.get("callType").and_then(JsonValue::as_str) != Some("call")
+            {
+                continue;
+            }
+            let to: Address = field(action, "to")?;
+            let input: Bytes = field(action, "input")?;
+            if to != self.address || !self.matches(&input) {
+                continue;
+            }
+            let trace_address: Vec<u64> = field(trace, "traceAddress")?;
+            calls.push(Call {
+                block_number: trace
+                    .get("blockNumber")
+                    .and_then(rpc_logs::parse_quantity)
+                    .context("Trace has no block number")?,
+                block_timestamp: 0,
+                transaction_hash: field(trace, "transactionHash")?,
+                trace_address: trace_address
+                    .iter()
+                    .map(u64::to_string)
+                    .collect::<Vec<_>>()
+                    .join("."),
+                from: field(action, "from")?,
+                value: field(action, "value")?,
+                success: trace.get("error").is_none_or(JsonValue::is_null),
+                input,
+            });
+        }
+
+        let blocks: BTreeSet<u64> = calls.iter().map(|call| call.block_number).collect();
+        let timestamps = rpc_logs::block_timestamps(provider, blocks).await?;
+        for call in &mut calls {
+            call.block_timestamp = timestamps[&call.block_number];
+        }
+        Ok(calls)
+    }
+}
+
+/// A field of an RPC object, null when missing
+fn field<T: DeserializeOwned>(value: &JsonValue, key: &str) -> Result<T> {
+    serde_json::from_value(value.get(key).cloned().unwrap_or_default())
+        .context(format!("Invalid '{}' in RPC response", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::providers::ProviderBuilder;
+    use alloy::sol_types::SolValue;
+    use tempfile::TempDir;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_indexes_decoded_calls_from_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let abi_path = temp_dir.path().join("Router.json");
+        std::fs::write(
+            &abi_path,
+            json!([{
+                "type": "function",
+                "name": "swap",
+                "stateMutability": "nonpayable",
+                "inputs": [
+                    {"name": "amountIn", "type": "uint256"},
+                    {"name": "value", "type": "uint256"},
+                    {"name": "", "type": "address[]"}
+                ],
+                "outputs": []
+            }])
+            .to_string(),
+        )
+        .unwrap();
+        let router = "0x00000000000000000000000000000000000000aa";
+        let contract: ContractConfig = toml::from_str(&format!(
+            r#"
+            chain = "sonic"
+            address = "{}"
+            abiPath = "{}"
+
+            [[calls]]
+            function = "swap"
+            startBlock = 100
+            "#,
+            router,
+            abi_path.display()
+        ))
+        .unwrap();
+        let spec = CallSpec::from_config("Router", &contract, &contract.calls[0]).unwrap();
+        assert_eq!(spec.table, "router_swap_calls");
+        assert_eq!(
+            spec.coverage_key(),
+            "calls/Router/swap(uint256,uint256,address[])"
+        );
+        let columns: Vec<String> = spec.columns().into_iter().map(|c| c.name).collect();
+        assert_eq!(&columns[8..], ["amount_in", "arg_value", "arg2"]);
+
+        let mut input = spec.function.selector().to_vec();
+        input.extend(
+            (
+                U256::from(1000),
+                U256::from(7),
+                vec![Address::repeat_byte(1)],
+            )
+                .abi_encode_params(),
+        );
+        let input = Bytes::from(input);
+        let server = MockServer::start().await;
+        let calldata = input.clone();
+        Mock::given(method("POST"))
+            .respond_with(move |request: &Request| {
+                let body: JsonValue = serde_json::from_slice(&request.body).unwrap();
+                let result = match body["method"].as_str().unwrap() {
+                    "eth_getBlockByNumber" => json!({
+                        "number": body["params"][0],
+                        "timestamp": "0x65f0a000",
+                        "transactions": [
+                            {
+                                "hash": format!("0x{}", "11".repeat(32)),
+                                "from": format!("0x{}", "bb".repeat(20)),
+                                "to": router,
+                                "value": "0x0",
+                                "input": calldata
+                            },
+                            {
+                                "hash": format!("0x{}", "22".repeat(32)),
+                                "from": format!("0x{}", "bb".repeat(20)),
+                                "to": null,
+                                "value": "0x0",
+                                "input": calldata
+                            }
+                        ]
+                    }),
+                    _ => json!({"status": "0x0"}),
+                };
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"jsonrpc": "2.0", "id": body["id"], "result": result}))
+            })
+            .mount(&server)
+            .await;
+
+        let provider = ProviderBuilder::new().connect_http(server.uri().parse().unwrap());
+        let calls = spec.fetch(&provider, 100, 101).await.unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1].block_number, 101);
+        assert!(!calls[0].success);
+
+        let insert = spec.insert_sql(&calls[0]).unwrap();
+        assert_eq!(
+            insert.sql,
+            "INSERT INTO router_swap_calls (block_number, block_timestamp, transaction_hash, \
+             trace_address, from_address, value, success, amount_in, arg_value, arg2) \
+             VALUES ($1, $2, $3, $4, $5, $6::numeric, $7, $8::numeric, $9::numeric, $10) ON CONFLICT (transaction_hash, trace_address) DO NOTHING"
+        );
+        let values: Vec<JsonValue> = insert
+            .values
+            .iter()
+            .skip(3)
+            .map(|field| field.value.to_json())
+            .collect();
+        assert_eq!(
+            values,
+            [
+                json!(""),
+                json!("0xbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"),
+                json!("0"),
+                json!(false),
+                json!("1000"),
+                json!("7"),
+                json!(["0x0101010101010101010101010101010101010101"]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+/// Poll interval for chains missing from the registry, about one Ethereum block
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(12);
+
+/// Well-known EVM chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainInfo {
+    /// Canonical name, as used in the `[chains]` config section
+    pub name: &'static str,
+    /// Other names the chain is commonly configured under
+    pub aliases: &'static [&'static str],
+    pub chain_id: u64,
+    pub block_time: Duration,
+    pub explorer_url: &'static str,
+    /// Blocks after which a reorg is not expected in practice
+    pub finality_depth: u64,
+}
+
+/// Chains smorty knows about. Other chain names are accepted but not validated
+pub const CHAINS: &[ChainInfo] = &[
+    ChainInfo {
+        name: "mainnet",
+        aliases: &["ethereum", "eth"],
+        chain_id: 1,
+        block_time: Duration::from_secs(12),
+        explorer_url: "https://etherscan.io",
+        finality_depth: 64,
+    },
+    ChainInfo {
+        name: "sepolia",
+        aliases: &[],
+        chain_id: 11_155_111,
+        block_time: Duration::from_secs(12),
+        explorer_url: "https://sepolia.etherscan.io",
+        finality_depth: 64,
+    },
+    ChainInfo {
+        name: "optimism",
+        aliases: &["op"],
+        chain_id: 10,
+        block_time: Duration::from_secs(2),
+        explorer_url: "https://optimistic.etherscan.io",
+        finality_depth: 10,
+    },
+    ChainInfo {
+        name: "base",
+        aliases: &[],
+        chain_id: 8453,
+        block_time: Duration::from_secs(2),
+        explorer_url: "https://basescan.org",
+        finality_depth: 10,
+    },
+    ChainInfo {
+        name: "arbitrum",
+        aliases: &["arbitrum-one", "arb"],
+        chain_id: 42_161,
+        block_time: Duration::from_millis(250),
+        explorer_url: "https://arbiscan.io",
+        finality_depth: 20,
+    },
+    ChainInfo {
+        name: "polygon",
+        aliases: &["matic", "polygon-pos"],
+        chain_id: 137,
+        block_time: Duration::from_secs(2),
+        explorer_url: "https://polygonscan.com",
+        finality_depth: 128,
+    },
+    ChainInfo {
+        name: "bsc",
+        aliases: &["bnb", "binance"],
+        chain_id: 56,
+        block_time: Duration::from_secs(3),
+        explorer_url: "https://bscscan.com",
+        finality_depth: 15,
+    },
+    ChainInfo {
+        name: "avalanche",
+        aliases: &["avax"],
+        chain_id: 43_114,
+        block_time: Duration::from_secs(2),
+        explorer_url: "https://snowtrace.io",
+        finality_depth: 1,
+    },
+    ChainInfo {
+        name: "gnosis",
+        aliases: &["xdai"],
+        chain_id: 100,
+        block_time: Duration::from_secs(5),
+        explorer_url: "https://gnosisscan.io",
+        finality_depth: 20,
+    },
+    ChainInfo {
+        name: "sonic",
+        aliases: &[],
+        chain_id: 146,
+        block_time: Duration::from_secs(1),
+        explorer_url: "https://sonicscan.org",
+        finality_depth: 1,
+    },
+];
+
+/// Look up a chain by name or alias, ignoring case
+pub fn lookup(name: &str) -> Option<&'static ChainInfo> {
+    CHAINS.iter().find(|chain| {
+        chain.name.eq_ignore_ascii_case(name)
+            || chain
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    })
+}
+
+/// How often the indexer daemon polls a chain for new blocks
+///
+/// One block time, bounded to 1-12 seconds so fast chains don't flood the RPC.
+pub fn poll_interval(name: &str) -> Duration {
+    lookup(name)
+        .map(|chain| {
+            chain
+                .block_time
+                .clamp(Duration::from_secs(1), DEFAULT_POLL_INTERVAL)
+        })
+        .unwrap_or(DEFAULT_POLL_INTERVAL)
+}
+
+/// Name with the chain id, for progress bars and logs
+pub fn display_name(name: &str) -> String {
+    match lookup(name) {
+        Some(chain) => format!("{} ({})", name, chain.chain_id),
+        None => name.to_string(),
+    }
+}
+
+/// Chain details for AI prompts, for chains in the registry
+pub fn describe(name: &str) -> Option<String> {
+    lookup(name).map(|chain| {
+        format!(
+            "chain id {}, ~{}ms block time, finality after {} blocks, explorer {}",
+            chain.chain_id,
+            chain.block_time.as_millis(),
+            chain.finality_depth,
+            chain.explorer_url
+        )
+    })
+}
+
+impl ChainInfo {
+    /// Explorer page for a contract or account
+    pub fn address_url(&self, address: &str) -> String {
+        format!("{}/address/{}", self.explorer_url, address)
+    }
+
+    /// Check the chain id reported by an RPC endpoint
+    pub fn verify_chain_id(&self, rpc_chain_id: u64) -> anyhow::Result<()> {
+        if rpc_chain_id != self.chain_id {
+            anyhow::bail!(
+                "RPC for chain '{}' reports chain id {}, expected {}. Check the [chains] section of your config",
+                self.name,
+                rpc_chain_id,
+                self.chain_id
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_by_alias() {
+        assert_eq!(lookup("Ethereum").unwrap().chain_id, 1);
+        assert_eq!(lookup("matic").unwrap().name, "polygon");
+        assert!(lookup("my-devnet").is_none());
+
+        assert_eq!(display_name("base"), "base (8453)");
+        assert_eq!(display_name("my-devnet"), "my-devnet");
+    }
+
+    #[test]
+    fn test_poll_interval_is_bounded() {
+        assert_eq!(poll_interval("mainnet"), Duration::from_secs(12));
+        assert_eq!(poll_interval("base"), Duration::from_secs(2));
+        assert_eq!(poll_interval("arbitrum"), Duration::from_secs(1));
+        assert_eq!(poll_interval("my-devnet"), DEFAULT_POLL_INTERVAL);
+    }
+
+    #[test]
+    fn test_verify_chain_id() {
+        let base = lookup("base").unwrap();
+        assert!(base.verify_chain_id(8453).is_ok());
+        assert!(base.verify_chain_id(1).is_err());
+    }
+}
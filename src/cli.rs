@@ -1,39 +1,250 @@
 use crate::constants;
-use clap::{Parser, Subcommand};
+use crate::daemon;
+use crate::secrets;
+use anyhow::{Context, Result};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use std::io::Write;
+use std::path::Path;
 
 #[derive(Parser, Debug)]
 #[command(name = "smorty")]
 #[command(about = constants::SMORTY_ASCII, long_about = None)]
 #[command(after_help = constants::SMORTY_DESCRIPTION)]
 pub struct Cli {
-    /// Path to config file
+    /// Path to config file, relative to the project directory
     #[arg(short, long, default_value = "config.toml")]
     pub config: String,
 
+    /// Project directory holding the config, ir/, migrations/ and prompts/
+    #[arg(long, global = true)]
+    pub project: Option<String>,
+
+    /// Write a JSON report of the error to this file if the command fails
+    ///
+    /// Relative to the current directory, not the project directory.
+    #[arg(long, global = true)]
+    pub error_report: Option<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
+    /// Create the config and directories of a new project in the current directory
+    Init {
+        /// Also write a docker-compose.yml (Postgres and `smorty run`) and a Dockerfile
+        #[arg(long)]
+        docker: bool,
+
+        /// Overwrite files that already exist
+        #[arg(short, long)]
+        force: bool,
+    },
+
     /// Generate spec IR (Intermediate Representation) from config using AI
-    GenSpec,
+    GenSpec {
+        /// Overwrite changed IR files without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Stream AI responses and print tokens as they arrive
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// Only regenerate specs whose contract ABI changed since generation, or that
+        /// have no IR yet, and generate a migration for any resulting schema change
+        #[arg(long)]
+        changed_only: bool,
+
+        /// Add the contracts of a CSV file (address, chain, name, tasks) to the config
+        /// and generate their specs
+        #[arg(long, value_name = "FILE", conflicts_with = "changed_only")]
+        manifest: Option<String>,
+
+        /// Decode a few recent logs of each spec with its new IR and print the rows
+        #[arg(long, conflicts_with = "manifest")]
+        preview: bool,
+    },
 
     /// Generate endpoint IR from config using AI
-    GenEndpoint,
+    GenEndpoint {
+        /// Overwrite changed IR files without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Stream AI responses and print tokens as they arrive
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
+    /// Write the OpenAPI document of the generated endpoints without starting the server
+    GenOpenapi {
+        /// File to write, or - for stdout
+        #[arg(short, long, default_value = "openapi.json")]
+        out: String,
+    },
+
+    /// Validate generated endpoint queries against their golden test fixtures
+    TestEndpoints {
+        /// Record the current query plans as the new golden plans
+        #[arg(short, long)]
+        update: bool,
+    },
+
+    /// Check spec IRs against the config and contract ABIs, e.g. after a contract upgrade
+    Validate,
+
+    /// List, show and diff generated IR files
+    Ir {
+        #[command(subcommand)]
+        command: IrCommand,
+    },
+
+    /// Manage the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Import specs and endpoints from another indexer
+    Import {
+        #[command(subcommand)]
+        command: ImportCommand,
+    },
 
     /// Generate database migration from IR
-    GenMigration,
+    GenMigration {
+        /// Description of the schema change for the changelog, asked for when omitted
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+
+    /// Inspect the history of schema changes
+    Schema {
+        #[command(subcommand)]
+        command: SchemaCommand,
+    },
 
     /// Run database migration
-    Migrate,
+    Migrate {
+        /// Apply all migrations to a temporary schema and compare it with schema.json,
+        /// without touching the database's own schema
+        #[arg(long)]
+        check: bool,
+
+        /// Database to create the temporary schema in, instead of the configured one
+        #[arg(long, requires = "check")]
+        shadow_database: Option<String>,
+    },
+
+    /// Remove old schema backups and IR files no longer in the config
+    Prune {
+        /// Number of newest schema backups to keep
+        #[arg(long, default_value = "5")]
+        keep_last: usize,
+
+        /// Also keep schema backups newer than this many days
+        #[arg(long)]
+        keep_days: Option<u64>,
+
+        /// Remove the files without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Benchmark smorty to help tune performance settings
+    Bench {
+        #[command(subcommand)]
+        target: BenchTarget,
+    },
 
     /// Run the indexer (fetch and process events)
     #[command(hide = true)]
     Index {
-        /// Run in daemon mode (continuously index new blocks)
+        /// Run in daemon mode (continuously index new blocks) in the background
         #[arg(short, long)]
         daemon: bool,
+
+        /// Keep the daemon in the foreground, e.g. under systemd or Docker
+        #[arg(long, requires = "daemon")]
+        foreground: bool,
+
+        /// Disable progress bars (e.g. in CI)
+        #[arg(short, long)]
+        quiet: bool,
+
+        /// PID file of the daemon
+        #[arg(long, default_value = daemon::DEFAULT_PID_FILE)]
+        pid_file: String,
+
+        /// Log file of the daemon when it runs in the background
+        #[arg(long, default_value = daemon::DEFAULT_LOG_FILE)]
+        log_file: String,
+
+        /// Print the decoded rows of a block range as JSON lines instead of inserting them
+        #[arg(long, conflicts_with = "daemon")]
+        dry_run: bool,
+
+        /// Number of blocks to decode in a dry run
+        #[arg(short, long, default_value = "100", requires = "dry_run")]
+        blocks: u64,
+
+        /// First block of the dry run, defaults to the most recent blocks
+        #[arg(long, requires = "dry_run")]
+        from_block: Option<u64>,
+
+        /// Only decode this chain in a dry run
+        #[arg(long, requires = "dry_run")]
+        chain: Option<String>,
+
+        /// Write the dry run's rows to this file instead of stdout
+        #[arg(short, long, requires = "dry_run")]
+        output: Option<String>,
+    },
+
+    /// Stop the indexer daemon
+    Stop {
+        /// PID file of the daemon
+        #[arg(long, default_value = daemon::DEFAULT_PID_FILE)]
+        pid_file: String,
+    },
+
+    /// Restart the indexer daemon in the background
+    Restart {
+        /// PID file of the daemon
+        #[arg(long, default_value = daemon::DEFAULT_PID_FILE)]
+        pid_file: String,
+
+        /// Log file of the daemon
+        #[arg(long, default_value = daemon::DEFAULT_LOG_FILE)]
+        log_file: String,
+    },
+
+    /// Pause indexing of a chain or spec, in running daemons too, until it is resumed
+    Pause {
+        /// Chain name, or spec as <contract>/<spec>
+        target: String,
+
+        /// Why it is paused, shown by `status`
+        #[arg(long)]
+        reason: Option<String>,
+    },
+
+    /// Resume indexing of a paused chain or spec
+    Resume {
+        /// Chain name, or spec as <contract>/<spec>
+        target: String,
+    },
+
+    /// Show how far each spec is indexed and any block ranges it missed
+    Status,
+
+    /// Copy indexed tables to external systems
+    Sync {
+        #[command(subcommand)]
+        command: SyncCommand,
     },
 
     /// Start the API server
@@ -46,6 +257,28 @@ pub enum Commands {
         /// Port to bind to
         #[arg(short, long, default_value = "3000")]
         port: u16,
+
+        /// Serve the endpoints as MCP tools over stdin and stdout instead of HTTP
+        #[arg(long)]
+        mcp: bool,
+
+        /// Also serve list and transaction lookup endpoints for every indexed table,
+        /// without needing endpoint IRs
+        #[arg(long)]
+        readonly: bool,
+    },
+
+    /// Print a shell completion script
+    Completions {
+        /// Shell to generate completions for
+        shell: Shell,
+    },
+
+    /// Print the man page, or write pages for every subcommand to a directory
+    Man {
+        /// Directory to write smorty.1 and a page per subcommand to
+        #[arg(short, long)]
+        out_dir: Option<String>,
     },
 
     /// Run both indexer and API server
@@ -59,3 +292,152 @@ pub enum Commands {
         port: u16,
     },
 }
+
+#[derive(Subcommand, Debug)]
+pub enum BenchTarget {
+    /// Measure indexing throughput on a block range, without committing any rows
+    Index {
+        /// Number of most recent blocks to fetch from the RPC
+        #[arg(short, long, default_value = "1000")]
+        blocks: u64,
+
+        /// Only benchmark this chain
+        #[arg(long)]
+        chain: Option<String>,
+
+        /// Replay logs from a fixture file instead of fetching them
+        #[arg(short, long, conflicts_with = "save_fixture")]
+        fixture: Option<String>,
+
+        /// Save the fetched logs to a fixture file for later runs
+        #[arg(long)]
+        save_fixture: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum IrCommand {
+    /// List every spec and endpoint IR with its key metadata
+    List,
+
+    /// Pretty-print an IR, given as <contract>/<spec>, an endpoint path or a file
+    Show {
+        /// IR to show
+        name: String,
+    },
+
+    /// Diff two IRs, or a spec IR against its table in the database
+    Diff {
+        /// IR to compare from, as <contract>/<spec>, an endpoint path or a file
+        old: String,
+
+        /// IR to compare to
+        #[arg(required_unless_present = "schema")]
+        new: Option<String>,
+
+        /// Compare the spec's columns with its table in the database instead
+        #[arg(long, conflicts_with = "new")]
+        schema: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ImportCommand {
+    /// Convert a subgraph's data sources and entities into spec IRs, ABIs and a config stub
+    Subgraph {
+        /// Directory with subgraph.yaml and schema.graphql
+        dir: String,
+
+        /// Config stub to write, for merging into the config
+        #[arg(long, default_value = "subgraph.toml")]
+        out: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Create a key for encrypted config values
+    Keygen {
+        /// Key file to create
+        #[arg(short, long, default_value = secrets::DEFAULT_KEY_FILE)]
+        out: String,
+    },
+
+    /// Encrypt a value for the config, e.g. an API key or database URI
+    Encrypt {
+        /// Value to encrypt, read from stdin when omitted
+        value: Option<String>,
+    },
+
+    /// Check the config for likely mistakes, such as unused chains or tasks naming missing tables
+    Lint,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SchemaCommand {
+    /// List generated migrations with their changes and descriptions, newest first
+    History {
+        /// Only migrations changing this table
+        #[arg(short, long)]
+        table: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SyncCommand {
+    /// Load the rows added since the last sync into the `[warehouse]` BigQuery or Snowflake tables
+    Warehouse,
+}
+
+/// Write a completion script for `shell`
+pub fn write_completions(shell: Shell, out: &mut impl Write) {
+    clap_complete::generate(shell, &mut Cli::command(), "smorty", out);
+}
+
+/// Command definition for man pages, which can't show the ASCII art
+fn man_command() -> clap::Command {
+    Cli::command()
+        .about(constants::SMORTY_DESCRIPTION)
+        .after_help(None::<&str>)
+}
+
+/// Write the top-level man page
+pub fn write_man_page(out: &mut impl Write) -> Result<()> {
+    clap_mangen::Man::new(man_command())
+        .render(out)
+        .context("Failed to render man page")
+}
+
+/// Write man pages for smorty and all of its subcommands to `dir`
+pub fn write_man_pages(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+    clap_mangen::generate_to(man_command(), dir)
+        .context(format!("Failed to write man pages to {}", dir.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_completions_and_man_pages_cover_subcommands() {
+        let mut completions = Vec::new();
+        write_completions(Shell::Bash, &mut completions);
+        let completions = String::from_utf8(completions).unwrap();
+        assert!(completions.contains("gen-spec"));
+        assert!(completions.contains("completions"));
+
+        let temp_dir = TempDir::new().unwrap();
+        write_man_pages(temp_dir.path()).unwrap();
+        assert!(temp_dir.path().join("smorty.1").exists());
+        assert!(temp_dir.path().join("smorty-gen-migration.1").exists());
+    }
+
+    #[test]
+    fn test_project_is_global() {
+        let cli = Cli::try_parse_from(["smorty", "migrate", "--project", "indexers/dex"]).unwrap();
+        assert_eq!(cli.project.as_deref(), Some("indexers/dex"));
+        assert_eq!(cli.config, "config.toml");
+    }
+}
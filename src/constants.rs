@@ -1,4 +1,4 @@
-/// Shared constants for the smorty indexer
+//! Shared constants for the smorty indexer
 
 /// ASCII art logo
 pub const SMORTY_ASCII: &str = r#"⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⢀⣀⡀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀⠀
@@ -0,0 +1,253 @@
+//! Block ranges each spec has been indexed over
+//!
+//! `MAX(block_number)` of a table only says how far its newest event is, not
+//! whether every chunk before it was processed: a crash mid-chunk, or another
+//! spec writing to the same table, can leave holes behind it. The indexer instead
+//! records the ranges it finished for each spec, resumes after the last one and
+//! fetches the holes again.
+
+use crate::ai::IrGenerationResult;
+use crate::ir::Ir;
+use anyhow::{Context, Result};
+use sqlx::PgPool;
+
+/// Block ranges indexed per spec, merged so adjacent ranges become one row
+pub const COVERAGE_TABLE: &str = "smorty_coverage";
+
+/// Sorted, non-overlapping inclusive block ranges
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockRanges {
+    ranges: Vec<(u64, u64)>,
+}
+
+impl BlockRanges {
+    /// Add a range, merging it with the ranges it overlaps or touches
+    pub fn insert(&mut self, from: u64, to: u64) {
+        let (mut from, mut to) = (from.min(to), from.max(to));
+        let mut merged = Vec::with_capacity(self.ranges.len() + 1);
+        for &(start, end) in &self.ranges {
+            if end.saturating_add(1) < from || to.saturating_add(1) < start {
+                merged.push((start, end));
+            } else {
+                from = from.min(start);
+                to = to.max(end);
+            }
+        }
+        merged.push((from, to));
+        merged.sort_unstable();
+        self.ranges = merged;
+    }
+
+    /// Last block covered, if any
+    pub fn end(&self) -> Option<u64> {
+        self.ranges.last().map(|&(_, end)| end)
+    }
+
+    pub fn ranges(&self) -> &[(u64, u64)] {
+        &self.ranges
+    }
+
+    /// Ranges between `start` and `end` that aren't covered
+    pub fn gaps(&self, start: u64, end: u64) -> Vec<(u64, u64)> {
+        let mut gaps = Vec::new();
+        let mut next = start;
+        for &(from, to) in &self.ranges {
+            if next > end {
+                break;
+            }
+            if from > next {
+                gaps.push((next, (from - 1).min(end)));
+            }
+            next = next.max(to.saturating_add(1));
+        }
+        if next <= end {
+            gaps.push((next, end));
+        }
+        gaps
+    }
+
+    /// First block from `start` the indexer still has to fetch, gaps included
+    pub fn next_block(&self, start: u64) -> u64 {
+        match self.end() {
+            Some(end) => self
+                .gaps(start, end)
+                .first()
+                .map_or_else(|| (end + 1).max(start), |&(from, _)| from),
+            None => start,
+        }
+    }
+}
+
+/// Ranges indexed for a spec, identified by `<contract>/<spec>`
+pub async fn load(pool: &PgPool, spec: &str) -> Result<BlockRanges> {
+    let rows: Vec<(i64, i64)> = sqlx::query_as(&format!(
+        "SELECT from_block, to_block FROM {} WHERE spec = $1 ORDER BY from_block",
+        COVERAGE_TABLE
+    ))
+    .bind(spec)
+    .fetch_all(pool)
+    .await
+    .context(format!("Failed to load indexed ranges of {}", spec))?;
+
+    let mut ranges = BlockRanges::default();
+    for (from, to) in rows {
+        ranges.insert(from as u64, to as u64);
+    }
+    Ok(ranges)
+}
+
+/// Record that a spec was indexed from `from` to `to`, merging it with its neighbours
+pub async fn record(pool: &PgPool, spec: &str, from: u64, to: u64) -> Result<()> {
+    checkpoint(pool, &[(spec, from, to)]).await
+}
+
+/// Record the ranges a chunk covered for every spec of a chain group at once
+///
+/// Either all specs move past the chunk or none do, so a crash between specs
+/// can't leave the group resuming from different blocks.
+pub async fn checkpoint(pool: &PgPool, ranges: &[(&str, u64, u64)]) -> Result<()> {
+    if ranges.is_empty() {
+        return Ok(());
+    }
+    let mut tx = pool
+        .begin()
+        .await
+        .context("Failed to start chunk checkpoint")?;
+    for &(spec, from, to) in ranges {
+        record_in(&mut tx, spec, from, to).await?;
+    }
+    tx.commit()
+        .await
+        .context("Failed to commit chunk checkpoint")?;
+    Ok(())
+}
+
+async fn record_in(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    spec: &str,
+    from: u64,
+    to: u64,
+) -> Result<()> {
+    let context = || format!("Failed to record indexed range of {}", spec);
+
+    let neighbours: Vec<(i64, i64)> = sqlx::query_as(&format!(
+        "DELETE FROM {} WHERE spec = $1 AND to_block >= $2 - 1 AND from_block <= $3 + 1
+         RETURNING from_block, to_block",
+        COVERAGE_TABLE
+    ))
+    .bind(spec)
+    .bind(from as i64)
+    .bind(to as i64)
+    .fetch_all(&mut **tx)
+    .await
+    .with_context(context)?;
+
+    let mut ranges = BlockRanges::default();
+    ranges.insert(from, to);
+    for (start, end) in neighbours {
+        ranges.insert(start as u64, end as u64);
+    }
+    for &(start, end) in ranges.ranges() {
+        sqlx::query(&format!(
+            "INSERT INTO {} (spec, from_block, to_block) VALUES ($1, $2, $3)",
+            COVERAGE_TABLE
+        ))
+        .bind(spec)
+        .bind(start as i64)
+        .bind(end as i64)
+        .execute(&mut **tx)
+        .await
+        .with_context(context)?;
+    }
+    Ok(())
+}
+
+/// How far a spec is indexed and the holes left behind, shown by `smorty status`
+#[derive(Debug, Clone)]
+pub struct SpecCoverage {
+    pub contract_name: String,
+    pub spec_name: String,
+    pub chain: String,
+    pub start_block: u64,
+    pub indexed_to: Option<u64>,
+    pub gaps: Vec<(u64, u64)>,
+}
+
+impl SpecCoverage {
+    /// Coverage of every spec, in the order of the IR specs
+    pub async fn collect(
+        pool: &PgPool,
+        ir_specs: &[(String, String, IrGenerationResult)],
+    ) -> Result<Vec<Self>> {
+        let mut report = Vec::with_capacity(ir_specs.len());
+        for (contract_name, spec_name, ir) in ir_specs {
+            let ranges = load(pool, &Ir::source_name(contract_name, spec_name)).await?;
+            let indexed_to = ranges.end();
+            let gaps = indexed_to
+                .map(|end| ranges.gaps(ir.start_block, end))
+                .unwrap_or_default();
+            report.push(Self {
+                contract_name: contract_name.clone(),
+                spec_name: spec_name.clone(),
+                chain: ir.chain.clone(),
+                start_block: ir.start_block,
+                indexed_to,
+                gaps,
+            });
+        }
+        Ok(report)
+    }
+
+    /// Whether the spec is indexed without gaps up to `end_block`
+    pub fn is_complete(&self, end_block: u64) -> bool {
+        self.indexed_to.is_some_and(|end| end >= end_block) && self.gaps.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_merges_adjacent_ranges() {
+        let mut ranges = BlockRanges::default();
+        ranges.insert(10, 19);
+        ranges.insert(30, 39);
+        ranges.insert(20, 24);
+        assert_eq!(ranges.ranges(), &[(10, 24), (30, 39)]);
+
+        ranges.insert(22, 35);
+        assert_eq!(ranges.ranges(), &[(10, 39)]);
+        assert_eq!(ranges.end(), Some(39));
+
+        ranges.insert(0, 3);
+        assert_eq!(ranges.ranges(), &[(0, 3), (10, 39)]);
+    }
+
+    #[test]
+    fn test_gaps() {
+        let mut ranges = BlockRanges::default();
+        ranges.insert(10, 19);
+        ranges.insert(30, 39);
+
+        assert_eq!(ranges.gaps(10, 39), vec![(20, 29)]);
+        assert_eq!(ranges.gaps(0, 45), vec![(0, 9), (20, 29), (40, 45)]);
+        assert_eq!(ranges.gaps(12, 18), vec![]);
+        assert_eq!(ranges.gaps(25, 32), vec![(25, 29)]);
+        assert_eq!(BlockRanges::default().gaps(5, 7), vec![(5, 7)]);
+    }
+
+    #[test]
+    fn test_next_block() {
+        let mut ranges = BlockRanges::default();
+        assert_eq!(ranges.next_block(10), 10);
+
+        ranges.insert(10, 19);
+        assert_eq!(ranges.next_block(10), 20);
+        assert_eq!(ranges.next_block(25), 25);
+
+        ranges.insert(30, 39);
+        assert_eq!(ranges.next_block(10), 20);
+        assert_eq!(ranges.next_block(30), 40);
+    }
+}
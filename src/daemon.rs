@@ -0,0 +1,212 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// PID file of the indexer daemon, relative to the working directory
+pub const DEFAULT_PID_FILE: &str = "smorty.pid";
+
+/// Where a detached daemon writes its logs
+pub const DEFAULT_LOG_FILE: &str = "smorty.log";
+
+/// How long `smorty stop` waits for the daemon to exit
+const STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// PID file held by a running daemon, removed when dropped
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write the current process id, failing if another daemon is still running
+    ///
+    /// A PID file left behind by a daemon that crashed is overwritten.
+    pub fn create(path: &Path) -> Result<Self> {
+        if let Some(pid) = read_pid(path)?
+            && is_running(pid)
+        {
+            anyhow::bail!(
+                "Indexer daemon is already running (pid {}). Stop it with 'smorty stop'",
+                pid
+            );
+        }
+
+        fs::write(path, format!("{}\n", std::process::id()))
+            .context(format!("Failed to write PID file {}", path.display()))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Leave the file alone if a newer daemon has taken it over
+        if read_pid(&self.path).ok().flatten() == Some(std::process::id()) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Process id in a PID file, or `None` if there is no PID file
+pub fn read_pid(path: &Path) -> Result<Option<u32>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).context(format!("Failed to read PID file {}", path.display()));
+        }
+    };
+
+    let pid = content
+        .trim()
+        .parse()
+        .context(format!("Invalid PID file {}", path.display()))?;
+    Ok(Some(pid))
+}
+
+/// Whether a process with this id exists
+pub fn is_running(pid: u32) -> bool {
+    kill(pid, "0").unwrap_or(false)
+}
+
+/// Ask the daemon in the PID file to shut down and wait for it to exit
+///
+/// Returns `false` if no daemon was running.
+pub fn stop(pid_file: &Path) -> Result<bool> {
+    let Some(pid) = read_pid(pid_file)? else {
+        return Ok(false);
+    };
+    if !is_running(pid) {
+        tracing::info!("Removing stale PID file {}", pid_file.display());
+        let _ = fs::remove_file(pid_file);
+        return Ok(false);
+    }
+
+    tracing::info!("Stopping indexer daemon (pid {})", pid);
+    if !kill(pid, "TERM")? {
+        anyhow::bail!("Failed to send SIGTERM to pid {}", pid);
+    }
+
+    let started = Instant::now();
+    while is_running(pid) {
+        if started.elapsed() > STOP_TIMEOUT {
+            anyhow::bail!(
+                "Indexer daemon (pid {}) did not exit within {}s",
+                pid,
+                STOP_TIMEOUT.as_secs()
+            );
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+
+    // The daemon removes its PID file on shutdown, unless it was killed
+    let _ = fs::remove_file(pid_file);
+    Ok(true)
+}
+
+/// Start this executable again in the background with `args`, returning its process id
+///
+/// The child gets its own process group, so it survives the terminal closing,
+/// and its output goes to `log_file`.
+pub fn spawn_detached(args: &[String], log_file: &Path) -> Result<u32> {
+    let exe = std::env::current_exe().context("Failed to locate the smorty executable")?;
+    let log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .context(format!("Failed to open log file {}", log_file.display()))?;
+
+    let mut command = Command::new(exe);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone().context("Failed to open log file")?)
+        .stderr(log);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let child = command.spawn().context("Failed to start indexer daemon")?;
+    Ok(child.id())
+}
+
+/// Wait for SIGTERM or Ctrl-C
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => {}
+                    _ = tokio::signal::ctrl_c() => {}
+                }
+                return;
+            }
+            Err(e) => tracing::warn!("Failed to install SIGTERM handler: {}", e),
+        }
+    }
+
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Send a signal with `kill`, returning whether it was delivered
+fn kill(pid: u32, signal: &str) -> Result<bool> {
+    let status = Command::new("kill")
+        .arg(format!("-{}", signal))
+        .arg(pid.to_string())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .context("Failed to run kill")?;
+    Ok(status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_pid_file_lifecycle() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("smorty.pid");
+        assert_eq!(read_pid(&path).unwrap(), None);
+
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(&path).unwrap(), Some(std::process::id()));
+        assert!(is_running(std::process::id()));
+
+        // This process is still running, so a second daemon is refused
+        assert!(PidFile::create(&path).is_err());
+
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_stale_pid_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("smorty.pid");
+
+        // Past the default pid_max, so no process can have it
+        fs::write(&path, "4194305\n").unwrap();
+        assert!(!stop(&path).unwrap());
+        assert!(!path.exists());
+
+        fs::write(&path, "4194305\n").unwrap();
+        let _pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(&path).unwrap(), Some(std::process::id()));
+
+        fs::write(&path, "not a pid").unwrap();
+        assert!(read_pid(&path).is_err());
+    }
+}
@@ -0,0 +1,661 @@
+//! Per-chain databases, configured with `[database.perChain]`
+//!
+//! The indexer writes each chain's tables to the chain's database, or to
+//! `database.uri` for chains without one, and `migrate` applies every migration
+//! to every database so they all have the same tables. The server sends each
+//! endpoint's query to the databases its tables are written to. When that's more
+//! than one, the query runs on each of them and the rows are merged, which only
+//! gives the right answer for queries of a single table that don't aggregate
+//! rows, so other queries over several databases are refused.
+
+use crate::ai::{EndpointIrResult, IrGenerationResult};
+use crate::config::{ContractConfig, DatabaseConfig, WatchlistConfig};
+use crate::server::SqlParam;
+use crate::sql_guard::{self, Token};
+use crate::transform;
+use anyhow::Result;
+use serde_json::Value as JsonValue;
+use sqlx::PgPool;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+/// Pools of the API server, and which of them hold each table
+#[derive(Debug, Clone)]
+pub struct DatabaseRouter {
+    config: Arc<DatabaseConfig>,
+    /// Pools of every database, by `uri`
+    pools: Arc<HashMap<String, PgPool>>,
+    /// Databases each spec and state table is written to
+    tables: Arc<HashMap<String, BTreeSet<String>>>,
+    /// Database of each contract's chain
+    contracts: Arc<HashMap<String, String>>,
+}
+
+impl DatabaseRouter {
+    /// Route every table to `database.uri` until [`DatabaseRouter::with_tables`] is called
+    ///
+    /// `pools` must have a pool for `database.uri`.
+    pub fn new(config: &DatabaseConfig, pools: HashMap<String, PgPool>) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+            pools: Arc::new(pools),
+            tables: Arc::default(),
+            contracts: Arc::default(),
+        }
+    }
+
+    /// Route the tables of each spec to the database of its chain
+    pub fn with_tables(mut self, ir_specs: &[(String, String, IrGenerationResult)]) -> Self {
+        self.tables = Arc::new(table_databases(&self.config, ir_specs));
+        self.contracts = Arc::new(
+            ir_specs
+                .iter()
+                .map(|(contract_name, _, ir)| {
+                    (
+                        contract_name.clone(),
+                        self.config.uri_for_chain(&ir.chain).to_string(),
+                    )
+                })
+                .collect(),
+        );
+        self
+    }
+
+    /// Route each watchlist's table to the database of its chain
+    pub fn with_watchlists(mut self, watchlists: &[WatchlistConfig]) -> Self {
+        let tables = Arc::make_mut(&mut self.tables);
+        for watchlist in watchlists {
+            tables
+                .entry(watchlist.table.clone())
+                .or_default()
+                .insert(self.config.uri_for_chain(&watchlist.chain).to_string());
+        }
+        self
+    }
+
+    /// Route the call tables of each contract to the database of its chain
+    pub fn with_calls(mut self, contracts: &HashMap<String, ContractConfig>) -> Self {
+        let tables = Arc::make_mut(&mut self.tables);
+        for (contract_name, contract) in contracts {
+            for call in &contract.calls {
+                tables
+                    .entry(call.table_name(contract_name))
+                    .or_default()
+                    .insert(self.config.uri_for_chain(&contract.chain).to_string());
+            }
+        }
+        self
+    }
+
+    /// Pool of `database.uri`, used for everything that isn't a chain's table
+    pub fn default_pool(&self) -> &PgPool {
+        &self.pools[&self.config.uri]
+    }
+
+    /// Pool of the database a contract's events are written to
+    pub fn contract_pool(&self, contract_name: &str) -> PgPool {
+        self.contracts
+            .get(contract_name)
+            .and_then(|uri| self.pools.get(uri))
+            .unwrap_or_else(|| self.default_pool())
+            .clone()
+    }
+
+    /// Pools of the databases `table` is written to
+    pub fn table_pools(&self, table: &str) -> Vec<PgPool> {
+        self.pools_for(&self.table_uris(table))
+    }
+
+    /// Pools of the databases holding any table the endpoint reads
+    pub fn endpoint_pools(&self, endpoint_ir: &EndpointIrResult) -> Vec<PgPool> {
+        let mut uris: BTreeSet<String> = endpoint_ir
+            .tables_referenced
+            .iter()
+            .flat_map(|table| self.table_uris(table))
+            .collect();
+        if uris.is_empty() {
+            uris.insert(self.config.uri.clone());
+        }
+        self.pools_for(&uris)
+    }
+
+    /// How the rows of the endpoint's databases are merged, `None` when it reads one
+    ///
+    /// Fails when merging the rows of each database wouldn't give the query's result.
+    pub fn endpoint_merge(&self, endpoint_ir: &EndpointIrResult) -> Result<Option<RowMerge>> {
+        if self.endpoint_pools(endpoint_ir).len() < 2 {
+            return Ok(None);
+        }
+        RowMerge::plan(endpoint_ir).map(Some)
+    }
+
+    fn table_uris(&self, table: &str) -> BTreeSet<String> {
+        self.tables
+            .get(table)
+            .cloned()
+            .unwrap_or_else(|| BTreeSet::from([self.config.uri.clone()]))
+    }
+
+    fn pools_for(&self, uris: &BTreeSet<String>) -> Vec<PgPool> {
+        uris.iter()
+            .map(|uri| {
+                self.pools
+                    .get(uri)
+                    .unwrap_or_else(|| self.default_pool())
+                    .clone()
+            })
+            .collect()
+    }
+}
+
+/// Databases each spec table and state table is written to, by table name
+///
+/// A table shared by specs of different chains can be in several databases.
+pub fn table_databases(
+    config: &DatabaseConfig,
+    ir_specs: &[(String, String, IrGenerationResult)],
+) -> HashMap<String, BTreeSet<String>> {
+    let mut tables: HashMap<String, BTreeSet<String>> = HashMap::new();
+    for (_, _, ir) in ir_specs {
+        let uri = config.uri_for_chain(&ir.chain).to_string();
+        let state_table = ir.state_fold.as_ref().map(|fold| &fold.table_name);
+        for table in std::iter::once(&ir.table_schema.table_name).chain(state_table) {
+            tables.entry(table.clone()).or_default().insert(uri.clone());
+        }
+    }
+    tables
+}
+
+/// Aggregate functions, whose result over each database isn't the result over all of them
+const AGGREGATES: &[&str] = &[
+    "count",
+    "sum",
+    "avg",
+    "min",
+    "max",
+    "array_agg",
+    "string_agg",
+    "json_agg",
+    "jsonb_agg",
+    "json_object_agg",
+    "jsonb_object_agg",
+    "bool_and",
+    "bool_or",
+    "every",
+    "bit_and",
+    "bit_or",
+    "stddev",
+    "stddev_pop",
+    "stddev_samp",
+    "variance",
+    "var_pop",
+    "var_samp",
+    "percentile_cont",
+    "percentile_disc",
+    "mode",
+];
+
+/// Keywords of clauses that group, dedupe, combine or number rows across the result
+const WHOLE_RESULT_KEYWORDS: &[&str] = &[
+    "group",
+    "having",
+    "distinct",
+    "join",
+    "union",
+    "intersect",
+    "except",
+    "over",
+    "window",
+    "offset",
+    "fetch",
+];
+
+/// Rows the query returns, as in its top-level `LIMIT`
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeLimit {
+    Rows(usize),
+    /// Number of the parameter holding the limit, `$1` is 1
+    Param(usize),
+}
+
+/// Order of a response field in the query's top-level `ORDER BY`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeOrder {
+    pub field: String,
+    pub descending: bool,
+    pub nulls_first: bool,
+}
+
+/// How the rows a query returns from each database are merged into its result
+///
+/// Each database returns its own first rows, so merging sorts them again and keeps
+/// as many as the query's limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowMerge {
+    pub order_by: Vec<MergeOrder>,
+    pub limit: Option<MergeLimit>,
+}
+
+impl RowMerge {
+    /// Plan the merge of an endpoint's rows from several databases
+    ///
+    /// Only queries of one table without stages, aggregates or clauses over the
+    /// whole result can be merged, and only by `ORDER BY` columns that are
+    /// untransformed response fields.
+    pub fn plan(endpoint_ir: &EndpointIrResult) -> Result<Self> {
+        let refuse = |reason: &str| {
+            anyhow::anyhow!(
+                "it reads tables from several databases, and {} can't be merged",
+                reason
+            )
+        };
+        if endpoint_ir.tables_referenced.len() != 1 {
+            return Err(refuse("a query of several tables"));
+        }
+        if !endpoint_ir.stages.is_empty() {
+            return Err(refuse("a query with stages"));
+        }
+
+        let tokens = sql_guard::tokenize(&endpoint_ir.sql_query)?;
+        for (index, token) in tokens.iter().enumerate() {
+            if let Some(keyword) = WHOLE_RESULT_KEYWORDS
+                .iter()
+                .find(|keyword| token.is_keyword(keyword))
+            {
+                return Err(refuse(&format!("a query with {}", keyword.to_uppercase())));
+            }
+            if let Some(name) = token.ident()
+                && AGGREGATES.contains(&name)
+                && tokens.get(index + 1) == Some(&Token::Symbol('('))
+            {
+                return Err(refuse(&format!("a query using {}()", name.to_uppercase())));
+            }
+        }
+
+        // Clauses of the outermost statement, split at its ORDER BY and LIMIT
+        let mut depth = 0i32;
+        let mut order_by: Option<Vec<&Token>> = None;
+        let mut limit: Option<Vec<&Token>> = None;
+        let mut tokens = tokens.iter().peekable();
+        while let Some(token) = tokens.next() {
+            match token {
+                Token::Symbol('(') => depth += 1,
+                Token::Symbol(')') => depth -= 1,
+                _ if depth > 0 => {}
+                _ if token.is_keyword("order")
+                    && tokens.peek().is_some_and(|next| next.is_keyword("by")) =>
+                {
+                    tokens.next();
+                    order_by = Some(Vec::new());
+                    continue;
+                }
+                _ if token.is_keyword("limit") => {
+                    limit = Some(Vec::new());
+                    continue;
+                }
+                _ => {}
+            }
+            if let Some(clause) = limit.as_mut().or(order_by.as_mut()) {
+                clause.push(token);
+            }
+        }
+
+        let limit = match limit.as_deref() {
+            None => None,
+            Some([all]) if all.is_keyword("all") => None,
+            Some([Token::Number(rows)]) => Some(MergeLimit::Rows(rows.parse()?)),
+            Some([Token::Param(index)]) => Some(MergeLimit::Param(*index)),
+            Some(_) => return Err(refuse("a LIMIT that isn't a number or parameter")),
+        };
+
+        let order_by = order_by
+            .unwrap_or_default()
+            .split(|token| **token == Token::Symbol(','))
+            .filter(|item| !item.is_empty())
+            .map(|item| Self::order(endpoint_ir, item).ok_or_else(|| refuse("its ORDER BY")))
+            .collect::<Result<_>>()?;
+
+        Ok(Self { order_by, limit })
+    }
+
+    /// Order of one `ORDER BY` item, if it's an untransformed response field
+    fn order(endpoint_ir: &EndpointIrResult, item: &[&Token]) -> Option<MergeOrder> {
+        // Only the column of a qualified name is a response field
+        let (column, modifiers) = match item {
+            [_, Token::Symbol('.'), column, modifiers @ ..] => (column, modifiers),
+            [column, modifiers @ ..] => (column, modifiers),
+            [] => return None,
+        };
+        let field = endpoint_ir
+            .response_schema
+            .fields
+            .iter()
+            .find(|field| Some(field.name.as_str()) == column.ident())
+            .filter(|field| !transform::has_transforms(field))?;
+
+        let keywords: Vec<&str> = modifiers
+            .iter()
+            .map(|token| match token {
+                Token::Word(word) => Some(word.as_str()),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        let (descending, nulls) = match keywords.as_slice() {
+            ["asc", nulls @ ..] => (false, nulls),
+            ["desc", nulls @ ..] => (true, nulls),
+            nulls => (false, nulls),
+        };
+        // Postgres sorts NULLs as larger than any value by default
+        let nulls_first = match nulls {
+            [] => descending,
+            ["nulls", "first"] => true,
+            ["nulls", "last"] => false,
+            _ => return None,
+        };
+        Some(MergeOrder {
+            field: field.name.clone(),
+            descending,
+            nulls_first,
+        })
+    }
+
+    /// Sort the rows of every database and keep the query's limit
+    pub fn apply(&self, rows: &mut Vec<JsonValue>, params: &[SqlParam]) {
+        if !self.order_by.is_empty() {
+            rows.sort_by(|a, b| {
+                self.order_by
+                    .iter()
+                    .map(|order| compare_field(a, b, order))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        let limit = match self.limit {
+            Some(MergeLimit::Rows(rows)) => Some(rows),
+            Some(MergeLimit::Param(index)) => match params.get(index.wrapping_sub(1)) {
+                Some(SqlParam::U64(limit)) => usize::try_from(*limit).ok(),
+                Some(SqlParam::I64(limit)) => usize::try_from(*limit).ok(),
+                _ => None,
+            },
+            None => None,
+        };
+        if let Some(limit) = limit {
+            rows.truncate(limit);
+        }
+    }
+}
+
+/// Compare a field of two rows like Postgres' `ORDER BY` would
+fn compare_field(a: &JsonValue, b: &JsonValue, order: &MergeOrder) -> Ordering {
+    let (a, b) = (&a[&order.field], &b[&order.field]);
+    let ordering = match (a.is_null(), b.is_null()) {
+        (true, true) => return Ordering::Equal,
+        (true, false) if order.nulls_first => return Ordering::Less,
+        (true, false) => return Ordering::Greater,
+        (false, true) if order.nulls_first => return Ordering::Greater,
+        (false, true) => return Ordering::Less,
+        (false, false) => compare_values(a, b),
+    };
+    if order.descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+fn compare_values(a: &JsonValue, b: &JsonValue) -> Ordering {
+    match (a, b) {
+        (JsonValue::Number(a), JsonValue::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => a
+                .as_f64()
+                .partial_cmp(&b.as_f64())
+                .unwrap_or(Ordering::Equal),
+        },
+        // NUMERIC columns, e.g. uint256 amounts, are returned as their digits
+        (JsonValue::String(a), JsonValue::String(b)) => {
+            compare_integers(a, b).unwrap_or_else(|| a.cmp(b))
+        }
+        (JsonValue::Bool(a), JsonValue::Bool(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+/// Compare two strings of decimal integers by value, `None` if either isn't one
+fn compare_integers(a: &str, b: &str) -> Option<Ordering> {
+    fn parse(s: &str) -> Option<(bool, &str)> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let digits = digits.trim_start_matches('0');
+        Some((negative && !digits.is_empty(), digits))
+    }
+    let ((a_negative, a), (b_negative, b)) = (parse(a)?, parse(b)?);
+    let magnitude = a.len().cmp(&b.len()).then_with(|| a.cmp(b));
+    Some(match (a_negative, b_negative) {
+        (false, false) => magnitude,
+        (true, true) => magnitude.reverse(),
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::TableSchema;
+    use crate::config::ChainDatabaseConfig;
+
+    const MAIN_DB: &str = "postgresql://smorty@main-db/smorty";
+    const BASE_DB: &str = "postgresql://smorty@base-db/smorty";
+
+    fn spec_ir(chain: &str, table_name: &str) -> (String, String, IrGenerationResult) {
+        let ir = IrGenerationResult {
+            event_name: "Transfer".to_string(),
+            event_signature: "Transfer(address,address,uint256)".to_string(),
+            start_block: 0,
+            contract_address: "0x0000000000000000000000000000000000000001".to_string(),
+            chain: chain.to_string(),
+            indexed_fields: vec![],
+            table_schema: TableSchema {
+                table_name: table_name.to_string(),
+                columns: vec![],
+                indexes: vec![],
+                computed_columns: vec![],
+            },
+            description: String::new(),
+            abi_hash: None,
+            state_fold: None,
+        };
+        ("Token".to_string(), format!("{}_{}", chain, table_name), ir)
+    }
+
+    fn endpoint(tables: &[&str]) -> EndpointIrResult {
+        query_endpoint(tables, "SELECT 1")
+    }
+
+    fn query_endpoint(tables: &[&str], sql: &str) -> EndpointIrResult {
+        serde_json::from_value(serde_json::json!({
+            "endpoint_path": "/api/transfers",
+            "description": "",
+            "method": "GET",
+            "path_params": [],
+            "query_params": [],
+            "response_schema": {"name": "Transfers", "fields": [
+                {"name": "block_number", "type": "i64", "description": ""},
+                {"name": "value", "type": "String", "description": ""},
+                {"name": "total", "type": "i64", "description": ""},
+            ]},
+            "sql_query": sql,
+            "tables_referenced": tables,
+        }))
+        .unwrap()
+    }
+
+    fn two_database_router() -> DatabaseRouter {
+        let config = DatabaseConfig {
+            uri: MAIN_DB.to_string(),
+            api_uri: None,
+            per_chain: HashMap::from([(
+                "base".to_string(),
+                ChainDatabaseConfig {
+                    uri: BASE_DB.to_string(),
+                    api_uri: None,
+                },
+            )]),
+        };
+        let ir_specs = vec![
+            spec_ir("mainnet", "transfers"),
+            spec_ir("base", "transfers"),
+        ];
+        let pools = [MAIN_DB, BASE_DB]
+            .into_iter()
+            .map(|uri| (uri.to_string(), PgPool::connect_lazy(uri).unwrap()))
+            .collect();
+        DatabaseRouter::new(&config, pools).with_tables(&ir_specs)
+    }
+
+    fn hosts(pools: Vec<PgPool>) -> Vec<String> {
+        pools
+            .iter()
+            .map(|pool| pool.connect_options().get_host().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_routes_tables_to_chain_databases() {
+        let config = DatabaseConfig {
+            uri: MAIN_DB.to_string(),
+            api_uri: None,
+            per_chain: HashMap::from([(
+                "base".to_string(),
+                ChainDatabaseConfig {
+                    uri: BASE_DB.to_string(),
+                    api_uri: None,
+                },
+            )]),
+        };
+        let ir_specs = vec![
+            spec_ir("mainnet", "transfers"),
+            spec_ir("base", "transfers"),
+            spec_ir("base", "swaps"),
+            spec_ir("mainnet", "pools"),
+        ];
+        let pools = [MAIN_DB, BASE_DB]
+            .into_iter()
+            .map(|uri| (uri.to_string(), PgPool::connect_lazy(uri).unwrap()))
+            .collect();
+        let router = DatabaseRouter::new(&config, pools).with_tables(&ir_specs);
+
+        assert_eq!(hosts(router.table_pools("swaps")), vec!["base-db"]);
+        assert_eq!(hosts(router.table_pools("pools")), vec!["main-db"]);
+        assert_eq!(
+            hosts(router.table_pools("smorty_coverage")),
+            vec!["main-db"]
+        );
+        assert_eq!(
+            hosts(router.table_pools("transfers")),
+            vec!["base-db", "main-db"]
+        );
+        assert_eq!(
+            hosts(router.endpoint_pools(&endpoint(&["swaps", "pools"]))),
+            vec!["base-db", "main-db"]
+        );
+        assert_eq!(
+            hosts(router.endpoint_pools(&endpoint(&[]))),
+            vec!["main-db"]
+        );
+        assert_eq!(hosts(vec![router.contract_pool("Token")]), vec!["main-db"]);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_queries_that_cant_be_merged() {
+        let router = two_database_router();
+
+        for sql in [
+            "SELECT COUNT(*) AS total FROM transfers",
+            "SELECT block_number, count (*) AS total FROM transfers GROUP BY 1 LIMIT $1",
+            "SELECT DISTINCT value FROM transfers LIMIT 10",
+            "SELECT * FROM transfers ORDER BY block_number LIMIT 10 OFFSET 10",
+            "SELECT * FROM transfers ORDER BY block_number + 1 LIMIT 10",
+            "SELECT * FROM transfers ORDER BY 1 LIMIT 10",
+            "SELECT * FROM transfers ORDER BY log_index LIMIT 10",
+        ] {
+            assert!(
+                router
+                    .endpoint_merge(&query_endpoint(&["transfers"], sql))
+                    .is_err(),
+                "{}",
+                sql
+            );
+        }
+        assert!(
+            router
+                .endpoint_merge(&query_endpoint(
+                    &["transfers", "swaps"],
+                    "SELECT * FROM transfers t JOIN swaps s ON s.tx = t.tx LIMIT 10"
+                ))
+                .is_err()
+        );
+        // Within one database, any query is fine
+        assert_eq!(
+            router
+                .endpoint_merge(&query_endpoint(
+                    &["swaps"],
+                    "SELECT COUNT(*) AS total FROM swaps"
+                ))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merges_rows_of_two_databases() {
+        let router = two_database_router();
+        let row_merge = router
+            .endpoint_merge(&query_endpoint(
+                &["transfers"],
+                "SELECT block_number, value FROM transfers t \
+                 WHERE value <> '0' ORDER BY t.value DESC, block_number LIMIT $1",
+            ))
+            .unwrap()
+            .unwrap();
+        assert_eq!(row_merge.limit, Some(MergeLimit::Param(1)));
+
+        let row = |block_number: i64, value: &str| serde_json::json!({"block_number": block_number, "value": value});
+        // Each database's own first rows, concatenated
+        let mut rows = vec![
+            row(10, "900"),
+            row(11, "20"),
+            row(12, "3"),
+            row(20, "1000"),
+            row(21, "20"),
+            row(22, "5"),
+        ];
+        row_merge.apply(&mut rows, &[SqlParam::U64(4)]);
+
+        assert_eq!(
+            rows,
+            vec![
+                row(20, "1000"),
+                row(10, "900"),
+                row(11, "20"),
+                row(21, "20")
+            ]
+        );
+
+        let row_merge = RowMerge::plan(&query_endpoint(
+            &["transfers"],
+            "SELECT * FROM transfers LIMIT 2",
+        ))
+        .unwrap();
+        let mut rows = vec![row(1, "1"), row(2, "2"), row(3, "3")];
+        row_merge.apply(&mut rows, &[]);
+        assert_eq!(rows.len(), 2);
+    }
+}
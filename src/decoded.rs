@@ -0,0 +1,281 @@
+//! Typed values decoded from event logs
+//!
+//! Decoding stays separate from storage: the Postgres writer binds the values of
+//! a [`DecodedEvent`] to its INSERT, and other consumers can take its values or
+//! JSON as they are.
+
+use crate::ai::IrGenerationResult;
+use alloy::dyn_abi::{DynSolType, DynSolValue};
+use alloy::primitives::{Address, B256, Bytes, I256, U256};
+use alloy::rpc::types::Log;
+use anyhow::{Context, Result};
+use serde_json::Value as JsonValue;
+
+/// A decoded event parameter
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodedValue {
+    Address(Address),
+    U256(U256),
+    I256(I256),
+    /// Fixed-size or dynamic bytes
+    Bytes(Bytes),
+    Bool(bool),
+    String(String),
+    /// Arrays and structs, with structs as arrays of their fields since event
+    /// ABIs in the IR don't carry component names
+    Array(Vec<DecodedValue>),
+    /// Topic of an indexed `bytesN`, or the keccak hash of an indexed string,
+    /// bytes, struct or array
+    Topic(B256),
+    /// Value missing from the log's data
+    Null,
+    /// Block number, timestamp or log index a row is stored with, rather than
+    /// an event parameter
+    Int(i64),
+}
+
+impl DecodedValue {
+    /// Typed value of an ABI-decoded value
+    pub fn from_sol(value: &DynSolValue) -> Self {
+        match value {
+            DynSolValue::Bool(b) => Self::Bool(*b),
+            DynSolValue::Int(i, _) => Self::I256(*i),
+            DynSolValue::Uint(u, _) => Self::U256(*u),
+            DynSolValue::Address(a) => Self::Address(*a),
+            DynSolValue::FixedBytes(word, size) => {
+                Self::Bytes(Bytes::copy_from_slice(&word[..*size]))
+            }
+            DynSolValue::Bytes(bytes) => Self::Bytes(Bytes::copy_from_slice(bytes)),
+            DynSolValue::String(s) => Self::String(s.clone()),
+            DynSolValue::Array(values)
+            | DynSolValue::FixedArray(values)
+            | DynSolValue::Tuple(values) => {
+                Self::Array(values.iter().map(Self::from_sol).collect())
+            }
+            DynSolValue::Function(f) => Self::Bytes(Bytes::copy_from_slice(f.as_slice())),
+        }
+    }
+
+    /// Value of an indexed parameter from its topic
+    fn from_topic(topic: B256, solidity_type: &str) -> Self {
+        match solidity_type {
+            // Address is stored in the last 20 bytes of the topic
+            "address" => Self::Address(Address::from_slice(&topic[12..])),
+            "bool" => Self::Bool(topic.iter().any(|&b| b != 0)),
+            t if t.starts_with("uint") => Self::U256(U256::from_be_bytes(topic.0)),
+            // Signed integers are sign-extended to 32 bytes
+            t if t.starts_with("int") => Self::I256(I256::from_raw(U256::from_be_bytes(topic.0))),
+            _ => Self::Topic(topic),
+        }
+    }
+
+    /// Value of a static parameter from its 32-byte word in the log's data
+    fn from_word(word: &[u8], solidity_type: &str) -> Self {
+        match solidity_type {
+            // Address is in the last 20 bytes
+            "address" => Self::Address(Address::from_slice(&word[12..])),
+            "bool" => Self::Bool(word.iter().any(|&b| b != 0)),
+            t if t.starts_with("uint") => Self::U256(U256::from_be_slice(word)),
+            t if t.starts_with("int") => Self::I256(I256::from_raw(U256::from_be_slice(word))),
+            // Fixed-size bytes (bytesN) are left-aligned
+            t if t.starts_with("bytes") && t.len() > 5 => match t[5..].parse::<usize>() {
+                Ok(size) if size <= word.len() => {
+                    Self::Bytes(Bytes::copy_from_slice(&word[..size]))
+                }
+                _ => Self::Bytes(Bytes::copy_from_slice(word)),
+            },
+            // Dynamic types would need their offsets followed, keep the raw word
+            _ => Self::Bytes(Bytes::copy_from_slice(word)),
+        }
+    }
+
+    /// JSON form of the value
+    ///
+    /// Integers are strings so 256-bit values keep their precision.
+    pub fn to_json(&self) -> JsonValue {
+        match self {
+            Self::Address(a) => JsonValue::String(format!("{:#x}", a)),
+            Self::U256(u) => JsonValue::String(u.to_string()),
+            Self::I256(i) => JsonValue::String(i.to_string()),
+            Self::Bytes(bytes) => JsonValue::String(format!("0x{}", hex::encode(bytes))),
+            Self::Bool(b) => JsonValue::Bool(*b),
+            Self::String(s) => JsonValue::String(s.clone()),
+            Self::Array(values) => JsonValue::Array(values.iter().map(Self::to_json).collect()),
+            Self::Topic(topic) => JsonValue::String(format!("{:#x}", topic)),
+            Self::Null => JsonValue::Null,
+            Self::Int(i) => JsonValue::from(*i),
+        }
+    }
+}
+
+/// A decoded event parameter with its name and type from the IR
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedField {
+    pub name: String,
+    pub solidity_type: String,
+    pub value: DecodedValue,
+}
+
+impl DecodedField {
+    pub fn new(name: &str, solidity_type: &str, value: DecodedValue) -> Self {
+        Self {
+            name: name.to_string(),
+            solidity_type: solidity_type.to_string(),
+            value,
+        }
+    }
+
+    /// Whether the field is stored as JSON (structs and arrays)
+    pub fn is_json(&self) -> bool {
+        is_json_type(&self.solidity_type)
+    }
+}
+
+/// Parameters of an event, in the order of the IR's indexed fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedEvent {
+    pub fields: Vec<DecodedField>,
+}
+
+impl DecodedEvent {
+    /// Decode a log's topics and data with the fields of its IR
+    pub fn decode(log: &Log, ir: &IrGenerationResult) -> Result<Self> {
+        // Topics: [event_signature, indexed_param_1, indexed_param_2, ...]
+        // Data: concatenated non-indexed parameters
+        let topics = log.topics();
+        let mut topic_index = 1; // Skip first topic (event signature)
+
+        let data = &log.data().data;
+        let mut data_offset = 0;
+
+        // Structs and arrays are ABI-encoded with offsets, so decode the whole data section
+        let non_indexed: Vec<&str> = ir
+            .indexed_fields
+            .iter()
+            .filter(|field| !field.indexed)
+            .map(|field| field.solidity_type.as_str())
+            .collect();
+        let mut sol_values = if non_indexed.iter().any(|t| is_json_type(t)) {
+            Some(decode_data_values(data, &non_indexed)?.into_iter())
+        } else {
+            None
+        };
+
+        let mut fields = Vec::with_capacity(ir.indexed_fields.len());
+        for field in &ir.indexed_fields {
+            let value = if field.indexed && topic_index < topics.len() {
+                let topic = topics[topic_index];
+                topic_index += 1;
+                if is_json_type(&field.solidity_type) {
+                    // Reference types are stored in topics as the keccak hash of their encoding
+                    DecodedValue::Topic(topic)
+                } else {
+                    DecodedValue::from_topic(topic, &field.solidity_type)
+                }
+            } else if let Some(values) = sol_values.as_mut() {
+                let value = values
+                    .next()
+                    .context("Event data has fewer values than fields")?;
+                DecodedValue::from_sol(&value)
+            } else {
+                // All values in data are 32-byte aligned
+                match data.get(data_offset..data_offset + 32) {
+                    Some(word) => {
+                        data_offset += 32;
+                        DecodedValue::from_word(word, &field.solidity_type)
+                    }
+                    None => DecodedValue::Null,
+                }
+            };
+
+            fields.push(DecodedField {
+                name: field.name.clone(),
+                solidity_type: field.solidity_type.clone(),
+                value,
+            });
+        }
+
+        Ok(Self { fields })
+    }
+
+    /// Parameters as a JSON object keyed by field name
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(
+            self.fields
+                .iter()
+                .map(|field| (field.name.clone(), field.value.to_json()))
+                .collect(),
+        )
+    }
+}
+
+/// Whether a Solidity type is stored in a JSONB column (structs and arrays)
+pub fn is_json_type(solidity_type: &str) -> bool {
+    let solidity_type = solidity_type.trim();
+    solidity_type.ends_with(']')
+        || solidity_type.starts_with('(')
+        || solidity_type.starts_with("tuple")
+}
+
+/// Decode the non-indexed values of an event from its data section
+fn decode_data_values(data: &[u8], solidity_types: &[&str]) -> Result<Vec<DynSolValue>> {
+    let types = solidity_types
+        .iter()
+        .map(|t| DynSolType::parse(t).context(format!("Unsupported Solidity type: {}", t)))
+        .collect::<Result<Vec<_>>>()?;
+
+    match DynSolType::Tuple(types)
+        .abi_decode_sequence(data)
+        .context("Failed to ABI-decode event data")?
+    {
+        DynSolValue::Tuple(values) => Ok(values),
+        value => Ok(vec![value]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_structs_and_arrays_to_json() {
+        let encoded = DynSolValue::Tuple(vec![
+            DynSolValue::Int(I256::try_from(-7).unwrap(), 256),
+            DynSolValue::Array(vec![
+                DynSolValue::Address(Address::repeat_byte(0x11)),
+                DynSolValue::Address(Address::repeat_byte(0x22)),
+            ]),
+            DynSolValue::Tuple(vec![
+                DynSolValue::String("it's".to_string()),
+                DynSolValue::Bool(true),
+            ]),
+        ])
+        .abi_encode_sequence()
+        .unwrap();
+
+        assert!(is_json_type("address[]"));
+        assert!(is_json_type("(string,bool)"));
+        assert!(!is_json_type("uint256"));
+
+        let values =
+            decode_data_values(&encoded, &["int256", "address[]", "(string,bool)"]).unwrap();
+        let values: Vec<DecodedValue> = values.iter().map(DecodedValue::from_sol).collect();
+        assert_eq!(values[0], DecodedValue::I256(I256::try_from(-7).unwrap()));
+        assert_eq!(
+            values[1].to_json(),
+            serde_json::json!([
+                "0x1111111111111111111111111111111111111111",
+                "0x2222222222222222222222222222222222222222"
+            ])
+        );
+        assert_eq!(values[2].to_json(), serde_json::json!(["it's", true]));
+
+        // Signed integers in data words and topics are sign-extended
+        let word = B256::from(I256::try_from(-7).unwrap().into_raw());
+        assert_eq!(
+            DecodedValue::from_word(word.as_slice(), "int24").to_json(),
+            "-7"
+        );
+        assert_eq!(DecodedValue::from_topic(word, "int24"), values[0]);
+    }
+}
@@ -0,0 +1,183 @@
+//! Rows printed by `smorty index --dry-run` instead of being inserted
+//!
+//! Each decoded log becomes one JSON line with its table and column values,
+//! built exactly as the indexer would insert it, so decoding and the schema
+//! mapping can be checked before running migrations. `gen-spec --preview`
+//! prints a few such rows per spec from its most recent logs.
+
+use serde::Serialize;
+use serde::ser::{SerializeMap, Serializer};
+use serde_json::Value;
+
+/// A row the indexer would insert
+#[derive(Debug, Clone, Serialize)]
+pub struct DryRunRow {
+    /// `<contract>/<spec>` the log matched
+    pub spec: String,
+    pub table: String,
+    /// Column values in insert order
+    #[serde(serialize_with = "serialize_columns")]
+    pub columns: Vec<(String, Value)>,
+}
+
+impl DryRunRow {
+    /// Build a row from the columns and SQL literals of an INSERT
+    pub fn new(spec: String, table: String, columns: &[String], values: &[String]) -> Self {
+        let columns = columns
+            .iter()
+            .zip(values)
+            .map(|(column, value)| (column.clone(), literal_to_json(value)))
+            .collect();
+        Self {
+            spec,
+            table,
+            columns,
+        }
+    }
+}
+
+/// Counts printed after a dry run
+#[derive(Debug, Clone, Default)]
+pub struct DryRunSummary {
+    pub chain: String,
+    pub from_block: u64,
+    pub to_block: u64,
+    pub logs: usize,
+    pub rows: usize,
+    pub failed: usize,
+}
+
+impl DryRunSummary {
+    pub fn render(&self) -> String {
+        format!(
+            "{}: blocks {} to {}, {} logs, {} rows, {} failed to decode",
+            self.chain, self.from_block, self.to_block, self.logs, self.rows, self.failed
+        )
+    }
+}
+
+/// Newest logs of a spec decoded as rows, printed by `gen-spec --preview`
+#[derive(Debug, Clone)]
+pub struct SpecPreview {
+    /// `<contract>/<spec>`
+    pub spec: String,
+    /// Blocks searched for logs
+    pub from_block: u64,
+    pub to_block: u64,
+    pub rows: Vec<DryRunRow>,
+    pub failed: usize,
+}
+
+impl SpecPreview {
+    pub fn render(&self) -> String {
+        let mut text = if self.rows.is_empty() && self.failed == 0 {
+            format!(
+                "{}: no matching logs in blocks {} to {}",
+                self.spec, self.from_block, self.to_block
+            )
+        } else {
+            format!(
+                "{}: {} sample rows from blocks {} to {}",
+                self.spec,
+                self.rows.len(),
+                self.from_block,
+                self.to_block
+            )
+        };
+        if self.failed > 0 {
+            text.push_str(&format!(", {} logs failed to decode", self.failed));
+        }
+        for row in &self.rows {
+            let columns: Vec<String> = row
+                .columns
+                .iter()
+                .map(|(column, value)| format!("{}={}", column, value))
+                .collect();
+            text.push_str(&format!("\n  {}: {}", row.table, columns.join(" ")));
+        }
+        text
+    }
+}
+
+/// Keep columns in insert order rather than sorting them like a JSON map would
+fn serialize_columns<S: Serializer>(
+    columns: &[(String, Value)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(columns.len()))?;
+    for (column, value) in columns {
+        map.serialize_entry(column, value)?;
+    }
+    map.end()
+}
+
+/// Readable value of a SQL literal: strings are unquoted, other expressions kept as text
+fn literal_to_json(literal: &str) -> Value {
+    if let Some(inner) = literal
+        .strip_prefix('\'')
+        .and_then(|rest| rest.strip_suffix('\''))
+        .filter(|inner| !inner.replace("''", "").contains('\''))
+    {
+        return Value::String(inner.replace("''", "'"));
+    }
+
+    match literal {
+        "NULL" => Value::Null,
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => literal
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::String(literal.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_keep_column_order() {
+        let columns = [
+            "block_number",
+            "transaction_hash",
+            "pool",
+            "memo",
+            "day",
+            "flag",
+        ]
+        .map(String::from);
+        let values = [
+            "123",
+            "'0xabc'",
+            "'0x0000000000000000000000000000000000000001'",
+            "'it''s'",
+            "to_timestamp(1700000000)::date",
+            "true",
+        ]
+        .map(String::from);
+        let row = DryRunRow::new("C/Swap".to_string(), "swaps".to_string(), &columns, &values);
+
+        assert_eq!(
+            serde_json::to_string(&row).unwrap(),
+            r#"{"spec":"C/Swap","table":"swaps","columns":{"block_number":123,"transaction_hash":"0xabc","pool":"0x0000000000000000000000000000000000000001","memo":"it's","day":"to_timestamp(1700000000)::date","flag":true}}"#
+        );
+
+        let preview = SpecPreview {
+            spec: "C/Swap".to_string(),
+            from_block: 100,
+            to_block: 199,
+            rows: vec![DryRunRow::new(
+                "C/Swap".to_string(),
+                "swaps".to_string(),
+                &columns[..3],
+                &values[..3],
+            )],
+            failed: 1,
+        };
+        assert_eq!(
+            preview.render(),
+            "C/Swap: 1 sample rows from blocks 100 to 199, 1 logs failed to decode\n  swaps: block_number=123 transaction_hash=\"0xabc\" pool=\"0x0000000000000000000000000000000000000001\""
+        );
+    }
+}
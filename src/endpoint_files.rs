@@ -0,0 +1,236 @@
+//! File names of endpoint IRs and their test fixtures
+//!
+//! Endpoint routes are encoded into file names that are valid on every platform
+//! and decode back to the route: `/` becomes `_`, a parameter `{pool}` becomes
+//! `+pool`, and any other character that isn't an ASCII letter, digit or `-` is
+//! written as `~XX` per UTF-8 byte. Endpoints other than GET get their method as
+//! an extension. So `/api/pools/{pool}/swaps` is stored as
+//! `api_pools_+pool_swaps.json`, and `/api/a-b`, `/api/a_b` and `/api/a/b` no
+//! longer share a file.
+//!
+//! `ir/endpoints/manifest.json` maps each route to its file. IRs saved under the
+//! names used before this encoding are still found and moved on their next save.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Manifest of endpoint IR files, next to the IRs
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// File name of the root path `/`
+const ROOT: &str = "~2F";
+
+/// Device names Windows reserves regardless of extension
+const RESERVED_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// File name of an endpoint's IR, e.g. `api_pools_+pool.json` for `GET /api/pools/{pool}`
+pub fn file_name(endpoint_path: &str, method: &str) -> String {
+    format!("{}.json", stem(endpoint_path, method))
+}
+
+/// File stem of an endpoint, without the `.json` extension
+pub fn stem(endpoint_path: &str, method: &str) -> String {
+    let segments: Vec<String> = endpoint_path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(
+            |segment| match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                Some(param) => format!("+{}", escape(param)),
+                None => escape(segment),
+            },
+        )
+        .collect();
+
+    let mut stem = if segments.is_empty() {
+        ROOT.to_string()
+    } else {
+        segments.join("_")
+    };
+    if RESERVED_NAMES.contains(&stem.to_ascii_lowercase().as_str()) {
+        stem = format!("~{:02X}{}", stem.as_bytes()[0], &stem[1..]);
+    }
+
+    if method.eq_ignore_ascii_case("GET") {
+        stem
+    } else {
+        format!("{}.{}", stem, method.to_ascii_lowercase())
+    }
+}
+
+/// Endpoint path and method of a file stem made by [`stem`]
+pub fn decode(stem: &str) -> Option<(String, String)> {
+    let (stem, method) = match stem.split_once('.') {
+        Some((stem, method)) => (stem, method.to_ascii_uppercase()),
+        None => (stem, "GET".to_string()),
+    };
+    if stem == ROOT {
+        return Some(("/".to_string(), method));
+    }
+
+    let mut path = String::new();
+    for segment in stem.split('_') {
+        path.push('/');
+        match segment.strip_prefix('+') {
+            Some(param) => path.push_str(&format!("{{{}}}", unescape(param)?)),
+            None => path.push_str(&unescape(segment)?),
+        }
+    }
+    Some((path, method))
+}
+
+/// File name endpoints were saved under before the encoding, which could collide
+pub fn legacy_file_name(endpoint_path: &str, method: &str) -> String {
+    let stem = endpoint_path
+        .trim_start_matches('/')
+        .replace('/', "_")
+        .replace(['{', '}'], "");
+    if method.eq_ignore_ascii_case("GET") {
+        format!("{}.json", stem)
+    } else {
+        format!("{}.{}.json", stem, method.to_ascii_lowercase())
+    }
+}
+
+/// File of an endpoint in `dir`: the one in the manifest, else an existing file
+/// under the current or the legacy name, else the current name
+pub fn resolve(dir: &Path, endpoint_path: &str, method: &str) -> PathBuf {
+    let route = route(endpoint_path, method);
+    match Manifest::load(dir) {
+        Ok(manifest) => {
+            if let Some(file) = manifest.files.get(&route) {
+                return dir.join(file);
+            }
+        }
+        Err(e) => tracing::debug!("Ignoring endpoint manifest in {}: {:#}", dir.display(), e),
+    }
+
+    let current = dir.join(file_name(endpoint_path, method));
+    let legacy = dir.join(legacy_file_name(endpoint_path, method));
+    if !current.exists() && legacy.exists() {
+        legacy
+    } else {
+        current
+    }
+}
+
+fn route(endpoint_path: &str, method: &str) -> String {
+    format!("{} {}", method.to_ascii_uppercase(), endpoint_path)
+}
+
+/// Routes of the endpoint IRs in a directory and their files
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Manifest {
+    files: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// Manifest of `dir`, empty if it has none yet
+    pub fn load(dir: &Path) -> Result<Self> {
+        let file = dir.join(MANIFEST_FILE);
+        if !file.exists() {
+            return Ok(Self::default());
+        }
+        let content =
+            fs::read_to_string(&file).context(format!("Failed to read {}", file.display()))?;
+        serde_json::from_str(&content).context(format!("Failed to parse {}", file.display()))
+    }
+
+    /// Record the file of an endpoint and write the manifest
+    pub fn record(dir: &Path, endpoint_path: &str, method: &str, file: &Path) -> Result<()> {
+        let mut manifest = Self::load(dir).unwrap_or_default();
+        let name = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .context("Endpoint IR file has no name")?;
+        manifest
+            .files
+            .insert(route(endpoint_path, method), name.to_string());
+
+        let json =
+            serde_json::to_string_pretty(&manifest).context("Failed to serialize manifest")?;
+        let file = dir.join(MANIFEST_FILE);
+        fs::write(&file, json).context(format!("Failed to write {}", file.display()))
+    }
+}
+
+/// Keep ASCII letters, digits and `-`, and write other characters as `~XX` per byte
+fn escape(segment: &str) -> String {
+    let mut escaped = String::with_capacity(segment.len());
+    for c in segment.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' {
+            escaped.push(c);
+        } else {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                escaped.push_str(&format!("~{:02X}", byte));
+            }
+        }
+    }
+    escaped
+}
+
+fn unescape(segment: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(segment.len());
+    let mut rest = segment.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'~' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_names_are_distinct_and_reversible() {
+        let routes = [
+            ("/api/a-b", "GET"),
+            ("/api/a_b", "GET"),
+            ("/api/a/b", "GET"),
+            ("/api/{a}/b", "GET"),
+            ("/api/pools/{pool}/swaps", "POST"),
+            ("/api/fees:daily", "GET"),
+            ("/con", "GET"),
+            ("/", "GET"),
+        ];
+        let stems: Vec<String> = routes
+            .iter()
+            .map(|(path, method)| stem(path, method))
+            .collect();
+
+        assert_eq!(stems[0], "api_a-b");
+        assert_eq!(stems[1], "api_a~5Fb");
+        assert_eq!(stems[2], "api_a_b");
+        assert_eq!(stems[3], "api_+a_b");
+        assert_eq!(stems[4], "api_pools_+pool_swaps.post");
+        assert_eq!(stems[6], "~63on");
+        for ((path, method), stem) in routes.iter().zip(&stems) {
+            assert!(
+                !stem.contains([':', '<', '>', '"', '\\', '|', '?', '*', '/']),
+                "{}",
+                stem
+            );
+            assert_eq!(
+                decode(stem),
+                Some((path.to_string(), method.to_string())),
+                "{}",
+                stem
+            );
+        }
+    }
+}
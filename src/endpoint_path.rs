@@ -0,0 +1,231 @@
+//! Canonical form of endpoint paths
+//!
+//! Paths from the config and from generated IRs are lowercased, lose their
+//! trailing slash and have their `{param}` segments checked, so they can be
+//! compared with each other and registered with axum's router. Requests are
+//! matched the same way, so `/API/Pools/` reaches `/api/pools`.
+
+use anyhow::{Result, bail};
+use std::collections::HashSet;
+
+/// Normalize an endpoint path, rejecting paths axum's router can't register
+///
+/// Static segments are lowercased, parameter names are kept as they are since
+/// they are matched against the IR's path parameters.
+pub fn normalize(path: &str) -> Result<String> {
+    let path = path.trim();
+    if !path.starts_with('/') {
+        bail!("Endpoint path '{}' must start with '/'", path);
+    }
+
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.is_empty() {
+        return Ok("/".to_string());
+    }
+
+    let mut params = HashSet::new();
+    let mut segments = Vec::new();
+    for segment in trimmed[1..].split('/') {
+        match parse_segment(segment) {
+            Some(Segment::Static(name)) => segments.push(name),
+            Some(Segment::Param(name)) => {
+                if !params.insert(name.clone()) {
+                    bail!(
+                        "Endpoint path '{}' repeats the parameter {{{}}}",
+                        path,
+                        name
+                    );
+                }
+                segments.push(format!("{{{}}}", name));
+            }
+            None => bail!(
+                "Endpoint path '{}' has an invalid segment '{}'. Use letters, digits, '-', '_' and '.', or a {{param}}",
+                path,
+                segment
+            ),
+        }
+    }
+
+    Ok(format!("/{}", segments.join("/")))
+}
+
+/// Shape of a normalized path, two paths with the same shape conflict in the router
+///
+/// `/api/pools/{id}` and `/api/pools/{address}` both become `/api/pools/{}`.
+pub fn route_shape(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            if segment.starts_with('{') {
+                "{}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// HTTP methods generated endpoints can be served with
+pub const METHODS: &[&str] = &["GET", "POST"];
+
+/// Uppercase an endpoint's HTTP method, rejecting methods the router doesn't serve
+pub fn normalize_method(method: &str) -> Result<String> {
+    let method = method.trim().to_ascii_uppercase();
+    if !METHODS.contains(&method.as_str()) {
+        bail!(
+            "Unsupported endpoint method '{}', expected one of {}",
+            method,
+            METHODS.join(", ")
+        );
+    }
+    Ok(method)
+}
+
+/// Whether two endpoints can't be registered together
+///
+/// Endpoints on the same path can differ in method, e.g. a GET list and a POST
+/// search. Spelling a route's parameters differently conflicts whatever the method,
+/// since both methods must be attached to one route.
+pub fn conflicts(path: &str, method: &str, other_path: &str, other_method: &str) -> bool {
+    route_shape(path) == route_shape(other_path)
+        && (path != other_path || method.eq_ignore_ascii_case(other_method))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Static(String),
+    Param(String),
+}
+
+fn parse_segment(segment: &str) -> Option<Segment> {
+    if let Some(name) = segment
+        .strip_prefix('{')
+        .and_then(|rest| rest.strip_suffix('}'))
+    {
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        return valid.then(|| Segment::Param(name.to_string()));
+    }
+
+    let valid = !segment.is_empty()
+        && segment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    valid.then(|| Segment::Static(segment.to_ascii_lowercase()))
+}
+
+/// Registered endpoint paths, used to route requests that differ only in case
+#[derive(Debug, Clone, Default)]
+pub struct RouteTemplates {
+    templates: Vec<Vec<Segment>>,
+}
+
+impl RouteTemplates {
+    /// Templates from normalized endpoint paths
+    pub fn new<'a>(paths: impl IntoIterator<Item = &'a str>) -> Self {
+        let templates = paths
+            .into_iter()
+            .map(|path| path.split('/').skip(1).filter_map(parse_segment).collect())
+            .collect();
+        Self { templates }
+    }
+
+    /// The template's spelling of a request path matching it up to case and a trailing slash
+    ///
+    /// Parameter values are kept as they are. Other paths, such as the Swagger UI's
+    /// which rely on their trailing slash, return `None`.
+    pub fn canonicalize(&self, path: &str) -> Option<String> {
+        let trimmed = path.trim_end_matches('/');
+        if trimmed.is_empty() {
+            return None;
+        }
+
+        let segments: Vec<&str> = trimmed[1..].split('/').collect();
+        let template = self.templates.iter().find(|template| {
+            template.len() == segments.len()
+                && template
+                    .iter()
+                    .zip(&segments)
+                    .all(|(expected, actual)| match expected {
+                        Segment::Static(name) => name.eq_ignore_ascii_case(actual),
+                        Segment::Param(_) => true,
+                    })
+        })?;
+
+        let rewritten: Vec<&str> = template
+            .iter()
+            .zip(&segments)
+            .map(|(expected, actual)| match expected {
+                Segment::Static(name) => name.as_str(),
+                Segment::Param(_) => actual,
+            })
+            .collect();
+        Some(format!("/{}", rewritten.join("/")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize("/API/Pools/{poolAddress}/").unwrap(),
+            "/api/pools/{poolAddress}"
+        );
+        assert_eq!(normalize(" /api/fee-updates ").unwrap(), "/api/fee-updates");
+        assert_eq!(normalize("/").unwrap(), "/");
+
+        for invalid in [
+            "api/pools",
+            "/api//pools",
+            "/api/:id",
+            "/api/{*rest}",
+            "/api/{id}.json",
+            "/api/{}",
+            "/api/{id}/{id}",
+            "/api/pools?limit=10",
+        ] {
+            assert!(normalize(invalid).is_err(), "{}", invalid);
+        }
+
+        assert_eq!(route_shape("/api/pools/{id}"), "/api/pools/{}");
+    }
+
+    #[test]
+    fn test_methods_share_a_path() {
+        assert_eq!(normalize_method(" post ").unwrap(), "POST");
+        assert!(normalize_method("DELETE").is_err());
+
+        assert!(!conflicts("/api/pools", "GET", "/api/pools", "POST"));
+        assert!(conflicts("/api/pools", "GET", "/api/pools", "get"));
+        assert!(conflicts(
+            "/api/pools/{id}",
+            "GET",
+            "/api/pools/{address}",
+            "POST"
+        ));
+        assert!(!conflicts("/api/pools", "GET", "/api/swaps", "GET"));
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let templates = RouteTemplates::new(["/api/pools/{address}", "/api/fee-updates"]);
+
+        assert_eq!(
+            templates.canonicalize("/API/Pools/0xABCdef").as_deref(),
+            Some("/api/pools/0xABCdef")
+        );
+        assert_eq!(
+            templates.canonicalize("/api/Fee-Updates/").as_deref(),
+            Some("/api/fee-updates")
+        );
+        assert_eq!(templates.canonicalize("/swagger-ui/"), None);
+        assert_eq!(templates.canonicalize("/api/pools"), None);
+        assert_eq!(templates.canonicalize("/"), None);
+    }
+}
@@ -0,0 +1,390 @@
+use crate::ai::{EndpointIrResult, EndpointTestCase, EndpointTestFixture};
+use crate::endpoint_files;
+use crate::error::SmortyError;
+use crate::ir::Ir;
+use crate::server;
+use anyhow::{Context, Result};
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{PgPool, Row};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Directory holding golden endpoint test fixtures
+pub const TESTS_DIR: &str = "ir/endpoints/tests";
+
+/// Golden tests for generated endpoints
+///
+/// Each fixture holds sample parameters for an endpoint and the query plan
+/// recorded for them. `smorty test-endpoints` re-runs EXPLAIN for every case
+/// against the current endpoint IR, so a regenerated query that no longer
+/// accepts the parameters, fails to plan, or reads different tables is caught.
+pub struct EndpointTest;
+
+/// Outcome of running a single test case
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaseOutcome {
+    /// Query planned and touches the same relations as the recorded plan
+    Passed,
+    /// Query planned, but plan nodes differ from the recording
+    PlanChanged,
+    /// No plan has been recorded yet
+    Unrecorded,
+    /// Query failed to build or plan, or relations differ from the recording
+    Failed(String),
+}
+
+impl EndpointTest {
+    /// Connect to the database for recording plans, returning `None` if it is unreachable
+    pub async fn try_connect(database_uri: &str) -> Option<PgPool> {
+        match PgPoolOptions::new()
+            .max_connections(1)
+            .acquire_timeout(Duration::from_secs(5))
+            .connect(database_uri)
+            .await
+        {
+            Ok(pool) => Some(pool),
+            Err(e) => {
+                tracing::warn!(
+                    "Database unavailable, endpoint test plans will not be recorded: {}",
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Check whether a fixture already exists for an endpoint
+    pub fn fixture_exists(endpoint_path: &str, method: &str) -> bool {
+        Self::fixture_path(Path::new(TESTS_DIR), endpoint_path, method).exists()
+    }
+
+    /// Save a fixture in the ir/endpoints/tests/ directory
+    pub fn save_fixture(fixture: &EndpointTestFixture) -> Result<()> {
+        Self::save_fixture_to_dir(Path::new(TESTS_DIR), fixture)
+    }
+
+    /// Save a fixture to a specific directory (used for testing)
+    fn save_fixture_to_dir(base_dir: &Path, fixture: &EndpointTestFixture) -> Result<()> {
+        if !base_dir.exists() {
+            fs::create_dir_all(base_dir).context("Failed to create endpoint tests directory")?;
+        }
+
+        let fixture_file = Self::fixture_path(base_dir, &fixture.endpoint_path, &fixture.method);
+        let fixture_json =
+            serde_json::to_string_pretty(fixture).context("Failed to serialize test fixture")?;
+
+        fs::write(&fixture_file, fixture_json)
+            .context(format!("Failed to write test fixture: {:?}", fixture_file))?;
+
+        tracing::info!("  Saved endpoint test fixture to: {:?}", fixture_file);
+
+        Ok(())
+    }
+
+    /// Load all fixtures from a directory
+    fn load_all_fixtures_from_dir(base_dir: &Path) -> Result<Vec<EndpointTestFixture>> {
+        if !base_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut files: Vec<_> = fs::read_dir(base_dir)?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("json"))
+            .collect();
+        files.sort();
+
+        let mut fixtures = Vec::new();
+        for file in files {
+            let content = fs::read_to_string(&file)
+                .context(format!("Failed to read test fixture: {:?}", file))?;
+            let fixture: EndpointTestFixture = serde_json::from_str(&content)
+                .context(format!("Failed to parse test fixture: {:?}", file))?;
+            fixtures.push(fixture);
+        }
+
+        Ok(fixtures)
+    }
+
+    fn fixture_path(base_dir: &Path, endpoint_path: &str, method: &str) -> std::path::PathBuf {
+        endpoint_files::resolve(base_dir, endpoint_path, method)
+    }
+
+    /// Record the query plan for every case in a fixture
+    pub async fn record_plans(
+        pool: &PgPool,
+        endpoint_ir: &EndpointIrResult,
+        fixture: &mut EndpointTestFixture,
+    ) -> Result<()> {
+        for case in &mut fixture.cases {
+            case.query_plan = Self::explain(pool, endpoint_ir, case)
+                .await
+                .context(format!("EXPLAIN failed for test case '{}'", case.name))?;
+        }
+
+        Ok(())
+    }
+
+    /// Build the endpoint query for a test case and return its normalized plan
+    async fn explain(
+        pool: &PgPool,
+        endpoint_ir: &EndpointIrResult,
+        case: &EndpointTestCase,
+    ) -> Result<Vec<String>> {
+        let path_params: HashMap<String, String> = case
+            .path_params
+            .iter()
+            .map(|p| (p.name.clone(), p.value.clone()))
+            .collect();
+        let query_params: HashMap<String, String> = case
+            .query_params
+            .iter()
+            .map(|p| (p.name.clone(), p.value.clone()))
+            .collect();
+
+        let (sql, sql_params) = server::build_sql_query(endpoint_ir, &path_params, &query_params)
+            .map_err(|e| anyhow::anyhow!("Invalid parameters: {:?}", e))?;
+
+        // Staged queries are explained with the outputs their stages return
+        let mut conn = pool
+            .acquire()
+            .await
+            .context("Failed to acquire a connection")?;
+        let sql_params = server::run_stages(&mut conn, endpoint_ir, &sql_params, false)
+            .await
+            .map_err(|e| anyhow::anyhow!("Stages failed: {:?}", e))?;
+        let rows = server::bind_params(&format!("EXPLAIN {}", sql), &sql_params)
+            .fetch_all(&mut *conn)
+            .await
+            .context("EXPLAIN query failed")?;
+
+        let lines = rows
+            .iter()
+            .map(|row| row.try_get::<String, _>(0))
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to read EXPLAIN output")?;
+
+        Ok(Self::normalize_plan(&lines))
+    }
+
+    /// Reduce EXPLAIN text output to plan node names
+    ///
+    /// Costs, row estimates and detail lines (filters, sort keys) depend on the data
+    /// and are dropped so recorded plans stay stable.
+    pub fn normalize_plan(lines: &[String]) -> Vec<String> {
+        lines
+            .iter()
+            .filter_map(|line| {
+                let (node, _) = line.split_once("  (cost=")?;
+                Some(node.trim().trim_start_matches("->").trim().to_string())
+            })
+            .collect()
+    }
+
+    /// Extract the relations scanned by a normalized plan
+    pub fn plan_relations(plan: &[String]) -> BTreeSet<String> {
+        plan.iter()
+            .filter_map(|node| {
+                let (_, target) = node.split_once(" on ")?;
+                target.split_whitespace().next().map(|s| s.to_string())
+            })
+            .collect()
+    }
+
+    /// Compare a fresh plan with the recorded one
+    pub fn compare_plans(recorded: &[String], current: &[String]) -> CaseOutcome {
+        if recorded.is_empty() {
+            return CaseOutcome::Unrecorded;
+        }
+
+        let recorded_relations = Self::plan_relations(recorded);
+        let current_relations = Self::plan_relations(current);
+
+        if recorded_relations != current_relations {
+            return CaseOutcome::Failed(format!(
+                "query reads different tables: recorded {:?}, now {:?}",
+                recorded_relations, current_relations
+            ));
+        }
+
+        if recorded != current {
+            return CaseOutcome::PlanChanged;
+        }
+
+        CaseOutcome::Passed
+    }
+
+    /// Run all endpoint test fixtures against the database
+    ///
+    /// With `update`, the current plans are written back as the new golden plans.
+    pub async fn run_all(database_uri: &str, update: bool) -> Result<()> {
+        let fixtures = Self::load_all_fixtures_from_dir(Path::new(TESTS_DIR))?;
+
+        if fixtures.is_empty() {
+            tracing::warn!("No endpoint test fixtures found in {}", TESTS_DIR);
+            return Ok(());
+        }
+
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .connect(database_uri)
+            .await
+            .context("Failed to connect to database")?;
+
+        let mut failures = 0;
+        let mut total = 0;
+
+        for mut fixture in fixtures {
+            let endpoint_ir = match Ir::load_ir_endpoint(&fixture.endpoint_path, &fixture.method) {
+                Ok(ir) => ir,
+                Err(e) => {
+                    println!(
+                        "FAIL {} (endpoint IR missing: {})",
+                        fixture.endpoint_path, e
+                    );
+                    failures += fixture.cases.len();
+                    total += fixture.cases.len();
+                    continue;
+                }
+            };
+
+            for case in &mut fixture.cases {
+                total += 1;
+
+                let outcome = match Self::explain(&pool, &endpoint_ir, case).await {
+                    Ok(plan) => {
+                        let outcome = Self::compare_plans(&case.query_plan, &plan);
+                        if update {
+                            case.query_plan = plan;
+                        }
+                        outcome
+                    }
+                    Err(e) => CaseOutcome::Failed(format!("{:#}", e)),
+                };
+
+                let label = format!("{} [{}]", fixture.endpoint_path, case.name);
+                match outcome {
+                    CaseOutcome::Passed => println!("ok   {}", label),
+                    CaseOutcome::PlanChanged => println!("ok   {} (query plan changed)", label),
+                    CaseOutcome::Unrecorded => println!("ok   {} (no recorded plan)", label),
+                    CaseOutcome::Failed(reason) => {
+                        failures += 1;
+                        println!("FAIL {}: {}", label, reason);
+                    }
+                }
+            }
+
+            if update {
+                Self::save_fixture(&fixture)?;
+            }
+        }
+
+        println!("\n{} passed, {} failed", total - failures, failures);
+
+        if failures > 0 {
+            let error = anyhow::anyhow!("{} endpoint test case(s) failed", failures);
+            if failures < total {
+                return Err(SmortyError::Partial(error).into());
+            }
+            return Err(error);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::TestParam;
+    use tempfile::TempDir;
+
+    fn plan(nodes: &[&str]) -> Vec<String> {
+        nodes.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_normalize_plan() {
+        let lines = plan(&[
+            "Limit  (cost=0.29..8.31 rows=1 width=72)",
+            "  ->  Index Scan using idx_block on univ3_pool_swap  (cost=0.29..8.31 rows=1 width=72)",
+            "        Index Cond: (block_number > $1)",
+            "        Filter: (sender = $2)",
+        ]);
+
+        assert_eq!(
+            EndpointTest::normalize_plan(&lines),
+            plan(&["Limit", "Index Scan using idx_block on univ3_pool_swap"])
+        );
+    }
+
+    #[test]
+    fn test_plan_relations() {
+        let nodes = plan(&[
+            "Hash Join",
+            "Seq Scan on balv2_vault_swap s",
+            "Index Scan using idx_pool on balv2_vault_pool p",
+        ]);
+
+        let relations = EndpointTest::plan_relations(&nodes);
+        assert_eq!(relations.len(), 2);
+        assert!(relations.contains("balv2_vault_swap"));
+        assert!(relations.contains("balv2_vault_pool"));
+    }
+
+    #[test]
+    fn test_compare_plans() {
+        let recorded = plan(&["Limit", "Seq Scan on swaps"]);
+
+        assert_eq!(
+            EndpointTest::compare_plans(&recorded, &recorded.clone()),
+            CaseOutcome::Passed
+        );
+        assert_eq!(
+            EndpointTest::compare_plans(
+                &recorded,
+                &plan(&["Limit", "Index Scan using idx on swaps"])
+            ),
+            CaseOutcome::PlanChanged
+        );
+        assert!(matches!(
+            EndpointTest::compare_plans(&recorded, &plan(&["Limit", "Seq Scan on mints"])),
+            CaseOutcome::Failed(_)
+        ));
+        assert_eq!(
+            EndpointTest::compare_plans(&[], &recorded),
+            CaseOutcome::Unrecorded
+        );
+    }
+
+    #[test]
+    fn test_save_and_load_fixtures() {
+        let temp_dir = TempDir::new().unwrap();
+        let tests_dir = temp_dir.path().join("tests");
+
+        let fixture = EndpointTestFixture {
+            endpoint_path: "/api/pool/{pool}/swaps".to_string(),
+            method: "GET".to_string(),
+            cases: vec![EndpointTestCase {
+                name: "defaults".to_string(),
+                description: "Only required parameters".to_string(),
+                path_params: vec![TestParam {
+                    name: "pool".to_string(),
+                    value: "0x1234567890123456789012345678901234567890".to_string(),
+                }],
+                query_params: vec![],
+                query_plan: vec![],
+            }],
+        };
+
+        EndpointTest::save_fixture_to_dir(&tests_dir, &fixture).unwrap();
+        assert!(tests_dir.join("api_pool_+pool_swaps.json").exists());
+
+        let loaded = EndpointTest::load_all_fixtures_from_dir(&tests_dir).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].endpoint_path, fixture.endpoint_path);
+        assert_eq!(loaded[0].cases[0].path_params[0].name, "pool");
+    }
+}
@@ -0,0 +1,226 @@
+use crate::ai::EndpointIrResult;
+use crate::config::{Config, ResponseEnvelope};
+use serde_json::{Value as JsonValue, json};
+use std::collections::HashMap;
+use utoipa::openapi::{
+    ArrayBuilder, ObjectBuilder, RefOr, Schema, Type, schema::AdditionalProperties,
+};
+
+/// Media type of JSON:API documents
+pub const JSON_API_CONTENT_TYPE: &str = "application/vnd.api+json";
+
+/// Response envelope for each endpoint, resolved from the server default and
+/// per-endpoint overrides in the config
+#[derive(Debug, Clone, Default)]
+pub struct ResponseEnvelopes {
+    default: ResponseEnvelope,
+    overrides: HashMap<String, ResponseEnvelope>,
+}
+
+impl ResponseEnvelopes {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            default: config.server.response_envelope,
+            overrides: config
+                .endpoints
+                .iter()
+                .filter_map(|endpoint| {
+                    endpoint
+                        .response_envelope
+                        .map(|envelope| (endpoint.route(), envelope))
+                })
+                .collect(),
+        }
+    }
+
+    /// Envelope used by an endpoint
+    pub fn for_endpoint(&self, endpoint: &EndpointIrResult) -> ResponseEnvelope {
+        self.overrides
+            .get(&endpoint.route())
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+impl ResponseEnvelope {
+    /// Content type of the response body
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ResponseEnvelope::JsonApi => JSON_API_CONTENT_TYPE,
+            ResponseEnvelope::Data | ResponseEnvelope::Bare => "application/json",
+        }
+    }
+
+    /// Wrap the rows returned by an endpoint
+    pub fn wrap(&self, rows: Vec<JsonValue>, endpoint_ir: &EndpointIrResult) -> JsonValue {
+        match self {
+            ResponseEnvelope::Data => json!({ "count": rows.len(), "data": rows }),
+            ResponseEnvelope::Bare => JsonValue::Array(rows),
+            ResponseEnvelope::JsonApi => {
+                let count = rows.len();
+                let resources: Vec<JsonValue> = rows
+                    .into_iter()
+                    .enumerate()
+                    .map(|(position, row)| json_api_resource(position, row, endpoint_ir))
+                    .collect();
+                json!({ "data": resources, "meta": { "count": count } })
+            }
+        }
+    }
+
+    /// OpenAPI schema of a response, given the schema of a single row
+    pub fn wrap_schema(&self, row_schema: Schema) -> Schema {
+        match self {
+            ResponseEnvelope::Data => ObjectBuilder::new()
+                .property(
+                    "data",
+                    ArrayBuilder::new().items(RefOr::T(row_schema)).build(),
+                )
+                .property("count", count_schema())
+                .required("data")
+                .required("count")
+                .build()
+                .into(),
+            ResponseEnvelope::Bare => ArrayBuilder::new()
+                .items(RefOr::T(row_schema))
+                .build()
+                .into(),
+            ResponseEnvelope::JsonApi => {
+                let resource = ObjectBuilder::new()
+                    .property("type", ObjectBuilder::new().schema_type(Type::String))
+                    .property(
+                        "id",
+                        ObjectBuilder::new()
+                            .schema_type(Type::String)
+                            .description(Some(
+                                "The row's `id` field, or its position in the response",
+                            )),
+                    )
+                    .property("attributes", row_schema)
+                    .required("type")
+                    .required("id")
+                    .required("attributes");
+                ObjectBuilder::new()
+                    .property("data", ArrayBuilder::new().items(resource).build())
+                    .property(
+                        "meta",
+                        ObjectBuilder::new()
+                            .property("count", count_schema())
+                            .additional_properties(Some(AdditionalProperties::FreeForm(true))),
+                    )
+                    .required("data")
+                    .build()
+                    .into()
+            }
+        }
+    }
+}
+
+/// JSON:API resource object for a row
+///
+/// The row's `id` field becomes the resource id when present, otherwise its position.
+fn json_api_resource(
+    position: usize,
+    mut row: JsonValue,
+    endpoint_ir: &EndpointIrResult,
+) -> JsonValue {
+    let id = match row.as_object_mut().and_then(|obj| obj.remove("id")) {
+        Some(JsonValue::String(id)) => id,
+        Some(JsonValue::Null) | None => position.to_string(),
+        Some(other) => other.to_string(),
+    };
+    json!({
+        "type": endpoint_ir.response_schema.name,
+        "id": id,
+        "attributes": row,
+    })
+}
+
+fn count_schema() -> RefOr<Schema> {
+    ObjectBuilder::new()
+        .schema_type(Type::Integer)
+        .description(Some("Number of items returned"))
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{ResponseField, ResponseSchema};
+
+    fn create_endpoint_ir() -> EndpointIrResult {
+        EndpointIrResult {
+            endpoint_path: "/api/pools".to_string(),
+            description: "Pools".to_string(),
+            method: "GET".to_string(),
+            path_params: vec![],
+            query_params: vec![],
+            response_schema: ResponseSchema {
+                name: "Pool".to_string(),
+                fields: vec![ResponseField {
+                    name: "address".to_string(),
+                    field_type: "String".to_string(),
+                    description: "Pool address".to_string(),
+                    scale: None,
+                    format: None,
+                }],
+            },
+            sql_query: "SELECT address FROM pools".to_string(),
+            stages: Vec::new(),
+            tables_referenced: vec!["pools".to_string()],
+            tags: vec![],
+            scopes: vec![],
+            cache_ttl: None,
+        }
+    }
+
+    #[test]
+    fn test_wraps_rows_per_envelope() {
+        let endpoint_ir = create_endpoint_ir();
+        let rows = || {
+            vec![
+                json!({"address": "0xa"}),
+                json!({"id": 7, "address": "0xb"}),
+            ]
+        };
+
+        assert_eq!(
+            ResponseEnvelope::Data.wrap(rows(), &endpoint_ir),
+            json!({"data": rows(), "count": 2})
+        );
+        assert_eq!(
+            ResponseEnvelope::Bare.wrap(rows(), &endpoint_ir),
+            json!(rows())
+        );
+        assert_eq!(
+            ResponseEnvelope::JsonApi.wrap(rows(), &endpoint_ir),
+            json!({
+                "data": [
+                    {"type": "Pool", "id": "0", "attributes": {"address": "0xa"}},
+                    {"type": "Pool", "id": "7", "attributes": {"address": "0xb"}}
+                ],
+                "meta": {"count": 2}
+            })
+        );
+    }
+
+    #[test]
+    fn test_endpoint_overrides_server_default() {
+        let mut envelopes = ResponseEnvelopes {
+            default: ResponseEnvelope::Bare,
+            overrides: HashMap::new(),
+        };
+        envelopes
+            .overrides
+            .insert("GET /api/pools".to_string(), ResponseEnvelope::JsonApi);
+
+        let mut endpoint_ir = create_endpoint_ir();
+        assert_eq!(
+            envelopes.for_endpoint(&endpoint_ir),
+            ResponseEnvelope::JsonApi
+        );
+        // The override is for GET only
+        endpoint_ir.method = "POST".to_string();
+        assert_eq!(envelopes.for_endpoint(&endpoint_ir), ResponseEnvelope::Bare);
+    }
+}
@@ -0,0 +1,178 @@
+//! Error kinds for library consumers and the CLI
+//!
+//! Code inside the crate keeps using `anyhow` for context, and tags errors with
+//! a [`SmortyError`] kind where they originate. The kind survives any context
+//! added on the way up and can be recovered with [`SmortyError::find`].
+
+use anyhow::Context;
+use serde::Serialize;
+use std::path::Path;
+use thiserror::Error;
+
+/// Result whose error carries a [`SmortyError`] kind
+pub type Result<T, E = SmortyError> = std::result::Result<T, E>;
+
+/// What went wrong, wrapping the underlying error and its context
+#[derive(Debug, Error)]
+pub enum SmortyError {
+    /// Invalid or unreadable config, or config that doesn't match the chain it points at
+    #[error(transparent)]
+    Config(anyhow::Error),
+    /// Failed call to an RPC endpoint
+    #[error(transparent)]
+    Rpc(anyhow::Error),
+    /// Event or row that couldn't be decoded
+    #[error(transparent)]
+    Decode(anyhow::Error),
+    /// Failed to generate or apply migrations
+    #[error(transparent)]
+    Migration(anyhow::Error),
+    /// Failed call to the AI provider
+    #[error(transparent)]
+    Ai(anyhow::Error),
+    /// Failure in the API server
+    #[error(transparent)]
+    Api(anyhow::Error),
+    /// Some items succeeded and others failed
+    #[error(transparent)]
+    Partial(anyhow::Error),
+}
+
+/// Process exit codes of the CLI, so CI can tell failures apart
+///
+/// 2 is left out since clap exits with it on usage errors.
+pub mod exit_code {
+    pub const SUCCESS: u8 = 0;
+    /// Failure without a more specific kind
+    pub const FAILURE: u8 = 1;
+    pub const CONFIG: u8 = 3;
+    pub const AI: u8 = 4;
+    pub const RPC: u8 = 5;
+    pub const MIGRATION: u8 = 6;
+    pub const PARTIAL: u8 = 7;
+    pub const DECODE: u8 = 8;
+    pub const API: u8 = 9;
+}
+
+impl SmortyError {
+    /// Kind of an error, from the outermost tag in its chain
+    pub fn find(error: &anyhow::Error) -> Option<&SmortyError> {
+        error.chain().find_map(|cause| cause.downcast_ref())
+    }
+
+    /// Short name of the kind, for logs and error reports
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SmortyError::Config(_) => "config",
+            SmortyError::Rpc(_) => "rpc",
+            SmortyError::Decode(_) => "decode",
+            SmortyError::Migration(_) => "migration",
+            SmortyError::Ai(_) => "ai",
+            SmortyError::Api(_) => "api",
+            SmortyError::Partial(_) => "partial",
+        }
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            SmortyError::Config(_) => exit_code::CONFIG,
+            SmortyError::Rpc(_) => exit_code::RPC,
+            SmortyError::Decode(_) => exit_code::DECODE,
+            SmortyError::Migration(_) => exit_code::MIGRATION,
+            SmortyError::Ai(_) => exit_code::AI,
+            SmortyError::Api(_) => exit_code::API,
+            SmortyError::Partial(_) => exit_code::PARTIAL,
+        }
+    }
+}
+
+/// Machine-readable description of a failed command, written by `--error-report`
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    /// Kind of the error, or null if it has none
+    pub kind: Option<&'static str>,
+    pub exit_code: u8,
+    pub message: String,
+    /// Underlying errors, outermost first
+    pub causes: Vec<String>,
+}
+
+impl ErrorReport {
+    pub fn new(error: &anyhow::Error) -> Self {
+        let kind = SmortyError::find(error);
+        Self {
+            kind: kind.map(SmortyError::kind),
+            exit_code: kind.map_or(exit_code::FAILURE, SmortyError::exit_code),
+            message: error.to_string(),
+            causes: error.chain().skip(1).map(ToString::to_string).collect(),
+        }
+    }
+
+    /// Write the report as JSON
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let json =
+            serde_json::to_string_pretty(self).context("Failed to serialize error report")?;
+        std::fs::write(path, json).context(format!(
+            "Failed to write error report to {}",
+            path.display()
+        ))
+    }
+}
+
+/// Tag the error of a result with a kind, e.g. `.kind(SmortyError::Rpc)`
+pub trait ResultExt<T> {
+    fn kind(self, kind: fn(anyhow::Error) -> SmortyError) -> Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> ResultExt<T> for std::result::Result<T, E> {
+    fn kind(self, kind: fn(anyhow::Error) -> SmortyError) -> Result<T> {
+        self.map_err(|e| kind(e.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_kind_survives_context() {
+        let result: anyhow::Result<()> = Err(anyhow::anyhow!("connection refused"))
+            .context("Failed to fetch logs")
+            .kind(SmortyError::Rpc)
+            .context("Failed to index chain 'base'");
+        let error = result.unwrap_err();
+
+        let kind = SmortyError::find(&error).unwrap();
+        assert!(matches!(kind, SmortyError::Rpc(_)));
+        assert_eq!(kind.kind(), "rpc");
+        // Tagging doesn't change the message or its causes
+        assert_eq!(
+            format!("{:#}", error),
+            "Failed to index chain 'base': Failed to fetch logs: connection refused"
+        );
+
+        assert!(SmortyError::find(&anyhow::anyhow!("untagged")).is_none());
+    }
+
+    #[test]
+    fn test_error_report() {
+        let error = anyhow::Error::from(SmortyError::Config(anyhow::anyhow!(
+            "indexer.chunkSize must be greater than 0"
+        )))
+        .context("Failed to load config from: config.toml");
+
+        let report = ErrorReport::new(&error);
+        assert_eq!(report.kind, Some("config"));
+        assert_eq!(report.exit_code, exit_code::CONFIG);
+        assert_eq!(report.message, "Failed to load config from: config.toml");
+        assert_eq!(
+            report.causes,
+            vec!["indexer.chunkSize must be greater than 0"]
+        );
+
+        let report = ErrorReport::new(&anyhow::anyhow!("disk full"));
+        assert_eq!(report.kind, None);
+        assert_eq!(report.exit_code, exit_code::FAILURE);
+    }
+}
@@ -0,0 +1,461 @@
+use crate::ai::EndpointIrResult;
+use crate::config::{ExportConfig, ExportStorage};
+use crate::redaction::ResponseRedactions;
+use crate::server::{SqlParam, bind_params, decode_field};
+use anyhow::{Context, Result};
+use arrow_array::{ArrayRef, BooleanArray, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use axum::body::Bytes;
+use axum::http::Method;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use object_store::ObjectStore;
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::buffered::BufWriter;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path as ObjectPath;
+use object_store::signer::Signer;
+use parquet::arrow::AsyncArrowWriter;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Rows buffered before a record batch is written
+const EXPORT_BATCH_ROWS: usize = 10_000;
+
+/// How long finished jobs can be looked up
+const JOB_RETENTION: chrono::Duration = chrono::Duration::hours(24);
+
+/// Background export jobs writing endpoint results to Parquet files
+///
+/// Shared across router reloads so jobs can still be looked up afterwards.
+#[derive(Clone)]
+pub struct ExportJobs {
+    store: Arc<dyn ObjectStore>,
+    /// Set for S3 storage, where downloads go through presigned URLs
+    signer: Option<Arc<AmazonS3>>,
+    prefix: String,
+    url_expiry: Duration,
+    jobs: Arc<RwLock<HashMap<String, ExportJob>>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportJob {
+    pub id: String,
+    pub endpoint: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub status: ExportStatus,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum ExportStatus {
+    Running,
+    Completed {
+        rows: usize,
+        finished_at: DateTime<Utc>,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+impl ExportJobs {
+    pub fn new(config: &ExportConfig) -> Result<Self> {
+        let (store, signer): (Arc<dyn ObjectStore>, _) = match config.storage {
+            ExportStorage::Local => {
+                std::fs::create_dir_all(&config.directory).context(format!(
+                    "Failed to create export directory {}",
+                    config.directory
+                ))?;
+                let store = LocalFileSystem::new_with_prefix(&config.directory)
+                    .context("Failed to open export directory")?;
+                (Arc::new(store), None)
+            }
+            ExportStorage::S3 => {
+                let bucket = config
+                    .bucket
+                    .as_deref()
+                    .context("server.exports.bucket is required for s3 storage")?;
+                let mut builder = AmazonS3Builder::from_env().with_bucket_name(bucket);
+                if let Some(region) = &config.region {
+                    builder = builder.with_region(region);
+                }
+                let store = Arc::new(builder.build().context("Failed to configure S3")?);
+                (Arc::clone(&store) as Arc<dyn ObjectStore>, Some(store))
+            }
+        };
+
+        Ok(Self {
+            store,
+            signer,
+            prefix: config.prefix.trim_matches('/').to_string(),
+            url_expiry: Duration::from_secs(config.url_expiry_secs),
+            jobs: Arc::default(),
+        })
+    }
+
+    /// Start exporting the results of an endpoint query, returning the job
+    pub fn spawn(
+        &self,
+        pool: PgPool,
+        endpoint_ir: EndpointIrResult,
+        sql: String,
+        params: Vec<SqlParam>,
+        strict: bool,
+        redactions: Arc<ResponseRedactions>,
+    ) -> ExportJob {
+        let job = ExportJob {
+            id: hex::encode(rand::random::<[u8; 16]>()),
+            endpoint: endpoint_ir.endpoint_path.clone(),
+            created_at: Utc::now(),
+            status: ExportStatus::Running,
+        };
+
+        if let Ok(mut jobs) = self.jobs.write() {
+            let cutoff = Utc::now() - JOB_RETENTION;
+            jobs.retain(|_, job| job.created_at > cutoff);
+            jobs.insert(job.id.clone(), job.clone());
+        }
+
+        let exports = self.clone();
+        let id = job.id.clone();
+        tokio::spawn(async move {
+            let result = async {
+                let sink = ParquetSink::create(
+                    Arc::clone(&exports.store),
+                    exports.object_path(&id),
+                    &endpoint_ir,
+                )?;
+                export_query(
+                    &pool,
+                    sink,
+                    &endpoint_ir,
+                    &sql,
+                    &params,
+                    strict,
+                    &redactions,
+                )
+                .await
+            }
+            .await;
+
+            let status = match result {
+                Ok(rows) => {
+                    tracing::info!("Export {} finished with {} rows", id, rows);
+                    ExportStatus::Completed {
+                        rows,
+                        finished_at: Utc::now(),
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Export {} failed: {:#}", id, e);
+                    ExportStatus::Failed {
+                        error: format!("{:#}", e),
+                    }
+                }
+            };
+            if let Ok(mut jobs) = exports.jobs.write()
+                && let Some(job) = jobs.get_mut(&id)
+            {
+                job.status = status;
+            }
+        });
+
+        job
+    }
+
+    pub fn get(&self, id: &str) -> Option<ExportJob> {
+        self.jobs.read().ok()?.get(id).cloned()
+    }
+
+    /// Presigned download URL, `None` when files are served by the API itself
+    pub async fn signed_url(&self, id: &str) -> Result<Option<String>> {
+        let Some(signer) = &self.signer else {
+            return Ok(None);
+        };
+        let url = signer
+            .signed_url(Method::GET, &self.object_path(id), self.url_expiry)
+            .await
+            .context("Failed to sign download URL")?;
+        Ok(Some(url.to_string()))
+    }
+
+    /// Stream of the export file contents
+    pub async fn read(
+        &self,
+        id: &str,
+    ) -> Result<impl futures::Stream<Item = object_store::Result<Bytes>> + use<>> {
+        let file = self
+            .store
+            .get(&self.object_path(id))
+            .await
+            .context("Failed to read export file")?;
+        Ok(file.into_stream())
+    }
+
+    fn object_path(&self, id: &str) -> ObjectPath {
+        if self.prefix.is_empty() {
+            ObjectPath::from(format!("{}.parquet", id))
+        } else {
+            ObjectPath::from(format!("{}/{}.parquet", self.prefix, id))
+        }
+    }
+}
+
+/// Stream the query results into a Parquet file, returning the number of rows
+async fn export_query(
+    pool: &PgPool,
+    mut sink: ParquetSink,
+    endpoint_ir: &EndpointIrResult,
+    sql: &str,
+    params: &[SqlParam],
+    strict: bool,
+    redactions: &ResponseRedactions,
+) -> Result<usize> {
+    let mut rows = bind_params(sql, params).fetch(pool);
+
+    while let Some(row) = rows.try_next().await.context("Export query failed")? {
+        let mut values = Vec::with_capacity(endpoint_ir.response_schema.fields.len());
+        for field in &endpoint_ir.response_schema.fields {
+            let value = match decode_field(&row, &field.name, &field.field_type) {
+                Ok(value) => value,
+                Err(e) if strict => anyhow::bail!(
+                    "Failed to decode column '{}' as {}: {}",
+                    field.name,
+                    field.field_type,
+                    e
+                ),
+                Err(_) => JsonValue::Null,
+            };
+            values.push(value);
+        }
+        redactions.redact_values(endpoint_ir, &mut values);
+        sink.push(values).await?;
+    }
+
+    sink.finish().await
+}
+
+/// Parquet file writer taking rows of response field values
+struct ParquetSink {
+    writer: AsyncArrowWriter<BufWriter>,
+    schema: SchemaRef,
+    pending: Vec<Vec<JsonValue>>,
+    rows: usize,
+}
+
+impl ParquetSink {
+    fn create(
+        store: Arc<dyn ObjectStore>,
+        path: ObjectPath,
+        endpoint_ir: &EndpointIrResult,
+    ) -> Result<Self> {
+        let schema = Arc::new(arrow_schema(endpoint_ir));
+        let writer =
+            AsyncArrowWriter::try_new(BufWriter::new(store, path), Arc::clone(&schema), None)?;
+
+        Ok(Self {
+            writer,
+            schema,
+            pending: Vec::with_capacity(EXPORT_BATCH_ROWS),
+            rows: 0,
+        })
+    }
+
+    async fn push(&mut self, values: Vec<JsonValue>) -> Result<()> {
+        self.pending.push(values);
+        if self.pending.len() >= EXPORT_BATCH_ROWS {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let batch = record_batch(&self.schema, &self.pending)?;
+        self.writer.write(&batch).await?;
+        self.rows += self.pending.len();
+        self.pending.clear();
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Result<usize> {
+        self.flush().await?;
+        self.writer
+            .close()
+            .await
+            .context("Failed to write export file")?;
+        Ok(self.rows)
+    }
+}
+
+/// Arrow schema for the response fields of an endpoint
+fn arrow_schema(endpoint_ir: &EndpointIrResult) -> Schema {
+    let fields: Vec<Field> = endpoint_ir
+        .response_schema
+        .fields
+        .iter()
+        .map(|field| Field::new(&field.name, arrow_type(&field.field_type), true))
+        .collect();
+    Schema::new(fields)
+}
+
+/// Arrow type of a response field type. JSON values and anything else are written as strings
+fn arrow_type(field_type: &str) -> DataType {
+    let base_type = field_type
+        .strip_prefix("Option<")
+        .and_then(|s| s.strip_suffix('>'))
+        .unwrap_or(field_type);
+    match base_type {
+        "i64" | "i32" | "u32" | "u64" => DataType::Int64,
+        "bool" => DataType::Boolean,
+        _ => DataType::Utf8,
+    }
+}
+
+fn record_batch(schema: &SchemaRef, rows: &[Vec<JsonValue>]) -> Result<RecordBatch> {
+    let columns: Vec<ArrayRef> = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(i, field)| -> ArrayRef {
+            let values = rows.iter().map(|row| &row[i]);
+            match field.data_type() {
+                DataType::Int64 => Arc::new(values.map(|v| v.as_i64()).collect::<Int64Array>()),
+                DataType::Boolean => {
+                    Arc::new(values.map(|v| v.as_bool()).collect::<BooleanArray>())
+                }
+                _ => Arc::new(
+                    values
+                        .map(|v| match v {
+                            JsonValue::Null => None,
+                            JsonValue::String(s) => Some(s.clone()),
+                            other => Some(other.to_string()),
+                        })
+                        .collect::<StringArray>(),
+                ),
+            }
+        })
+        .collect();
+
+    RecordBatch::try_new(Arc::clone(schema), columns).context("Failed to build record batch")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{ResponseField, ResponseSchema};
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::json;
+
+    fn create_endpoint_ir() -> EndpointIrResult {
+        let field = |name: &str, field_type: &str| ResponseField {
+            name: name.to_string(),
+            field_type: field_type.to_string(),
+            description: String::new(),
+            scale: None,
+            format: None,
+        };
+        EndpointIrResult {
+            endpoint_path: "/api/swaps/export".to_string(),
+            description: "All swaps".to_string(),
+            method: "GET".to_string(),
+            path_params: vec![],
+            query_params: vec![],
+            response_schema: ResponseSchema {
+                name: "Swap".to_string(),
+                fields: vec![
+                    field("block_number", "i64"),
+                    field("sender", "String"),
+                    field("amounts", "serde_json::Value"),
+                    field("exact_in", "Option<bool>"),
+                ],
+            },
+            sql_query: "SELECT block_number, sender, amounts, exact_in FROM swaps".to_string(),
+            stages: Vec::new(),
+            tables_referenced: vec!["swaps".to_string()],
+            tags: vec![],
+            scopes: vec![],
+            cache_ttl: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_writes_rows_to_parquet() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn ObjectStore> =
+            Arc::new(LocalFileSystem::new_with_prefix(dir.path()).unwrap());
+
+        let mut sink = ParquetSink::create(
+            store,
+            ObjectPath::from("job.parquet"),
+            &create_endpoint_ir(),
+        )
+        .unwrap();
+        sink.push(vec![
+            json!(100),
+            json!("0xa"),
+            json!(["1", "2"]),
+            json!(true),
+        ])
+        .await
+        .unwrap();
+        sink.push(vec![
+            json!(101),
+            json!("0xb"),
+            JsonValue::Null,
+            JsonValue::Null,
+        ])
+        .await
+        .unwrap();
+        assert_eq!(sink.finish().await.unwrap(), 2);
+
+        let file = std::fs::File::open(dir.path().join("job.parquet")).unwrap();
+        let batch = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(batch.num_rows(), 2);
+        let blocks = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(blocks.value(1), 101);
+        let amounts = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(amounts.value(0), "[\"1\",\"2\"]");
+        assert!(amounts.is_null(1));
+        assert_eq!(batch.schema().field(3).data_type(), &DataType::Boolean);
+    }
+
+    #[test]
+    fn test_job_status_serialization() {
+        let job = ExportJob {
+            id: "abc".to_string(),
+            endpoint: "/api/swaps/export".to_string(),
+            created_at: Utc::now(),
+            status: ExportStatus::Failed {
+                error: "boom".to_string(),
+            },
+        };
+        let value = serde_json::to_value(&job).unwrap();
+        assert_eq!(value["status"], "failed");
+        assert_eq!(value["error"], "boom");
+    }
+}
@@ -0,0 +1,183 @@
+//! Mapping of event fields to the table columns they are stored in
+//!
+//! Each field of a spec IR names its column, e.g.
+//! `{"name": "swapFeePercentage", ..., "column": "swap_fee_percentage"}`, so rows
+//! are inserted by name whatever order the columns are in. IRs without the
+//! mapping get it from the column names when they are loaded: the column with
+//! the field's name, or the one matching it ignoring case and underscores.
+
+use crate::ai::IrGenerationResult;
+use crate::ir::SOURCE_COLUMN;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+
+/// Columns every event table has, filled from the log rather than its fields
+pub const STANDARD_COLUMNS: &[&str] = &[
+    "id",
+    "block_number",
+    "block_timestamp",
+    "transaction_hash",
+    "log_index",
+];
+
+/// Set the column of fields that have none, and check that every field and
+/// every event column are mapped exactly once
+pub fn map_columns(ir: &mut IrGenerationResult) -> Result<()> {
+    let event_columns = event_columns(ir);
+
+    let mut columns = Vec::with_capacity(ir.indexed_fields.len());
+    for field in &ir.indexed_fields {
+        let column = match &field.column {
+            Some(column) => column.as_str(),
+            None => match_column(&field.name, &event_columns).context(format!(
+                "Field '{}' has no column, and no column of table '{}' matches its name. Set its \"column\" in the IR",
+                field.name, ir.table_schema.table_name
+            ))?,
+        };
+        columns.push(column);
+    }
+
+    let mut mapped: HashMap<&str, &str> = HashMap::new();
+    for (field, &column) in ir.indexed_fields.iter().zip(&columns) {
+        if !event_columns.contains(&column) {
+            anyhow::bail!(
+                "Field '{}' is mapped to '{}', which is not an event column of table '{}'",
+                field.name,
+                column,
+                ir.table_schema.table_name
+            );
+        }
+        if let Some(other) = mapped.insert(column, &field.name) {
+            anyhow::bail!(
+                "Fields '{}' and '{}' are both mapped to column '{}'",
+                other,
+                field.name,
+                column
+            );
+        }
+    }
+
+    let unmapped: Vec<&str> = event_columns
+        .iter()
+        .copied()
+        .filter(|column| !mapped.contains_key(column))
+        .collect();
+    if !unmapped.is_empty() {
+        anyhow::bail!(
+            "Columns {} of table '{}' are not mapped to any event field",
+            unmapped.join(", "),
+            ir.table_schema.table_name
+        );
+    }
+
+    let columns: Vec<String> = columns.into_iter().map(str::to_string).collect();
+    for (field, column) in ir.indexed_fields.iter_mut().zip(columns) {
+        field.column = Some(column);
+    }
+    Ok(())
+}
+
+/// Columns of an IR's table that hold event fields
+fn event_columns(ir: &IrGenerationResult) -> Vec<&str> {
+    ir.table_schema
+        .columns
+        .iter()
+        .map(|column| column.name.as_str())
+        .filter(|name| !STANDARD_COLUMNS.contains(name) && *name != SOURCE_COLUMN)
+        .filter(|name| {
+            !ir.table_schema
+                .computed_columns
+                .iter()
+                .any(|computed| computed.name == *name)
+        })
+        .collect()
+}
+
+/// Column named like a field: the same name, or the only one equal ignoring
+/// case and underscores
+fn match_column<'a>(field: &str, columns: &[&'a str]) -> Option<&'a str> {
+    if let Some(column) = columns.iter().find(|column| **column == field) {
+        return Some(column);
+    }
+    let normalize = |name: &str| name.replace('_', "").to_lowercase();
+    let field = normalize(field);
+    let mut matches = columns.iter().filter(|column| normalize(column) == field);
+    match (matches.next(), matches.next()) {
+        (Some(column), None) => Some(column),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{ColumnDef, EventField, TableSchema};
+
+    fn ir(fields: &[(&str, Option<&str>)], columns: &[&str]) -> IrGenerationResult {
+        IrGenerationResult {
+            event_name: "Swap".to_string(),
+            event_signature: "Swap(address,uint256)".to_string(),
+            start_block: 0,
+            contract_address: String::new(),
+            chain: "mainnet".to_string(),
+            indexed_fields: fields
+                .iter()
+                .map(|(name, column)| EventField {
+                    name: name.to_string(),
+                    solidity_type: "uint256".to_string(),
+                    rust_type: "String".to_string(),
+                    indexed: false,
+                    column: column.map(str::to_string),
+                })
+                .collect(),
+            table_schema: TableSchema {
+                table_name: "swaps".to_string(),
+                columns: ["id", "block_number", "log_index"]
+                    .iter()
+                    .chain(columns)
+                    .map(|name| ColumnDef {
+                        name: name.to_string(),
+                        column_type: "TEXT".to_string(),
+                    })
+                    .collect(),
+                indexes: vec![],
+                computed_columns: vec![],
+            },
+            description: String::new(),
+            abi_hash: None,
+            state_fold: None,
+        }
+    }
+
+    #[test]
+    fn test_maps_fields_to_columns_by_name() {
+        // Columns in a different order than the fields
+        let mut swap = ir(
+            &[
+                ("amountIn", None),
+                ("sender", None),
+                ("fee", Some("fee_bps")),
+            ],
+            &["sender", "fee_bps", "amount_in"],
+        );
+        map_columns(&mut swap).unwrap();
+        let columns: Vec<_> = swap
+            .indexed_fields
+            .iter()
+            .map(|field| field.column.as_deref().unwrap())
+            .collect();
+        assert_eq!(columns, ["amount_in", "sender", "fee_bps"]);
+
+        let error = map_columns(&mut ir(&[("amount", None)], &["amount_in"])).unwrap_err();
+        assert!(error.to_string().contains("no column"));
+        let error =
+            map_columns(&mut ir(&[("sender", None)], &["sender", "recipient"])).unwrap_err();
+        assert!(error.to_string().contains("recipient"));
+        let error = map_columns(&mut ir(
+            &[("a", Some("sender")), ("b", Some("sender"))],
+            &["sender"],
+        ))
+        .unwrap_err();
+        assert!(error.to_string().contains("both mapped"));
+    }
+}
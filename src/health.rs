@@ -0,0 +1,260 @@
+//! Stuck-spec alerts for the indexer daemon
+//!
+//! The daemon retries failed passes forever, so a spec whose cursor stops
+//! moving would otherwise only show up as repeated log lines. With
+//! `indexer.stuckPolls` set, a spec whose last indexed block hasn't advanced
+//! for that many polls while the chain head moved raises an alert: an error is
+//! logged, the spec is recorded in the alerts table shown by `smorty status`,
+//! and the alert is posted to `indexer.alertWebhook` if one is configured.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Specs currently stuck, cleared when they advance again
+pub const SPEC_ALERTS_TABLE: &str = "smorty_spec_alerts";
+
+/// How long a webhook may take to accept an alert
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A spec that stopped advancing while its chain moved on
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StuckAlert {
+    pub chain: String,
+    /// Spec as `<contract>/<spec>`
+    pub spec: String,
+    /// Last indexed block, if any
+    pub cursor: Option<u64>,
+    pub head: u64,
+    /// Polls the cursor stayed at `cursor` while the head advanced
+    pub polls: u32,
+    pub last_error: Option<String>,
+}
+
+/// Change in a spec's health after a poll
+#[derive(Debug, Clone, PartialEq)]
+pub enum HealthEvent {
+    Stuck(StuckAlert),
+    Recovered { chain: String, spec: String },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SpecProgress {
+    cursor: Option<u64>,
+    head: u64,
+    polls: u32,
+    alerted: bool,
+}
+
+/// Cursors and chain heads seen by one chain's daemon loop
+#[derive(Debug)]
+pub struct StuckSpecs {
+    threshold: u32,
+    specs: HashMap<String, SpecProgress>,
+}
+
+impl StuckSpecs {
+    /// Alert once a cursor stays put for `threshold` polls
+    pub fn new(threshold: u32) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            specs: HashMap::new(),
+        }
+    }
+
+    /// Record a spec's cursor and the chain head after a poll
+    ///
+    /// A spec is reported as stuck once, and as recovered when its cursor moves again.
+    pub fn observe(
+        &mut self,
+        chain: &str,
+        spec: &str,
+        cursor: Option<u64>,
+        head: u64,
+        last_error: Option<&str>,
+    ) -> Option<HealthEvent> {
+        let Some(progress) = self.specs.get_mut(spec) else {
+            self.specs.insert(
+                spec.to_string(),
+                SpecProgress {
+                    cursor,
+                    head,
+                    polls: 0,
+                    alerted: false,
+                },
+            );
+            return None;
+        };
+
+        if progress.cursor != cursor {
+            let recovered = progress.alerted;
+            *progress = SpecProgress {
+                cursor,
+                head,
+                polls: 0,
+                alerted: false,
+            };
+            return recovered.then(|| HealthEvent::Recovered {
+                chain: chain.to_string(),
+                spec: spec.to_string(),
+            });
+        }
+
+        // An idle chain isn't a stuck spec
+        if head <= progress.head {
+            return None;
+        }
+        progress.head = head;
+        progress.polls += 1;
+        if progress.polls < self.threshold || progress.alerted {
+            return None;
+        }
+        progress.alerted = true;
+        Some(HealthEvent::Stuck(StuckAlert {
+            chain: chain.to_string(),
+            spec: spec.to_string(),
+            cursor,
+            head,
+            polls: progress.polls,
+            last_error: last_error.map(str::to_string),
+        }))
+    }
+}
+
+/// Log an alert, record it in the alerts table and post it to the webhook
+pub async fn raise(pool: &PgPool, webhook: Option<&str>, alert: &StuckAlert) {
+    tracing::error!(
+        "Spec {} on chain '{}' is stuck at block {} while the head moved to {} over {} polls{}",
+        alert.spec,
+        alert.chain,
+        alert
+            .cursor
+            .map_or_else(|| "none".to_string(), |cursor| cursor.to_string()),
+        alert.head,
+        alert.polls,
+        alert
+            .last_error
+            .as_deref()
+            .map(|e| format!(", last error: {}", e))
+            .unwrap_or_default()
+    );
+
+    if let Err(e) = record(pool, alert).await {
+        tracing::warn!("{:#}", e);
+    }
+    if let Some(url) = webhook
+        && let Err(e) = post(
+            url,
+            &serde_json::json!({"event": "spec_stuck", "alert": alert}),
+        )
+        .await
+    {
+        tracing::warn!("Failed to send stuck-spec alert to webhook: {:#}", e);
+    }
+}
+
+/// Remove a spec from the alerts table once it advances
+pub async fn clear(pool: &PgPool, spec: &str) -> Result<()> {
+    sqlx::query(&format!(
+        "DELETE FROM {} WHERE spec = $1",
+        SPEC_ALERTS_TABLE
+    ))
+    .bind(spec)
+    .execute(pool)
+    .await
+    .context("Failed to clear stuck-spec alert")?;
+    Ok(())
+}
+
+/// Stuck specs and their alerts, keyed by `<contract>/<spec>`
+pub async fn load(pool: &PgPool) -> Result<HashMap<String, StuckAlert>> {
+    let rows = sqlx::query(&format!(
+        "SELECT chain, spec, cursor, head, polls, last_error FROM {}",
+        SPEC_ALERTS_TABLE
+    ))
+    .fetch_all(pool)
+    .await
+    .context("Failed to load stuck-spec alerts")?;
+
+    let mut alerts = HashMap::new();
+    for row in rows {
+        let alert = StuckAlert {
+            chain: row.try_get("chain")?,
+            spec: row.try_get("spec")?,
+            cursor: row
+                .try_get::<Option<i64>, _>("cursor")?
+                .map(|cursor| cursor as u64),
+            head: row.try_get::<i64, _>("head")? as u64,
+            polls: row.try_get::<i32, _>("polls")? as u32,
+            last_error: row.try_get("last_error")?,
+        };
+        alerts.insert(alert.spec.clone(), alert);
+    }
+    Ok(alerts)
+}
+
+async fn record(pool: &PgPool, alert: &StuckAlert) -> Result<()> {
+    sqlx::query(&format!(
+        "INSERT INTO {} (spec, chain, cursor, head, polls, last_error, raised_at)
+         VALUES ($1, $2, $3, $4, $5, $6, NOW())
+         ON CONFLICT (spec) DO UPDATE SET chain = $2, cursor = $3, head = $4, polls = $5,
+             last_error = $6, raised_at = NOW()",
+        SPEC_ALERTS_TABLE
+    ))
+    .bind(&alert.spec)
+    .bind(&alert.chain)
+    .bind(alert.cursor.map(|cursor| cursor as i64))
+    .bind(alert.head as i64)
+    .bind(alert.polls as i32)
+    .bind(&alert.last_error)
+    .execute(pool)
+    .await
+    .context("Failed to record stuck-spec alert")?;
+    Ok(())
+}
+
+/// Post an alert event to a webhook as JSON
+pub(crate) async fn post(url: &str, event: &serde_json::Value) -> Result<()> {
+    reqwest::Client::new()
+        .post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .json(event)
+        .send()
+        .await
+        .context("Request failed")?
+        .error_for_status()
+        .context("Webhook rejected the alert")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alerts_once_when_cursor_stalls_while_head_moves() {
+        let mut stuck = StuckSpecs::new(2);
+        let mut observe =
+            |cursor, head| stuck.observe("sonic", "Pool/Swap", cursor, head, Some("boom"));
+
+        assert_eq!(observe(Some(100), 100), None);
+        // The chain is idle, so the spec isn't stuck
+        assert_eq!(observe(Some(100), 100), None);
+        assert_eq!(observe(Some(100), 101), None);
+        let Some(HealthEvent::Stuck(alert)) = observe(Some(100), 102) else {
+            panic!("expected an alert");
+        };
+        assert_eq!(alert.polls, 2);
+        assert_eq!(alert.last_error.as_deref(), Some("boom"));
+        assert_eq!(observe(Some(100), 103), None);
+
+        assert!(matches!(
+            observe(Some(103), 103),
+            Some(HealthEvent::Recovered { .. })
+        ));
+        assert_eq!(observe(Some(103), 104), None);
+    }
+}
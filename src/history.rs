@@ -0,0 +1,114 @@
+//! Detecting RPCs that no longer serve the blocks a spec starts at
+//!
+//! Full nodes and nodes with history expiry drop old blocks, and `eth_getLogs`
+//! over those blocks returns no logs instead of an error, leaving an empty
+//! table. Before indexing, the indexer checks the earliest block the specs of
+//! each chain resume at and, when the RPC doesn't serve it, searches for the
+//! earliest block it does. `indexer.prunedHistory` decides whether that fails the run (`"fail"`,
+//! the default) or starts the affected specs there (`"clamp"`).
+
+use alloy::eips::BlockNumberOrTag;
+use alloy::providers::Provider;
+use anyhow::{Context, Result};
+use serde_json::Value as JsonValue;
+
+/// Whether the RPC serves block `block`
+///
+/// Pruned blocks come back as `null` or as a JSON-RPC error ("pruned history
+/// unavailable", "missing trie node"), transport failures are returned as errors.
+pub async fn serves_block<P: Provider>(provider: &P, block: u64) -> Result<bool> {
+    let result: Result<JsonValue, _> = provider
+        .raw_request(
+            "eth_getBlockByNumber".into(),
+            (BlockNumberOrTag::Number(block), false),
+        )
+        .await;
+    match result {
+        Ok(header) => Ok(!header.is_null()),
+        Err(e) if e.as_error_resp().is_some() => {
+            tracing::debug!("RPC doesn't serve block {}: {}", block, e);
+            Ok(false)
+        }
+        Err(e) => Err(e).context(format!("Failed to fetch block {}", block)),
+    }
+}
+
+/// Earliest block from `from` up to `head` the RPC serves, by binary search
+///
+/// Takes a single request when the RPC serves `from`, and `None` when it doesn't
+/// even serve the head.
+pub async fn earliest_block<P: Provider>(
+    provider: &P,
+    from: u64,
+    head: u64,
+) -> Result<Option<u64>> {
+    if serves_block(provider, from).await? {
+        return Ok(Some(from));
+    }
+    if !serves_block(provider, head).await? {
+        return Ok(None);
+    }
+
+    let (mut low, mut high) = (from + 1, head);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if serves_block(provider, mid).await? {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Ok(Some(low))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::providers::ProviderBuilder;
+    use serde_json::json;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_finds_the_earliest_served_block() {
+        // Blocks before 1000 are pruned, reported as null below 500 and as an error above
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(|request: &Request| {
+                let body: JsonValue = serde_json::from_slice(&request.body).unwrap();
+                let hex = body["params"][0].as_str().unwrap();
+                let block = u64::from_str_radix(hex.trim_start_matches("0x"), 16).unwrap();
+                let response = match block {
+                    0..500 => json!({"jsonrpc": "2.0", "id": body["id"], "result": null}),
+                    500..1000 => json!({
+                        "jsonrpc": "2.0",
+                        "id": body["id"],
+                        "error": {"code": 4444, "message": "pruned history unavailable"}
+                    }),
+                    _ => json!({
+                        "jsonrpc": "2.0",
+                        "id": body["id"],
+                        "result": {"number": hex, "timestamp": "0x65f0a000"}
+                    }),
+                };
+                ResponseTemplate::new(200).set_body_json(response)
+            })
+            .mount(&server)
+            .await;
+
+        let provider = ProviderBuilder::new().connect_http(server.uri().parse().unwrap());
+        assert_eq!(
+            earliest_block(&provider, 0, 5000).await.unwrap(),
+            Some(1000)
+        );
+        assert_eq!(
+            earliest_block(&provider, 750, 5000).await.unwrap(),
+            Some(1000)
+        );
+        assert_eq!(
+            earliest_block(&provider, 1200, 5000).await.unwrap(),
+            Some(1200)
+        );
+        assert_eq!(earliest_block(&provider, 0, 999).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,231 @@
+//! Checks run on each IR after it is generated and before it is saved
+//!
+//! Projects can enforce their own policies, such as table naming rules or SQL
+//! constructs they don't allow, either as shell commands in `[[hooks]]` or as
+//! [`IrValidator`]s registered on [`crate::ir::Ir`]. A rejected IR fails generation.
+
+use crate::ai::{EndpointIrResult, IrGenerationResult};
+use crate::config::{HookConfig, HookTarget};
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// An IR that was just generated
+#[derive(Debug, Clone, Copy)]
+pub enum GeneratedIr<'a> {
+    Spec {
+        contract_name: &'a str,
+        spec_name: &'a str,
+        ir: &'a IrGenerationResult,
+    },
+    Endpoint(&'a EndpointIrResult),
+}
+
+impl GeneratedIr<'_> {
+    /// `spec` or `endpoint`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Spec { .. } => "spec",
+            Self::Endpoint(_) => "endpoint",
+        }
+    }
+
+    /// `<contract>/<spec>`, or the endpoint path
+    pub fn name(&self) -> String {
+        match self {
+            Self::Spec {
+                contract_name,
+                spec_name,
+                ..
+            } => format!("{}/{}", contract_name, spec_name),
+            Self::Endpoint(endpoint) => endpoint.endpoint_path.clone(),
+        }
+    }
+
+    fn to_json(self) -> Result<String> {
+        match self {
+            Self::Spec { ir, .. } => serde_json::to_string_pretty(ir),
+            Self::Endpoint(endpoint) => serde_json::to_string_pretty(endpoint),
+        }
+        .context("Failed to serialize IR")
+    }
+}
+
+/// A check of generated IR; an error rejects it
+pub trait IrValidator: Send + Sync {
+    /// Shown when the IR is rejected
+    fn name(&self) -> &str;
+
+    fn validate(&self, ir: GeneratedIr<'_>) -> Result<()>;
+}
+
+/// Shell command from `[[hooks]]`
+///
+/// The command runs with `sh -c`, gets the IR as JSON on stdin and `SMORTY_IR_KIND`
+/// and `SMORTY_IR_NAME` in its environment. A non-zero exit rejects the IR, with
+/// the command's output as the reason.
+#[derive(Debug, Clone)]
+pub struct ShellHook {
+    config: HookConfig,
+}
+
+impl ShellHook {
+    pub fn new(config: HookConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl IrValidator for ShellHook {
+    fn name(&self) -> &str {
+        &self.config.command
+    }
+
+    fn validate(&self, ir: GeneratedIr<'_>) -> Result<()> {
+        let applies = matches!(
+            (self.config.on, ir),
+            (HookTarget::All, _)
+                | (HookTarget::Spec, GeneratedIr::Spec { .. })
+                | (HookTarget::Endpoint, GeneratedIr::Endpoint(_))
+        );
+        if !applies {
+            return Ok(());
+        }
+
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.config.command)
+            .env("SMORTY_IR_KIND", ir.kind())
+            .env("SMORTY_IR_NAME", ir.name())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to run hook")?;
+
+        // A hook may exit without reading its input, so a broken pipe isn't an error
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(ir.to_json()?.as_bytes());
+        }
+
+        let output = child.wait_with_output().context("Failed to run hook")?;
+        if output.status.success() {
+            return Ok(());
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let reason = if stderr.trim().is_empty() {
+            stdout.trim()
+        } else {
+            stderr.trim()
+        };
+        anyhow::bail!(
+            "{}{}",
+            output.status,
+            if reason.is_empty() {
+                String::new()
+            } else {
+                format!(": {}", reason)
+            }
+        )
+    }
+}
+
+/// Run every validator, failing on the first rejection
+pub fn run_all(validators: &[Box<dyn IrValidator>], ir: GeneratedIr<'_>) -> Result<()> {
+    for validator in validators {
+        validator.validate(ir).with_context(|| {
+            format!(
+                "Hook '{}' rejected the IR for {} {}",
+                validator.name(),
+                ir.kind(),
+                ir.name()
+            )
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::ai::{ResponseSchema, TableSchema};
+
+    fn spec_ir() -> IrGenerationResult {
+        IrGenerationResult {
+            event_name: "Transfer".to_string(),
+            event_signature: "Transfer(address,address,uint256)".to_string(),
+            start_block: 0,
+            contract_address: "0x0000000000000000000000000000000000000001".to_string(),
+            chain: "mainnet".to_string(),
+            indexed_fields: vec![],
+            table_schema: TableSchema {
+                table_name: "Transfers".to_string(),
+                columns: vec![],
+                indexes: vec![],
+                computed_columns: vec![],
+            },
+            description: "Token transfers".to_string(),
+            abi_hash: None,
+            state_fold: None,
+        }
+    }
+
+    fn hook(command: &str, on: HookTarget) -> Box<dyn IrValidator> {
+        Box::new(ShellHook::new(HookConfig {
+            command: command.to_string(),
+            on,
+        }))
+    }
+
+    #[test]
+    fn test_shell_hooks() {
+        let ir = spec_ir();
+        let generated = GeneratedIr::Spec {
+            contract_name: "Token",
+            spec_name: "transfers",
+            ir: &ir,
+        };
+
+        let accept = hook(
+            r#"test "$SMORTY_IR_NAME" = Token/transfers && grep -q '"table_name": "Transfers"'"#,
+            HookTarget::All,
+        );
+        run_all(&[accept], generated).unwrap();
+
+        let reject = hook(
+            r#"if grep -q '"table_name": "[a-z_]*"'; then exit 0; fi; echo "table names must be snake_case" >&2; exit 1"#,
+            HookTarget::Spec,
+        );
+        let err = format!("{:#}", run_all(&[reject], generated).unwrap_err());
+        assert!(err.contains("rejected the IR for spec Token/transfers"));
+        assert!(err.contains("table names must be snake_case"));
+
+        // Endpoint hooks don't run on specs
+        run_all(&[hook("exit 1", HookTarget::Endpoint)], generated).unwrap();
+
+        let endpoint = EndpointIrResult {
+            endpoint_path: "/api/transfers".to_string(),
+            method: "GET".to_string(),
+            description: String::new(),
+            path_params: vec![],
+            query_params: vec![],
+            response_schema: ResponseSchema {
+                name: "Transfer".to_string(),
+                fields: vec![],
+            },
+            sql_query: "SELECT * FROM transfers".to_string(),
+            stages: Vec::new(),
+            tables_referenced: vec!["transfers".to_string()],
+            tags: vec![],
+            scopes: vec![],
+            cache_ttl: None,
+        };
+        let err = run_all(
+            &[hook("exit 3", HookTarget::All)],
+            GeneratedIr::Endpoint(&endpoint),
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", err).contains("endpoint /api/transfers"));
+    }
+}
@@ -0,0 +1,295 @@
+//! Table and column names of generated spec IRs
+//!
+//! The AI sometimes names columns after camelCase event fields, uses accented
+//! letters or goes past PostgreSQL's identifier limit, which Postgres would
+//! silently truncate. Before a spec IR is saved its names are transliterated to
+//! ASCII snake_case, and references to renamed columns in field mappings,
+//! indexes and expressions follow them. IRs loaded from disk, which may have
+//! been edited by hand, are checked without renaming anything.
+
+use crate::ai::IrGenerationResult;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+/// Longest identifier PostgreSQL keeps, in bytes
+pub const MAX_IDENTIFIER_LEN: usize = 63;
+
+/// Check that a name is ASCII snake_case, doesn't start with a digit and fits in 63 bytes
+pub fn check(name: &str) -> Result<()> {
+    if name.is_empty() {
+        anyhow::bail!("the name is empty");
+    }
+    if name.len() > MAX_IDENTIFIER_LEN {
+        anyhow::bail!(
+            "'{}' is {} characters long, PostgreSQL identifiers are at most {}",
+            name,
+            name.len(),
+            MAX_IDENTIFIER_LEN
+        );
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '_'))
+    {
+        anyhow::bail!(
+            "'{}' contains '{}', only lowercase ASCII letters, digits and '_' are allowed",
+            name,
+            c
+        );
+    }
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        anyhow::bail!("'{}' starts with a digit", name);
+    }
+    Ok(())
+}
+
+/// ASCII snake_case form of a name, e.g. `swap_fee_percentage` for `swapFeePercentage`
+/// or `prix_reserve` for `prixRéservé`
+pub fn transliterate(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len());
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c.is_uppercase() && previous_lower {
+            snake.push('_');
+        }
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+        for c in c.to_lowercase() {
+            match c {
+                'a'..='z' | '0'..='9' => snake.push(c),
+                _ => match fold_latin(c) {
+                    Some(ascii) => snake.push_str(ascii),
+                    None => snake.push('_'),
+                },
+            }
+        }
+    }
+
+    let mut name = String::with_capacity(snake.len());
+    for part in snake.split('_').filter(|part| !part.is_empty()) {
+        if !name.is_empty() {
+            name.push('_');
+        }
+        name.push_str(part);
+    }
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    name.truncate(MAX_IDENTIFIER_LEN);
+    name
+}
+
+/// ASCII spelling of accented Latin letters
+fn fold_latin(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => "e",
+        'ğ' => "g",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'ı' => "i",
+        'ł' => "l",
+        'ñ' | 'ń' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+        'œ' => "oe",
+        'ř' => "r",
+        'ś' | 'š' | 'ş' => "s",
+        'ß' => "ss",
+        'ť' | 'ţ' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => "u",
+        'ý' | 'ÿ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    })
+}
+
+/// Transliterate the table and column names of a generated spec IR, and update
+/// the references to renamed columns
+pub fn sanitize_spec(ir: &mut IrGenerationResult) -> Result<()> {
+    let table = transliterate(&ir.table_schema.table_name);
+    if table != ir.table_schema.table_name {
+        tracing::warn!(
+            "Renamed table '{}' to '{}'",
+            ir.table_schema.table_name,
+            table
+        );
+        ir.table_schema.table_name = table;
+    }
+
+    let mut renames = HashMap::new();
+    let names = ir
+        .table_schema
+        .columns
+        .iter_mut()
+        .map(|column| &mut column.name)
+        .chain(
+            ir.table_schema
+                .computed_columns
+                .iter_mut()
+                .map(|column| &mut column.name),
+        );
+    for name in names {
+        let renamed = transliterate(name);
+        if renamed != *name {
+            tracing::warn!(
+                "Renamed column '{}' of table '{}' to '{}'",
+                name,
+                ir.table_schema.table_name,
+                renamed
+            );
+            renames.insert(std::mem::replace(name, renamed.clone()), renamed);
+        }
+    }
+
+    for index in &mut ir.table_schema.indexes {
+        index.name = transliterate(&index.name);
+    }
+    if let Some(fold) = &mut ir.state_fold {
+        for column in &mut fold.key_columns {
+            column.name = transliterate(&column.name);
+        }
+        fold.value_column = transliterate(&fold.value_column);
+    }
+
+    if !renames.is_empty() {
+        for field in &mut ir.indexed_fields {
+            if let Some(renamed) = field.column.as_ref().and_then(|c| renames.get(c)) {
+                field.column = Some(renamed.clone());
+            }
+        }
+        for index in &mut ir.table_schema.indexes {
+            for column in &mut index.columns {
+                *column = rename_identifiers(column, &renames);
+            }
+            if let Some(predicate) = &mut index.predicate {
+                *predicate = rename_identifiers(predicate, &renames);
+            }
+        }
+        for column in &mut ir.table_schema.computed_columns {
+            column.expression = rename_identifiers(&column.expression, &renames);
+        }
+        if let Some(fold) = &mut ir.state_fold {
+            for delta in &mut fold.deltas {
+                for key in &mut delta.keys {
+                    *key = rename_identifiers(key, &renames);
+                }
+                delta.value = rename_identifiers(&delta.value, &renames);
+            }
+        }
+    }
+
+    check_spec(ir)
+}
+
+/// Check every table and column name of a spec IR
+///
+/// Index names aren't checked, the migration generator shortens them as needed.
+pub fn check_spec(ir: &IrGenerationResult) -> Result<()> {
+    let table = &ir.table_schema.table_name;
+    check(table).map_err(|e| anyhow::anyhow!("Invalid table name: {}", e))?;
+
+    let mut seen = HashSet::new();
+    let columns = ir
+        .table_schema
+        .columns
+        .iter()
+        .map(|column| column.name.as_str())
+        .chain(
+            ir.table_schema
+                .computed_columns
+                .iter()
+                .map(|column| column.name.as_str()),
+        );
+    for column in columns {
+        check(column)
+            .map_err(|e| anyhow::anyhow!("Invalid column name in table '{}': {}", table, e))?;
+        if !seen.insert(column) {
+            anyhow::bail!("Table '{}' has column '{}' more than once", table, column);
+        }
+    }
+    if let Some(fold) = &ir.state_fold {
+        for column in fold.key_columns.iter().map(|c| c.name.as_str()) {
+            check(column).map_err(|e| anyhow::anyhow!("Invalid state column name: {}", e))?;
+        }
+        check(&fold.value_column)
+            .map_err(|e| anyhow::anyhow!("Invalid state column name: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Replace whole identifiers in a SQL expression, quoted or not
+fn rename_identifiers(sql: &str, renames: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '"' {
+            let mut quoted = String::new();
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                quoted.push(c);
+            }
+            match renames.get(&quoted) {
+                Some(renamed) => out.push_str(renamed),
+                None => out.push_str(&format!("\"{}\"", quoted)),
+            }
+        } else if c == '\'' {
+            // Leave string literals alone
+            out.push(c);
+            for c in chars.by_ref() {
+                out.push(c);
+                if c == '\'' {
+                    break;
+                }
+            }
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut word = String::from(c);
+            while let Some(&next) = chars.peek() {
+                if !(next.is_alphanumeric() || next == '_') {
+                    break;
+                }
+                word.push(next);
+                chars.next();
+            }
+            out.push_str(renames.get(&word).unwrap_or(&word));
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transliterates_to_valid_identifiers() {
+        assert_eq!(transliterate("swapFeePercentage"), "swap_fee_percentage");
+        assert_eq!(transliterate("prixRéservé"), "prix_reserve");
+        assert_eq!(transliterate("Straße Größe"), "strasse_grosse");
+        assert_eq!(transliterate("24hVolume"), "_24h_volume");
+        assert_eq!(transliterate("代币"), "_");
+        assert_eq!(transliterate(&"a".repeat(70)).len(), MAX_IDENTIFIER_LEN);
+        for name in ["swapFeePercentage", "prixRéservé", "24hVolume", "代币"] {
+            assert!(check(&transliterate(name)).is_ok(), "{}", name);
+        }
+
+        assert!(check("swap_fee").is_ok());
+        assert!(check("swapFee").is_err());
+        assert!(check("1st_pool").is_err());
+        assert!(check(&"a".repeat(64)).is_err());
+
+        let renames =
+            HashMap::from([("montantÉchangé".to_string(), "montant_echange".to_string())]);
+        assert_eq!(
+            rename_identifiers(
+                "\"montantÉchangé\" / 1e18 + montantÉchangé_x + 'montantÉchangé'",
+                &renames
+            ),
+            "montant_echange / 1e18 + montantÉchangé_x + 'montantÉchangé'"
+        );
+    }
+}
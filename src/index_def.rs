@@ -0,0 +1,316 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Index access methods Postgres ships with
+const INDEX_METHODS: &[&str] = &["btree", "hash", "gist", "spgist", "gin", "brin"];
+
+/// Structured index definition in a table schema IR
+///
+/// Rendered to `CREATE INDEX` by the migration generator. IR files written before
+/// indexes were structured store them as SQL strings, which are parsed on load.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "IndexDefRepr")]
+pub struct IndexDef {
+    pub name: String,
+    /// Column names or expressions, e.g. `block_number DESC` or `lower(owner)`
+    pub columns: Vec<String>,
+    /// Access method such as `brin` or `gin`, btree when unset
+    pub method: Option<String>,
+    /// Partial index condition, without the `WHERE`
+    pub predicate: Option<String>,
+    pub unique: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IndexDefRepr {
+    Sql(String),
+    Structured {
+        name: String,
+        columns: Vec<String>,
+        #[serde(default)]
+        method: Option<String>,
+        #[serde(default)]
+        predicate: Option<String>,
+        #[serde(default)]
+        unique: bool,
+    },
+}
+
+impl TryFrom<IndexDefRepr> for IndexDef {
+    type Error = anyhow::Error;
+
+    fn try_from(repr: IndexDefRepr) -> Result<Self> {
+        let index = match repr {
+            IndexDefRepr::Sql(sql) => Self::parse(&sql)?,
+            IndexDefRepr::Structured {
+                name,
+                columns,
+                method,
+                predicate,
+                unique,
+            } => Self {
+                name,
+                columns,
+                // Strict structured output sends empty strings rather than omitting fields
+                method: method
+                    .filter(|m| !m.trim().is_empty())
+                    .map(|m| m.trim().to_lowercase()),
+                predicate: predicate.filter(|p| !p.trim().is_empty()),
+                unique,
+            },
+        };
+        index.validate()?;
+        Ok(index)
+    }
+}
+
+impl IndexDef {
+    /// Plain btree index over the given columns
+    pub fn new(name: &str, columns: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            method: None,
+            predicate: None,
+            unique: false,
+        }
+    }
+
+    /// Parse a `CREATE [UNIQUE] INDEX name ON table [USING method] (...) [WHERE ...]` statement
+    pub fn parse(sql: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("Unsupported index definition: {}", sql);
+        let sql = sql.trim().trim_end_matches(';');
+
+        let rest = strip_keyword(sql, "CREATE").ok_or_else(invalid)?;
+        let (unique, rest) = match strip_keyword(rest, "UNIQUE") {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let rest = strip_keyword(rest, "INDEX").ok_or_else(invalid)?;
+        let rest = strip_keyword(rest, "CONCURRENTLY").unwrap_or(rest);
+        let rest = strip_keyword(rest, "IF NOT EXISTS").unwrap_or(rest);
+
+        let on = find_keyword(rest, "ON").ok_or_else(invalid)?;
+        let name = rest[..on].trim().to_string();
+        let rest = rest[on + 2..].trim_start();
+
+        // Table name, up to the column list or USING
+        let table_end = rest
+            .find(|c: char| c == '(' || c.is_whitespace())
+            .ok_or_else(invalid)?;
+        let rest = rest[table_end..].trim_start();
+
+        let (method, rest) = match strip_keyword(rest, "USING") {
+            Some(rest) => {
+                let end = rest.find('(').ok_or_else(invalid)?;
+                (Some(rest[..end].trim().to_lowercase()), &rest[end..])
+            }
+            None => (None, rest),
+        };
+
+        let close = matching_paren(rest).ok_or_else(invalid)?;
+        let columns = split_top_level(&rest[1..close]);
+        let rest = rest[close + 1..].trim();
+
+        let predicate = if rest.is_empty() {
+            None
+        } else {
+            Some(
+                strip_keyword(rest, "WHERE")
+                    .ok_or_else(invalid)?
+                    .trim()
+                    .to_string(),
+            )
+        };
+
+        Ok(Self {
+            name,
+            columns,
+            method,
+            predicate,
+            unique,
+        })
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty()
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            anyhow::bail!("Invalid index name: '{}'", self.name);
+        }
+        if self.columns.iter().all(|c| c.trim().is_empty()) {
+            anyhow::bail!("Index '{}' has no columns", self.name);
+        }
+        if let Some(method) = &self.method
+            && !INDEX_METHODS.contains(&method.as_str())
+        {
+            anyhow::bail!(
+                "Index '{}' uses unknown method '{}', expected one of: {}",
+                self.name,
+                method,
+                INDEX_METHODS.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Render the `CREATE INDEX` statement, without a trailing semicolon
+    pub fn to_sql(&self, index_name: &str, table_name: &str) -> String {
+        let mut sql = format!(
+            "CREATE {}INDEX {} ON {}",
+            if self.unique { "UNIQUE " } else { "" },
+            index_name,
+            table_name
+        );
+
+        if let Some(method) = &self.method {
+            sql.push_str(&format!(" USING {}", method));
+        }
+
+        let columns: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| {
+                let column = column.trim();
+                // Function calls must be parenthesised to be used as index expressions
+                if column.contains('(') && !column.starts_with('(') {
+                    format!("({})", column)
+                } else {
+                    column.to_string()
+                }
+            })
+            .collect();
+        sql.push_str(&format!("({})", columns.join(", ")));
+
+        if let Some(predicate) = &self.predicate {
+            sql.push_str(&format!(" WHERE {}", predicate));
+        }
+
+        sql
+    }
+}
+
+/// Strip a leading case-insensitive keyword followed by whitespace or `(`
+fn strip_keyword<'a>(sql: &'a str, keyword: &str) -> Option<&'a str> {
+    let sql = sql.trim_start();
+    let head = sql.get(..keyword.len())?;
+    let rest = &sql[keyword.len()..];
+    let boundary = rest.is_empty() || rest.starts_with(|c: char| c.is_whitespace() || c == '(');
+    (head.eq_ignore_ascii_case(keyword) && boundary).then(|| rest.trim_start())
+}
+
+/// Position of a whitespace-delimited keyword
+fn find_keyword(sql: &str, keyword: &str) -> Option<usize> {
+    let upper = sql.to_uppercase();
+    upper.match_indices(keyword).map(|(i, _)| i).find(|&i| {
+        i > 0
+            && upper[..i].ends_with(char::is_whitespace)
+            && upper[i + keyword.len()..].starts_with(char::is_whitespace)
+    })
+}
+
+/// Index of the parenthesis closing the one `sql` starts with
+fn matching_paren(sql: &str) -> Option<usize> {
+    if !sql.starts_with('(') {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, c) in sql.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split on commas outside parentheses
+fn split_top_level(list: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in list.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    parts.push(current.trim().to_string());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_legacy_sql_strings() {
+        let index: IndexDef =
+            serde_json::from_str("\"CREATE INDEX idx_block_number ON {table_name}(block_number)\"")
+                .unwrap();
+        assert_eq!(index, IndexDef::new("idx_block_number", &["block_number"]));
+
+        let index = IndexDef::parse(
+            "create unique index idx_owner on {table_name} using btree (lower(owner), block_number DESC) where amount > 0",
+        )
+        .unwrap();
+        assert!(index.unique);
+        assert_eq!(index.method.as_deref(), Some("btree"));
+        assert_eq!(index.columns, vec!["lower(owner)", "block_number DESC"]);
+        assert_eq!(index.predicate.as_deref(), Some("amount > 0"));
+
+        assert!(IndexDef::parse("from_address").is_err());
+    }
+
+    #[test]
+    fn test_renders_structured_index() {
+        let index: IndexDef = serde_json::from_value(serde_json::json!({
+            "name": "idx_large_swaps",
+            "columns": ["lower(recipient)", "block_number"],
+            "method": "BRIN",
+            "predicate": "amount > 1000",
+            "unique": false
+        }))
+        .unwrap();
+        assert_eq!(
+            index.to_sql("uni_swaps_idx_large_swaps", "uni_swaps"),
+            "CREATE INDEX uni_swaps_idx_large_swaps ON uni_swaps USING brin((lower(recipient)), block_number) WHERE amount > 1000"
+        );
+
+        // Round-trips through its own SQL
+        let sql = index.to_sql("idx_large_swaps", "{table_name}");
+        let parsed = IndexDef::parse(&sql).unwrap();
+        assert_eq!(parsed.predicate, index.predicate);
+        assert_eq!(parsed.method, index.method);
+    }
+
+    #[test]
+    fn test_rejects_invalid_indexes() {
+        let unknown_method = serde_json::json!({
+            "name": "idx_a", "columns": ["a"], "method": "fulltext", "predicate": null, "unique": false
+        });
+        assert!(serde_json::from_value::<IndexDef>(unknown_method).is_err());
+
+        let bad_name = serde_json::json!({
+            "name": "idx a; DROP TABLE t", "columns": ["a"], "method": null, "predicate": null, "unique": false
+        });
+        assert!(serde_json::from_value::<IndexDef>(bad_name).is_err());
+    }
+}
@@ -1,17 +1,51 @@
+use crate::abi;
 use crate::ai::IrGenerationResult;
-use crate::config::Config;
-use crate::ir::Ir;
-use crate::schema_state::SchemaState;
-use alloy::primitives::{Address, FixedBytes};
-use alloy::providers::{Provider, ProviderBuilder};
+use crate::bench::{ChunkTiming, IndexBenchReport, LogFixture};
+use crate::calls::CallSpec;
+use crate::chains;
+use crate::config::{Config, PrunedHistory, WriteMode};
+use crate::coverage::{self, BlockRanges};
+use crate::decoded::{DecodedEvent, DecodedField, DecodedValue};
+use crate::dry_run::{DryRunRow, DryRunSummary, SpecPreview};
+use crate::error::{ResultExt, SmortyError};
+use crate::health::{self, HealthEvent, StuckSpecs};
+use crate::history;
+use crate::ir::{Ir, SOURCE_COLUMN};
+use crate::log_buffer::LogBuffer;
+use crate::migration::Migration;
+use crate::pause::{self, Pause};
+use crate::progress::{ChainProgress, IndexProgress};
+use crate::rate_limit::RpcRateLimits;
+use crate::redaction;
+use crate::rpc_logs;
+use crate::schema_state::{SCHEMA_STATE_FILE, SchemaState};
+use crate::spec_order::SpecGraph;
+use crate::start_block;
+use crate::system::{self, IndexLocks};
+use crate::verify::{self, Mismatch};
+use crate::watchlist::{WatchedContract, Watchlist};
+use alloy::json_abi::JsonAbi;
+use alloy::primitives::{Address, B256, keccak256};
+use alloy::providers::Provider;
 use alloy::rpc::types::{Filter, Log};
 use anyhow::{Context, Result};
-use sqlx::{PgPool, Row};
-use std::collections::HashMap;
+use sqlx::postgres::PgArguments;
+use sqlx::query::Query;
+use sqlx::{Acquire, PgPool, Postgres, Row};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Write;
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
-use tokio::time::{Duration, interval};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::{Instant, interval};
+
+/// Logs decoded per spec by `preview`
+const PREVIEW_SAMPLES: usize = 5;
+
+/// Most chunks searched back from the head for a spec's logs by `preview`
+const PREVIEW_CHUNKS: usize = 20;
 
 /// Represents a single event spec to index
 #[derive(Debug, Clone)]
@@ -19,22 +53,168 @@ struct IndexSpec {
     contract_name: String,
     spec_name: String,
     ir: IrGenerationResult,
+    address: Address,
+    /// Keccak-256 hash of the event signature, the first topic of matching logs
+    topic0: B256,
+    /// Value of the source column when the spec writes to a shared table
+    source: Option<String>,
+    /// Columns identifying a row for `writeMode = "upsert"`, empty when appending
+    conflict_keys: Vec<String>,
+    /// Last block of a bounded historical spec
+    end_block: Option<u64>,
+}
+
+impl IndexSpec {
+    fn new(contract_name: String, spec_name: String, ir: IrGenerationResult) -> Result<Self> {
+        let address = Address::from_str(&ir.contract_address).context(format!(
+            "Invalid contract address for {}/{}",
+            contract_name, spec_name
+        ))?;
+        let topic0 = keccak256(ir.event_signature.as_bytes());
+        Ok(Self {
+            contract_name,
+            spec_name,
+            ir,
+            address,
+            topic0,
+            source: None,
+            conflict_keys: Vec::new(),
+            end_block: None,
+        })
+    }
+
+    /// Tag rows with this spec's source, for specs writing to a shared table
+    fn with_shared_table(mut self, shared: bool) -> Self {
+        self.source = shared.then(|| Ir::source_name(&self.contract_name, &self.spec_name));
+        self
+    }
+
+    /// Update the row with the same conflict keys instead of adding one
+    fn with_conflict_keys(mut self, conflict_keys: Vec<String>) -> Self {
+        self.conflict_keys = conflict_keys;
+        self
+    }
+
+    /// Stop indexing the spec after `end_block`
+    fn with_end_block(mut self, end_block: Option<u64>) -> Self {
+        self.end_block = end_block;
+        self
+    }
+
+    /// Last block to index when the chain head is at `head`
+    fn last_block(&self, head: u64) -> u64 {
+        self.end_block.map_or(head, |end| end.min(head))
+    }
+
+    /// Whether a bounded spec is indexed up to its end block
+    fn is_complete(&self, cursor: Option<u64>) -> bool {
+        self.end_block
+            .is_some_and(|end| cursor.is_some_and(|cursor| cursor >= end))
+    }
 }
 
 /// Group of specs organized by chain for efficient indexing
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct ChainGroup {
     chain: String,
     rpc_url: String,
     specs: Vec<IndexSpec>,
     min_start_block: u64,
+    /// Contract name by address for every spec, paused or not, for watchlists
+    watched: BTreeMap<Address, String>,
+    /// Index into `specs` by contract address and topic0
+    dispatch: HashMap<(Address, B256), usize>,
+}
+
+impl ChainGroup {
+    fn new(chain: String, rpc_url: String, specs: Vec<IndexSpec>) -> Self {
+        // Find minimum start block across all specs for this chain
+        let min_start_block = specs.iter().map(|s| s.ir.start_block).min().unwrap_or(0);
+
+        // The first spec for an address and event wins
+        let mut dispatch = HashMap::new();
+        let mut watched = BTreeMap::new();
+        for (idx, spec) in specs.iter().enumerate() {
+            dispatch.entry((spec.address, spec.topic0)).or_insert(idx);
+            watched
+                .entry(spec.address)
+                .or_insert_with(|| spec.contract_name.clone());
+        }
+
+        Self {
+            chain,
+            rpc_url,
+            specs,
+            min_start_block,
+            watched,
+            dispatch,
+        }
+    }
+
+    /// The chain and specs of the group that are paused, as pause targets
+    fn paused(&self, pauses: &HashMap<String, Pause>) -> Vec<String> {
+        std::iter::once(self.chain.clone())
+            .chain(
+                self.specs
+                    .iter()
+                    .map(|spec| Ir::source_name(&spec.contract_name, &spec.spec_name)),
+            )
+            .filter(|target| pauses.contains_key(target))
+            .collect()
+    }
+
+    /// The group without its paused specs, `None` when the whole chain is paused
+    fn unpaused(&self, pauses: &HashMap<String, Pause>) -> Option<Self> {
+        if pauses.contains_key(&self.chain) {
+            return None;
+        }
+        let specs = self
+            .specs
+            .iter()
+            .filter(|spec| {
+                !pauses.contains_key(&Ir::source_name(&spec.contract_name, &spec.spec_name))
+            })
+            .cloned()
+            .collect();
+        // Watchlists still start where the full group does and watch all its contracts
+        let mut group = Self::new(self.chain.clone(), self.rpc_url.clone(), specs);
+        group.min_start_block = self.min_start_block;
+        group.watched = self.watched.clone();
+        Some(group)
+    }
+
+    /// Contract addresses to fetch logs for, of the specs flagged in `active`
+    fn active_addresses(&self, active: &[bool]) -> Vec<Address> {
+        let addresses: BTreeSet<Address> = self
+            .specs
+            .iter()
+            .zip(active)
+            .filter(|(_, active)| **active)
+            .map(|(spec, _)| spec.address)
+            .collect();
+        addresses.into_iter().collect()
+    }
+
+    /// Position in `specs` of the spec a log belongs to
+    fn spec_index(&self, log: &Log) -> Option<usize> {
+        let topic0 = log.topics().first()?;
+        self.dispatch.get(&(log.address(), *topic0)).copied()
+    }
 }
 
 /// Main indexer struct that manages the indexing process
+#[derive(Clone)]
 pub struct Indexer {
     config: Arc<Config>,
     db_pool: PgPool,
+    /// Pool of `database.uri`, where the pauses of every chain are recorded
+    main_pool: PgPool,
+    /// Pools of the databases in `database.perChain`, by chain
+    chain_pools: HashMap<String, PgPool>,
     schema: SchemaState,
+    progress: IndexProgress,
+    /// RPC budgets shared by every chain group on a provider
+    rate_limits: RpcRateLimits,
 }
 
 impl Indexer {
@@ -45,34 +225,114 @@ impl Indexer {
             .await
             .context("Failed to connect to database")?;
 
+        system::ensure_system_tables(&db_pool).await?;
+
+        // Chains sharing a database share its pool
+        let mut pools_by_uri: HashMap<&str, PgPool> = HashMap::new();
+        let mut chain_pools = HashMap::new();
+        for (chain, database) in &config.database.per_chain {
+            let pool = match pools_by_uri.get(database.uri.as_str()) {
+                Some(pool) => pool.clone(),
+                None => {
+                    let pool = PgPool::connect(&database.uri).await.context(format!(
+                        "Failed to connect to database of chain '{}'",
+                        chain
+                    ))?;
+                    system::ensure_system_tables(&pool).await?;
+                    pools_by_uri.insert(&database.uri, pool.clone());
+                    pool
+                }
+            };
+            chain_pools.insert(chain.clone(), pool);
+        }
+
         // Load schema state
-        let schema = SchemaState::load(Path::new("migrations/schema.json"))
-            .context("Failed to load migrations/schema.json")?;
+        let schema = SchemaState::load(Path::new(SCHEMA_STATE_FILE))
+            .context(format!("Failed to load {}", SCHEMA_STATE_FILE))?;
+
+        Ok(Self {
+            config: Arc::new(config.clone()),
+            main_pool: db_pool.clone(),
+            db_pool,
+            chain_pools,
+            schema,
+            progress: IndexProgress::hidden(),
+            rate_limits: RpcRateLimits::new(&config.chains.rate_limits),
+        })
+    }
+
+    /// Create an indexer that never connects to the database, for dry runs
+    ///
+    /// Rows are built against the schema the current IR would migrate to, so
+    /// `gen-migration` and `migrate` don't need to run first.
+    pub fn offline(config: &Config) -> Result<Self> {
+        let db_pool = PgPool::connect_lazy(&config.database.uri).context("Invalid database URI")?;
+        let ir_specs = Ir::load_all_ir_specs(config)?;
+        let schema = Migration::build_schema_state_from_ir(&ir_specs)?;
 
         Ok(Self {
             config: Arc::new(config.clone()),
+            main_pool: db_pool.clone(),
             db_pool,
+            chain_pools: HashMap::new(),
             schema,
+            progress: IndexProgress::hidden(),
+            rate_limits: RpcRateLimits::new(&config.chains.rate_limits),
         })
     }
 
+    /// Indexer writing to the database of `chain`
+    fn for_chain(&self, chain: &str) -> Self {
+        let mut indexer = self.clone();
+        if let Some(pool) = self.chain_pools.get(chain) {
+            indexer.db_pool = pool.clone();
+        }
+        indexer
+    }
+
+    /// Show progress bars while indexing
+    pub fn with_progress(mut self, progress: IndexProgress) -> Self {
+        self.progress = progress;
+        self
+    }
+
     /// Start the indexer
     pub async fn start(&self, daemon: bool) -> Result<()> {
         tracing::info!("Loading IR files...");
-        let ir_specs = Ir::load_all_ir_specs(&self.config)?;
+        let mut ir_specs = Ir::load_all_ir_specs(&self.config)?;
         tracing::info!("Loaded {} IR specs", ir_specs.len());
+        self.resolve_start_blocks(&mut ir_specs).await?;
 
         // Group specs by chain for efficient indexing
-        let chain_groups = self.group_specs_by_chain(ir_specs)?;
+        let mut chain_groups = self.group_specs_by_chain(ir_specs)?;
         tracing::info!("Organized into {} chain groups", chain_groups.len());
 
-        for group in &chain_groups {
+        // Two indexers on the same chain would duplicate work and race each other's cursors
+        let chains: Vec<&str> = chain_groups
+            .iter()
+            .map(|group| group.chain.as_str())
+            .collect();
+        let _locks = IndexLocks::acquire(&self.db_pool, &chains).await?;
+
+        for group in &mut chain_groups {
             tracing::info!(
                 "Chain '{}': {} specs, starting from block {}",
-                group.chain,
+                chains::display_name(&group.chain),
                 group.specs.len(),
                 group.min_start_block
             );
+            if let Some(chain) = chains::lookup(&group.chain) {
+                for spec in &group.specs {
+                    tracing::debug!(
+                        "  - {}/{}: {}",
+                        spec.contract_name,
+                        spec.spec_name,
+                        chain.address_url(&spec.ir.contract_address)
+                    );
+                }
+            }
+            self.verify_chain_id(group).await?;
+            self.check_history(group).await?;
         }
 
         if daemon {
@@ -82,42 +342,461 @@ impl Indexer {
         }
     }
 
+    /// Make sure the RPC of a known chain actually serves that chain
+    async fn verify_chain_id(&self, group: &ChainGroup) -> Result<()> {
+        let Some(chain) = chains::lookup(&group.chain) else {
+            tracing::debug!(
+                "Chain '{}' is not in the chain registry, skipping chain id check",
+                group.chain
+            );
+            return Ok(());
+        };
+
+        let provider = self.rate_limits.provider(&group.rpc_url)?;
+        match provider.get_chain_id().await {
+            Ok(rpc_chain_id) => Ok(chain
+                .verify_chain_id(rpc_chain_id)
+                .kind(SmortyError::Config)?),
+            Err(e) => {
+                tracing::warn!("Failed to get chain id for chain '{}': {}", group.chain, e);
+                Ok(())
+            }
+        }
+    }
+
+    /// Make sure the RPC still serves the blocks the specs of a chain resume at
+    ///
+    /// Non-archive nodes return no logs for pruned blocks rather than an error,
+    /// so this fails, or moves the affected specs' start up with `prunedHistory = "clamp"`.
+    async fn check_history(&self, group: &mut ChainGroup) -> Result<()> {
+        // Coverage lives in the database of the chain
+        let indexer = self.for_chain(&group.chain);
+        let mut next_blocks = Vec::with_capacity(group.specs.len());
+        for spec in &group.specs {
+            let ranges = indexer.load_coverage(spec).await?;
+            next_blocks.push(ranges.next_block(spec.ir.start_block));
+        }
+        let provider = self.rate_limits.provider(&group.rpc_url)?;
+        check_served_history(
+            &provider,
+            group,
+            &next_blocks,
+            self.config.indexer.pruned_history,
+        )
+        .await
+    }
+
+    /// Measure decode and insert throughput without committing any rows
+    ///
+    /// Replays the logs from `fixture` if given, otherwise fetches the last
+    /// `blocks` blocks of each chain (optionally saving them to `save_fixture`).
+    pub async fn bench(
+        &self,
+        blocks: u64,
+        chain: Option<&str>,
+        fixture: Option<&Path>,
+        save_fixture: Option<&Path>,
+    ) -> Result<Vec<IndexBenchReport>> {
+        let ir_specs = Ir::load_all_ir_specs(&self.config)?;
+        let fixture = fixture.map(LogFixture::load).transpose()?;
+        let chain = chain.or(fixture.as_ref().map(|f| f.chain.as_str()));
+
+        let chain_groups: Vec<ChainGroup> = self
+            .group_specs_by_chain(ir_specs)?
+            .into_iter()
+            .filter(|group| chain.is_none_or(|chain| group.chain == chain))
+            .collect();
+
+        if chain_groups.is_empty() {
+            anyhow::bail!("No IR specs found for chain '{}'", chain.unwrap_or("*"));
+        }
+        if save_fixture.is_some() && chain_groups.len() > 1 {
+            anyhow::bail!("Multiple chains configured, pass --chain to choose which one to save");
+        }
+
+        let mut reports = Vec::new();
+        for group in &chain_groups {
+            let (fixture, chunks) = match &fixture {
+                Some(fixture) => (fixture.clone(), Vec::new()),
+                None => self.fetch_logs(group, blocks, None).await?,
+            };
+
+            if let Some(path) = save_fixture {
+                fixture.save(path)?;
+                tracing::info!("Saved {} logs to: {:?}", fixture.logs.len(), path);
+            }
+
+            let mut report = self
+                .for_chain(&group.chain)
+                .replay_logs(group, &fixture)
+                .await?;
+            report.chunks = chunks;
+            reports.push(report);
+        }
+
+        Ok(reports)
+    }
+
+    /// Decode `blocks` blocks of each chain and write the rows they'd insert as JSON lines
+    ///
+    /// Starts at `from_block` if given, otherwise covers the most recent blocks.
+    pub async fn dry_run(
+        &self,
+        blocks: u64,
+        from_block: Option<u64>,
+        chain: Option<&str>,
+        out: &mut dyn Write,
+    ) -> Result<Vec<DryRunSummary>> {
+        let ir_specs = Ir::load_all_ir_specs(&self.config)?;
+        let chain_groups: Vec<ChainGroup> = self
+            .group_specs_by_chain(ir_specs)?
+            .into_iter()
+            .filter(|group| chain.is_none_or(|chain| group.chain == chain))
+            .collect();
+
+        if chain_groups.is_empty() {
+            anyhow::bail!("No IR specs found for chain '{}'", chain.unwrap_or("*"));
+        }
+
+        let mut summaries = Vec::new();
+        for group in &chain_groups {
+            let (fixture, _) = self.fetch_logs(group, blocks, from_block).await?;
+            let mut summary = DryRunSummary {
+                chain: group.chain.clone(),
+                from_block: fixture.from_block,
+                to_block: fixture.to_block,
+                ..Default::default()
+            };
+
+            // Logs removed by a reorg delete rows rather than insert them
+            for log in fixture.logs.iter().filter(|log| !log.removed) {
+                let Some(spec) = group.spec_index(log).map(|idx| &group.specs[idx]) else {
+                    continue;
+                };
+                summary.logs += 1;
+
+                match self.build_row(log, spec) {
+                    Ok(row) => {
+                        let row = DryRunRow::new(
+                            Ir::source_name(&spec.contract_name, &spec.spec_name),
+                            spec.ir.table_schema.table_name.clone(),
+                            &row.columns,
+                            &row.literals(),
+                        );
+                        serde_json::to_writer(&mut *out, &row)
+                            .context("Failed to write dry run row")?;
+                        writeln!(out).context("Failed to write dry run row")?;
+                        summary.rows += 1;
+                    }
+                    Err(e) => {
+                        summary.failed += 1;
+                        tracing::warn!(
+                            "Failed to decode log for {}/{}: {:#}",
+                            spec.contract_name,
+                            spec.spec_name,
+                            e
+                        );
+                    }
+                }
+            }
+
+            summaries.push(summary);
+        }
+
+        Ok(summaries)
+    }
+
+    /// Decode the newest [`PREVIEW_SAMPLES`] logs of each spec as the rows they'd insert
+    ///
+    /// Logs are searched back from the chain head one chunk at a time, for at most
+    /// [`PREVIEW_CHUNKS`] chunks or until the spec's start block.
+    pub async fn preview(&self) -> Result<Vec<SpecPreview>> {
+        let ir_specs = Ir::load_all_ir_specs(&self.config)?;
+        let chunk_size = self.config.indexer.chunk_size;
+
+        let mut previews = Vec::new();
+        for group in self.group_specs_by_chain(ir_specs)? {
+            let provider = self.rate_limits.provider(&group.rpc_url)?;
+            let head = provider
+                .get_block_number()
+                .await
+                .context("Failed to get current block number")
+                .kind(SmortyError::Rpc)?;
+
+            for spec in &group.specs {
+                let source = Ir::source_name(&spec.contract_name, &spec.spec_name);
+                let mut logs = Vec::new();
+                let mut from_block = head;
+                let mut to_block = head;
+                for _ in 0..PREVIEW_CHUNKS {
+                    from_block = to_block
+                        .saturating_sub(chunk_size - 1)
+                        .max(spec.ir.start_block.min(to_block));
+                    let filter = Filter::new()
+                        .address(spec.address)
+                        .event_signature(spec.topic0)
+                        .from_block(from_block)
+                        .to_block(to_block);
+                    let mut chunk = Vec::new();
+                    rpc_logs::stream_logs(&provider, &filter, |log| {
+                        chunk.push(log);
+                        Ok(())
+                    })
+                    .await
+                    .context(format!("Failed to fetch logs of {}", source))?;
+                    logs.extend(chunk.into_iter().rev().filter(|log| !log.removed));
+
+                    if logs.len() >= PREVIEW_SAMPLES || from_block <= spec.ir.start_block {
+                        break;
+                    }
+                    to_block = from_block - 1;
+                }
+
+                let mut preview = SpecPreview {
+                    spec: source.clone(),
+                    from_block,
+                    to_block: head,
+                    rows: Vec::new(),
+                    failed: 0,
+                };
+                for log in logs.iter().take(PREVIEW_SAMPLES) {
+                    match self.build_row(log, spec) {
+                        Ok(row) => preview.rows.push(DryRunRow::new(
+                            source.clone(),
+                            spec.ir.table_schema.table_name.clone(),
+                            &row.columns,
+                            &row.literals(),
+                        )),
+                        Err(e) => {
+                            preview.failed += 1;
+                            tracing::warn!("Failed to decode log for {}: {:#}", source, e);
+                        }
+                    }
+                }
+                previews.push(preview);
+            }
+        }
+
+        Ok(previews)
+    }
+
+    /// Fetch `blocks` blocks of a chain from `from_block`, or the most recent ones,
+    /// timing each request
+    async fn fetch_logs(
+        &self,
+        group: &ChainGroup,
+        blocks: u64,
+        from_block: Option<u64>,
+    ) -> Result<(LogFixture, Vec<ChunkTiming>)> {
+        let provider = self.rate_limits.provider(&group.rpc_url)?;
+
+        let current_block = provider
+            .get_block_number()
+            .await
+            .context("Failed to get current block number")
+            .kind(SmortyError::Rpc)?;
+        let (from_block, to_block) = match from_block {
+            Some(from_block) => (
+                from_block,
+                (from_block + blocks.max(1) - 1).min(current_block),
+            ),
+            None => (
+                current_block.saturating_sub(blocks.max(1) - 1),
+                current_block,
+            ),
+        };
+
+        let addresses = group
+            .specs
+            .iter()
+            .map(|spec| Address::from_str(&spec.ir.contract_address))
+            .collect::<Result<Vec<_>, _>>()
+            .context("Invalid contract address")?;
+
+        tracing::info!(
+            "Fetching logs for chain '{}' from block {} to {}",
+            group.chain,
+            from_block,
+            to_block
+        );
+
+        let chunk_size = self.config.indexer.chunk_size;
+        let mut logs = Vec::new();
+        let mut chunks = Vec::new();
+        let mut chunk_from = from_block;
+        while chunk_from <= to_block {
+            let chunk_to = std::cmp::min(chunk_from + chunk_size - 1, to_block);
+            let filter = Filter::new()
+                .address(addresses.clone())
+                .from_block(chunk_from)
+                .to_block(chunk_to);
+
+            let started = Instant::now();
+            let chunk_logs = rpc_logs::get_logs(&provider, &filter).await?;
+            chunks.push(ChunkTiming {
+                blocks: chunk_to - chunk_from + 1,
+                logs: chunk_logs.len(),
+                latency: started.elapsed(),
+            });
+
+            logs.extend(chunk_logs);
+            chunk_from = chunk_to + 1;
+        }
+
+        let fixture = LogFixture {
+            chain: group.chain.clone(),
+            from_block,
+            to_block,
+            logs,
+        };
+
+        Ok((fixture, chunks))
+    }
+
+    /// Decode and insert logs inside a transaction that is rolled back
+    async fn replay_logs(
+        &self,
+        group: &ChainGroup,
+        fixture: &LogFixture,
+    ) -> Result<IndexBenchReport> {
+        let mut report = IndexBenchReport {
+            chain: group.chain.clone(),
+            from_block: fixture.from_block,
+            to_block: fixture.to_block,
+            chunk_size: self.config.indexer.chunk_size,
+            logs: fixture.logs.len(),
+            ..Default::default()
+        };
+
+        let mut tx = self
+            .db_pool
+            .begin()
+            .await
+            .context("Failed to start transaction")?;
+
+        for log in &fixture.logs {
+            let Some(spec) = group.spec_index(log).map(|idx| &group.specs[idx]) else {
+                continue;
+            };
+            report.matched += 1;
+
+            let started = Instant::now();
+            let insert_query = self.build_insert_query(log, spec);
+            report.decode_time += started.elapsed();
+
+            let Ok(insert_query) = insert_query else {
+                report.failed += 1;
+                continue;
+            };
+
+            // A savepoint keeps one failed insert from aborting the whole transaction
+            let started = Instant::now();
+            let mut savepoint = (&mut *tx)
+                .begin()
+                .await
+                .context("Failed to create savepoint")?;
+            let result = insert_query.query().execute(&mut *savepoint).await;
+            if result.is_ok() {
+                savepoint
+                    .commit()
+                    .await
+                    .context("Failed to release savepoint")?;
+            } else {
+                report.failed += 1;
+                savepoint
+                    .rollback()
+                    .await
+                    .context("Failed to roll back savepoint")?;
+            }
+            report.insert_time += started.elapsed();
+        }
+
+        tx.rollback()
+            .await
+            .context("Failed to roll back benchmark transaction")?;
+
+        Ok(report)
+    }
+
+    /// Resolve the start blocks of specs configured relative to their chain
+    async fn resolve_start_blocks(
+        &self,
+        ir_specs: &mut [(String, String, IrGenerationResult)],
+    ) -> Result<()> {
+        for ir_spec in ir_specs {
+            let chain = ir_spec.2.chain.clone();
+            let provider = self
+                .rate_limits
+                .provider(self.config.get_rpc_url(&chain)?)?;
+            let indexer = self.for_chain(&chain);
+            start_block::apply(&indexer.db_pool, &provider, &self.config, ir_spec).await?;
+        }
+        Ok(())
+    }
+
     /// Group IR specs by chain for efficient processing
     fn group_specs_by_chain(
         &self,
         ir_specs: Vec<(String, String, IrGenerationResult)>,
     ) -> Result<Vec<ChainGroup>> {
         let mut chain_map: HashMap<String, Vec<IndexSpec>> = HashMap::new();
+        let mut abis: HashMap<String, Option<JsonAbi>> = HashMap::new();
 
         // Group specs by chain
         for (contract_name, spec_name, ir) in ir_specs {
-            let spec = IndexSpec {
-                contract_name,
-                spec_name,
-                ir,
+            let spec_config = self.config.spec(&contract_name, &spec_name);
+            let shared = spec_config.is_some_and(|spec| spec.table.is_some());
+            let conflict_keys = match spec_config {
+                Some(spec) if spec.write_mode == WriteMode::Upsert => spec.conflict_keys.clone(),
+                _ => Vec::new(),
             };
+            let end_block = spec_config.and_then(|spec| spec.end_block);
+            let mut spec = IndexSpec::new(contract_name, spec_name, ir)?
+                .with_shared_table(shared)
+                .with_conflict_keys(conflict_keys)
+                .with_end_block(end_block);
+
+            // Filter on the topic0 of the event in the ABI, never one derived from a typo
+            let abi = abis.entry(spec.contract_name.clone()).or_insert_with(|| {
+                let path = &self.config.contracts.get(&spec.contract_name)?.abi_path;
+                abi::load(Path::new(path))
+                    .inspect_err(|e| tracing::warn!("Not checking event signatures: {:#}", e))
+                    .ok()
+            });
+            if let Some(abi) = abi {
+                let event = abi::resolve_event(abi, &spec.ir.event_name, &spec.ir.event_signature)
+                    .context(format!(
+                        "IR for {}/{} doesn't match the contract ABI",
+                        spec.contract_name, spec.spec_name
+                    ))
+                    .kind(SmortyError::Config)?;
+                spec.topic0 = event.selector();
+            }
 
             chain_map
                 .entry(spec.ir.chain.clone())
-                .or_insert_with(Vec::new)
+                .or_default()
                 .push(spec);
         }
 
-        // Convert to ChainGroup with RPC URLs and min start blocks
-        let mut groups = Vec::new();
-        for (chain, specs) in chain_map {
-            let rpc_url = self.config.get_rpc_url(&chain)?.clone();
-
-            // Find minimum start block across all specs for this chain
-            let min_start_block = specs.iter().map(|s| s.ir.start_block).min().unwrap_or(0);
+        // Chains whose contracts only index calls still get a group
+        for contract in self.config.contracts.values() {
+            if !contract.calls.is_empty() {
+                chain_map.entry(contract.chain.clone()).or_default();
+            }
+        }
 
-            groups.push(ChainGroup {
-                chain,
-                rpc_url,
-                specs,
-                min_start_block,
+        // Convert to ChainGroup with RPC URLs and min start blocks, in processing order
+        let graph = SpecGraph::from_config(&self.config)?;
+        let mut groups = Vec::new();
+        for (chain, mut specs) in chain_map {
+            specs.sort_by_key(|spec| {
+                graph.spec_rank(&Ir::source_name(&spec.contract_name, &spec.spec_name))
             });
+            let rpc_url = self.config.get_rpc_url(&chain)?.clone();
+            groups.push(ChainGroup::new(chain, rpc_url, specs));
         }
+        groups.sort_by_key(|group| graph.chain_rank(&group.chain));
 
         Ok(groups)
     }
@@ -126,17 +805,26 @@ impl Indexer {
     async fn run_once(&self, chain_groups: Vec<ChainGroup>) -> Result<()> {
         tracing::info!("Running indexer in one-time mode");
 
+        let pauses = pause::load(&self.main_pool).await?;
         for group in chain_groups {
+            let Some(group) = group.unpaused(&pauses) else {
+                tracing::info!("Skipping chain '{}', it is paused", group.chain);
+                continue;
+            };
             tracing::info!(
                 "Indexing chain '{}' with {} specs",
                 group.chain,
                 group.specs.len()
             );
 
-            if let Err(e) = self.index_chain_group(&group).await {
+            let mut chain_progress = self.progress.chain(&chains::display_name(&group.chain));
+            let indexer = self.for_chain(&group.chain);
+            if let Err(e) = indexer.index_chain_group(&group, &mut chain_progress).await {
                 tracing::error!("Failed to index chain '{}': {:?}", group.chain, e);
                 return Err(e);
             }
+            indexer.index_watchlists(&group).await?;
+            indexer.index_calls(&group).await?;
         }
 
         tracing::info!("One-time indexing complete");
@@ -147,32 +835,122 @@ impl Indexer {
     async fn run_daemon_mode(&self, chain_groups: Vec<ChainGroup>) -> Result<()> {
         tracing::info!("Running indexer in daemon mode");
 
+        // Each chain announces when it first catches up, chains waiting on it start then
+        let graph = SpecGraph::from_config(&self.config)?;
+        let mut caught_up: HashMap<String, watch::Sender<bool>> = chain_groups
+            .iter()
+            .map(|group| (group.chain.clone(), watch::channel(false).0))
+            .collect();
+        let prerequisites: Vec<Vec<(String, watch::Receiver<bool>)>> = chain_groups
+            .iter()
+            .map(|group| {
+                graph
+                    .prerequisites(&group.chain)
+                    .into_iter()
+                    .filter_map(|chain| {
+                        let receiver = caught_up.get(&chain)?.subscribe();
+                        Some((chain, receiver))
+                    })
+                    .collect()
+            })
+            .collect();
+
         // Create tasks for each chain
         let mut tasks = Vec::new();
 
-        for group in chain_groups {
-            let indexer = Self {
-                config: Arc::clone(&self.config),
-                db_pool: self.db_pool.clone(),
-                schema: self.schema.clone(),
-            };
+        for (group, prerequisites) in chain_groups.into_iter().zip(prerequisites) {
+            let indexer = self.for_chain(&group.chain);
+            let done = caught_up
+                .remove(&group.chain)
+                .expect("every chain has a sender");
 
             let task = tokio::spawn(async move {
+                for (chain, mut receiver) in prerequisites {
+                    if !*receiver.borrow() {
+                        tracing::info!(
+                            "Chain '{}' waits for chain '{}' to catch up",
+                            group.chain,
+                            chain
+                        );
+                    }
+                    let _ = receiver.wait_for(|done| *done).await;
+                }
+
                 tracing::info!(
                     "Starting daemon for chain '{}' with {} specs",
                     group.chain,
                     group.specs.len()
                 );
 
-                // Poll every 12 seconds (approximately 1 block on Ethereum)
-                let mut ticker = interval(Duration::from_secs(12));
+                // Poll about once per block
+                let mut ticker = interval(chains::poll_interval(&group.chain));
+                let mut chain_progress =
+                    indexer.progress.chain(&chains::display_name(&group.chain));
+                let mut stuck = indexer.config.indexer.stuck_polls.map(StuckSpecs::new);
+                let mut last_error = None;
+                let verify = indexer.config.indexer.verify.clone();
+                let mut next_verify = verify
+                    .as_ref()
+                    .map(|verify| Instant::now() + Duration::from_secs(verify.interval_secs));
+                let mut last_paused = Vec::new();
 
                 loop {
                     ticker.tick().await;
 
-                    if let Err(e) = indexer.index_chain_group(&group).await {
-                        tracing::error!("Error indexing chain '{}': {:?}", group.chain, e);
-                        // Continue despite errors
+                    // Pauses can change while the daemon runs, from the CLI or the API
+                    let pauses = match pause::load(&indexer.main_pool).await {
+                        Ok(pauses) => pauses,
+                        Err(e) => {
+                            tracing::warn!("{:#}", e);
+                            continue;
+                        }
+                    };
+                    let paused = group.paused(&pauses);
+                    if paused != last_paused {
+                        if paused.is_empty() {
+                            tracing::info!("Resuming chain '{}'", group.chain);
+                        } else {
+                            tracing::info!("Chain '{}': paused {}", group.chain, paused.join(", "));
+                        }
+                        last_paused = paused;
+                    }
+                    let Some(group) = group.unpaused(&pauses) else {
+                        continue;
+                    };
+
+                    match indexer.index_chain_group(&group, &mut chain_progress).await {
+                        Ok(()) => {
+                            done.send_if_modified(|done| !std::mem::replace(done, true));
+                        }
+                        Err(e) => {
+                            tracing::error!("Error indexing chain '{}': {:?}", group.chain, e);
+                            last_error = Some(format!("{:#}", e));
+                            // Continue despite errors
+                        }
+                    }
+                    if let Err(e) = indexer.index_watchlists(&group).await {
+                        tracing::error!(
+                            "Error indexing watchlists of chain '{}': {:?}",
+                            group.chain,
+                            e
+                        );
+                    }
+                    if let Err(e) = indexer.index_calls(&group).await {
+                        tracing::error!("Error indexing calls on chain '{}': {:?}", group.chain, e);
+                    }
+
+                    if let Some(stuck) = &mut stuck {
+                        indexer
+                            .check_stuck(&group, stuck, last_error.as_deref())
+                            .await;
+                    }
+
+                    if let Some(verify) = &verify
+                        && next_verify.is_some_and(|at| Instant::now() >= at)
+                    {
+                        indexer.verify_samples(&group, verify.samples).await;
+                        next_verify =
+                            Some(Instant::now() + Duration::from_secs(verify.interval_secs));
                     }
                 }
             });
@@ -189,59 +967,299 @@ impl Indexer {
         Ok(())
     }
 
+    /// Check random rows of each spec against their logs, alerting on mismatches
+    async fn verify_samples(&self, group: &ChainGroup, samples: usize) {
+        let provider = match self.rate_limits.provider(&group.rpc_url) {
+            Ok(provider) => provider,
+            Err(e) => {
+                tracing::warn!("Not verifying chain '{}': {:#}", group.chain, e);
+                return;
+            }
+        };
+
+        let mut checked = 0;
+        for spec in &group.specs {
+            for _ in 0..samples {
+                match self.verify_sample(&provider, group, spec).await {
+                    Ok(Some(mismatch)) => {
+                        verify::raise(self.config.indexer.alert_webhook.as_deref(), &mismatch)
+                            .await;
+                    }
+                    Ok(None) => {}
+                    // Usually a flaky RPC, the next pass samples again
+                    Err(e) => {
+                        tracing::warn!(
+                            "Failed to verify a row of {}/{}: {:#}",
+                            spec.contract_name,
+                            spec.spec_name,
+                            e
+                        );
+                        break;
+                    }
+                }
+                checked += 1;
+            }
+        }
+        tracing::debug!("Verified {} rows on chain '{}'", checked, group.chain);
+    }
+
+    /// Compare a random row of a spec with its log fetched again from the RPC
+    ///
+    /// `None` when the row matches, or the spec has no rows yet.
+    async fn verify_sample<P: Provider>(
+        &self,
+        provider: &P,
+        group: &ChainGroup,
+        spec: &IndexSpec,
+    ) -> Result<Option<Mismatch>> {
+        let table_name = &spec.ir.table_schema.table_name;
+        let source = spec.source.as_deref();
+
+        let range_sql = verify::block_range_sql(table_name, source.is_some());
+        let mut range = sqlx::query_as(&range_sql);
+        if let Some(source) = source {
+            range = range.bind(source);
+        }
+        let (Some(min), Some(max)): (Option<i64>, Option<i64>) = range
+            .fetch_one(&self.db_pool)
+            .await
+            .context("Failed to load the indexed block range")?
+        else {
+            return Ok(None);
+        };
+
+        let sample_sql = verify::sample_sql(table_name, source.is_some());
+        let mut sample = sqlx::query_as(&sample_sql).bind(rand::random_range(min..=max));
+        if let Some(source) = source {
+            sample = sample.bind(source);
+        }
+        let Some((block_number, tx_hash, log_index)): Option<(i64, String, i64)> = sample
+            .fetch_optional(&self.db_pool)
+            .await
+            .context("Failed to sample a row")?
+        else {
+            return Ok(None);
+        };
+        let mismatch = |problem: String| Mismatch {
+            chain: group.chain.clone(),
+            spec: Ir::source_name(&spec.contract_name, &spec.spec_name),
+            block_number: block_number as u64,
+            transaction_hash: tx_hash.clone(),
+            log_index: log_index as u64,
+            problem,
+        };
+
+        let filter = Filter::new()
+            .address(spec.address)
+            .event_signature(spec.topic0)
+            .from_block(block_number as u64)
+            .to_block(block_number as u64);
+        let logs = rpc_logs::get_logs(provider, &filter).await?;
+        let Some(log) = logs.iter().find(|log| {
+            log.log_index == Some(log_index as u64)
+                && log
+                    .transaction_hash
+                    .is_some_and(|hash| format!("{:#x}", hash).eq_ignore_ascii_case(&tx_hash))
+        }) else {
+            return Ok(Some(mismatch(
+                "the RPC returns no such log, it may have been removed by a reorg".to_string(),
+            )));
+        };
+
+        let row = match self.build_row(log, spec) {
+            Ok(row) => row,
+            Err(e) => {
+                return Ok(Some(mismatch(format!(
+                    "the log no longer decodes: {:#}",
+                    e
+                ))));
+            }
+        };
+        let compare_sql = verify::compare_sql(
+            table_name,
+            &row.columns,
+            &row.expressions,
+            row.values.len(),
+            source.is_some(),
+        );
+        let mut compare = bind_values(sqlx::query(&compare_sql), &row.values)
+            .bind(&tx_hash)
+            .bind(log_index);
+        if let Some(source) = source {
+            compare = compare.bind(source);
+        }
+        // The row may have been deleted since it was sampled
+        let differing: Option<Vec<String>> = compare
+            .fetch_optional(&self.db_pool)
+            .await
+            .context("Failed to compare the row with its log")?
+            .map(|row| row.try_get(0))
+            .transpose()?;
+        Ok(differing
+            .filter(|columns| !columns.is_empty())
+            .map(|columns| {
+                mismatch(format!(
+                    "{} differ from the decoded log",
+                    columns.join(", ")
+                ))
+            }))
+    }
+
+    /// Compare each spec's cursor with the chain head and raise or clear stuck-spec alerts
+    async fn check_stuck(
+        &self,
+        group: &ChainGroup,
+        stuck: &mut StuckSpecs,
+        last_error: Option<&str>,
+    ) {
+        let head = match self.rate_limits.provider(&group.rpc_url) {
+            Ok(provider) => provider.get_block_number().await.ok(),
+            Err(_) => None,
+        };
+        // Without the head there's no telling whether the chain moved
+        let Some(head) = head else {
+            return;
+        };
+
+        // Specs starting past the head have nothing to index yet
+        for spec in group
+            .specs
+            .iter()
+            .filter(|spec| spec.ir.start_block <= head)
+        {
+            let source = Ir::source_name(&spec.contract_name, &spec.spec_name);
+            let cursor = match self.load_coverage(spec).await {
+                Ok(ranges) => ranges.end(),
+                Err(e) => {
+                    tracing::debug!("Failed to load coverage of {}: {:#}", source, e);
+                    continue;
+                }
+            };
+            // Complete specs stop advancing on purpose
+            if spec.is_complete(cursor) {
+                continue;
+            }
+            match stuck.observe(&group.chain, &source, cursor, head, last_error) {
+                Some(HealthEvent::Stuck(alert)) => {
+                    health::raise(
+                        &self.db_pool,
+                        self.config.indexer.alert_webhook.as_deref(),
+                        &alert,
+                    )
+                    .await;
+                }
+                Some(HealthEvent::Recovered { .. }) => {
+                    tracing::info!(
+                        "Spec {} on chain '{}' is advancing again",
+                        source,
+                        group.chain
+                    );
+                    if let Err(e) = health::clear(&self.db_pool, &source).await {
+                        tracing::warn!("{:#}", e);
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
     /// Index all specs for a single chain in one pass
-    async fn index_chain_group(&self, group: &ChainGroup) -> Result<()> {
+    async fn index_chain_group(
+        &self,
+        group: &ChainGroup,
+        progress: &mut ChainProgress,
+    ) -> Result<()> {
         // Create provider
-        let provider = ProviderBuilder::new()
-            .connect_http(group.rpc_url.parse().context("Invalid RPC URL")?)
-            .root()
-            .clone();
+        let provider = self.rate_limits.provider(&group.rpc_url)?;
 
         // Get current block number
         let current_block = provider
             .get_block_number()
             .await
-            .context("Failed to get current block number")?;
+            .context("Failed to get current block number")
+            .kind(SmortyError::Rpc)?;
 
-        // For each spec, check last indexed block and determine where to start
+        // For each spec, check which blocks were indexed and determine where to start
         // We need to find the MINIMUM start block to ensure we don't miss any events
-        let mut spec_start_blocks: Vec<(usize, u64)> = Vec::new(); // (spec_index, start_block)
+        let mut spec_start_blocks: Vec<u64> = Vec::with_capacity(group.specs.len());
 
-        for (idx, spec) in group.specs.iter().enumerate() {
-            let last_indexed = self
-                .get_last_indexed_block(&spec.ir.table_schema.table_name)
-                .await?;
+        for spec in &group.specs {
+            let ranges = self.load_coverage(spec).await?;
 
-            let spec_start = if last_indexed > 0 {
+            let spec_start = match ranges.end() {
                 // Resume from where we left off
-                last_indexed + 1
-            } else {
+                Some(end) => (end + 1).max(spec.ir.start_block),
                 // Start from the configured start block
-                spec.ir.start_block
+                None => spec.ir.start_block,
             };
 
-            spec_start_blocks.push((idx, spec_start));
+            spec_start_blocks.push(spec_start);
 
             tracing::debug!(
                 "  - {}/{}: starting from block {} (last indexed: {})",
                 spec.contract_name,
                 spec.spec_name,
                 spec_start,
-                if last_indexed > 0 {
-                    last_indexed.to_string()
-                } else {
-                    "none".to_string()
-                }
+                ranges
+                    .end()
+                    .map_or_else(|| "none".to_string(), |end| end.to_string())
             );
-        }
 
-        // Find the minimum start block across all specs
-        // This ensures we fetch logs from the earliest point needed
+            // Holes left by a crash mid-chunk, or ranges before a lowered start block
+            if let Some(end) = ranges.end() {
+                for (from, to) in ranges.gaps(spec.ir.start_block, end) {
+                    tracing::info!(
+                        "Re-fetching blocks {} to {} missed by {}/{}",
+                        from,
+                        to,
+                        spec.contract_name,
+                        spec.spec_name
+                    );
+                    if let Err(e) = self.fill_gap(&provider, spec, from, to).await {
+                        tracing::warn!(
+                            "Failed to re-fetch blocks {} to {} for {}/{}, will retry on the next pass: {:#}",
+                            from,
+                            to,
+                            spec.contract_name,
+                            spec.spec_name,
+                            e
+                        );
+                    }
+                }
+            }
+        }
+
+        // Bounded specs indexed up to their end block are complete and skipped
+        let active: Vec<bool> = group
+            .specs
+            .iter()
+            .zip(&spec_start_blocks)
+            .map(|(spec, &spec_start)| spec.end_block.is_none_or(|end| spec_start <= end))
+            .collect();
+        if !active.contains(&true) {
+            tracing::debug!("All specs of chain '{}' are complete", group.chain);
+            progress.finish(current_block);
+            return Ok(());
+        }
+
+        // Find the minimum start block across all specs
+        // This ensures we fetch logs from the earliest point needed
         let start_block = spec_start_blocks
             .iter()
-            .map(|(_, block)| *block)
+            .zip(&active)
+            .filter(|(_, active)| **active)
+            .map(|(&spec_start, _)| spec_start)
             .min()
             .unwrap_or(group.min_start_block);
+        // Stop at the end block of bounded specs when no spec needs more
+        let current_block = group
+            .specs
+            .iter()
+            .zip(&active)
+            .filter(|(_, active)| **active)
+            .map(|(spec, _)| spec.last_block(current_block))
+            .max()
+            .unwrap_or(current_block);
 
         // If we're already caught up, nothing to do
         if start_block > current_block {
@@ -251,6 +1269,7 @@ impl Indexer {
                 current_block,
                 start_block
             );
+            progress.finish(current_block);
             return Ok(());
         }
 
@@ -262,26 +1281,15 @@ impl Indexer {
             current_block - start_block + 1
         );
 
-        // Build a map of contract addresses to their specs
-        let mut contract_spec_map: HashMap<Address, Vec<&IndexSpec>> = HashMap::new();
-        for spec in &group.specs {
-            let address =
-                Address::from_str(&spec.ir.contract_address).context("Invalid contract address")?;
-            contract_spec_map
-                .entry(address)
-                .or_insert_with(Vec::new)
-                .push(spec);
-        }
-
-        // Collect all contract addresses
-        let addresses: Vec<Address> = contract_spec_map.keys().copied().collect();
+        let addresses = group.active_addresses(&active);
 
         // Fetch logs in chunks to avoid RPC limits
-        const CHUNK_SIZE: u64 = 1000;
+        let chunk_size = self.config.indexer.chunk_size;
         let mut from_block = start_block;
+        progress.start(start_block, current_block);
 
         while from_block <= current_block {
-            let to_block = std::cmp::min(from_block + CHUNK_SIZE - 1, current_block);
+            let to_block = std::cmp::min(from_block + chunk_size - 1, current_block);
 
             tracing::debug!(
                 "Fetching logs for chain '{}' from block {} to {}",
@@ -296,73 +1304,122 @@ impl Indexer {
                 .from_block(from_block)
                 .to_block(to_block);
 
-            // Fetch logs
-            let logs = provider
-                .get_logs(&filter)
-                .await
-                .context("Failed to fetch logs")?;
+            // Determine which spec each log belongs to by address and event signature.
+            // Specs are in processing order, so a spec's logs are inserted after
+            // those of the specs it depends on; each spec keeps its logs in order
+            let rpc_started = Instant::now();
+            let mut matched = LogBuffer::new(group.specs.len(), &self.config.indexer);
+            rpc_logs::stream_logs(&provider, &filter, |log| match group.spec_index(&log) {
+                Some(idx) => matched.push(idx, log),
+                None => Ok(()),
+            })
+            .await?;
+            let rpc_latency = rpc_started.elapsed();
+
+            tracing::debug!(
+                "Found {} logs for chain '{}' ({} spilled to disk)",
+                matched.len(),
+                group.chain,
+                matched.spilled()
+            );
 
-            tracing::debug!("Found {} logs for chain '{}'", logs.len(), group.chain);
+            // Tables that received new rows in this chunk
+            let mut inserted_tables = BTreeSet::new();
+            let mut events_inserted = 0;
 
             // Process each log
-            for log in logs {
-                // Determine which spec(s) this log belongs to
-                let address = log.address();
-                if let Some(specs) = contract_spec_map.get(&address) {
-                    // Match log to the correct spec based on event signature
-                    for spec in specs.iter() {
-                        // Check if this log matches this spec's event signature
-                        if self.log_matches_spec(&log, &spec.ir) {
-                            // Find the spec's start block from our tracking
-                            let spec_start = group
-                                .specs
-                                .iter()
-                                .position(|s| {
-                                    s.contract_name == spec.contract_name
-                                        && s.spec_name == spec.spec_name
-                                })
-                                .and_then(|idx| {
-                                    spec_start_blocks
-                                        .iter()
-                                        .find(|(i, _)| *i == idx)
-                                        .map(|(_, block)| *block)
-                                })
-                                .unwrap_or(0);
-
-                            // Check if this log is within the range for this specific spec
-                            if let Some(log_block) = log.block_number {
-                                if log_block < spec_start {
-                                    // Skip this log - it's before this spec's start block
-                                    tracing::trace!(
-                                        "Skipping log for {}/{} at block {} (spec starts at {})",
-                                        spec.contract_name,
-                                        spec.spec_name,
-                                        log_block,
-                                        spec_start
-                                    );
-                                    break;
-                                }
-                            }
+            for entry in matched.drain() {
+                let (idx, log) = entry?;
+                let spec = &group.specs[idx];
+                let spec_start = spec_start_blocks[idx];
+
+                // Check if this log is within the range for this specific spec
+                if let Some(log_block) = log.block_number
+                    && log_block < spec_start
+                {
+                    // Skip this log - it's before this spec's start block
+                    tracing::trace!(
+                        "Skipping log for {}/{} at block {} (spec starts at {})",
+                        spec.contract_name,
+                        spec.spec_name,
+                        log_block,
+                        spec_start
+                    );
+                    continue;
+                }
+                if let (Some(log_block), Some(end)) = (log.block_number, spec.end_block)
+                    && log_block > end
+                {
+                    continue;
+                }
 
-                            if let Err(e) = self.process_log(&log, &spec.ir).await {
-                                tracing::warn!(
-                                    "Skipping log for {}/{} due to error (this can happen with unreliable chains): {:?}",
-                                    spec.contract_name,
-                                    spec.spec_name,
-                                    e
-                                );
-                                // Continue processing other logs
-                            }
-                            // A log can only match one event signature, so break
-                            break;
+                match self.process_log(&log, spec).await {
+                    Ok(true) => {
+                        if !log.removed {
+                            events_inserted += 1;
                         }
+                        inserted_tables.insert(spec.ir.table_schema.table_name.as_str());
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        tracing::warn!(
+                            "Skipping log for {}/{} due to error (this can happen with unreliable chains): {:?}",
+                            spec.contract_name,
+                            spec.spec_name,
+                            e
+                        );
+                        // Continue processing other logs
                     }
                 }
             }
 
+            self.finish_chunk(group.specs.iter(), &inserted_tables, from_block)
+                .await;
+
+            // Only chunks that were fully processed count as indexed
+            let sources: Vec<(String, u64, u64)> = group
+                .specs
+                .iter()
+                .zip(&spec_start_blocks)
+                .zip(&active)
+                .filter(|&((_, &spec_start), &active)| active && spec_start <= to_block)
+                .map(|((spec, &spec_start), _)| {
+                    (
+                        Ir::source_name(&spec.contract_name, &spec.spec_name),
+                        from_block.max(spec_start),
+                        spec.last_block(to_block),
+                    )
+                })
+                .filter(|(_, from, to)| from <= to)
+                .collect();
+            let ranges: Vec<(&str, u64, u64)> = sources
+                .iter()
+                .map(|(source, from, to)| (source.as_str(), *from, *to))
+                .collect();
+            match coverage::checkpoint(&self.db_pool, &ranges).await {
+                Ok(()) => {
+                    for (spec, _) in group.specs.iter().zip(&active).filter(|(_, a)| **a) {
+                        if let Some(end) = spec.end_block
+                            && (from_block..=to_block).contains(&end)
+                        {
+                            tracing::info!(
+                                "{}/{} reached its end block {} and is complete",
+                                spec.contract_name,
+                                spec.spec_name,
+                                end
+                            );
+                        }
+                    }
+                }
+                Err(e) => tracing::warn!("{:#}", e),
+            }
+
+            progress.advance(to_block - from_block + 1, events_inserted, rpc_latency);
             from_block = to_block + 1;
         }
 
+        progress.finish(current_block);
+
         tracing::info!(
             "Successfully indexed chain '{}' up to block {}",
             group.chain,
@@ -372,33 +1429,320 @@ impl Indexer {
         Ok(())
     }
 
-    /// Check if a log matches a spec's event signature
-    fn log_matches_spec(&self, log: &Log, ir: &IrGenerationResult) -> bool {
-        // The first topic is the event signature hash
-        if log.topics().is_empty() {
-            return false;
+    /// Index the logs of the chain's contracts mentioning a watched address, up to the head
+    async fn index_watchlists(&self, group: &ChainGroup) -> Result<()> {
+        let watchlists = self
+            .config
+            .watchlists
+            .iter()
+            .filter(|watchlist| watchlist.chain == group.chain)
+            .map(Watchlist::from_config)
+            .collect::<Result<Vec<_>>>()?;
+        if watchlists.is_empty() {
+            return Ok(());
+        }
+
+        let provider = self.rate_limits.provider(&group.rpc_url)?;
+        let head = provider
+            .get_block_number()
+            .await
+            .context("Failed to get current block number")
+            .kind(SmortyError::Rpc)?;
+        let contracts = self.watched_contracts(group);
+        let addresses: Vec<Address> = contracts.keys().copied().collect();
+        // A filter without addresses would match the logs of every contract
+        if addresses.is_empty() {
+            return Ok(());
         }
+        let chunk_size = self.config.indexer.chunk_size;
+
+        for watchlist in &watchlists {
+            let key = watchlist.coverage_key();
+            let start = watchlist.start_block.unwrap_or(group.min_start_block);
+            let mut from_block = coverage::load(&self.db_pool, &key)
+                .await?
+                .end()
+                .map_or(start, |end| (end + 1).max(start));
+            if from_block <= head {
+                tracing::info!(
+                    "Indexing watchlist '{}' on chain '{}' from block {} to {}",
+                    watchlist.name,
+                    group.chain,
+                    from_block,
+                    head
+                );
+            }
 
-        let event_signature_hash = self.calculate_event_signature_hash(&ir.event_signature);
-        let log_topic = log.topics()[0];
+            while from_block <= head {
+                let to_block = std::cmp::min(from_block + chunk_size - 1, head);
+                let mut rows = 0;
+                for filter in watchlist.filters(&addresses, from_block, to_block) {
+                    for log in rpc_logs::get_logs(&provider, &filter).await? {
+                        let Some(contract) = contracts.get(&log.address()) else {
+                            continue;
+                        };
+                        let insert = match watchlist.insert_sql(&log, contract) {
+                            Ok(Some(insert)) => insert,
+                            Ok(None) => continue,
+                            Err(e) => {
+                                tracing::warn!(
+                                    "Skipping log for watchlist '{}': {:#}",
+                                    watchlist.name,
+                                    e
+                                );
+                                continue;
+                            }
+                        };
+                        rows += insert
+                            .query()
+                            .execute(&self.db_pool)
+                            .await
+                            .context(format!(
+                                "Failed to insert log into watchlist table {}",
+                                watchlist.table
+                            ))?
+                            .rows_affected();
+                    }
+                }
 
-        event_signature_hash == log_topic
+                if rows > 0
+                    && let Err(e) =
+                        system::record_table_activity(&self.db_pool, &[&watchlist.table]).await
+                {
+                    tracing::warn!("{:#}", e);
+                }
+                coverage::record(&self.db_pool, &key, from_block, to_block).await?;
+                from_block = to_block + 1;
+            }
+        }
+        Ok(())
     }
 
-    /// Calculate the Keccak-256 hash of an event signature
-    fn calculate_event_signature_hash(&self, signature: &str) -> FixedBytes<32> {
-        use alloy::primitives::keccak256;
-        keccak256(signature.as_bytes())
+    /// Index the calls of the functions configured on a chain's contracts
+    async fn index_calls(&self, group: &ChainGroup) -> Result<()> {
+        let mut contracts: Vec<_> = self
+            .config
+            .contracts
+            .iter()
+            .filter(|(_, contract)| contract.chain == group.chain)
+            .collect();
+        contracts.sort_by_key(|(name, _)| *name);
+        let mut call_specs = Vec::new();
+        for (contract_name, contract) in contracts {
+            for call in &contract.calls {
+                call_specs.push(
+                    CallSpec::from_config(contract_name, contract, call)
+                        .kind(SmortyError::Config)?,
+                );
+            }
+        }
+        if call_specs.is_empty() {
+            return Ok(());
+        }
+
+        let provider = self.rate_limits.provider(&group.rpc_url)?;
+        let head = provider
+            .get_block_number()
+            .await
+            .context("Failed to get current block number")
+            .kind(SmortyError::Rpc)?;
+        let chunk_size = self.config.indexer.chunk_size;
+
+        for spec in &call_specs {
+            let key = spec.coverage_key();
+            let mut from_block = coverage::load(&self.db_pool, &key)
+                .await?
+                .end()
+                .map_or(spec.start_block, |end| (end + 1).max(spec.start_block));
+            if from_block <= head {
+                tracing::info!(
+                    "Indexing calls of {}.{} on chain '{}' from block {} to {}",
+                    spec.contract_name,
+                    spec.function.name,
+                    group.chain,
+                    from_block,
+                    head
+                );
+            }
+
+            while from_block <= head {
+                let to_block = std::cmp::min(from_block + chunk_size - 1, head);
+                let mut rows = 0;
+                let calls = spec
+                    .fetch(&provider, from_block, to_block)
+                    .await
+                    .kind(SmortyError::Rpc)?;
+                for call in calls {
+                    let insert = match spec.insert_sql(&call) {
+                        Ok(insert) => insert,
+                        Err(e) => {
+                            tracing::warn!(
+                                "Skipping call in transaction {:#x}: {:#}",
+                                call.transaction_hash,
+                                e
+                            );
+                            continue;
+                        }
+                    };
+                    rows += insert
+                        .query()
+                        .execute(&self.db_pool)
+                        .await
+                        .context(format!("Failed to insert call into table {}", spec.table))?
+                        .rows_affected();
+                }
+
+                if rows > 0
+                    && let Err(e) =
+                        system::record_table_activity(&self.db_pool, &[&spec.table]).await
+                {
+                    tracing::warn!("{:#}", e);
+                }
+                coverage::record(&self.db_pool, &key, from_block, to_block).await?;
+                from_block = to_block + 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Contracts of a chain's specs by address, with the event names of their ABIs
+    fn watched_contracts(&self, group: &ChainGroup) -> HashMap<Address, WatchedContract> {
+        let mut contracts = HashMap::new();
+        for (address, contract_name) in &group.watched {
+            contracts.entry(*address).or_insert_with(|| {
+                let events = self
+                    .config
+                    .contracts
+                    .get(contract_name)
+                    .and_then(|contract| abi::load(Path::new(&contract.abi_path)).ok())
+                    .map(|abi| {
+                        abi.events()
+                            .map(|event| (event.selector(), event.name.clone()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                WatchedContract {
+                    name: contract_name.clone(),
+                    events,
+                }
+            });
+        }
+        contracts
+    }
+
+    /// Blocks indexed for a spec
+    ///
+    /// Tables indexed before ranges were recorded are trusted up to their last event.
+    async fn load_coverage(&self, spec: &IndexSpec) -> Result<BlockRanges> {
+        let source = Ir::source_name(&spec.contract_name, &spec.spec_name);
+        let mut ranges = coverage::load(&self.db_pool, &source).await?;
+        if ranges.end().is_some() {
+            return Ok(ranges);
+        }
+
+        let last_indexed = self
+            .get_last_indexed_block(&spec.ir.table_schema.table_name, spec.source.as_deref())
+            .await?;
+        if last_indexed > 0 {
+            let start = spec.ir.start_block.min(last_indexed);
+            coverage::record(&self.db_pool, &source, start, last_indexed).await?;
+            ranges.insert(start, last_indexed);
+        }
+        Ok(ranges)
+    }
+
+    /// Fetch the logs of a single spec again over a range it missed
+    async fn fill_gap<P: Provider>(
+        &self,
+        provider: &P,
+        spec: &IndexSpec,
+        from: u64,
+        to: u64,
+    ) -> Result<()> {
+        let source = Ir::source_name(&spec.contract_name, &spec.spec_name);
+        let chunk_size = self.config.indexer.chunk_size;
+        let mut from_block = from;
+
+        while from_block <= to {
+            let to_block = std::cmp::min(from_block + chunk_size - 1, to);
+            let filter = Filter::new()
+                .address(spec.address)
+                .event_signature(spec.topic0)
+                .from_block(from_block)
+                .to_block(to_block);
+            let mut logs = LogBuffer::new(1, &self.config.indexer);
+            rpc_logs::stream_logs(provider, &filter, |log| logs.push(0, log)).await?;
+
+            let mut inserted_tables = BTreeSet::new();
+            for entry in logs.drain() {
+                let (_, log) = entry?;
+                match self.process_log(&log, spec).await {
+                    Ok(true) => {
+                        inserted_tables.insert(spec.ir.table_schema.table_name.as_str());
+                    }
+                    Ok(false) => {}
+                    Err(e) => tracing::warn!(
+                        "Skipping log for {}/{} due to error: {:?}",
+                        spec.contract_name,
+                        spec.spec_name,
+                        e
+                    ),
+                }
+            }
+
+            self.finish_chunk(std::iter::once(spec), &inserted_tables, from_block)
+                .await;
+            coverage::record(&self.db_pool, &source, from_block, to_block).await?;
+            from_block = to_block + 1;
+        }
+
+        Ok(())
+    }
+
+    /// Refresh state tables and table activity after a chunk's rows are inserted
+    ///
+    /// Failures are only logged, the next refresh catches up.
+    async fn finish_chunk<'a>(
+        &self,
+        specs: impl Iterator<Item = &'a IndexSpec>,
+        inserted_tables: &BTreeSet<&str>,
+        from_block: u64,
+    ) {
+        // Recompute state tables of specs that received events
+        for spec in specs {
+            let event_table = spec.ir.table_schema.table_name.as_str();
+            if let Some(fold) = &spec.ir.state_fold
+                && inserted_tables.contains(event_table)
+                && let Err(e) = fold.refresh(&self.db_pool, event_table, from_block).await
+            {
+                tracing::warn!("{:#}", e);
+            }
+        }
+
+        let inserted_tables: Vec<&str> = inserted_tables.iter().copied().collect();
+        if let Err(e) = system::record_table_activity(&self.db_pool, &inserted_tables).await {
+            tracing::warn!("{:#}", e);
+        }
     }
 
     /// Get the last indexed block number for a table
-    async fn get_last_indexed_block(&self, table_name: &str) -> Result<u64> {
-        let query = format!(
+    ///
+    /// In a shared table, only rows from `source` are considered, so a spec added
+    /// to the table later still indexes its history.
+    async fn get_last_indexed_block(&self, table_name: &str, source: Option<&str>) -> Result<u64> {
+        let mut query = format!(
             "SELECT COALESCE(MAX(block_number), 0) as max_block FROM {}",
             table_name
         );
+        if source.is_some() {
+            query.push_str(&format!(" WHERE {} = $1", SOURCE_COLUMN));
+        }
 
-        let row = sqlx::query(&query)
+        let mut query = sqlx::query(&query);
+        if let Some(source) = source {
+            query = query.bind(source);
+        }
+        let row = query
             .fetch_one(&self.db_pool)
             .await
             .context("Failed to query last indexed block")?;
@@ -409,7 +1753,98 @@ impl Indexer {
     }
 
     /// Process a single log and insert into database
-    async fn process_log(&self, log: &Log, ir: &IrGenerationResult) -> Result<()> {
+    ///
+    /// Logs the provider marks as `removed` by a reorg delete the row they were
+    /// indexed as instead. Returns `false` if the table was left unchanged.
+    async fn process_log(&self, log: &Log, spec: &IndexSpec) -> Result<bool> {
+        if log.removed {
+            return self.remove_log(log, spec).await;
+        }
+
+        let ir = &spec.ir;
+        let insert_query = self
+            .build_insert_query(log, spec)
+            .kind(SmortyError::Decode)?;
+
+        let inserted = match insert_query.query().execute(&self.db_pool).await {
+            Ok(result) => result.rows_affected() > 0,
+            Err(e) => {
+                return Err(anyhow::anyhow!("Failed to insert log into database: {}", e));
+            }
+        };
+
+        tracing::debug!(
+            "Inserted log for {} at block {} (tx: {:#x})",
+            ir.event_name,
+            log.block_number.unwrap_or_default(),
+            log.transaction_hash.unwrap_or_default()
+        );
+
+        Ok(inserted)
+    }
+
+    /// Delete the row of a log that was removed by a reorg
+    ///
+    /// Upserted rows are deleted too, since the state they replaced can't be restored.
+    async fn remove_log(&self, log: &Log, spec: &IndexSpec) -> Result<bool> {
+        let (Some(tx_hash), Some(log_index)) = (log.transaction_hash, log.log_index) else {
+            anyhow::bail!("Removed log missing transaction hash or log index");
+        };
+        let table_name = &spec.ir.table_schema.table_name;
+        let delete_query = delete_query(table_name, spec.source.is_some());
+
+        let mut delete = sqlx::query(&delete_query)
+            .bind(format!("{:#x}", tx_hash))
+            .bind(log_index as i64);
+        if let Some(source) = &spec.source {
+            delete = delete.bind(source);
+        }
+        let deleted = delete
+            .execute(&self.db_pool)
+            .await
+            .context("Failed to delete removed log from database")?
+            .rows_affected()
+            > 0;
+
+        if deleted && !spec.conflict_keys.is_empty() {
+            tracing::warn!(
+                "Deleted upserted row of {}/{} for removed log (tx: {:#x}), its previous state is not restored",
+                spec.contract_name,
+                spec.spec_name,
+                tx_hash
+            );
+        } else {
+            tracing::debug!(
+                "Removed log for {} at block {} (tx: {:#x})",
+                spec.ir.event_name,
+                log.block_number.unwrap_or_default(),
+                tx_hash
+            );
+        }
+
+        Ok(deleted)
+    }
+
+    /// Decode a log and build the INSERT statement for its table
+    fn build_insert_query(&self, log: &Log, spec: &IndexSpec) -> Result<BoundQuery> {
+        let row = self.build_row(log, spec)?;
+        let table_name = &spec.ir.table_schema.table_name;
+        Ok(BoundQuery {
+            sql: format!(
+                "INSERT INTO {} ({}) VALUES ({}) {}",
+                table_name,
+                row.columns.join(", "),
+                row.expressions.join(", "),
+                on_conflict(table_name, &row.columns, &spec.conflict_keys)
+            ),
+            values: row.values,
+        })
+    }
+
+    /// Columns of the row a log is inserted as, with the values bound to them
+    fn build_row(&self, log: &Log, spec: &IndexSpec) -> Result<InsertRow> {
+        let ir = &spec.ir;
+
         // Get block details - if any are missing, skip this log gracefully
         let block_number = match log.block_number {
             Some(bn) => bn,
@@ -439,9 +1874,9 @@ impl Indexer {
             }
         };
 
-        // Decode event data (returns field name -> value)
-        let decoded_values = match self.decode_event_data(log, ir) {
-            Ok(values) => values,
+        // Decode event data
+        let decoded = match DecodedEvent::decode(log, ir) {
+            Ok(decoded) => decoded,
             Err(e) => {
                 return Err(anyhow::anyhow!("Failed to decode event data: {}", e));
             }
@@ -458,202 +1893,762 @@ impl Indexer {
             }
         };
 
-        // Build a map of field names to their order in indexed_fields
-        let mut field_order: HashMap<&str, usize> = HashMap::new();
-        for (idx, field) in ir.indexed_fields.iter().enumerate() {
-            field_order.insert(&field.name, idx);
-        }
-
         // Build INSERT query using actual column names from schema
-        let mut columns = vec![
-            "block_number".to_string(),
-            "block_timestamp".to_string(),
-            "transaction_hash".to_string(),
-            "log_index".to_string(),
-        ];
-
-        let mut values: Vec<String> = vec![
-            block_number.to_string(),
-            block_timestamp.to_string(),
-            format!("'{:#x}'", tx_hash),
-            log_index.to_string(),
-        ];
-
-        // Add event-specific fields using the column names from migrations/schema.json
-        // Iterate through columns in the schema (excluding standard columns)
-        for column in &table_schema.columns {
-            if !matches!(
-                column.name.as_str(),
-                "id" | "block_number" | "block_timestamp" | "transaction_hash" | "log_index"
-            ) {
-                // Find the corresponding value from decoded_values
-                // We need to match by position since field names might differ
-                let field_idx = columns.len() - 4; // Offset by the 4 standard columns
-                if field_idx < decoded_values.len() {
-                    columns.push(column.name.clone());
-                    values.push(decoded_values[field_idx].1.clone());
-                }
+        let mut row = InsertRow::default();
+        row.push(
+            "block_number",
+            DecodedField::new(
+                "block_number",
+                "int64",
+                DecodedValue::Int(block_number as i64),
+            ),
+        );
+        row.push(
+            "block_timestamp",
+            DecodedField::new(
+                "block_timestamp",
+                "int64",
+                DecodedValue::Int(block_timestamp as i64),
+            ),
+        );
+        row.push(
+            "transaction_hash",
+            DecodedField::new("transaction_hash", "bytes32", DecodedValue::Topic(tx_hash)),
+        );
+        row.push(
+            "log_index",
+            DecodedField::new("log_index", "int64", DecodedValue::Int(log_index as i64)),
+        );
+
+        // Add each event field to the column the IR maps it to, if
+        // migrations/schema.json has that column
+        for (field, decoded) in ir.indexed_fields.iter().zip(decoded.fields) {
+            let column = field
+                .column
+                .as_deref()
+                .context(format!("Field '{}' is not mapped to a column", field.name))?;
+            if table_schema.get_column(column).is_some() {
+                row.push(column, decoded);
             }
         }
 
-        let insert_query = format!(
-            "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT DO NOTHING",
-            ir.table_schema.table_name,
-            columns.join(", "),
-            values.join(", ")
-        );
+        if let Some(source) = &spec.source {
+            row.push(
+                SOURCE_COLUMN,
+                DecodedField::new(
+                    SOURCE_COLUMN,
+                    "string",
+                    DecodedValue::String(source.clone()),
+                ),
+            );
+        }
 
-        match sqlx::query(&insert_query).execute(&self.db_pool).await {
-            Ok(_) => {}
-            Err(e) => {
-                return Err(anyhow::anyhow!("Failed to insert log into database: {}", e));
-            }
+        // Generated columns are computed by Postgres, the rest from this row's values
+        let computed: Vec<(String, String)> = ir
+            .table_schema
+            .computed_columns
+            .iter()
+            .filter(|computed| computed.on_insert)
+            .map(|computed| {
+                let expression =
+                    substitute_columns(&computed.expression, &row.columns, &row.expressions);
+                (computed.name.clone(), expression)
+            })
+            .collect();
+        for (column, expression) in computed {
+            row.columns.push(column);
+            row.expressions.push(expression);
         }
 
-        tracing::debug!(
-            "Inserted log for {} at block {} (tx: {:#x})",
-            ir.event_name,
-            block_number,
-            tx_hash
+        redaction::redact_insert(
+            &self.config.column_policies,
+            &ir.table_schema.table_name,
+            &mut row.columns,
+            &mut row.expressions,
         );
 
-        Ok(())
+        Ok(row)
     }
+}
 
-    /// Decode event data from a log
-    /// This uses alloy's built-in ABI decoding capabilities
-    fn decode_event_data(
-        &self,
-        log: &Log,
-        ir: &IrGenerationResult,
-    ) -> Result<Vec<(String, String)>> {
-        let mut result = Vec::new();
+/// SQL with `$n` placeholders, and the values bound to them from `$1` on
+#[derive(Debug, Clone)]
+pub struct BoundQuery {
+    pub sql: String,
+    pub values: Vec<DecodedField>,
+}
 
-        // Topics: [event_signature, indexed_param_1, indexed_param_2, ...]
-        // Data: concatenated non-indexed parameters
+impl BoundQuery {
+    /// The query with its values bound
+    pub fn query(&self) -> Query<'_, Postgres, PgArguments> {
+        bind_values(sqlx::query(&self.sql), &self.values)
+    }
+}
 
-        let topics = log.topics();
-        let mut topic_index = 1; // Skip first topic (event signature)
+/// Columns of a row to insert, and the SQL of each column's value: the
+/// placeholder of a bound value, or an expression of placeholders
+#[derive(Debug, Clone, Default)]
+pub struct InsertRow {
+    pub columns: Vec<String>,
+    pub expressions: Vec<String>,
+    /// Values bound to the placeholders, from `$1` on
+    pub values: Vec<DecodedField>,
+}
 
-        let data = log.data().data.clone();
-        let mut data_offset = 0;
+impl InsertRow {
+    /// Add a column whose value is bound to the next placeholder
+    pub fn push(&mut self, column: &str, field: DecodedField) {
+        self.expressions
+            .push(placeholder(&field, self.values.len() + 1));
+        self.columns.push(column.to_string());
+        self.values.push(field);
+    }
 
-        for field in &ir.indexed_fields {
-            let value_str = if field.indexed && topic_index < topics.len() {
-                // Indexed field - get from topics
-                let topic = topics[topic_index];
-                topic_index += 1;
+    /// The value of each column as SQL literals, for output that isn't run
+    pub fn literals(&self) -> Vec<String> {
+        self.expressions
+            .iter()
+            .map(|expression| substitute_placeholders(expression, &self.values))
+            .collect()
+    }
+}
 
-                // Format based on Solidity type
-                self.format_topic_value(&topic, &field.solidity_type)?
-            } else {
-                // Non-indexed field - get from data
-                let value =
-                    self.extract_data_value(&data, &mut data_offset, &field.solidity_type)?;
-                value
-            };
+/// Postgres type a decoded value is bound as
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BoundType {
+    Text,
+    /// Integers, bound as their digits and cast to NUMERIC
+    Numeric,
+    Bytes,
+    Bool,
+    BigInt,
+    /// Structs and arrays, bound as JSONB
+    Json,
+}
 
-            result.push((field.name.clone(), value_str));
+impl BoundType {
+    fn of(field: &DecodedField) -> Self {
+        if field.is_json() {
+            return Self::Json;
+        }
+        match &field.value {
+            DecodedValue::Address(_) | DecodedValue::String(_) | DecodedValue::Topic(_) => {
+                Self::Text
+            }
+            DecodedValue::U256(_) | DecodedValue::I256(_) => Self::Numeric,
+            DecodedValue::Bytes(_) => Self::Bytes,
+            DecodedValue::Bool(_) => Self::Bool,
+            DecodedValue::Array(_) => Self::Json,
+            DecodedValue::Int(_) => Self::BigInt,
+            // A NULL has the type of the values the field would otherwise have
+            DecodedValue::Null => match field.solidity_type.as_str() {
+                "bool" => Self::Bool,
+                t if t.starts_with("uint") || t.starts_with("int") => Self::Numeric,
+                t if t.starts_with("bytes") => Self::Bytes,
+                _ => Self::Text,
+            },
         }
+    }
+}
 
-        Ok(result)
+/// Placeholder of the `$index` value, cast to the type it is stored as
+pub(crate) fn placeholder(field: &DecodedField, index: usize) -> String {
+    match BoundType::of(field) {
+        BoundType::Numeric => format!("${}::numeric", index),
+        _ => format!("${}", index),
     }
+}
 
-    /// Format a topic value based on its Solidity type
-    fn format_topic_value(&self, topic: &FixedBytes<32>, solidity_type: &str) -> Result<String> {
-        let value = match solidity_type {
-            "address" => {
-                // Address is stored in last 20 bytes of the topic
-                let bytes = &topic.as_slice()[12..];
-                let addr = Address::from_slice(bytes);
-                format!("'{:#x}'", addr)
-            }
-            "bool" => {
-                // Bool is stored as 0 or 1
-                let is_true = topic.as_slice().iter().any(|&b| b != 0);
-                if is_true {
-                    "true".to_string()
-                } else {
-                    "false".to_string()
-                }
-            }
-            t if t.starts_with("uint") || t.starts_with("int") => {
-                // Integer types - convert to decimal string
-                let value = alloy::primitives::U256::from_be_bytes(topic.0);
-                format!("'{}'", value)
-            }
-            t if t.starts_with("bytes") => {
-                // Fixed-size bytes
-                format!("'{:#x}'", topic)
-            }
-            _ => {
-                // Default: hex representation
-                format!("'{:#x}'", topic)
-            }
+/// Bind decoded values to the next placeholders of a query
+pub(crate) fn bind_values<'q>(
+    mut query: Query<'q, Postgres, PgArguments>,
+    values: &[DecodedField],
+) -> Query<'q, Postgres, PgArguments> {
+    for field in values {
+        let value = &field.value;
+        query = match BoundType::of(field) {
+            BoundType::Json => query.bind(value.to_json()),
+            BoundType::Bool => query.bind(match value {
+                DecodedValue::Bool(b) => Some(*b),
+                _ => None,
+            }),
+            BoundType::BigInt => query.bind(match value {
+                DecodedValue::Int(i) => Some(*i),
+                _ => None,
+            }),
+            BoundType::Bytes => query.bind(match value {
+                DecodedValue::Bytes(bytes) => Some(bytes.to_vec()),
+                _ => None,
+            }),
+            BoundType::Text | BoundType::Numeric => query.bind(match value {
+                DecodedValue::Null => None,
+                DecodedValue::String(s) => Some(s.clone()),
+                _ => value.to_json().as_str().map(str::to_string),
+            }),
         };
+    }
+    query
+}
 
-        Ok(value)
+/// SQL literal a decoded field is stored as, for dry runs and previews
+fn sql_literal(field: &DecodedField) -> String {
+    if field.is_json() {
+        return json_literal(&field.value.to_json());
+    }
+    match &field.value {
+        DecodedValue::Address(a) => format!("'{:#x}'", a),
+        DecodedValue::U256(u) => format!("'{}'", u),
+        DecodedValue::I256(i) => format!("'{}'", i),
+        DecodedValue::Bytes(bytes) => format!("'\\x{}'", hex::encode(bytes)),
+        DecodedValue::Bool(b) => b.to_string(),
+        DecodedValue::String(s) => format!("'{}'", s.replace('\'', "''")),
+        DecodedValue::Array(_) => json_literal(&field.value.to_json()),
+        DecodedValue::Topic(topic) => format!("'{:#x}'", topic),
+        DecodedValue::Null => "NULL".to_string(),
+        DecodedValue::Int(i) => i.to_string(),
     }
+}
 
-    /// Extract a value from the data field
-    fn extract_data_value(
-        &self,
-        data: &[u8],
-        offset: &mut usize,
-        solidity_type: &str,
-    ) -> Result<String> {
-        // All values in data are 32-byte aligned
-        const WORD_SIZE: usize = 32;
-
-        if *offset + WORD_SIZE > data.len() {
-            // Not enough data - return NULL
-            return Ok("NULL".to_string());
-        }
-
-        let word = &data[*offset..*offset + WORD_SIZE];
-        *offset += WORD_SIZE;
-
-        let value = match solidity_type {
-            "address" => {
-                // Address is in last 20 bytes
-                let bytes = &word[12..];
-                let addr = Address::from_slice(bytes);
-                format!("'{:#x}'", addr)
-            }
-            "bool" => {
-                // Bool is in last byte
-                let is_true = word.iter().any(|&b| b != 0);
-                if is_true {
-                    "true".to_string()
-                } else {
-                    "false".to_string()
+/// DELETE statement for the row a log was indexed as, bound to its transaction
+/// hash and log index, and its source when `shared`
+fn delete_query(table_name: &str, shared: bool) -> String {
+    let mut query = format!(
+        "DELETE FROM {} WHERE transaction_hash = $1 AND log_index = $2",
+        table_name
+    );
+    if shared {
+        query.push_str(&format!(" AND {} = $3", SOURCE_COLUMN));
+    }
+    query
+}
+
+/// Conflict clause of an INSERT
+///
+/// Appends skip logs that were already indexed. Upserts overwrite the row with the
+/// same conflict keys, unless it already holds a later event, so reindexing an
+/// older range never rolls the state back.
+fn on_conflict(table_name: &str, columns: &[String], conflict_keys: &[String]) -> String {
+    if conflict_keys.is_empty() {
+        return "ON CONFLICT DO NOTHING".to_string();
+    }
+
+    let updates: Vec<String> = columns
+        .iter()
+        .filter(|column| !conflict_keys.contains(column))
+        .map(|column| format!("{} = EXCLUDED.{}", column, column))
+        .collect();
+    format!(
+        "ON CONFLICT ({}) DO UPDATE SET {} WHERE ({}.block_number, {}.log_index) < (EXCLUDED.block_number, EXCLUDED.log_index)",
+        conflict_keys.join(", "),
+        updates.join(", "),
+        table_name,
+        table_name
+    )
+}
+
+/// Replace references to `columns` in a SQL expression with their values
+///
+/// Quoted strings and identifiers that aren't columns of the row are left alone.
+fn substitute_columns(expression: &str, columns: &[String], values: &[String]) -> String {
+    let mut result = String::with_capacity(expression.len());
+    let mut chars = expression.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c == '\'' {
+            // Copy string literals verbatim, '' is an escaped quote
+            result.push(c);
+            while let Some((_, c)) = chars.next() {
+                result.push(c);
+                if c == '\'' {
+                    if chars.peek().is_some_and(|(_, next)| *next == '\'') {
+                        result.push('\'');
+                        chars.next();
+                    } else {
+                        break;
+                    }
                 }
             }
-            t if t.starts_with("uint") || t.starts_with("int") => {
-                // Integer types
-                let value = alloy::primitives::U256::from_be_slice(word);
-                format!("'{}'", value)
-            }
-            t if t.starts_with("bytes") && t.len() > 5 => {
-                // Fixed-size bytes (bytesN)
-                if let Ok(size) = t[5..].parse::<usize>() {
-                    let bytes = &word[..size];
-                    format!("'\\x{}'", hex::encode(bytes))
-                } else {
-                    format!("'\\x{}'", hex::encode(word))
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = start + c.len_utf8();
+            while let Some((i, c)) = chars.peek().copied() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
                 }
+                end = i + c.len_utf8();
+                chars.next();
             }
-            "string" | "bytes" => {
-                // Dynamic types - would need to handle offsets
-                // For now, return hex representation
-                format!("'\\x{}'", hex::encode(word))
+
+            // Type names after a cast and function names aren't column references
+            let word = &expression[start..end];
+            let is_type = result.ends_with("::");
+            let is_function = chars.peek().is_some_and(|(_, next)| *next == '(');
+            match columns.iter().position(|column| column == word) {
+                Some(idx) if !is_type && !is_function => {
+                    result.push_str(&format!("({})", values[idx]))
+                }
+                _ => result.push_str(word),
             }
-            _ => {
-                // Default: hex representation
-                format!("'\\x{}'", hex::encode(word))
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+/// Replace the placeholders in SQL with the literals of their values
+///
+/// The NUMERIC casts of placeholders are dropped with them, as the literals are
+/// the values the columns were written with.
+fn substitute_placeholders(sql: &str, values: &[DecodedField]) -> String {
+    let mut result = String::with_capacity(sql.len());
+    let mut rest = sql;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let digits = rest[start + 1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len() - start - 1);
+        let field = rest[start + 1..start + 1 + digits]
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| values.get(index.checked_sub(1)?));
+        let Some(field) = field else {
+            result.push('$');
+            rest = &rest[start + 1..];
+            continue;
+        };
+        result.push_str(&sql_literal(field));
+        rest = &rest[start + 1 + digits..];
+        if BoundType::of(field) == BoundType::Numeric {
+            rest = rest.strip_prefix("::numeric").unwrap_or(rest);
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Quote JSON as a SQL string literal for a JSONB column
+fn json_literal(value: &serde_json::Value) -> String {
+    format!("'{}'", value.to_string().replace('\'', "''"))
+}
+
+/// Fail or clamp the specs whose next block to fetch, in `next_blocks`, the RPC no longer serves
+async fn check_served_history<P: Provider>(
+    provider: &P,
+    group: &mut ChainGroup,
+    next_blocks: &[u64],
+    pruned_history: PrunedHistory,
+) -> Result<()> {
+    let Some(from) = next_blocks.iter().min().copied() else {
+        return Ok(());
+    };
+    let earliest = match provider.get_block_number().await {
+        Ok(head) if from <= head => history::earliest_block(provider, from, head).await,
+        Ok(_) => return Ok(()),
+        Err(e) => Err(e.into()),
+    };
+    let earliest = match earliest {
+        Ok(Some(earliest)) if earliest == from => return Ok(()),
+        Ok(Some(earliest)) => earliest,
+        Ok(None) => {
+            return Err(SmortyError::Rpc(anyhow::anyhow!(
+                "RPC for chain '{}' doesn't serve the chain head",
+                group.chain
+            ))
+            .into());
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Failed to check the history served by the RPC of chain '{}': {:#}",
+                group.chain,
+                e
+            );
+            return Ok(());
+        }
+    };
+
+    let pruned: Vec<usize> = (0..group.specs.len())
+        .filter(|&idx| next_blocks[idx] < earliest)
+        .collect();
+    let names = pruned
+        .iter()
+        .map(|&idx| {
+            let spec = &group.specs[idx];
+            format!(
+                "{}/{} (block {})",
+                spec.contract_name, spec.spec_name, next_blocks[idx]
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    match pruned_history {
+        PrunedHistory::Fail => Err(SmortyError::Config(anyhow::anyhow!(
+            "RPC for chain '{}' only serves blocks from {}, so logs of {} would be missing. \
+             Use an archive node in [chains], or set prunedHistory = \"clamp\" under [indexer] \
+             to start these specs at block {}",
+            group.chain,
+            earliest,
+            names,
+            earliest
+        ))
+        .into()),
+        PrunedHistory::Clamp => {
+            tracing::warn!(
+                "RPC for chain '{}' only serves blocks from {}, starting {} there",
+                group.chain,
+                earliest,
+                names
+            );
+            for idx in pruned {
+                group.specs[idx].ir.start_block = earliest;
             }
+            group.min_start_block = group.min_start_block.max(earliest);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::TableSchema;
+    use alloy::primitives::{LogData, U256};
+
+    fn create_spec(spec_name: &str, event_signature: &str, address: &str) -> IndexSpec {
+        let ir = IrGenerationResult {
+            event_name: spec_name.to_string(),
+            event_signature: event_signature.to_string(),
+            start_block: 100,
+            contract_address: address.to_string(),
+            chain: "mainnet".to_string(),
+            indexed_fields: vec![],
+            table_schema: TableSchema {
+                table_name: spec_name.to_lowercase(),
+                columns: vec![],
+                indexes: vec![],
+                computed_columns: vec![],
+            },
+            description: String::new(),
+            abi_hash: None,
+            state_fold: None,
+        };
+        IndexSpec::new("Token".to_string(), spec_name.to_string(), ir).unwrap()
+    }
+
+    #[test]
+    fn test_drops_paused_specs_and_chains() {
+        let token = "0x0000000000000000000000000000000000000001";
+        let mut transfer = create_spec("Transfer", "Transfer(address,address,uint256)", token);
+        transfer.ir.start_block = 50;
+        let group = ChainGroup::new(
+            "mainnet".to_string(),
+            "http://localhost:8545".to_string(),
+            vec![
+                transfer,
+                create_spec("Approval", "Approval(address,address,uint256)", token),
+            ],
+        );
+        let pause = |target: &str| {
+            (
+                target.to_string(),
+                Pause {
+                    target: target.to_string(),
+                    reason: None,
+                    paused_at: chrono::Utc::now(),
+                },
+            )
         };
 
-        Ok(value)
+        let pauses = HashMap::from([pause("Token/Transfer"), pause("base")]);
+        assert_eq!(group.paused(&pauses), ["Token/Transfer"]);
+        let unpaused = group.unpaused(&pauses).unwrap();
+        assert_eq!(unpaused.specs.len(), 1);
+        assert_eq!(unpaused.specs[0].spec_name, "Approval");
+        assert_eq!(unpaused.min_start_block, 50);
+        assert_eq!(unpaused.watched, group.watched);
+        assert_eq!(
+            unpaused.spec_index(&Log {
+                inner: alloy::primitives::Log {
+                    address: Address::from_str(token).unwrap(),
+                    data: LogData::new_unchecked(
+                        vec![keccak256("Approval(address,address,uint256)")],
+                        Default::default()
+                    ),
+                },
+                ..Default::default()
+            }),
+            Some(0)
+        );
+
+        let pauses = HashMap::from([pause("mainnet")]);
+        assert_eq!(group.paused(&pauses), ["mainnet"]);
+        assert!(group.unpaused(&pauses).is_none());
+        assert_eq!(group.unpaused(&HashMap::new()).unwrap().specs.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_checks_history_from_where_specs_resume() {
+        use alloy::providers::ProviderBuilder;
+        use serde_json::json;
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+        // Head at 5000, blocks before 1000 are pruned
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(|request: &Request| {
+                let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                let result = if body["method"] == "eth_blockNumber" {
+                    json!("0x1388")
+                } else {
+                    let hex = body["params"][0].as_str().unwrap();
+                    let block = u64::from_str_radix(hex.trim_start_matches("0x"), 16).unwrap();
+                    if block < 1000 {
+                        json!(null)
+                    } else {
+                        json!({"number": hex, "timestamp": "0x65f0a000"})
+                    }
+                };
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"jsonrpc": "2.0", "id": body["id"], "result": result}))
+            })
+            .mount(&server)
+            .await;
+        let provider = ProviderBuilder::new().connect_http(server.uri().parse().unwrap());
+
+        let token = "0x0000000000000000000000000000000000000001";
+        let group = ChainGroup::new(
+            "mainnet".to_string(),
+            server.uri(),
+            vec![
+                create_spec("Transfer", "Transfer(address,address,uint256)", token),
+                create_spec("Approval", "Approval(address,address,uint256)", token),
+            ],
+        );
+
+        // Both specs already indexed past the pruned blocks
+        let mut caught_up = group.clone();
+        check_served_history(
+            &provider,
+            &mut caught_up,
+            &[2000, 1500],
+            PrunedHistory::Fail,
+        )
+        .await
+        .unwrap();
+        assert_eq!(caught_up.specs[0].ir.start_block, 100);
+        assert_eq!(caught_up.specs[1].ir.start_block, 100);
+
+        // Approval hasn't been indexed yet
+        let mut fresh = group.clone();
+        assert!(
+            check_served_history(&provider, &mut fresh, &[2000, 100], PrunedHistory::Fail)
+                .await
+                .is_err()
+        );
+        check_served_history(&provider, &mut fresh, &[2000, 100], PrunedHistory::Clamp)
+            .await
+            .unwrap();
+        assert_eq!(fresh.specs[0].ir.start_block, 100);
+        assert_eq!(fresh.specs[1].ir.start_block, 1000);
+        assert_eq!(fresh.min_start_block, 1000);
+    }
+
+    #[test]
+    fn test_watches_contracts_of_paused_specs() {
+        let token = "0x0000000000000000000000000000000000000001";
+        let pool = "0x0000000000000000000000000000000000000002";
+        let mut swap = create_spec("Swap", "Swap(address,uint256)", pool);
+        swap.contract_name = "Pool".to_string();
+        let group = ChainGroup::new(
+            "mainnet".to_string(),
+            "http://localhost:8545".to_string(),
+            vec![
+                create_spec("Transfer", "Transfer(address,address,uint256)", token),
+                swap,
+            ],
+        );
+        let pauses = HashMap::from([(
+            "Pool/Swap".to_string(),
+            Pause {
+                target: "Pool/Swap".to_string(),
+                reason: None,
+                paused_at: chrono::Utc::now(),
+            },
+        )]);
+
+        // The pool's only spec is paused, but watchlist coverage still advances over it
+        let unpaused = group.unpaused(&pauses).unwrap();
+        assert_eq!(unpaused.active_addresses(&[true]).len(), 1);
+        assert_eq!(
+            unpaused.watched.into_iter().collect::<Vec<_>>(),
+            [
+                (Address::from_str(token).unwrap(), "Token".to_string()),
+                (Address::from_str(pool).unwrap(), "Pool".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dispatches_logs_by_address_and_topic0() {
+        let token = "0x0000000000000000000000000000000000000001";
+        let other = "0x0000000000000000000000000000000000000002";
+        let group = ChainGroup::new(
+            "mainnet".to_string(),
+            "http://localhost:8545".to_string(),
+            vec![
+                create_spec("Transfer", "Transfer(address,address,uint256)", token),
+                create_spec("Approval", "Approval(address,address,uint256)", token),
+                create_spec("Sync", "Sync(uint112,uint112)", other),
+            ],
+        );
+        assert_eq!(group.active_addresses(&[true; 3]).len(), 2);
+        assert_eq!(group.active_addresses(&[false, false, true]).len(), 1);
+        assert_eq!(group.min_start_block, 100);
+
+        let log = |address: &str, topics: Vec<B256>| Log {
+            inner: alloy::primitives::Log {
+                address: Address::from_str(address).unwrap(),
+                data: LogData::new_unchecked(topics, Default::default()),
+            },
+            ..Default::default()
+        };
+        let approval = keccak256("Approval(address,address,uint256)");
+
+        assert_eq!(group.spec_index(&log(token, vec![approval])), Some(1));
+        assert_eq!(
+            group.spec_index(&log(other, vec![keccak256("Sync(uint112,uint112)")])),
+            Some(2)
+        );
+        assert_eq!(group.spec_index(&log(other, vec![approval])), None);
+        assert_eq!(group.spec_index(&log(token, vec![])), None);
+    }
+
+    #[test]
+    fn test_bounded_specs_stop_at_their_end_block() {
+        let token = "0x0000000000000000000000000000000000000001";
+        let open = create_spec("Transfer", "Transfer(address,address,uint256)", token);
+        assert_eq!(open.last_block(500), 500);
+        assert!(!open.is_complete(Some(500)));
+
+        let bounded = open.with_end_block(Some(200));
+        assert_eq!(bounded.last_block(150), 150);
+        assert_eq!(bounded.last_block(500), 200);
+        assert!(!bounded.is_complete(None));
+        assert!(!bounded.is_complete(Some(199)));
+        assert!(bounded.is_complete(Some(200)));
+    }
+
+    #[test]
+    fn test_substitutes_row_values_into_computed_expressions() {
+        let columns = ["block_timestamp", "amount", "date"].map(String::from);
+        let values = ["1700000000", "'1500000000000000000'", "'x'"].map(String::from);
+
+        assert_eq!(
+            substitute_columns("amount / 1e18", &columns, &values),
+            "('1500000000000000000') / 1e18"
+        );
+        assert_eq!(
+            substitute_columns(
+                "(to_timestamp(block_timestamp) AT TIME ZONE 'UTC')::date",
+                &columns,
+                &values
+            ),
+            "(to_timestamp((1700000000)) AT TIME ZONE 'UTC')::date"
+        );
+        // Column names inside string literals are not references
+        assert_eq!(
+            substitute_columns("'amount''s' || date", &columns, &values),
+            "'amount''s' || ('x')"
+        );
+    }
+
+    #[test]
+    fn test_upserts_keep_the_latest_event() {
+        let columns = ["block_number", "log_index", "pool", "fee"].map(String::from);
+
+        assert_eq!(on_conflict("fees", &columns, &[]), "ON CONFLICT DO NOTHING");
+        assert_eq!(
+            on_conflict("fees", &columns, &["pool".to_string()]),
+            "ON CONFLICT (pool) DO UPDATE SET block_number = EXCLUDED.block_number, \
+             log_index = EXCLUDED.log_index, fee = EXCLUDED.fee \
+             WHERE (fees.block_number, fees.log_index) < (EXCLUDED.block_number, EXCLUDED.log_index)"
+        );
+    }
+
+    #[test]
+    fn test_removed_logs_delete_their_row() {
+        assert_eq!(
+            delete_query("transfers", false),
+            "DELETE FROM transfers WHERE transaction_hash = $1 AND log_index = $2"
+        );
+        assert_eq!(
+            delete_query("transfers", true),
+            "DELETE FROM transfers WHERE transaction_hash = $1 AND log_index = $2 \
+             AND source = $3"
+        );
+    }
+
+    #[test]
+    fn test_binds_row_values_to_placeholders() {
+        let mut row = InsertRow::default();
+        row.push(
+            "block_number",
+            DecodedField::new("block_number", "int64", DecodedValue::Int(42)),
+        );
+        row.push(
+            "memo",
+            DecodedField::new("memo", "string", DecodedValue::String("it's".to_string())),
+        );
+        row.push(
+            "fee",
+            DecodedField::new("fee", "uint24", DecodedValue::Null),
+        );
+        row.columns.push("block_day".to_string());
+        row.expressions.push(substitute_columns(
+            "block_number / 7200",
+            &row.columns,
+            &row.expressions,
+        ));
+
+        assert_eq!(row.expressions, ["$1", "$2", "$3::numeric", "($1) / 7200"]);
+        // Dry runs show the values as the literals they used to be inserted as
+        assert_eq!(row.literals(), ["42", "'it''s'", "NULL", "(42) / 7200"]);
+    }
+
+    #[test]
+    fn test_renders_decoded_fields_as_sql_literals() {
+        let field = |solidity_type: &str, value: DecodedValue| DecodedField {
+            name: "value".to_string(),
+            solidity_type: solidity_type.to_string(),
+            value,
+        };
+
+        assert_eq!(
+            sql_literal(&field("uint256", DecodedValue::U256(U256::from(7)))),
+            "'7'"
+        );
+        assert_eq!(
+            sql_literal(&field("bytes4", DecodedValue::Bytes(vec![0xab; 4].into()))),
+            "'\\xabababab'"
+        );
+        assert_eq!(
+            sql_literal(&field(
+                "bytes32",
+                DecodedValue::Topic(B256::repeat_byte(0x01))
+            )),
+            format!("'{:#x}'", B256::repeat_byte(0x01))
+        );
+        assert_eq!(sql_literal(&field("bool", DecodedValue::Null)), "NULL");
+        assert_eq!(
+            sql_literal(&field(
+                "(string,bool)",
+                DecodedValue::Array(vec![
+                    DecodedValue::String("it's".to_string()),
+                    DecodedValue::Bool(true),
+                ])
+            )),
+            "'[\"it''s\",true]'"
+        );
     }
 }
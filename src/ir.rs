@@ -1,30 +1,158 @@
-use crate::ai::{AiClient, EndpointIrResult, IrGenerationResult};
-use crate::config::{Config, ContractConfig, EndpointConfig, SpecConfig};
+use crate::abi;
+use crate::ai::{AiClient, ColumnDef, EndpointIrResult, IrGenerationResult};
+use crate::config::{
+    Config, ContractConfig, EndpointConfig, HookConfig, SpecConfig, StateConfig, WriteMode,
+};
+use crate::endpoint_files::{self, MANIFEST_FILE};
+use crate::endpoint_path;
+use crate::endpoint_test::EndpointTest;
+use crate::field_mapping;
+use crate::hooks::{self, GeneratedIr, IrValidator, ShellHook};
+use crate::identifier;
+use crate::index_def::IndexDef;
+use crate::ir_diff::IrDiff;
+use crate::search::{self, SearchIndex};
+use crate::sql_guard;
+use crate::state_table::StateFold;
+use crate::table_relevance;
+use crate::validate;
 use anyhow::{Context, Result};
 use serde_json::Value;
+use sqlx::PgPool;
 use std::fs;
+use std::io::{BufRead, IsTerminal, Write};
 use std::path::Path;
 
+/// Directory of spec IRs, one subdirectory per contract
+pub const SPECS_DIR: &str = "ir/specs";
+
+/// Directory of endpoint IRs
+pub const ENDPOINTS_DIR: &str = "ir/endpoints";
+
+/// Column of shared tables holding the spec each row came from, as `<contract>/<spec>`
+pub const SOURCE_COLUMN: &str = "source";
+
 pub struct Ir {
     ai_client: AiClient,
+    /// Overwrite existing IR files without asking for confirmation
+    assume_yes: bool,
+    /// Number of existing IR files to include as few-shot examples
+    few_shot_examples: usize,
+    /// Maximum number of spec tables described in endpoint prompts (0 includes all)
+    endpoint_tables: usize,
+    /// Only regenerate specs whose contract ABI changed, see `validate::SpecStatus`
+    changed_only: bool,
+    /// Only generate specs of these contracts, or of every contract when empty
+    contracts: Vec<String>,
+    /// Checks each generated IR must pass before it is saved
+    validators: Vec<Box<dyn IrValidator>>,
 }
 
 impl Ir {
     pub fn new(ai_client: AiClient) -> Self {
-        Self { ai_client }
+        Self {
+            ai_client,
+            assume_yes: false,
+            few_shot_examples: 0,
+            endpoint_tables: 0,
+            changed_only: false,
+            contracts: Vec::new(),
+            validators: Vec::new(),
+        }
+    }
+
+    /// Include up to `count` existing IR files as examples in generation prompts
+    pub fn with_few_shot_examples(mut self, count: usize) -> Self {
+        self.few_shot_examples = count;
+        self
+    }
+
+    /// Only describe the `count` spec tables most relevant to each endpoint in its prompt
+    pub fn with_endpoint_tables(mut self, count: usize) -> Self {
+        self.endpoint_tables = count;
+        self
+    }
+
+    /// Reject generated IRs that fail `validator`
+    pub fn with_validator(mut self, validator: impl IrValidator + 'static) -> Self {
+        self.validators.push(Box::new(validator));
+        self
     }
 
-    /// Generate IR for all contracts in the config
-    pub async fn generate_all(&self, config: &Config) -> Result<()> {
+    /// Run the `[[hooks]]` shell commands on each generated IR
+    pub fn with_hooks(mut self, hooks: &[HookConfig]) -> Self {
+        for hook in hooks {
+            self = self.with_validator(ShellHook::new(hook.clone()));
+        }
+        self
+    }
+
+    /// Skip the interactive review when regenerated IR differs from the existing file
+    pub fn with_assume_yes(mut self, assume_yes: bool) -> Self {
+        self.assume_yes = assume_yes;
+        self
+    }
+
+    /// Only generate specs that have no IR yet or whose contract ABI changed since generation
+    pub fn with_changed_only(mut self, changed_only: bool) -> Self {
+        self.changed_only = changed_only;
+        self
+    }
+
+    /// Only generate specs of the named contracts
+    pub fn with_contracts(mut self, contracts: Vec<String>) -> Self {
+        self.contracts = contracts;
+        self
+    }
+
+    /// Generate IR for all contracts in the config, returning the number of specs generated
+    pub async fn generate_all(&self, config: &Config) -> Result<usize> {
         tracing::info!("Starting IR generation for all contracts");
 
+        let mut generated = 0;
         for (contract_name, contract_config) in &config.contracts {
+            if !self.contracts.is_empty() && !self.contracts.contains(contract_name) {
+                continue;
+            }
             tracing::info!("Generating IR for contract: {}", contract_name);
-            self.generate_contract(contract_name, contract_config)
+            generated += self
+                .generate_contract(contract_name, contract_config)
                 .await?;
         }
 
         tracing::info!("IR generation complete");
+        if generated > 0 {
+            self.update_search_index(config).await;
+        }
+        Ok(generated)
+    }
+
+    /// Refresh the embeddings behind `/api/_search`
+    ///
+    /// Search is optional, so failures are logged rather than failing generation.
+    async fn update_search_index(&self, config: &Config) {
+        if let Err(e) = self.try_update_search_index(config).await {
+            tracing::warn!("Failed to update the search index: {:#}", e);
+        }
+    }
+
+    async fn try_update_search_index(&self, config: &Config) -> Result<()> {
+        let specs: Vec<IrGenerationResult> = Self::load_all_ir_specs(config)?
+            .into_iter()
+            .map(|(_, _, ir)| ir)
+            .collect();
+        let endpoints = Self::load_all_ir_endpoints()?;
+
+        let mut documents = search::Document::tables(&specs);
+        documents.extend(search::Document::endpoints(&endpoints));
+
+        let path = Path::new(search::EMBEDDINGS_FILE);
+        let mut index = SearchIndex::load(path)?;
+        let embedded = index.update(&self.ai_client, &documents).await?;
+        index.save(path)?;
+        if embedded > 0 {
+            tracing::info!("Embedded {} tables and endpoints for search", embedded);
+        }
         Ok(())
     }
 
@@ -33,27 +161,60 @@ impl Ir {
         &self,
         contract_name: &str,
         contract_config: &ContractConfig,
-    ) -> Result<()> {
-        // Load ABI
-        let abi_content = fs::read_to_string(&contract_config.abi_path).context(format!(
-            "Failed to read ABI file: {}",
-            contract_config.abi_path
-        ))?;
-
-        let abi: Value = serde_json::from_str(&abi_content).context("Failed to parse ABI JSON")?;
+    ) -> Result<usize> {
+        let abi = validate::read_abi(&contract_config.abi_path)?;
+        let abi_hash = abi::abi_hash(&abi);
 
         // Generate IR for each spec
+        let mut generated = 0;
         for spec in &contract_config.specs {
+            if self.changed_only {
+                let existing = Self::find_ir_spec(contract_name, &spec.name)?;
+                let status = validate::spec_status(existing.as_ref(), &abi);
+                if !status.needs_regeneration() {
+                    tracing::info!("  Skipping spec {}: {}", spec.name, status);
+                    continue;
+                }
+            }
+
             tracing::info!("  Generating spec: {}", spec.name);
-            let ir = self
-                .generate_spec(contract_name, &contract_config, spec, &abi)
+            let examples = Self::load_spec_examples(
+                Path::new(SPECS_DIR),
+                contract_name,
+                &spec.name,
+                self.few_shot_examples,
+            );
+            let mut ir = self
+                .generate_spec(contract_name, contract_config, spec, &abi, &examples)
                 .await?;
+            ir.abi_hash = Some(abi_hash.clone());
+            if let Some(state) = spec.state.as_ref().filter(|state| state.fold.is_none()) {
+                let task = contract_config.state_task(contract_name, spec)?;
+                let fold = self
+                    .ai_client
+                    .generate_state_fold(&state.table, task.as_deref().unwrap_or_default(), &ir)
+                    .await
+                    .context(format!(
+                        "Failed to generate state fold for spec: {}",
+                        spec.name
+                    ))?;
+                ir.state_fold = Some(fold);
+            }
+            hooks::run_all(
+                &self.validators,
+                GeneratedIr::Spec {
+                    contract_name,
+                    spec_name: &spec.name,
+                    ir: &ir,
+                },
+            )?;
 
             // Save spec IR to file
             self.save_ir_spec(contract_name, spec, &ir)?;
+            generated += 1;
         }
 
-        Ok(())
+        Ok(generated)
     }
 
     /// Generate IR for a single spec
@@ -63,17 +224,20 @@ impl Ir {
         contract: &ContractConfig,
         spec: &SpecConfig,
         abi: &Value,
+        examples: &[IrGenerationResult],
     ) -> Result<IrGenerationResult> {
         let ir = self
             .ai_client
-            .generate_ir(
+            .generate_ir_with_examples(
                 contract_name,
                 &spec.name,
-                spec.start_block,
+                // Relative starts are resolved by the indexer on its first run
+                spec.start_block.and_then(|start| start.block()),
                 contract.address.as_str(),
                 contract.chain.as_str(),
                 abi,
-                &spec.task,
+                &contract.spec_task(contract_name, spec)?,
+                examples,
             )
             .await
             .context(format!("Failed to generate IR for spec: {}", spec.name))?;
@@ -81,6 +245,91 @@ impl Ir {
         Ok(ir)
     }
 
+    /// Pick existing spec IRs to use as few-shot examples
+    ///
+    /// Specs from the same contract are preferred, then the rest of the project in
+    /// directory order. The spec being generated is never included, and files that
+    /// fail to parse are skipped.
+    fn load_spec_examples(
+        base_dir: &Path,
+        contract_name: &str,
+        spec_name: &str,
+        limit: usize,
+    ) -> Vec<IrGenerationResult> {
+        if limit == 0 || !base_dir.exists() {
+            return Vec::new();
+        }
+
+        let mut candidates = Vec::new();
+        let mut contract_dirs: Vec<_> = fs::read_dir(base_dir)
+            .map(|entries| entries.flatten().map(|e| e.path()).collect())
+            .unwrap_or_default();
+        contract_dirs.sort();
+
+        for contract_dir in contract_dirs.iter().filter(|p| p.is_dir()) {
+            let same_contract =
+                contract_dir.file_name().and_then(|n| n.to_str()) == Some(contract_name);
+
+            let mut files: Vec<_> = fs::read_dir(contract_dir)
+                .map(|entries| entries.flatten().map(|e| e.path()).collect())
+                .unwrap_or_default();
+            files.sort();
+
+            for file in files {
+                if file.extension().and_then(|s| s.to_str()) != Some("json") {
+                    continue;
+                }
+                if same_contract && file.file_stem().and_then(|s| s.to_str()) == Some(spec_name) {
+                    continue;
+                }
+
+                match fs::read_to_string(&file)
+                    .ok()
+                    .and_then(|content| serde_json::from_str::<IrGenerationResult>(&content).ok())
+                {
+                    Some(ir) => candidates.push((!same_contract, ir)),
+                    None => tracing::debug!("Skipping unreadable IR example: {:?}", file),
+                }
+            }
+        }
+
+        // Stable sort keeps directory order within each group
+        candidates.sort_by_key(|(other_contract, _)| *other_contract);
+        candidates
+            .into_iter()
+            .take(limit)
+            .map(|(_, ir)| ir)
+            .collect()
+    }
+
+    /// Pick existing endpoint IRs to use as few-shot examples
+    fn load_endpoint_examples(
+        base_dir: &Path,
+        endpoint_path: &str,
+        limit: usize,
+    ) -> Vec<EndpointIrResult> {
+        if limit == 0 || !base_dir.exists() {
+            return Vec::new();
+        }
+
+        let mut files: Vec<_> = fs::read_dir(base_dir)
+            .map(|entries| entries.flatten().map(|e| e.path()).collect())
+            .unwrap_or_default();
+        files.sort();
+
+        files
+            .iter()
+            .filter(|file| file.extension().and_then(|s| s.to_str()) == Some("json"))
+            .filter_map(|file| {
+                fs::read_to_string(file)
+                    .ok()
+                    .and_then(|content| serde_json::from_str::<EndpointIrResult>(&content).ok())
+            })
+            .filter(|ir| ir.endpoint_path != endpoint_path)
+            .take(limit)
+            .collect()
+    }
+
     /// Save spec IR to file in the ir/specs/ directory
     fn save_ir_spec(
         &self,
@@ -88,7 +337,7 @@ impl Ir {
         spec: &SpecConfig,
         ir: &IrGenerationResult,
     ) -> Result<()> {
-        self.save_ir_spec_to_dir(Path::new("ir/specs"), contract_name, spec, ir)
+        self.save_ir_spec_to_dir(Path::new(SPECS_DIR), contract_name, spec, ir)
     }
 
     /// Save spec IR to a specific directory (used for testing)
@@ -99,6 +348,13 @@ impl Ir {
         spec: &SpecConfig,
         ir: &IrGenerationResult,
     ) -> Result<()> {
+        let mut ir = ir.clone();
+        identifier::sanitize_spec(&mut ir).context(format!(
+            "Spec {}/{} has invalid table or column names",
+            contract_name, spec.name
+        ))?;
+        let ir = &ir;
+
         // Create ir directory if it doesn't exist
         if !base_dir.exists() {
             fs::create_dir_all(base_dir).context("Failed to create ir directory")?;
@@ -115,6 +371,22 @@ impl Ir {
 
         // Save IR as JSON
         let ir_file = contract_dir.join(format!("{}.json", spec.name));
+
+        // Review changes against the existing IR before overwriting it
+        if ir_file.exists() {
+            let existing: IrGenerationResult = serde_json::from_str(
+                &fs::read_to_string(&ir_file)
+                    .context(format!("Failed to read IR file: {:?}", ir_file))?,
+            )
+            .context(format!("Failed to parse existing IR file: {:?}", ir_file))?;
+
+            let diff = IrDiff::compute_spec(&existing, ir);
+            if !self.confirm_overwrite(&ir_file, &diff)? {
+                tracing::info!("    Kept existing IR: {:?}", ir_file);
+                return Ok(());
+            }
+        }
+
         let ir_json = serde_json::to_string_pretty(ir).context("Failed to serialize IR")?;
 
         fs::write(&ir_file, ir_json).context(format!("Failed to write IR file: {:?}", ir_file))?;
@@ -126,7 +398,7 @@ impl Ir {
 
     /// Load spec IR from file in the ir/specs/ directory
     pub fn load_ir_spec(contract_name: &str, spec_name: &str) -> Result<IrGenerationResult> {
-        let ir_file = Path::new("ir/specs")
+        let ir_file = Path::new(SPECS_DIR)
             .join(contract_name)
             .join(format!("{}.json", spec_name));
 
@@ -139,13 +411,53 @@ impl Ir {
         Ok(ir)
     }
 
+    /// Load spec IR from file, or `None` if it hasn't been generated yet
+    pub fn find_ir_spec(
+        contract_name: &str,
+        spec_name: &str,
+    ) -> Result<Option<IrGenerationResult>> {
+        let ir_file = Path::new(SPECS_DIR)
+            .join(contract_name)
+            .join(format!("{}.json", spec_name));
+        if !ir_file.exists() {
+            return Ok(None);
+        }
+
+        Self::load_ir_spec(contract_name, spec_name).map(Some)
+    }
+
     /// Load all spec IR files
+    ///
+    /// Specs with a shared `table` get that table name and its source column.
     pub fn load_all_ir_specs(config: &Config) -> Result<Vec<(String, String, IrGenerationResult)>> {
         let mut results = Vec::new();
 
         for (contract_name, contract_config) in &config.contracts {
             for spec in &contract_config.specs {
-                let ir = Self::load_ir_spec(contract_name, &spec.name)?;
+                let mut ir = Self::load_ir_spec(contract_name, &spec.name)?;
+                identifier::check_spec(&ir)
+                    .context(format!("Invalid names in {}/{}", contract_name, spec.name))?;
+                field_mapping::map_columns(&mut ir).context(format!(
+                    "Invalid field columns for {}/{}",
+                    contract_name, spec.name
+                ))?;
+                if let Some(table) = &spec.table {
+                    Self::share_table(&mut ir, table).context(format!(
+                        "Failed to share table '{}' with {}/{}",
+                        table, contract_name, spec.name
+                    ))?;
+                }
+                match &spec.state {
+                    Some(state) => Self::resolve_state_fold(&mut ir, state)
+                        .context(format!("Invalid state for {}/{}", contract_name, spec.name))?,
+                    None => ir.state_fold = None,
+                }
+                if spec.write_mode == WriteMode::Upsert {
+                    Self::add_conflict_index(&mut ir, &spec.conflict_keys).context(format!(
+                        "Invalid conflictKeys for {}/{}",
+                        contract_name, spec.name
+                    ))?;
+                }
                 results.push((contract_name.clone(), spec.name.clone(), ir));
             }
         }
@@ -153,6 +465,75 @@ impl Ir {
         Ok(results)
     }
 
+    /// Set the state fold from its template, or check the one generated by `gen-spec`
+    fn resolve_state_fold(ir: &mut IrGenerationResult, state: &StateConfig) -> Result<()> {
+        let mut fold = match state.fold {
+            Some(_) => StateFold::from_template(state, ir)?,
+            None => ir
+                .state_fold
+                .clone()
+                .context("The state fold hasn't been generated yet, run gen-spec")?,
+        };
+        fold.table_name = state.table.clone();
+        fold.validate(&ir.table_schema.table_name)?;
+        ir.state_fold = Some(fold);
+        Ok(())
+    }
+
+    /// Add the unique index upserts conflict on
+    fn add_conflict_index(ir: &mut IrGenerationResult, conflict_keys: &[String]) -> Result<()> {
+        for key in conflict_keys {
+            if !ir
+                .table_schema
+                .columns
+                .iter()
+                .any(|column| &column.name == key)
+            {
+                anyhow::bail!(
+                    "'{}' is not a column of table '{}'",
+                    key,
+                    ir.table_schema.table_name
+                );
+            }
+        }
+
+        let keys: Vec<&str> = conflict_keys.iter().map(String::as_str).collect();
+        let mut index = IndexDef::new("idx_conflict_keys", &keys);
+        index.unique = true;
+        ir.table_schema.indexes.push(index);
+        Ok(())
+    }
+
+    /// Point an IR at a table shared with other specs, adding the indexed source column
+    fn share_table(ir: &mut IrGenerationResult, table: &str) -> Result<()> {
+        if ir
+            .table_schema
+            .columns
+            .iter()
+            .any(|column| column.name == SOURCE_COLUMN)
+        {
+            anyhow::bail!(
+                "Column '{}' is reserved in shared tables, rename it in the IR",
+                SOURCE_COLUMN
+            );
+        }
+
+        ir.table_schema.table_name = table.to_string();
+        ir.table_schema.columns.push(ColumnDef {
+            name: SOURCE_COLUMN.to_string(),
+            column_type: "TEXT NOT NULL".to_string(),
+        });
+        ir.table_schema
+            .indexes
+            .push(IndexDef::new("idx_source", &[SOURCE_COLUMN]));
+        Ok(())
+    }
+
+    /// Value of the source column for rows of a spec writing to a shared table
+    pub fn source_name(contract_name: &str, spec_name: &str) -> String {
+        format!("{}/{}", contract_name, spec_name)
+    }
+
     /// Generate IR for all endpoints in the config
     pub async fn generate_all_endpoints(&self, config: &Config) -> Result<()> {
         tracing::info!("Starting endpoint IR generation");
@@ -161,6 +542,9 @@ impl Ir {
         let spec_irs = Self::load_all_ir_specs(config)?;
         let spec_irs_ref: Vec<_> = spec_irs.iter().map(|(_, _, ir)| ir.clone()).collect();
 
+        // Database is optional here, it is only used to record test fixture query plans
+        let db_pool = EndpointTest::try_connect(&config.database.uri).await;
+
         for (index, endpoint_config) in config.endpoints.iter().enumerate() {
             tracing::info!(
                 "Generating endpoint IR {}/{}: {}",
@@ -168,11 +552,12 @@ impl Ir {
                 config.endpoints.len(),
                 endpoint_config.endpoint
             );
-            self.generate_endpoint(&endpoint_config, &spec_irs_ref)
+            self.generate_endpoint(endpoint_config, &spec_irs_ref, db_pool.as_ref())
                 .await?;
         }
 
         tracing::info!("Endpoint IR generation complete");
+        self.update_search_index(config).await;
         Ok(())
     }
 
@@ -181,71 +566,328 @@ impl Ir {
         &self,
         endpoint_config: &EndpointConfig,
         available_tables: &[IrGenerationResult],
+        db_pool: Option<&PgPool>,
     ) -> Result<()> {
-        let endpoint_ir = self
+        let examples = Self::load_endpoint_examples(
+            Path::new(ENDPOINTS_DIR),
+            &endpoint_config.endpoint,
+            self.few_shot_examples,
+        );
+
+        let tables = self.tables_for_endpoint(endpoint_config, available_tables);
+
+        let mut endpoint_ir = self
             .ai_client
-            .generate_endpoint_ir(
+            .generate_endpoint_ir_with_examples(
                 &endpoint_config.endpoint,
                 &endpoint_config.description,
                 &endpoint_config.task,
-                available_tables,
+                &tables,
+                &examples,
             )
             .await
             .context(format!(
                 "Failed to generate endpoint IR for: {}",
                 endpoint_config.endpoint
             ))?;
-
-        // Save endpoint IR to file
-        self.save_ir_endpoint(&endpoint_ir)?;
+        endpoint_ir.method = endpoint_config.method.clone();
+        endpoint_ir.tags = endpoint_config.tags.clone();
+        endpoint_ir.scopes = endpoint_config.scopes.clone();
+        endpoint_ir.endpoint_path = Self::configured_path(endpoint_config, &endpoint_ir)?;
+        hooks::run_all(&self.validators, GeneratedIr::Endpoint(&endpoint_ir))?;
+
+        // Save endpoint IR to file, then create its golden test fixture
+        if self.save_ir_endpoint(&endpoint_ir)? {
+            self.generate_endpoint_test(&endpoint_ir, db_pool).await;
+        }
 
         Ok(())
     }
 
+    /// The endpoint's path as configured, which the generated IR may spell differently
+    fn configured_path(
+        endpoint_config: &EndpointConfig,
+        endpoint_ir: &EndpointIrResult,
+    ) -> Result<String> {
+        let configured = endpoint_path::normalize(&endpoint_config.endpoint)?;
+        match endpoint_path::normalize(&endpoint_ir.endpoint_path) {
+            Ok(generated) if generated == configured => {}
+            _ => tracing::warn!(
+                "Generated IR has path '{}', using the configured path '{}'",
+                endpoint_ir.endpoint_path,
+                configured
+            ),
+        }
+        Ok(configured)
+    }
+
+    /// The spec tables described in an endpoint's prompt
+    fn tables_for_endpoint(
+        &self,
+        endpoint_config: &EndpointConfig,
+        available_tables: &[IrGenerationResult],
+    ) -> Vec<IrGenerationResult> {
+        let query = format!(
+            "{} {} {}",
+            endpoint_config.endpoint, endpoint_config.description, endpoint_config.task
+        );
+        let tables: Vec<IrGenerationResult> =
+            table_relevance::select_tables(&query, available_tables, self.endpoint_tables)
+                .into_iter()
+                .cloned()
+                .collect();
+        if tables.len() < available_tables.len() {
+            tracing::info!(
+                "  Using {} of {} tables: {}",
+                tables.len(),
+                available_tables.len(),
+                tables
+                    .iter()
+                    .map(|ir| ir.table_schema.table_name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        tables
+    }
+
+    /// Regenerate an endpoint whose SQL failed at runtime and save the fixed IR
+    ///
+    /// The database error is fed back to the model, and each repaired query must be
+    /// read-only and prepare successfully on `pool` before it is accepted.
+    pub async fn repair_endpoint(
+        &self,
+        config: &Config,
+        broken: &EndpointIrResult,
+        error: &str,
+        pool: &PgPool,
+    ) -> Result<EndpointIrResult> {
+        use sqlx::Executor;
+        const MAX_REPAIR_ATTEMPTS: usize = 3;
+
+        let endpoint_config = config
+            .endpoints
+            .iter()
+            .find(|endpoint| {
+                endpoint.endpoint == broken.endpoint_path
+                    && endpoint.method.eq_ignore_ascii_case(&broken.method)
+            })
+            .context(format!(
+                "Endpoint {} {} is not in the config",
+                broken.method, broken.endpoint_path
+            ))?;
+        let spec_irs: Vec<IrGenerationResult> = Self::load_all_ir_specs(config)?
+            .into_iter()
+            .map(|(_, _, ir)| ir)
+            .collect();
+        let tables = self.tables_for_endpoint(endpoint_config, &spec_irs);
+
+        let mut failure = format!(
+            "This SQL query failed at runtime:\n{}\nDatabase error: {}",
+            broken.sql_query, error
+        );
+        for attempt in 1..=MAX_REPAIR_ATTEMPTS {
+            let mut repaired = self
+                .ai_client
+                .repair_endpoint_ir(
+                    &endpoint_config.endpoint,
+                    &endpoint_config.description,
+                    &endpoint_config.task,
+                    &tables,
+                    &failure,
+                )
+                .await?;
+            repaired.method = endpoint_config.method.clone();
+            repaired.tags = endpoint_config.tags.clone();
+            repaired.scopes = endpoint_config.scopes.clone();
+            repaired.endpoint_path = Self::configured_path(endpoint_config, &repaired)?;
+
+            let checked = match sql_guard::check_read_only_sql(&repaired.sql_query) {
+                Ok(()) => pool
+                    .prepare(repaired.sql_query.as_str())
+                    .await
+                    .map(|_| ())
+                    .map_err(anyhow::Error::from),
+                Err(e) => Err(e),
+            };
+            match checked {
+                Ok(()) => {
+                    hooks::run_all(&self.validators, GeneratedIr::Endpoint(&repaired))?;
+                    self.save_ir_endpoint(&repaired)?;
+                    return Ok(repaired);
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Repaired query for {} still fails (attempt {}/{}): {:#}",
+                        broken.endpoint_path,
+                        attempt,
+                        MAX_REPAIR_ATTEMPTS,
+                        e
+                    );
+                    failure = format!(
+                        "This SQL query failed to prepare:\n{}\nDatabase error: {:#}",
+                        repaired.sql_query, e
+                    );
+                }
+            }
+        }
+
+        anyhow::bail!(
+            "Failed to repair endpoint {} after {} attempts. Last error: {}",
+            broken.endpoint_path,
+            MAX_REPAIR_ATTEMPTS,
+            failure
+        )
+    }
+
+    /// Generate a golden test fixture for an endpoint if one does not exist yet
+    ///
+    /// Existing fixtures are kept so they can catch regressions in regenerated SQL.
+    /// Failures are logged rather than returned, since the endpoint IR itself is valid.
+    async fn generate_endpoint_test(
+        &self,
+        endpoint_ir: &EndpointIrResult,
+        db_pool: Option<&PgPool>,
+    ) {
+        if EndpointTest::fixture_exists(&endpoint_ir.endpoint_path, &endpoint_ir.method) {
+            tracing::info!(
+                "  Keeping existing test fixture for {}",
+                endpoint_ir.endpoint_path
+            );
+            return;
+        }
+
+        let mut fixture = match self.ai_client.generate_endpoint_test(endpoint_ir).await {
+            Ok(fixture) => fixture,
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to generate test fixture for {}: {:#}",
+                    endpoint_ir.endpoint_path,
+                    e
+                );
+                return;
+            }
+        };
+        fixture.endpoint_path = endpoint_ir.endpoint_path.clone();
+        fixture.method = endpoint_ir.method.clone();
+
+        if let Some(pool) = db_pool {
+            if let Err(e) = EndpointTest::record_plans(pool, endpoint_ir, &mut fixture).await {
+                tracing::warn!(
+                    "Generated SQL for {} failed EXPLAIN validation: {:#}",
+                    endpoint_ir.endpoint_path,
+                    e
+                );
+            }
+        } else {
+            tracing::info!("  Run 'smorty test-endpoints --update' to record query plans");
+        }
+
+        if let Err(e) = EndpointTest::save_fixture(&fixture) {
+            tracing::warn!("{:#}", e);
+        }
+    }
+
     /// Save endpoint IR to file in the ir/endpoints/ directory
-    fn save_ir_endpoint(&self, ir: &EndpointIrResult) -> Result<()> {
-        self.save_ir_endpoint_to_dir(Path::new("ir/endpoints"), ir)
+    ///
+    /// Returns `false` if the user chose to keep the existing file
+    fn save_ir_endpoint(&self, ir: &EndpointIrResult) -> Result<bool> {
+        self.save_ir_endpoint_to_dir(Path::new(ENDPOINTS_DIR), ir)
     }
 
     /// Save endpoint IR to a specific directory (used for testing)
-    fn save_ir_endpoint_to_dir(&self, base_dir: &Path, ir: &EndpointIrResult) -> Result<()> {
+    fn save_ir_endpoint_to_dir(&self, base_dir: &Path, ir: &EndpointIrResult) -> Result<bool> {
+        let mut ir = ir.clone();
+        ir.endpoint_path = endpoint_path::normalize(&ir.endpoint_path)?;
+        let ir = &ir;
+
         // Create ir/endpoints directory if it doesn't exist
         if !base_dir.exists() {
             fs::create_dir_all(base_dir).context("Failed to create ir/endpoints directory")?;
         }
 
-        // Convert endpoint path to filename
-        // e.g., "/api/pool/{pool}/fees" -> "api_pool_fees"
-        let filename = ir
-            .endpoint_path
-            .trim_start_matches('/')
-            .replace('/', "_")
-            .replace('{', "")
-            .replace('}', "");
-
         // Save IR as JSON
-        let ir_file = base_dir.join(format!("{}.json", filename));
+        let ir_file = base_dir.join(endpoint_files::file_name(&ir.endpoint_path, &ir.method));
+        let existing_file = endpoint_files::resolve(base_dir, &ir.endpoint_path, &ir.method);
+
+        // Review changes against the existing IR before overwriting it
+        if existing_file.exists() {
+            let existing: EndpointIrResult =
+                serde_json::from_str(&fs::read_to_string(&existing_file).context(format!(
+                    "Failed to read endpoint IR file: {:?}",
+                    existing_file
+                ))?)
+                .context(format!(
+                    "Failed to parse existing endpoint IR file: {:?}",
+                    existing_file
+                ))?;
+
+            let diff = IrDiff::compute_endpoint(&existing, ir);
+            if !self.confirm_overwrite(&existing_file, &diff)? {
+                tracing::info!("  Kept existing endpoint IR: {:?}", existing_file);
+                return Ok(false);
+            }
+        }
+
         let ir_json =
             serde_json::to_string_pretty(ir).context("Failed to serialize endpoint IR")?;
 
         fs::write(&ir_file, ir_json)
             .context(format!("Failed to write endpoint IR file: {:?}", ir_file))?;
+        // Move IRs saved under a legacy name to the current one
+        if existing_file != ir_file && existing_file.exists() {
+            fs::remove_file(&existing_file).context(format!(
+                "Failed to remove old endpoint IR file: {:?}",
+                existing_file
+            ))?;
+        }
+        endpoint_files::Manifest::record(base_dir, &ir.endpoint_path, &ir.method, &ir_file)?;
 
         tracing::info!("  Saved endpoint IR to: {:?}", ir_file);
 
-        Ok(())
+        Ok(true)
     }
 
-    /// Load endpoint IR from file in the ir/endpoints/ directory
-    pub fn load_ir_endpoint(endpoint_path: &str) -> Result<EndpointIrResult> {
-        // Convert endpoint path to filename
-        let filename = endpoint_path
-            .trim_start_matches('/')
-            .replace('/', "_")
-            .replace('{', "")
-            .replace('}', "");
+    /// Show the diff for an existing IR file and decide whether to overwrite it
+    ///
+    /// Returns `true` if the new IR should be written. Unchanged files are always
+    /// rewritten. With `--yes` every change is accepted; otherwise the user is
+    /// prompted, and non-interactive sessions fail instead of silently overwriting.
+    fn confirm_overwrite(&self, ir_file: &Path, diff: &IrDiff) -> Result<bool> {
+        if !diff.has_changes() {
+            return Ok(true);
+        }
+
+        println!("\nChanges to {}:", ir_file.display());
+        print!("{}", diff.render());
+
+        if self.assume_yes {
+            return Ok(true);
+        }
+
+        let stdin = std::io::stdin();
+        if !stdin.is_terminal() {
+            anyhow::bail!(
+                "Regenerated IR differs from {:?}. Re-run with --yes to overwrite without confirmation",
+                ir_file
+            );
+        }
+
+        print!("Overwrite {}? [y/N] ", ir_file.display());
+        std::io::stdout().flush()?;
+
+        let mut answer = String::new();
+        stdin
+            .lock()
+            .read_line(&mut answer)
+            .context("Failed to read confirmation")?;
+
+        Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+    }
 
-        let ir_file = Path::new("ir/endpoints").join(format!("{}.json", filename));
+    /// Load endpoint IR from file in the ir/endpoints/ directory
+    pub fn load_ir_endpoint(endpoint_path: &str, method: &str) -> Result<EndpointIrResult> {
+        let ir_file = endpoint_files::resolve(Path::new(ENDPOINTS_DIR), endpoint_path, method);
 
         let ir_content = fs::read_to_string(&ir_file)
             .context(format!("Failed to read endpoint IR file: {:?}", ir_file))?;
@@ -258,7 +900,7 @@ impl Ir {
 
     /// Load all endpoint IR files
     pub fn load_all_ir_endpoints() -> Result<Vec<EndpointIrResult>> {
-        let endpoints_dir = Path::new("ir/endpoints");
+        let endpoints_dir = Path::new(ENDPOINTS_DIR);
 
         if !endpoints_dir.exists() {
             return Ok(Vec::new());
@@ -270,7 +912,8 @@ impl Ir {
             let entry = entry?;
             let path = entry.path();
 
-            if path.extension().and_then(|s| s.to_str()) == Some("json") {
+            let is_manifest = path.file_name().and_then(|s| s.to_str()) == Some(MANIFEST_FILE);
+            if path.extension().and_then(|s| s.to_str()) == Some("json") && !is_manifest {
                 let ir_content = fs::read_to_string(&path)
                     .context(format!("Failed to read endpoint IR file: {:?}", path))?;
 
@@ -289,6 +932,9 @@ impl Ir {
 mod tests {
     use super::*;
     use crate::ai::{ColumnDef, EventField, TableSchema};
+    use crate::config::SpecPriority;
+    use crate::index_def::IndexDef;
+    use crate::start_block::StartBlock;
     use tempfile::TempDir;
 
     // NOTE: These tests use temporary directories to avoid interfering with the actual ir/ directory
@@ -307,12 +953,14 @@ mod tests {
                     solidity_type: "uint256".to_string(),
                     rust_type: "String".to_string(),
                     indexed: false,
+                    column: None,
                 },
                 EventField {
                     name: "user".to_string(),
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true,
+                    column: None,
                 },
             ],
             table_schema: TableSchema {
@@ -335,11 +983,12 @@ mod tests {
                         column_type: "VARCHAR(42) NOT NULL".to_string(),
                     },
                 ],
-                indexes: vec![
-                    "CREATE INDEX idx_block_number ON {table_name}(block_number)".to_string(),
-                ],
+                indexes: vec![IndexDef::new("idx_block_number", &["block_number"])],
+                computed_columns: vec![],
             },
             description: "Get test events".to_string(),
+            abi_hash: None,
+            state_fold: None,
         }
     }
 
@@ -352,8 +1001,16 @@ mod tests {
     fn create_mock_spec(name: &str) -> SpecConfig {
         SpecConfig {
             name: name.to_string(),
-            start_block: Some(0),
+            start_block: Some(StartBlock::Block(0)),
+            end_block: None,
             task: "Test task".to_string(),
+            table: None,
+            write_mode: WriteMode::Append,
+            conflict_keys: Vec::new(),
+            state: None,
+            priority: SpecPriority::Normal,
+            depends_on: vec![],
+            vars: Default::default(),
         }
     }
 
@@ -406,6 +1063,109 @@ mod tests {
         assert_eq!(loaded_ir.description, mock_ir.description);
     }
 
+    #[test]
+    fn test_save_ir_spec_overwrite_requires_confirmation() {
+        let temp_dir = TempDir::new().unwrap();
+        let ir_dir = temp_dir.path().join("ir");
+
+        let spec = create_mock_spec("TestEvent");
+        let original_ir = create_mock_ir();
+        let mut changed_ir = create_mock_ir();
+        changed_ir.table_schema.columns[2].column_type = "TEXT NOT NULL".to_string();
+
+        let ir_file = ir_dir.join("TestContract").join("TestEvent.json");
+
+        // Unchanged IR is rewritten without prompting
+        let ir_generator = Ir::new(create_mock_ai_client());
+        ir_generator
+            .save_ir_spec_to_dir(&ir_dir, "TestContract", &spec, &original_ir)
+            .expect("Failed to save IR");
+        ir_generator
+            .save_ir_spec_to_dir(&ir_dir, "TestContract", &spec, &original_ir)
+            .expect("Saving identical IR should not need confirmation");
+
+        // Changed IR is refused when stdin is not a terminal
+        let result = ir_generator.save_ir_spec_to_dir(&ir_dir, "TestContract", &spec, &changed_ir);
+        assert!(result.is_err(), "Changed IR should require confirmation");
+        assert!(result.unwrap_err().to_string().contains("--yes"));
+
+        let loaded: IrGenerationResult =
+            serde_json::from_str(&fs::read_to_string(&ir_file).unwrap()).unwrap();
+        assert_eq!(
+            loaded.table_schema.columns[2].column_type,
+            "NUMERIC(78, 0) NOT NULL"
+        );
+
+        // --yes overwrites
+        let ir_generator = Ir::new(create_mock_ai_client()).with_assume_yes(true);
+        ir_generator
+            .save_ir_spec_to_dir(&ir_dir, "TestContract", &spec, &changed_ir)
+            .expect("Failed to overwrite IR");
+
+        let loaded: IrGenerationResult =
+            serde_json::from_str(&fs::read_to_string(&ir_file).unwrap()).unwrap();
+        assert_eq!(loaded.table_schema.columns[2].column_type, "TEXT NOT NULL");
+    }
+
+    #[test]
+    fn test_load_spec_examples_prefers_same_contract() {
+        let temp_dir = TempDir::new().unwrap();
+        let ir_dir = temp_dir.path().join("ir");
+        let ir_generator = Ir::new(create_mock_ai_client());
+
+        for (contract, spec_name) in [("Alpha", "Deposit"), ("Beta", "Swap"), ("Beta", "Mint")] {
+            let mut ir = create_mock_ir();
+            ir.table_schema.table_name = format!("{}_{}", contract, spec_name).to_lowercase();
+            ir_generator
+                .save_ir_spec_to_dir(&ir_dir, contract, &create_mock_spec(spec_name), &ir)
+                .unwrap();
+        }
+
+        let examples = Ir::load_spec_examples(&ir_dir, "Beta", "Swap", 2);
+        let tables: Vec<_> = examples
+            .iter()
+            .map(|ir| ir.table_schema.table_name.as_str())
+            .collect();
+        assert_eq!(tables, vec!["beta_mint", "alpha_deposit"]);
+
+        assert!(Ir::load_spec_examples(&ir_dir, "Beta", "Swap", 0).is_empty());
+        assert!(
+            Ir::load_spec_examples(&temp_dir.path().join("missing"), "Beta", "Swap", 2).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_load_endpoint_examples_excludes_current_endpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let ir_dir = temp_dir.path().join("endpoints");
+        let ir_generator = Ir::new(create_mock_ai_client());
+
+        for path in ["/api/deposits", "/api/swaps"] {
+            let ir = EndpointIrResult {
+                endpoint_path: path.to_string(),
+                description: "Test endpoint".to_string(),
+                method: "GET".to_string(),
+                path_params: vec![],
+                query_params: vec![],
+                response_schema: crate::ai::ResponseSchema {
+                    name: "TestResponse".to_string(),
+                    fields: vec![],
+                },
+                sql_query: "SELECT 1".to_string(),
+                stages: Vec::new(),
+                tables_referenced: vec![],
+                tags: vec![],
+                scopes: vec![],
+                cache_ttl: None,
+            };
+            ir_generator.save_ir_endpoint_to_dir(&ir_dir, &ir).unwrap();
+        }
+
+        let examples = Ir::load_endpoint_examples(&ir_dir, "/api/swaps", 2);
+        assert_eq!(examples.len(), 1);
+        assert_eq!(examples[0].endpoint_path, "/api/deposits");
+    }
+
     #[test]
     fn test_load_ir_spec_nonexistent_file() {
         // Try to load non-existent IR
@@ -463,6 +1223,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_share_table() {
+        let mut ir = create_mock_ir();
+        Ir::share_table(&mut ir, "transfers").unwrap();
+
+        assert_eq!(ir.table_schema.table_name, "transfers");
+        let source = ir.table_schema.columns.last().unwrap();
+        assert_eq!(source.name, SOURCE_COLUMN);
+        assert_eq!(source.column_type, "TEXT NOT NULL");
+        assert_eq!(ir.table_schema.indexes[1].columns, vec![SOURCE_COLUMN]);
+        assert_eq!(Ir::source_name("USDC", "Transfer"), "USDC/Transfer");
+
+        // An event field named like the discriminator would be ambiguous
+        assert!(Ir::share_table(&mut ir, "transfers").is_err());
+    }
+
     #[test]
     fn test_save_ir_spec_creates_directories() {
         let temp_dir = TempDir::new().unwrap();
@@ -629,18 +1405,21 @@ mod tests {
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true,
+                    column: None,
                 },
                 EventField {
                     name: "to".to_string(),
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true,
+                    column: None,
                 },
                 EventField {
                     name: "amount".to_string(),
                     solidity_type: "uint256".to_string(),
                     rust_type: "String".to_string(),
                     indexed: false,
+                    column: None,
                 },
             ],
             table_schema: TableSchema {
@@ -659,9 +1438,15 @@ mod tests {
                         column_type: "NUMERIC(78,0)".to_string(),
                     },
                 ],
-                indexes: vec!["from_address".to_string(), "to_address".to_string()],
+                indexes: vec![
+                    IndexDef::new("idx_from_address", &["from_address"]),
+                    IndexDef::new("idx_to_address", &["to_address"]),
+                ],
+                computed_columns: vec![],
             },
             description: "Tracks ERC20 transfer events".to_string(),
+            abi_hash: None,
+            state_fold: None,
         };
 
         // Test case 2: Pool creation event (different types)
@@ -677,12 +1462,14 @@ mod tests {
                     solidity_type: "bytes32".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true,
+                    column: None,
                 },
                 EventField {
                     name: "liquidity".to_string(),
                     solidity_type: "uint256".to_string(),
                     rust_type: "String".to_string(),
                     indexed: false,
+                    column: None,
                 },
             ],
             table_schema: TableSchema {
@@ -697,9 +1484,12 @@ mod tests {
                         column_type: "NUMERIC(78,0)".to_string(),
                     },
                 ],
-                indexes: vec!["pool_id".to_string()],
+                indexes: vec![IndexDef::new("idx_pool_id", &["pool_id"])],
+                computed_columns: vec![],
             },
             description: "Tracks pool creation events".to_string(),
+            abi_hash: None,
+            state_fold: None,
         };
 
         // Save both IRs
@@ -752,8 +1542,11 @@ mod tests {
                 table_name: "contract_paused".to_string(),
                 columns: vec![], // Only system columns (block_number, tx_hash, etc)
                 indexes: vec![],
+                computed_columns: vec![],
             },
             description: "Tracks when contract is paused".to_string(),
+            abi_hash: None,
+            state_fold: None,
         };
 
         let ai_client = create_mock_ai_client();
@@ -799,24 +1592,28 @@ mod tests {
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true, // indexed #1
+                    column: None,
                 },
                 EventField {
                     name: "receiver".to_string(),
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true, // indexed #2
+                    column: None,
                 },
                 EventField {
                     name: "operator".to_string(),
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true, // indexed #3 (max)
+                    column: None,
                 },
                 EventField {
                     name: "value".to_string(),
                     solidity_type: "uint256".to_string(),
                     rust_type: "String".to_string(),
                     indexed: false, // not indexed, stored in data
+                    column: None,
                 },
             ],
             table_schema: TableSchema {
@@ -840,12 +1637,15 @@ mod tests {
                     },
                 ],
                 indexes: vec![
-                    "sender".to_string(),
-                    "receiver".to_string(),
-                    "operator".to_string(),
+                    IndexDef::new("idx_sender", &["sender"]),
+                    IndexDef::new("idx_receiver", &["receiver"]),
+                    IndexDef::new("idx_operator", &["operator"]),
                 ],
+                computed_columns: vec![],
             },
             description: "Event with maximum indexed parameters".to_string(),
+            abi_hash: None,
+            state_fold: None,
         };
 
         let ai_client = create_mock_ai_client();
@@ -891,18 +1691,21 @@ mod tests {
                     solidity_type: "bytes".to_string(),
                     rust_type: "String".to_string(), // hex encoded
                     indexed: false,
+                    column: None,
                 },
                 EventField {
                     name: "values".to_string(),
                     solidity_type: "uint256[]".to_string(),
                     rust_type: "String".to_string(), // JSON array
                     indexed: false,
+                    column: None,
                 },
                 EventField {
                     name: "sender".to_string(),
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true,
+                    column: None,
                 },
             ],
             table_schema: TableSchema {
@@ -921,9 +1724,12 @@ mod tests {
                         column_type: "VARCHAR(42)".to_string(),
                     },
                 ],
-                indexes: vec!["sender".to_string()],
+                indexes: vec![IndexDef::new("idx_sender", &["sender"])],
+                computed_columns: vec![],
             },
             description: "Event with complex dynamic types".to_string(),
+            abi_hash: None,
+            state_fold: None,
         };
 
         let ai_client = create_mock_ai_client();
@@ -985,6 +1791,7 @@ mod tests {
                 solidity_type: "address".to_string(),
                 rust_type: "String".to_string(),
                 indexed: true,
+                column: None,
             }],
             table_schema: TableSchema {
                 table_name: "token_a_transfers".to_string(), // unique name
@@ -993,8 +1800,11 @@ mod tests {
                     column_type: "VARCHAR(42)".to_string(),
                 }],
                 indexes: vec![],
+                computed_columns: vec![],
             },
             description: "Token A transfers".to_string(),
+            abi_hash: None,
+            state_fold: None,
         };
 
         // Contract B also has Transfer event
@@ -1009,6 +1819,7 @@ mod tests {
                 solidity_type: "address".to_string(),
                 rust_type: "String".to_string(),
                 indexed: true,
+                column: None,
             }],
             table_schema: TableSchema {
                 table_name: "token_b_transfers".to_string(), // different unique name
@@ -1017,8 +1828,11 @@ mod tests {
                     column_type: "VARCHAR(42)".to_string(),
                 }],
                 indexes: vec![],
+                computed_columns: vec![],
             },
             description: "Token B transfers".to_string(),
+            abi_hash: None,
+            state_fold: None,
         };
 
         // Save both
@@ -1082,12 +1896,14 @@ mod tests {
                     solidity_type: "uint112".to_string(),
                     rust_type: "String".to_string(),
                     indexed: false,
+                    column: None,
                 },
                 EventField {
                     name: "reserve1".to_string(),
                     solidity_type: "uint112".to_string(),
                     rust_type: "String".to_string(),
                     indexed: false,
+                    column: None,
                 },
             ],
             table_schema: TableSchema {
@@ -1103,8 +1919,11 @@ mod tests {
                     },
                 ],
                 indexes: vec![],
+                computed_columns: vec![],
             },
             description: "Uniswap pair sync events".to_string(),
+            abi_hash: None,
+            state_fold: None,
         };
 
         let ai_client = create_mock_ai_client();
@@ -1152,6 +1971,7 @@ mod tests {
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true,
+                    column: None,
                 }],
                 table_schema: TableSchema {
                     table_name: format!("{}_swaps", chain),
@@ -1160,8 +1980,11 @@ mod tests {
                         column_type: "VARCHAR(42)".to_string(),
                     }],
                     indexes: vec![],
+                    computed_columns: vec![],
                 },
                 description: format!("Swaps on {}", chain),
+                abi_hash: None,
+                state_fold: None,
             };
 
             ir_generator
@@ -1171,7 +1994,7 @@ mod tests {
                     &create_mock_spec("swaps"),
                     &ir,
                 )
-                .expect(&format!("Failed to save {} IR", chain));
+                .unwrap_or_else(|_| panic!("Failed to save {} IR", chain));
         }
 
         // Verify all chains saved correctly
@@ -0,0 +1,626 @@
+use crate::ai::{EndpointIrResult, IrGenerationResult};
+use crate::state_table::{FoldDelta, StateFold};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+/// Represents the changes between an existing IR file and a regenerated one
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct IrDiff {
+    pub changes: Vec<IrChange>,
+}
+
+/// A single change to an IR document, addressed by a dotted path
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrChange {
+    /// Location of the change, e.g. `table_schema.columns.amount`
+    pub path: String,
+    /// Previous value (`None` if the entry was added)
+    pub old: Option<String>,
+    /// New value (`None` if the entry was removed)
+    pub new: Option<String>,
+}
+
+impl IrDiff {
+    /// Compute the difference between two spec IRs
+    pub fn compute_spec(old: &IrGenerationResult, new: &IrGenerationResult) -> Self {
+        let mut diff = Self::default();
+
+        diff.compare("event_name", &old.event_name, &new.event_name);
+        diff.compare(
+            "event_signature",
+            &old.event_signature,
+            &new.event_signature,
+        );
+        diff.compare("start_block", &old.start_block, &new.start_block);
+        diff.compare(
+            "contract_address",
+            &old.contract_address,
+            &new.contract_address,
+        );
+        diff.compare("chain", &old.chain, &new.chain);
+        diff.compare("description", &old.description, &new.description);
+
+        // Event fields keyed by name, with the column each is stored in
+        diff.compare_keyed(
+            "indexed_fields",
+            old.indexed_fields.iter().map(Self::describe_field),
+            new.indexed_fields.iter().map(Self::describe_field),
+        );
+
+        diff.compare(
+            "table_schema.table_name",
+            &old.table_schema.table_name,
+            &new.table_schema.table_name,
+        );
+
+        // Columns keyed by name
+        diff.compare_keyed(
+            "table_schema.columns",
+            old.table_schema
+                .columns
+                .iter()
+                .map(|c| (c.name.clone(), c.column_type.clone())),
+            new.table_schema
+                .columns
+                .iter()
+                .map(|c| (c.name.clone(), c.column_type.clone())),
+        );
+
+        diff.compare_keyed(
+            "table_schema.computed_columns",
+            old.table_schema
+                .computed_columns
+                .iter()
+                .map(|c| (c.name.clone(), c.definition())),
+            new.table_schema
+                .computed_columns
+                .iter()
+                .map(|c| (c.name.clone(), c.definition())),
+        );
+
+        // Indexes keyed by name, compared by their rendered SQL
+        diff.compare_keyed(
+            "table_schema.indexes",
+            old.table_schema
+                .indexes
+                .iter()
+                .map(|i| (i.name.clone(), i.to_sql(&i.name, "{table_name}"))),
+            new.table_schema
+                .indexes
+                .iter()
+                .map(|i| (i.name.clone(), i.to_sql(&i.name, "{table_name}"))),
+        );
+
+        match (&old.state_fold, &new.state_fold) {
+            (Some(old), Some(new)) => {
+                diff.compare("state_fold.table_name", &old.table_name, &new.table_name);
+                diff.compare(
+                    "state_fold.value_column",
+                    &old.value_column,
+                    &new.value_column,
+                );
+                diff.compare_keyed(
+                    "state_fold.key_columns",
+                    old.key_columns
+                        .iter()
+                        .map(|c| (c.name.clone(), c.column_type.clone())),
+                    new.key_columns
+                        .iter()
+                        .map(|c| (c.name.clone(), c.column_type.clone())),
+                );
+                // Deltas have no names, so they are keyed by position
+                diff.compare_keyed(
+                    "state_fold.deltas",
+                    old.deltas.iter().enumerate().map(Self::describe_delta),
+                    new.deltas.iter().enumerate().map(Self::describe_delta),
+                );
+            }
+            (None, None) => {}
+            (old, new) => diff.changes.push(IrChange {
+                path: "state_fold".to_string(),
+                old: old.as_ref().map(Self::describe_state_fold),
+                new: new.as_ref().map(Self::describe_state_fold),
+            }),
+        }
+
+        diff
+    }
+
+    /// Compute the difference between two endpoint IRs
+    pub fn compute_endpoint(old: &EndpointIrResult, new: &EndpointIrResult) -> Self {
+        let mut diff = Self::default();
+
+        diff.compare("endpoint_path", &old.endpoint_path, &new.endpoint_path);
+        diff.compare("method", &old.method, &new.method);
+        diff.compare("description", &old.description, &new.description);
+
+        diff.compare_keyed(
+            "path_params",
+            old.path_params
+                .iter()
+                .map(|p| (p.name.clone(), p.param_type.clone())),
+            new.path_params
+                .iter()
+                .map(|p| (p.name.clone(), p.param_type.clone())),
+        );
+
+        diff.compare_keyed(
+            "query_params",
+            old.query_params
+                .iter()
+                .map(|p| (p.name.clone(), Self::describe_query_param(p))),
+            new.query_params
+                .iter()
+                .map(|p| (p.name.clone(), Self::describe_query_param(p))),
+        );
+
+        diff.compare(
+            "response_schema.name",
+            &old.response_schema.name,
+            &new.response_schema.name,
+        );
+        diff.compare_keyed(
+            "response_schema.fields",
+            old.response_schema
+                .fields
+                .iter()
+                .map(|f| (f.name.clone(), f.field_type.clone())),
+            new.response_schema
+                .fields
+                .iter()
+                .map(|f| (f.name.clone(), f.field_type.clone())),
+        );
+
+        diff.compare_keyed(
+            "stages",
+            old.stages.iter().map(Self::describe_stage),
+            new.stages.iter().map(Self::describe_stage),
+        );
+        diff.compare("sql_query", &old.sql_query, &new.sql_query);
+        diff.compare_set(
+            "tables_referenced",
+            &old.tables_referenced,
+            &new.tables_referenced,
+        );
+        diff.compare_set("tags", &old.tags, &new.tags);
+        diff.compare_set("scopes", &old.scopes, &new.scopes);
+
+        diff
+    }
+
+    /// Compute the difference between a table's columns in the database and its spec IR
+    ///
+    /// `live` holds column names and types as reported by Postgres. Types are
+    /// compared without constraints and with aliases such as `VARCHAR` resolved.
+    pub fn compute_live_schema(live: &[(String, String)], ir: &IrGenerationResult) -> Self {
+        let mut diff = Self::default();
+
+        let ir_columns = ir
+            .table_schema
+            .columns
+            .iter()
+            .map(|c| (c.name.clone(), c.column_type.clone()))
+            .chain(
+                ir.table_schema
+                    .computed_columns
+                    .iter()
+                    .map(|c| (c.name.clone(), c.column_type.clone())),
+            );
+        diff.compare_keyed(
+            "columns",
+            live.iter()
+                .map(|(name, column_type)| (name.clone(), normalize_column_type(column_type))),
+            ir_columns.map(|(name, column_type)| (name, normalize_column_type(&column_type))),
+        );
+
+        diff
+    }
+
+    /// Check if there are any changes
+    pub fn has_changes(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    /// Render the diff as human-readable lines for terminal review
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        for change in &self.changes {
+            match (&change.old, &change.new) {
+                (None, Some(new)) => out.push_str(&format!("  + {}: {}\n", change.path, new)),
+                (Some(old), None) => out.push_str(&format!("  - {}: {}\n", change.path, old)),
+                (Some(old), Some(new)) => {
+                    out.push_str(&format!("  ~ {}:\n", change.path));
+                    out.push_str(&format!("      - {}\n", old));
+                    out.push_str(&format!("      + {}\n", new));
+                }
+                (None, None) => {}
+            }
+        }
+
+        out
+    }
+
+    /// Compare two scalar values and record a modification if they differ
+    fn compare<T: PartialEq + Display>(&mut self, path: &str, old: &T, new: &T) {
+        if old != new {
+            self.changes.push(IrChange {
+                path: path.to_string(),
+                old: Some(old.to_string()),
+                new: Some(new.to_string()),
+            });
+        }
+    }
+
+    /// Compare two lists of named entries, reporting additions, removals and modifications
+    fn compare_keyed(
+        &mut self,
+        path: &str,
+        old: impl Iterator<Item = (String, String)>,
+        new: impl Iterator<Item = (String, String)>,
+    ) {
+        // BTreeMap keeps the output ordering stable
+        let old: BTreeMap<String, String> = old.collect();
+        let new: BTreeMap<String, String> = new.collect();
+
+        for (name, old_value) in &old {
+            match new.get(name) {
+                None => self.changes.push(IrChange {
+                    path: format!("{}.{}", path, name),
+                    old: Some(old_value.clone()),
+                    new: None,
+                }),
+                Some(new_value) if new_value != old_value => self.changes.push(IrChange {
+                    path: format!("{}.{}", path, name),
+                    old: Some(old_value.clone()),
+                    new: Some(new_value.clone()),
+                }),
+                Some(_) => {}
+            }
+        }
+
+        for (name, new_value) in &new {
+            if !old.contains_key(name) {
+                self.changes.push(IrChange {
+                    path: format!("{}.{}", path, name),
+                    old: None,
+                    new: Some(new_value.clone()),
+                });
+            }
+        }
+    }
+
+    /// Compare two unordered lists of strings
+    fn compare_set(&mut self, path: &str, old: &[String], new: &[String]) {
+        let old: BTreeSet<&String> = old.iter().collect();
+        let new: BTreeSet<&String> = new.iter().collect();
+
+        for removed in old.difference(&new) {
+            self.changes.push(IrChange {
+                path: path.to_string(),
+                old: Some((*removed).clone()),
+                new: None,
+            });
+        }
+
+        for added in new.difference(&old) {
+            self.changes.push(IrChange {
+                path: path.to_string(),
+                old: None,
+                new: Some((*added).clone()),
+            });
+        }
+    }
+
+    fn describe_field(field: &crate::ai::EventField) -> (String, String) {
+        let mut description = field.solidity_type.clone();
+        if field.indexed {
+            description.push_str(" indexed");
+        }
+        if let Some(column) = &field.column {
+            description.push_str(&format!(" -> {}", column));
+        }
+        (field.name.clone(), description)
+    }
+
+    fn describe_state_fold(fold: &StateFold) -> String {
+        let keys: Vec<&str> = fold.key_columns.iter().map(|c| c.name.as_str()).collect();
+        format!(
+            "{} ({} by {})",
+            fold.table_name,
+            fold.value_column,
+            keys.join(", ")
+        )
+    }
+
+    fn describe_delta((index, delta): (usize, &FoldDelta)) -> (String, String) {
+        (
+            index.to_string(),
+            format!("({}) += {}", delta.keys.join(", "), delta.value),
+        )
+    }
+
+    fn describe_query_param(param: &crate::ai::QueryParam) -> String {
+        let mut description = match &param.default {
+            Some(default) => format!("{} (default: {})", param.param_type, default),
+            None => param.param_type.clone(),
+        };
+        if !param.allowed_values.is_empty() {
+            let values: Vec<String> = param.allowed_values.iter().map(|v| v.to_string()).collect();
+            description.push_str(&format!(" one of [{}]", values.join(", ")));
+        }
+        description
+    }
+
+    fn describe_stage(stage: &crate::ai::QueryStage) -> (String, String) {
+        (
+            stage.name.clone(),
+            format!("{} -> [{}]", stage.sql_query, stage.outputs.join(", ")),
+        )
+    }
+}
+
+/// Column type as Postgres reports it, e.g. `VARCHAR(42) NOT NULL` becomes `character varying(42)`
+fn normalize_column_type(column_type: &str) -> String {
+    const CONSTRAINTS: [&str; 8] = [
+        "NOT",
+        "NULL",
+        "DEFAULT",
+        "PRIMARY",
+        "UNIQUE",
+        "REFERENCES",
+        "CHECK",
+        "GENERATED",
+    ];
+
+    let words: Vec<&str> = column_type
+        .split_whitespace()
+        .take_while(|word| !CONSTRAINTS.iter().any(|c| word.eq_ignore_ascii_case(c)))
+        .collect();
+    let base = words.join(" ").to_lowercase().replace(", ", ",");
+
+    let (name, rest) = match base.find(['(', '[']) {
+        Some(idx) => base.split_at(idx),
+        None => (base.as_str(), ""),
+    };
+    let name = match name.trim_end() {
+        "varchar" => "character varying",
+        "char" => "character",
+        "int" | "int4" | "serial" => "integer",
+        "int8" | "bigserial" => "bigint",
+        "int2" | "smallserial" => "smallint",
+        "bool" => "boolean",
+        "decimal" => "numeric",
+        "float8" => "double precision",
+        "float4" => "real",
+        "timestamptz" => "timestamp with time zone",
+        "timestamp" => "timestamp without time zone",
+        other => other,
+    };
+    format!("{}{}", name, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{
+        ColumnDef, EventField, QueryParam, ResponseField, ResponseSchema, TableSchema,
+    };
+    use crate::index_def::IndexDef;
+    use serde_json::json;
+
+    fn create_spec_ir() -> IrGenerationResult {
+        IrGenerationResult {
+            event_name: "Transfer".to_string(),
+            event_signature: "Transfer(address,address,uint256)".to_string(),
+            start_block: 100,
+            contract_address: "0x0000000000000000000000000000000000000001".to_string(),
+            chain: "mainnet".to_string(),
+            indexed_fields: vec![EventField {
+                name: "from".to_string(),
+                solidity_type: "address".to_string(),
+                rust_type: "String".to_string(),
+                indexed: true,
+                column: None,
+            }],
+            table_schema: TableSchema {
+                table_name: "token_transfers".to_string(),
+                columns: vec![ColumnDef {
+                    name: "from_address".to_string(),
+                    column_type: "VARCHAR(42) NOT NULL".to_string(),
+                }],
+                indexes: vec![IndexDef::new("idx_from", &["from_address"])],
+                computed_columns: vec![],
+            },
+            description: "Token transfers".to_string(),
+            abi_hash: None,
+            state_fold: None,
+        }
+    }
+
+    fn create_endpoint_ir() -> EndpointIrResult {
+        EndpointIrResult {
+            endpoint_path: "/api/transfers".to_string(),
+            description: "Recent transfers".to_string(),
+            method: "GET".to_string(),
+            path_params: vec![],
+            query_params: vec![QueryParam {
+                name: "limit".to_string(),
+                param_type: "u32".to_string(),
+                default: Some(json!(50)),
+                description: String::new(),
+                allowed_values: vec![],
+            }],
+            response_schema: ResponseSchema {
+                name: "TransferResponse".to_string(),
+                fields: vec![ResponseField {
+                    name: "from_address".to_string(),
+                    field_type: "String".to_string(),
+                    description: "Sender".to_string(),
+                    scale: None,
+                    format: None,
+                }],
+            },
+            sql_query: "SELECT from_address FROM token_transfers LIMIT $1".to_string(),
+            stages: Vec::new(),
+            tables_referenced: vec!["token_transfers".to_string()],
+            tags: vec![],
+            scopes: vec![],
+            cache_ttl: None,
+        }
+    }
+
+    #[test]
+    fn test_identical_spec_has_no_changes() {
+        let ir = create_spec_ir();
+        let diff = IrDiff::compute_spec(&ir, &ir.clone());
+        assert!(!diff.has_changes());
+        assert!(diff.render().is_empty());
+    }
+
+    #[test]
+    fn test_spec_column_changes() {
+        let old = create_spec_ir();
+        let mut new = create_spec_ir();
+        new.table_schema.columns[0].column_type = "TEXT NOT NULL".to_string();
+        new.table_schema.columns.push(ColumnDef {
+            name: "amount".to_string(),
+            column_type: "NUMERIC(78, 0) NOT NULL".to_string(),
+        });
+
+        let diff = IrDiff::compute_spec(&old, &new);
+
+        assert_eq!(diff.changes.len(), 2);
+        assert!(diff.changes.contains(&IrChange {
+            path: "table_schema.columns.from_address".to_string(),
+            old: Some("VARCHAR(42) NOT NULL".to_string()),
+            new: Some("TEXT NOT NULL".to_string()),
+        }));
+        assert!(diff.changes.contains(&IrChange {
+            path: "table_schema.columns.amount".to_string(),
+            old: None,
+            new: Some("NUMERIC(78, 0) NOT NULL".to_string()),
+        }));
+    }
+
+    #[test]
+    fn test_spec_table_rename_and_field_removal() {
+        let old = create_spec_ir();
+        let mut new = create_spec_ir();
+        new.table_schema.table_name = "tkn_transfers".to_string();
+        new.indexed_fields.clear();
+
+        let diff = IrDiff::compute_spec(&old, &new);
+        let rendered = diff.render();
+
+        assert!(rendered.contains("~ table_schema.table_name"));
+        assert!(rendered.contains("- indexed_fields.from: address indexed"));
+    }
+
+    #[test]
+    fn test_spec_field_column_and_state_fold_changes() {
+        let mut old = create_spec_ir();
+        old.indexed_fields[0].column = Some("from_address".to_string());
+        let mut new = old.clone();
+        new.indexed_fields[0].column = Some("sender".to_string());
+        new.state_fold = Some(StateFold {
+            table_name: "balances".to_string(),
+            key_columns: vec![ColumnDef {
+                name: "holder".to_string(),
+                column_type: "VARCHAR(42) NOT NULL".to_string(),
+            }],
+            value_column: "balance".to_string(),
+            deltas: vec![FoldDelta {
+                keys: vec!["from_address".to_string()],
+                value: "-amount".to_string(),
+            }],
+        });
+
+        let diff = IrDiff::compute_spec(&old, &new);
+
+        assert_eq!(
+            diff.changes,
+            vec![
+                IrChange {
+                    path: "indexed_fields.from".to_string(),
+                    old: Some("address indexed -> from_address".to_string()),
+                    new: Some("address indexed -> sender".to_string()),
+                },
+                IrChange {
+                    path: "state_fold".to_string(),
+                    old: None,
+                    new: Some("balances (balance by holder)".to_string()),
+                },
+            ]
+        );
+
+        old = new.clone();
+        if let Some(fold) = &mut new.state_fold {
+            fold.deltas[0].value = "amount".to_string();
+        }
+        assert_eq!(
+            IrDiff::compute_spec(&old, &new).changes,
+            vec![IrChange {
+                path: "state_fold.deltas.0".to_string(),
+                old: Some("(from_address) += -amount".to_string()),
+                new: Some("(from_address) += amount".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_endpoint_sql_change() {
+        let old = create_endpoint_ir();
+        let mut new = create_endpoint_ir();
+        new.sql_query = "SELECT from_address FROM token_transfers ORDER BY 1 LIMIT $1".to_string();
+
+        let diff = IrDiff::compute_endpoint(&old, &new);
+
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].path, "sql_query");
+    }
+
+    #[test]
+    fn test_endpoint_query_param_default_change() {
+        let old = create_endpoint_ir();
+        let mut new = create_endpoint_ir();
+        new.query_params[0].default = Some(json!(100));
+        new.tables_referenced.push("other_table".to_string());
+
+        let diff = IrDiff::compute_endpoint(&old, &new);
+
+        assert_eq!(diff.changes.len(), 2);
+        assert!(diff.render().contains("u32 (default: 100)"));
+        assert!(diff.render().contains("+ tables_referenced: other_table"));
+    }
+
+    #[test]
+    fn test_live_schema_ignores_aliases_and_constraints() {
+        let ir = create_spec_ir();
+        let live = vec![(
+            "from_address".to_string(),
+            "character varying(42)".to_string(),
+        )];
+        assert!(!IrDiff::compute_live_schema(&live, &ir).has_changes());
+
+        let live = vec![
+            ("from_address".to_string(), "text".to_string()),
+            ("memo".to_string(), "text".to_string()),
+        ];
+        let diff = IrDiff::compute_live_schema(&live, &ir);
+        let rendered = diff.render();
+        assert_eq!(diff.changes.len(), 2);
+        assert!(rendered.contains("~ columns.from_address"));
+        assert!(rendered.contains("- columns.memo: text"));
+
+        assert_eq!(
+            normalize_column_type("NUMERIC(78, 0) NOT NULL DEFAULT 0"),
+            "numeric(78,0)"
+        );
+        assert_eq!(
+            normalize_column_type("TIMESTAMPTZ"),
+            "timestamp with time zone"
+        );
+        assert_eq!(normalize_column_type("INT8[]"), "bigint[]");
+    }
+}
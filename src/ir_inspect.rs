@@ -0,0 +1,215 @@
+//! `smorty ir list/show/diff`: inspect generated IR without reading the JSON files
+
+use crate::ai::{EndpointIrResult, IrGenerationResult};
+use crate::endpoint_path;
+use crate::ir::{ENDPOINTS_DIR, Ir, SPECS_DIR};
+use crate::ir_diff::IrDiff;
+use anyhow::{Context, Result};
+use sqlx::PgPool;
+use std::fs;
+use std::path::Path;
+
+/// A spec or endpoint IR, resolved from a name or a file path
+#[derive(Debug, Clone)]
+pub enum IrDocument {
+    Spec(Box<IrGenerationResult>),
+    Endpoint(Box<EndpointIrResult>),
+}
+
+impl IrDocument {
+    /// Load an IR from a JSON file, `<contract>/<spec>` or an endpoint path such as
+    /// `/api/pools`, optionally preceded by its method as in `POST /api/pools`
+    pub fn resolve(name: &str) -> Result<Self> {
+        let path = Path::new(name);
+        if path.is_file() {
+            return Self::load_file(path);
+        }
+
+        let (method, endpoint) = match name.split_once(' ') {
+            Some((method, endpoint)) if endpoint.trim_start().starts_with('/') => (
+                endpoint_path::normalize_method(method)?,
+                endpoint.trim_start(),
+            ),
+            _ => ("GET".to_string(), name),
+        };
+        if endpoint.starts_with('/') {
+            let path = endpoint_path::normalize(endpoint)?;
+            return Ok(Self::Endpoint(Box::new(Ir::load_ir_endpoint(
+                &path, &method,
+            )?)));
+        }
+        match name.split_once('/') {
+            Some((contract_name, spec_name)) => Ok(Self::Spec(Box::new(Ir::load_ir_spec(
+                contract_name,
+                spec_name,
+            )?))),
+            None => anyhow::bail!(
+                "'{}' is not an IR file, a <contract>/<spec> or an endpoint path",
+                name
+            ),
+        }
+    }
+
+    /// Parse an IR file, telling specs and endpoints apart by their fields
+    fn load_file(path: &Path) -> Result<Self> {
+        let content =
+            fs::read_to_string(path).context(format!("Failed to read IR file: {:?}", path))?;
+        let value: serde_json::Value =
+            serde_json::from_str(&content).context(format!("Failed to parse {:?}", path))?;
+
+        if value.get("endpoint_path").is_some() {
+            let ir = serde_json::from_value(value)
+                .context(format!("Failed to parse endpoint IR: {:?}", path))?;
+            Ok(Self::Endpoint(Box::new(ir)))
+        } else {
+            let ir = serde_json::from_value(value)
+                .context(format!("Failed to parse spec IR: {:?}", path))?;
+            Ok(Self::Spec(Box::new(ir)))
+        }
+    }
+
+    /// Pretty-printed JSON
+    pub fn render(&self) -> Result<String> {
+        let json = match self {
+            Self::Spec(ir) => serde_json::to_string_pretty(ir),
+            Self::Endpoint(ir) => serde_json::to_string_pretty(ir),
+        };
+        json.context("Failed to serialize IR")
+    }
+
+    /// Difference between two IRs of the same kind
+    pub fn diff(&self, other: &Self) -> Result<IrDiff> {
+        match (self, other) {
+            (Self::Spec(old), Self::Spec(new)) => Ok(IrDiff::compute_spec(old, new)),
+            (Self::Endpoint(old), Self::Endpoint(new)) => Ok(IrDiff::compute_endpoint(old, new)),
+            _ => anyhow::bail!("Can't diff a spec IR with an endpoint IR"),
+        }
+    }
+}
+
+/// One line of `smorty ir list`
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrListing {
+    /// `<contract>/<spec>` or the endpoint path
+    pub name: String,
+    pub summary: String,
+}
+
+/// Every spec IR under `ir/specs`, sorted by name
+pub fn list_specs() -> Result<Vec<IrListing>> {
+    let mut listings = Vec::new();
+    let specs_dir = Path::new(SPECS_DIR);
+    if !specs_dir.exists() {
+        return Ok(listings);
+    }
+
+    for contract in fs::read_dir(specs_dir)? {
+        let contract = contract?.path();
+        if !contract.is_dir() {
+            continue;
+        }
+        let contract_name = contract.file_name().unwrap_or_default().to_string_lossy();
+        for entry in fs::read_dir(&contract)? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            let spec_name = path.file_stem().unwrap_or_default().to_string_lossy();
+            let IrDocument::Spec(ir) = IrDocument::load_file(&path)? else {
+                continue;
+            };
+            listings.push(IrListing {
+                name: Ir::source_name(&contract_name, &spec_name),
+                summary: spec_summary(&ir),
+            });
+        }
+    }
+
+    listings.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(listings)
+}
+
+/// Every endpoint IR under `ir/endpoints`, sorted by path
+pub fn list_endpoints() -> Result<Vec<IrListing>> {
+    if !Path::new(ENDPOINTS_DIR).exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut listings: Vec<IrListing> = Ir::load_all_ir_endpoints()?
+        .iter()
+        .map(|ir| IrListing {
+            name: ir.endpoint_path.clone(),
+            summary: endpoint_summary(ir),
+        })
+        .collect();
+    listings.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(listings)
+}
+
+fn spec_summary(ir: &IrGenerationResult) -> String {
+    format!(
+        "{} on {} from block {} -> {} ({} columns)",
+        ir.event_signature,
+        ir.chain,
+        ir.start_block,
+        ir.table_schema.table_name,
+        ir.table_schema.columns.len() + ir.table_schema.computed_columns.len()
+    )
+}
+
+fn endpoint_summary(ir: &EndpointIrResult) -> String {
+    format!(
+        "{} reading {} ({} query params)",
+        ir.method,
+        ir.tables_referenced.join(", "),
+        ir.query_params.len()
+    )
+}
+
+/// Column names and types of a table as Postgres reports them, in column order
+pub async fn live_columns(pool: &PgPool, table_name: &str) -> Result<Vec<(String, String)>> {
+    let columns: Vec<(String, String)> = sqlx::query_as(
+        "SELECT attname::TEXT, format_type(atttypid, atttypmod)
+         FROM pg_attribute
+         WHERE attrelid = to_regclass($1) AND attnum > 0 AND NOT attisdropped
+         ORDER BY attnum",
+    )
+    .bind(table_name)
+    .fetch_all(pool)
+    .await
+    .context(format!("Failed to read columns of table {}", table_name))?;
+
+    if columns.is_empty() {
+        anyhow::bail!("Table '{}' doesn't exist in the database", table_name);
+    }
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_file_detects_kind() {
+        let dir = TempDir::new().unwrap();
+        let endpoint = dir.path().join("api_pools.json");
+        fs::write(
+            &endpoint,
+            r#"{"endpoint_path": "/api/pools", "description": "", "method": "GET",
+                "path_params": [], "query_params": [],
+                "response_schema": {"name": "Pools", "fields": []},
+                "sql_query": "SELECT 1", "tables_referenced": ["pools"]}"#,
+        )
+        .unwrap();
+
+        let document = IrDocument::resolve(endpoint.to_str().unwrap()).unwrap();
+        let IrDocument::Endpoint(ir) = &document else {
+            panic!("expected an endpoint IR");
+        };
+        assert_eq!(endpoint_summary(ir), "GET reading pools (0 query params)");
+        assert!(document.diff(&document).unwrap().changes.is_empty());
+
+        assert!(IrDocument::resolve("not-an-ir").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use anyhow::Result;
+
+/// Incremental structural check of a JSON document arriving in chunks
+///
+/// Only tracks nesting, strings and what may follow the top-level value, which
+/// is enough to notice a malformed streamed response long before it finishes.
+/// The complete document is still parsed with serde once it has arrived.
+#[derive(Debug, Default)]
+pub struct JsonStreamValidator {
+    /// Open objects and arrays, as their closing characters
+    stack: Vec<char>,
+    in_string: bool,
+    escaped: bool,
+    started: bool,
+    complete: bool,
+    /// Characters seen so far, for error messages
+    position: usize,
+}
+
+impl JsonStreamValidator {
+    /// Feed the next chunk of the document
+    pub fn push(&mut self, chunk: &str) -> Result<()> {
+        for c in chunk.chars() {
+            self.position += 1;
+            self.push_char(c)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the top-level value has been closed
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    fn push_char(&mut self, c: char) -> Result<()> {
+        if self.in_string {
+            if self.escaped {
+                self.escaped = false;
+            } else if c == '\\' {
+                self.escaped = true;
+            } else if c == '"' {
+                self.in_string = false;
+            } else if c.is_control() {
+                anyhow::bail!("unescaped control character in string at {}", self.position);
+            }
+            return Ok(());
+        }
+
+        if c.is_whitespace() {
+            return Ok(());
+        }
+
+        if self.complete {
+            anyhow::bail!("unexpected '{}' after end of JSON at {}", c, self.position);
+        }
+
+        if !self.started {
+            if c != '{' && c != '[' {
+                anyhow::bail!("expected '{{' or '[' but found '{}'", c);
+            }
+            self.started = true;
+        }
+
+        match c {
+            '{' => self.stack.push('}'),
+            '[' => self.stack.push(']'),
+            '}' | ']' => {
+                if self.stack.pop() != Some(c) {
+                    anyhow::bail!("unexpected '{}' at {}", c, self.position);
+                }
+                self.complete = self.stack.is_empty();
+            }
+            '"' => self.in_string = true,
+            ':' | ',' | '-' | '+' | '.' => {}
+            c if c.is_ascii_alphanumeric() => {}
+            c => anyhow::bail!("unexpected '{}' at {}", c, self.position),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_chunked_document() {
+        let mut validator = JsonStreamValidator::default();
+        for chunk in [
+            "{\"sql_query\": \"SELECT ",
+            "'}' FROM t\", \"params\": [1, ",
+            "-2.5e3, true, null], \"nested\": {\"a\\\"b\": {}}",
+        ] {
+            validator.push(chunk).unwrap();
+            assert!(!validator.is_complete());
+        }
+
+        validator.push("}\n").unwrap();
+        assert!(validator.is_complete());
+    }
+
+    #[test]
+    fn test_rejects_malformed_documents_early() {
+        assert!(JsonStreamValidator::default().push("```json\n{").is_err());
+        assert!(JsonStreamValidator::default().push("{\"a\": [1}").is_err());
+        assert!(JsonStreamValidator::default().push("{\"a\": 1} {").is_err());
+        assert!(
+            JsonStreamValidator::default()
+                .push("{\"a\": \"x\ny\"}")
+                .is_err()
+        );
+        assert!(JsonStreamValidator::default().push("{\"a\": @}").is_err());
+    }
+}
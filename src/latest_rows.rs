@@ -0,0 +1,182 @@
+//! Read-through cache of the newest rows of "latest events" endpoints
+//!
+//! Endpoints that page through one table newest first, e.g.
+//! `SELECT ... FROM swaps ORDER BY block_number DESC, log_index DESC LIMIT $1`,
+//! are polled far more often than the table changes. With `server.latestRows`
+//! set, the first request runs the query for that many rows and keeps them in
+//! memory; later requests for up to that many rows are sliced from the cache
+//! until the table's watermark moves. Filtered and historical queries, and
+//! endpoints scoped to a tenant, always go to Postgres.
+
+use crate::ai::EndpointIrResult;
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+/// Newest rows of each cacheable endpoint, with the watermark they were read at
+#[derive(Debug, Default)]
+pub struct LatestRows {
+    capacity: usize,
+    /// Routes of the endpoints served from the cache
+    endpoints: HashSet<String>,
+    rows: Mutex<HashMap<String, (i64, Vec<JsonValue>)>>,
+}
+
+impl LatestRows {
+    /// Cache of `capacity` rows for each endpoint in `endpoints` that only reads
+    /// the newest rows of its table
+    pub fn new<'a>(
+        capacity: usize,
+        endpoints: impl IntoIterator<Item = &'a EndpointIrResult>,
+    ) -> Self {
+        let endpoints: HashSet<String> = endpoints
+            .into_iter()
+            .filter(|endpoint| is_latest_query(endpoint))
+            .map(EndpointIrResult::route)
+            .collect();
+        if !endpoints.is_empty() {
+            tracing::info!(
+                "Caching the latest {} rows of {} endpoint(s)",
+                capacity,
+                endpoints.len()
+            );
+        }
+        Self {
+            capacity,
+            endpoints,
+            rows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Number of rows kept for each endpoint
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Whether requests to an endpoint can be served from the cache
+    pub fn serves(&self, endpoint: &EndpointIrResult) -> bool {
+        self.endpoints.contains(&endpoint.route())
+    }
+
+    /// First `limit` cached rows of an endpoint, if they were read at `watermark`
+    pub fn get(
+        &self,
+        endpoint: &EndpointIrResult,
+        watermark: i64,
+        limit: usize,
+    ) -> Option<Vec<JsonValue>> {
+        let rows = self.rows.lock().unwrap_or_else(|e| e.into_inner());
+        let (read_at, cached) = rows.get(&endpoint.route())?;
+        (*read_at == watermark).then(|| cached.iter().take(limit).cloned().collect())
+    }
+
+    /// Replace the cached rows of an endpoint
+    pub fn insert(&self, endpoint: &EndpointIrResult, watermark: i64, rows: Vec<JsonValue>) {
+        self.rows
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(endpoint.route(), (watermark, rows));
+    }
+}
+
+/// Whether an endpoint only returns the newest rows of a single table, so that
+/// fewer rows are always a prefix of more
+///
+/// The endpoint must be a GET whose query takes the limit as its only parameter, read one table with no
+/// filter, join, grouping or subquery, and order by `block_number DESC`
+/// (optionally then `log_index DESC`).
+pub fn is_latest_query(endpoint: &EndpointIrResult) -> bool {
+    let [table] = endpoint.tables_referenced.as_slice() else {
+        return false;
+    };
+    if !endpoint.method.eq_ignore_ascii_case("GET")
+        || !endpoint.path_params.is_empty()
+        || endpoint.query_params.len() != 1
+        || endpoint.query_params[0].name != "limit"
+    {
+        return false;
+    }
+
+    let sql = endpoint
+        .sql_query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let Some(rest) = sql.strip_prefix("select ") else {
+        return false;
+    };
+    let Some((columns, from)) = rest.split_once(" from ") else {
+        return false;
+    };
+    if columns.contains("select") {
+        return false;
+    }
+
+    let table = table.to_lowercase();
+    [
+        "order by block_number desc limit $1",
+        "order by block_number desc, log_index desc limit $1",
+    ]
+    .iter()
+    .any(|tail| from == format!("{} {}", table, tail))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{QueryParam, ResponseSchema};
+    use serde_json::json;
+
+    fn endpoint(sql: &str) -> EndpointIrResult {
+        EndpointIrResult {
+            endpoint_path: "/api/swaps/latest".to_string(),
+            description: "Latest swaps".to_string(),
+            method: "GET".to_string(),
+            path_params: vec![],
+            query_params: vec![QueryParam {
+                name: "limit".to_string(),
+                param_type: "i64".to_string(),
+                default: Some(json!(50)),
+                description: String::new(),
+                allowed_values: vec![],
+            }],
+            response_schema: ResponseSchema {
+                name: "Swap".to_string(),
+                fields: vec![],
+            },
+            sql_query: sql.to_string(),
+            stages: Vec::new(),
+            tables_referenced: vec!["swaps".to_string()],
+            tags: vec![],
+            scopes: vec![],
+            cache_ttl: None,
+        }
+    }
+
+    #[test]
+    fn test_caches_only_newest_rows_queries() {
+        assert!(is_latest_query(&endpoint(
+            "SELECT block_number, amount::NUMERIC / 1e18 AS amount\nFROM swaps\nORDER BY block_number DESC, log_index DESC\nLIMIT $1"
+        )));
+        assert!(!is_latest_query(&endpoint(
+            "SELECT * FROM swaps WHERE amount > 0 ORDER BY block_number DESC LIMIT $1"
+        )));
+        assert!(!is_latest_query(&endpoint(
+            "SELECT * FROM swaps ORDER BY block_number ASC LIMIT $1"
+        )));
+        assert!(!is_latest_query(&endpoint(
+            "SELECT *, (SELECT 1 FROM pools) FROM swaps ORDER BY block_number DESC LIMIT $1"
+        )));
+
+        let swaps = endpoint("SELECT * FROM swaps ORDER BY block_number DESC LIMIT $1");
+        let cache = LatestRows::new(3, [&swaps]);
+        assert!(cache.serves(&swaps));
+        assert!(cache.get(&swaps, 10, 2).is_none());
+
+        cache.insert(&swaps, 10, vec![json!(3), json!(2), json!(1)]);
+        assert_eq!(cache.get(&swaps, 10, 2), Some(vec![json!(3), json!(2)]));
+        // The table moved on, so the rows are stale
+        assert!(cache.get(&swaps, 11, 2).is_none());
+    }
+}
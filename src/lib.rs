@@ -1,11 +1,79 @@
 // Library modules for smorty indexer
+pub mod abi;
 pub mod ai;
+pub mod artifact;
+pub mod audit;
+pub mod auth;
+pub mod bench;
+pub mod calls;
+pub mod chains;
 pub mod cli;
 pub mod config;
+pub mod config_lint;
 pub mod constants;
+pub mod coverage;
+pub mod daemon;
+pub mod databases;
+pub mod decoded;
+pub mod dry_run;
+pub mod endpoint_files;
+pub mod endpoint_path;
+pub mod endpoint_test;
+pub mod envelope;
+pub mod error;
+pub mod export;
+pub mod field_mapping;
+pub mod health;
+pub mod history;
+pub mod hooks;
+pub mod identifier;
+pub mod index_def;
 pub mod indexer;
 pub mod ir;
+pub mod ir_diff;
+pub mod ir_inspect;
+pub mod json_stream;
+pub mod latest_rows;
+pub mod log_buffer;
+pub mod manifest;
+pub mod mcp;
 pub mod migration;
+pub mod migration_check;
+pub mod numeric;
+pub mod pause;
+pub mod privacy;
+pub mod progress;
+pub mod prompts;
+pub mod prune;
+pub mod query_repair;
+pub mod query_stages;
+pub mod rate_limit;
+pub mod redaction;
+pub mod rpc_logs;
+pub mod scaffold;
 pub mod schema_diff;
+pub mod schema_history;
 pub mod schema_state;
+pub mod search;
+pub mod secrets;
 pub mod server;
+pub mod slow_query;
+pub mod spec_order;
+pub mod sql_guard;
+pub mod sse;
+pub mod start_block;
+pub mod state_table;
+pub mod subgraph;
+pub mod system;
+pub mod table_api;
+pub mod table_relevance;
+pub mod table_stats;
+pub mod task_template;
+pub mod tenancy;
+pub mod transform;
+pub mod usage;
+pub mod validate;
+pub mod verify;
+pub mod warehouse;
+pub mod watchlist;
+pub mod watermark;
@@ -0,0 +1,179 @@
+//! Memory-bounded queue of a chunk's logs, grouped by spec
+//!
+//! Dense ranges can return hundreds of thousands of logs per chunk. Once more
+//! than `indexer.maxBufferedLogs` are held, the buffered logs are written to
+//! temporary files under `indexer.spillDir` (the system temp directory by
+//! default) and read back in batches while the chunk is processed, so memory
+//! stays flat whatever the event density.
+
+use crate::config::IndexerConfig;
+use alloy::rpc::types::Log;
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// Logs of a chunk by spec index, spilling to disk past a limit
+pub struct LogBuffer {
+    queues: Vec<SpecQueue>,
+    /// Logs held in memory, 0 for no limit
+    max_buffered: usize,
+    spill_dir: PathBuf,
+    buffered: usize,
+    spilled: usize,
+}
+
+/// Logs of one spec, in the order they were pushed: spilled ones first
+#[derive(Default)]
+struct SpecQueue {
+    logs: Vec<Log>,
+    spill: Option<BufWriter<File>>,
+}
+
+impl LogBuffer {
+    pub fn new(specs: usize, config: &IndexerConfig) -> Self {
+        Self {
+            queues: (0..specs).map(|_| SpecQueue::default()).collect(),
+            max_buffered: config.max_buffered_logs,
+            spill_dir: config
+                .spill_dir
+                .as_ref()
+                .map(PathBuf::from)
+                .unwrap_or_else(std::env::temp_dir),
+            buffered: 0,
+            spilled: 0,
+        }
+    }
+
+    /// Add a log of the spec at `spec`
+    pub fn push(&mut self, spec: usize, log: Log) -> Result<()> {
+        self.queues[spec].logs.push(log);
+        self.buffered += 1;
+        if self.max_buffered > 0 && self.buffered >= self.max_buffered {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Number of logs pushed
+    pub fn len(&self) -> usize {
+        self.buffered + self.spilled
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of logs written to disk
+    pub fn spilled(&self) -> usize {
+        self.spilled
+    }
+
+    /// Write the logs held in memory to each spec's spill file
+    fn spill(&mut self) -> Result<()> {
+        for queue in &mut self.queues {
+            if queue.logs.is_empty() {
+                continue;
+            }
+            let writer = match &mut queue.spill {
+                Some(writer) => writer,
+                None => {
+                    let file = tempfile::tempfile_in(&self.spill_dir).context(format!(
+                        "Failed to create a spill file in {}",
+                        self.spill_dir.display()
+                    ))?;
+                    queue.spill.insert(BufWriter::new(file))
+                }
+            };
+            for log in queue.logs.drain(..) {
+                serde_json::to_writer(&mut *writer, &log)?;
+                writer
+                    .write_all(b"\n")
+                    .context("Failed to write spilled logs")?;
+            }
+        }
+        tracing::debug!("Spilled {} logs to disk", self.buffered);
+        self.spilled += self.buffered;
+        self.buffered = 0;
+        Ok(())
+    }
+
+    /// All logs with their spec index, by spec and then in the order they were pushed
+    pub fn drain(self) -> impl Iterator<Item = Result<(usize, Log)>> + Send {
+        self.queues
+            .into_iter()
+            .enumerate()
+            .flat_map(|(spec, queue)| {
+                read_spill(queue.spill)
+                    .chain(queue.logs.into_iter().map(Ok))
+                    .map(move |log| log.map(|log| (spec, log)))
+            })
+    }
+}
+
+/// Logs written to a spill file, read back one line at a time
+fn read_spill(spill: Option<BufWriter<File>>) -> Box<dyn Iterator<Item = Result<Log>> + Send> {
+    let Some(writer) = spill else {
+        return Box::new(std::iter::empty());
+    };
+    let file = writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .and_then(|mut file| file.seek(SeekFrom::Start(0)).map(|_| file));
+    match file {
+        Ok(file) => Box::new(BufReader::new(file).lines().map(|line| {
+            let line = line.context("Failed to read spilled logs")?;
+            serde_json::from_str(&line).context("Failed to parse spilled log")
+        })),
+        Err(e) => Box::new(std::iter::once(
+            Err(e).context("Failed to read spilled logs"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc_logs::parse_log;
+    use serde_json::json;
+
+    fn log(block: u64) -> Log {
+        parse_log(json!({
+            "address": "0x4200000000000000000000000000000000000010",
+            "topics": [],
+            "data": "0x",
+            "blockNumber": format!("{:#x}", block),
+            "blockTimestamp": "0x65f0a000",
+            "logIndex": "0x0",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_spills_and_keeps_order_by_spec() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = IndexerConfig {
+            max_buffered_logs: 3,
+            spill_dir: Some(dir.path().display().to_string()),
+            ..IndexerConfig::default()
+        };
+        let mut buffer = LogBuffer::new(2, &config);
+        for block in 1..=7 {
+            buffer.push((block % 2) as usize, log(block)).unwrap();
+        }
+        assert_eq!(buffer.len(), 7);
+        assert_eq!(buffer.spilled(), 6);
+
+        let drained: Vec<(usize, u64)> = buffer
+            .drain()
+            .map(|entry| {
+                let (spec, log) = entry.unwrap();
+                (spec, log.block_number.unwrap())
+            })
+            .collect();
+        assert_eq!(
+            drained,
+            [(0, 2), (0, 4), (0, 6), (1, 1), (1, 3), (1, 5), (1, 7)]
+        );
+    }
+}
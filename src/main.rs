@@ -1,27 +1,112 @@
 use anyhow::{Context, Result};
 use clap::Parser;
 use smorty::ai::AiClient;
-use smorty::cli::{Cli, Commands};
+use smorty::cli::{
+    self, BenchTarget, Cli, Commands, ConfigCommand, ImportCommand, IrCommand, SchemaCommand,
+    SyncCommand,
+};
 use smorty::config::Config;
+use smorty::config_lint;
+use smorty::coverage::SpecCoverage;
+use smorty::daemon::{self, PidFile};
+use smorty::endpoint_test::EndpointTest;
+use smorty::error::{ErrorReport, ResultExt, SmortyError};
+use smorty::health;
 use smorty::indexer::Indexer;
 use smorty::ir::Ir;
+use smorty::ir_diff::IrDiff;
+use smorty::ir_inspect::{self, IrDocument};
+use smorty::manifest;
 use smorty::migration::Migration;
+use smorty::migration_check;
+use smorty::pause::{self, Pause};
+use smorty::progress::{IndexProgress, LogWriter};
+use smorty::prompts::{self, PromptTemplates};
+use smorty::prune::{self, RetentionPolicy};
+use smorty::scaffold::{Scaffold, Scaffolded};
+use smorty::schema_history::{self, ChangeDescription};
+use smorty::schema_state::{SCHEMA_STATE_FILE, SchemaState};
+use smorty::secrets::ConfigKey;
 use smorty::server;
+use smorty::start_block;
+use smorty::subgraph;
+use smorty::system;
+use smorty::validate::{self, SpecStatus};
+use smorty::warehouse::WarehouseSync;
+use sqlx::PgPool;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize tracing
+async fn main() -> ExitCode {
+    // Parse CLI arguments
+    let cli = Cli::parse();
+
+    // Initialize tracing; stdout carries the protocol when serving MCP
+    let writer = match cli.command {
+        Commands::Serve { mcp: true, .. } => BoxMakeWriter::new(std::io::stderr),
+        _ => BoxMakeWriter::new(LogWriter::default),
+    };
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "smorty=info,tower_http=debug".into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_writer(writer))
         .init();
+    // Resolved before changing into the project directory
+    let error_report = cli
+        .error_report
+        .as_deref()
+        .and_then(|path| std::path::absolute(path).ok());
+
+    let Err(e) = execute(cli).await else {
+        return ExitCode::SUCCESS;
+    };
+    eprintln!("Error: {:?}", e);
+
+    let report = ErrorReport::new(&e);
+    if let Some(path) = error_report
+        && let Err(write_err) = report.write(&path)
+    {
+        eprintln!("{:#}", write_err);
+    }
 
-    // Parse CLI arguments
-    let cli = Cli::parse();
+    ExitCode::from(report.exit_code)
+}
+
+async fn execute(cli: Cli) -> Result<()> {
+    // Every path smorty reads or writes is relative to the project directory
+    if let Some(project) = &cli.project {
+        std::env::set_current_dir(project)
+            .context(format!("Failed to enter project directory: {}", project))
+            .kind(SmortyError::Config)?;
+    }
+
+    // These commands don't need a valid config
+    match &cli.command {
+        Commands::Init { docker, force } => return init(&cli.config, *docker, *force),
+        Commands::Stop { pid_file } => return stop(Path::new(pid_file)),
+        Commands::Import { command } => return import(command),
+        Commands::Config { command } => return config_command(command, &cli.config),
+        Commands::Schema { command } => return schema(command),
+        Commands::Completions { shell } => {
+            cli::write_completions(*shell, &mut std::io::stdout());
+            return Ok(());
+        }
+        Commands::Man { out_dir } => {
+            return match out_dir {
+                Some(dir) => cli::write_man_pages(Path::new(dir)),
+                None => cli::write_man_page(&mut std::io::stdout()),
+            };
+        }
+        _ => {}
+    }
 
     // Load config
     let config =
@@ -31,23 +116,134 @@ async fn main() -> Result<()> {
 
     // Handle commands
     match cli.command {
-        Commands::GenSpec => {
-            gen_spec(&config).await?;
+        Commands::GenSpec {
+            yes,
+            verbose,
+            changed_only,
+            manifest,
+            preview,
+        } => {
+            if let Some(manifest) = manifest {
+                gen_spec_manifest(&cli.config, &config, Path::new(&manifest), yes, verbose).await?;
+            } else {
+                gen_spec(&config, yes, verbose, changed_only).await?;
+            }
+            if preview {
+                preview_specs(&config).await?;
+            }
+        }
+        Commands::GenEndpoint { yes, verbose } => {
+            gen_endpoint(&config, yes, verbose).await?;
+        }
+        Commands::GenOpenapi { out } => {
+            gen_openapi(&config, &out)?;
         }
-        Commands::GenEndpoint => {
-            gen_endpoint(&config).await?;
+        Commands::TestEndpoints { update } => {
+            test_endpoints(&config, update).await?;
         }
-        Commands::GenMigration => {
-            gen_migration(&config)?;
+        Commands::Validate => {
+            validate(&config)?;
         }
-        Commands::Migrate => {
-            migrate(&config).await?;
+        Commands::Ir { command } => {
+            ir(&config, command).await?;
         }
-        Commands::Index { daemon } => {
-            index(&config, daemon).await?;
+        Commands::GenMigration { message } => {
+            let description = match message {
+                Some(message) => ChangeDescription::Message(message),
+                None => ChangeDescription::Prompt,
+            };
+            gen_migration(&config, &description).await?;
         }
-        Commands::Serve { address, port } => {
-            serve(&config, &address, port).await?;
+        Commands::Migrate {
+            check,
+            shadow_database,
+        } => {
+            if check {
+                migration_check::check(&config.database.uri, shadow_database.as_deref()).await?;
+            } else {
+                migrate(&config).await?;
+            }
+        }
+        Commands::Prune {
+            keep_last,
+            keep_days,
+            yes,
+        } => {
+            let policy = RetentionPolicy {
+                keep_last,
+                keep_days,
+            };
+            prune::prune(&config, policy, yes)?;
+        }
+        Commands::Bench { target } => {
+            bench(&config, target).await?;
+        }
+        Commands::Index {
+            daemon,
+            foreground,
+            quiet,
+            pid_file,
+            log_file,
+            dry_run,
+            blocks,
+            from_block,
+            chain,
+            output,
+        } => {
+            if dry_run {
+                index_dry_run(
+                    &config,
+                    blocks,
+                    from_block,
+                    chain.as_deref(),
+                    output.as_deref().map(Path::new),
+                )
+                .await?;
+            } else if daemon && !foreground {
+                detach(&cli.config, Path::new(&pid_file), Path::new(&log_file))?;
+            } else {
+                let pid_file = daemon.then_some(Path::new(&pid_file));
+                index(&config, pid_file, quiet).await?;
+            }
+        }
+        Commands::Init { .. }
+        | Commands::Stop { .. }
+        | Commands::Import { .. }
+        | Commands::Config { .. }
+        | Commands::Schema { .. }
+        | Commands::Completions { .. }
+        | Commands::Man { .. } => {
+            // Handled before loading the config
+        }
+        Commands::Restart { pid_file, log_file } => {
+            stop(Path::new(&pid_file))?;
+            detach(&cli.config, Path::new(&pid_file), Path::new(&log_file))?;
+        }
+        Commands::Pause { target, reason } => {
+            pause(&config, &target, reason.as_deref()).await?;
+        }
+        Commands::Resume { target } => {
+            resume(&config, &target).await?;
+        }
+        Commands::Status => {
+            status(&config).await?;
+        }
+        Commands::Sync { command } => {
+            sync(&config, &command).await?;
+        }
+        Commands::Serve {
+            address,
+            port,
+            mcp,
+            readonly,
+        } => {
+            let mut config = config;
+            config.server.table_api |= readonly;
+            if mcp {
+                server::serve_mcp(&config).await?;
+            } else {
+                serve(&config, &address, port).await?;
+            }
         }
         Commands::Run { address, port } => {
             run(&config, &address, port).await?;
@@ -57,37 +253,91 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn gen_spec(config: &Config) -> Result<()> {
+async fn gen_spec(
+    config: &Config,
+    assume_yes: bool,
+    verbose: bool,
+    changed_only: bool,
+) -> Result<()> {
     tracing::info!("Starting spec IR generation");
 
     // Create AI client
-    let ai_client = AiClient::new(
-        config.ai.openai.api_key.clone(),
-        config.ai.openai.model.clone(),
-        config.ai.openai.temperature,
-    );
+    let ai_client = AiClient::from_config(&config.ai)
+        .with_prompts(PromptTemplates::load(Path::new(prompts::PROMPTS_DIR))?)
+        .with_verbose(verbose);
 
     // Generate spec IR
-    let ir_generator = Ir::new(ai_client);
-    ir_generator.generate_all(config).await?;
+    let ir_generator = Ir::new(ai_client)
+        .with_assume_yes(assume_yes)
+        .with_few_shot_examples(config.ai.few_shot_examples)
+        .with_changed_only(changed_only)
+        .with_hooks(&config.hooks);
+    let generated = ir_generator.generate_all(config).await?;
 
     tracing::info!("Spec IR generation complete");
 
+    // Regenerated specs may change their tables
+    if changed_only && generated > 0 {
+        gen_migration(config, &ChangeDescription::Prompt).await?;
+    }
+
     Ok(())
 }
 
-async fn gen_endpoint(config: &Config) -> Result<()> {
+/// Print sample rows decoded from each spec's most recent logs
+async fn preview_specs(config: &Config) -> Result<()> {
+    // The schema comes from the IR, so no migration is needed first
+    let previews = Indexer::offline(config)?.preview().await?;
+    for preview in &previews {
+        println!("{}", preview.render());
+    }
+    Ok(())
+}
+
+async fn gen_spec_manifest(
+    config_path: &str,
+    config: &Config,
+    manifest: &Path,
+    assume_yes: bool,
+    verbose: bool,
+) -> Result<()> {
+    let entries = manifest::read(manifest).kind(SmortyError::Config)?;
+    let config =
+        manifest::expand(Path::new(config_path), config, &entries).kind(SmortyError::Config)?;
+    tracing::info!(
+        "Generating spec IR for {} contracts from {}",
+        entries.len(),
+        manifest.display()
+    );
+
+    let ai_client = AiClient::from_config(&config.ai)
+        .with_prompts(PromptTemplates::load(Path::new(prompts::PROMPTS_DIR))?)
+        .with_verbose(verbose);
+    let ir_generator = Ir::new(ai_client)
+        .with_assume_yes(assume_yes)
+        .with_few_shot_examples(config.ai.few_shot_examples)
+        .with_contracts(entries.into_iter().map(|entry| entry.name).collect())
+        .with_hooks(&config.hooks);
+    ir_generator.generate_all(&config).await?;
+
+    tracing::info!("Spec IR generation complete");
+    Ok(())
+}
+
+async fn gen_endpoint(config: &Config, assume_yes: bool, verbose: bool) -> Result<()> {
     tracing::info!("Starting endpoint IR generation");
 
     // Create AI client
-    let ai_client = AiClient::new(
-        config.ai.openai.api_key.clone(),
-        config.ai.openai.model.clone(),
-        config.ai.openai.temperature,
-    );
+    let ai_client = AiClient::from_config(&config.ai)
+        .with_prompts(PromptTemplates::load(Path::new(prompts::PROMPTS_DIR))?)
+        .with_verbose(verbose);
 
     // Generate endpoint IR
-    let ir_generator = Ir::new(ai_client);
+    let ir_generator = Ir::new(ai_client)
+        .with_assume_yes(assume_yes)
+        .with_few_shot_examples(config.ai.few_shot_examples)
+        .with_endpoint_tables(config.ai.endpoint_tables)
+        .with_hooks(&config.hooks);
     ir_generator.generate_all_endpoints(config).await?;
 
     tracing::info!("Endpoint IR generation complete");
@@ -95,10 +345,223 @@ async fn gen_endpoint(config: &Config) -> Result<()> {
     Ok(())
 }
 
-fn gen_migration(config: &Config) -> Result<()> {
+fn gen_openapi(config: &Config, out: &str) -> Result<()> {
+    let openapi = server::openapi_json(config)?;
+    if out == "-" {
+        println!("{}", openapi);
+    } else {
+        std::fs::write(out, openapi).context(format!("Failed to write {}", out))?;
+        tracing::info!("Wrote OpenAPI document to {}", out);
+    }
+    Ok(())
+}
+
+async fn test_endpoints(config: &Config, update: bool) -> Result<()> {
+    tracing::info!("Running endpoint tests");
+
+    EndpointTest::run_all(&config.database.uri, update).await?;
+
+    tracing::info!("Endpoint tests complete");
+
+    Ok(())
+}
+
+fn init(config_path: &str, docker: bool, force: bool) -> Result<()> {
+    let done = Scaffold::new(config_path)
+        .with_docker(docker)
+        .with_force(force)
+        .write(Path::new("."))?;
+    for item in &done {
+        match item {
+            Scaffolded::Created(path) => println!("created  {}", path.display()),
+            Scaffolded::Skipped(path) => {
+                println!("skipped  {} (exists, --force overwrites)", path.display())
+            }
+        }
+    }
+    println!(
+        "Fill in {} and copy your ABIs into abi/, then run `smorty gen-spec`",
+        config_path
+    );
+    Ok(())
+}
+
+fn import(command: &ImportCommand) -> Result<()> {
+    match command {
+        ImportCommand::Subgraph { dir, out } => {
+            let summary = subgraph::import(Path::new(dir), Path::new(out))?;
+            println!(
+                "Imported {} contracts with {} specs and {} suggested endpoints into {}",
+                summary.contracts, summary.specs, summary.endpoints, out
+            );
+            if !summary.skipped_templates.is_empty() {
+                println!(
+                    "Skipped templates without a fixed address: {}",
+                    summary.skipped_templates.join(", ")
+                );
+            }
+            println!(
+                "Review {}, merge it into your config and run gen-endpoint",
+                out
+            );
+        }
+    }
+    Ok(())
+}
+
+fn schema(command: &SchemaCommand) -> Result<()> {
+    match command {
+        SchemaCommand::History { table } => {
+            let entries = schema_history::load(Path::new(schema_history::CHANGELOG_FILE))?;
+            let history = schema_history::render(&entries, table.as_deref());
+            if history.is_empty() {
+                println!(
+                    "No schema changes recorded in {}",
+                    schema_history::CHANGELOG_FILE
+                );
+            } else {
+                print!("{}", history);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn config_command(command: &ConfigCommand, config_path: &str) -> Result<()> {
+    match command {
+        ConfigCommand::Keygen { out } => {
+            ConfigKey::write(&ConfigKey::generate(), Path::new(out))?;
+            println!(
+                "Wrote a new config key to {}. Keep it out of version control",
+                out
+            );
+        }
+        ConfigCommand::Encrypt { value } => {
+            let value = match value {
+                Some(value) => value.clone(),
+                None => {
+                    let mut value = String::new();
+                    std::io::stdin()
+                        .read_to_string(&mut value)
+                        .context("Failed to read value from stdin")?;
+                    value.trim_end_matches(['\r', '\n']).to_string()
+                }
+            };
+            let key = ConfigKey::load().kind(SmortyError::Config)?;
+            println!("{}", key.encrypt(&value));
+        }
+        ConfigCommand::Lint => {
+            let config = Config::parse(config_path)
+                .context(format!("Failed to load config from: {}", config_path))?;
+            let schema = SchemaState::load(Path::new(SCHEMA_STATE_FILE))
+                .context(format!("Failed to load {}", SCHEMA_STATE_FILE))?;
+            let lints = config_lint::lint(&config, &schema);
+            for lint in &lints {
+                println!("warn  {}: {}", lint.location, lint.problem);
+                println!("      fix: {}", lint.fix);
+            }
+            if !lints.is_empty() {
+                return Err(SmortyError::Config(anyhow::anyhow!(
+                    "{} problem(s) found in {}",
+                    lints.len(),
+                    config_path
+                ))
+                .into());
+            }
+            println!("No problems found in {}", config_path);
+        }
+    }
+    Ok(())
+}
+
+fn validate(config: &Config) -> Result<()> {
+    let checks = validate::check_specs(config).kind(SmortyError::Config)?;
+
+    let mut problems = 0;
+    for check in &checks {
+        let name = format!("{}/{}", check.contract_name, check.spec_name);
+        match &check.status {
+            SpecStatus::Current => println!("ok    {}", name),
+            status if status.is_problem() => {
+                problems += 1;
+                println!("FAIL  {}: {}", name, status);
+            }
+            status => println!("warn  {}: {}", name, status),
+        }
+    }
+
+    if problems > 0 {
+        return Err(SmortyError::Config(anyhow::anyhow!(
+            "{} of {} specs are out of date with the config or their ABI",
+            problems,
+            checks.len()
+        ))
+        .into());
+    }
+
+    println!("All {} specs are valid", checks.len());
+    Ok(())
+}
+
+async fn ir(config: &Config, command: IrCommand) -> Result<()> {
+    match command {
+        IrCommand::List => {
+            println!("Specs:");
+            for listing in ir_inspect::list_specs()? {
+                println!("  {:<48} {}", listing.name, listing.summary);
+            }
+            println!("Endpoints:");
+            for listing in ir_inspect::list_endpoints()? {
+                println!("  {:<48} {}", listing.name, listing.summary);
+            }
+        }
+        IrCommand::Show { name } => {
+            println!("{}", IrDocument::resolve(&name)?.render()?);
+        }
+        IrCommand::Diff { old, new, schema } => {
+            let diff = match new {
+                Some(new) => IrDocument::resolve(&old)?.diff(&IrDocument::resolve(&new)?)?,
+                None if schema => {
+                    // Shared tables are only named in the config, so take the spec as loaded
+                    let ir = Ir::load_all_ir_specs(config)?
+                        .into_iter()
+                        .find(|(contract_name, spec_name, _)| {
+                            Ir::source_name(contract_name, spec_name) == old
+                        })
+                        .map(|(_, _, ir)| ir)
+                        .context(format!(
+                            "No spec '{}' in the config, use <contract>/<spec>",
+                            old
+                        ))?;
+                    let pool = PgPool::connect(config.database.uri_for_chain(&ir.chain))
+                        .await
+                        .context("Failed to connect to database")?;
+                    let live = ir_inspect::live_columns(&pool, &ir.table_schema.table_name).await?;
+                    IrDiff::compute_live_schema(&live, &ir)
+                }
+                None => unreachable!("clap requires a second IR or --schema"),
+            };
+
+            if diff.has_changes() {
+                print!("{}", diff.render());
+            } else {
+                println!("No differences");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn gen_migration(config: &Config, description: &ChangeDescription) -> Result<()> {
     tracing::info!("Generating migration from IR");
 
-    Migration::generate_from_ir(config)?;
+    // Every database gets the same migrations, so a table large in any of them counts
+    let mut large_tables = BTreeSet::new();
+    for (uri, _) in config.database.databases() {
+        large_tables.extend(Migration::find_large_tables(uri).await);
+    }
+    Migration::generate_from_ir_with_large_tables(config, &large_tables, description)?;
 
     tracing::info!("Migration generation complete");
 
@@ -108,30 +571,300 @@ fn gen_migration(config: &Config) -> Result<()> {
 async fn migrate(config: &Config) -> Result<()> {
     tracing::info!("Running database migrations");
 
-    Migration::run_migrations(&config.database.uri).await?;
+    // Per-chain databases get every table too, so shared SQL works on each of them
+    for (uri, _) in config.database.databases() {
+        Migration::run_migrations(uri).await?;
+    }
 
     tracing::info!("Migrations complete");
 
     Ok(())
 }
 
-async fn index(config: &Config, daemon: bool) -> Result<()> {
+async fn bench(config: &Config, target: BenchTarget) -> Result<()> {
+    match target {
+        BenchTarget::Index {
+            blocks,
+            chain,
+            fixture,
+            save_fixture,
+        } => {
+            tracing::info!("Benchmarking indexer");
+
+            let indexer = Indexer::new(config).await?;
+            let reports = indexer
+                .bench(
+                    blocks,
+                    chain.as_deref(),
+                    fixture.as_deref().map(Path::new),
+                    save_fixture.as_deref().map(Path::new),
+                )
+                .await?;
+
+            for report in reports {
+                println!("{}\n", report.render());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Run the indexer once, or as a daemon holding `pid_file` until it's stopped
+async fn index(config: &Config, pid_file: Option<&Path>, quiet: bool) -> Result<()> {
     tracing::info!("Starting indexer");
 
     // Create indexer instance
-    let indexer = Indexer::new(config).await?;
-
-    // Start indexing
-    indexer.start(daemon).await?;
+    let indexer = Indexer::new(config)
+        .await?
+        .with_progress(IndexProgress::new(quiet));
+
+    let Some(pid_file) = pid_file else {
+        indexer.start(false).await?;
+        tracing::info!("Indexer finished");
+        return Ok(());
+    };
+
+    let _pid_file = PidFile::create(pid_file)?;
+    if let Some(warehouse) = &config.warehouse
+        && let Some(interval_secs) = warehouse.interval_secs
+    {
+        let sync = WarehouseSync::connect(config, warehouse).await?;
+        tokio::spawn(sync.run(std::time::Duration::from_secs(interval_secs)));
+    }
+    tokio::select! {
+        result = indexer.start(true) => result?,
+        _ = daemon::shutdown_signal() => tracing::info!("Received shutdown signal, stopping indexer"),
+    }
 
     tracing::info!("Indexer finished");
     Ok(())
 }
 
+/// Print the rows a block range would insert, without touching the database
+async fn index_dry_run(
+    config: &Config,
+    blocks: u64,
+    from_block: Option<u64>,
+    chain: Option<&str>,
+    output: Option<&Path>,
+) -> Result<()> {
+    let indexer = Indexer::offline(config)?;
+
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).context(format!("Failed to create {:?}", path))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let summaries = indexer.dry_run(blocks, from_block, chain, &mut out).await?;
+    out.flush().context("Failed to write dry run rows")?;
+
+    // Keep stdout to the rows so it can be piped
+    for summary in summaries {
+        eprintln!("{}", summary.render());
+    }
+    Ok(())
+}
+
+/// Start the indexer daemon in the background
+fn detach(config_path: &str, pid_file: &Path, log_file: &Path) -> Result<()> {
+    if let Some(pid) = daemon::read_pid(pid_file)?
+        && daemon::is_running(pid)
+    {
+        anyhow::bail!("Indexer daemon is already running (pid {})", pid);
+    }
+
+    let args = [
+        "--config",
+        config_path,
+        "index",
+        "--daemon",
+        "--foreground",
+        "--quiet",
+        "--pid-file",
+        &pid_file.to_string_lossy(),
+    ]
+    .map(String::from);
+    let pid = daemon::spawn_detached(&args, log_file)?;
+
+    println!(
+        "Indexer daemon started (pid {}), logging to {}",
+        pid,
+        log_file.display()
+    );
+    println!("Stop it with 'smorty stop'");
+
+    Ok(())
+}
+
+fn stop(pid_file: &Path) -> Result<()> {
+    if daemon::stop(pid_file)? {
+        println!("Indexer daemon stopped");
+    } else {
+        println!("Indexer daemon is not running");
+    }
+    Ok(())
+}
+
+/// Pause indexing of a chain or spec until it is resumed
+async fn pause(config: &Config, target: &str, reason: Option<&str>) -> Result<()> {
+    pause::target_chain(config, target)?;
+    let pool = PgPool::connect(&config.database.uri)
+        .await
+        .context("Failed to connect to database")?;
+    system::ensure_system_tables(&pool).await?;
+    pause::pause(&pool, target, reason).await?;
+    println!(
+        "Paused {}, running indexers stop it on their next poll",
+        target
+    );
+    Ok(())
+}
+
+/// Resume indexing of a paused chain or spec
+async fn resume(config: &Config, target: &str) -> Result<()> {
+    pause::target_chain(config, target)?;
+    let pool = PgPool::connect(&config.database.uri)
+        .await
+        .context("Failed to connect to database")?;
+    system::ensure_system_tables(&pool).await?;
+    if pause::resume(&pool, target).await? {
+        println!("Resumed {}", target);
+    } else {
+        println!("{} is not paused", target);
+    }
+    Ok(())
+}
+
+/// Print each spec's indexed range and unresolved gaps
+async fn status(config: &Config) -> Result<()> {
+    let mut ir_specs = Ir::load_all_ir_specs(config)?;
+    let mut pools = HashMap::new();
+    for (uri, _) in config.database.databases() {
+        let pool = PgPool::connect(uri)
+            .await
+            .context("Failed to connect to database")?;
+        system::ensure_system_tables(&pool).await?;
+        pools.insert(uri, pool);
+    }
+
+    // Coverage is recorded in the database each chain is indexed into
+    let mut report = Vec::new();
+    for spec in &mut ir_specs {
+        let pool = &pools[config.database.uri_for_chain(&spec.2.chain)];
+        start_block::apply_recorded(pool, config, spec).await?;
+        report.extend(SpecCoverage::collect(pool, std::slice::from_ref(spec)).await?);
+    }
+    let mut alerts = HashMap::new();
+    for pool in pools.values() {
+        alerts.extend(health::load(pool).await?);
+    }
+    let pauses = pause::load(&pools[config.database.uri.as_str()]).await?;
+    let mut paused_chains: Vec<&Pause> = pauses
+        .values()
+        .filter(|pause| !pause.target.contains('/'))
+        .collect();
+    paused_chains.sort_by_key(|pause| &pause.target);
+    for pause in paused_chains {
+        println!("chain {} {}", pause.target, describe_pause(pause));
+    }
+    let mut gaps = 0;
+    for spec in &report {
+        let name = format!("{}/{}", spec.contract_name, spec.spec_name);
+        let Some(indexed_to) = spec.indexed_to else {
+            println!("{:<40} {:<10} not indexed yet", name, spec.chain);
+            if let Some(pause) = pauses.get(&name) {
+                println!("    {}", describe_pause(pause));
+            }
+            continue;
+        };
+        match config
+            .spec(&spec.contract_name, &spec.spec_name)
+            .and_then(|spec| spec.end_block)
+        {
+            Some(end_block) if spec.is_complete(end_block) => println!(
+                "{:<40} {:<10} blocks {} to {}, complete",
+                name, spec.chain, spec.start_block, end_block
+            ),
+            Some(end_block) => {
+                let total = end_block.saturating_sub(spec.start_block) + 1;
+                let done = indexed_to.min(end_block).saturating_sub(spec.start_block) + 1;
+                println!(
+                    "{:<40} {:<10} blocks {} to {} of {} ({:.1}%)",
+                    name,
+                    spec.chain,
+                    spec.start_block,
+                    indexed_to,
+                    end_block,
+                    done as f64 * 100.0 / total as f64
+                );
+            }
+            None => println!(
+                "{:<40} {:<10} blocks {} to {}",
+                name, spec.chain, spec.start_block, indexed_to
+            ),
+        }
+        for (from, to) in &spec.gaps {
+            gaps += 1;
+            println!("    gap: blocks {} to {}", from, to);
+        }
+        if let Some(pause) = pauses.get(&name) {
+            println!("    {}", describe_pause(pause));
+        }
+        if let Some(alert) = alerts.get(&name) {
+            println!(
+                "    stuck: no progress for {} polls while the head moved to {}",
+                alert.polls, alert.head
+            );
+            if let Some(error) = &alert.last_error {
+                println!("    last error: {}", error);
+            }
+        }
+    }
+
+    if gaps > 0 {
+        println!(
+            "{} gaps will be re-fetched the next time the indexer runs",
+            gaps
+        );
+    }
+    Ok(())
+}
+
+/// `paused since <time>` and the reason, for `status`
+fn describe_pause(pause: &Pause) -> String {
+    let since = pause.paused_at.format("%Y-%m-%d %H:%M:%S UTC");
+    match &pause.reason {
+        Some(reason) => format!("paused since {}: {}", since, reason),
+        None => format!("paused since {}", since),
+    }
+}
+
 async fn serve(config: &Config, address: &str, port: u16) -> Result<()> {
     server::serve(config, address, port).await
 }
 
+async fn sync(config: &Config, command: &SyncCommand) -> Result<()> {
+    match command {
+        SyncCommand::Warehouse => {
+            let warehouse = config
+                .warehouse
+                .as_ref()
+                .context("No [warehouse] section in the config")
+                .kind(SmortyError::Config)?;
+            let sync = WarehouseSync::connect(config, warehouse).await?;
+            for table in sync.sync().await? {
+                println!(
+                    "{:<40} {:>8} rows, synced to {}",
+                    table.table, table.rows, table.cursor
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
 async fn run(config: &Config, address: &str, port: u16) -> Result<()> {
     tracing::info!("Starting indexer and API server on {}:{}", address, port);
 
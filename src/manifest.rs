@@ -0,0 +1,216 @@
+//! Contract lists for `gen-spec --manifest`
+//!
+//! A manifest is a CSV file with one contract per row:
+//!
+//! ```csv
+//! address,chain,name,tasks
+//! 0xC02a...6Cc2,mainnet,WETH,"Transfer: track transfers; Deposit: track deposits"
+//! ```
+//!
+//! `tasks` holds the contract's specs as `<spec>: <task>`, separated by `;`. Each
+//! row becomes a `[contracts.<name>]` entry appended to the config, reading its ABI
+//! from `abi/<name>.json`.
+
+use crate::config::Config;
+use alloy::primitives::Address;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Directory the ABIs of manifest contracts are read from
+pub const MANIFEST_ABI_DIR: &str = "abi";
+
+/// A row of the manifest
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ManifestEntry {
+    pub address: String,
+    pub chain: String,
+    pub name: String,
+    pub tasks: String,
+}
+
+/// A spec of a manifest contract
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestSpec {
+    pub name: String,
+    pub task: String,
+}
+
+impl ManifestEntry {
+    /// Specs listed in `tasks`
+    pub fn specs(&self) -> Result<Vec<ManifestSpec>> {
+        let specs: Vec<ManifestSpec> = self
+            .tasks
+            .split(';')
+            .map(str::trim)
+            .filter(|task| !task.is_empty())
+            .map(|task| {
+                let (name, task) = task.split_once(':').context(format!(
+                    "Task '{}' of contract {} should be written as '<spec>: <task>'",
+                    task, self.name
+                ))?;
+                Ok(ManifestSpec {
+                    name: name.trim().to_string(),
+                    task: task.trim().to_string(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        if specs.is_empty() {
+            anyhow::bail!("Contract {} has no tasks", self.name);
+        }
+        Ok(specs)
+    }
+
+    pub fn abi_path(&self) -> String {
+        format!("{}/{}.json", MANIFEST_ABI_DIR, self.name)
+    }
+}
+
+/// Read the contracts of a manifest file
+pub fn read(path: &Path) -> Result<Vec<ManifestEntry>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .context(format!("Failed to read manifest: {}", path.display()))?;
+    reader
+        .deserialize()
+        .enumerate()
+        .map(|(i, row)| row.context(format!("Invalid row {} in manifest", i + 2)))
+        .collect()
+}
+
+/// Config entries for manifest contracts not already in `config`
+///
+/// Contracts already configured are skipped, so a manifest can be extended and
+/// run again. Fails if a contract's chain or ABI is missing.
+pub fn config_entries(config: &Config, entries: &[ManifestEntry]) -> Result<String> {
+    let mut toml = String::new();
+    for entry in entries {
+        if config.contracts.contains_key(&entry.name) {
+            tracing::info!("Contract {} is already in the config, skipping", entry.name);
+            continue;
+        }
+        if entry.name.is_empty()
+            || !entry
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            anyhow::bail!(
+                "Contract name '{}' should only contain letters, digits, '_' and '-'",
+                entry.name
+            );
+        }
+        if !config.chains.rpc_urls.contains_key(&entry.chain) {
+            anyhow::bail!(
+                "Contract {} is on chain '{}' which is not defined in chains section",
+                entry.name,
+                entry.chain
+            );
+        }
+        Address::from_str(&entry.address).context(format!(
+            "Invalid address for contract {}: {}",
+            entry.name, entry.address
+        ))?;
+        if !Path::new(&entry.abi_path()).exists() {
+            anyhow::bail!(
+                "ABI file '{}' for contract {} does not exist",
+                entry.abi_path(),
+                entry.name
+            );
+        }
+
+        let specs = entry.specs()?;
+        let _ = write!(
+            toml,
+            "\n[contracts.{}]\nchain = {}\naddress = {}\nabiPath = {}\n",
+            entry.name,
+            quote(&entry.chain),
+            quote(&entry.address),
+            quote(&entry.abi_path())
+        );
+        for spec in specs {
+            let _ = write!(
+                toml,
+                "\n[[contracts.{}.specs]]\nname = {}\ntask = {}\n",
+                entry.name,
+                quote(&spec.name),
+                quote(&spec.task)
+            );
+        }
+    }
+    Ok(toml)
+}
+
+/// Append the manifest's contracts to the config file and load the extended config
+///
+/// The file is restored if the extended config doesn't load.
+pub fn expand(config_path: &Path, config: &Config, entries: &[ManifestEntry]) -> Result<Config> {
+    let additions = config_entries(config, entries)?;
+    if additions.is_empty() {
+        return Ok(config.clone());
+    }
+
+    let original = fs::read_to_string(config_path).context("Failed to read config file")?;
+    let mut extended = original.clone();
+    if !extended.ends_with('\n') {
+        extended.push('\n');
+    }
+    extended.push_str(&additions);
+    fs::write(config_path, &extended).context("Failed to write config file")?;
+
+    match Config::load(config_path) {
+        Ok(config) => Ok(config),
+        Err(e) => {
+            fs::write(config_path, original).context("Failed to restore config file")?;
+            Err(anyhow::Error::from(e).context("Manifest contracts don't make a valid config"))
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_manifest_and_formats_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contracts.csv");
+        fs::write(
+            &path,
+            "address,chain,name,tasks\n\
+             0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2,mainnet,WETH,\"Transfer: track \"\"all\"\" transfers; Deposit: track deposits\"\n",
+        )
+        .unwrap();
+
+        let entries = read(&path).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "WETH");
+        assert_eq!(
+            entries[0].specs().unwrap(),
+            vec![
+                ManifestSpec {
+                    name: "Transfer".to_string(),
+                    task: "track \"all\" transfers".to_string(),
+                },
+                ManifestSpec {
+                    name: "Deposit".to_string(),
+                    task: "track deposits".to_string(),
+                },
+            ]
+        );
+
+        let unnamed = ManifestEntry {
+            tasks: "track transfers".to_string(),
+            ..entries[0].clone()
+        };
+        assert!(unnamed.specs().is_err());
+    }
+}
@@ -0,0 +1,493 @@
+//! Model Context Protocol server, so LLM agents can query the generated endpoints
+//!
+//! `smorty serve --mcp` speaks JSON-RPC over stdin and stdout, one message per
+//! line, and advertises every endpoint as a tool whose input schema is built
+//! from its path and query parameters. Tool calls go through the same router as
+//! HTTP requests, so API keys, tenancy, transforms and envelopes all apply.
+//! Logs are written to stderr in this mode.
+
+use crate::ai::EndpointIrResult;
+use crate::auth::API_KEY_HEADER;
+use crate::config::Config;
+use crate::server;
+use anyhow::{Context, Result};
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Request, header};
+use serde_json::{Map, Value as JsonValue, json};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tower::ServiceExt;
+
+/// Environment variable with the API key sent with tool calls, when keys are required
+pub const API_KEY_ENV: &str = "SMORTY_API_KEY";
+
+/// Protocol revision used when the client asks for one we don't know
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2024-11-05", "2025-03-26", "2025-06-18"];
+
+/// Largest endpoint response returned to the client
+const MAX_RESPONSE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Tool names are limited to 64 characters
+const MAX_TOOL_NAME_LEN: usize = 64;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// MCP server dispatching tool calls to the API router
+pub struct McpServer {
+    app: Router,
+    config: Arc<Config>,
+    api_key: Option<String>,
+}
+
+/// Error answered to a JSON-RPC request
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl McpServer {
+    pub fn new(app: Router, config: Arc<Config>) -> Self {
+        Self {
+            app,
+            config,
+            api_key: None,
+        }
+    }
+
+    /// Send this key with every tool call
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+
+    /// Answer messages from stdin until it is closed
+    pub async fn run_stdio(self) -> Result<()> {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut stdout = tokio::io::stdout();
+
+        while let Some(line) = lines.next_line().await.context("Failed to read stdin")? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some(response) = self.handle_message(&line).await else {
+                continue;
+            };
+            let mut out = serde_json::to_vec(&response)?;
+            out.push(b'\n');
+            stdout
+                .write_all(&out)
+                .await
+                .context("Failed to write stdout")?;
+            stdout.flush().await.context("Failed to write stdout")?;
+        }
+
+        Ok(())
+    }
+
+    /// Response to a message, or `None` for notifications
+    async fn handle_message(&self, line: &str) -> Option<JsonValue> {
+        let message: JsonValue = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => {
+                return Some(error_response(
+                    JsonValue::Null,
+                    RpcError::new(PARSE_ERROR, format!("Invalid JSON: {}", e)),
+                ));
+            }
+        };
+
+        let Some(method) = message.get("method").and_then(JsonValue::as_str) else {
+            // Responses from the client to requests we never send
+            if message.get("id").is_some() && message.get("method").is_none() {
+                return None;
+            }
+            return Some(error_response(
+                message.get("id").cloned().unwrap_or(JsonValue::Null),
+                RpcError::new(INVALID_REQUEST, "Expected a JSON-RPC request"),
+            ));
+        };
+        let id = message.get("id").cloned()?;
+        let params = message.get("params").cloned().unwrap_or(json!({}));
+
+        let result = match method {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => self.list_tools(),
+            "tools/call" => self.call_tool(&params).await,
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method '{}'", method),
+            )),
+        };
+
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(error) => error_response(id, error),
+        })
+    }
+
+    fn initialize(&self, params: &JsonValue) -> JsonValue {
+        let requested = params.get("protocolVersion").and_then(JsonValue::as_str);
+        let version = requested
+            .filter(|version| SUPPORTED_PROTOCOL_VERSIONS.contains(version))
+            .unwrap_or(PROTOCOL_VERSION);
+        json!({
+            "protocolVersion": version,
+            "capabilities": {"tools": {"listChanged": false}},
+            "serverInfo": {"name": "smorty", "version": env!("CARGO_PKG_VERSION")},
+            "instructions": "Each tool queries a smorty API endpoint over indexed on-chain data and returns its JSON response."
+        })
+    }
+
+    /// Endpoints served as tools, with their tool names
+    fn tools(&self) -> Result<Vec<(String, EndpointIrResult)>, RpcError> {
+        let endpoints = server::load_endpoints(&self.config)
+            .map_err(|e| RpcError::new(INTERNAL_ERROR, format!("{:#}", e)))?;
+        // Export endpoints run as background jobs, not as a single request
+        let exports: HashSet<&str> = self
+            .config
+            .endpoints
+            .iter()
+            .filter(|endpoint| endpoint.export)
+            .map(|endpoint| endpoint.endpoint.as_str())
+            .collect();
+
+        let mut names = HashSet::new();
+        let mut tools = Vec::new();
+        for endpoint in endpoints {
+            if exports.contains(endpoint.endpoint_path.as_str()) {
+                continue;
+            }
+            let mut base = tool_name(&endpoint.endpoint_path);
+            if !endpoint.method.eq_ignore_ascii_case("GET") {
+                let suffix = format!("_{}", endpoint.method.to_lowercase());
+                base.truncate(MAX_TOOL_NAME_LEN - suffix.len());
+                base.push_str(&suffix);
+            }
+            let mut name = base.clone();
+            let mut n = 2;
+            while !names.insert(name.clone()) {
+                let suffix = format!("_{}", n);
+                name = format!(
+                    "{}{}",
+                    &base[..base.len().min(MAX_TOOL_NAME_LEN - suffix.len())],
+                    suffix
+                );
+                n += 1;
+            }
+            tools.push((name, endpoint));
+        }
+        Ok(tools)
+    }
+
+    fn list_tools(&self) -> Result<JsonValue, RpcError> {
+        let tools: Vec<JsonValue> = self
+            .tools()?
+            .iter()
+            .map(|(name, endpoint)| tool(name, endpoint))
+            .collect();
+        Ok(json!({"tools": tools}))
+    }
+
+    async fn call_tool(&self, params: &JsonValue) -> Result<JsonValue, RpcError> {
+        let name = params
+            .get("name")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "Missing tool name"))?;
+        let arguments = match params.get("arguments") {
+            None | Some(JsonValue::Null) => Map::new(),
+            Some(JsonValue::Object(arguments)) => arguments.clone(),
+            Some(_) => {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    "Tool arguments should be an object",
+                ));
+            }
+        };
+
+        let tools = self.tools()?;
+        let (_, endpoint) = tools
+            .iter()
+            .find(|(tool, _)| tool == name)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Unknown tool '{}'", name)))?;
+        let uri = request_uri(endpoint, &arguments)
+            .map_err(|e| RpcError::new(INVALID_PARAMS, format!("{:#}", e)))?;
+
+        let mut request = Request::builder()
+            .method(endpoint.method.as_str())
+            .uri(&uri);
+        if let Some(key) = &self.api_key {
+            request = request.header(API_KEY_HEADER, key);
+        }
+        let body = if endpoint.method.eq_ignore_ascii_case("GET") {
+            Body::empty()
+        } else {
+            request = request.header(header::CONTENT_TYPE, "application/json");
+            Body::from(JsonValue::Object(body_arguments(endpoint, &arguments)).to_string())
+        };
+        let request = request
+            .body(body)
+            .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+        let response = self
+            .app
+            .clone()
+            .oneshot(request)
+            .await
+            .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), MAX_RESPONSE_BYTES)
+            .await
+            .map_err(|e| {
+                RpcError::new(INTERNAL_ERROR, format!("Failed to read response: {}", e))
+            })?;
+        let text = String::from_utf8_lossy(&body).into_owned();
+
+        // Errors are tool results, so the agent sees why its call failed
+        Ok(json!({
+            "content": [{"type": "text", "text": if status.is_success() {
+                text
+            } else {
+                format!("{}: {}", status, text)
+            }}],
+            "isError": !status.is_success(),
+        }))
+    }
+}
+
+fn error_response(id: JsonValue, error: RpcError) -> JsonValue {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": error.code, "message": error.message},
+    })
+}
+
+/// Tool name of an endpoint, e.g. `pools_by_pool_swaps` for `/api/pools/{pool}/swaps`
+pub fn tool_name(endpoint_path: &str) -> String {
+    let parts: Vec<String> = endpoint_path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .enumerate()
+        .filter(|(i, segment)| !(*i == 0 && *segment == "api"))
+        .map(
+            |(_, segment)| match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                Some(param) => format!("by_{}", param),
+                None => segment.to_string(),
+            },
+        )
+        .collect();
+    let mut name: String = parts
+        .join("_")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.is_empty() {
+        name = "root".to_string();
+    }
+    name.truncate(MAX_TOOL_NAME_LEN);
+    name
+}
+
+/// Tool definition of an endpoint, with a JSON schema of its parameters
+fn tool(name: &str, endpoint: &EndpointIrResult) -> JsonValue {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+
+    for param in &endpoint.path_params {
+        let mut schema = serde_json::to_value(server::generate_param_schema(&param.param_type))
+            .unwrap_or_else(|_| json!({"type": "string"}));
+        schema["description"] = json!(param.description);
+        properties.insert(param.name.clone(), schema);
+        required.push(param.name.clone());
+    }
+    for param in &endpoint.query_params {
+        let mut schema = serde_json::to_value(server::generate_query_param_schema(param))
+            .unwrap_or_else(|_| json!({"type": "string"}));
+        schema["description"] = json!(server::describe_query_param(param));
+        properties.insert(param.name.clone(), schema);
+        if param.default.is_none() {
+            required.push(param.name.clone());
+        }
+    }
+
+    json!({
+        "name": name,
+        "title": endpoint.endpoint_path,
+        "description": endpoint.description,
+        "inputSchema": {
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        },
+    })
+}
+
+/// Arguments a POST tool call sends as its JSON body, everything but path params
+fn body_arguments(
+    endpoint: &EndpointIrResult,
+    arguments: &Map<String, JsonValue>,
+) -> Map<String, JsonValue> {
+    arguments
+        .iter()
+        .filter(|(name, _)| {
+            !endpoint
+                .path_params
+                .iter()
+                .any(|param| &param.name == *name)
+        })
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// Path and query string of the request a tool call makes, POST endpoints
+/// take their other arguments in the body
+fn request_uri(endpoint: &EndpointIrResult, arguments: &Map<String, JsonValue>) -> Result<String> {
+    let known: HashSet<&str> = endpoint
+        .path_params
+        .iter()
+        .map(|param| param.name.as_str())
+        .chain(
+            endpoint
+                .query_params
+                .iter()
+                .map(|param| param.name.as_str()),
+        )
+        .collect();
+    if let Some(unknown) = arguments.keys().find(|name| !known.contains(name.as_str())) {
+        anyhow::bail!("Unknown argument '{}'", unknown);
+    }
+
+    let mut url = reqwest::Url::parse("http://smorty/").expect("base URL is valid");
+    {
+        let mut segments = url
+            .path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Invalid base URL"))?;
+        segments.clear();
+        for segment in endpoint.endpoint_path.split('/').filter(|s| !s.is_empty()) {
+            match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                Some(param) => {
+                    let value = arguments
+                        .get(param)
+                        .context(format!("Missing argument '{}'", param))?;
+                    segments.push(&argument_string(value));
+                }
+                None => {
+                    segments.push(segment);
+                }
+            }
+        }
+    }
+
+    let query: Vec<(&str, String)> = endpoint
+        .query_params
+        .iter()
+        .filter_map(|param| {
+            let value = arguments
+                .get(&param.name)
+                .filter(|value| !value.is_null())?;
+            Some((param.name.as_str(), argument_string(value)))
+        })
+        .collect();
+    if !query.is_empty() && endpoint.method.eq_ignore_ascii_case("GET") {
+        url.query_pairs_mut().extend_pairs(query);
+    }
+
+    Ok(match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    })
+}
+
+/// Argument as it would be written in a URL
+fn argument_string(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{PathParam, QueryParam, ResponseSchema};
+
+    #[test]
+    fn test_endpoints_become_tools() {
+        let endpoint = EndpointIrResult {
+            endpoint_path: "/api/pools/{pool}/swaps".to_string(),
+            description: "Swaps of a pool".to_string(),
+            method: "GET".to_string(),
+            path_params: vec![PathParam {
+                name: "pool".to_string(),
+                param_type: "String".to_string(),
+                description: "Pool address".to_string(),
+            }],
+            query_params: vec![
+                QueryParam {
+                    name: "limit".to_string(),
+                    param_type: "i64".to_string(),
+                    default: Some(json!(50)),
+                    description: "Rows to return".to_string(),
+                    allowed_values: vec![],
+                },
+                QueryParam {
+                    name: "side".to_string(),
+                    param_type: "String".to_string(),
+                    default: None,
+                    description: String::new(),
+                    allowed_values: vec![json!("buy"), json!("sell")],
+                },
+            ],
+            response_schema: ResponseSchema {
+                name: "Swap".to_string(),
+                fields: vec![],
+            },
+            sql_query: String::new(),
+            stages: Vec::new(),
+            tables_referenced: vec![],
+            tags: vec![],
+            scopes: vec![],
+            cache_ttl: None,
+        };
+
+        assert_eq!(tool_name(&endpoint.endpoint_path), "pools_by_pool_swaps");
+        let tool = tool("pools_by_pool_swaps", &endpoint);
+        assert_eq!(tool["inputSchema"]["required"], json!(["pool", "side"]));
+        assert_eq!(
+            tool["inputSchema"]["properties"]["limit"]["type"],
+            "integer"
+        );
+        assert_eq!(
+            tool["inputSchema"]["properties"]["side"]["enum"],
+            json!(["buy", "sell"])
+        );
+
+        let arguments = json!({"pool": "0xab cd", "limit": 10, "side": "buy"});
+        assert_eq!(
+            request_uri(&endpoint, arguments.as_object().unwrap()).unwrap(),
+            "/api/pools/0xab%20cd/swaps?limit=10&side=buy"
+        );
+        let arguments = json!({"pool": "0xabcd", "sort": "asc"});
+        assert!(request_uri(&endpoint, arguments.as_object().unwrap()).is_err());
+    }
+}
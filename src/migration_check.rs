@@ -0,0 +1,312 @@
+use crate::error::{self, ResultExt, SmortyError};
+use crate::migration::MIGRATIONS_DIR;
+use crate::schema_state::{SCHEMA_STATE_FILE, SchemaState};
+use anyhow::{Context, Result};
+use sqlx::migrate::Migrator;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{PgPool, Row};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Tables and indexes created by applying the migrations
+#[derive(Debug, Default)]
+struct AppliedSchema {
+    /// Column types by column name, by table name
+    tables: BTreeMap<String, BTreeMap<String, String>>,
+    /// Index names by table name
+    indexes: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Apply all migrations to an empty schema and compare the result with schema.json
+///
+/// The temporary schema is created in `shadow_url` if given, otherwise in the
+/// database at `database_url`, and dropped afterwards.
+pub async fn check(database_url: &str, shadow_url: Option<&str>) -> error::Result<()> {
+    run_check(shadow_url.unwrap_or(database_url))
+        .await
+        .kind(SmortyError::Migration)
+}
+
+async fn run_check(url: &str) -> Result<()> {
+    let state = SchemaState::load(Path::new(SCHEMA_STATE_FILE))?;
+    let schema = format!("smorty_check_{}", hex::encode(rand::random::<[u8; 4]>()));
+
+    let admin = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(url)
+        .await
+        .context("Failed to connect to database")?;
+    sqlx::query(&format!("CREATE SCHEMA {}", schema))
+        .execute(&admin)
+        .await
+        .context("Failed to create temporary schema")?;
+
+    tracing::info!("Applying migrations to temporary schema {}", schema);
+    let applied = apply_and_inspect(url, &schema).await;
+
+    if let Err(e) = sqlx::query(&format!("DROP SCHEMA {} CASCADE", schema))
+        .execute(&admin)
+        .await
+    {
+        tracing::warn!("Failed to drop temporary schema {}: {}", schema, e);
+    }
+
+    let problems = compare(&state, &applied?);
+    if !problems.is_empty() {
+        anyhow::bail!(
+            "Applying the migrations from scratch doesn't produce the schema in {}:\n  - {}",
+            SCHEMA_STATE_FILE,
+            problems.join("\n  - ")
+        );
+    }
+
+    tracing::info!(
+        "Migrations match {} ({} tables)",
+        SCHEMA_STATE_FILE,
+        state.tables.len()
+    );
+    Ok(())
+}
+
+/// Run the migrations with `schema` first on the search path, then read back what they created
+async fn apply_and_inspect(url: &str, schema: &str) -> Result<AppliedSchema> {
+    let options = PgConnectOptions::from_str(url)
+        .context("Invalid database URI")?
+        .options([("search_path", schema)]);
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await
+        .context("Failed to connect to database")?;
+
+    let migrator = Migrator::new(Path::new(MIGRATIONS_DIR))
+        .await
+        .context("Failed to load migrations")?;
+    migrator
+        .run(&pool)
+        .await
+        .context("Failed to apply migrations to the temporary schema")?;
+
+    inspect(&pool, schema).await
+}
+
+async fn inspect(pool: &PgPool, schema: &str) -> Result<AppliedSchema> {
+    let mut applied = AppliedSchema::default();
+
+    let columns = sqlx::query(
+        "SELECT c.relname AS table_name, a.attname AS column_name, \
+                format_type(a.atttypid, a.atttypmod) AS column_type \
+         FROM pg_attribute a \
+         JOIN pg_class c ON c.oid = a.attrelid \
+         JOIN pg_namespace n ON n.oid = c.relnamespace \
+         WHERE n.nspname = $1 AND c.relkind IN ('r', 'p') \
+           AND a.attnum > 0 AND NOT a.attisdropped \
+           AND c.relname <> '_sqlx_migrations'",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await
+    .context("Failed to read columns of the temporary schema")?;
+    for row in columns {
+        applied
+            .tables
+            .entry(row.try_get("table_name")?)
+            .or_default()
+            .insert(row.try_get("column_name")?, row.try_get("column_type")?);
+    }
+
+    let indexes = sqlx::query(
+        "SELECT tablename, indexname FROM pg_indexes \
+         WHERE schemaname = $1 AND tablename <> '_sqlx_migrations'",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await
+    .context("Failed to read indexes of the temporary schema")?;
+    for row in indexes {
+        applied
+            .indexes
+            .entry(row.try_get("tablename")?)
+            .or_default()
+            .insert(row.try_get("indexname")?);
+    }
+
+    Ok(applied)
+}
+
+/// Differences between schema.json and the applied schema
+fn compare(state: &SchemaState, applied: &AppliedSchema) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let mut table_names: Vec<&String> = state.tables.keys().collect();
+    table_names.sort();
+    for table_name in table_names {
+        let table = &state.tables[table_name];
+        let Some(columns) = applied.tables.get(table_name) else {
+            problems.push(format!("table {} is missing", table_name));
+            continue;
+        };
+
+        for column in &table.columns {
+            match columns.get(&column.name) {
+                None => problems.push(format!("column {}.{} is missing", table_name, column.name)),
+                Some(actual) if *actual != normalize_column_type(&column.column_type) => problems
+                    .push(format!(
+                        "column {}.{} is {}, expected {}",
+                        table_name, column.name, actual, column.column_type
+                    )),
+                Some(_) => {}
+            }
+        }
+        for column_name in columns.keys() {
+            if !table
+                .columns
+                .iter()
+                .any(|column| column.name == *column_name)
+            {
+                problems.push(format!(
+                    "column {}.{} is not in schema.json",
+                    table_name, column_name
+                ));
+            }
+        }
+
+        let indexes = applied.indexes.get(table_name);
+        for index in &table.indexes {
+            if !indexes.is_some_and(|indexes| indexes.contains(&index.name)) {
+                problems.push(format!("index {} on {} is missing", index.name, table_name));
+            }
+        }
+    }
+
+    for table_name in applied.tables.keys() {
+        if !state.tables.contains_key(table_name) {
+            problems.push(format!("table {} is not in schema.json", table_name));
+        }
+    }
+
+    problems
+}
+
+/// Declared column type as Postgres' `format_type` reports it, without constraints
+fn normalize_column_type(declared: &str) -> String {
+    const CONSTRAINTS: &[&str] = &[
+        "NOT",
+        "NULL",
+        "PRIMARY",
+        "UNIQUE",
+        "DEFAULT",
+        "REFERENCES",
+        "CHECK",
+        "GENERATED",
+        "COLLATE",
+        "CONSTRAINT",
+    ];
+
+    let words: Vec<&str> = declared
+        .split_whitespace()
+        .take_while(|word| !CONSTRAINTS.contains(&word.to_uppercase().as_str()))
+        .collect();
+    let declared = words.join(" ").to_lowercase();
+
+    let (name, args) = match declared.find('(') {
+        Some(i) => (declared[..i].trim(), declared[i..].replace(' ', "")),
+        None => (declared.trim(), String::new()),
+    };
+    let name = match name {
+        "varchar" => "character varying",
+        "char" => "character",
+        "int" | "int4" | "serial" | "serial4" => "integer",
+        "int8" | "bigserial" | "serial8" => "bigint",
+        "int2" | "smallserial" | "serial2" => "smallint",
+        "bool" => "boolean",
+        "decimal" => "numeric",
+        "float8" | "float" => "double precision",
+        "float4" => "real",
+        "timestamp" => "timestamp without time zone",
+        "timestamptz" => "timestamp with time zone",
+        other => other,
+    };
+
+    format!("{}{}", name, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema_state::{ColumnState, IndexState, TableState};
+
+    #[test]
+    fn test_normalize_column_type() {
+        assert_eq!(normalize_column_type("BIGSERIAL PRIMARY KEY"), "bigint");
+        assert_eq!(
+            normalize_column_type("VARCHAR(42) NOT NULL"),
+            "character varying(42)"
+        );
+        assert_eq!(
+            normalize_column_type("NUMERIC(78, 0) NOT NULL"),
+            "numeric(78,0)"
+        );
+        assert_eq!(normalize_column_type("JSONB"), "jsonb");
+    }
+
+    #[test]
+    fn test_compare_reports_drift() {
+        let mut state = SchemaState::new();
+        let mut table = TableState::new(
+            "token_transfers".to_string(),
+            "Token".to_string(),
+            "Transfer".to_string(),
+        );
+        table.add_column(ColumnState::new(
+            "id".to_string(),
+            "BIGSERIAL PRIMARY KEY".to_string(),
+        ));
+        table.add_column(ColumnState::new(
+            "amount".to_string(),
+            "NUMERIC(78, 0) NOT NULL".to_string(),
+        ));
+        table.add_index(IndexState::new(
+            "token_transfers_idx_amount".to_string(),
+            "CREATE INDEX token_transfers_idx_amount ON token_transfers (amount)".to_string(),
+        ));
+        state.add_table(table);
+
+        let mut applied = AppliedSchema::default();
+        applied.tables.insert(
+            "token_transfers".to_string(),
+            BTreeMap::from([
+                ("id".to_string(), "bigint".to_string()),
+                ("amount".to_string(), "numeric(78,0)".to_string()),
+            ]),
+        );
+        applied.indexes.insert(
+            "token_transfers".to_string(),
+            BTreeSet::from([
+                "token_transfers_pkey".to_string(),
+                "token_transfers_idx_amount".to_string(),
+            ]),
+        );
+        assert!(compare(&state, &applied).is_empty());
+
+        // A hand-edited migration changed a type and skipped the index
+        applied
+            .tables
+            .get_mut("token_transfers")
+            .unwrap()
+            .insert("amount".to_string(), "text".to_string());
+        applied.indexes.clear();
+        applied
+            .tables
+            .insert("scratch".to_string(), BTreeMap::new());
+        assert_eq!(
+            compare(&state, &applied),
+            vec![
+                "column token_transfers.amount is text, expected NUMERIC(78, 0) NOT NULL",
+                "index token_transfers_idx_amount on token_transfers is missing",
+                "table scratch is not in schema.json",
+            ]
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Decoding of Postgres NUMERIC values
+//!
+//! Aggregates such as `AVG`, `SUM` over NUMERIC columns and moving averages over
+//! window frames return NUMERIC. sqlx only decodes it through a decimal crate,
+//! so [`PgDecimal`] reads the wire format into its exact decimal string, which
+//! endpoints return for `Decimal` response fields and parse for `f64` ones.
+
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgTypeInfo, PgValueFormat, PgValueRef, Postgres};
+use sqlx::{Decode, Type, TypeInfo};
+
+/// Sign of a NUMERIC value, including the special values
+const SIGN_NEG: u16 = 0x4000;
+const SIGN_NAN: u16 = 0xC000;
+const SIGN_INF: u16 = 0xD000;
+const SIGN_NEG_INF: u16 = 0xF000;
+
+/// A NUMERIC value as its exact decimal string, e.g. `-1234.5600`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgDecimal(pub String);
+
+impl PgDecimal {
+    /// Nearest `f64`, `None` for NaN and infinities which JSON can't represent
+    pub fn to_f64(&self) -> Option<f64> {
+        self.0.parse::<f64>().ok().filter(|value| value.is_finite())
+    }
+}
+
+impl Type<Postgres> for PgDecimal {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("NUMERIC")
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        ty.name() == "NUMERIC"
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for PgDecimal {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        match value.format() {
+            PgValueFormat::Binary => Ok(Self(from_binary(value.as_bytes()?)?)),
+            PgValueFormat::Text => Ok(Self(value.as_str()?.to_string())),
+        }
+    }
+}
+
+/// Decimal string of a NUMERIC in the binary format: the number of base-10000
+/// digits, the weight of the first one, the sign and the display scale, followed
+/// by the digits
+fn from_binary(bytes: &[u8]) -> Result<String, BoxDynError> {
+    let word = |i: usize| -> Result<u16, BoxDynError> {
+        bytes
+            .get(i * 2..i * 2 + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(|| "NUMERIC value is truncated".into())
+    };
+    let ndigits = word(0)? as usize;
+    let weight = word(1)? as i16 as i64;
+    let sign = word(2)?;
+    let scale = word(3)? as usize;
+    let digits = (0..ndigits)
+        .map(|i| word(4 + i))
+        .collect::<Result<Vec<u16>, _>>()?;
+
+    match sign {
+        SIGN_NAN => return Ok("NaN".to_string()),
+        SIGN_INF => return Ok("Infinity".to_string()),
+        SIGN_NEG_INF => return Ok("-Infinity".to_string()),
+        _ => {}
+    }
+    // Digit of base-10000 power `power`, zero outside the stored digits
+    let digit = |power: i64| -> u16 {
+        usize::try_from(weight - power)
+            .ok()
+            .and_then(|i| digits.get(i).copied())
+            .unwrap_or(0)
+    };
+
+    let mut integer = String::new();
+    for power in (0..=weight).rev() {
+        if integer.is_empty() {
+            integer = digit(power).to_string();
+        } else {
+            integer.push_str(&format!("{:04}", digit(power)));
+        }
+    }
+    if integer.is_empty() {
+        integer.push('0');
+    }
+
+    let mut fraction = String::new();
+    let mut power = -1;
+    while fraction.len() < scale {
+        fraction.push_str(&format!("{:04}", digit(power)));
+        power -= 1;
+    }
+    fraction.truncate(scale);
+
+    let negative = sign == SIGN_NEG && digits.iter().any(|&d| d != 0);
+    let mut decimal = if negative {
+        format!("-{}", integer)
+    } else {
+        integer
+    };
+    if !fraction.is_empty() {
+        decimal.push('.');
+        decimal.push_str(&fraction);
+    }
+    Ok(decimal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numeric(weight: i16, sign: u16, scale: u16, digits: &[u16]) -> Vec<u8> {
+        [digits.len() as u16, weight as u16, sign, scale]
+            .iter()
+            .chain(digits)
+            .flat_map(|word| word.to_be_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_decodes_binary_numerics_exactly() {
+        let cases = [
+            (numeric(0, 0, 0, &[]), "0"),
+            (numeric(0, 0, 2, &[]), "0.00"),
+            (numeric(1, 0, 0, &[12, 3456]), "123456"),
+            (numeric(0, SIGN_NEG, 4, &[1234, 5600]), "-1234.5600"),
+            (numeric(-1, 0, 6, &[50]), "0.005000"),
+            (numeric(2, 0, 0, &[1]), "100000000"),
+            // uint256 max keeps every digit
+            (
+                numeric(
+                    19,
+                    0,
+                    0,
+                    &[
+                        11, 5792, 892, 3731, 6195, 4235, 7098, 5008, 6879, 785, 3269, 9846, 6564,
+                        564, 394, 5758, 4007, 9131, 2963, 9935,
+                    ],
+                ),
+                "115792089237316195423570985008687907853269984665640564039457584007913129639935",
+            ),
+            (numeric(0, SIGN_NAN, 0, &[]), "NaN"),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(from_binary(&bytes).unwrap(), expected);
+        }
+
+        assert!(from_binary(&[0, 1]).is_err());
+        assert_eq!(PgDecimal("12.50".to_string()).to_f64(), Some(12.5));
+        assert_eq!(PgDecimal("NaN".to_string()).to_f64(), None);
+    }
+}
@@ -0,0 +1,97 @@
+//! Pausing the indexer at runtime
+//!
+//! `smorty pause` and `POST /api/_admin/pause` stop the indexer from indexing a
+//! chain, or a single spec given as `<contract>/<spec>`, until it is resumed, e.g.
+//! for a database maintenance window. Pauses are recorded in `database.uri`, which
+//! the indexer checks on every poll, so they reach a daemon running in another
+//! process without restarting it. A resumed spec continues from its last indexed block.
+
+use crate::config::Config;
+use crate::error::SmortyError;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+
+/// Paused chains and specs
+pub const PAUSES_TABLE: &str = "smorty_pauses";
+
+/// A paused chain or spec
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pause {
+    /// Chain name, or spec as `<contract>/<spec>`
+    pub target: String,
+    pub reason: Option<String>,
+    pub paused_at: DateTime<Utc>,
+}
+
+/// Chain a pause target is on, or an error if it's neither a chain nor a spec
+pub fn target_chain<'a>(config: &'a Config, target: &str) -> Result<&'a str> {
+    if let Some((contract_name, spec_name)) = target.split_once('/') {
+        if config.spec(contract_name, spec_name).is_none() {
+            return Err(SmortyError::Config(anyhow::anyhow!(
+                "Spec '{}' is not defined in the config",
+                target
+            ))
+            .into());
+        }
+        return Ok(&config.contracts[contract_name].chain);
+    }
+    match config.chains.rpc_urls.get_key_value(target) {
+        Some((chain, _)) => Ok(chain),
+        None => Err(SmortyError::Config(anyhow::anyhow!(
+            "'{}' is neither a chain nor a spec, use a chain name or <contract>/<spec>",
+            target
+        ))
+        .into()),
+    }
+}
+
+/// Pause a chain or spec, replacing the reason of an existing pause
+pub async fn pause(pool: &PgPool, target: &str, reason: Option<&str>) -> Result<()> {
+    sqlx::query(&format!(
+        "INSERT INTO {} (target, reason, paused_at) VALUES ($1, $2, NOW())
+         ON CONFLICT (target) DO UPDATE SET reason = $2",
+        PAUSES_TABLE
+    ))
+    .bind(target)
+    .bind(reason)
+    .execute(pool)
+    .await
+    .context(format!("Failed to pause {}", target))?;
+    Ok(())
+}
+
+/// Resume a chain or spec, returning whether it was paused
+pub async fn resume(pool: &PgPool, target: &str) -> Result<bool> {
+    let result = sqlx::query(&format!("DELETE FROM {} WHERE target = $1", PAUSES_TABLE))
+        .bind(target)
+        .execute(pool)
+        .await
+        .context(format!("Failed to resume {}", target))?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Current pauses, keyed by target
+pub async fn load(pool: &PgPool) -> Result<HashMap<String, Pause>> {
+    let rows = sqlx::query(&format!(
+        "SELECT target, reason, EXTRACT(EPOCH FROM paused_at)::BIGINT AS paused_at FROM {}",
+        PAUSES_TABLE
+    ))
+    .fetch_all(pool)
+    .await
+    .context("Failed to load pauses")?;
+
+    let mut pauses = HashMap::new();
+    for row in rows {
+        let pause = Pause {
+            target: row.try_get("target")?,
+            reason: row.try_get("reason")?,
+            paused_at: DateTime::from_timestamp(row.try_get("paused_at")?, 0).unwrap_or_default(),
+        };
+        pauses.insert(pause.target.clone(), pause);
+    }
+    Ok(pauses)
+}
@@ -0,0 +1,117 @@
+//! Privacy mode for hosted APIs
+//!
+//! With `server.privacy.anonymizeIps`, client IPs are truncated before they are
+//! logged: IPv4 addresses to their /24 and IPv6 addresses to their /48.
+//! Parameters of the endpoints in `skipParams` are left out of the debug logs
+//! and the slow query log. `gdpr = true` turns on both, for every endpoint.
+
+use crate::config::PrivacyConfig;
+use axum::http::HeaderMap;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// Matches every endpoint in `skipParams`
+const ALL_ENDPOINTS: &str = "*";
+
+/// What request details the server may log
+#[derive(Debug, Clone, Default)]
+pub struct Privacy {
+    anonymize_ips: bool,
+    all_params: bool,
+    skip_params: HashSet<String>,
+}
+
+impl Privacy {
+    pub fn from_config(config: &PrivacyConfig) -> Self {
+        Self {
+            anonymize_ips: config.gdpr || config.anonymize_ips,
+            all_params: config.gdpr || config.skip_params.iter().any(|p| p == ALL_ENDPOINTS),
+            skip_params: config.skip_params.iter().cloned().collect(),
+        }
+    }
+
+    /// Client address to log for a request, truncated in privacy mode
+    pub fn client(&self, headers: &HeaderMap, peer: Option<SocketAddr>) -> Option<String> {
+        let ip = client_ip(headers, peer)?;
+        let ip = if self.anonymize_ips {
+            anonymize(ip)
+        } else {
+            ip
+        };
+        Some(ip.to_string())
+    }
+
+    /// Whether parameters of the endpoint at `endpoint_path` may be logged
+    pub fn captures_params(&self, endpoint_path: &str) -> bool {
+        !self.all_params && !self.skip_params.contains(endpoint_path)
+    }
+}
+
+/// Address of the client, from the first `X-Forwarded-For` entry, `X-Real-IP`
+/// or the peer of the connection
+fn client_ip(headers: &HeaderMap, peer: Option<SocketAddr>) -> Option<IpAddr> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .and_then(|value| value.trim().parse().ok())
+    };
+    header("x-forwarded-for")
+        .or_else(|| header("x-real-ip"))
+        .or_else(|| peer.map(|peer| peer.ip()))
+}
+
+/// Zero all but the network part of an address
+fn anonymize(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            IpAddr::V4(Ipv4Addr::new(a, b, c, 0))
+        }
+        IpAddr::V6(ip) => {
+            let [a, b, c, ..] = ip.segments();
+            IpAddr::V6(Ipv6Addr::new(a, b, c, 0, 0, 0, 0, 0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncates_client_ips_and_skips_params() {
+        let privacy = Privacy::from_config(&PrivacyConfig {
+            anonymize_ips: true,
+            skip_params: vec!["/api/wallets/{address}".to_string()],
+            gdpr: false,
+        });
+
+        let mut headers = HeaderMap::new();
+        let peer = Some("10.0.0.7:51234".parse().unwrap());
+        assert_eq!(privacy.client(&headers, peer).as_deref(), Some("10.0.0.0"));
+        headers.insert(
+            "x-forwarded-for",
+            "2001:db8:85a3:8d3:1319:8a2e:370:7348, 10.0.0.1"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            privacy.client(&headers, peer).as_deref(),
+            Some("2001:db8:85a3::")
+        );
+        assert_eq!(
+            Privacy::default().client(&headers, peer).as_deref(),
+            Some("2001:db8:85a3:8d3:1319:8a2e:370:7348")
+        );
+
+        assert!(!privacy.captures_params("/api/wallets/{address}"));
+        assert!(privacy.captures_params("/api/pools"));
+        let gdpr = Privacy::from_config(&PrivacyConfig {
+            gdpr: true,
+            ..Default::default()
+        });
+        assert!(!gdpr.captures_params("/api/pools"));
+    }
+}
@@ -0,0 +1,126 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::{self, Write};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Shared progress display, so log output can be interleaved with the bars
+fn multi() -> &'static MultiProgress {
+    static MULTI: OnceLock<MultiProgress> = OnceLock::new();
+    MULTI.get_or_init(MultiProgress::new)
+}
+
+/// Log writer that hides progress bars while a line is written
+///
+/// Pass `LogWriter::default` to the tracing fmt layer's `with_writer`.
+#[derive(Default)]
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        multi().suspend(|| io::stdout().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+/// Progress display for the indexer, with one bar per chain
+#[derive(Clone)]
+pub struct IndexProgress {
+    enabled: bool,
+}
+
+impl IndexProgress {
+    /// Create a progress display; bars are only drawn on an interactive terminal
+    pub fn new(quiet: bool) -> Self {
+        Self { enabled: !quiet }
+    }
+
+    /// Progress display that never draws anything
+    pub fn hidden() -> Self {
+        Self { enabled: false }
+    }
+
+    /// Add a progress bar for a chain
+    pub fn chain(&self, chain: &str) -> ChainProgress {
+        let bar = if self.enabled {
+            multi().add(ProgressBar::new(0))
+        } else {
+            ProgressBar::with_draw_target(Some(0), ProgressDrawTarget::hidden())
+        };
+
+        bar.set_style(
+            ProgressStyle::with_template(
+                "{prefix:>12.bold} [{bar:30.cyan/blue}] {pos}/{len} blocks | {msg} | ETA {eta}",
+            )
+            .expect("valid progress template")
+            .progress_chars("=> "),
+        );
+        bar.set_prefix(chain.to_string());
+
+        ChainProgress { bar, events: 0 }
+    }
+}
+
+/// Progress bar for a single chain's block range
+pub struct ChainProgress {
+    bar: ProgressBar,
+    events: u64,
+}
+
+impl ChainProgress {
+    /// Start tracking a new block range
+    pub fn start(&mut self, from_block: u64, to_block: u64) {
+        self.events = 0;
+        self.bar.set_length(to_block.saturating_sub(from_block) + 1);
+        self.bar.set_position(0);
+        self.bar.reset_eta();
+        self.bar.set_message("0 events");
+    }
+
+    /// Record a processed chunk of blocks
+    pub fn advance(&mut self, blocks: u64, events_inserted: u64, rpc_latency: Duration) {
+        self.events += events_inserted;
+        self.bar.inc(blocks);
+        self.bar.set_message(format!(
+            "{} events | rpc {}ms",
+            self.events,
+            rpc_latency.as_millis()
+        ));
+    }
+
+    /// Mark the chain as caught up to a block
+    pub fn finish(&self, block: u64) {
+        self.bar.set_position(self.bar.length().unwrap_or(0));
+        self.bar
+            .set_message(format!("{} events | synced to {}", self.events, block));
+    }
+
+    /// Number of events inserted since the range started
+    pub fn events(&self) -> u64 {
+        self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_progress_counts_events() {
+        let progress = IndexProgress::hidden();
+        let mut chain = progress.chain("mainnet");
+
+        chain.start(100, 2099);
+        chain.advance(1000, 5, Duration::from_millis(120));
+        chain.advance(1000, 3, Duration::from_millis(80));
+        assert_eq!(chain.events(), 8);
+        assert_eq!(chain.bar.position(), 2000);
+        assert_eq!(chain.bar.length(), Some(2000));
+
+        chain.start(2100, 2100);
+        assert_eq!(chain.events(), 0);
+        assert_eq!(chain.bar.length(), Some(1));
+    }
+}
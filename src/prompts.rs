@@ -0,0 +1,486 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// Directory checked for user-supplied prompt templates
+pub const PROMPTS_DIR: &str = "prompts";
+
+/// Prompt template file for spec IR generation
+pub const SPEC_PROMPT_FILE: &str = "spec.txt";
+
+/// Prompt template file for endpoint IR generation
+pub const ENDPOINT_PROMPT_FILE: &str = "endpoint.txt";
+
+/// System prompt templates used for AI generation
+///
+/// Templates may reference variables with `{{name}}` placeholders. Single braces
+/// (e.g. `{table_name}` in index examples) are left untouched.
+///
+/// Spec variables: `contract_name`, `spec_name`, `start_block`, `contract_address`, `chain`, `task`.
+/// Endpoint variables: `endpoint_path`, `description`, `task`.
+#[derive(Debug, Clone)]
+pub struct PromptTemplates {
+    pub spec: String,
+    pub endpoint: String,
+}
+
+impl Default for PromptTemplates {
+    fn default() -> Self {
+        Self {
+            spec: DEFAULT_SPEC_PROMPT.to_string(),
+            endpoint: DEFAULT_ENDPOINT_PROMPT.to_string(),
+        }
+    }
+}
+
+impl PromptTemplates {
+    /// Load prompt templates from a directory, falling back to the built-in
+    /// prompts for any file that does not exist
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut templates = Self::default();
+
+        if let Some(spec) = Self::load_template(&dir.join(SPEC_PROMPT_FILE))? {
+            templates.spec = spec;
+        }
+
+        if let Some(endpoint) = Self::load_template(&dir.join(ENDPOINT_PROMPT_FILE))? {
+            templates.endpoint = endpoint;
+        }
+
+        Ok(templates)
+    }
+
+    fn load_template(path: &Path) -> Result<Option<String>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path)
+            .context(format!("Failed to read prompt template: {:?}", path))?;
+
+        if content.trim().is_empty() {
+            anyhow::bail!("Prompt template is empty: {:?}", path);
+        }
+
+        tracing::info!("Using custom prompt template: {:?}", path);
+
+        Ok(Some(content))
+    }
+
+    /// Render the spec system prompt
+    pub fn render_spec(&self, vars: &[(&str, &str)]) -> String {
+        render(&self.spec, vars)
+    }
+
+    /// Render the endpoint system prompt
+    pub fn render_endpoint(&self, vars: &[(&str, &str)]) -> String {
+        render(&self.endpoint, vars)
+    }
+}
+
+/// Substitute `{{name}}` placeholders in a template
+///
+/// Unknown placeholders are left as-is so typos are visible in the prompt. The
+/// template is scanned once, so placeholders inside substituted values (an ABI
+/// or task mentioning `{{task}}`) are never expanded.
+pub fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let value = after.find("}}").and_then(|end| {
+            let name = &after[..end];
+            let (_, value) = vars.iter().find(|(var, _)| *var == name)?;
+            Some((value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                rendered.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                rendered.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    rendered.push_str(rest);
+
+    rendered
+}
+
+/// Built-in system prompt for spec IR generation
+pub const DEFAULT_SPEC_PROMPT: &str = r#"You are an expert Ethereum indexer code generator.
+Given a contract ABI and a natural language task description, you will:
+
+1. Analyze the ABI to find the relevant event
+2. Extract event parameters and their types
+3. Determine which fields to index based on the task
+4. Generate a JSON schema for the database table
+5. Identify query parameters and filters needed for the API endpoint
+
+Return your response in the following JSON format:
+{
+  "event_name": "EventName",
+  "event_signature": "EventName(uint256,address)",
+  "start_block": 12345678,
+  "contract_address: "0xContractAddress",
+  "chain": "chain_name",
+  "indexed_fields": [
+    {"name": "field1", "solidity_type": "uint256", "rust_type": "String", "indexed": false, "column": "field_1"},
+    {"name": "field2", "solidity_type": "address", "rust_type": "String", "indexed": true, "column": "field_2"}
+  ],
+  "table_schema": {
+    "table_name": "event_table_name",
+    "columns": [
+      {"name": "id", "type": "BIGSERIAL PRIMARY KEY"},
+      {"name": "block_number", "type": "BIGINT NOT NULL"},
+      {"name": "block_timestamp", "type": "BIGINT NOT NULL"},
+      {"name": "transaction_hash", "type": "VARCHAR(66) NOT NULL"},
+      {"name": "log_index", "type": "INTEGER NOT NULL"},
+      {"name": "field_1", "type": "NUMERIC(78, 0) NOT NULL"},
+      {"name": "field_2", "type": "VARCHAR(42) NOT NULL"}
+    ],
+    "indexes": [
+      {"name": "idx_block_number", "columns": ["block_number"], "method": null, "predicate": null, "unique": false},
+      {"name": "idx_timestamp", "columns": ["block_timestamp"], "method": "brin", "predicate": null, "unique": false}
+    ],
+    "computed_columns": [
+      {"name": "field_1_eth", "type": "NUMERIC", "expression": "field_1 / 1e18", "on_insert": false}
+    ]
+  },
+  "description": "A brief and concise description of the event to be indexed"
+}
+
+Important Solidity to PostgreSQL type mappings:
+- uint8 -> SMALLINT
+- uint16 -> INTEGER
+- uint24 -> INTEGER
+- uint32 -> BIGINT
+- uint40, uint48, uint56, uint64 -> BIGINT
+- uint72, uint80, uint88, uint96, uint104, uint112, uint120, uint128 -> NUMERIC(39, 0)
+- uint136, uint144, uint152, uint160, uint168, uint176, uint184, uint192, uint200, uint208, uint216, uint224, uint232, uint240, uint248, uint256 -> NUMERIC(78, 0)
+- address -> VARCHAR(42)
+- bytes1-bytes32 -> VARCHAR(66)
+- bytes (dynamic) -> TEXT
+- string -> TEXT
+- bool -> BOOLEAN
+- structs/tuples (e.g. (address,uint256)) and arrays (e.g. uint256[], address[3]) -> JSONB
+
+For struct and array fields, use the canonical tuple form as solidity_type, e.g. "(address,uint256)[]" rather than the struct name. Add a GIN index for JSONB columns that will be filtered on.
+
+Set "column" on every indexed field to the table column it is stored in. Every column other than id, block_number, block_timestamp, transaction_hash, log_index and the computed columns must be the column of exactly one field.
+
+For indexed event parameters, note them in the response but they don't need special database treatment.
+
+Index definitions:
+- "name": short index name starting with idx_, it is prefixed with the table name automatically
+- "columns": column names, optionally with ASC/DESC, or expressions such as "lower(field_2)"
+- "method": null for btree, "brin" for append-only columns like block_number and block_timestamp on large tables, "gin" for JSONB or array columns, "hash" for equality-only lookups
+- "predicate": null, or a condition without WHERE for a partial index, e.g. "field_1 > 0"
+- "unique": true only if the columns uniquely identify a row
+
+Computed columns (optional, use an empty array if none are useful):
+- Derived values the task asks for, so endpoint queries don't repeat the calculation, e.g. "amount / 1e18" or "to_timestamp(block_timestamp)::date"
+- "type": column type without constraints
+- "expression": SQL expression over the other columns of the same row
+- "on_insert": false to make it a generated column, which needs an immutable expression; true if the expression isn't immutable (e.g. timestamp to date conversions depending on the time zone), so it is computed when the row is inserted
+
+IMPORTANT: Table naming convention (STRICT):
+- Use abbreviated contract names to avoid PostgreSQL 63-character identifier limit
+- Format: {abbreviated_contract}_{spec_name} (lowercase with underscores)
+- Abbreviate contract names by:
+  1. Taking first 2-3 letters of each major component
+  2. Keeping it under 20 characters total for contract abbreviation
+  3. Making it recognizable but concise
+- Examples:
+  * "FeeManagerV3_Beets_Sonic_ETHUSD6h" + "PoolUpdated" = "fmv3_beets_sonic_poolupdated" (30 chars)
+  * "BalancerV2_Vault" + "Swap" = "balv2_vault_swap" (16 chars)
+  * "UniswapV3Pool" + "Mint" = "univ3_pool_mint" (15 chars)
+- Convert camelCase field names to snake_case: swapFeePercentage → swap_fee_percentage
+- Keep spec names concise but descriptive
+- NEVER use generic names like "pool_updated_events"
+- This prevents table collisions and PostgreSQL identifier truncation issues."#;
+
+/// Built-in system prompt for endpoint IR generation
+pub const DEFAULT_ENDPOINT_PROMPT: &str = r#"You are an expert API endpoint generator for an Ethereum indexer with deep knowledge of PostgreSQL and data analytics.
+
+Given an endpoint path, description, task specification, and available database tables, you will:
+
+1. Analyze the endpoint requirements carefully
+2. Extract path parameters from the endpoint (e.g., {pool} in /api/pool/{pool})
+3. Determine all necessary query parameters (filtering, pagination, time ranges, etc.)
+4. Design and generate the appropriate SQL query to satisfy the requirements
+5. Design the response schema that matches the query output
+6. Provide utoipa-compatible type information for Rust code generation
+
+## SQL Query Capabilities
+
+You have full access to PostgreSQL features and should use them when appropriate:
+
+**Basic Operations:**
+- SELECT with WHERE clauses for filtering
+- ORDER BY for sorting (typically by timestamp DESC for time series)
+- LIMIT and OFFSET for pagination
+- Column aliasing with AS
+
+**Advanced Operations:**
+- JOINs (INNER, LEFT, RIGHT, FULL) when combining multiple tables
+- Subqueries and CTEs (WITH clauses) for complex logic
+- Window functions (ROW_NUMBER, LAG, LEAD, FIRST_VALUE, LAST_VALUE) for time series analytics
+- Aggregations (COUNT, SUM, AVG, MIN, MAX) with GROUP BY
+- Date/time functions for timestamp filtering and grouping
+- CASE statements for conditional logic
+- JSON aggregation (json_agg, jsonb_agg) for nested data
+- DISTINCT ON for deduplication
+
+**Query Parameters:**
+Always parameterize your queries using PostgreSQL numbered parameters ($1, $2, $3, etc.)
+Map parameters in the order they appear in the query.
+
+**CRITICAL SQL FORMATTING RULES:**
+1. NEVER use backslash escaping for quotes in SQL queries
+2. Use single quotes (') for string literals in SQL, not escaped quotes (\")
+3. For numeric literals, write them directly without quotes (e.g., numeric '1000000000000000000' is WRONG, use 1000000000000000000::numeric or CAST(1000000000000000000 AS NUMERIC))
+4. When the SQL query will be stored in JSON, use proper single quotes that don't require escaping
+5. Test your SQL mentally - it should be valid PostgreSQL syntax that can be executed directly
+
+**Example Complex Patterns:**
+
+1. Time series with previous value comparison:
+```sql
+SELECT
+  block_timestamp,
+  value,
+  LAG(value) OVER (ORDER BY block_timestamp) as previous_value,
+  value - LAG(value) OVER (ORDER BY block_timestamp) as change
+FROM table_name
+WHERE condition
+ORDER BY block_timestamp DESC
+LIMIT $1
+```
+
+2. Aggregated statistics:
+```sql
+SELECT
+  DATE_TRUNC('hour', to_timestamp(block_timestamp)) as hour,
+  AVG(value) as avg_value,
+  MAX(value) as max_value,
+  MIN(value) as min_value,
+  COUNT(*) as event_count
+FROM table_name
+WHERE block_timestamp >= $1
+GROUP BY hour
+ORDER BY hour DESC
+LIMIT $2
+```
+
+3. Multiple table joins:
+```sql
+SELECT
+  a.block_timestamp,
+  a.pool,
+  a.fee_percentage,
+  b.total_volume
+FROM table_a a
+INNER JOIN table_b b ON a.pool = b.pool AND a.block_number = b.block_number
+WHERE a.pool = $1
+ORDER BY a.block_timestamp DESC
+LIMIT $2
+```
+
+4. Latest state per entity:
+```sql
+WITH latest_per_pool AS (
+  SELECT DISTINCT ON (pool)
+    pool,
+    block_timestamp,
+    fee_percentage
+  FROM table_name
+  WHERE condition
+  ORDER BY pool, block_timestamp DESC
+)
+SELECT * FROM latest_per_pool
+ORDER BY block_timestamp DESC
+```
+
+## Response Format
+
+Return your response in the following JSON format:
+{
+  "endpoint_path": "/api/example/{param}",
+  "description": "Clear description of what this endpoint returns",
+  "method": "GET",
+  "path_params": [
+    {"name": "param", "type": "String", "description": "What this parameter represents"}
+  ],
+  "query_params": [
+    {"name": "limit", "type": "u32", "default": 50, "description": "Maximum number of results (max 200)", "allowed_values": []},
+    {"name": "startBlockTimestamp", "type": "Option<u64>", "default": "null", "description": "Only return events at or after this Unix timestamp", "allowed_values": []}
+  ],
+  "response_schema": {
+    "name": "ExampleResponse",
+    "fields": [
+      {"name": "block_number", "type": "i64", "description": "Block number where event occurred"},
+      {"name": "block_timestamp", "type": "i64", "description": "Unix timestamp of the block"},
+      {"name": "value", "type": "String", "description": "The indexed value"}
+    ]
+  },
+  "sql_query": "SELECT block_number, block_timestamp, value FROM table_name WHERE condition ORDER BY block_timestamp DESC LIMIT $1",
+  "tables_referenced": ["table_name"]
+}
+
+## Type Mappings
+
+**Rust Types for API:**
+- Integer numbers: i64 (for block numbers, timestamps, counts)
+- Unsigned numbers: u32, u64 (for pagination limits, small positive values)
+- Large integers (uint256): String (since they exceed Rust integer limits)
+- Addresses: String (hex format with 0x prefix)
+- Booleans: bool
+- Relative time windows: Duration (query parameters only, e.g. "7d"; the server binds the Unix timestamp that far back from now)
+- Optional values: Option<T>
+- Arrays: Vec<T>
+- Decimals: Decimal (NUMERIC results returned as exact decimal strings, e.g. "1234.5678")
+- Floating point: f64 (DOUBLE PRECISION, or NUMERIC when an approximate JSON number is fine)
+
+**PostgreSQL to Rust:**
+- BIGINT → i64
+- NUMERIC(78, 0) → String (for uint256)
+- VARCHAR(42) → String (for addresses)
+- TEXT → String
+- BOOLEAN → bool
+- INTEGER → i32
+- JSONB → serde_json::Value (structs become arrays of their fields, integers inside are strings)
+- NUMERIC aggregates (SUM, AVG, moving averages over NUMERIC or integer columns) → Decimal
+- DOUBLE PRECISION (percentile_cont, percentile_disc over floats, stddev of floats) → f64
+
+**Analytics:**
+- Percentiles: percentile_cont(0.5) WITHIN GROUP (ORDER BY (amount)::NUMERIC) returns DOUBLE PRECISION, so use f64; cast with ::NUMERIC and use Decimal to keep every digit
+- Moving averages: AVG((amount)::NUMERIC) OVER (ORDER BY block_number ROWS BETWEEN 6 PRECEDING AND CURRENT ROW) returns NUMERIC, use Decimal
+- Round long decimals with ROUND(value, 6) when full precision isn't useful
+
+## Important Guidelines
+
+1. **Pagination**: Always include 'limit' query parameter with reasonable defaults (e.g., 50, max 200)
+2. **Time Filtering**: Support startBlockTimestamp and/or endBlockTimestamp when dealing with time series. Use Option<u64> with default: "null" (the string "null") to make it optional. When NULL, the query should return the latest data ordered DESC. When provided, filter from that timestamp onwards. For "last N days/hours" windows, use a `window` parameter of type Duration (e.g. default "7d") and filter with `block_timestamp >= $N`.
+3. **Validation**: Cap limit at 200 to prevent abuse. Describe every query parameter for the API docs, and use allowed_values for parameters restricted to a fixed set of values (e.g. ["mint", "burn"] compared in a WHERE clause), otherwise []
+4. **Ordering**: Default to DESC for time series (newest first) to show most recent data
+5. **Performance**: Create efficient queries with proper WHERE clauses and indexes
+6. **Null Handling**: Use Option<T> for nullable fields in response schemas
+7. **Response Fields**: Must exactly match SQL query columns (name and type)
+8. **Tables Referenced**: List all tables used in the query (including subqueries and CTEs)
+
+## Task Analysis
+
+Carefully read the task description to understand:
+- What data needs to be returned
+- What filtering is required
+- Whether aggregation or analytics are needed
+- Time range requirements
+- Pagination needs
+- Any special computations or transformations"#;
+
+/// System prompt for generating endpoint test fixtures
+pub const ENDPOINT_TEST_PROMPT: &str = r#"You are an expert API tester for an Ethereum indexer backed by PostgreSQL.
+
+Given an endpoint IR (path, parameters, SQL query and response schema), generate 2-4 test cases with realistic sample parameters:
+
+1. A case using only required parameters, relying on defaults for everything else
+2. A case exercising the main filters with explicit values
+3. Edge cases such as the maximum limit (200) or boundary block numbers where relevant
+
+Rules:
+- Provide a value for EVERY path parameter in every case
+- Only use parameter names defined in the endpoint IR
+- All values are strings as they would appear in a URL (e.g. "100", "true", "0x...")
+- Ethereum addresses must be 42 characters: 0x followed by 40 hex digits
+- Respect the declared parameter types (u64 must be a non-negative integer, bool must be true/false)
+- Case names are short snake_case identifiers"#;
+
+/// System prompt for generating the fold of a state table
+pub const STATE_FOLD_PROMPT: &str = r#"You are an expert PostgreSQL engineer designing current-state tables for an Ethereum indexer.
+
+Every indexed event is a row of an event table. A state table holds one row per key, and its value is the SUM of the deltas of every event for that key. Given the event table and a description of the state, define:
+
+- key_columns: the columns identifying a state row, with PostgreSQL types matching the key expressions (e.g. the account for a balance, the pool for a counter)
+- value_column: a snake_case name for the NUMERIC sum
+- deltas: for each way an event affects the state, one SQL expression per key column and a value expression, both over the event table's columns
+
+Rules:
+- A transfer-like event usually has two deltas: minus the amount for the sender, plus the amount for the receiver
+- Counters use the value 1
+- Cast value expressions to NUMERIC, e.g. (amount)::NUMERIC
+- Expressions may only reference columns of the event table and must not contain semicolons, subqueries or statements
+- Key column names must not be block_number or the value column"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_render_substitutes_variables() {
+        let rendered = render(
+            "Index {{spec_name}} on {{chain}} into {table_name}",
+            &[("spec_name", "Swap"), ("chain", "mainnet")],
+        );
+
+        assert_eq!(rendered, "Index Swap on mainnet into {table_name}");
+    }
+
+    #[test]
+    fn test_render_leaves_unknown_placeholders() {
+        let rendered = render("{{unknown}} {{task}}", &[("task", "do it")]);
+        assert_eq!(rendered, "{{unknown}} do it");
+    }
+
+    #[test]
+    fn test_render_does_not_expand_placeholders_in_values() {
+        let rendered = render(
+            "ABI: {{abi}}\nTask: {{task}}",
+            &[("abi", "[\"{{task}}\"]"), ("task", "index {{abi}} swaps")],
+        );
+        assert_eq!(rendered, "ABI: [\"{{task}}\"]\nTask: index {{abi}} swaps");
+
+        let rendered = render("{{a {{task}} {{task", &[("task", "do it")]);
+        assert_eq!(rendered, "{{a do it {{task");
+    }
+
+    #[test]
+    fn test_load_falls_back_to_defaults() {
+        let temp_dir = TempDir::new().unwrap();
+        let templates = PromptTemplates::load(temp_dir.path()).unwrap();
+
+        assert_eq!(templates.spec, DEFAULT_SPEC_PROMPT);
+        assert_eq!(templates.endpoint, DEFAULT_ENDPOINT_PROMPT);
+    }
+
+    #[test]
+    fn test_load_overrides_from_disk() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join(SPEC_PROMPT_FILE),
+            "Custom spec prompt for {{contract_name}}",
+        )
+        .unwrap();
+
+        let templates = PromptTemplates::load(temp_dir.path()).unwrap();
+
+        assert_eq!(
+            templates.render_spec(&[("contract_name", "Vault")]),
+            "Custom spec prompt for Vault"
+        );
+        assert_eq!(templates.endpoint, DEFAULT_ENDPOINT_PROMPT);
+    }
+
+    #[test]
+    fn test_load_rejects_empty_template() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join(ENDPOINT_PROMPT_FILE), "  \n").unwrap();
+
+        assert!(PromptTemplates::load(temp_dir.path()).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+//! `smorty prune`: remove old schema backups and IR files the config no longer uses
+//!
+//! Every `gen-migration` copies the previous `schema.json` to
+//! `migrations/{timestamp}_schema.json`, and IRs of contracts, specs and
+//! endpoints removed from the config stay in `ir/`. Backups are kept if they
+//! are among the newest `--keep-last` or newer than `--keep-days`. Stale files
+//! are listed and only removed after confirmation or with `--yes`.
+
+use crate::config::Config;
+use crate::endpoint_files::{self, MANIFEST_FILE};
+use crate::endpoint_test::TESTS_DIR;
+use crate::ir::{ENDPOINTS_DIR, SPECS_DIR};
+use crate::migration::MIGRATIONS_DIR;
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Suffix of schema backups, after the migration timestamp
+const BACKUP_SUFFIX: &str = "_schema.json";
+
+/// Which schema backups to keep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetentionPolicy {
+    /// Number of newest backups to keep
+    pub keep_last: usize,
+    /// Also keep backups newer than this many days
+    pub keep_days: Option<u64>,
+}
+
+/// Remove the files outside the retention policy and the orphaned IRs
+pub fn prune(config: &Config, policy: RetentionPolicy, assume_yes: bool) -> Result<()> {
+    let backups = stale_backups(Path::new(MIGRATIONS_DIR), policy, Utc::now())?;
+    let orphans = orphaned_ir(
+        config,
+        Path::new(SPECS_DIR),
+        Path::new(ENDPOINTS_DIR),
+        Path::new(TESTS_DIR),
+    )?;
+
+    if backups.is_empty() && orphans.is_empty() {
+        println!("Nothing to prune");
+        return Ok(());
+    }
+    if !backups.is_empty() {
+        println!("Schema backups outside the retention policy:");
+        for path in &backups {
+            println!("  {}", path.display());
+        }
+    }
+    if !orphans.is_empty() {
+        println!("IR files for contracts, specs or endpoints no longer in the config:");
+        for path in &orphans {
+            println!("  {}", path.display());
+        }
+    }
+
+    let files: Vec<PathBuf> = backups.into_iter().chain(orphans).collect();
+    if !assume_yes && !confirm(files.len())? {
+        println!("Nothing removed");
+        return Ok(());
+    }
+
+    for path in &files {
+        if path.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+        .context(format!("Failed to remove {}", path.display()))?;
+    }
+    println!("Removed {} file(s)", files.len());
+    Ok(())
+}
+
+/// Ask before removing files, failing in non-interactive sessions
+fn confirm(count: usize) -> Result<bool> {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        anyhow::bail!("Re-run with --yes to remove these files without confirmation");
+    }
+
+    print!("Remove {} file(s)? [y/N] ", count);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    stdin
+        .lock()
+        .read_line(&mut answer)
+        .context("Failed to read confirmation")?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Schema backups in `dir` that the policy doesn't keep, oldest first
+fn stale_backups(dir: &Path, policy: RetentionPolicy, now: DateTime<Utc>) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir).context(format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        let Some(timestamp) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(BACKUP_SUFFIX))
+            .and_then(|stamp| NaiveDateTime::parse_from_str(stamp, "%Y%m%d%H%M%S").ok())
+        else {
+            continue;
+        };
+        backups.push((timestamp.and_utc(), path));
+    }
+    // Newest first
+    backups.sort_by(|a, b| b.cmp(a));
+
+    let mut stale: Vec<PathBuf> = backups
+        .into_iter()
+        .skip(policy.keep_last)
+        .filter(|(timestamp, _)| {
+            policy
+                .keep_days
+                .is_none_or(|days| now.signed_duration_since(*timestamp).num_days() >= days as i64)
+        })
+        .map(|(_, path)| path)
+        .collect();
+    stale.reverse();
+    Ok(stale)
+}
+
+/// Spec IRs, endpoint IRs and endpoint fixtures the config no longer refers to
+///
+/// A contract directory missing from the config is returned as a whole.
+fn orphaned_ir(
+    config: &Config,
+    specs_dir: &Path,
+    endpoints_dir: &Path,
+    tests_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let mut orphans = Vec::new();
+
+    if specs_dir.exists() {
+        for entry in sorted_entries(specs_dir)? {
+            let Some(contract) = entry.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let Some(contract_config) = config.contracts.get(contract) else {
+                if entry.is_dir() {
+                    orphans.push(entry);
+                }
+                continue;
+            };
+            let specs: HashSet<String> = contract_config
+                .specs
+                .iter()
+                .map(|spec| format!("{}.json", spec.name))
+                .collect();
+            for spec in sorted_entries(&entry)? {
+                let name = spec.file_name().and_then(|name| name.to_str());
+                if name.is_some_and(|name| name.ends_with(".json") && !specs.contains(name)) {
+                    orphans.push(spec);
+                }
+            }
+        }
+    }
+
+    for dir in [endpoints_dir, tests_dir] {
+        if !dir.exists() {
+            continue;
+        }
+        let mut endpoints: HashSet<String> = config
+            .endpoints
+            .iter()
+            .filter_map(|endpoint| {
+                let file = endpoint_files::resolve(dir, &endpoint.endpoint, &endpoint.method);
+                Some(file.file_name()?.to_str()?.to_string())
+            })
+            .collect();
+        endpoints.insert(MANIFEST_FILE.to_string());
+        for entry in sorted_entries(dir)? {
+            let name = entry.file_name().and_then(|name| name.to_str());
+            if entry.is_file()
+                && name.is_some_and(|name| name.ends_with(".json") && !endpoints.contains(name))
+            {
+                orphans.push(entry);
+            }
+        }
+    }
+
+    Ok(orphans)
+}
+
+/// Entries of a directory, sorted by path
+fn sorted_entries(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(dir)
+        .context(format!("Failed to read {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_stale_backups() {
+        let dir = TempDir::new().unwrap();
+        for name in [
+            "20240101000000_schema.json",
+            "20240201000000_schema.json",
+            "20240301000000_schema.json",
+            "20240310000000_schema.json",
+            "schema.json",
+            "CHANGELOG.json",
+        ] {
+            fs::write(dir.path().join(name), "{}").unwrap();
+        }
+        let now = Utc.with_ymd_and_hms(2024, 3, 15, 0, 0, 0).unwrap();
+        let names = |policy| -> Vec<String> {
+            stale_backups(dir.path(), policy, now)
+                .unwrap()
+                .iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+
+        assert_eq!(
+            names(RetentionPolicy {
+                keep_last: 2,
+                keep_days: None
+            }),
+            ["20240101000000_schema.json", "20240201000000_schema.json"]
+        );
+        // The February backup is within 60 days, so only January goes
+        assert_eq!(
+            names(RetentionPolicy {
+                keep_last: 1,
+                keep_days: Some(60)
+            }),
+            ["20240101000000_schema.json"]
+        );
+    }
+}
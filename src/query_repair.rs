@@ -0,0 +1,150 @@
+//! Repairs of endpoints whose generated SQL fails at serve time
+//!
+//! With `server.repairQueries` set, a query failing with a syntax error or an
+//! unknown table, column or function is reported here. A background worker asks
+//! the AI for a fixed IR, with the database error in the prompt, saves it and
+//! reloads the router. The failing request still gets its error; later requests
+//! use the repaired endpoint.
+
+use crate::ai::{AiClient, EndpointIrResult};
+use crate::config::Config;
+use crate::ir::Ir;
+use anyhow::Result;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// An endpoint isn't repaired again within this long of its last attempt
+const REPAIR_COOLDOWN: Duration = Duration::from_secs(10 * 60);
+
+/// Reports waiting for the worker; more are dropped rather than slowing requests
+const QUEUE_SIZE: usize = 16;
+
+/// A failed query reported for repair
+#[derive(Debug)]
+struct RepairRequest {
+    endpoint_path: String,
+    method: String,
+    error: String,
+}
+
+/// Handle used by request handlers to report failed queries
+#[derive(Debug, Clone)]
+pub struct QueryRepairs {
+    sender: mpsc::Sender<RepairRequest>,
+}
+
+/// Receiving end of [`QueryRepairs`], consumed by [`spawn_worker`]
+pub struct RepairQueue {
+    receiver: mpsc::Receiver<RepairRequest>,
+}
+
+impl QueryRepairs {
+    pub fn channel() -> (Self, RepairQueue) {
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        (Self { sender }, RepairQueue { receiver })
+    }
+
+    /// Queue a repair if the error means the SQL itself is wrong
+    pub fn report(&self, endpoint_ir: &EndpointIrResult, error: &sqlx::Error) {
+        let sqlx::Error::Database(db_error) = error else {
+            return;
+        };
+        if !db_error.code().is_some_and(|code| is_repairable(&code)) {
+            return;
+        }
+
+        let _ = self.sender.try_send(RepairRequest {
+            endpoint_path: endpoint_ir.endpoint_path.clone(),
+            method: endpoint_ir.method.clone(),
+            error: db_error.message().to_string(),
+        });
+    }
+}
+
+/// Whether a SQLSTATE points at the query rather than the data or the server
+///
+/// Class 42 covers syntax errors and unknown tables, columns and functions.
+/// Permission errors (42501) are left alone, since no query rewrite fixes them.
+fn is_repairable(code: &str) -> bool {
+    code.starts_with("42") && code != "42501"
+}
+
+/// Repair reported endpoints one at a time, calling `reload` after each fix
+pub fn spawn_worker<F, Fut>(queue: RepairQueue, config: Arc<Config>, pool: PgPool, reload: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<usize>> + Send,
+{
+    let mut receiver = queue.receiver;
+    tokio::spawn(async move {
+        let ir = Ir::new(AiClient::from_config(&config.ai))
+            .with_assume_yes(true)
+            .with_endpoint_tables(config.ai.endpoint_tables)
+            .with_hooks(&config.hooks);
+        let mut attempted: HashMap<String, Instant> = HashMap::new();
+
+        while let Some(request) = receiver.recv().await {
+            let key = format!("{} {}", request.method, request.endpoint_path);
+            if attempted
+                .get(&key)
+                .is_some_and(|last| last.elapsed() < REPAIR_COOLDOWN)
+            {
+                continue;
+            }
+            attempted.insert(key, Instant::now());
+
+            tracing::warn!(
+                "Repairing endpoint {} after a query error: {}",
+                request.endpoint_path,
+                request.error
+            );
+
+            // The served IR may have been rewritten, e.g. for tenancy, so repair the saved one
+            let repaired = match Ir::load_ir_endpoint(&request.endpoint_path, &request.method) {
+                Ok(broken) => {
+                    ir.repair_endpoint(&config, &broken, &request.error, &pool)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = repaired {
+                tracing::error!(
+                    "Failed to repair endpoint {}: {:#}",
+                    request.endpoint_path,
+                    e
+                );
+                continue;
+            }
+
+            match reload().await {
+                Ok(_) => tracing::info!("Repaired endpoint {}", request.endpoint_path),
+                Err(e) => tracing::error!(
+                    "Repaired endpoint {} but failed to reload: {:#}",
+                    request.endpoint_path,
+                    e
+                ),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_repairable() {
+        // syntax_error, undefined_column, undefined_table, undefined_function
+        for code in ["42601", "42703", "42P01", "42883"] {
+            assert!(is_repairable(code), "{}", code);
+        }
+        // insufficient_privilege, division_by_zero, query_canceled
+        for code in ["42501", "22012", "57014"] {
+            assert!(!is_repairable(code), "{}", code);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Multi-stage endpoint queries
+//!
+//! An endpoint IR can run `stages` before its `sql_query`, for requests like "the
+//! top pools, then their recent swaps" that are unwieldy as one statement. Each
+//! stage is a read-only query whose `outputs` columns are passed to every later
+//! query as `TEXT[]` parameters, numbered after the request's path and query
+//! parameters in stage and column order, e.g. `pool = ANY($2)`. The stages and
+//! the final query run in one repeatable-read transaction, so they all read the
+//! same snapshot.
+
+use crate::ai::{EndpointIrResult, QueryStage};
+use crate::identifier;
+use crate::sql_guard;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+
+/// Rows a stage may return, its outputs are bound as arrays of this many values
+pub const MAX_STAGE_ROWS: usize = 10_000;
+
+/// Check that stages have unique names, outputs, and read-only SQL
+pub fn check(endpoint: &EndpointIrResult) -> Result<()> {
+    let mut names = HashSet::new();
+    for stage in &endpoint.stages {
+        identifier::check(&stage.name).context("Invalid stage name")?;
+        if !names.insert(stage.name.as_str()) {
+            anyhow::bail!("Stage '{}' is defined twice", stage.name);
+        }
+        if stage.outputs.is_empty() {
+            anyhow::bail!("Stage '{}' has no outputs", stage.name);
+        }
+        for output in &stage.outputs {
+            identifier::check(output)
+                .context(format!("Invalid output of stage '{}'", stage.name))?;
+        }
+        sql_guard::check_read_only_sql(&stage.sql_query)
+            .context(format!("Invalid SQL of stage '{}'", stage.name))?;
+    }
+    Ok(())
+}
+
+/// Parameters the outputs of the first `count` stages take
+pub fn outputs_before(stages: &[QueryStage], count: usize) -> usize {
+    stages
+        .iter()
+        .take(count)
+        .map(|stage| stage.outputs.len())
+        .sum()
+}
+
+/// Query for a stage's outputs as text, with one row more than allowed so
+/// oversized stages are detected rather than cut off
+pub fn outputs_sql(stage: &QueryStage) -> String {
+    let columns: Vec<String> = stage
+        .outputs
+        .iter()
+        .map(|output| format!("stage.{}::TEXT", output))
+        .collect();
+    format!(
+        "SELECT {} FROM ({}) AS stage LIMIT {}",
+        columns.join(", "),
+        stage.sql_query.trim(),
+        MAX_STAGE_ROWS + 1
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(name: &str, sql: &str, outputs: &[&str]) -> QueryStage {
+        QueryStage {
+            name: name.to_string(),
+            sql_query: sql.to_string(),
+            outputs: outputs.iter().map(|output| output.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_checks_stages_and_numbers_their_outputs() {
+        let top_pools = stage(
+            "top_pools",
+            "SELECT pool, token0 FROM pool_volumes ORDER BY volume DESC LIMIT 10",
+            &["pool", "token0"],
+        );
+        let tokens = stage(
+            "tokens",
+            "SELECT DISTINCT token FROM tokens WHERE token = ANY($2)",
+            &["token"],
+        );
+        assert_eq!(
+            outputs_sql(&top_pools),
+            "SELECT stage.pool::TEXT, stage.token0::TEXT FROM \
+             (SELECT pool, token0 FROM pool_volumes ORDER BY volume DESC LIMIT 10) AS stage LIMIT 10001"
+        );
+        let stages = [top_pools, tokens];
+        assert_eq!(outputs_before(&stages, 1), 2);
+        assert_eq!(outputs_before(&stages, 2), 3);
+
+        let mut endpoint: EndpointIrResult = serde_json::from_value(serde_json::json!({
+            "endpoint_path": "/api/top-pool-swaps",
+            "description": "",
+            "method": "GET",
+            "path_params": [],
+            "query_params": [],
+            "response_schema": {"name": "Swap", "fields": []},
+            "sql_query": "SELECT * FROM swaps WHERE pool = ANY($1) OR token = ANY($3) LIMIT 50",
+            "stages": stages,
+            "tables_referenced": ["swaps"]
+        }))
+        .unwrap();
+        check(&endpoint).unwrap();
+
+        endpoint.stages[1].name = "top_pools".to_string();
+        assert!(check(&endpoint).is_err());
+        endpoint.stages[1] = stage("tokens", "DELETE FROM tokens RETURNING token", &["token"]);
+        assert!(check(&endpoint).is_err());
+        endpoint.stages[1] = stage("tokens", "SELECT token FROM tokens", &[]);
+        assert!(check(&endpoint).is_err());
+    }
+}
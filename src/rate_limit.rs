@@ -0,0 +1,196 @@
+//! RPC request budgets shared by every chain on the same provider
+//!
+//! Configured per RPC host under `[chains.rateLimits]`, in requests per second:
+//!
+//! ```toml
+//! [chains.rateLimits]
+//! "g.alchemy.com" = 25
+//! ```
+//!
+//! A budget applies to every chain whose RPC URL is on that host or one of its
+//! subdomains, so chains indexed in parallel against one provider account stay
+//! under its global limit together. The most specific host wins.
+
+use crate::error::{ResultExt, SmortyError};
+use alloy::providers::{Provider, ProviderBuilder, RootProvider};
+use alloy::rpc::client::ClientBuilder;
+use alloy::rpc::json_rpc::{RequestPacket, ResponsePacket};
+use alloy::transports::{TransportError, TransportFut};
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use std::time::Duration;
+use tokio::time::Instant;
+use tower::{Layer, Service};
+
+/// Rate limiters by RPC host
+#[derive(Debug, Clone, Default)]
+pub struct RpcRateLimits {
+    /// Longest host first, so subdomains take precedence
+    limits: Vec<(String, RateLimitLayer)>,
+}
+
+impl RpcRateLimits {
+    pub fn new(requests_per_second: &BTreeMap<String, u32>) -> Self {
+        let mut limits: Vec<(String, RateLimitLayer)> = requests_per_second
+            .iter()
+            .map(|(host, rps)| (host.to_lowercase(), RateLimitLayer::new(*rps)))
+            .collect();
+        limits.sort_by_key(|(host, _)| std::cmp::Reverse(host.len()));
+        Self { limits }
+    }
+
+    /// Limiter shared by RPC URLs on the same host as `rpc_url`
+    fn for_url(&self, rpc_url: &str) -> Option<&RateLimitLayer> {
+        let url = reqwest::Url::parse(rpc_url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        self.limits
+            .iter()
+            .find(|(limit_host, _)| {
+                host == *limit_host || host.ends_with(&format!(".{}", limit_host))
+            })
+            .map(|(_, layer)| layer)
+    }
+
+    /// HTTP provider for an RPC URL, throttled by the budget of its host
+    pub fn provider(&self, rpc_url: &str) -> Result<RootProvider> {
+        let url = rpc_url
+            .parse()
+            .context("Invalid RPC URL")
+            .kind(SmortyError::Config)?;
+        let client = match self.for_url(rpc_url) {
+            Some(layer) => ClientBuilder::default().layer(layer.clone()).http(url),
+            None => ClientBuilder::default().http(url),
+        };
+        Ok(ProviderBuilder::new().connect_client(client).root().clone())
+    }
+}
+
+/// Transport layer spacing requests out to a rate, allowing a second's worth of burst
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(requests_per_second: u32) -> Self {
+        Self {
+            limiter: Arc::new(RateLimiter::new(requests_per_second)),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: Arc::clone(&self.limiter),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S> Service<RequestPacket> for RateLimitService<S>
+where
+    S: Service<RequestPacket, Response = ResponsePacket, Error = TransportError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        let limiter = Arc::clone(&self.limiter);
+        let mut inner = self.inner.clone();
+        Box::pin(async move {
+            limiter.acquire().await;
+            inner.call(request).await
+        })
+    }
+}
+
+/// Generic cell rate algorithm: each request moves the theoretical arrival time
+/// on by one interval, and waits while it is more than a burst ahead of now
+#[derive(Debug)]
+struct RateLimiter {
+    interval: Duration,
+    burst: Duration,
+    arrival: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: u32) -> Self {
+        let requests_per_second = requests_per_second.max(1);
+        let interval = Duration::from_secs(1) / requests_per_second;
+        Self {
+            interval,
+            burst: interval * (requests_per_second - 1),
+            arrival: Mutex::new(None),
+        }
+    }
+
+    /// Wait for this request's turn
+    async fn acquire(&self) {
+        let wait = self.reserve(Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Reserve a slot at `now`, returning how long to wait for it
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut arrival = self.arrival.lock().unwrap_or_else(|e| e.into_inner());
+        let tat = arrival.map_or(now, |tat| tat.max(now));
+        *arrival = Some(tat + self.interval);
+        tat.saturating_duration_since(now + self.burst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_match_hosts_and_space_requests() {
+        let limits = RpcRateLimits::new(&BTreeMap::from([
+            ("alchemy.com".to_string(), 10),
+            ("eth-mainnet.g.alchemy.com".to_string(), 5),
+        ]));
+        let limiter = |url: &str| {
+            limits
+                .for_url(url)
+                .map(|layer| layer.limiter.interval.as_millis())
+        };
+        assert_eq!(
+            limiter("https://eth-mainnet.g.alchemy.com/v2/key"),
+            Some(200)
+        );
+        assert_eq!(
+            limiter("https://base-mainnet.g.alchemy.com/v2/key"),
+            Some(100)
+        );
+        assert_eq!(limiter("https://notalchemy.com"), None);
+        assert_eq!(limiter("https://rpc.soniclabs.com"), None);
+
+        // Two requests per second: the first two go out at once, then one every 500ms
+        let limiter = RateLimiter::new(2);
+        let now = Instant::now();
+        let waits: Vec<u128> = (0..4).map(|_| limiter.reserve(now).as_millis()).collect();
+        assert_eq!(waits, [0, 0, 500, 1000]);
+    }
+}
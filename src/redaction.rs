@@ -0,0 +1,303 @@
+//! Column-level redaction, configured with `columnPolicies`
+//!
+//! Insert-stage policies rewrite the SQL values of the indexer's INSERTs, so the
+//! original value never reaches the database. Response-stage policies rewrite
+//! decoded rows before endpoints return them or exports write them.
+
+use crate::ai::EndpointIrResult;
+use crate::config::{ColumnPolicy, ColumnPolicyConfig, PolicyStage};
+use serde_json::{Value as JsonValue, json};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Apply the insert-stage policies for `table` to the columns and SQL values of an INSERT
+pub fn redact_insert(
+    policies: &[ColumnPolicyConfig],
+    table: &str,
+    columns: &mut Vec<String>,
+    values: &mut Vec<String>,
+) {
+    for policy in policies
+        .iter()
+        .filter(|policy| policy.at == PolicyStage::Insert && applies_to(policy, table))
+    {
+        let Some(idx) = columns.iter().position(|column| *column == policy.column) else {
+            continue;
+        };
+
+        match policy.policy {
+            ColumnPolicy::Drop => {
+                columns.remove(idx);
+                values.remove(idx);
+            }
+            ColumnPolicy::Hash => {
+                values[idx] = format!(
+                    "encode(sha256(convert_to(({})::text, 'UTF8')), 'hex')",
+                    values[idx]
+                );
+            }
+            ColumnPolicy::Truncate => {
+                values[idx] = format!(
+                    "left(({})::text, {})",
+                    values[idx],
+                    policy.length.unwrap_or(0)
+                );
+            }
+        }
+    }
+}
+
+/// Response-stage policies of each endpoint, matched to its response fields
+#[derive(Debug, Default)]
+pub struct ResponseRedactions {
+    /// Field names and their policy, by endpoint route
+    by_endpoint: HashMap<String, Vec<(String, ColumnPolicyConfig)>>,
+    /// Response-stage policies, for rows read straight from a table
+    policies: Vec<ColumnPolicyConfig>,
+}
+
+impl ResponseRedactions {
+    /// Match response-stage policies to the fields of endpoints reading their tables
+    pub fn new(policies: &[ColumnPolicyConfig], endpoints: &[EndpointIrResult]) -> Self {
+        let mut by_endpoint = HashMap::new();
+
+        for endpoint in endpoints {
+            let fields: Vec<(String, ColumnPolicyConfig)> = endpoint
+                .response_schema
+                .fields
+                .iter()
+                .filter_map(|field| {
+                    policies
+                        .iter()
+                        .find(|policy| {
+                            policy.at == PolicyStage::Response
+                                && policy.column == field.name
+                                && endpoint
+                                    .tables_referenced
+                                    .iter()
+                                    .any(|table| applies_to(policy, table))
+                        })
+                        .map(|policy| (field.name.clone(), policy.clone()))
+                })
+                .collect();
+
+            if !fields.is_empty() {
+                by_endpoint.insert(endpoint.route(), fields);
+            }
+        }
+
+        Self {
+            by_endpoint,
+            policies: policies
+                .iter()
+                .filter(|policy| policy.at == PolicyStage::Response)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Redact a JSON object row read from `table`, e.g. by `/sse/{table}`
+    pub fn redact_table_row(&self, table: &str, row: &mut serde_json::Map<String, JsonValue>) {
+        for policy in self.policies.iter().filter(|p| applies_to(p, table)) {
+            let Some(value) = row.get_mut(&policy.column) else {
+                continue;
+            };
+            match redact_value(policy, value) {
+                Some(redacted) => *value = redacted,
+                None => {
+                    row.remove(&policy.column);
+                }
+            }
+        }
+    }
+
+    /// Redact a JSON object row returned by an endpoint
+    pub fn redact_row(
+        &self,
+        endpoint_ir: &EndpointIrResult,
+        row: &mut serde_json::Map<String, JsonValue>,
+    ) {
+        let Some(fields) = self.by_endpoint.get(&endpoint_ir.route()) else {
+            return;
+        };
+
+        for (name, policy) in fields {
+            let Some(value) = row.get_mut(name) else {
+                continue;
+            };
+            match redact_value(policy, value) {
+                Some(redacted) => *value = redacted,
+                None => {
+                    row.remove(name);
+                }
+            }
+        }
+    }
+
+    /// Redact values ordered like the endpoint's response fields
+    ///
+    /// Dropped fields become null, since every row must have every field.
+    pub fn redact_values(&self, endpoint_ir: &EndpointIrResult, values: &mut [JsonValue]) {
+        let Some(fields) = self.by_endpoint.get(&endpoint_ir.route()) else {
+            return;
+        };
+
+        for (field, value) in endpoint_ir.response_schema.fields.iter().zip(values) {
+            if let Some((_, policy)) = fields.iter().find(|(name, _)| *name == field.name) {
+                *value = redact_value(policy, value).unwrap_or(JsonValue::Null);
+            }
+        }
+    }
+}
+
+/// Whether a policy covers `table`
+pub(crate) fn applies_to(policy: &ColumnPolicyConfig, table: &str) -> bool {
+    policy.table.as_deref().is_none_or(|t| t == table)
+}
+
+/// Redacted value, or `None` if it should be left out
+///
+/// Values are redacted as text, matching what the insert-stage SQL does.
+pub(crate) fn redact_value(policy: &ColumnPolicyConfig, value: &JsonValue) -> Option<JsonValue> {
+    if policy.policy == ColumnPolicy::Drop {
+        return None;
+    }
+    if value.is_null() {
+        return Some(JsonValue::Null);
+    }
+
+    let text = match value {
+        JsonValue::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    match policy.policy {
+        ColumnPolicy::Hash => Some(json!(hex::encode(Sha256::digest(text.as_bytes())))),
+        ColumnPolicy::Truncate => Some(json!(
+            text.chars()
+                .take(policy.length.unwrap_or(0))
+                .collect::<String>()
+        )),
+        ColumnPolicy::Drop => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{ResponseField, ResponseSchema};
+
+    fn policy(
+        table: Option<&str>,
+        column: &str,
+        policy: ColumnPolicy,
+        at: PolicyStage,
+    ) -> ColumnPolicyConfig {
+        ColumnPolicyConfig {
+            table: table.map(str::to_string),
+            column: column.to_string(),
+            policy,
+            length: Some(4),
+            at,
+        }
+    }
+
+    #[test]
+    fn test_redact_insert() {
+        let policies = vec![
+            policy(None, "sender", ColumnPolicy::Hash, PolicyStage::Insert),
+            policy(
+                Some("transactions"),
+                "calldata",
+                ColumnPolicy::Truncate,
+                PolicyStage::Insert,
+            ),
+            policy(None, "memo", ColumnPolicy::Drop, PolicyStage::Insert),
+            policy(None, "amount", ColumnPolicy::Hash, PolicyStage::Response),
+        ];
+        let mut columns: Vec<String> = ["sender", "calldata", "memo", "amount"]
+            .map(String::from)
+            .to_vec();
+        let mut values: Vec<String> = ["'0xabc'", "'\\xdeadbeef'", "'hi'", "'100'"]
+            .map(String::from)
+            .to_vec();
+
+        redact_insert(&policies, "transactions", &mut columns, &mut values);
+
+        assert_eq!(columns, vec!["sender", "calldata", "amount"]);
+        assert_eq!(
+            values,
+            vec![
+                "encode(sha256(convert_to(('0xabc')::text, 'UTF8')), 'hex')",
+                "left(('\\xdeadbeef')::text, 4)",
+                "'100'",
+            ]
+        );
+
+        // The truncate policy only applies to its own table
+        let mut columns = vec!["calldata".to_string()];
+        let mut values = vec!["'\\xdeadbeef'".to_string()];
+        redact_insert(&policies, "swaps", &mut columns, &mut values);
+        assert_eq!(values, vec!["'\\xdeadbeef'"]);
+    }
+
+    #[test]
+    fn test_redact_response_rows() {
+        let endpoint = EndpointIrResult {
+            endpoint_path: "/api/transfers".to_string(),
+            method: "GET".to_string(),
+            description: String::new(),
+            path_params: vec![],
+            query_params: vec![],
+            response_schema: ResponseSchema {
+                name: "Transfer".to_string(),
+                fields: ["sender", "memo", "amount"]
+                    .iter()
+                    .map(|name| ResponseField {
+                        name: name.to_string(),
+                        field_type: "String".to_string(),
+                        description: String::new(),
+                        scale: None,
+                        format: None,
+                    })
+                    .collect(),
+            },
+            sql_query: "SELECT sender, memo, amount FROM transfers".to_string(),
+            stages: Vec::new(),
+            tables_referenced: vec!["transfers".to_string()],
+            tags: vec![],
+            scopes: vec![],
+            cache_ttl: None,
+        };
+        let policies = vec![
+            policy(
+                Some("transfers"),
+                "sender",
+                ColumnPolicy::Hash,
+                PolicyStage::Response,
+            ),
+            policy(None, "memo", ColumnPolicy::Drop, PolicyStage::Response),
+            policy(
+                Some("swaps"),
+                "amount",
+                ColumnPolicy::Drop,
+                PolicyStage::Response,
+            ),
+        ];
+        let redactions = ResponseRedactions::new(&policies, std::slice::from_ref(&endpoint));
+
+        let mut row = json!({"sender": "abc", "memo": "hi", "amount": "100"});
+        redactions.redact_row(&endpoint, row.as_object_mut().unwrap());
+        assert_eq!(
+            row,
+            json!({
+                "sender": "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+                "amount": "100",
+            })
+        );
+
+        let mut values = vec![json!("abc"), json!("hi"), JsonValue::Null];
+        redactions.redact_values(&endpoint, &mut values);
+        assert_eq!(values[1], JsonValue::Null);
+        assert_eq!(values[2], JsonValue::Null);
+    }
+}
@@ -0,0 +1,265 @@
+//! Log fetching that tolerates RPCs deviating from the Ethereum JSON-RPC spec
+//!
+//! L2 nodes return logs from system and deposit transactions with numeric
+//! quantities, missing fields or a null `removed` flag, and many nodes omit
+//! `blockTimestamp`. A single such log would fail the whole `eth_getLogs`
+//! response with a typed request, so logs are parsed one by one instead.
+
+use crate::error::{self, ResultExt, SmortyError};
+use alloy::eips::BlockNumberOrTag;
+use alloy::providers::Provider;
+use alloy::rpc::types::{Filter, Log};
+use anyhow::{Context, Result};
+use futures::{StreamExt, TryStreamExt};
+use serde_json::{Value as JsonValue, json};
+use std::collections::{BTreeSet, HashMap};
+
+/// Quantity fields of a log that may be returned as JSON numbers or left out
+const QUANTITY_FIELDS: &[&str] = &[
+    "blockNumber",
+    "blockTimestamp",
+    "transactionIndex",
+    "logIndex",
+];
+
+/// Block headers fetched at once when filling in missing timestamps
+pub(crate) const BLOCK_FETCH_CONCURRENCY: usize = 8;
+
+/// Fetch logs matching `filter`, skipping logs that can't be parsed
+///
+/// Logs without a block timestamp get the timestamp of their block.
+pub async fn get_logs<P: Provider>(provider: &P, filter: &Filter) -> error::Result<Vec<Log>> {
+    let mut logs = Vec::new();
+    stream_logs(provider, filter, |log| {
+        logs.push(log);
+        Ok(())
+    })
+    .await
+    .kind(SmortyError::Rpc)?;
+    Ok(logs)
+}
+
+/// Fetch logs matching `filter` and hand them to `sink` one at a time
+///
+/// Each raw log is dropped once it is parsed, so only the RPC response and
+/// whatever `sink` keeps are held in memory.
+pub async fn stream_logs<P: Provider>(
+    provider: &P,
+    filter: &Filter,
+    mut sink: impl FnMut(Log) -> Result<()>,
+) -> Result<()> {
+    let raw: Vec<JsonValue> = provider
+        .raw_request("eth_getLogs".into(), (filter,))
+        .await
+        .context("Failed to fetch logs")
+        .kind(SmortyError::Rpc)?;
+
+    // Blocks of logs the RPC sent without a timestamp
+    let blocks: BTreeSet<u64> = raw
+        .iter()
+        .filter(|value| {
+            value
+                .get("blockTimestamp")
+                .and_then(parse_quantity)
+                .is_none_or(|timestamp| timestamp == 0)
+        })
+        .filter_map(|value| value.get("blockNumber").and_then(parse_quantity))
+        .collect();
+    let timestamps = block_timestamps(provider, blocks)
+        .await
+        .kind(SmortyError::Rpc)?;
+
+    for value in raw {
+        let mut log = match parse_log(value.clone()) {
+            Ok(log) => log,
+            Err(e) => {
+                tracing::warn!(
+                    "Skipping log the RPC returned malformed: {:#}: {}",
+                    e,
+                    value
+                );
+                continue;
+            }
+        };
+        if log.block_timestamp.is_none() {
+            log.block_timestamp = log
+                .block_number
+                .and_then(|block| timestamps.get(&block).copied());
+        }
+        sink(log)?;
+    }
+
+    Ok(())
+}
+
+/// Parse a log, normalizing the quirks of L2 and non-geth RPCs
+pub fn parse_log(mut value: JsonValue) -> Result<Log> {
+    let log = value.as_object_mut().context("Log is not a JSON object")?;
+
+    for field in QUANTITY_FIELDS {
+        match log.get(*field) {
+            None => {
+                log.insert(field.to_string(), JsonValue::Null);
+            }
+            Some(JsonValue::Number(n)) => {
+                let n = n
+                    .as_u64()
+                    .context(format!("Invalid {} in log: {}", field, n))?;
+                log.insert(field.to_string(), json!(format!("{:#x}", n)));
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Some nodes send a zero timestamp instead of leaving it out
+    if log.get("blockTimestamp").and_then(parse_quantity) == Some(0) {
+        log.insert("blockTimestamp".to_string(), JsonValue::Null);
+    }
+
+    // Geth sends false, some L2 nodes send null
+    if !log.get("removed").is_some_and(JsonValue::is_boolean) {
+        log.insert("removed".to_string(), json!(false));
+    }
+
+    // Anonymous events from system transactions can come without topics or data
+    if log.get("topics").is_none_or(JsonValue::is_null) {
+        log.insert("topics".to_string(), json!([]));
+    }
+    if log.get("data").is_none_or(JsonValue::is_null) {
+        log.insert("data".to_string(), json!("0x"));
+    }
+
+    serde_json::from_value(value).context("Failed to parse log")
+}
+
+/// Timestamps of blocks from their headers, for logs whose RPC didn't include one
+pub async fn block_timestamps<P: Provider>(
+    provider: &P,
+    blocks: BTreeSet<u64>,
+) -> Result<HashMap<u64, u64>> {
+    if blocks.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    tracing::debug!("Fetching timestamps for {} blocks", blocks.len());
+
+    let timestamps: HashMap<u64, u64> = futures::stream::iter(blocks)
+        .map(|block| async move { anyhow::Ok((block, block_timestamp(provider, block).await?)) })
+        .buffer_unordered(BLOCK_FETCH_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    Ok(timestamps)
+}
+
+/// Timestamp of a block from its header
+pub async fn block_timestamp<P: Provider>(provider: &P, block: u64) -> Result<u64> {
+    // Raw headers, since L2 headers don't always deserialize as Ethereum headers
+    let header: JsonValue = provider
+        .raw_request(
+            "eth_getBlockByNumber".into(),
+            (BlockNumberOrTag::Number(block), false),
+        )
+        .await
+        .context(format!("Failed to fetch block {}", block))?;
+    header
+        .get("timestamp")
+        .and_then(parse_quantity)
+        .context(format!("Block {} has no timestamp", block))
+}
+
+/// Parse a hex string or JSON number quantity
+pub(crate) fn parse_quantity(value: &JsonValue) -> Option<u64> {
+    match value {
+        JsonValue::String(s) => u64::from_str_radix(s.strip_prefix("0x")?, 16).ok(),
+        JsonValue::Number(n) => n.as_u64(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::providers::ProviderBuilder;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    const TRANSFER_TOPIC: &str =
+        "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+    /// Log from an Optimism deposit transaction, without a block timestamp
+    fn optimism_deposit_log() -> JsonValue {
+        json!({
+            "address": "0x4200000000000000000000000000000000000010",
+            "topics": [TRANSFER_TOPIC],
+            "data": "0x",
+            "blockHash": "0x5b0c8d1d8a0fd4a5bd1bb3e7a7c7b06ad4b3c0e1d2f3a4b5c6d7e8f9a0b1c2d3",
+            "blockNumber": "0x7a1200",
+            "transactionHash": "0x1f7c1e4b3d6e2f9a8b7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0b9c8d7e6f5a",
+            "transactionIndex": "0x0",
+            "logIndex": "0x0",
+            "removed": false,
+            "sourceHash": "0x9b1a3f6f6b1c3e2d4a5b6c7d8e9f0a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6e7f"
+        })
+    }
+
+    #[test]
+    fn test_parses_l2_log_shapes() {
+        let log = parse_log(optimism_deposit_log()).unwrap();
+        assert_eq!(log.block_number, Some(8_000_000));
+        assert_eq!(log.block_timestamp, None);
+
+        // Arbitrum-style system log: null removed flag, numeric quantities, no tx index
+        let mut arbitrum = optimism_deposit_log();
+        let fields = arbitrum.as_object_mut().unwrap();
+        fields.insert("removed".to_string(), JsonValue::Null);
+        fields.insert("blockNumber".to_string(), json!(8_000_000));
+        fields.insert("blockTimestamp".to_string(), json!("0x0"));
+        fields.insert("data".to_string(), JsonValue::Null);
+        fields.remove("transactionIndex");
+        let log = parse_log(arbitrum).unwrap();
+        assert_eq!(log.block_number, Some(8_000_000));
+        assert_eq!(log.block_timestamp, None);
+        assert_eq!(log.transaction_index, None);
+        assert!(!log.removed);
+        assert!(log.data().data.is_empty());
+
+        assert!(parse_log(json!("not a log")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_logs_fills_missing_timestamps() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(|request: &Request| {
+                let body: JsonValue = serde_json::from_slice(&request.body).unwrap();
+                let result = match body["method"].as_str().unwrap() {
+                    "eth_getLogs" => json!([
+                        optimism_deposit_log(),
+                        // Logs that can't be parsed are skipped, not fatal
+                        {"address": "not an address"}
+                    ]),
+                    "eth_getBlockByNumber" => {
+                        assert_eq!(body["params"][0], "0x7a1200");
+                        json!({"number": "0x7a1200", "timestamp": "0x65f0a000"})
+                    }
+                    other => panic!("unexpected method {}", other),
+                };
+                ResponseTemplate::new(200).set_body_json(json!({
+                    "jsonrpc": "2.0",
+                    "id": body["id"],
+                    "result": result
+                }))
+            })
+            .mount(&server)
+            .await;
+
+        let provider = ProviderBuilder::new().connect_http(server.uri().parse().unwrap());
+        let logs = get_logs(&provider, &Filter::new().from_block(0u64).to_block(1u64))
+            .await
+            .unwrap();
+
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].block_timestamp, Some(0x65f0a000));
+    }
+}
@@ -0,0 +1,172 @@
+//! `smorty init`: the config and directories of a new project
+//!
+//! With `--docker` it also writes a docker-compose.yml running Postgres and
+//! `smorty run`, and a Dockerfile building an image of the project. Both are
+//! rendered from templates embedded in the binary, with `${project}`,
+//! `${config}` and `${port}` filled in.
+
+use crate::ir::{ENDPOINTS_DIR, SPECS_DIR};
+use crate::migration::MIGRATIONS_DIR;
+use crate::task_template::{self, TaskVars};
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+const CONFIG_TEMPLATE: &str = include_str!("../config.toml.example");
+const COMPOSE_TEMPLATE: &str = include_str!("../templates/docker/docker-compose.yml");
+const DOCKERFILE_TEMPLATE: &str = include_str!("../templates/docker/Dockerfile");
+const DOCKERIGNORE_TEMPLATE: &str = include_str!("../templates/docker/dockerignore");
+
+/// Directories every project has
+const DIRS: [&str; 4] = ["abi", SPECS_DIR, ENDPOINTS_DIR, MIGRATIONS_DIR];
+
+/// Port the API is served on in the Docker files
+const DEFAULT_PORT: u16 = 3000;
+
+/// What `init` did with a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scaffolded {
+    Created(PathBuf),
+    /// Already there and left alone
+    Skipped(PathBuf),
+}
+
+/// Project scaffolding, written into a directory
+#[derive(Debug, Clone)]
+pub struct Scaffold {
+    config_path: String,
+    docker: bool,
+    force: bool,
+}
+
+impl Scaffold {
+    pub fn new(config_path: impl Into<String>) -> Self {
+        Self {
+            config_path: config_path.into(),
+            docker: false,
+            force: false,
+        }
+    }
+
+    /// Also write docker-compose.yml, Dockerfile and .dockerignore
+    pub fn with_docker(mut self, docker: bool) -> Self {
+        self.docker = docker;
+        self
+    }
+
+    /// Overwrite files that already exist
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Files of the scaffold, by path relative to the project directory
+    fn files(&self, project: &str) -> Result<Vec<(String, String)>> {
+        let mut files = vec![(self.config_path.clone(), CONFIG_TEMPLATE.to_string())];
+        if self.docker {
+            let mut vars = TaskVars::new();
+            vars.insert("project".to_string(), project.into());
+            vars.insert("config".to_string(), self.config_path.as_str().into());
+            vars.insert("port".to_string(), i64::from(DEFAULT_PORT).into());
+            for (path, template) in [
+                ("docker-compose.yml", COMPOSE_TEMPLATE),
+                ("Dockerfile", DOCKERFILE_TEMPLATE),
+                (".dockerignore", DOCKERIGNORE_TEMPLATE),
+            ] {
+                let contents = task_template::render(template, &vars)
+                    .context(format!("Failed to render the {} template", path))?;
+                files.push((path.to_string(), contents));
+            }
+        }
+        Ok(files)
+    }
+
+    /// Create the project's directories and files in `dir`
+    pub fn write(&self, dir: &Path) -> Result<Vec<Scaffolded>> {
+        let mut done = Vec::new();
+        for name in DIRS {
+            let path = dir.join(name);
+            if path.is_dir() {
+                continue;
+            }
+            std::fs::create_dir_all(&path)
+                .context(format!("Failed to create {}", path.display()))?;
+            done.push(Scaffolded::Created(path));
+        }
+
+        for (name, contents) in self.files(&project_name(dir))? {
+            let path = dir.join(name);
+            if path.exists() && !self.force {
+                done.push(Scaffolded::Skipped(path));
+                continue;
+            }
+            std::fs::write(&path, contents)
+                .context(format!("Failed to write {}", path.display()))?;
+            done.push(Scaffolded::Created(path));
+        }
+        Ok(done)
+    }
+}
+
+/// Compose project name from the directory name, which Compose limits to
+/// lowercase letters, digits, dashes and underscores
+fn project_name(dir: &Path) -> String {
+    let name: String = std::path::absolute(dir)
+        .ok()
+        .and_then(|dir| dir.file_name()?.to_str().map(str::to_lowercase))
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    let name = name.trim_start_matches(['-', '_']);
+    if name.is_empty() {
+        "smorty".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_writes_project_with_docker_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("My Indexer");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("config.toml"), "# mine").unwrap();
+
+        let done = Scaffold::new("config.toml")
+            .with_docker(true)
+            .write(&dir)
+            .unwrap();
+
+        assert!(done.contains(&Scaffolded::Skipped(dir.join("config.toml"))));
+        assert_eq!(
+            std::fs::read_to_string(dir.join("config.toml")).unwrap(),
+            "# mine"
+        );
+        assert!(dir.join("ir/specs").is_dir());
+        assert!(dir.join("migrations").is_dir());
+
+        let compose = std::fs::read_to_string(dir.join("docker-compose.yml")).unwrap();
+        assert!(compose.contains("name: myindexer\n"), "{}", compose);
+        assert!(compose.contains("- ./config.toml:/app/config.toml:ro"));
+        assert!(compose.contains("\"run\", \"--port\", \"3000\""));
+        let dockerfile = std::fs::read_to_string(dir.join("Dockerfile")).unwrap();
+        assert!(dockerfile.contains("COPY config.toml ./"));
+        assert!(dockerfile.contains("\"$SMORTY_GIT\""));
+        assert!(dir.join(".dockerignore").exists());
+
+        let done = Scaffold::new("config.toml")
+            .with_force(true)
+            .write(&dir)
+            .unwrap();
+        assert_eq!(done, [Scaffolded::Created(dir.join("config.toml"))]);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("config.toml")).unwrap(),
+            CONFIG_TEMPLATE
+        );
+    }
+}
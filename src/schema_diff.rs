@@ -136,13 +136,24 @@ impl SchemaDiff {
         let old_idx_names: HashSet<String> = old_indexes.keys().cloned().collect();
         let new_idx_names: HashSet<String> = new_indexes.keys().cloned().collect();
 
+        // An index whose definition changed (columns, method, predicate) is rebuilt
+        let indexes_changed: HashSet<String> = old_idx_names
+            .intersection(&new_idx_names)
+            .filter(|name| !old_indexes[*name].same_definition(new_indexes[*name]))
+            .cloned()
+            .collect();
+
         let indexes_added: Vec<IndexState> = new_idx_names
             .difference(&old_idx_names)
+            .chain(&indexes_changed)
             .filter_map(|name| new_indexes.get(name).map(|i| (*i).clone()))
             .collect();
 
-        let indexes_dropped: Vec<String> =
-            old_idx_names.difference(&new_idx_names).cloned().collect();
+        let indexes_dropped: Vec<String> = old_idx_names
+            .difference(&new_idx_names)
+            .chain(&indexes_changed)
+            .cloned()
+            .collect();
 
         TableDiff {
             table_name: new_table.name.clone(),
@@ -389,6 +400,44 @@ mod tests {
         assert_eq!(table_diff.indexes_dropped.len(), 0);
     }
 
+    #[test]
+    fn test_index_definition_changed() {
+        let mut old_state = SchemaState::new();
+        old_state.add_table(create_test_table(
+            "users",
+            vec![("id", "BIGSERIAL PRIMARY KEY"), ("email", "TEXT NOT NULL")],
+            vec![("idx_email", "CREATE INDEX idx_email ON users (email)")],
+        ));
+
+        // Formatting differences alone don't rebuild the index
+        let mut same_state = SchemaState::new();
+        same_state.add_table(create_test_table(
+            "users",
+            vec![("id", "BIGSERIAL PRIMARY KEY"), ("email", "TEXT NOT NULL")],
+            vec![(
+                "idx_email",
+                "CREATE INDEX idx_email ON users USING btree(email)",
+            )],
+        ));
+        assert!(!SchemaDiff::compute(&old_state, &same_state).has_changes());
+
+        let mut new_state = SchemaState::new();
+        new_state.add_table(create_test_table(
+            "users",
+            vec![("id", "BIGSERIAL PRIMARY KEY"), ("email", "TEXT NOT NULL")],
+            vec![(
+                "idx_email",
+                "CREATE INDEX idx_email ON users(email) WHERE email <> ''",
+            )],
+        ));
+
+        let diff = SchemaDiff::compute(&old_state, &new_state);
+        let table_diff = &diff.tables_modified[0];
+        assert_eq!(table_diff.indexes_dropped, vec!["idx_email".to_string()]);
+        assert_eq!(table_diff.indexes_added.len(), 1);
+        assert!(table_diff.indexes_added[0].definition.contains("WHERE"));
+    }
+
     #[test]
     fn test_index_dropped() {
         let mut old_state = SchemaState::new();
@@ -0,0 +1,231 @@
+//! Changelog of schema changes, kept in `migrations/CHANGELOG.json`
+//!
+//! `gen-migration` adds an entry for every migration it writes, with the changes
+//! it makes and a description given with `--message` or at the prompt.
+//! `smorty schema history` lists the entries.
+
+use crate::schema_diff::SchemaDiff;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::Path;
+
+/// Changelog file, next to the schema backups
+pub const CHANGELOG_FILE: &str = "migrations/CHANGELOG.json";
+
+/// A generated migration and why it was made
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangelogEntry {
+    /// When the migration was generated, as in its file name
+    pub timestamp: String,
+    pub migration: String,
+    /// Schema state before the migration, if there was one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<String>,
+    #[serde(default)]
+    pub description: String,
+    /// Tables the migration touches
+    pub tables: Vec<String>,
+    pub changes: Vec<String>,
+}
+
+/// How `gen-migration` gets the description of a change
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeDescription {
+    /// Given on the command line
+    Message(String),
+    /// Asked for when running in a terminal
+    Prompt,
+    /// Recorded without a description
+    None,
+}
+
+impl ChangeDescription {
+    /// The description, showing the changes and asking for it if needed
+    pub fn resolve(&self, changes: &[String]) -> Result<String> {
+        match self {
+            Self::Message(message) => Ok(message.trim().to_string()),
+            Self::None => Ok(String::new()),
+            Self::Prompt => {
+                let stdin = std::io::stdin();
+                if !stdin.is_terminal() {
+                    return Ok(String::new());
+                }
+                println!("\nSchema changes:");
+                for change in changes {
+                    println!("  - {}", change);
+                }
+                print!("Describe this change (optional): ");
+                std::io::stdout().flush()?;
+                let mut answer = String::new();
+                stdin
+                    .lock()
+                    .read_line(&mut answer)
+                    .context("Failed to read change description")?;
+                Ok(answer.trim().to_string())
+            }
+        }
+    }
+}
+
+/// Load the changelog, empty if there is none yet
+pub fn load(path: &Path) -> Result<Vec<ChangelogEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).context(format!("Failed to parse {}", path.display()))
+}
+
+/// Add an entry to the end of the changelog
+pub fn append(path: &Path, entry: ChangelogEntry) -> Result<()> {
+    let mut entries = load(path)?;
+    entries.push(entry);
+    let json = serde_json::to_string_pretty(&entries)?;
+    fs::write(path, json + "\n").context(format!("Failed to write {}", path.display()))
+}
+
+/// Tables touched by a schema diff and a line per change, sorted by table
+pub fn changes(diff: &SchemaDiff) -> (Vec<String>, Vec<String>) {
+    let mut tables = Vec::new();
+    let mut changes = Vec::new();
+
+    let mut added: Vec<_> = diff.tables_added.iter().collect();
+    added.sort_by_key(|table| &table.name);
+    for table in added {
+        tables.push(table.name.clone());
+        changes.push(format!(
+            "Created table {} ({} columns)",
+            table.name,
+            table.columns.len()
+        ));
+    }
+
+    let mut dropped = diff.tables_dropped.clone();
+    dropped.sort();
+    for table in dropped {
+        changes.push(format!("Dropped table {}", table));
+        tables.push(table);
+    }
+
+    let mut modified: Vec<_> = diff.tables_modified.iter().collect();
+    modified.sort_by_key(|table| &table.table_name);
+    for table in modified {
+        let name = &table.table_name;
+        tables.push(name.clone());
+        for column in &table.columns_added {
+            changes.push(format!(
+                "Added column {}.{} ({})",
+                name, column.name, column.column_type
+            ));
+        }
+        for column in &table.columns_dropped {
+            changes.push(format!("Dropped column {}.{}", name, column));
+        }
+        for column in &table.columns_modified {
+            changes.push(format!(
+                "Changed column {}.{} from {} to {}",
+                name, column.column_name, column.old_type, column.new_type
+            ));
+        }
+        for index in &table.indexes_added {
+            changes.push(format!("Added index {} on {}", index.name, name));
+        }
+        for index in &table.indexes_dropped {
+            changes.push(format!("Dropped index {} on {}", index, name));
+        }
+    }
+
+    (tables, changes)
+}
+
+/// Render entries for `smorty schema history`, newest first
+pub fn render(entries: &[ChangelogEntry], table: Option<&str>) -> String {
+    let mut out = String::new();
+    for entry in entries
+        .iter()
+        .rev()
+        .filter(|entry| table.is_none_or(|table| entry.tables.iter().any(|t| t == table)))
+    {
+        out.push_str(&format!("{}  {}\n", entry.timestamp, entry.migration));
+        if !entry.description.is_empty() {
+            out.push_str(&format!("    {}\n", entry.description));
+        }
+        for change in &entry.changes {
+            out.push_str(&format!("    - {}\n", change));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema_diff::{ColumnModification, TableDiff};
+    use crate::schema_state::{ColumnState, TableState};
+
+    #[test]
+    fn test_records_and_renders_changes() {
+        let diff = SchemaDiff {
+            tables_added: vec![TableState::new(
+                "pool_swaps".to_string(),
+                "Pool".to_string(),
+                "Swap".to_string(),
+            )],
+            tables_dropped: vec![],
+            tables_modified: vec![TableDiff {
+                table_name: "pool_fees".to_string(),
+                columns_added: vec![ColumnState::new("fee".to_string(), "BIGINT".to_string())],
+                columns_dropped: vec![],
+                columns_modified: vec![ColumnModification {
+                    column_name: "amount".to_string(),
+                    old_type: "BIGINT".to_string(),
+                    new_type: "NUMERIC(78, 0)".to_string(),
+                }],
+                indexes_added: vec![],
+                indexes_dropped: vec![],
+            }],
+        };
+        let (tables, changes) = changes(&diff);
+        assert_eq!(tables, ["pool_swaps", "pool_fees"]);
+        assert_eq!(
+            changes,
+            [
+                "Created table pool_swaps (0 columns)",
+                "Added column pool_fees.fee (BIGINT)",
+                "Changed column pool_fees.amount from BIGINT to NUMERIC(78, 0)",
+            ]
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("CHANGELOG.json");
+        for (timestamp, tables) in [
+            ("20250101000000", vec!["pool_swaps"]),
+            ("20250201000000", vec!["pool_fees"]),
+        ] {
+            append(
+                &path,
+                ChangelogEntry {
+                    timestamp: timestamp.to_string(),
+                    migration: format!("{}_schema_update.sql", timestamp),
+                    backup: None,
+                    description: "Track fees".to_string(),
+                    tables: tables.into_iter().map(str::to_string).collect(),
+                    changes: vec!["Added column pool_fees.fee (BIGINT)".to_string()],
+                },
+            )
+            .unwrap();
+        }
+        let entries = load(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        let history = render(&entries, Some("pool_fees"));
+        assert!(
+            history
+                .starts_with("20250201000000  20250201000000_schema_update.sql\n    Track fees\n")
+        );
+        assert!(!history.contains("20250101000000"));
+    }
+}
@@ -1,9 +1,13 @@
+use crate::index_def::IndexDef;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::Path;
 
+/// Current schema state, written by `gen-migration`
+pub const SCHEMA_STATE_FILE: &str = "migrations/schema.json";
+
 /// Represents the state of a database schema at a point in time
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SchemaState {
@@ -11,6 +15,9 @@ pub struct SchemaState {
     pub tables: HashMap<String, TableState>,
     /// Timestamp when this state was captured
     pub timestamp: String,
+    /// SHA-256 of each generated migration file, by file name
+    #[serde(default)]
+    pub migrations: BTreeMap<String, String>,
 }
 
 /// State of a single table
@@ -53,6 +60,7 @@ impl SchemaState {
         Self {
             tables: HashMap::new(),
             timestamp: chrono::Utc::now().to_rfc3339(),
+            migrations: BTreeMap::new(),
         }
     }
 
@@ -152,6 +160,32 @@ impl IndexState {
         Self { name, definition }
     }
 
+    /// Whether two definitions build the same index, ignoring formatting
+    pub fn same_definition(&self, other: &IndexState) -> bool {
+        match (
+            IndexDef::parse(&self.definition),
+            IndexDef::parse(&other.definition),
+        ) {
+            (Ok(a), Ok(b)) => {
+                let normalize = |index: IndexDef| {
+                    let collapse = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ");
+                    (
+                        index
+                            .columns
+                            .iter()
+                            .map(|c| collapse(c))
+                            .collect::<Vec<_>>(),
+                        index.method.unwrap_or_else(|| "btree".to_string()),
+                        index.predicate.as_deref().map(collapse),
+                        index.unique,
+                    )
+                };
+                normalize(a) == normalize(b)
+            }
+            _ => self.definition == other.definition,
+        }
+    }
+
     /// Extract index name from CREATE INDEX statement
     pub fn extract_index_name(create_index_sql: &str) -> Option<String> {
         // Parse "CREATE INDEX idx_name ON table(...)"
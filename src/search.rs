@@ -0,0 +1,359 @@
+//! Semantic search over tables and endpoints, served at `/api/_search`
+//!
+//! `gen-spec` and `gen-endpoint` embed a short document per table and endpoint and
+//! store the vectors in `ir/embeddings.json`. Documents are only re-embedded when
+//! their text or the embedding model changes. At query time only the query is
+//! embedded, and documents are ranked by cosine similarity.
+
+use crate::ai::{AiClient, EndpointIrResult, IrGenerationResult};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Embeddings of the project's tables and endpoints
+pub const EMBEDDINGS_FILE: &str = "ir/embeddings.json";
+
+/// Whether a search result is a table or an endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    Table,
+    Endpoint,
+}
+
+/// Text to embed for one table or endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub kind: EntryKind,
+    /// Table name or endpoint path
+    pub name: String,
+    pub description: String,
+    pub text: String,
+}
+
+impl Document {
+    /// One document per table, the first spec writing to a shared table describes it
+    pub fn tables(specs: &[IrGenerationResult]) -> Vec<Document> {
+        let mut seen = HashSet::new();
+        specs
+            .iter()
+            .filter(|ir| seen.insert(ir.table_schema.table_name.clone()))
+            .map(|ir| {
+                let columns: Vec<&str> = ir
+                    .table_schema
+                    .columns
+                    .iter()
+                    .map(|column| column.name.as_str())
+                    .chain(
+                        ir.table_schema
+                            .computed_columns
+                            .iter()
+                            .map(|column| column.name.as_str()),
+                    )
+                    .collect();
+                Document {
+                    kind: EntryKind::Table,
+                    name: ir.table_schema.table_name.clone(),
+                    description: ir.description.clone(),
+                    text: format!(
+                        "Table {} on {}: {}\nEvent: {}\nColumns: {}",
+                        ir.table_schema.table_name,
+                        ir.chain,
+                        ir.description,
+                        ir.event_signature,
+                        columns.join(", ")
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// One document per endpoint
+    pub fn endpoints(endpoints: &[EndpointIrResult]) -> Vec<Document> {
+        endpoints
+            .iter()
+            .map(|endpoint| Document {
+                kind: EntryKind::Endpoint,
+                name: endpoint.endpoint_path.clone(),
+                description: endpoint.description.clone(),
+                text: format!(
+                    "Endpoint {} {}: {}\nReturns: {}\nTables: {}",
+                    endpoint.method,
+                    endpoint.endpoint_path,
+                    endpoint.description,
+                    endpoint
+                        .response_schema
+                        .fields
+                        .iter()
+                        .map(|field| field.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    endpoint.tables_referenced.join(", ")
+                ),
+            })
+            .collect()
+    }
+
+    fn text_hash(&self) -> String {
+        hex::encode(Sha256::digest(self.text.as_bytes()))
+    }
+}
+
+/// A stored embedding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchEntry {
+    pub kind: EntryKind,
+    pub name: String,
+    pub description: String,
+    /// SHA-256 of the embedded text, to skip unchanged documents
+    pub text_hash: String,
+    pub embedding: Vec<f32>,
+}
+
+/// A search result
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub kind: EntryKind,
+    pub name: String,
+    pub description: String,
+    /// Cosine similarity with the query, between -1 and 1
+    pub score: f32,
+}
+
+/// Embeddings of every table and endpoint, stored in `ir/embeddings.json`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    /// Embedding model of the entries; queries must use the same one
+    pub model: String,
+    pub entries: Vec<SearchEntry>,
+}
+
+impl SearchIndex {
+    /// Load the index, or an empty one if it hasn't been built yet
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content =
+            fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content).context(format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context(format!("Failed to create {}", parent.display()))?;
+        }
+        let json = serde_json::to_string(self).context("Failed to serialize search index")?;
+        fs::write(path, json).context(format!("Failed to write {}", path.display()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Embed new and changed documents and drop entries that no longer exist
+    ///
+    /// Returns the number of documents embedded.
+    pub async fn update(&mut self, ai_client: &AiClient, documents: &[Document]) -> Result<usize> {
+        let model = ai_client.embedding_model();
+        let stale = self.stale_documents(model, documents);
+        if stale.is_empty() && self.entries.len() == documents.len() {
+            return Ok(0);
+        }
+
+        let embeddings = if stale.is_empty() {
+            Vec::new()
+        } else {
+            ai_client
+                .embed(stale.iter().map(|doc| doc.text.clone()).collect())
+                .await
+                .context("Failed to embed tables and endpoints")?
+        };
+        let mut embedded: HashMap<(EntryKind, &str), Vec<f32>> = stale
+            .iter()
+            .map(|doc| (doc.kind, doc.name.as_str()))
+            .zip(embeddings)
+            .collect();
+
+        let mut existing: HashMap<(EntryKind, String), SearchEntry> =
+            std::mem::take(&mut self.entries)
+                .into_iter()
+                .map(|entry| ((entry.kind, entry.name.clone()), entry))
+                .collect();
+
+        self.entries = documents
+            .iter()
+            .filter_map(|doc| {
+                let embedding = match embedded.remove(&(doc.kind, doc.name.as_str())) {
+                    Some(embedding) => embedding,
+                    None => existing.remove(&(doc.kind, doc.name.clone()))?.embedding,
+                };
+                Some(SearchEntry {
+                    kind: doc.kind,
+                    name: doc.name.clone(),
+                    description: doc.description.clone(),
+                    text_hash: doc.text_hash(),
+                    embedding,
+                })
+            })
+            .collect();
+        self.model = model.to_string();
+
+        Ok(stale.len())
+    }
+
+    /// Documents without an up to date embedding
+    fn stale_documents<'a>(&self, model: &str, documents: &'a [Document]) -> Vec<&'a Document> {
+        if self.model != model {
+            return documents.iter().collect();
+        }
+
+        let hashes: HashMap<(EntryKind, &str), &str> = self
+            .entries
+            .iter()
+            .map(|entry| ((entry.kind, entry.name.as_str()), entry.text_hash.as_str()))
+            .collect();
+        documents
+            .iter()
+            .filter(|doc| {
+                hashes.get(&(doc.kind, doc.name.as_str())).copied() != Some(&*doc.text_hash())
+            })
+            .collect()
+    }
+
+    /// The `limit` entries most similar to a query embedding
+    pub fn rank(&self, query: &[f32], limit: usize) -> Vec<SearchHit> {
+        let mut hits: Vec<SearchHit> = self
+            .entries
+            .iter()
+            .map(|entry| SearchHit {
+                kind: entry.kind,
+                name: entry.name.clone(),
+                description: entry.description.clone(),
+                score: cosine_similarity(query, &entry.embedding),
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(limit);
+        hits
+    }
+}
+
+/// Search index together with the client embedding queries
+pub struct Search {
+    index: SearchIndex,
+    ai_client: Arc<AiClient>,
+}
+
+impl Search {
+    pub fn new(index: SearchIndex, ai_client: Arc<AiClient>) -> Self {
+        Self { index, ai_client }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Tables and endpoints most relevant to a free-text query
+    pub async fn query(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        if self.ai_client.embedding_model() != self.index.model {
+            anyhow::bail!(
+                "The search index was built with {}, but ai.embeddingModel is {}. Run gen-spec or gen-endpoint to rebuild it",
+                self.index.model,
+                self.ai_client.embedding_model()
+            );
+        }
+
+        let embedding = self
+            .ai_client
+            .embed(vec![query.to_string()])
+            .await?
+            .pop()
+            .context("No embedding returned for the query")?;
+        Ok(self.index.rank(&embedding, limit))
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(kind: EntryKind, name: &str, text: &str) -> Document {
+        Document {
+            kind,
+            name: name.to_string(),
+            description: String::new(),
+            text: text.to_string(),
+        }
+    }
+
+    fn entry(doc: &Document, embedding: Vec<f32>) -> SearchEntry {
+        SearchEntry {
+            kind: doc.kind,
+            name: doc.name.clone(),
+            description: doc.description.clone(),
+            text_hash: doc.text_hash(),
+            embedding,
+        }
+    }
+
+    #[test]
+    fn test_stale_documents() {
+        let swaps = document(EntryKind::Table, "swaps", "Table swaps");
+        let fees = document(EntryKind::Table, "fees", "Table fees");
+        let index = SearchIndex {
+            model: "text-embedding-3-small".to_string(),
+            entries: vec![entry(&swaps, vec![1.0, 0.0])],
+        };
+
+        let changed = document(EntryKind::Table, "swaps", "Table swaps with amounts");
+        let endpoint = document(EntryKind::Endpoint, "swaps", "Table swaps");
+        let documents = vec![
+            swaps.clone(),
+            fees.clone(),
+            changed.clone(),
+            endpoint.clone(),
+        ];
+
+        let stale = index.stale_documents("text-embedding-3-small", &documents);
+        assert_eq!(stale, vec![&fees, &changed, &endpoint]);
+
+        // A different model invalidates every embedding
+        let stale = index.stale_documents("text-embedding-3-large", &documents);
+        assert_eq!(stale.len(), 4);
+    }
+
+    #[test]
+    fn test_rank() {
+        let swaps = document(EntryKind::Table, "swaps", "");
+        let fees = document(EntryKind::Endpoint, "/api/fees", "");
+        let index = SearchIndex {
+            model: String::new(),
+            entries: vec![entry(&swaps, vec![1.0, 0.0]), entry(&fees, vec![0.6, 0.8])],
+        };
+
+        let hits = index.rank(&[0.0, 2.0], 10);
+        assert_eq!(hits[0].name, "/api/fees");
+        assert!((hits[0].score - 0.8).abs() < 1e-6);
+        assert_eq!(hits[1].score, 0.0);
+
+        assert_eq!(index.rank(&[1.0, 0.0], 1).len(), 1);
+    }
+}
@@ -0,0 +1,180 @@
+//! Encrypted config values, so config files can be committed without plaintext secrets
+//!
+//! Any string in the config can be replaced by the output of
+//! `smorty config encrypt`, e.g. `apiKey = "enc:v1:..."`. Values are encrypted
+//! with AES-256-GCM under a project key, read from `SMORTY_CONFIG_KEY` (hex), the
+//! file named by `SMORTY_CONFIG_KEY_FILE`, or `.smorty.key` in the project
+//! directory, and are decrypted when the config is loaded.
+
+use anyhow::{Context, Result};
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Prefix of encrypted config values
+pub const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+/// Environment variable holding the hex-encoded key
+pub const CONFIG_KEY_ENV: &str = "SMORTY_CONFIG_KEY";
+
+/// Environment variable naming the key file
+pub const CONFIG_KEY_FILE_ENV: &str = "SMORTY_CONFIG_KEY_FILE";
+
+/// Key file used when neither variable is set, relative to the project directory
+pub const DEFAULT_KEY_FILE: &str = ".smorty.key";
+
+/// Key that config values are encrypted with
+pub struct ConfigKey {
+    key: LessSafeKey,
+}
+
+impl ConfigKey {
+    /// A new random key, hex-encoded
+    pub fn generate() -> String {
+        hex::encode(rand::random::<[u8; 32]>())
+    }
+
+    /// Parse a hex-encoded key
+    pub fn parse(hex_key: &str) -> Result<Self> {
+        let bytes = hex::decode(hex_key.trim()).context("Config key should be hex")?;
+        if bytes.len() != 32 {
+            anyhow::bail!("Config key should be 32 bytes, got {}", bytes.len());
+        }
+        Ok(Self::from_bytes(&bytes))
+    }
+
+    /// Key from the environment or the default key file
+    pub fn load() -> Result<Self> {
+        if let Ok(hex_key) = std::env::var(CONFIG_KEY_ENV) {
+            return Self::parse(&hex_key).context(format!("Invalid {}", CONFIG_KEY_ENV));
+        }
+        let path = std::env::var(CONFIG_KEY_FILE_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(DEFAULT_KEY_FILE));
+        let hex_key = fs::read_to_string(&path).context(format!(
+            "No config key found: set {} or {}, or create {} with 'smorty config keygen'",
+            CONFIG_KEY_ENV,
+            CONFIG_KEY_FILE_ENV,
+            path.display()
+        ))?;
+        Self::parse(&hex_key).context(format!("Invalid key file {}", path.display()))
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let key = UnboundKey::new(&AES_256_GCM, bytes).expect("key is 32 bytes");
+        Self {
+            key: LessSafeKey::new(key),
+        }
+    }
+
+    /// Encrypt a value for the config
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        let nonce = rand::random::<[u8; NONCE_LEN]>();
+        let mut data = plaintext.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+            .expect("plaintext fits in a single message");
+        format!(
+            "{}{}{}",
+            ENCRYPTED_PREFIX,
+            hex::encode(nonce),
+            hex::encode(data)
+        )
+    }
+
+    /// Decrypt a value written by `encrypt`
+    pub fn decrypt(&self, value: &str) -> Result<String> {
+        let encoded = value
+            .strip_prefix(ENCRYPTED_PREFIX)
+            .context("Value is not encrypted")?;
+        let bytes = hex::decode(encoded).context("Encrypted value should be hex")?;
+        if bytes.len() < NONCE_LEN {
+            anyhow::bail!("Encrypted value is too short");
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).expect("nonce has NONCE_LEN bytes");
+        let mut data = ciphertext.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut data)
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt value, is this the right key?"))?;
+        String::from_utf8(plaintext.to_vec()).context("Decrypted value is not UTF-8")
+    }
+
+    /// Write the key to a new file only the current user can read
+    pub fn write(hex_key: &str, path: &Path) -> Result<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path).context(format!(
+            "Failed to create {}, it may already exist",
+            path.display()
+        ))?;
+        writeln!(file, "{}", hex_key).context(format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Whether a config file has encrypted values
+pub fn has_encrypted_values(content: &str) -> bool {
+    content.contains(ENCRYPTED_PREFIX)
+}
+
+/// Decrypt every encrypted string in a parsed config
+pub fn decrypt_values(value: &mut toml::Value, key: &ConfigKey) -> Result<()> {
+    decrypt_at(value, key, "")
+}
+
+fn decrypt_at(value: &mut toml::Value, key: &ConfigKey, path: &str) -> Result<()> {
+    match value {
+        toml::Value::String(s) if s.starts_with(ENCRYPTED_PREFIX) => {
+            *s = key
+                .decrypt(s)
+                .context(format!("Failed to decrypt config value {}", path))?;
+        }
+        toml::Value::Array(values) => {
+            for (i, value) in values.iter_mut().enumerate() {
+                decrypt_at(value, key, &format!("{}[{}]", path, i))?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (name, value) in table.iter_mut() {
+                let path = if path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", path, name)
+                };
+                decrypt_at(value, key, &path)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_values_round_trip() {
+        let hex_key = ConfigKey::generate();
+        let key = ConfigKey::parse(&hex_key).unwrap();
+        let encrypted = key.encrypt("sk-secret");
+        assert!(encrypted.starts_with(ENCRYPTED_PREFIX));
+        assert_ne!(key.encrypt("sk-secret"), encrypted);
+
+        let mut config: toml::Value = toml::from_str(&format!(
+            "[ai.openai]\napiKey = \"{}\"\nmodel = \"gpt-4o\"\n",
+            encrypted
+        ))
+        .unwrap();
+        decrypt_values(&mut config, &key).unwrap();
+        assert_eq!(config["ai"]["openai"]["apiKey"].as_str(), Some("sk-secret"));
+        assert_eq!(config["ai"]["openai"]["model"].as_str(), Some("gpt-4o"));
+
+        let other_key = ConfigKey::parse(&ConfigKey::generate()).unwrap();
+        assert!(other_key.decrypt(&encrypted).is_err());
+    }
+}
@@ -1,21 +1,62 @@
-use crate::ai::EndpointIrResult;
-use crate::config::Config;
+use crate::ai::{AiClient, EndpointIrResult, QueryParam};
+use crate::auth::ApiKeyStore;
+use crate::config::{ADMIN_SCOPE, Config, EndpointDefaultsConfig, ResponseEnvelope};
 use crate::constants;
+use crate::databases::{DatabaseRouter, RowMerge};
+use crate::endpoint_path::{self, RouteTemplates};
+use crate::envelope::{self, ResponseEnvelopes};
+use crate::error::{ResultExt, SmortyError};
+use crate::export::{ExportJobs, ExportStatus};
 use crate::ir::Ir;
+use crate::latest_rows::LatestRows;
+use crate::mcp::{self, McpServer};
+use crate::numeric::PgDecimal;
+use crate::pause::{self, Pause};
+use crate::privacy::Privacy;
+use crate::query_repair::{self, QueryRepairs};
+use crate::query_stages;
+use crate::redaction::ResponseRedactions;
+use crate::schema_state::{SCHEMA_STATE_FILE, SchemaState};
+use crate::search::{self, Search, SearchIndex};
+use crate::slow_query::SlowQueryLog;
+use crate::sql_guard;
+use crate::sse::{RowCursor, TableStream, TenantScope};
+use crate::system;
+use crate::table_api;
+use crate::table_stats::{ContractStats, TableStats};
+use crate::tenancy::TenantFilter;
+use crate::transform;
+use crate::usage::UsageRecorder;
+use crate::watermark::{self, TableWatermarks};
 use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
 use axum::{
-    Json, Router,
-    extract::{Path as AxumPath, Query},
-    http::StatusCode,
-    response::{IntoResponse, Response},
-    routing::get,
+    Extension, Json, Router,
+    body::{Body, Bytes},
+    extract::{ConnectInfo, Path as AxumPath, Query, Request},
+    http::{
+        HeaderMap, HeaderValue, Method, StatusCode,
+        header::{ALLOW, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, ETAG, LOCATION},
+    },
+    response::{
+        IntoResponse, Redirect, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{MethodRouter, get, post},
 };
+use futures::Stream;
 use serde::Deserialize;
 use serde_json::{Value as JsonValue, json};
-use sqlx::postgres::PgPoolOptions;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions, PgStatement, PgTypeInfo};
 use sqlx::{PgPool, Row};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tower::{ServiceExt, service_fn};
 use tower_http::cors::{Any, CorsLayer};
 use utoipa::openapi::path::*;
 use utoipa::openapi::*;
@@ -24,17 +65,55 @@ use utoipa_swagger_ui::SwaggerUi;
 /// Shared application state
 #[derive(Clone)]
 pub struct AppState {
-    pub db_pool: PgPool,
+    /// Read-only pools of `database.uri` and the per-chain databases
+    pub databases: DatabaseRouter,
     pub endpoints: Arc<Vec<EndpointIrResult>>,
+    pub api_keys: Arc<ApiKeyStore>,
+    pub tenancy: Option<Arc<TenantFilter>>,
+    pub watermarks: TableWatermarks,
+    /// Tables managed by smorty, from migrations/schema.json
+    pub schema: Arc<SchemaState>,
+    pub envelopes: Arc<ResponseEnvelopes>,
+    /// Routes of endpoints that fail on undecodable columns instead of returning NULL
+    pub strict_decoding: Arc<HashSet<String>>,
+    /// How rows are merged for endpoints reading several databases, keyed by route
+    pub row_merges: Arc<HashMap<String, RowMerge>>,
+    /// Limits on concurrent queries, keyed by route
+    pub concurrency_limits: Arc<HashMap<String, Arc<Semaphore>>>,
+    pub exports: ExportJobs,
+    /// Routes of endpoints that run as export jobs
+    pub export_endpoints: Arc<HashSet<String>>,
+    pub redactions: Arc<ResponseRedactions>,
+    /// Embeddings of tables and endpoints for `/api/_search`
+    pub search: Arc<Search>,
+    /// Set when `server.repairQueries` is on
+    pub repairs: Option<QueryRepairs>,
+    /// Set when `server.usageAnalytics` is on
+    pub usage: Option<UsageRecorder>,
+    /// Set when `server.slowQueryMs` is
+    pub slow_queries: Option<SlowQueryLog>,
+    /// Set when `server.latestRows` is
+    pub latest_rows: Option<Arc<LatestRows>>,
+    pub privacy: Arc<Privacy>,
 }
 
 /// API error type
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum ApiError {
+    #[error("Database error: {0}")]
     Database(sqlx::Error),
+    #[error("{0}")]
     Internal(String),
+    #[error("{0}")]
     BadRequest(String),
+    #[error("{0}")]
     NotFound(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    MethodNotAllowed(String),
 }
 
 impl IntoResponse for ApiError {
@@ -47,13 +126,22 @@ impl IntoResponse for ApiError {
             ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
             ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
             ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
+            ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            ApiError::MethodNotAllowed(msg) => (StatusCode::METHOD_NOT_ALLOWED, msg),
         };
 
         let body = Json(json!({
             "error": error_message
         }));
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if status == StatusCode::METHOD_NOT_ALLOWED {
+            response
+                .headers_mut()
+                .insert(ALLOW, HeaderValue::from_static(READ_ONLY_METHODS));
+        }
+        response
     }
 }
 
@@ -69,6 +157,43 @@ impl From<anyhow::Error> for ApiError {
     }
 }
 
+impl From<ApiError> for SmortyError {
+    fn from(err: ApiError) -> Self {
+        SmortyError::Api(err.into())
+    }
+}
+
+/// Methods accepted by every endpoint, all of which are read-only
+const READ_ONLY_METHODS: &str = "GET, HEAD, OPTIONS";
+
+/// GET route that also answers OPTIONS, and 405 with an `Allow` header for other methods
+///
+/// axum's `get` already serves HEAD by running the handler and dropping the body, so
+/// HEAD responses carry the same status and ETag as GET.
+fn read_only_route(route: MethodRouter) -> MethodRouter {
+    route_allowing(route, HeaderValue::from_static(READ_ONLY_METHODS))
+}
+
+/// Route answering OPTIONS with the methods in `allow`, and 405 for other methods
+fn route_allowing(route: MethodRouter, allow: HeaderValue) -> MethodRouter {
+    let options_allow = allow.clone();
+    route
+        .options(move || {
+            let allow = options_allow.clone();
+            async move { (StatusCode::NO_CONTENT, [(ALLOW, allow)]) }
+        })
+        .fallback(move |method: Method| {
+            let allow = allow.clone();
+            async move {
+                let mut response =
+                    ApiError::MethodNotAllowed(format!("Method {} not allowed", method))
+                        .into_response();
+                response.headers_mut().insert(ALLOW, allow);
+                response
+            }
+        })
+}
+
 /// Generic query parameters for filtering and pagination
 #[derive(Debug, Deserialize)]
 pub struct GenericQueryParams {
@@ -80,51 +205,582 @@ pub struct GenericQueryParams {
 pub async fn serve(config: &Config, address: &str, port: u16) -> Result<()> {
     tracing::info!("Starting API server on {}:{}", address, port);
 
-    // Create database pool
+    let reloader = start(config).await?;
+    let (pauses_reloader, pause_reloader, resume_reloader) =
+        (reloader.clone(), reloader.clone(), reloader.clone());
+    let app = reloadable_app(Arc::clone(&reloader.router))
+        .route(
+            "/api/_admin/reload",
+            post(move |headers: HeaderMap| {
+                let reloader = reloader.clone();
+                async move { handle_reload(reloader, headers).await }
+            }),
+        )
+        .route(
+            "/api/_admin/pauses",
+            get(move |headers: HeaderMap| {
+                let reloader = pauses_reloader.clone();
+                async move { handle_list_pauses(reloader, headers).await }
+            }),
+        )
+        .route(
+            "/api/_admin/pause",
+            post(
+                move |headers: HeaderMap, Json(request): Json<PauseRequest>| {
+                    let reloader = pause_reloader.clone();
+                    async move { handle_pause(reloader, headers, request).await }
+                },
+            ),
+        )
+        .route(
+            "/api/_admin/resume",
+            post(
+                move |headers: HeaderMap, Json(request): Json<PauseRequest>| {
+                    let reloader = resume_reloader.clone();
+                    async move { handle_resume(reloader, headers, request).await }
+                },
+            ),
+        );
+
+    // Start server
+    let listener = tokio::net::TcpListener::bind(format!("{}:{}", address, port))
+        .await
+        .context("Failed to bind to address")
+        .kind(SmortyError::Api)?;
+
+    tracing::info!("API server listening on http://{}:{}", address, port);
+    tracing::info!(
+        "Swagger UI available at http://{}:{}/swagger-ui",
+        address,
+        port
+    );
+
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .context("Server error")
+    .kind(SmortyError::Api)?;
+
+    Ok(())
+}
+
+/// Serve the generated endpoints as MCP tools over stdin and stdout
+pub async fn serve_mcp(config: &Config) -> Result<()> {
+    tracing::info!("Starting MCP server on stdio");
+
+    let reloader = start(config).await?;
+    let app = reloadable_app(Arc::clone(&reloader.router));
+    McpServer::new(app, Arc::clone(&reloader.config))
+        .with_api_key(std::env::var(mcp::API_KEY_ENV).ok())
+        .run_stdio()
+        .await
+        .kind(SmortyError::Api)?;
+
+    Ok(())
+}
+
+/// Connect to the databases, build the router and start the background workers
+async fn start(config: &Config) -> Result<RouterReloader> {
+    let mut pools = HashMap::new();
+    for (uri, api_uri) in config.database.databases() {
+        pools.insert(uri.to_string(), connect_api_pool(uri, api_uri).await?);
+    }
+    let databases = DatabaseRouter::new(&config.database, pools);
+
+    tracing::info!("Connected to database");
+
+    let api_keys = ApiKeyStore::from_config(&config.server);
+    if api_keys.is_enabled() {
+        tracing::info!(
+            "API key authentication enabled ({} key(s))",
+            config.server.api_keys.len()
+        );
+    }
+
+    // Track per-table high-water marks for ETags
+    let watermarks = TableWatermarks::default();
+    watermarks.spawn_refresh();
+
+    // Handlers report failed queries to the repair worker, which reloads the router after a fix
+    let (repairs, repair_queue) = if config.server.repair_queries {
+        let (repairs, queue) = QueryRepairs::channel();
+        (Some(repairs), Some(queue))
+    } else {
+        (None, None)
+    };
+
+    // Usage is written with the indexer's role, the API pool is read-only
+    let usage = if config.server.usage_analytics {
+        let usage = UsageRecorder::connect(&config.database.uri).await?;
+        usage.spawn_flush();
+        tracing::info!("Usage analytics enabled");
+        Some(usage)
+    } else {
+        None
+    };
+
+    // Kept across reloads so `/api/_slow-queries` survives them
+    let slow_queries = config
+        .server
+        .slow_query_ms
+        .map(|ms| SlowQueryLog::new(Duration::from_millis(ms), &config.column_policies));
+
+    let reloader = RouterReloader {
+        config: Arc::new(config.clone()),
+        databases,
+        api_keys: Arc::new(api_keys),
+        watermarks,
+        exports: ExportJobs::new(&config.server.exports)?,
+        ai_client: Arc::new(AiClient::from_config(&config.ai)),
+        repairs,
+        usage,
+        slow_queries,
+        pauses: PgPool::connect_lazy(&config.database.uri)
+            .context("Invalid database URI")
+            .kind(SmortyError::Config)?,
+        router: Arc::new(ArcSwap::from_pointee(Router::new())),
+        reloading: Arc::new(tokio::sync::Mutex::new(())),
+    };
+    reloader.reload().await?;
+    reloader.spawn_signal_handler();
+
+    if let Some(queue) = repair_queue {
+        tracing::info!("Query repair enabled");
+        let repair_reloader = reloader.clone();
+        query_repair::spawn_worker(
+            queue,
+            Arc::clone(&reloader.config),
+            reloader.databases.default_pool().clone(),
+            move || {
+                let reloader = repair_reloader.clone();
+                async move { reloader.reload().await }
+            },
+        );
+    }
+
+    Ok(reloader)
+}
+
+/// Prepared statements kept per API connection, enough for every endpoint's query
+const STATEMENT_CACHE_CAPACITY: usize = 1024;
+
+/// Create the pool used to serve API requests from the database at `uri`
+///
+/// Every connection defaults to read-only transactions. If `api_uri` is set, its
+/// role must also be unable to write, which is checked up front.
+async fn connect_api_pool(uri: &str, api_uri: Option<&str>) -> Result<PgPool> {
+    let options = PgConnectOptions::from_str(api_uri.unwrap_or(uri))
+        .context("Invalid database URI")?
+        .options([("default_transaction_read_only", "on")])
+        .statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+
     let db_pool = PgPoolOptions::new()
         .max_connections(10)
-        .connect(&config.database.uri)
+        .connect_with(options)
         .await
         .context("Failed to connect to database")?;
 
-    tracing::info!("Connected to database");
+    if api_uri.is_some() {
+        check_read_only_role(&db_pool).await?;
+        tracing::info!("API database role is read-only");
+    } else {
+        tracing::warn!(
+            "database.apiUri is not set, the API shares the indexer's database role (read-only transactions only)"
+        );
+    }
 
-    // Load all endpoint IRs
-    let endpoints = Ir::load_all_ir_endpoints().context("Failed to load endpoint IRs")?;
+    Ok(db_pool)
+}
 
-    if endpoints.is_empty() {
-        tracing::warn!("No endpoint IRs found. Did you run 'gen-endpoint' first?");
-    } else {
-        tracing::info!("Loaded {} endpoint(s)", endpoints.len());
-        for endpoint in &endpoints {
-            tracing::info!("  - {} {}", endpoint.method, endpoint.endpoint_path);
+/// Fail if the connected role can write to any table or create objects in the schema
+async fn check_read_only_role(pool: &PgPool) -> Result<()> {
+    let row = sqlx::query(
+        "SELECT r.rolsuper AS is_superuser,
+                has_schema_privilege(current_schema(), 'CREATE') AS can_create,
+                ARRAY(
+                    SELECT c.relname::text FROM pg_class c
+                    WHERE c.relnamespace = to_regnamespace(current_schema())
+                      AND c.relkind IN ('r', 'p')
+                      AND (has_table_privilege(c.oid, 'INSERT')
+                        OR has_table_privilege(c.oid, 'UPDATE')
+                        OR has_table_privilege(c.oid, 'DELETE')
+                        OR has_table_privilege(c.oid, 'TRUNCATE'))
+                    ORDER BY c.relname
+                ) AS writable_tables
+         FROM pg_roles r WHERE r.rolname = current_user",
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to check privileges of the API database role")?;
+
+    let is_superuser: bool = row.try_get("is_superuser")?;
+    let can_create: bool = row.try_get("can_create")?;
+    let writable_tables: Vec<String> = row.try_get("writable_tables")?;
+
+    if is_superuser {
+        anyhow::bail!("database.apiUri must use a read-only role, but it is a superuser");
+    }
+    if can_create {
+        anyhow::bail!(
+            "database.apiUri must use a read-only role, but it can create objects in the schema"
+        );
+    }
+    if !writable_tables.is_empty() {
+        anyhow::bail!(
+            "database.apiUri must use a read-only role, but it can write to: {}",
+            writable_tables.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Semaphores for endpoints with `maxConcurrency` set
+fn concurrency_limits(config: &Config) -> HashMap<String, Arc<Semaphore>> {
+    config
+        .endpoints
+        .iter()
+        .filter_map(|endpoint| {
+            let limit = endpoint.max_concurrency?;
+            Some((endpoint.route(), Arc::new(Semaphore::new(limit))))
+        })
+        .collect()
+}
+
+/// Rebuilds the router from the endpoint IRs on disk and swaps it in atomically
+///
+/// In-flight requests finish on the router they started with, so reloading never
+/// drops connections.
+#[derive(Clone)]
+struct RouterReloader {
+    config: Arc<Config>,
+    /// Every table on `database.uri` until spec IRs are loaded
+    databases: DatabaseRouter,
+    api_keys: Arc<ApiKeyStore>,
+    watermarks: TableWatermarks,
+    exports: ExportJobs,
+    /// Embeds search queries
+    ai_client: Arc<AiClient>,
+    repairs: Option<QueryRepairs>,
+    usage: Option<UsageRecorder>,
+    slow_queries: Option<SlowQueryLog>,
+    /// Writes pauses with the indexer's role, the API pool is read-only
+    pauses: PgPool,
+    router: Arc<ArcSwap<Router>>,
+    /// Serializes reloads so a slow one can't overwrite a newer one
+    reloading: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl RouterReloader {
+    /// Load endpoint IRs, rebuild the router and swap it in
+    ///
+    /// On error the current router keeps serving. Returns the number of endpoints.
+    async fn reload(&self) -> Result<usize> {
+        let _guard = self.reloading.lock().await;
+
+        let mut endpoints = load_endpoints(&self.config)?;
+
+        // Spec IRs say which chain, and so which database, each table is written to
+        let databases = if self.config.database.per_chain.is_empty() {
+            self.databases.clone()
+        } else {
+            let ir_specs =
+                Ir::load_all_ir_specs(&self.config).context("Failed to load spec IRs")?;
+            self.databases
+                .clone()
+                .with_tables(&ir_specs)
+                .with_watchlists(&self.config.watchlists)
+                .with_calls(&self.config.contracts)
+        };
+
+        // Rewrite endpoint SQL to filter rows by the caller's tenant
+        let tenancy = match &self.config.server.tenancy {
+            Some(tenancy_config) => {
+                tracing::info!("Tenancy enabled on column '{}'", tenancy_config.column);
+                let filter = TenantFilter::prepare(
+                    self.databases.default_pool(),
+                    tenancy_config,
+                    &mut endpoints,
+                )
+                .await?;
+                Some(Arc::new(filter))
+            }
+            None => None,
+        };
+
+        // Prepare each query with the types requests bind, so type errors show up now
+        // rather than on the first request. Response fields must be columns the query
+        // returns, or they are always NULL
+        let mut strict_decoding = HashSet::new();
+        let mut row_merges = HashMap::new();
+        let mut checked = Vec::with_capacity(endpoints.len());
+        for endpoint in endpoints {
+            let tenant_scoped = tenancy
+                .as_ref()
+                .is_some_and(|tenancy| tenancy.is_scoped(&endpoint));
+            // Rows from several databases only add up to the query's result for some queries
+            let row_merge = match databases.endpoint_merge(&endpoint) {
+                Ok(row_merge) => row_merge,
+                Err(e) => {
+                    tracing::error!(
+                        "Refusing to register endpoint {}: {:#}",
+                        endpoint.endpoint_path,
+                        e
+                    );
+                    continue;
+                }
+            };
+            let pools = databases.endpoint_pools(&endpoint);
+            let statement = match prepare_endpoint_query(&pools, &endpoint, tenant_scoped).await {
+                Ok(statement) => statement,
+                Err(e) => {
+                    tracing::error!(
+                        "Refusing to register endpoint {}: failed to prepare its query: {}",
+                        endpoint.endpoint_path,
+                        e
+                    );
+                    if let Some(repairs) = &self.repairs {
+                        repairs.report(&endpoint, &e);
+                    }
+                    continue;
+                }
+            };
+
+            let strict = self.config.strict_decoding(&endpoint.route());
+            if let Err(e) = check_response_fields(&endpoint, &statement) {
+                if strict {
+                    tracing::error!(
+                        "Refusing to register endpoint {}: {:#}",
+                        endpoint.endpoint_path,
+                        e
+                    );
+                    continue;
+                }
+                tracing::warn!("Endpoint {}: {:#}", endpoint.endpoint_path, e);
+            }
+            if strict {
+                strict_decoding.insert(endpoint.route());
+            }
+            if let Some(row_merge) = row_merge {
+                row_merges.insert(endpoint.route(), row_merge);
+            }
+            checked.push(endpoint);
         }
+        let endpoints = checked;
+
+        let schema = SchemaState::load(std::path::Path::new(SCHEMA_STATE_FILE))
+            .context(format!("Failed to load {}", SCHEMA_STATE_FILE))?;
+
+        self.watermarks.track(
+            TableWatermarks::tables_for(&endpoints)
+                .into_iter()
+                .map(|table| {
+                    let pools = databases.table_pools(&table);
+                    (table, pools)
+                })
+                .collect(),
+        );
+
+        let redactions = ResponseRedactions::new(&self.config.column_policies, &endpoints);
+
+        let export_endpoints: HashSet<String> = self
+            .config
+            .endpoints
+            .iter()
+            .filter(|endpoint| endpoint.export)
+            .map(|endpoint| endpoint.route())
+            .collect();
+
+        // Tenant-scoped queries differ per caller, so only shared ones are cached
+        let latest_rows = self.config.server.latest_rows.map(|capacity| {
+            Arc::new(LatestRows::new(
+                capacity,
+                endpoints.iter().filter(|endpoint| {
+                    endpoint.stages.is_empty()
+                        && !export_endpoints.contains(&endpoint.route())
+                        && !tenancy
+                            .as_ref()
+                            .is_some_and(|tenancy| tenancy.is_scoped(endpoint))
+                }),
+            ))
+        });
+
+        // Search is optional, a broken index shouldn't keep endpoints from being served
+        let search_index = SearchIndex::load(std::path::Path::new(search::EMBEDDINGS_FILE))
+            .unwrap_or_else(|e| {
+                tracing::warn!("Search is disabled: {:#}", e);
+                SearchIndex::default()
+            });
+
+        let endpoint_count = endpoints.len();
+        let state = AppState {
+            databases,
+            endpoints: Arc::new(endpoints),
+            api_keys: Arc::clone(&self.api_keys),
+            tenancy,
+            watermarks: self.watermarks.clone(),
+            schema: Arc::new(schema),
+            envelopes: Arc::new(ResponseEnvelopes::from_config(&self.config)),
+            strict_decoding: Arc::new(strict_decoding),
+            row_merges: Arc::new(row_merges),
+            concurrency_limits: Arc::new(concurrency_limits(&self.config)),
+            exports: self.exports.clone(),
+            export_endpoints: Arc::new(export_endpoints),
+            redactions: Arc::new(redactions),
+            search: Arc::new(Search::new(search_index, Arc::clone(&self.ai_client))),
+            repairs: self.repairs.clone(),
+            usage: self.usage.clone(),
+            slow_queries: self.slow_queries.clone(),
+            latest_rows,
+            privacy: Arc::new(Privacy::from_config(&self.config.server.privacy)),
+        };
+
+        let router = build_router(state).await?;
+        self.router.store(Arc::new(router));
+
+        Ok(endpoint_count)
     }
 
-    // Create shared state
-    let state = AppState {
-        db_pool,
-        endpoints: Arc::new(endpoints),
-    };
+    /// Reload endpoints whenever the process receives SIGHUP
+    fn spawn_signal_handler(&self) {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
+
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(hangup) => hangup,
+                Err(e) => {
+                    tracing::warn!("Failed to install SIGHUP handler: {}", e);
+                    return;
+                }
+            };
 
-    // Build router
-    let app = build_router(state).await?;
+            let reloader = self.clone();
+            tokio::spawn(async move {
+                while hangup.recv().await.is_some() {
+                    tracing::info!("Received SIGHUP, reloading endpoints");
+                    if let Err(e) = reloader.reload().await {
+                        tracing::error!("Failed to reload endpoints: {:#}", e);
+                    }
+                }
+            });
+        }
+    }
+}
 
-    // Start server
-    let listener = tokio::net::TcpListener::bind(format!("{}:{}", address, port))
+/// Router that forwards every request to the current router in `router`
+fn reloadable_app(router: Arc<ArcSwap<Router>>) -> Router {
+    Router::new().fallback_service(service_fn(move |request: Request| {
+        let router = router.load_full();
+        async move { Router::clone(&router).oneshot(request).await }
+    }))
+}
+
+/// Reload endpoint IRs and the OpenAPI spec without restarting the server
+async fn handle_reload(
+    reloader: RouterReloader,
+    headers: HeaderMap,
+) -> Result<Json<JsonValue>, ApiError> {
+    let api_key = reloader.api_keys.authenticate_admin(&headers)?;
+    tracing::info!("Reload requested by API key '{}'", api_key.display_name());
+
+    let endpoints = reloader
+        .reload()
         .await
-        .context("Failed to bind to address")?;
+        .map_err(|e| ApiError::Internal(format!("Failed to reload endpoints: {:#}", e)))?;
 
-    tracing::info!("API server listening on http://{}:{}", address, port);
+    Ok(Json(json!({
+        "status": "reloaded",
+        "endpoints": endpoints
+    })))
+}
+
+/// Body of `/api/_admin/pause` and `/api/_admin/resume`
+#[derive(Debug, Deserialize)]
+struct PauseRequest {
+    /// Chain name, or spec as `<contract>/<spec>`
+    target: String,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Paused chains and specs, by target
+async fn handle_list_pauses(
+    reloader: RouterReloader,
+    headers: HeaderMap,
+) -> Result<Json<JsonValue>, ApiError> {
+    reloader.api_keys.authenticate_admin(&headers)?;
+    let pauses = load_pauses(&reloader).await?;
+    let mut pauses: Vec<Pause> = pauses.into_values().collect();
+    pauses.sort_by(|a, b| a.target.cmp(&b.target));
+    Ok(Json(json!({ "pauses": pauses })))
+}
+
+/// Pause indexing of a chain or spec, in the running indexer too
+async fn handle_pause(
+    reloader: RouterReloader,
+    headers: HeaderMap,
+    request: PauseRequest,
+) -> Result<Json<JsonValue>, ApiError> {
+    let api_key = reloader.api_keys.authenticate_admin(&headers)?;
+    pause::target_chain(&reloader.config, &request.target)
+        .map_err(|e| ApiError::BadRequest(format!("{:#}", e)))?;
+    load_pauses(&reloader).await?;
+    pause::pause(&reloader.pauses, &request.target, request.reason.as_deref())
+        .await
+        .map_err(|e| ApiError::Internal(format!("{:#}", e)))?;
     tracing::info!(
-        "Swagger UI available at http://{}:{}/swagger-ui",
-        address,
-        port
+        "{} paused by API key '{}'",
+        request.target,
+        api_key.display_name()
     );
 
-    axum::serve(listener, app).await.context("Server error")?;
+    Ok(Json(json!({
+        "status": "paused",
+        "target": request.target
+    })))
+}
 
-    Ok(())
+/// Resume indexing of a paused chain or spec
+async fn handle_resume(
+    reloader: RouterReloader,
+    headers: HeaderMap,
+    request: PauseRequest,
+) -> Result<Json<JsonValue>, ApiError> {
+    let api_key = reloader.api_keys.authenticate_admin(&headers)?;
+    load_pauses(&reloader).await?;
+    let resumed = pause::resume(&reloader.pauses, &request.target)
+        .await
+        .map_err(|e| ApiError::Internal(format!("{:#}", e)))?;
+    if !resumed {
+        return Err(ApiError::NotFound(format!(
+            "{} is not paused",
+            request.target
+        )));
+    }
+    tracing::info!(
+        "{} resumed by API key '{}'",
+        request.target,
+        api_key.display_name()
+    );
+
+    Ok(Json(json!({
+        "status": "resumed",
+        "target": request.target
+    })))
+}
+
+/// Current pauses, creating the pauses table if no indexer has yet
+async fn load_pauses(reloader: &RouterReloader) -> Result<HashMap<String, Pause>, ApiError> {
+    let pauses = async {
+        system::ensure_system_tables(&reloader.pauses).await?;
+        pause::load(&reloader.pauses).await
+    };
+    pauses
+        .await
+        .map_err(|e| ApiError::Internal(format!("{:#}", e)))
 }
 
 /// Build the Axum router with dynamic routes
@@ -132,60 +788,469 @@ async fn build_router(state: AppState) -> Result<Router> {
     let mut router = Router::new();
 
     // Add root endpoint
-    router = router.route("/", get(root_handler));
+    router = router.route("/", read_only_route(get(root_handler)));
 
     // Add health check endpoint
-    router = router.route("/health", get(health_check));
+    router = router.route("/health", read_only_route(get(health_check)));
+
+    // Add system endpoints
+    let tables_state = state.clone();
+    router = router.route(
+        "/api/_tables",
+        read_only_route(get(move |headers: HeaderMap| {
+            let state = tables_state.clone();
+            async move { handle_list_tables(state, headers).await }
+        })),
+    );
+    let table_state = state.clone();
+    router = router.route(
+        "/api/_tables/{name}",
+        read_only_route(get(
+            move |headers: HeaderMap, AxumPath(name): AxumPath<String>| {
+                let state = table_state.clone();
+                async move { handle_get_table(state, headers, name).await }
+            },
+        )),
+    );
 
-    // Add dynamic endpoints from IR
-    for endpoint_ir in state.endpoints.iter() {
-        let endpoint_ir_clone = endpoint_ir.clone();
-        let handler_state = state.clone();
-
-        // Create handler for this endpoint
-        let handler = move |path: AxumPath<HashMap<String, String>>,
-                            query: Query<GenericQueryParams>| {
-            let endpoint_ir = endpoint_ir_clone.clone();
-            let state = handler_state.clone();
-            async move { handle_dynamic_endpoint(state, endpoint_ir, path, query).await }
-        };
+    let sse_state = state.clone();
+    router = router.route(
+        "/sse/{table}",
+        read_only_route(get(
+            move |headers: HeaderMap, AxumPath(table): AxumPath<String>| {
+                let state = sse_state.clone();
+                async move { handle_sse(state, headers, table).await }
+            },
+        )),
+    );
 
-        // Register route based on method
-        match endpoint_ir.method.to_uppercase().as_str() {
-            "GET" => {
-                router = router.route(&endpoint_ir.endpoint_path, get(handler));
-                tracing::debug!("Registered GET {}", endpoint_ir.endpoint_path);
-            }
+    let contract_stats_state = state.clone();
+    router = router.route(
+        "/api/_contracts/{name}/stats",
+        read_only_route(get(
+            move |headers: HeaderMap,
+                  AxumPath(name): AxumPath<String>,
+                  Query(query): Query<ContractStatsParams>| {
+                let state = contract_stats_state.clone();
+                async move { handle_contract_stats(state, headers, name, query).await }
+            },
+        )),
+    );
+
+    let search_state = state.clone();
+    router = router.route(
+        "/api/_search",
+        read_only_route(get(
+            move |headers: HeaderMap, Query(query): Query<SearchParams>| {
+                let state = search_state.clone();
+                async move { handle_search(state, headers, query).await }
+            },
+        )),
+    );
+
+    let usage_state = state.clone();
+    router = router.route(
+        "/api/_usage",
+        read_only_route(get(
+            move |headers: HeaderMap, Query(query): Query<UsageParams>| {
+                let state = usage_state.clone();
+                async move { handle_usage(state, headers, query).await }
+            },
+        )),
+    );
+
+    let slow_queries_state = state.clone();
+    router = router.route(
+        "/api/_slow-queries",
+        read_only_route(get(move |headers: HeaderMap| {
+            let state = slow_queries_state.clone();
+            async move { handle_slow_queries(state, headers).await }
+        })),
+    );
+
+    let export_state = state.clone();
+    router = router.route(
+        "/api/_exports/{id}",
+        read_only_route(get(
+            move |headers: HeaderMap, AxumPath(id): AxumPath<String>| {
+                let state = export_state.clone();
+                async move { handle_get_export(state, headers, id).await }
+            },
+        )),
+    );
+    let download_state = state.clone();
+    router = router.route(
+        "/api/_exports/{id}/download",
+        read_only_route(get(
+            move |headers: HeaderMap, AxumPath(id): AxumPath<String>| {
+                let state = download_state.clone();
+                async move { handle_download_export(state, headers, id).await }
+            },
+        )),
+    );
+
+    // Add dynamic endpoints from IR, with every method of a path on one route
+    let mut routes: BTreeMap<String, Vec<(String, MethodRouter)>> = BTreeMap::new();
+    for endpoint_ir in state.endpoints.iter() {
+        let get_state = state.clone();
+        let get_ir = endpoint_ir.clone();
+        let post_state = state.clone();
+        let post_ir = endpoint_ir.clone();
+
+        let method = endpoint_ir.method.to_uppercase();
+        let route = match method.as_str() {
+            "GET" => get(
+                move |headers: HeaderMap,
+                      peer: Option<Extension<ConnectInfo<SocketAddr>>>,
+                      path: AxumPath<HashMap<String, String>>,
+                      query: Query<GenericQueryParams>| {
+                    serve_endpoint(
+                        get_state.clone(),
+                        get_ir.clone(),
+                        headers,
+                        peer.map(|peer| peer.0.0),
+                        path,
+                        query,
+                        Bytes::new(),
+                    )
+                },
+            ),
+            // Parameters can also be sent as a JSON object in the body
+            "POST" => post(
+                move |headers: HeaderMap,
+                      peer: Option<Extension<ConnectInfo<SocketAddr>>>,
+                      path: AxumPath<HashMap<String, String>>,
+                      query: Query<GenericQueryParams>,
+                      body: Bytes| {
+                    serve_endpoint(
+                        post_state.clone(),
+                        post_ir.clone(),
+                        headers,
+                        peer.map(|peer| peer.0.0),
+                        path,
+                        query,
+                        body,
+                    )
+                },
+            ),
             _ => {
                 tracing::warn!(
                     "Unsupported method {} for endpoint {}",
                     endpoint_ir.method,
                     endpoint_ir.endpoint_path
                 );
+                continue;
             }
+        };
+        tracing::debug!("Registered {} {}", method, endpoint_ir.endpoint_path);
+        routes
+            .entry(endpoint_ir.endpoint_path.clone())
+            .or_default()
+            .push((method, route));
+    }
+    for (path, method_routes) in routes {
+        let has = |method: &str| method_routes.iter().any(|(m, _)| m == method);
+        let mut allow = Vec::new();
+        if has("GET") {
+            allow.extend(["GET", "HEAD"]);
+        }
+        if has("POST") {
+            allow.push("POST");
         }
+        allow.push("OPTIONS");
+        let allow = HeaderValue::from_str(&allow.join(", "))
+            .unwrap_or_else(|_| HeaderValue::from_static(READ_ONLY_METHODS));
+
+        let route = method_routes
+            .into_iter()
+            .map(|(_, route)| route)
+            .fold(MethodRouter::new(), MethodRouter::merge);
+        router = router.route(&path, route_allowing(route, allow));
     }
 
     // Add CORS middleware
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
-        .allow_headers(Any);
+        .allow_headers(Any)
+        .expose_headers([ETAG]);
 
     router = router.layer(cors);
 
     // Generate OpenAPI spec dynamically from endpoint IRs
-    let openapi_spec = generate_openapi_spec(&state.endpoints);
+    let openapi_spec = generate_openapi_spec(
+        &state.endpoints,
+        &state.schema,
+        &state.envelopes,
+        &state.api_keys,
+    );
 
     // Add Swagger UI with dynamic spec
     router =
         router.merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", openapi_spec));
 
-    Ok(router)
+    // Route `/API/Pools/` to `/api/pools` before matching
+    let templates = RouteTemplates::new(
+        state
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.endpoint_path.as_str()),
+    );
+    Ok(
+        Router::new().fallback_service(service_fn(move |mut request: Request| {
+            canonicalize_request_path(&templates, &mut request);
+            Router::clone(&router).oneshot(request)
+        })),
+    )
+}
+
+/// Rewrite the request's path to the spelling its route was registered with
+fn canonicalize_request_path(templates: &RouteTemplates, request: &mut Request) {
+    let path = request.uri().path();
+    let Some(canonical) = templates.canonicalize(path) else {
+        return;
+    };
+    if canonical == path {
+        return;
+    }
+
+    let path_and_query = match request.uri().query() {
+        Some(query) => format!("{}?{}", canonical, query),
+        None => canonical,
+    };
+    let mut parts = request.uri().clone().into_parts();
+    match path_and_query.parse() {
+        Ok(path_and_query) => parts.path_and_query = Some(path_and_query),
+        Err(_) => return,
+    }
+    if let Ok(uri) = axum::http::Uri::from_parts(parts) {
+        *request.uri_mut() = uri;
+    }
+}
+
+/// Endpoint IRs that can be registered, with normalized paths
+///
+/// With `server.tableApi`, the generated table endpoints that don't conflict with
+/// an IR are added after them.
+pub(crate) fn load_endpoints(config: &Config) -> Result<Vec<EndpointIrResult>> {
+    let defaults = &config.endpoint_defaults;
+    // Load all endpoint IRs
+    let mut endpoints = Ir::load_all_ir_endpoints().context("Failed to load endpoint IRs")?;
+
+    // IR files may have been edited by hand, never register SQL that can write
+    endpoints.retain(|endpoint| {
+        match sql_guard::check_read_only_sql(&endpoint.sql_query)
+            .and_then(|()| query_stages::check(endpoint))
+        {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::error!(
+                    "Refusing to register endpoint {}: {:#}",
+                    endpoint.endpoint_path,
+                    e
+                );
+                false
+            }
+        }
+    });
+
+    endpoints.retain(|endpoint| {
+        match endpoint
+            .response_schema
+            .fields
+            .iter()
+            .try_for_each(transform::check)
+        {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::error!(
+                    "Refusing to register endpoint {}: {:#}",
+                    endpoint.endpoint_path,
+                    e
+                );
+                false
+            }
+        }
+    });
+
+    // Generated SQL sometimes forgets its LIMIT, never return a whole table
+    let order_by = defaults.order_by().transpose()?;
+    endpoints.retain_mut(|endpoint| match shape_rows(endpoint, order_by) {
+        Ok(()) => {
+            apply_defaults(endpoint, defaults);
+            true
+        }
+        Err(e) => {
+            tracing::error!(
+                "Refusing to register endpoint {}: {:#}",
+                endpoint.endpoint_path,
+                e
+            );
+            false
+        }
+    });
+
+    // Hand-edited IRs may not be normalized, and axum panics on paths it can't route
+    let mut routes: Vec<(String, String)> = Vec::new();
+    endpoints.retain_mut(|endpoint| {
+        let normalized = endpoint_path::normalize(&endpoint.endpoint_path)
+            .and_then(|path| Ok((path, endpoint_path::normalize_method(&endpoint.method)?)));
+        match normalized {
+            Ok((path, method)) => {
+                endpoint.endpoint_path = path;
+                endpoint.method = method;
+            }
+            Err(e) => {
+                tracing::error!("Refusing to register endpoint: {:#}", e);
+                return false;
+            }
+        }
+        if routes.iter().any(|(path, method)| {
+            endpoint_path::conflicts(path, method, &endpoint.endpoint_path, &endpoint.method)
+        }) {
+            tracing::error!(
+                "Refusing to register endpoint {}: it conflicts with another endpoint",
+                endpoint.route()
+            );
+            return false;
+        }
+        routes.push((endpoint.endpoint_path.clone(), endpoint.method.clone()));
+        true
+    });
+
+    if config.server.table_api {
+        let schema = SchemaState::load(std::path::Path::new(SCHEMA_STATE_FILE))
+            .context(format!("Failed to load {}", SCHEMA_STATE_FILE))?;
+        for mut endpoint in table_api::endpoints(&schema) {
+            if routes.iter().any(|(path, method)| {
+                endpoint_path::conflicts(path, method, &endpoint.endpoint_path, &endpoint.method)
+            }) {
+                tracing::debug!(
+                    "Table endpoint {} is replaced by an endpoint IR",
+                    endpoint.route()
+                );
+                continue;
+            }
+            apply_defaults(&mut endpoint, defaults);
+            endpoints.push(endpoint);
+        }
+    }
+
+    if endpoints.is_empty() {
+        tracing::warn!("No endpoint IRs found. Did you run 'gen-endpoint' first?");
+    } else {
+        tracing::info!("Loaded {} endpoint(s)", endpoints.len());
+        for endpoint in &endpoints {
+            tracing::info!("  - {} {}", endpoint.method, endpoint.endpoint_path);
+        }
+    }
+
+    Ok(endpoints)
+}
+
+/// Most rows a request can ask for with the `limit` parameter
+const MAX_LIMIT: u32 = 200;
+
+/// Wrap an endpoint query without a top-level LIMIT in one bound to its `limit`
+/// parameter, adding the parameter with a default of [`MAX_LIMIT`] if it has none
+///
+/// Queries without a top-level ORDER BY are also sorted by `order_by`, the
+/// column and direction of `endpointDefaults.order`, when they return that column.
+fn shape_rows(endpoint: &mut EndpointIrResult, order_by: Option<(&str, &str)>) -> Result<()> {
+    let order_by = order_by.filter(|(column, _)| {
+        endpoint
+            .response_schema
+            .fields
+            .iter()
+            .any(|field| field.name == *column)
+    });
+    let order_by = match order_by {
+        Some(order_by) if !sql_guard::has_top_level_order_by(&endpoint.sql_query)? => {
+            Some(order_by)
+        }
+        _ => None,
+    };
+    if sql_guard::has_top_level_limit(&endpoint.sql_query)? {
+        if order_by.is_some() {
+            endpoint.sql_query = sql_guard::wrap(&endpoint.sql_query, order_by, None);
+        }
+        return Ok(());
+    }
+
+    let position = match endpoint
+        .query_params
+        .iter()
+        .position(|param| param.name == "limit")
+    {
+        Some(position) => position,
+        // A new parameter would shift the numbers of the stage outputs after it
+        None if !endpoint.stages.is_empty() => {
+            anyhow::bail!("Query has stages but no LIMIT or limit parameter");
+        }
+        None => {
+            endpoint.query_params.push(QueryParam {
+                name: "limit".to_string(),
+                param_type: "u32".to_string(),
+                default: Some(json!(MAX_LIMIT)),
+                description: "Maximum number of rows to return".to_string(),
+                allowed_values: vec![],
+            });
+            endpoint.query_params.len() - 1
+        }
+    };
+    let param_index = endpoint.path_params.len() + position + 1;
+    tracing::warn!(
+        "Endpoint {} has no LIMIT, limiting its rows with ${}",
+        endpoint.route(),
+        param_index
+    );
+    endpoint.sql_query = sql_guard::wrap(&endpoint.sql_query, order_by, Some(param_index));
+    Ok(())
+}
+
+/// Merge `[endpointDefaults]` into an endpoint IR
+///
+/// The default limit replaces the generated one; the cache TTL and scopes only
+/// apply to endpoints that don't set their own.
+fn apply_defaults(endpoint: &mut EndpointIrResult, defaults: &EndpointDefaultsConfig) {
+    if let Some(limit) = defaults.limit
+        && let Some(param) = endpoint
+            .query_params
+            .iter_mut()
+            .find(|param| param.name == "limit")
+    {
+        param.default = Some(json!(limit.min(MAX_LIMIT)));
+    }
+    if endpoint.cache_ttl.is_none() {
+        endpoint.cache_ttl = defaults.cache_ttl;
+    }
+    if endpoint.scopes.is_empty() {
+        endpoint.scopes = defaults.scopes.clone();
+    }
+}
+
+/// OpenAPI document of the endpoint IRs, as served at `/api-docs/openapi.json`
+///
+/// Endpoints whose queries fail to prepare against the database are left out of
+/// the served document, but can't be detected without one.
+pub fn openapi_json(config: &Config) -> Result<String> {
+    let endpoints = load_endpoints(config)?;
+    let schema = SchemaState::load(std::path::Path::new(SCHEMA_STATE_FILE))
+        .context(format!("Failed to load {}", SCHEMA_STATE_FILE))?;
+    let envelopes = ResponseEnvelopes::from_config(config);
+    let api_keys = ApiKeyStore::from_config(&config.server);
+    generate_openapi_spec(&endpoints, &schema, &envelopes, &api_keys)
+        .to_pretty_json()
+        .context("Failed to serialize OpenAPI document")
 }
 
 /// Generate OpenAPI specification from endpoint IRs
-fn generate_openapi_spec(endpoints: &[EndpointIrResult]) -> utoipa::openapi::OpenApi {
+fn generate_openapi_spec(
+    endpoints: &[EndpointIrResult],
+    schema: &SchemaState,
+    envelopes: &ResponseEnvelopes,
+    api_keys: &ApiKeyStore,
+) -> utoipa::openapi::OpenApi {
     let mut openapi = OpenApiBuilder::new()
         .info(
             InfoBuilder::new()
@@ -202,27 +1267,169 @@ fn generate_openapi_spec(endpoints: &[EndpointIrResult]) -> utoipa::openapi::Ope
     let mut paths = PathsBuilder::new();
 
     for endpoint_ir in endpoints {
-        let path_item = generate_path_item(endpoint_ir);
+        let path_item = generate_path_item(
+            endpoint_ir,
+            &endpoint_tags(endpoint_ir, schema),
+            envelopes.for_endpoint(endpoint_ir),
+        );
         paths = paths.path(&endpoint_ir.endpoint_path, path_item);
     }
 
+    paths = paths
+        .path(
+            "/api/_tables",
+            generate_system_path_item("Statistics for all smorty-managed tables", None),
+        )
+        .path(
+            "/api/_tables/{name}",
+            generate_system_path_item(
+                "Statistics for a single table, with an exact row count",
+                Some("name"),
+            ),
+        )
+        .path(
+            "/api/_contracts/{name}/stats",
+            generate_system_path_item(
+                "Events indexed per day for each spec of a contract, over the last `days` days (default 30)",
+                Some("name"),
+            ),
+        );
+
     openapi.paths = paths.build();
 
+    if api_keys.is_enabled() {
+        add_security(&mut openapi, endpoints);
+    }
+
     openapi
 }
 
+/// Name of the OpenAPI security scheme for the `X-API-Key` header
+const API_KEY_SCHEME: &str = "apiKey";
+
+/// Name of the OpenAPI security scheme for `Authorization: Bearer <key>`
+const BEARER_SCHEME: &str = "bearer";
+
+/// Built-in routes reading every tenant's rows, which need an admin key
+const ADMIN_SYSTEM_PATHS: &[&str] = &[
+    "/api/_tables",
+    "/api/_tables/{name}",
+    "/api/_contracts/{name}/stats",
+];
+
+/// Declare the API key schemes and mark every operation as requiring a key,
+/// with the scopes its endpoint needs
+fn add_security(openapi: &mut utoipa::openapi::OpenApi, endpoints: &[EndpointIrResult]) {
+    use utoipa::openapi::security::{
+        ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme,
+    };
+
+    let components = openapi.components.take().unwrap_or_default();
+    openapi.components = Some(
+        ComponentsBuilder::from(components)
+            .security_scheme(
+                API_KEY_SCHEME,
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                    "X-API-Key",
+                    "API key from [[server.apiKeys]]",
+                ))),
+            )
+            .security_scheme(
+                BEARER_SCHEME,
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .description(Some("API key from [[server.apiKeys]]"))
+                        .build(),
+                ),
+            )
+            .build(),
+    );
+
+    let admin_scopes = [ADMIN_SCOPE.to_string()];
+    for (path, item) in openapi.paths.paths.iter_mut() {
+        let operations = [("GET", item.get.as_mut()), ("POST", item.post.as_mut())];
+        for (method, operation) in operations {
+            let Some(operation) = operation else {
+                continue;
+            };
+            let scopes = endpoints
+                .iter()
+                .find(|endpoint| {
+                    endpoint.endpoint_path == *path && endpoint.method.eq_ignore_ascii_case(method)
+                })
+                .map(|endpoint| endpoint.scopes.as_slice())
+                .unwrap_or(if ADMIN_SYSTEM_PATHS.contains(&path.as_str()) {
+                    &admin_scopes
+                } else {
+                    &[]
+                });
+            operation.security = Some(vec![
+                SecurityRequirement::new(API_KEY_SCHEME, scopes),
+                SecurityRequirement::new(BEARER_SCHEME, scopes),
+            ]);
+            operation.responses.responses.insert(
+                "401".to_string(),
+                ResponseBuilder::new()
+                    .description("Missing or invalid API key")
+                    .build()
+                    .into(),
+            );
+            if !scopes.is_empty() {
+                operation.responses.responses.insert(
+                    "403".to_string(),
+                    ResponseBuilder::new()
+                        .description(format!(
+                            "API key lacks a required scope: {}",
+                            scopes.join(", ")
+                        ))
+                        .build()
+                        .into(),
+                );
+            }
+        }
+    }
+}
+
+/// OpenAPI tags for an endpoint
+///
+/// Uses the tags from the endpoint config, otherwise the contracts owning the tables
+/// the query reads, falling back to the table names.
+fn endpoint_tags(endpoint_ir: &EndpointIrResult, schema: &SchemaState) -> Vec<String> {
+    if !endpoint_ir.tags.is_empty() {
+        return endpoint_ir.tags.clone();
+    }
+
+    let tags: BTreeSet<String> = endpoint_ir
+        .tables_referenced
+        .iter()
+        .map(|table| match schema.get_table(table) {
+            Some(table_state) => table_state.source.contract_name.clone(),
+            None => table.clone(),
+        })
+        .collect();
+
+    tags.into_iter().collect()
+}
+
 /// Generate OpenAPI PathItem for an endpoint IR
-fn generate_path_item(endpoint_ir: &EndpointIrResult) -> PathItem {
+fn generate_path_item(
+    endpoint_ir: &EndpointIrResult,
+    tags: &[String],
+    envelope: ResponseEnvelope,
+) -> PathItem {
     let mut operation = OperationBuilder::new()
         .summary(Some(endpoint_ir.description.clone()))
+        .tags(Some(tags.to_vec()))
         .response(
             "200",
             ResponseBuilder::new()
                 .description("Successful response")
                 .content(
-                    "application/json",
+                    envelope.content_type(),
                     ContentBuilder::new()
-                        .schema(Some(generate_response_schema(endpoint_ir)))
+                        .schema(Some(generate_response_schema(endpoint_ir, envelope)))
+                        .example(Some(generate_response_example(endpoint_ir, envelope)))
                         .build(),
                 )
                 .build(),
@@ -253,6 +1460,36 @@ fn generate_path_item(endpoint_ir: &EndpointIrResult) -> PathItem {
         );
     }
 
+    // POST endpoints take their other parameters as a JSON object in the body
+    if endpoint_ir.method.eq_ignore_ascii_case("POST") {
+        let mut body_schema = ObjectBuilder::new();
+        let mut example = serde_json::Map::new();
+        for query_param in &endpoint_ir.query_params {
+            let RefOr::T(Schema::Object(mut schema)) = generate_query_param_schema(query_param)
+            else {
+                unreachable!("generate_param_schema always returns an object schema");
+            };
+            schema.description = Some(describe_query_param(query_param));
+            body_schema = body_schema.property(&query_param.name, schema);
+            if query_param.default.is_none() {
+                body_schema = body_schema.required(&query_param.name);
+            }
+            example.insert(query_param.name.clone(), query_param_example(query_param));
+        }
+        operation = operation.request_body(Some(
+            request_body::RequestBodyBuilder::new()
+                .content(
+                    "application/json",
+                    ContentBuilder::new()
+                        .schema(Some(body_schema))
+                        .example(Some(JsonValue::Object(example)))
+                        .build(),
+                )
+                .build(),
+        ));
+        return PathItem::new(HttpMethod::Post, operation.build());
+    }
+
     // Add query parameters
     for query_param in &endpoint_ir.query_params {
         let is_required = query_param.default.is_none();
@@ -260,12 +1497,14 @@ fn generate_path_item(endpoint_ir: &EndpointIrResult) -> PathItem {
             ParameterBuilder::new()
                 .name(&query_param.name)
                 .parameter_in(ParameterIn::Query)
+                .description(Some(describe_query_param(query_param)))
                 .required(if is_required {
                     Required::True
                 } else {
                     Required::False
                 })
-                .schema(Some(generate_param_schema(&query_param.param_type)))
+                .schema(Some(generate_query_param_schema(query_param)))
+                .example(Some(query_param_example(query_param)))
                 .build(),
         );
     }
@@ -283,8 +1522,50 @@ fn generate_path_item(endpoint_ir: &EndpointIrResult) -> PathItem {
     PathItem::new(http_method, operation)
 }
 
+/// Generate OpenAPI PathItem for a built-in system endpoint
+fn generate_system_path_item(summary: &str, path_param: Option<&str>) -> PathItem {
+    let mut operation = OperationBuilder::new()
+        .summary(Some(summary))
+        .tag("system")
+        .response(
+            "200",
+            ResponseBuilder::new()
+                .description("Successful response")
+                .build(),
+        )
+        .response(
+            "500",
+            ResponseBuilder::new()
+                .description("Internal server error")
+                .build(),
+        );
+
+    if let Some(name) = path_param {
+        operation = operation
+            .parameter(
+                ParameterBuilder::new()
+                    .name(name)
+                    .parameter_in(ParameterIn::Path)
+                    .required(Required::True)
+                    .schema(Some(generate_param_schema("String")))
+                    .build(),
+            )
+            .response(
+                "404",
+                ResponseBuilder::new()
+                    .description("Table not found")
+                    .build(),
+            );
+    }
+
+    PathItem::new(HttpMethod::Get, operation.build())
+}
+
 /// Generate OpenAPI schema for response
-fn generate_response_schema(endpoint_ir: &EndpointIrResult) -> RefOr<Schema> {
+fn generate_response_schema(
+    endpoint_ir: &EndpointIrResult,
+    envelope: ResponseEnvelope,
+) -> RefOr<Schema> {
     use utoipa::openapi::*;
 
     // Create response object schema
@@ -292,30 +1573,150 @@ fn generate_response_schema(endpoint_ir: &EndpointIrResult) -> RefOr<Schema> {
     for field in &endpoint_ir.response_schema.fields {
         data_schema = data_schema.property(
             &field.name,
-            generate_field_schema(&field.field_type, &field.description),
+            generate_field_schema(transform::output_type(field), &field.description),
         );
     }
 
-    // Wrapper object with data array and count
-    let wrapper = ObjectBuilder::new()
-        .property(
-            "data",
-            ArrayBuilder::new().items(data_schema.build()).build(),
-        )
-        .property(
-            "count",
-            ObjectBuilder::new()
-                .schema_type(Type::Integer)
-                .description(Some("Number of items returned"))
-                .build(),
-        )
-        .build();
+    RefOr::T(envelope.wrap_schema(Schema::Object(data_schema.build())))
+}
+
+/// Default value of a query parameter, `None` when it has none or defaults to NULL
+fn query_param_default(query_param: &QueryParam) -> Option<&JsonValue> {
+    query_param
+        .default
+        .as_ref()
+        .filter(|d| !d.is_null() && d.as_str() != Some("null"))
+}
+
+/// Description of a query parameter for the API docs, noting its default
+pub(crate) fn describe_query_param(query_param: &QueryParam) -> String {
+    let mut description = query_param.description.trim().to_string();
+    if description.is_empty() {
+        description = format!("Filter by {}", query_param.name);
+    }
+
+    match (&query_param.default, query_param_default(query_param)) {
+        (None, _) => {}
+        (Some(_), None) => description.push_str(" (optional)"),
+        (Some(_), Some(default)) => description.push_str(&format!(" (default: {})", default)),
+    }
+
+    if query_param.name == "limit" {
+        description.push_str(". Capped at 200");
+    }
+
+    description
+}
+
+/// OpenAPI schema for a query parameter, with its default and allowed values
+pub(crate) fn generate_query_param_schema(query_param: &QueryParam) -> RefOr<Schema> {
+    let RefOr::T(Schema::Object(mut schema)) = generate_param_schema(&query_param.param_type)
+    else {
+        unreachable!("generate_param_schema always returns an object schema");
+    };
+
+    schema.default = query_param_default(query_param).cloned();
+    if !query_param.allowed_values.is_empty() {
+        schema.enum_values = Some(query_param.allowed_values.clone());
+    }
+    if query_param.name == "limit" {
+        schema.maximum = Some(200.0.into());
+    }
+
+    RefOr::T(Schema::Object(schema))
+}
 
-    RefOr::T(Schema::Object(wrapper))
+/// Example value for a query parameter
+fn query_param_example(query_param: &QueryParam) -> JsonValue {
+    if let Some(default) = query_param_default(query_param) {
+        default.clone()
+    } else if let Some(first) = query_param.allowed_values.first() {
+        first.clone()
+    } else {
+        example_value(&query_param.param_type, &query_param.name)
+    }
 }
 
+/// Synthesized example response, following the response envelope
+fn generate_response_example(
+    endpoint_ir: &EndpointIrResult,
+    envelope: ResponseEnvelope,
+) -> JsonValue {
+    let item: serde_json::Map<String, JsonValue> = endpoint_ir
+        .response_schema
+        .fields
+        .iter()
+        .map(|field| {
+            (
+                field.name.clone(),
+                example_value(transform::output_type(field), &field.name),
+            )
+        })
+        .collect();
+
+    envelope.wrap(vec![JsonValue::Object(item)], endpoint_ir)
+}
+
+/// Plausible example value for a type, guessed from the field name
+fn example_value(field_type: &str, name: &str) -> JsonValue {
+    let base_type = field_type
+        .strip_prefix("Option<")
+        .and_then(|s| s.strip_suffix('>'))
+        .unwrap_or(field_type);
+    let name = name.to_lowercase();
+
+    match base_type {
+        "Duration" => json!("7d"),
+        "i64" | "i32" | "u32" | "u64" => {
+            if name.contains("timestamp") || name.ends_with("time") {
+                json!(1_700_000_000)
+            } else if name.contains("block") {
+                json!(19_000_000)
+            } else if name == "limit" {
+                json!(50)
+            } else {
+                json!(1)
+            }
+        }
+        "f64" | "f32" => json!(1.5),
+        "Decimal" => json!("1234.5678"),
+        "bool" => json!(true),
+        t if is_json_field_type(t) => json!([]),
+        _ => {
+            if name.contains("hash") {
+                json!(format!("0x{}", "ab".repeat(32)))
+            } else if name.contains("address")
+                || [
+                    "pool",
+                    "owner",
+                    "sender",
+                    "recipient",
+                    "from",
+                    "to",
+                    "token",
+                    "user",
+                ]
+                .iter()
+                .any(|n| name == *n || name.starts_with(&format!("{}_", n)))
+            {
+                json!("0x1f9840a85d5af5bf1d1762f925bdaddc4201f984")
+            } else if ["amount", "value", "balance", "price", "fee", "liquidity"]
+                .iter()
+                .any(|n| name.contains(n))
+            {
+                json!("1000000000000000000")
+            } else {
+                json!("string")
+            }
+        }
+    }
+}
+
+/// Values `Duration` parameters accept, a whole number and a unit
+const DURATION_PATTERN: &str = "^[0-9]+[smhdw]$";
+
 /// Generate OpenAPI schema for a parameter type
-fn generate_param_schema(param_type: &str) -> RefOr<Schema> {
+pub(crate) fn generate_param_schema(param_type: &str) -> RefOr<Schema> {
     use utoipa::openapi::*;
 
     let base_type = param_type
@@ -335,6 +1736,13 @@ fn generate_param_schema(param_type: &str) -> RefOr<Schema> {
             .build(),
         "String" => ObjectBuilder::new().schema_type(Type::String).build(),
         "bool" => ObjectBuilder::new().schema_type(Type::Boolean).build(),
+        "Duration" => ObjectBuilder::new()
+            .schema_type(Type::String)
+            .pattern(Some(DURATION_PATTERN))
+            .description(Some(
+                "Time window back from now, e.g. 30m, 12h or 7d (units: s, m, h, d, w)",
+            ))
+            .build(),
         _ => ObjectBuilder::new().schema_type(Type::String).build(),
     };
 
@@ -361,9 +1769,19 @@ fn generate_field_schema(field_type: &str, description: &str) -> RefOr<Schema> {
         "String" => ObjectBuilder::new()
             .schema_type(Type::String)
             .description(Some(description)),
+        "f64" | "f32" => ObjectBuilder::new()
+            .schema_type(Type::Number)
+            .format(Some(SchemaFormat::KnownFormat(KnownFormat::Double)))
+            .description(Some(description)),
+        "Decimal" => ObjectBuilder::new()
+            .schema_type(Type::String)
+            .format(Some(SchemaFormat::Custom("decimal".to_string())))
+            .description(Some(description)),
         "bool" => ObjectBuilder::new()
             .schema_type(Type::Boolean)
             .description(Some(description)),
+        // Any JSON value
+        t if is_json_field_type(t) => ObjectBuilder::new().description(Some(description)),
         _ => ObjectBuilder::new()
             .schema_type(Type::String)
             .description(Some(description)),
@@ -390,33 +1808,612 @@ async fn health_check() -> impl IntoResponse {
     }))
 }
 
+/// List statistics for every smorty-managed table
+async fn handle_list_tables(
+    state: AppState,
+    headers: HeaderMap,
+) -> Result<Json<JsonValue>, ApiError> {
+    state.api_keys.authenticate_admin(&headers)?;
+
+    let mut tables: Vec<_> = state.schema.tables.values().collect();
+    tables.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut stats = Vec::new();
+    for table in tables {
+        let pools = state.databases.table_pools(&table.name);
+        match TableStats::collect_all(&pools, table, false).await? {
+            Some(table_stats) => stats.push(table_stats),
+            None => tracing::debug!("Table {} has not been migrated yet", table.name),
+        }
+    }
+
+    Ok(Json(json!({
+        "data": stats,
+        "count": stats.len()
+    })))
+}
+
+/// Query parameters of `/api/_contracts/{name}/stats`
+#[derive(Debug, Deserialize)]
+pub struct ContractStatsParams {
+    days: Option<u32>,
+}
+
+/// Default and maximum number of days covered by `/api/_contracts/{name}/stats`
+const DEFAULT_STATS_DAYS: u32 = 30;
+const MAX_STATS_DAYS: u32 = 365;
+
+/// Events per day for each spec of a contract
+async fn handle_contract_stats(
+    state: AppState,
+    headers: HeaderMap,
+    name: String,
+    query: ContractStatsParams,
+) -> Result<Json<JsonValue>, ApiError> {
+    state.api_keys.authenticate_admin(&headers)?;
+
+    let days = query.days.unwrap_or(DEFAULT_STATS_DAYS);
+    if days == 0 || days > MAX_STATS_DAYS {
+        return Err(ApiError::BadRequest(format!(
+            "days must be between 1 and {}",
+            MAX_STATS_DAYS
+        )));
+    }
+
+    let tables: Vec<_> = state
+        .schema
+        .tables
+        .values()
+        .filter(|table| table.source.contract_name == name)
+        .collect();
+    if tables.is_empty() {
+        return Err(ApiError::NotFound(format!("Unknown contract: {}", name)));
+    }
+
+    let pool = state.databases.contract_pool(&name);
+    let stats = ContractStats::collect(&pool, &name, &tables, days).await?;
+    Ok(Json(json!(stats)))
+}
+
+/// Query parameters of `/api/_search`
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    q: Option<String>,
+    limit: Option<usize>,
+}
+
+/// Default and maximum number of `/api/_search` results
+const SEARCH_DEFAULT_LIMIT: usize = 10;
+const SEARCH_MAX_LIMIT: usize = 50;
+
+/// Tables and endpoints most relevant to a free-text query
+async fn handle_search(
+    state: AppState,
+    headers: HeaderMap,
+    params: SearchParams,
+) -> Result<Json<JsonValue>, ApiError> {
+    state.api_keys.authenticate(&headers)?;
+
+    let query = params.q.as_deref().map(str::trim).unwrap_or_default();
+    if query.is_empty() {
+        return Err(ApiError::BadRequest(
+            "Missing query parameter 'q'".to_string(),
+        ));
+    }
+    if state.search.is_empty() {
+        return Err(ApiError::NotFound(format!(
+            "No search index found at {}, run gen-spec or gen-endpoint to build it",
+            search::EMBEDDINGS_FILE
+        )));
+    }
+
+    let limit = params
+        .limit
+        .unwrap_or(SEARCH_DEFAULT_LIMIT)
+        .clamp(1, SEARCH_MAX_LIMIT);
+    let hits = state
+        .search
+        .query(query, limit)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Search failed: {:#}", e)))?;
+
+    Ok(Json(json!({
+        "data": hits,
+        "count": hits.len()
+    })))
+}
+
+/// Status of an export job, with a download URL once it has completed
+async fn handle_get_export(
+    state: AppState,
+    headers: HeaderMap,
+    id: String,
+) -> Result<Json<JsonValue>, ApiError> {
+    state.api_keys.authenticate(&headers)?;
+
+    let job = state
+        .exports
+        .get(&id)
+        .ok_or_else(|| ApiError::NotFound(format!("Unknown export job: {}", id)))?;
+
+    let mut body = json!(job);
+    if matches!(job.status, ExportStatus::Completed { .. }) {
+        let download_url = match state.exports.signed_url(&id).await? {
+            Some(url) => url,
+            None => format!("/api/_exports/{}/download", id),
+        };
+        body["download_url"] = json!(download_url);
+    }
+
+    Ok(Json(body))
+}
+
+/// Query parameters of `/api/_usage`
+#[derive(Debug, Deserialize)]
+pub struct UsageParams {
+    /// Hours of usage to report, defaults to 24
+    pub hours: Option<u32>,
+}
+
+/// Longest period `/api/_usage` reports on
+const MAX_USAGE_HOURS: u32 = 24 * 90;
+
+/// Request counts, latency percentiles and error rates per endpoint
+async fn handle_usage(
+    state: AppState,
+    headers: HeaderMap,
+    query: UsageParams,
+) -> Result<Json<JsonValue>, ApiError> {
+    state.api_keys.authenticate_admin(&headers)?;
+    let usage = state.usage.as_ref().ok_or_else(|| {
+        ApiError::NotFound("Usage analytics are disabled, set server.usageAnalytics".to_string())
+    })?;
+
+    let hours = query.hours.unwrap_or(24);
+    if hours == 0 || hours > MAX_USAGE_HOURS {
+        return Err(ApiError::BadRequest(format!(
+            "hours must be between 1 and {}",
+            MAX_USAGE_HOURS
+        )));
+    }
+
+    let routes: Vec<String> = state
+        .endpoints
+        .iter()
+        .map(EndpointIrResult::route)
+        .collect();
+    let endpoints = usage.report(hours, &routes).await?;
+
+    Ok(Json(json!({
+        "hours": hours,
+        "endpoints": endpoints
+    })))
+}
+
+/// Latest endpoint queries slower than `server.slowQueryMs`, newest first
+async fn handle_slow_queries(
+    state: AppState,
+    headers: HeaderMap,
+) -> Result<Json<JsonValue>, ApiError> {
+    state.api_keys.authenticate_admin(&headers)?;
+    let slow_queries = state.slow_queries.as_ref().ok_or_else(|| {
+        ApiError::NotFound("Slow query logging is disabled, set server.slowQueryMs".to_string())
+    })?;
+
+    Ok(Json(json!({ "queries": slow_queries.recent() })))
+}
+
+/// Download the Parquet file of a completed export job
+async fn handle_download_export(
+    state: AppState,
+    headers: HeaderMap,
+    id: String,
+) -> Result<Response, ApiError> {
+    state.api_keys.authenticate(&headers)?;
+
+    let job = state
+        .exports
+        .get(&id)
+        .ok_or_else(|| ApiError::NotFound(format!("Unknown export job: {}", id)))?;
+    if !matches!(job.status, ExportStatus::Completed { .. }) {
+        return Err(ApiError::NotFound(format!(
+            "Export job {} has not completed",
+            id
+        )));
+    }
+
+    if let Some(url) = state.exports.signed_url(&id).await? {
+        return Ok(Redirect::temporary(&url).into_response());
+    }
+
+    let file = state.exports.read(&id).await?;
+    Ok((
+        [
+            (CONTENT_TYPE, "application/vnd.apache.parquet".to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.parquet\"", id),
+            ),
+        ],
+        Body::from_stream(file),
+    )
+        .into_response())
+}
+
+/// Statistics for a single smorty-managed table
+async fn handle_get_table(
+    state: AppState,
+    headers: HeaderMap,
+    name: String,
+) -> Result<Json<JsonValue>, ApiError> {
+    state.api_keys.authenticate_admin(&headers)?;
+
+    // Only tables from the schema are looked up, so the name is never used as raw SQL
+    let table = state
+        .schema
+        .get_table(&name)
+        .ok_or_else(|| ApiError::NotFound(format!("Unknown table: {}", name)))?;
+
+    let pools = state.databases.table_pools(&table.name);
+    let stats = TableStats::collect_all(&pools, table, true)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Table {} has not been migrated yet", name)))?;
+
+    Ok(Json(json!(stats)))
+}
+
+/// Stream new rows of a table as server-sent events
+async fn handle_sse(
+    state: AppState,
+    headers: HeaderMap,
+    name: String,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let api_key = state.api_keys.authenticate(&headers)?;
+
+    // Only tables from the schema are streamed, so the name is never used as raw SQL
+    let table = state
+        .schema
+        .get_table(&name)
+        .ok_or_else(|| ApiError::NotFound(format!("Unknown table: {}", name)))?;
+    if table.get_column("block_number").is_none() || table.get_column("log_index").is_none() {
+        return Err(ApiError::BadRequest(format!(
+            "Table {} has no block_number and log_index to stream rows by",
+            name
+        )));
+    }
+    let pools = state.databases.table_pools(&table.name);
+    let [pool] = pools.as_slice() else {
+        return Err(ApiError::BadRequest(format!(
+            "Table {} is written to several databases and can't be streamed as one",
+            name
+        )));
+    };
+
+    let tenant = match state.tenancy.as_ref().and_then(|tenancy| {
+        tenancy
+            .table_column(&table.name)
+            .map(|column| (tenancy, column))
+    }) {
+        Some((tenancy, (column, data_type))) => Some(TenantScope {
+            column: column.to_string(),
+            data_type: data_type.to_string(),
+            tenant: tenancy.tenant_for(api_key)?.to_string(),
+        }),
+        None => None,
+    };
+
+    let cursor = match headers.get("last-event-id") {
+        Some(id) => id.to_str().ok().and_then(RowCursor::parse).ok_or_else(|| {
+            ApiError::BadRequest("Last-Event-ID should be <block_number>:<log_index>".to_string())
+        })?,
+        None => RowCursor::tip(pool, table).await?,
+    };
+
+    let stream = TableStream::new(
+        pool.clone(),
+        table,
+        tenant,
+        Arc::clone(&state.redactions),
+        cursor,
+    );
+    Ok(Sse::new(stream.into_events()).keep_alive(KeepAlive::default()))
+}
+
+/// Serve a request to a generated endpoint, recording its usage
+async fn serve_endpoint(
+    state: AppState,
+    endpoint_ir: EndpointIrResult,
+    headers: HeaderMap,
+    peer: Option<SocketAddr>,
+    path_params: AxumPath<HashMap<String, String>>,
+    query_params: Query<GenericQueryParams>,
+    body: Bytes,
+) -> Response {
+    let started = Instant::now();
+    let usage = state.usage.clone();
+    let route = endpoint_ir.route();
+    let client = state.privacy.client(&headers, peer);
+    let response = handle_dynamic_endpoint(
+        state,
+        endpoint_ir,
+        headers,
+        client.as_deref(),
+        path_params,
+        query_params.0.params,
+        body,
+    )
+    .await
+    .into_response();
+    tracing::debug!(
+        "{} from {} returned {} in {:.0}ms",
+        route,
+        client.as_deref().unwrap_or("unknown client"),
+        response.status().as_u16(),
+        started.elapsed().as_secs_f64() * 1000.0
+    );
+    if let Some(usage) = usage {
+        usage.record(&route, response.status().as_u16(), started.elapsed());
+    }
+    response
+}
+
+/// Parameters of a request: the query string, overridden by a JSON object body
+fn request_params(
+    mut params: HashMap<String, String>,
+    body: &[u8],
+) -> Result<HashMap<String, String>, ApiError> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(params);
+    }
+    let body: serde_json::Map<String, JsonValue> = serde_json::from_slice(body)
+        .map_err(|e| ApiError::BadRequest(format!("Body should be a JSON object: {}", e)))?;
+    for (name, value) in body {
+        let value = match value {
+            JsonValue::Null => continue,
+            JsonValue::String(s) => s,
+            JsonValue::Number(n) => n.to_string(),
+            JsonValue::Bool(b) => b.to_string(),
+            JsonValue::Array(_) | JsonValue::Object(_) => {
+                return Err(ApiError::BadRequest(format!(
+                    "Parameter {} should be a string, number or boolean",
+                    name
+                )));
+            }
+        };
+        params.insert(name, value);
+    }
+    Ok(params)
+}
+
 /// Dynamic endpoint handler
 async fn handle_dynamic_endpoint(
     state: AppState,
     endpoint_ir: EndpointIrResult,
+    headers: HeaderMap,
+    client: Option<&str>,
     path_params: AxumPath<HashMap<String, String>>,
-    query_params: Query<GenericQueryParams>,
-) -> Result<Json<JsonValue>, ApiError> {
-    tracing::debug!("Handling request to {}", endpoint_ir.endpoint_path);
-    tracing::debug!("Path params: {:?}", path_params.0);
-    tracing::debug!("Query params: {:?}", query_params.params);
+    query_params: HashMap<String, String>,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let api_key = state.api_keys.authorize(&headers, &endpoint_ir.scopes)?;
+    let query_params = request_params(query_params, &body)?;
+
+    tracing::debug!(
+        "Handling request to {} {}",
+        endpoint_ir.method,
+        endpoint_ir.endpoint_path
+    );
+    let captures_params = state.privacy.captures_params(&endpoint_ir.endpoint_path);
+    if captures_params {
+        tracing::debug!("Path params: {:?}", path_params.0);
+        tracing::debug!("Query params: {:?}", query_params);
+    }
 
     // Build SQL query with parameters
-    let (sql, sql_params) = build_sql_query(&endpoint_ir, &path_params.0, &query_params.params)?;
+    let (sql, mut sql_params) = build_sql_query(&endpoint_ir, &path_params.0, &query_params)?;
+
+    // Tenant-scoped queries take the caller's tenant as the last parameter
+    let mut tenant = None;
+    if let Some(tenancy) = &state.tenancy
+        && tenancy.is_scoped(&endpoint_ir)
+    {
+        let tenant_id = tenancy.tenant_for(api_key)?;
+        sql_params.push(SqlParam::String(tenant_id.to_string()));
+        tenant = Some(tenant_id);
+    }
 
-    tracing::debug!("Executing SQL: {}", sql);
-    tracing::debug!("SQL params: {:?}", sql_params);
+    let pools = state.databases.endpoint_pools(&endpoint_ir);
 
-    // Execute query
-    let rows = execute_query(&state.db_pool, &sql, &sql_params).await?;
+    // Export endpoints run in the background and write their results to storage
+    if state.export_endpoints.contains(&endpoint_ir.route()) {
+        if !endpoint_ir.stages.is_empty() {
+            return Err(ApiError::Internal(format!(
+                "Export endpoint {} has query stages, which exports don't support",
+                endpoint_ir.endpoint_path
+            )));
+        }
+        let [pool] = pools.as_slice() else {
+            return Err(ApiError::Internal(format!(
+                "Export endpoint {} reads tables from several databases, which exports don't support",
+                endpoint_ir.endpoint_path
+            )));
+        };
+        let strict = state.strict_decoding.contains(&endpoint_ir.route());
+        let job = state.exports.spawn(
+            pool.clone(),
+            endpoint_ir,
+            sql,
+            sql_params,
+            strict,
+            Arc::clone(&state.redactions),
+        );
+        let status_url = format!("/api/_exports/{}", job.id);
+        let mut body = json!(job);
+        body["status_url"] = json!(status_url);
+        return Ok((StatusCode::ACCEPTED, [(LOCATION, status_url)], Json(body)).into_response());
+    }
+
+    // Skip the query entirely if the client already has the current data
+    let etag = endpoint_ir
+        .method
+        .eq_ignore_ascii_case("GET")
+        .then(|| {
+            state
+                .watermarks
+                .etag(&endpoint_ir, &path_params.0, &query_params, tenant)
+        })
+        .flatten();
+    if let Some(etag) = &etag
+        && watermark::if_none_match(&headers, etag)
+    {
+        return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag.clone())]).into_response());
+    }
 
-    // Convert rows to JSON
-    let results = rows_to_json(rows, &endpoint_ir)?;
+    let strict = state.strict_decoding.contains(&endpoint_ir.route());
+    let latest = latest_rows(&state, &endpoint_ir, &pools, &sql_params);
+    let cached = latest
+        .as_ref()
+        .and_then(|(cache, watermark, limit)| cache.get(&endpoint_ir, *watermark, *limit));
+    let mut results = match cached {
+        Some(rows) => rows,
+        None => {
+            // Expensive endpoints queue here rather than taking over the connection pool
+            let _permit = match state.concurrency_limits.get(&endpoint_ir.route()) {
+                Some(semaphore) => Some(
+                    Arc::clone(semaphore)
+                        .acquire_owned()
+                        .await
+                        .map_err(|e| ApiError::Internal(e.to_string()))?,
+                ),
+                None => None,
+            };
 
-    Ok(Json(json!({
-        "data": results,
-        "count": results.len()
-    })))
+            // A cache miss reads the newest rows the cache holds through to it
+            let capacity;
+            let sql_params = match &latest {
+                Some((cache, _, _)) => {
+                    capacity = [SqlParam::U64(cache.capacity() as u64)];
+                    &capacity[..]
+                }
+                None => &sql_params[..],
+            };
+
+            tracing::debug!("Executing SQL: {}", sql);
+            if captures_params {
+                tracing::debug!("SQL params: {:?}", sql_params);
+            }
+
+            // Execute query, on every database holding the endpoint's tables
+            let started = Instant::now();
+            let queries = pools.iter().map(|pool| async {
+                if endpoint_ir.stages.is_empty() {
+                    execute_query(pool, &sql, sql_params).await
+                } else {
+                    execute_staged(pool, &endpoint_ir, &sql, sql_params, tenant.is_some()).await
+                }
+            });
+            let rows = match futures::future::try_join_all(queries).await {
+                Ok(rows) => rows.into_iter().flatten().collect::<Vec<_>>(),
+                Err(ApiError::Database(e)) => {
+                    if let Some(repairs) = &state.repairs {
+                        repairs.report(&endpoint_ir, &e);
+                    }
+                    return Err(ApiError::Database(e));
+                }
+                Err(e) => return Err(e),
+            };
+            if let Some(slow_queries) = &state.slow_queries {
+                slow_queries.observe(
+                    &endpoint_ir,
+                    &sql,
+                    if captures_params { sql_params } else { &[] },
+                    rows.len(),
+                    started.elapsed(),
+                    client,
+                );
+            }
+
+            // Convert rows to JSON
+            let mut results = rows_to_json(rows, &endpoint_ir, strict)?;
+            if let Some(row_merge) = state.row_merges.get(&endpoint_ir.route()) {
+                row_merge.apply(&mut results, sql_params);
+            }
+            match latest {
+                Some((cache, watermark, limit)) => {
+                    let first = results.iter().take(limit).cloned().collect();
+                    cache.insert(&endpoint_ir, watermark, results);
+                    first
+                }
+                None => results,
+            }
+        }
+    };
+    for row in &mut results {
+        if let JsonValue::Object(row) = row {
+            state.redactions.redact_row(&endpoint_ir, row);
+        }
+    }
+
+    let envelope = state.envelopes.for_endpoint(&endpoint_ir);
+    let mut response = Json(envelope.wrap(results, &endpoint_ir)).into_response();
+    if envelope == ResponseEnvelope::JsonApi {
+        response.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static(envelope::JSON_API_CONTENT_TYPE),
+        );
+    }
+
+    if let Some(etag) = etag
+        && let Ok(value) = HeaderValue::from_str(&etag)
+    {
+        response.headers_mut().insert(ETAG, value);
+    }
+    if let Some(ttl) = endpoint_ir.cache_ttl
+        && let Ok(value) = HeaderValue::from_str(&cache_control(ttl, state.api_keys.is_enabled()))
+    {
+        response.headers_mut().insert(CACHE_CONTROL, value);
+    }
+
+    Ok(response)
+}
+
+/// `Cache-Control` of an endpoint response, private when API keys guard the API
+fn cache_control(ttl: u64, private: bool) -> String {
+    if private {
+        format!("private, max-age={}", ttl)
+    } else {
+        format!("public, max-age={}", ttl)
+    }
+}
+
+/// Cache of a "latest events" request, the watermark of its table and the
+/// number of rows it asks for
+///
+/// Returns `None` when the request has to run its own query.
+fn latest_rows<'a>(
+    state: &'a AppState,
+    endpoint_ir: &EndpointIrResult,
+    pools: &[PgPool],
+    sql_params: &[SqlParam],
+) -> Option<(&'a LatestRows, i64, usize)> {
+    let cache = state.latest_rows.as_deref()?;
+    if !cache.serves(endpoint_ir) {
+        return None;
+    }
+    let limit = match sql_params {
+        [SqlParam::U64(limit)] => usize::try_from(*limit).ok()?,
+        [SqlParam::I64(limit)] => usize::try_from(*limit).ok()?,
+        _ => return None,
+    };
+    // Rows from several databases aren't in one order to slice from
+    let ([_], [table]) = (pools, endpoint_ir.tables_referenced.as_slice()) else {
+        return None;
+    };
+    if limit > cache.capacity() {
+        return None;
+    }
+    let watermark = state.watermarks.mark(table)?;
+    Some((cache, watermark, limit))
 }
 
 /// SQL parameter value that can be of different types
@@ -427,6 +2424,8 @@ pub enum SqlParam {
     U64(u64),
     Bool(bool),
     Null,
+    /// Outputs of a query stage, bound as `TEXT[]`
+    TextArray(Vec<Option<String>>),
 }
 
 /// Build SQL query with parameters
@@ -437,7 +2436,7 @@ pub enum SqlParam {
 /// 2. All user inputs are passed as bound parameters ($1, $2, etc.), never interpolated into SQL
 /// 3. Parameters are validated against the endpoint IR schema
 /// 4. Only parameters defined in the endpoint IR are accepted
-fn build_sql_query(
+pub(crate) fn build_sql_query(
     endpoint_ir: &EndpointIrResult,
     path_params: &HashMap<String, String>,
     query_params: &HashMap<String, String>,
@@ -466,6 +2465,7 @@ fn build_sql_query(
         let sql_param = if let Some(v) = query_params.get(&query_param.name) {
             // User provided a value - validate and convert it
             validate_parameter_value(&query_param.name, v, &query_param.param_type)?;
+            check_allowed_value(query_param, v)?;
 
             // Special validation for limit to prevent resource exhaustion
             if query_param.name == "limit" {
@@ -473,8 +2473,11 @@ fn build_sql_query(
                     .parse()
                     .map_err(|_| ApiError::BadRequest("Invalid limit parameter".to_string()))?;
 
-                if limit > 200 {
-                    return Err(ApiError::BadRequest("Limit cannot exceed 200".to_string()));
+                if limit > MAX_LIMIT {
+                    return Err(ApiError::BadRequest(format!(
+                        "Limit cannot exceed {}",
+                        MAX_LIMIT
+                    )));
                 }
                 SqlParam::U64(limit as u64)
             } else {
@@ -483,7 +2486,7 @@ fn build_sql_query(
         } else if let Some(default) = &query_param.default {
             // Use default value (from trusted IR)
             // Check if default is JSON null (which becomes "null" string)
-            if default.is_null() || default.to_string() == "null" {
+            if default.is_null() {
                 SqlParam::Null
             } else {
                 // Convert default JSON value to appropriate SQL param
@@ -517,6 +2520,32 @@ fn build_sql_query(
     Ok((sql, sql_params))
 }
 
+/// Reject values outside a query parameter's `allowed_values`
+fn check_allowed_value(query_param: &QueryParam, value: &str) -> Result<(), ApiError> {
+    if query_param.allowed_values.is_empty() {
+        return Ok(());
+    }
+
+    let allowed: Vec<String> = query_param
+        .allowed_values
+        .iter()
+        .map(|v| match v {
+            JsonValue::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .collect();
+
+    if allowed.iter().any(|a| a == value) {
+        Ok(())
+    } else {
+        Err(ApiError::BadRequest(format!(
+            "Invalid value for parameter '{}': expected one of {}",
+            query_param.name,
+            allowed.join(", ")
+        )))
+    }
+}
+
 /// Convert a string value to a SqlParam based on the parameter type
 fn convert_to_sql_param(value: &str, param_type: &str) -> Result<SqlParam, ApiError> {
     // Check if this is an optional type and value is "null"
@@ -550,6 +2579,15 @@ fn convert_to_sql_param(value: &str, param_type: &str) -> Result<SqlParam, ApiEr
             })?;
             Ok(SqlParam::Bool(b))
         }
+        "Duration" => {
+            let seconds = parse_duration(value).ok_or_else(|| {
+                ApiError::BadRequest(format!("Parameter must be a duration like 7d: {}", value))
+            })?;
+            Ok(SqlParam::I64(duration_lower_bound(
+                seconds,
+                chrono::Utc::now().timestamp(),
+            )))
+        }
         "String" => Ok(SqlParam::String(value.to_string())),
         _ => {
             // Default to string for unknown types
@@ -558,6 +2596,29 @@ fn convert_to_sql_param(value: &str, param_type: &str) -> Result<SqlParam, ApiEr
     }
 }
 
+/// Seconds in a relative time like `90s`, `15m`, `12h`, `7d` or `2w`
+fn parse_duration(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let unit_start = value.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = value.split_at(unit_start);
+    let amount: i64 = amount.parse().ok()?;
+    let unit_seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    amount.checked_mul(unit_seconds)
+}
+
+/// Unix timestamp a duration parameter reaches back to from `now`, the lower
+/// bound queries compare `block_timestamp` against
+fn duration_lower_bound(seconds: i64, now: i64) -> i64 {
+    now.saturating_sub(seconds).max(0)
+}
+
 /// Validate parameter value based on its expected type
 ///
 /// # Security
@@ -605,6 +2666,14 @@ fn validate_parameter_value(name: &str, value: &str, param_type: &str) -> Result
                 ApiError::BadRequest(format!("Parameter '{}' must be true or false", name))
             })?;
         }
+        "Duration" => {
+            parse_duration(value).ok_or_else(|| {
+                ApiError::BadRequest(format!(
+                    "Parameter '{}' must be a duration like 30m, 12h or 7d",
+                    name
+                ))
+            })?;
+        }
         _ => {
             // Unknown type, perform basic validation
             if value.len() > 1000 {
@@ -620,13 +2689,90 @@ fn validate_parameter_value(name: &str, value: &str, param_type: &str) -> Result
 }
 
 /// Execute SQL query with parameters
-async fn execute_query(
+pub(crate) async fn execute_query(
+    pool: &PgPool,
+    sql: &str,
+    params: &[SqlParam],
+) -> Result<Vec<sqlx::postgres::PgRow>, ApiError> {
+    // Execute query
+    let rows = bind_params(sql, params).fetch_all(pool).await?;
+
+    Ok(rows)
+}
+
+/// Execute a staged endpoint query, running its stages first in the same
+/// repeatable-read transaction
+///
+/// `params` end with the tenant when `tenant_scoped`, as built for unstaged queries.
+pub(crate) async fn execute_staged(
     pool: &PgPool,
+    endpoint_ir: &EndpointIrResult,
     sql: &str,
     params: &[SqlParam],
+    tenant_scoped: bool,
 ) -> Result<Vec<sqlx::postgres::PgRow>, ApiError> {
-    // Build query with parameters
-    let mut query = sqlx::query(sql);
+    let mut transaction = pool.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+        .execute(&mut *transaction)
+        .await?;
+    let params = run_stages(&mut transaction, endpoint_ir, params, tenant_scoped).await?;
+    let rows = bind_params(sql, &params)
+        .fetch_all(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+
+    Ok(rows)
+}
+
+/// Run an endpoint's stages, returning the parameters of its final query: the
+/// request's, then every stage output as an array, then the tenant when scoped
+pub(crate) async fn run_stages(
+    conn: &mut sqlx::PgConnection,
+    endpoint_ir: &EndpointIrResult,
+    params: &[SqlParam],
+    tenant_scoped: bool,
+) -> Result<Vec<SqlParam>, ApiError> {
+    let (request_params, tenant) = match params.split_last() {
+        Some((tenant, request_params)) if tenant_scoped => {
+            (request_params, std::slice::from_ref(tenant))
+        }
+        _ => (params, &[][..]),
+    };
+
+    let mut params = request_params.to_vec();
+    for stage in &endpoint_ir.stages {
+        let sql = query_stages::outputs_sql(stage);
+        let stage_params: Vec<SqlParam> = params.iter().chain(tenant).cloned().collect();
+        let rows = bind_params(&sql, &stage_params)
+            .fetch_all(&mut *conn)
+            .await?;
+        if rows.len() > query_stages::MAX_STAGE_ROWS {
+            return Err(ApiError::BadRequest(format!(
+                "Stage '{}' returned more than {} rows",
+                stage.name,
+                query_stages::MAX_STAGE_ROWS
+            )));
+        }
+        for index in 0..stage.outputs.len() {
+            let values = rows
+                .iter()
+                .map(|row| row.try_get::<Option<String>, _>(index))
+                .collect::<Result<Vec<_>, _>>()?;
+            params.push(SqlParam::TextArray(values));
+        }
+    }
+    params.extend_from_slice(tenant);
+
+    Ok(params)
+}
+
+/// Build a query with its parameters bound
+pub(crate) fn bind_params<'q>(
+    sql: &'q str,
+    params: &'q [SqlParam],
+) -> sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments> {
+    // Persistent, so each connection prepares an endpoint's SQL once and reuses it
+    let mut query = sqlx::query(sql).persistent(true);
 
     for param in params {
         query = match param {
@@ -635,19 +2781,31 @@ async fn execute_query(
             SqlParam::U64(u) => query.bind(*u as i64), // PostgreSQL uses i64 for BIGINT
             SqlParam::Bool(b) => query.bind(b),
             SqlParam::Null => query.bind(None::<i64>), // Bind as NULL with type hint
+            SqlParam::TextArray(values) => query.bind(values),
         };
     }
 
-    // Execute query
-    let rows = query.fetch_all(pool).await?;
+    query
+}
 
-    Ok(rows)
+/// Whether a response field type holds a JSONB value, optionally wrapped in `Option<>`
+fn is_json_field_type(field_type: &str) -> bool {
+    let base_type = field_type
+        .strip_prefix("Option<")
+        .and_then(|s| s.strip_suffix('>'))
+        .unwrap_or(field_type);
+    matches!(base_type, "serde_json::Value" | "Value" | "Json")
 }
 
 /// Convert database rows to JSON
+///
+/// NULL values become JSON null. Columns that are missing or can't be decoded as
+/// the response field type are NULL too, unless `strict` is set. Field transforms
+/// are applied to the decoded values.
 fn rows_to_json(
     rows: Vec<sqlx::postgres::PgRow>,
     endpoint_ir: &EndpointIrResult,
+    strict: bool,
 ) -> Result<Vec<JsonValue>, ApiError> {
     let mut results = Vec::new();
 
@@ -656,64 +2814,25 @@ fn rows_to_json(
 
         // Use response schema to extract columns
         for field in &endpoint_ir.response_schema.fields {
-            let value: JsonValue = match field.field_type.as_str() {
-                "i64" | "i32" => {
-                    if let Ok(v) = row.try_get::<i64, _>(field.name.as_str()) {
-                        json!(v)
-                    } else {
-                        JsonValue::Null
-                    }
-                }
-                "u32" | "u64" => {
-                    if let Ok(v) = row.try_get::<i64, _>(field.name.as_str()) {
-                        json!(v)
-                    } else {
-                        JsonValue::Null
-                    }
-                }
-                "String" => {
-                    if let Ok(v) = row.try_get::<String, _>(field.name.as_str()) {
-                        json!(v)
-                    } else {
-                        JsonValue::Null
-                    }
-                }
-                "bool" => {
-                    if let Ok(v) = row.try_get::<bool, _>(field.name.as_str()) {
-                        json!(v)
-                    } else {
-                        JsonValue::Null
-                    }
-                }
-                t if t.starts_with("Option<") => {
-                    // Handle optional types
-                    let inner_type = t.trim_start_matches("Option<").trim_end_matches('>');
-                    match inner_type {
-                        "i64" | "i32" => row
-                            .try_get::<Option<i64>, _>(field.name.as_str())
-                            .ok()
-                            .flatten()
-                            .map(|v| json!(v))
-                            .unwrap_or(JsonValue::Null),
-                        "String" => row
-                            .try_get::<Option<String>, _>(field.name.as_str())
-                            .ok()
-                            .flatten()
-                            .map(|v| json!(v))
-                            .unwrap_or(JsonValue::Null),
-                        _ => JsonValue::Null,
-                    }
+            let value = match decode_field(&row, &field.name, &field.field_type) {
+                Ok(value) => value,
+                Err(e) if strict => {
+                    return Err(ApiError::Internal(format!(
+                        "Failed to decode column '{}' as {}: {}",
+                        field.name, field.field_type, e
+                    )));
                 }
-                _ => {
-                    // Try to get as string as fallback
-                    if let Ok(v) = row.try_get::<String, _>(field.name.as_str()) {
-                        json!(v)
-                    } else {
-                        JsonValue::Null
-                    }
+                Err(e) => {
+                    tracing::debug!("Returning NULL for column '{}': {}", field.name, e);
+                    JsonValue::Null
                 }
             };
 
+            let value = if transform::has_transforms(field) {
+                transform::apply(field, value)
+            } else {
+                value
+            };
             obj.insert(field.name.clone(), value);
         }
 
@@ -723,10 +2842,146 @@ fn rows_to_json(
     Ok(results)
 }
 
+/// Decode a column as a response field type, optionally wrapped in `Option<>`
+pub(crate) fn decode_field(
+    row: &sqlx::postgres::PgRow,
+    name: &str,
+    field_type: &str,
+) -> Result<JsonValue, sqlx::Error> {
+    let base_type = field_type
+        .strip_prefix("Option<")
+        .and_then(|s| s.strip_suffix('>'))
+        .unwrap_or(field_type);
+
+    let value = match base_type {
+        "i64" | "i32" | "u32" | "u64" => row.try_get::<Option<i64>, _>(name)?.map(|v| json!(v)),
+        // NUMERIC results, e.g. of SUM over uint256 columns, as their exact digits
+        "String" | "Decimal" => match row.try_get::<Option<String>, _>(name) {
+            Ok(v) => v.map(|v| json!(v)),
+            Err(e) => row
+                .try_get::<Option<PgDecimal>, _>(name)
+                .map_err(|_| e)?
+                .map(|v| json!(v.0)),
+        },
+        // DOUBLE PRECISION from percentile_cont, NUMERIC from AVG and moving averages
+        "f64" | "f32" => match row.try_get::<Option<f64>, _>(name) {
+            Ok(v) => v.map(|v| json!(v)),
+            Err(e) => row
+                .try_get::<Option<PgDecimal>, _>(name)
+                .map_err(|_| e)?
+                .and_then(|v| v.to_f64())
+                .map(|v| json!(v)),
+        },
+        "bool" => row.try_get::<Option<bool>, _>(name)?.map(|v| json!(v)),
+        t if is_json_field_type(t) => row.try_get::<Option<JsonValue>, _>(name)?,
+        // Try to get as string as fallback, passing JSONB columns through as-is
+        _ => match row.try_get::<Option<String>, _>(name) {
+            Ok(v) => v.map(|v| json!(v)),
+            Err(e) => row.try_get::<Option<JsonValue>, _>(name).map_err(|_| e)?,
+        },
+    };
+
+    Ok(value.unwrap_or(JsonValue::Null))
+}
+
+/// Response fields the endpoint query doesn't return
+fn missing_response_fields(endpoint_ir: &EndpointIrResult, columns: &[&str]) -> Vec<String> {
+    endpoint_ir
+        .response_schema
+        .fields
+        .iter()
+        .filter(|field| !columns.contains(&field.name.as_str()))
+        .map(|field| field.name.clone())
+        .collect()
+}
+
+/// Postgres types requests bind for the endpoint's parameters, in bind order
+///
+/// Mirrors `build_sql_query` and `bind_params`: integers and durations, which
+/// resolve to a Unix timestamp, bind as BIGINT, `bool` as BOOLEAN and everything
+/// else as TEXT, then stage outputs as TEXT[], with the tenant last when scoped.
+fn endpoint_param_types(endpoint_ir: &EndpointIrResult, tenant_scoped: bool) -> Vec<PgTypeInfo> {
+    let stage_outputs = query_stages::outputs_before(&endpoint_ir.stages, endpoint_ir.stages.len());
+    let param_types = endpoint_ir
+        .path_params
+        .iter()
+        .map(|param| param.param_type.as_str())
+        .chain(
+            endpoint_ir
+                .query_params
+                .iter()
+                .map(|param| param.param_type.as_str()),
+        )
+        .chain(std::iter::repeat_n("Vec<String>", stage_outputs))
+        .chain(tenant_scoped.then_some("String"));
+
+    param_types
+        .map(|param_type| {
+            let base_type = param_type
+                .strip_prefix("Option<")
+                .and_then(|s| s.strip_suffix('>'))
+                .unwrap_or(param_type);
+            match base_type {
+                "u32" | "u64" | "i32" | "i64" | "Duration" => {
+                    <i64 as sqlx::Type<sqlx::Postgres>>::type_info()
+                }
+                "bool" => <bool as sqlx::Type<sqlx::Postgres>>::type_info(),
+                "Vec<String>" => <Vec<String> as sqlx::Type<sqlx::Postgres>>::type_info(),
+                _ => <String as sqlx::Type<sqlx::Postgres>>::type_info(),
+            }
+        })
+        .collect()
+}
+
+/// Prepare the endpoint query with its parameter types on each database it reads
+///
+/// The statement stays in the cache of one connection per pool; the others
+/// prepare it on first use, keyed by the endpoint's SQL. Returns the first
+/// database's statement.
+async fn prepare_endpoint_query(
+    pools: &[PgPool],
+    endpoint_ir: &EndpointIrResult,
+    tenant_scoped: bool,
+) -> Result<PgStatement<'static>, sqlx::Error> {
+    use sqlx::{Executor, Statement};
+
+    let param_types = endpoint_param_types(endpoint_ir, tenant_scoped);
+    let (first, rest) = pools
+        .split_first()
+        .expect("endpoints read at least one database");
+    let statement = first
+        .prepare_with(&endpoint_ir.sql_query, &param_types)
+        .await?;
+    for pool in rest {
+        pool.prepare_with(&endpoint_ir.sql_query, &param_types)
+            .await?;
+    }
+    Ok(Statement::to_owned(&statement))
+}
+
+/// Check that the prepared endpoint query returns every response field
+fn check_response_fields(endpoint_ir: &EndpointIrResult, statement: &PgStatement) -> Result<()> {
+    use sqlx::{Column, Statement};
+
+    let columns: Vec<&str> = statement.columns().iter().map(|c| c.name()).collect();
+
+    let missing = missing_response_fields(endpoint_ir, &columns);
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Response fields not returned by the query: {} (query returns: {})",
+            missing.join(", "),
+            columns.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::ai::{PathParam, QueryParam, ResponseField, ResponseSchema};
+    use axum::body::Body;
 
     /// Helper to create a mock endpoint IR for testing
     fn create_mock_endpoint_ir() -> EndpointIrResult {
@@ -744,11 +2999,15 @@ mod tests {
                     name: "limit".to_string(),
                     param_type: "u32".to_string(),
                     default: Some(json!(50)),
+                    description: String::new(),
+                    allowed_values: vec![],
                 },
                 QueryParam {
                     name: "startBlockTimestamp".to_string(),
                     param_type: "Option<u64>".to_string(),
                     default: Some(json!("null")),
+                    description: String::new(),
+                    allowed_values: vec![],
                 },
             ],
             response_schema: ResponseSchema {
@@ -758,16 +3017,24 @@ mod tests {
                         name: "block_number".to_string(),
                         field_type: "i64".to_string(),
                         description: "Block number".to_string(),
+                        scale: None,
+                        format: None,
                     },
                     ResponseField {
                         name: "pool".to_string(),
                         field_type: "String".to_string(),
                         description: "Pool address".to_string(),
+                        scale: None,
+                        format: None,
                     },
                 ],
             },
             sql_query: "SELECT block_number, pool FROM test_table WHERE pool = $1 AND ($2::BIGINT IS NULL OR block_timestamp >= $2) ORDER BY block_number DESC LIMIT $3".to_string(),
+            stages: Vec::new(),
             tables_referenced: vec!["test_table".to_string()],
+            tags: vec![],
+            scopes: vec![],
+            cache_ttl: None,
         }
     }
 
@@ -869,6 +3136,31 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_duration_params_resolve_to_timestamp_bounds() {
+        assert_eq!(parse_duration("90s"), Some(90));
+        assert_eq!(parse_duration("15m"), Some(900));
+        assert_eq!(parse_duration("7d"), Some(7 * 86_400));
+        assert_eq!(parse_duration("2w"), Some(14 * 86_400));
+        for invalid in ["7", "d", "7 days", "-1d", "1.5h", "99999999999999999999w"] {
+            assert_eq!(parse_duration(invalid), None, "{}", invalid);
+        }
+
+        assert_eq!(duration_lower_bound(86_400, 1_700_000_000), 1_699_913_600);
+        assert_eq!(duration_lower_bound(86_400, 1_000), 0);
+
+        assert!(validate_parameter_value("window", "7d", "Option<Duration>").is_ok());
+        assert!(validate_parameter_value("window", "7x", "Duration").is_err());
+        assert!(matches!(
+            convert_to_sql_param("null", "Option<Duration>"),
+            Ok(SqlParam::Null)
+        ));
+        let Ok(SqlParam::I64(bound)) = convert_to_sql_param("1h", "Duration") else {
+            panic!("expected a timestamp");
+        };
+        assert!((chrono::Utc::now().timestamp() - 3600 - bound).abs() <= 1);
+    }
+
     #[test]
     fn test_build_sql_query_with_all_params() {
         let endpoint_ir = create_mock_endpoint_ir();
@@ -1051,6 +3343,8 @@ mod tests {
             name: "offset".to_string(),
             param_type: "Option<u32>".to_string(),
             default: Some(json!(0)),
+            description: String::new(),
+            allowed_values: vec![],
         });
 
         endpoint_ir.sql_query = "SELECT * FROM test WHERE pool = $1 AND ($2::BIGINT IS NULL OR block_timestamp >= $2) LIMIT $3 OFFSET $4".to_string();
@@ -1117,4 +3411,393 @@ mod tests {
         // The malicious_param should be completely ignored
         assert_eq!(params.len(), 3);
     }
+
+    /// State serving no endpoints, over a database that is never connected to
+    fn create_app_state(api_keys: ApiKeyStore, exports: &std::path::Path) -> AppState {
+        let uri = "postgres://localhost/smorty";
+        let databases = DatabaseRouter::new(
+            &crate::config::DatabaseConfig {
+                uri: uri.to_string(),
+                api_uri: None,
+                per_chain: HashMap::new(),
+            },
+            HashMap::from([(uri.to_string(), PgPool::connect_lazy(uri).unwrap())]),
+        );
+        let exports = ExportJobs::new(&crate::config::ExportConfig {
+            directory: exports.display().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        let ai_client = AiClient::new(String::new(), String::new(), 0.0);
+
+        AppState {
+            databases,
+            endpoints: Arc::default(),
+            api_keys: Arc::new(api_keys),
+            tenancy: None,
+            watermarks: TableWatermarks::default(),
+            schema: Arc::new(SchemaState::new()),
+            envelopes: Arc::default(),
+            strict_decoding: Arc::default(),
+            row_merges: Arc::default(),
+            concurrency_limits: Arc::default(),
+            exports,
+            export_endpoints: Arc::default(),
+            redactions: Arc::default(),
+            search: Arc::new(Search::new(SearchIndex::default(), Arc::new(ai_client))),
+            repairs: None,
+            usage: None,
+            slow_queries: None,
+            latest_rows: None,
+            privacy: Arc::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_table_stats_need_admin_key() {
+        let exports = tempfile::tempdir().unwrap();
+        let api_keys = ApiKeyStore::from_config(&crate::config::ServerConfig {
+            api_keys: vec![crate::config::ApiKeyConfig {
+                key: "tenant-key".to_string(),
+                name: Some("acme".to_string()),
+                metadata: HashMap::from([("tenant".to_string(), "acme".to_string())]),
+                scopes: vec![],
+            }],
+            ..Default::default()
+        });
+        let state = create_app_state(api_keys, exports.path());
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            crate::auth::API_KEY_HEADER,
+            HeaderValue::from_static("tenant-key"),
+        );
+
+        assert!(matches!(
+            handle_list_tables(state.clone(), headers.clone()).await,
+            Err(ApiError::Forbidden(_))
+        ));
+        assert!(matches!(
+            handle_get_table(state.clone(), headers.clone(), "transfers".to_string()).await,
+            Err(ApiError::Forbidden(_))
+        ));
+        assert!(matches!(
+            handle_contract_stats(
+                state,
+                headers,
+                "Token".to_string(),
+                ContractStatsParams { days: None }
+            )
+            .await,
+            Err(ApiError::Forbidden(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reloadable_app_uses_latest_router() {
+        let router = Arc::new(ArcSwap::from_pointee(
+            Router::new().route("/api/old", get(|| async { "old" })),
+        ));
+        let app = reloadable_app(Arc::clone(&router));
+        let request = |path: &str| Request::builder().uri(path).body(Body::empty()).unwrap();
+
+        let response = app.clone().oneshot(request("/api/old")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        router.store(Arc::new(
+            Router::new().route("/api/new", get(|| async { "new" })),
+        ));
+
+        let response = app.clone().oneshot(request("/api/new")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.oneshot(request("/api/old")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_route_methods() {
+        let app = Router::new()
+            .route("/api/test", read_only_route(get(|| async { "ok" })))
+            .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any));
+        let request = |method: Method| {
+            Request::builder()
+                .method(method)
+                .uri("/api/test")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(request(Method::HEAD)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.clone().oneshot(request(Method::OPTIONS)).await.unwrap();
+        assert!(response.status().is_success());
+
+        let response = app.oneshot(request(Method::DELETE)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[ALLOW], READ_ONLY_METHODS);
+    }
+
+    #[test]
+    fn test_limit_rows_wraps_unbounded_queries() {
+        let mut endpoint = create_mock_endpoint_ir();
+        endpoint.sql_query = "SELECT * FROM test WHERE pool = $1".to_string();
+        shape_rows(&mut endpoint, None).unwrap();
+        assert_eq!(
+            endpoint.sql_query,
+            "SELECT * FROM (SELECT * FROM test WHERE pool = $1) q LIMIT $2"
+        );
+
+        let mut endpoint = create_mock_endpoint_ir();
+        endpoint.query_params.clear();
+        endpoint.sql_query = "SELECT * FROM test WHERE pool = $1".to_string();
+        shape_rows(&mut endpoint, None).unwrap();
+        assert_eq!(endpoint.query_params[0].default, Some(json!(MAX_LIMIT)));
+        assert!(endpoint.sql_query.ends_with("q LIMIT $2"));
+
+        let mut endpoint = create_mock_endpoint_ir();
+        let sql = endpoint.sql_query.clone();
+        shape_rows(&mut endpoint, None).unwrap();
+        assert_eq!(endpoint.sql_query, sql);
+    }
+
+    #[test]
+    fn test_endpoint_defaults_are_merged_into_ir() {
+        let defaults = EndpointDefaultsConfig {
+            limit: Some(25),
+            order: Some("block_number DESC".to_string()),
+            cache_ttl: Some(30),
+            scopes: vec!["read".to_string()],
+        };
+        let order_by = defaults.order_by().transpose().unwrap();
+
+        let mut endpoint = create_mock_endpoint_ir();
+        endpoint.sql_query = "SELECT * FROM test WHERE pool = $1".to_string();
+        shape_rows(&mut endpoint, order_by).unwrap();
+        apply_defaults(&mut endpoint, &defaults);
+        assert_eq!(
+            endpoint.sql_query,
+            "SELECT * FROM (SELECT * FROM test WHERE pool = $1) q ORDER BY block_number DESC LIMIT $2"
+        );
+        assert_eq!(endpoint.query_params[0].default, Some(json!(25)));
+        assert_eq!(endpoint.cache_ttl, Some(30));
+        assert_eq!(endpoint.scopes, ["read"]);
+
+        // Queries that order their rows, and endpoints with their own settings, are kept
+        let mut endpoint = create_mock_endpoint_ir();
+        endpoint.cache_ttl = Some(5);
+        endpoint.scopes = vec!["admin".to_string()];
+        let sql = endpoint.sql_query.clone();
+        shape_rows(&mut endpoint, order_by).unwrap();
+        apply_defaults(&mut endpoint, &defaults);
+        assert_eq!(endpoint.sql_query, sql);
+        assert_eq!(endpoint.cache_ttl, Some(5));
+        assert_eq!(endpoint.scopes, ["admin"]);
+
+        assert_eq!(cache_control(30, false), "public, max-age=30");
+    }
+
+    #[test]
+    fn test_post_endpoint_shares_path_and_takes_json_body() {
+        let mut post = create_mock_endpoint_ir();
+        post.method = "POST".to_string();
+        let openapi = generate_openapi_spec(
+            &[create_mock_endpoint_ir(), post],
+            &SchemaState::new(),
+            &ResponseEnvelopes::default(),
+            &ApiKeyStore::default(),
+        );
+
+        let item = &openapi.paths.paths["/api/test/{pool}"];
+        let get = item.get.as_ref().unwrap();
+        let post = item.post.as_ref().unwrap();
+        assert_eq!(get.parameters.as_ref().unwrap().len(), 3);
+        assert_eq!(post.parameters.as_ref().unwrap().len(), 1);
+        assert!(post.request_body.is_some());
+
+        let params = request_params(
+            HashMap::from([("limit".to_string(), "10".to_string())]),
+            br#"{"limit": 5, "startBlockTimestamp": null}"#,
+        )
+        .unwrap();
+        assert_eq!(params["limit"], "5");
+        assert!(!params.contains_key("startBlockTimestamp"));
+        assert!(request_params(HashMap::new(), br#"{"limit": [5]}"#).is_err());
+    }
+
+    #[test]
+    fn test_openapi_spec_includes_system_endpoints() {
+        let openapi = generate_openapi_spec(
+            &[create_mock_endpoint_ir()],
+            &SchemaState::new(),
+            &ResponseEnvelopes::default(),
+            &ApiKeyStore::default(),
+        );
+
+        assert!(openapi.paths.paths.contains_key("/api/test/{pool}"));
+        assert!(openapi.paths.paths.contains_key("/api/_tables"));
+        assert!(openapi.paths.paths.contains_key("/api/_tables/{name}"));
+
+        let json: JsonValue = serde_json::from_str(&openapi.to_pretty_json().unwrap()).unwrap();
+        assert_eq!(json["openapi"], "3.1.0");
+    }
+
+    #[test]
+    fn test_openapi_spec_documents_query_params_and_examples() {
+        let mut endpoint_ir = create_mock_endpoint_ir();
+        endpoint_ir.query_params.push(QueryParam {
+            name: "kind".to_string(),
+            param_type: "String".to_string(),
+            default: None,
+            description: "Liquidity event kind".to_string(),
+            allowed_values: vec![json!("mint"), json!("burn")],
+        });
+
+        let openapi = serde_json::to_value(generate_openapi_spec(
+            &[endpoint_ir],
+            &SchemaState::new(),
+            &ResponseEnvelopes::default(),
+            &ApiKeyStore::default(),
+        ))
+        .unwrap();
+        let operation = &openapi["paths"]["/api/test/{pool}"]["get"];
+        let params = operation["parameters"].as_array().unwrap();
+        let param = |name: &str| params.iter().find(|p| p["name"] == name).unwrap();
+
+        assert_eq!(param("limit")["schema"]["default"], json!(50));
+        assert_eq!(param("limit")["schema"]["maximum"], json!(200));
+        assert!(
+            param("limit")["description"]
+                .as_str()
+                .unwrap()
+                .contains("(default: 50)")
+        );
+        assert_eq!(
+            param("startBlockTimestamp")["example"],
+            json!(1_700_000_000)
+        );
+        assert_eq!(param("kind")["schema"]["enum"], json!(["mint", "burn"]));
+        assert_eq!(param("kind")["example"], json!("mint"));
+        assert_eq!(param("kind")["description"], json!("Liquidity event kind"));
+
+        let example = &operation["responses"]["200"]["content"]["application/json"]["example"];
+        assert_eq!(example["count"], json!(1));
+        assert!(example["data"][0].is_object());
+    }
+
+    #[test]
+    fn test_openapi_spec_declares_security_with_scopes() {
+        use crate::config::{ApiKeyConfig, ServerConfig};
+
+        let api_keys = ApiKeyStore::from_config(&ServerConfig {
+            api_keys: vec![ApiKeyConfig {
+                key: "secret".to_string(),
+                name: None,
+                metadata: HashMap::new(),
+                scopes: vec![],
+            }],
+            ..Default::default()
+        });
+        let mut endpoint_ir = create_mock_endpoint_ir();
+        endpoint_ir.scopes = vec!["pools:read".to_string()];
+
+        let openapi = serde_json::to_value(generate_openapi_spec(
+            &[endpoint_ir],
+            &SchemaState::new(),
+            &ResponseEnvelopes::default(),
+            &api_keys,
+        ))
+        .unwrap();
+        let schemes = &openapi["components"]["securitySchemes"];
+        assert_eq!(schemes["apiKey"]["in"], "header");
+        assert_eq!(schemes["apiKey"]["name"], "X-API-Key");
+        assert_eq!(schemes["bearer"]["scheme"], "bearer");
+
+        let operation = &openapi["paths"]["/api/test/{pool}"]["get"];
+        assert_eq!(
+            operation["security"],
+            json!([{"apiKey": ["pools:read"]}, {"bearer": ["pools:read"]}])
+        );
+        assert!(operation["responses"]["403"].is_object());
+        assert_eq!(
+            openapi["paths"]["/api/_tables"]["get"]["security"],
+            json!([{"apiKey": ["admin"]}, {"bearer": ["admin"]}])
+        );
+    }
+
+    #[test]
+    fn test_missing_response_fields() {
+        let endpoint_ir = create_mock_endpoint_ir();
+
+        assert!(missing_response_fields(&endpoint_ir, &["block_number", "pool"]).is_empty());
+        assert_eq!(
+            missing_response_fields(&endpoint_ir, &["block_number", "pool_address"]),
+            vec!["pool"]
+        );
+    }
+
+    #[test]
+    fn test_endpoint_param_types_follow_bind_order() {
+        use sqlx::TypeInfo;
+
+        let endpoint_ir = create_mock_endpoint_ir();
+        let names = |tenant_scoped| {
+            endpoint_param_types(&endpoint_ir, tenant_scoped)
+                .iter()
+                .map(|t| t.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(false), vec!["TEXT", "INT8", "INT8"]);
+        assert_eq!(names(true), vec!["TEXT", "INT8", "INT8", "TEXT"]);
+    }
+
+    #[test]
+    fn test_endpoint_tags_default_to_contract() {
+        use crate::schema_state::TableState;
+
+        let mut schema = SchemaState::new();
+        schema.add_table(TableState::new(
+            "test_table".to_string(),
+            "UniswapV3Pool".to_string(),
+            "Swap".to_string(),
+        ));
+
+        let mut endpoint_ir = create_mock_endpoint_ir();
+        assert_eq!(endpoint_tags(&endpoint_ir, &schema), vec!["UniswapV3Pool"]);
+        assert_eq!(
+            endpoint_tags(&endpoint_ir, &SchemaState::new()),
+            vec!["test_table"]
+        );
+
+        endpoint_ir.tags = vec!["pools".to_string()];
+        let openapi = serde_json::to_value(generate_openapi_spec(
+            &[endpoint_ir],
+            &schema,
+            &ResponseEnvelopes::default(),
+            &ApiKeyStore::default(),
+        ))
+        .unwrap();
+        assert_eq!(
+            openapi["paths"]["/api/test/{pool}"]["get"]["tags"],
+            json!(["pools"])
+        );
+    }
+
+    #[test]
+    fn test_build_sql_query_rejects_values_outside_allowed_values() {
+        let mut endpoint_ir = create_mock_endpoint_ir();
+        endpoint_ir.query_params[0].allowed_values = vec![json!(10), json!(50)];
+
+        let path_params = HashMap::from([(
+            "pool".to_string(),
+            "0x1234567890123456789012345678901234567890".to_string(),
+        )]);
+        let query = |limit: &str| HashMap::from([("limit".to_string(), limit.to_string())]);
+
+        assert!(build_sql_query(&endpoint_ir, &path_params, &query("10")).is_ok());
+        assert!(matches!(
+            build_sql_query(&endpoint_ir, &path_params, &query("20")),
+            Err(ApiError::BadRequest(_))
+        ));
+    }
 }
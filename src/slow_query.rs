@@ -0,0 +1,215 @@
+//! Endpoint queries slower than `server.slowQueryMs`
+//!
+//! Slow queries are logged with their SQL, bound parameters and row count, and the
+//! most recent ones are kept in memory for `/api/_slow-queries`. Parameters named
+//! like a column with a `columnPolicies` entry are redacted with that policy, so
+//! the log doesn't leak values the responses hide. Requests are logged with their
+//! client, truncated in privacy mode, and without parameters for the endpoints
+//! privacy mode skips.
+
+use crate::ai::EndpointIrResult;
+use crate::config::ColumnPolicyConfig;
+use crate::redaction;
+use crate::server::SqlParam;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Value as JsonValue, json};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Slow queries kept for `/api/_slow-queries`
+const MAX_SLOW_QUERIES: usize = 100;
+
+/// A query that took longer than the threshold
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SlowQuery {
+    pub endpoint: String,
+    pub sql: String,
+    /// Bound parameters by name, in bind order
+    pub params: Vec<(String, JsonValue)>,
+    pub rows: usize,
+    /// Address of the client, see `privacy`
+    pub client: Option<String>,
+    pub duration_ms: f64,
+    pub at: DateTime<Utc>,
+}
+
+/// Logs slow endpoint queries and keeps the latest ones
+#[derive(Debug, Clone)]
+pub struct SlowQueryLog {
+    threshold: Duration,
+    policies: Arc<Vec<ColumnPolicyConfig>>,
+    recent: Arc<Mutex<VecDeque<SlowQuery>>>,
+}
+
+impl SlowQueryLog {
+    pub fn new(threshold: Duration, policies: &[ColumnPolicyConfig]) -> Self {
+        Self {
+            threshold,
+            policies: Arc::new(policies.to_vec()),
+            recent: Arc::default(),
+        }
+    }
+
+    /// Log the query of `endpoint_ir` if it took longer than the threshold
+    pub fn observe(
+        &self,
+        endpoint_ir: &EndpointIrResult,
+        sql: &str,
+        params: &[SqlParam],
+        rows: usize,
+        duration: Duration,
+        client: Option<&str>,
+    ) {
+        if duration < self.threshold {
+            return;
+        }
+
+        let query = SlowQuery {
+            endpoint: endpoint_ir.endpoint_path.clone(),
+            sql: sql.to_string(),
+            params: self.named_params(endpoint_ir, params),
+            rows,
+            client: client.map(str::to_string),
+            duration_ms: duration.as_secs_f64() * 1000.0,
+            at: Utc::now(),
+        };
+        tracing::warn!(
+            "Slow query on {} from {} took {:.0}ms and returned {} rows: {} with params {}",
+            query.endpoint,
+            query.client.as_deref().unwrap_or("unknown client"),
+            query.duration_ms,
+            query.rows,
+            query.sql,
+            serde_json::to_string(&query.params).unwrap_or_default()
+        );
+
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        if recent.len() == MAX_SLOW_QUERIES {
+            recent.pop_front();
+        }
+        recent.push_back(query);
+    }
+
+    /// Latest slow queries, newest first
+    pub fn recent(&self) -> Vec<SlowQuery> {
+        let recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        recent.iter().rev().cloned().collect()
+    }
+
+    /// Pair parameters with the names they were bound from, redacting covered columns
+    ///
+    /// Path parameters come first, then query parameters, then the tenant if the
+    /// endpoint is tenant-scoped, matching `build_sql_query`.
+    fn named_params(
+        &self,
+        endpoint_ir: &EndpointIrResult,
+        params: &[SqlParam],
+    ) -> Vec<(String, JsonValue)> {
+        let names = endpoint_ir
+            .path_params
+            .iter()
+            .map(|param| param.name.as_str())
+            .chain(
+                endpoint_ir
+                    .query_params
+                    .iter()
+                    .map(|param| param.name.as_str()),
+            )
+            .chain(std::iter::once("tenant"));
+
+        names
+            .zip(params)
+            .map(|(name, param)| {
+                let value = param_to_json(param);
+                let policy = self.policies.iter().find(|policy| {
+                    policy.column == name
+                        && endpoint_ir
+                            .tables_referenced
+                            .iter()
+                            .any(|table| redaction::applies_to(policy, table))
+                });
+                let value = match policy {
+                    Some(policy) => redaction::redact_value(policy, &value)
+                        .unwrap_or_else(|| json!("[redacted]")),
+                    None => value,
+                };
+                (name.to_string(), value)
+            })
+            .collect()
+    }
+}
+
+fn param_to_json(param: &SqlParam) -> JsonValue {
+    match param {
+        SqlParam::String(s) => json!(s),
+        SqlParam::I64(i) => json!(i),
+        SqlParam::U64(u) => json!(u),
+        SqlParam::Bool(b) => json!(b),
+        SqlParam::Null => JsonValue::Null,
+        SqlParam::TextArray(values) => json!(values),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ColumnPolicy, PolicyStage};
+
+    #[test]
+    fn test_observe_redacts_and_caps() {
+        let policies = vec![ColumnPolicyConfig {
+            table: None,
+            column: "sender".to_string(),
+            policy: ColumnPolicy::Drop,
+            length: None,
+            at: PolicyStage::Response,
+        }];
+        let log = SlowQueryLog::new(Duration::from_millis(100), &policies);
+        let endpoint_ir: EndpointIrResult = serde_json::from_value(json!({
+            "endpoint_path": "/api/transfers/{sender}",
+            "description": "",
+            "method": "GET",
+            "path_params": [{"name": "sender", "type": "String", "description": ""}],
+            "query_params": [{"name": "limit", "type": "u32", "required": false}],
+            "response_schema": {"name": "Transfers", "fields": []},
+            "sql_query": "SELECT 1",
+            "tables_referenced": ["transfers"],
+        }))
+        .unwrap();
+        let params = [SqlParam::String("0xabc".to_string()), SqlParam::U64(50)];
+
+        log.observe(
+            &endpoint_ir,
+            "SELECT 1",
+            &params,
+            3,
+            Duration::from_millis(10),
+            None,
+        );
+        assert!(log.recent().is_empty());
+
+        for _ in 0..MAX_SLOW_QUERIES + 5 {
+            log.observe(
+                &endpoint_ir,
+                "SELECT 1",
+                &params,
+                3,
+                Duration::from_millis(250),
+                Some("10.0.0.0"),
+            );
+        }
+        let recent = log.recent();
+        assert_eq!(recent.len(), MAX_SLOW_QUERIES);
+        assert_eq!(
+            recent[0].params,
+            vec![
+                ("sender".to_string(), json!("[redacted]")),
+                ("limit".to_string(), json!(50)),
+            ]
+        );
+        assert_eq!(recent[0].rows, 3);
+        assert_eq!(recent[0].client.as_deref(), Some("10.0.0.0"));
+    }
+}
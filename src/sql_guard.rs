@@ -0,0 +1,357 @@
+use anyhow::Result;
+
+/// Keywords that make a SELECT/WITH statement write data
+///
+/// Covers data-modifying CTEs (`WITH x AS (DELETE ... RETURNING *)`) and
+/// `SELECT ... INTO new_table`. DDL can't appear inside a single SELECT/WITH
+/// statement, so it is caught by the statement checks.
+const FORBIDDEN_KEYWORDS: &[&str] = &["insert", "update", "delete", "merge", "into"];
+
+/// Check that endpoint SQL is a single read-only SELECT/WITH statement
+///
+/// Generated SQL is validated when it is created, but IR files can be edited by
+/// hand, so the server runs this again before registering an endpoint.
+pub fn check_read_only_sql(sql: &str) -> Result<()> {
+    let code = strip_literals(sql)?;
+
+    if code.contains(';') {
+        anyhow::bail!("SQL must be a single statement without semicolons");
+    }
+
+    let words: Vec<String> = code
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    match words.first().map(String::as_str) {
+        Some("select") | Some("with") => {}
+        _ => anyhow::bail!("SQL query must start with SELECT or WITH"),
+    }
+
+    if let Some(keyword) = words
+        .iter()
+        .find(|word| FORBIDDEN_KEYWORDS.contains(&word.as_str()))
+    {
+        anyhow::bail!("SQL must be read-only, found '{}'", keyword.to_uppercase());
+    }
+
+    Ok(())
+}
+
+/// Check whether the outermost statement of a query limits its rows
+///
+/// Only `LIMIT` or `FETCH` outside parentheses count, so a limit inside a CTE or
+/// subquery doesn't bound the result.
+pub fn has_top_level_limit(sql: &str) -> Result<bool> {
+    has_top_level_keyword(sql, &["limit", "fetch"])
+}
+
+/// Check whether the outermost statement of a query orders its rows
+///
+/// An `ORDER BY` inside a CTE, subquery or window doesn't count.
+pub fn has_top_level_order_by(sql: &str) -> Result<bool> {
+    has_top_level_keyword(sql, &["order"])
+}
+
+fn has_top_level_keyword(sql: &str, keywords: &[&str]) -> Result<bool> {
+    let code = strip_literals(sql)?;
+    let mut depth = 0i32;
+    let mut word = String::new();
+    for c in code.chars().chain([' ']) {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            word.push(c);
+            continue;
+        }
+        if depth == 0 && keywords.iter().any(|k| word.eq_ignore_ascii_case(k)) {
+            return Ok(true);
+        }
+        word.clear();
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    Ok(false)
+}
+
+/// Wrap a query so its rows are sorted by `order_by`, a column and direction,
+/// and it returns at most the number of rows in parameter `$limit_param`
+pub fn wrap(sql: &str, order_by: Option<(&str, &str)>, limit_param: Option<usize>) -> String {
+    let mut wrapped = format!("SELECT * FROM ({}) q", sql.trim());
+    if let Some((column, direction)) = order_by {
+        wrapped.push_str(&format!(" ORDER BY {} {}", column, direction));
+    }
+    if let Some(param_index) = limit_param {
+        wrapped.push_str(&format!(" LIMIT ${}", param_index));
+    }
+    wrapped
+}
+
+/// Blank out string literals, quoted identifiers and comments so only SQL code remains
+fn strip_literals(sql: &str) -> Result<String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut code = String::with_capacity(sql.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        match c {
+            '\'' | '"' => {
+                i = closing_quote(&chars, i)?;
+                code.push(' ');
+            }
+            '-' if next == Some('-') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                code.push(' ');
+            }
+            '/' if next == Some('*') => {
+                i = comment_end(&chars, i)?;
+                code.push(' ');
+            }
+            '$' if next.is_some_and(|n| !n.is_ascii_digit()) => {
+                // $1 is a parameter; anything else starts a dollar-quoted string
+                anyhow::bail!("Dollar-quoted strings are not supported in endpoint SQL");
+            }
+            _ => code.push(c),
+        }
+        i += 1;
+    }
+
+    Ok(code)
+}
+
+/// A token of SQL code; comments and whitespace are skipped
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    /// Keyword or unquoted identifier, lowercased
+    Word(String),
+    /// Quoted identifier, lowercased like unquoted ones so `"Swaps"` matches `swaps`
+    QuotedIdent(String),
+    /// Numeric constant, or the digits on one side of its decimal point
+    Number(String),
+    /// String literal
+    Literal,
+    /// Positional parameter, `$1` is 1
+    Param(usize),
+    /// Any other character, e.g. `.`, `,` or `(`
+    Symbol(char),
+}
+
+impl Token {
+    /// Identifier the token names, quoted or not
+    pub fn ident(&self) -> Option<&str> {
+        match self {
+            Token::Word(word) | Token::QuotedIdent(word) => Some(word),
+            _ => None,
+        }
+    }
+
+    /// Check if the token is the unquoted keyword `keyword`, given in lowercase
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Word(word) if word == keyword)
+    }
+}
+
+/// Split SQL into tokens, so checks can't be fooled by quoting, spacing or comments
+pub fn tokenize(sql: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        match c {
+            '\'' => {
+                i = closing_quote(&chars, i)?;
+                tokens.push(Token::Literal);
+            }
+            '"' => {
+                let end = closing_quote(&chars, i)?;
+                let name: String = chars[i + 1..end].iter().collect();
+                tokens.push(Token::QuotedIdent(
+                    name.replace("\"\"", "\"").to_lowercase(),
+                ));
+                i = end;
+            }
+            '-' if next == Some('-') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if next == Some('*') => i = comment_end(&chars, i)?,
+            '$' if next.is_some_and(|n| n.is_ascii_digit()) => {
+                let start = i + 1;
+                while chars.get(i + 1).is_some_and(|n| n.is_ascii_digit()) {
+                    i += 1;
+                }
+                let number: String = chars[start..=i].iter().collect();
+                tokens.push(Token::Param(number.parse()?));
+            }
+            '$' if next.is_some() => {
+                anyhow::bail!("Dollar-quoted strings are not supported in endpoint SQL");
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                let start = i;
+                while chars
+                    .get(i + 1)
+                    .is_some_and(|n| n.is_ascii_alphanumeric() || *n == '_')
+                {
+                    i += 1;
+                }
+                let word: String = chars[start..=i].iter().collect();
+                tokens.push(if c.is_ascii_digit() {
+                    Token::Number(word)
+                } else {
+                    Token::Word(word.to_lowercase())
+                });
+            }
+            c if c.is_whitespace() => {}
+            _ => tokens.push(Token::Symbol(c)),
+        }
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+/// Index of the quote closing the string or identifier opened at `open`
+///
+/// Quotes are escaped by doubling them.
+fn closing_quote(chars: &[char], open: usize) -> Result<usize> {
+    let quote = chars[open];
+    let mut i = open + 1;
+    loop {
+        match chars.get(i) {
+            None => anyhow::bail!("Unterminated quoted string in SQL"),
+            Some(&c) if c == quote && chars.get(i + 1) == Some(&quote) => i += 2,
+            Some(&c) if c == quote => return Ok(i),
+            Some(_) => i += 1,
+        }
+    }
+}
+
+/// Index of the `/` ending the block comment opened at `open`, which may nest
+fn comment_end(chars: &[char], open: usize) -> Result<usize> {
+    let mut depth = 0;
+    let mut i = open;
+    loop {
+        match (chars.get(i), chars.get(i + 1)) {
+            (None, _) => anyhow::bail!("Unterminated comment in SQL"),
+            (Some('/'), Some('*')) => {
+                depth += 1;
+                i += 1;
+            }
+            (Some('*'), Some('/')) => {
+                depth -= 1;
+                i += 1;
+                if depth == 0 {
+                    return Ok(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_read_only_queries() {
+        assert!(check_read_only_sql("SELECT * FROM swaps WHERE pool = $1 LIMIT $2").is_ok());
+        assert!(
+            check_read_only_sql(
+                "WITH recent AS (SELECT * FROM swaps) SELECT * FROM recent -- latest swaps"
+            )
+            .is_ok()
+        );
+        // Keywords and semicolons inside literals and identifiers are fine
+        assert!(
+            check_read_only_sql("SELECT 'delete; drop' AS \"update\" FROM t /* insert; */").is_ok()
+        );
+        assert!(check_read_only_sql("SELECT updated_at, 'it''s' FROM t").is_ok());
+    }
+
+    #[test]
+    fn test_top_level_limit() {
+        assert!(has_top_level_limit("SELECT * FROM swaps ORDER BY 1 LIMIT $1").unwrap());
+        assert!(has_top_level_limit("SELECT * FROM swaps FETCH FIRST 10 ROWS ONLY").unwrap());
+        assert!(
+            !has_top_level_limit(
+                "WITH recent AS (SELECT * FROM swaps LIMIT 10) SELECT * FROM recent"
+            )
+            .unwrap()
+        );
+        assert!(!has_top_level_limit("SELECT 'limit' AS \"limit\" FROM swaps").unwrap());
+        assert_eq!(
+            wrap("SELECT * FROM swaps\n", None, Some(2)),
+            "SELECT * FROM (SELECT * FROM swaps) q LIMIT $2"
+        );
+    }
+
+    #[test]
+    fn test_top_level_order_by() {
+        assert!(has_top_level_order_by("SELECT * FROM swaps ORDER BY 1 LIMIT $1").unwrap());
+        assert!(
+            !has_top_level_order_by(
+                "SELECT pool, row_number() OVER (ORDER BY amount) FROM swaps LIMIT $1"
+            )
+            .unwrap()
+        );
+        assert!(!has_top_level_order_by("SELECT 'order' AS \"order\" FROM swaps").unwrap());
+        assert_eq!(
+            wrap(
+                "SELECT * FROM swaps",
+                Some(("block_number", "DESC")),
+                Some(1)
+            ),
+            "SELECT * FROM (SELECT * FROM swaps) q ORDER BY block_number DESC LIMIT $1"
+        );
+    }
+
+    #[test]
+    fn test_rejects_writes_and_multiple_statements() {
+        assert!(check_read_only_sql("DELETE FROM swaps").is_err());
+        assert!(check_read_only_sql("SELECT 1; DELETE FROM swaps").is_err());
+        assert!(check_read_only_sql("SELECT * FROM swaps;").is_err());
+        assert!(
+            check_read_only_sql("WITH gone AS (DELETE FROM swaps RETURNING *) SELECT * FROM gone")
+                .is_err()
+        );
+        assert!(check_read_only_sql("SELECT * INTO backup FROM swaps").is_err());
+        assert!(check_read_only_sql("SELECT $$; DROP TABLE swaps; $$").is_err());
+        assert!(check_read_only_sql("SELECT 'unterminated FROM t").is_err());
+        assert!(check_read_only_sql("/* SELECT */ UPDATE swaps SET x = 1").is_err());
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("SELECT \"Public\" . swaps/* x */, 'a''b' -- c\nFROM t LIMIT $12").unwrap(),
+            vec![
+                Token::Word("select".to_string()),
+                Token::QuotedIdent("public".to_string()),
+                Token::Symbol('.'),
+                Token::Word("swaps".to_string()),
+                Token::Symbol(','),
+                Token::Literal,
+                Token::Word("from".to_string()),
+                Token::Word("t".to_string()),
+                Token::Word("limit".to_string()),
+                Token::Param(12),
+            ]
+        );
+        assert!(tokenize("SELECT $$x$$").is_err());
+        assert!(tokenize("SELECT \"unterminated").is_err());
+    }
+}
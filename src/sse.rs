@@ -0,0 +1,223 @@
+//! New rows of a table as server-sent events, served at `/sse/{table}`
+//!
+//! Rows are polled in `(block_number, log_index)` order and each event's id is
+//! `<block_number>:<log_index>`, so a client reconnecting with `Last-Event-ID`
+//! resumes right after the last row it received. Without it the stream starts at
+//! the newest row in the table.
+
+use crate::redaction::ResponseRedactions;
+use crate::schema_state::TableState;
+use axum::response::sse::Event;
+use futures::Stream;
+use serde_json::Value as JsonValue;
+use sqlx::PgPool;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often a table is checked for new rows
+pub const SSE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Rows read per poll, a full batch is followed by another poll right away
+const SSE_BATCH_SIZE: usize = 500;
+
+/// Position of a row in the stream, after which the next event starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RowCursor {
+    pub block_number: i64,
+    pub log_index: i64,
+}
+
+impl RowCursor {
+    /// Parse an event id, e.g. from `Last-Event-ID`
+    pub fn parse(id: &str) -> Option<Self> {
+        let (block_number, log_index) = id.trim().split_once(':')?;
+        Some(Self {
+            block_number: block_number.parse().ok()?,
+            log_index: log_index.parse().ok()?,
+        })
+    }
+
+    /// Position of the newest row in `table`, before the first row if it is empty
+    pub async fn tip(pool: &PgPool, table: &TableState) -> Result<Self, sqlx::Error> {
+        let tip: Option<(i64, i64)> = sqlx::query_as(&format!(
+            "SELECT block_number::BIGINT, log_index::BIGINT FROM \"{}\" \
+             ORDER BY block_number DESC, log_index DESC LIMIT 1",
+            table.name
+        ))
+        .fetch_optional(pool)
+        .await?;
+        let (block_number, log_index) = tip.unwrap_or((-1, -1));
+        Ok(Self {
+            block_number,
+            log_index,
+        })
+    }
+}
+
+impl fmt::Display for RowCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.block_number, self.log_index)
+    }
+}
+
+/// Only rows of one tenant, see `tenancy`
+#[derive(Debug, Clone)]
+pub struct TenantScope {
+    pub column: String,
+    pub data_type: String,
+    pub tenant: String,
+}
+
+/// Rows of a table after a cursor
+pub struct TableStream {
+    pool: PgPool,
+    table: String,
+    sql: String,
+    tenant: Option<String>,
+    redactions: Arc<ResponseRedactions>,
+    cursor: RowCursor,
+}
+
+impl TableStream {
+    pub fn new(
+        pool: PgPool,
+        table: &TableState,
+        tenant: Option<TenantScope>,
+        redactions: Arc<ResponseRedactions>,
+        cursor: RowCursor,
+    ) -> Self {
+        Self {
+            pool,
+            table: table.name.clone(),
+            sql: select_sql(table, tenant.as_ref(), SSE_BATCH_SIZE),
+            tenant: tenant.map(|scope| scope.tenant),
+            redactions,
+            cursor,
+        }
+    }
+
+    /// Events for every row after the cursor, polling for new ones forever
+    pub fn into_events(self) -> impl Stream<Item = Result<Event, Infallible>> {
+        futures::stream::unfold(
+            (self, VecDeque::new(), true),
+            |(mut stream, mut pending, mut poll_now)| async move {
+                loop {
+                    if let Some(event) = pending.pop_front() {
+                        return Some((Ok(event), (stream, pending, poll_now)));
+                    }
+                    if !poll_now {
+                        tokio::time::sleep(SSE_POLL_INTERVAL).await;
+                    }
+                    match stream.next_events().await {
+                        Ok(events) => {
+                            poll_now = events.len() == SSE_BATCH_SIZE;
+                            pending.extend(events);
+                        }
+                        Err(e) => {
+                            poll_now = false;
+                            tracing::warn!("Failed to read new rows of {}: {}", stream.table, e);
+                        }
+                    }
+                }
+            },
+        )
+    }
+
+    async fn next_events(&mut self) -> Result<Vec<Event>, sqlx::Error> {
+        let mut query = sqlx::query_as::<_, (sqlx::types::Json<JsonValue>, i64, i64)>(&self.sql)
+            .bind(self.cursor.block_number)
+            .bind(self.cursor.log_index);
+        if let Some(tenant) = &self.tenant {
+            query = query.bind(tenant);
+        }
+        let rows = query.fetch_all(&self.pool).await?;
+
+        let mut events = Vec::with_capacity(rows.len());
+        for (sqlx::types::Json(mut row), block_number, log_index) in rows {
+            self.cursor = RowCursor {
+                block_number,
+                log_index,
+            };
+            if let JsonValue::Object(fields) = &mut row {
+                self.redactions.redact_table_row(&self.table, fields);
+            }
+            events.push(
+                Event::default()
+                    .event("row")
+                    .id(self.cursor.to_string())
+                    .data(row.to_string()),
+            );
+        }
+        Ok(events)
+    }
+}
+
+/// Query for up to `limit` rows after `($1, $2)`, with the tenant as `$3`
+///
+/// Each row is returned as a JSON object with its position. NUMERIC columns are
+/// read as text, so 256-bit values keep their precision.
+pub(crate) fn select_sql(table: &TableState, tenant: Option<&TenantScope>, limit: usize) -> String {
+    let columns = table
+        .columns
+        .iter()
+        .map(|column| {
+            if column.column_type.to_uppercase().starts_with("NUMERIC") {
+                format!("\"{0}\"::text AS \"{0}\"", column.name)
+            } else {
+                format!("\"{}\"", column.name)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let tenant_filter = tenant
+        .map(|scope| format!(" AND \"{}\" = $3::{}", scope.column, scope.data_type))
+        .unwrap_or_default();
+
+    format!(
+        "SELECT to_jsonb(r), r.block_number::BIGINT, r.log_index::BIGINT FROM (\
+         SELECT {columns} FROM \"{table}\" \
+         WHERE (block_number, log_index) > ($1, $2){tenant_filter} \
+         ORDER BY block_number, log_index LIMIT {limit}\
+         ) r ORDER BY r.block_number, r.log_index",
+        table = table.name,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema_state::ColumnState;
+
+    #[test]
+    fn test_cursor_and_select_sql() {
+        let cursor = RowCursor::parse("19000000:12").unwrap();
+        assert_eq!(cursor.to_string(), "19000000:12");
+        assert!(RowCursor::parse("19000000").is_none());
+
+        let mut table =
+            TableState::new("swaps".to_string(), "Pool".to_string(), "Swap".to_string());
+        for (name, column_type) in [
+            ("block_number", "BIGINT NOT NULL"),
+            ("log_index", "INTEGER NOT NULL"),
+            ("amount", "NUMERIC(78, 0) NOT NULL"),
+        ] {
+            table.add_column(ColumnState::new(name.to_string(), column_type.to_string()));
+        }
+        let tenant = TenantScope {
+            column: "pool".to_string(),
+            data_type: "character varying".to_string(),
+            tenant: "0xabc".to_string(),
+        };
+
+        let sql = select_sql(&table, Some(&tenant), 500);
+        assert!(sql.contains(
+            "SELECT \"block_number\", \"log_index\", \"amount\"::text AS \"amount\" FROM \"swaps\""
+        ));
+        assert!(sql.contains(
+            "WHERE (block_number, log_index) > ($1, $2) AND \"pool\" = $3::character varying"
+        ));
+    }
+}
@@ -0,0 +1,289 @@
+//! Start blocks relative to the chain
+//!
+//! Besides a block number, a spec's `startBlock` can be `"latest"`, a number of
+//! blocks behind the head such as `"latest-10000"`, or an ISO date
+//! (`"2024-06-01"` or `"2024-06-01T12:00:00Z"`) for the first block at or after
+//! it. The indexer resolves relative starts against the chain the first time it
+//! runs a spec and records the block in the start blocks table, so restarts and
+//! `smorty status` keep using the same block.
+
+use crate::ai::IrGenerationResult;
+use crate::config::Config;
+use crate::ir::Ir;
+use crate::rpc_logs;
+use alloy::providers::Provider;
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::{PgPool, Row};
+use std::fmt;
+use std::str::FromStr;
+
+/// Blocks relative starts resolved to, by spec and configured start
+pub const START_BLOCKS_TABLE: &str = "smorty_start_blocks";
+
+/// Where a spec starts indexing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartBlock {
+    /// A fixed block number
+    Block(u64),
+    /// This many blocks before the chain head
+    Latest { behind: u64 },
+    /// The first block at or after this time
+    Date(DateTime<Utc>),
+}
+
+impl StartBlock {
+    /// Block number of a fixed start
+    pub fn block(&self) -> Option<u64> {
+        match self {
+            Self::Block(block) => Some(*block),
+            _ => None,
+        }
+    }
+
+    /// Whether the start has to be resolved against the chain
+    pub fn is_relative(&self) -> bool {
+        self.block().is_none()
+    }
+
+    /// Block the start resolves to on the chain `provider` serves
+    async fn resolve_on_chain<P: Provider>(&self, provider: &P) -> Result<u64> {
+        let head = provider
+            .get_block_number()
+            .await
+            .context("Failed to fetch the chain head")?;
+        match self {
+            Self::Block(block) => Ok(*block),
+            Self::Latest { behind } => Ok(head.saturating_sub(*behind)),
+            Self::Date(date) => {
+                first_block_at(provider, head, date.timestamp().max(0) as u64).await
+            }
+        }
+    }
+}
+
+impl FromStr for StartBlock {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Ok(block) = s.parse() {
+            return Ok(Self::Block(block));
+        }
+        if let Some(rest) = s.strip_prefix("latest") {
+            let behind = match rest.trim_start().strip_prefix('-') {
+                Some(behind) => behind.trim().parse().context(format!(
+                    "Invalid start block '{}', expected latest-<blocks>",
+                    s
+                ))?,
+                None if rest.is_empty() => 0,
+                None => anyhow::bail!("Invalid start block '{}', expected latest-<blocks>", s),
+            };
+            return Ok(Self::Latest { behind });
+        }
+        if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+            return Ok(Self::Date(date.with_timezone(&Utc)));
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(Self::Date(date.and_time(Default::default()).and_utc()));
+        }
+        anyhow::bail!(
+            "Invalid start block '{}', expected a block number, latest, latest-<blocks> or an ISO date",
+            s
+        )
+    }
+}
+
+impl fmt::Display for StartBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Block(block) => write!(f, "{}", block),
+            Self::Latest { behind: 0 } => write!(f, "latest"),
+            Self::Latest { behind } => write!(f, "latest-{}", behind),
+            Self::Date(date) => write!(f, "{}", date.to_rfc3339()),
+        }
+    }
+}
+
+impl Serialize for StartBlock {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Block(block) => serializer.serialize_u64(*block),
+            other => serializer.collect_str(other),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for StartBlock {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Block(u64),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Block(block) => Ok(Self::Block(block)),
+            Raw::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Configured start of a spec, if any
+fn configured(config: &Config, contract_name: &str, spec_name: &str) -> Option<StartBlock> {
+    config.spec(contract_name, spec_name)?.start_block
+}
+
+/// Block a spec's relative start resolved to, recording it the first time
+///
+/// A recorded block is only reused while the configured start stays the same.
+async fn resolve<P: Provider>(
+    pool: &PgPool,
+    provider: &P,
+    spec: &str,
+    start: StartBlock,
+) -> Result<u64> {
+    if let Some(block) = start.block() {
+        return Ok(block);
+    }
+    if let Some(block) = recorded(pool, spec, start).await? {
+        return Ok(block);
+    }
+
+    let block = start.resolve_on_chain(provider).await.context(format!(
+        "Failed to resolve start block '{}' of {}",
+        start, spec
+    ))?;
+    sqlx::query(&format!(
+        "INSERT INTO {} (spec, start, block, resolved_at) VALUES ($1, $2, $3, NOW())
+         ON CONFLICT (spec) DO UPDATE SET start = $2, block = $3, resolved_at = NOW()",
+        START_BLOCKS_TABLE
+    ))
+    .bind(spec)
+    .bind(start.to_string())
+    .bind(block as i64)
+    .execute(pool)
+    .await
+    .context("Failed to record start block")?;
+    tracing::info!("Start block '{}' of {} resolved to {}", start, spec, block);
+    Ok(block)
+}
+
+/// Block recorded for a spec's relative start, if it was resolved before
+async fn recorded(pool: &PgPool, spec: &str, start: StartBlock) -> Result<Option<u64>> {
+    let row = sqlx::query(&format!(
+        "SELECT block FROM {} WHERE spec = $1 AND start = $2",
+        START_BLOCKS_TABLE
+    ))
+    .bind(spec)
+    .bind(start.to_string())
+    .fetch_optional(pool)
+    .await
+    .context("Failed to load start block")?;
+    row.map(|row| Ok(row.try_get::<i64, _>("block")? as u64))
+        .transpose()
+}
+
+/// Set the start block of an IR spec with a relative start to the one recorded
+///
+/// A spec not resolved yet keeps the start block of its IR.
+pub async fn apply_recorded(
+    pool: &PgPool,
+    config: &Config,
+    ir_spec: &mut (String, String, IrGenerationResult),
+) -> Result<()> {
+    let (contract_name, spec_name, ir) = ir_spec;
+    let Some(start) = configured(config, contract_name, spec_name).filter(StartBlock::is_relative)
+    else {
+        return Ok(());
+    };
+    let source = Ir::source_name(contract_name, spec_name);
+    if let Some(block) = recorded(pool, &source, start).await? {
+        ir.start_block = block;
+    }
+    Ok(())
+}
+
+/// Set the start block of an IR spec with a relative start, resolving it on the chain
+pub async fn apply<P: Provider>(
+    pool: &PgPool,
+    provider: &P,
+    config: &Config,
+    ir_spec: &mut (String, String, IrGenerationResult),
+) -> Result<()> {
+    let (contract_name, spec_name, ir) = ir_spec;
+    let Some(start) = configured(config, contract_name, spec_name).filter(StartBlock::is_relative)
+    else {
+        return Ok(());
+    };
+    let source = Ir::source_name(contract_name, spec_name);
+    ir.start_block = resolve(pool, provider, &source, start).await?;
+    Ok(())
+}
+
+/// First block with a timestamp at or after `timestamp`, by binary search up to `head`
+async fn first_block_at<P: Provider>(provider: &P, head: u64, timestamp: u64) -> Result<u64> {
+    if rpc_logs::block_timestamp(provider, head).await? < timestamp {
+        anyhow::bail!("The date is after the chain head");
+    }
+
+    let (mut low, mut high) = (0, head);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if rpc_logs::block_timestamp(provider, mid).await? < timestamp {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(low)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parses_fixed_and_relative_starts() {
+        assert_eq!(
+            "1000".parse::<StartBlock>().unwrap(),
+            StartBlock::Block(1000)
+        );
+        assert_eq!(
+            "latest".parse::<StartBlock>().unwrap(),
+            StartBlock::Latest { behind: 0 }
+        );
+        assert_eq!(
+            "latest-10000".parse::<StartBlock>().unwrap(),
+            StartBlock::Latest { behind: 10_000 }
+        );
+        let june = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            "2024-06-01".parse::<StartBlock>().unwrap(),
+            StartBlock::Date(june)
+        );
+        assert_eq!(
+            "2024-06-01T02:00:00+02:00".parse::<StartBlock>().unwrap(),
+            StartBlock::Date(june)
+        );
+        for invalid in ["latest+5", "latest-x", "yesterday", "-5"] {
+            assert!(invalid.parse::<StartBlock>().is_err(), "{}", invalid);
+        }
+
+        // Block numbers stay TOML integers, the rest are strings
+        #[derive(Deserialize)]
+        struct Spec {
+            start: StartBlock,
+        }
+        for start in ["1000", "\"latest-10000\"", "\"2024-06-01T00:00:00+00:00\""] {
+            let spec: Spec = toml::from_str(&format!("start = {}", start)).unwrap();
+            assert_eq!(
+                toml::Value::try_from(spec.start).unwrap().to_string(),
+                start
+            );
+        }
+    }
+}
@@ -0,0 +1,357 @@
+//! Current-state tables folded from event tables
+//!
+//! A spec with a `state` section keeps a second table with one row per key, such
+//! as a balance per account built from `Transfer` events. The fold is a list of
+//! deltas: each event adds a value to one or more keys, and a key's state is the
+//! sum of its deltas. Folds come from a built-in template or are generated from the
+//! state's task by `gen-spec` and stored in the spec IR.
+//!
+//! The indexer never adds deltas to stored values. After each chunk it recomputes
+//! every key touched by new events, or last updated at or after the chunk's first
+//! block, from the whole event table. Events dropped by a reorg therefore leave
+//! the state the next time their range is indexed.
+
+use crate::ai::{ColumnDef, IrGenerationResult};
+use crate::config::{FoldTemplate, StateConfig};
+use crate::index_def::IndexDef;
+use crate::sql_guard;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+/// Keys recomputed by a refresh, dropped at the end of its transaction
+const TOUCHED_KEYS_TABLE: &str = "smorty_touched_keys";
+
+/// How a spec's events fold into its state table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateFold {
+    pub table_name: String,
+    /// Columns identifying a row of the state table
+    pub key_columns: Vec<ColumnDef>,
+    /// NUMERIC column holding the sum of the deltas
+    pub value_column: String,
+    pub deltas: Vec<FoldDelta>,
+}
+
+/// Value an event adds to a key, as SQL expressions over the event table's columns
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FoldDelta {
+    /// One expression per key column
+    pub keys: Vec<String>,
+    pub value: String,
+}
+
+impl StateFold {
+    /// Build the fold of a template from the event columns named in the config
+    pub fn from_template(state: &StateConfig, ir: &IrGenerationResult) -> Result<Self> {
+        let template = state.fold.context("The state has no fold template")?;
+        let column = |name: &Option<String>| -> Result<ColumnDef> {
+            let name = name.as_deref().unwrap_or_default();
+            ir.table_schema
+                .columns
+                .iter()
+                .find(|column| column.name == name)
+                .cloned()
+                .context(format!(
+                    "'{}' is not a column of table '{}'",
+                    name, ir.table_schema.table_name
+                ))
+        };
+
+        let (key_columns, value_column, deltas) = match template {
+            FoldTemplate::Balance => {
+                let from = column(&state.from)?;
+                let to = column(&state.to)?;
+                let amount = column(&state.amount)?;
+                let account = ColumnDef {
+                    name: "account".to_string(),
+                    column_type: from.column_type,
+                };
+                let deltas = vec![
+                    FoldDelta {
+                        keys: vec![from.name],
+                        value: format!("-({})::NUMERIC", amount.name),
+                    },
+                    FoldDelta {
+                        keys: vec![to.name],
+                        value: format!("({})::NUMERIC", amount.name),
+                    },
+                ];
+                (vec![account], "balance", deltas)
+            }
+            FoldTemplate::Count => {
+                let key = column(&state.key)?;
+                let deltas = vec![FoldDelta {
+                    keys: vec![key.name.clone()],
+                    value: "1".to_string(),
+                }];
+                (vec![key], "event_count", deltas)
+            }
+            FoldTemplate::Sum => {
+                let key = column(&state.key)?;
+                let amount = column(&state.amount)?;
+                let deltas = vec![FoldDelta {
+                    keys: vec![key.name.clone()],
+                    value: format!("({})::NUMERIC", amount.name),
+                }];
+                (vec![key], "total", deltas)
+            }
+        };
+
+        Ok(Self {
+            table_name: state.table.clone(),
+            key_columns,
+            value_column: value_column.to_string(),
+            deltas,
+        })
+    }
+
+    /// Check names and expressions before they are used to build SQL
+    pub fn validate(&self, event_table: &str) -> Result<()> {
+        let names = std::iter::once(self.table_name.as_str())
+            .chain(self.key_columns.iter().map(|column| column.name.as_str()))
+            .chain(std::iter::once(self.value_column.as_str()));
+        for name in names {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                anyhow::bail!("'{}' is not a valid identifier", name);
+            }
+        }
+        if self.key_columns.is_empty() {
+            anyhow::bail!("The fold has no key columns");
+        }
+        if self
+            .key_columns
+            .iter()
+            .any(|column| column.name == self.value_column || column.name == "block_number")
+        {
+            anyhow::bail!(
+                "Key columns can't be named '{}' or 'block_number'",
+                self.value_column
+            );
+        }
+        if self.deltas.is_empty() {
+            anyhow::bail!("The fold has no deltas");
+        }
+        for delta in &self.deltas {
+            if delta.keys.len() != self.key_columns.len() {
+                anyhow::bail!(
+                    "Delta has {} key expressions for {} key columns",
+                    delta.keys.len(),
+                    self.key_columns.len()
+                );
+            }
+        }
+
+        // Expressions are spliced into queries, so each must be a plain read
+        sql_guard::check_read_only_sql(&self.deltas_sql(event_table, ""))
+            .context("Invalid fold expression")
+    }
+
+    /// Columns of the state table: the keys, the value and the block of the last event
+    pub fn columns(&self) -> Vec<ColumnDef> {
+        let mut columns = self.key_columns.clone();
+        columns.push(ColumnDef {
+            name: self.value_column.clone(),
+            column_type: "NUMERIC NOT NULL".to_string(),
+        });
+        columns.push(ColumnDef {
+            name: "block_number".to_string(),
+            column_type: "BIGINT NOT NULL".to_string(),
+        });
+        columns
+    }
+
+    /// Unique index on the keys and an index to find recently updated rows
+    pub fn indexes(&self) -> Vec<IndexDef> {
+        let keys: Vec<&str> = self
+            .key_columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect();
+        let mut key_index = IndexDef::new("idx_state_key", &keys);
+        key_index.unique = true;
+        vec![
+            key_index,
+            IndexDef::new("idx_state_block_number", &["block_number"]),
+        ]
+    }
+
+    /// Recompute the keys touched since `from_block` from the whole event table
+    ///
+    /// Events after the newest state row are always included, so a range whose
+    /// refresh failed is picked up by the next one.
+    pub async fn refresh(&self, pool: &PgPool, event_table: &str, from_block: u64) -> Result<()> {
+        let [touched, delete, insert] = self.refresh_sql(event_table);
+        let context = || format!("Failed to refresh state table {}", self.table_name);
+
+        let mut tx = pool.begin().await?;
+        sqlx::query(&touched)
+            .bind(from_block as i64)
+            .execute(&mut *tx)
+            .await
+            .with_context(context)?;
+        sqlx::query(&delete)
+            .execute(&mut *tx)
+            .await
+            .with_context(context)?;
+        sqlx::query(&insert)
+            .execute(&mut *tx)
+            .await
+            .with_context(context)?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Statements of a refresh: collect the touched keys, given the first block as `$1`,
+    /// delete their rows and insert them again
+    fn refresh_sql(&self, event_table: &str) -> [String; 3] {
+        let keys = self
+            .key_columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        [
+            format!(
+                "CREATE TEMP TABLE {touched} ON COMMIT DROP AS \
+                 SELECT {keys} FROM ({new_deltas}) d \
+                 UNION SELECT {keys} FROM {state} WHERE block_number >= $1",
+                touched = TOUCHED_KEYS_TABLE,
+                keys = keys,
+                new_deltas = self.deltas_sql(
+                    event_table,
+                    &format!(
+                        " WHERE block_number >= LEAST($1, (SELECT COALESCE(MAX(block_number) + 1, 0) FROM {}))",
+                        self.table_name
+                    )
+                ),
+                state = self.table_name,
+            ),
+            format!(
+                "DELETE FROM {state} WHERE ({keys}) IN (SELECT {keys} FROM {touched})",
+                state = self.table_name,
+                keys = keys,
+                touched = TOUCHED_KEYS_TABLE,
+            ),
+            format!(
+                "INSERT INTO {state} ({keys}, {value}, block_number) \
+                 SELECT {keys}, SUM({value}), MAX(block_number) FROM ({all_deltas}) d \
+                 WHERE ({keys}) IN (SELECT {keys} FROM {touched}) \
+                 GROUP BY {keys}",
+                state = self.table_name,
+                keys = keys,
+                value = self.value_column,
+                all_deltas = self.deltas_sql(event_table, ""),
+                touched = TOUCHED_KEYS_TABLE,
+            ),
+        ]
+    }
+
+    /// Every delta of the events matching `filter`, as rows of keys, value and block
+    fn deltas_sql(&self, event_table: &str, filter: &str) -> String {
+        self.deltas
+            .iter()
+            .map(|delta| {
+                let keys = delta
+                    .keys
+                    .iter()
+                    .zip(&self.key_columns)
+                    .map(|(expression, column)| format!("{} AS {}", expression, column.name))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "SELECT {}, {} AS {}, block_number FROM {}{}",
+                    keys, delta.value, self.value_column, event_table, filter
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" UNION ALL ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::TableSchema;
+
+    fn transfer_ir() -> IrGenerationResult {
+        let column = |name: &str, column_type: &str| ColumnDef {
+            name: name.to_string(),
+            column_type: column_type.to_string(),
+        };
+        IrGenerationResult {
+            event_name: "Transfer".to_string(),
+            event_signature: "Transfer(address,address,uint256)".to_string(),
+            start_block: 0,
+            contract_address: "0x0000000000000000000000000000000000000001".to_string(),
+            chain: "mainnet".to_string(),
+            indexed_fields: vec![],
+            table_schema: TableSchema {
+                table_name: "transfers".to_string(),
+                columns: vec![
+                    column("from_address", "TEXT NOT NULL"),
+                    column("to_address", "TEXT NOT NULL"),
+                    column("value", "NUMERIC(78, 0) NOT NULL"),
+                ],
+                indexes: vec![],
+                computed_columns: vec![],
+            },
+            description: String::new(),
+            abi_hash: None,
+            state_fold: None,
+        }
+    }
+
+    fn balance_state() -> StateConfig {
+        StateConfig {
+            table: "balances".to_string(),
+            fold: Some(FoldTemplate::Balance),
+            key: None,
+            from: Some("from_address".to_string()),
+            to: Some("to_address".to_string()),
+            amount: Some("value".to_string()),
+            task: None,
+        }
+    }
+
+    #[test]
+    fn test_balance_template() {
+        let fold = StateFold::from_template(&balance_state(), &transfer_ir()).unwrap();
+        fold.validate("transfers").unwrap();
+
+        assert_eq!(fold.key_columns[0].name, "account");
+        assert_eq!(fold.key_columns[0].column_type, "TEXT NOT NULL");
+        assert_eq!(
+            fold.deltas_sql("transfers", ""),
+            "SELECT from_address AS account, -(value)::NUMERIC AS balance, block_number FROM transfers \
+             UNION ALL SELECT to_address AS account, (value)::NUMERIC AS balance, block_number FROM transfers"
+        );
+
+        let [touched, delete, insert] = fold.refresh_sql("transfers");
+        assert!(touched.contains("UNION SELECT account FROM balances WHERE block_number >= $1"));
+        assert!(delete.starts_with("DELETE FROM balances WHERE (account) IN"));
+        assert!(insert.contains("SUM(balance), MAX(block_number)"));
+
+        let missing = StateConfig {
+            amount: Some("amount".to_string()),
+            ..balance_state()
+        };
+        assert!(StateFold::from_template(&missing, &transfer_ir()).is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_unsafe_folds() {
+        let mut fold = StateFold::from_template(&balance_state(), &transfer_ir()).unwrap();
+        fold.deltas[0].value = "1; DROP TABLE transfers".to_string();
+        assert!(fold.validate("transfers").is_err());
+
+        let mut fold = StateFold::from_template(&balance_state(), &transfer_ir()).unwrap();
+        fold.deltas[0].keys.push("to_address".to_string());
+        assert!(fold.validate("transfers").is_err());
+
+        let mut fold = StateFold::from_template(&balance_state(), &transfer_ir()).unwrap();
+        fold.table_name = "balances; --".to_string();
+        assert!(fold.validate("transfers").is_err());
+    }
+}
@@ -0,0 +1,613 @@
+//! Import of The Graph subgraphs, for `smorty import subgraph`
+//!
+//! Each `ethereum/contract` data source of `subgraph.yaml` becomes a contract,
+//! with a spec per event handler. The specs' IRs are built from the ABI events
+//! directly, storing every event parameter, so no AI call is needed to start
+//! indexing. Each `@entity` type of `schema.graphql` becomes a suggested endpoint,
+//! whose task describes the entity for `gen-endpoint` to rebuild with SQL.
+//!
+//! Config entries are written to a separate stub file to review and merge into
+//! `config.toml`, since mapping logic in AssemblyScript can't be carried over.
+
+use crate::abi;
+use crate::ai::{ColumnDef, EventField, IrGenerationResult, TableSchema};
+use crate::artifact;
+use crate::index_def::IndexDef;
+use crate::ir::SPECS_DIR;
+use crate::manifest::MANIFEST_ABI_DIR;
+use alloy::json_abi::Event;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+/// Longest identifier Postgres keeps without truncating
+const MAX_TABLE_NAME_LEN: usize = 63;
+
+#[derive(Debug, Deserialize)]
+struct SubgraphManifest {
+    #[serde(rename = "dataSources", default)]
+    data_sources: Vec<DataSource>,
+    /// Data sources created at runtime, without a fixed address
+    #[serde(default)]
+    templates: Vec<DataSource>,
+    schema: Option<SchemaFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SchemaFile {
+    file: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DataSource {
+    kind: String,
+    name: String,
+    network: String,
+    source: Source,
+    mapping: Mapping,
+}
+
+#[derive(Debug, Deserialize)]
+struct Source {
+    address: Option<String>,
+    abi: String,
+    #[serde(rename = "startBlock")]
+    start_block: Option<u64>,
+    #[serde(default, rename = "endBlock")]
+    end_block: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Mapping {
+    #[serde(default)]
+    entities: Vec<String>,
+    abis: Vec<AbiFile>,
+    #[serde(rename = "eventHandlers", default)]
+    event_handlers: Vec<EventHandler>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AbiFile {
+    name: String,
+    file: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventHandler {
+    event: String,
+    handler: String,
+}
+
+/// An `@entity` type of the subgraph schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+    pub name: String,
+    /// Field names and GraphQL types, without derived fields
+    pub fields: Vec<(String, String)>,
+}
+
+/// What `import` wrote
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub contracts: usize,
+    pub specs: usize,
+    pub endpoints: usize,
+    /// Templates, which have no address to index
+    pub skipped_templates: Vec<String>,
+}
+
+/// A spec imported from an event handler
+struct ImportedSpec {
+    name: String,
+    handler: String,
+    ir: IrGenerationResult,
+}
+
+/// Import the subgraph in `dir`, writing ABIs, spec IRs and the config stub `out`
+///
+/// Existing ABI and IR files are kept, so an import can be rerun after editing them.
+pub fn import(dir: &Path, out: &Path) -> Result<ImportSummary> {
+    if out.exists() {
+        anyhow::bail!(
+            "{} already exists, remove it or choose another file with --out",
+            out.display()
+        );
+    }
+
+    let manifest_path = dir.join("subgraph.yaml");
+    let manifest: SubgraphManifest = serde_yaml_ng::from_str(
+        &fs::read_to_string(&manifest_path)
+            .context(format!("Failed to read {}", manifest_path.display()))?,
+    )
+    .context(format!("Failed to parse {}", manifest_path.display()))?;
+
+    let entities = match &manifest.schema {
+        Some(schema) => {
+            let path = dir.join(&schema.file);
+            parse_schema(
+                &fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?,
+            )
+        }
+        None => Vec::new(),
+    };
+
+    let mut summary = ImportSummary {
+        skipped_templates: manifest
+            .templates
+            .iter()
+            .map(|template| template.name.clone())
+            .collect(),
+        ..Default::default()
+    };
+    let mut stub = format!(
+        "# Imported from the subgraph in {}\n# Review, set the RPC URLs and merge into config.toml\n",
+        dir.display()
+    );
+    if !summary.skipped_templates.is_empty() {
+        let _ = writeln!(
+            stub,
+            "# Templates have no fixed address and were not imported: {}",
+            summary.skipped_templates.join(", ")
+        );
+    }
+
+    let networks: BTreeSet<&str> = manifest
+        .data_sources
+        .iter()
+        .map(|source| source.network.as_str())
+        .collect();
+    stub.push_str("\n[chains]\n");
+    for network in networks {
+        let _ = writeln!(
+            stub,
+            "{} = \"YOUR_{}_RPC_URL\"",
+            quote_key(network),
+            network.to_uppercase().replace('-', "_")
+        );
+    }
+
+    // Tables written by the handlers of each entity, for the endpoint tasks
+    let mut entity_sources: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for source in &manifest.data_sources {
+        if !source.kind.starts_with("ethereum") {
+            tracing::warn!(
+                "Skipping data source {} of kind {}",
+                source.name,
+                source.kind
+            );
+            continue;
+        }
+        let address = source
+            .source
+            .address
+            .as_deref()
+            .context(format!("Data source {} has no address", source.name))?;
+        let abi_file = source
+            .mapping
+            .abis
+            .iter()
+            .find(|abi| abi.name == source.source.abi)
+            .context(format!(
+                "Data source {} uses ABI {} which is not in its mapping",
+                source.name, source.source.abi
+            ))?;
+        let abi_json = artifact::read(&dir.join(&abi_file.file))?;
+        let json_abi = abi::from_value(&abi_json)?;
+        let abi_path = format!("{}/{}.json", MANIFEST_ABI_DIR, source.name);
+        write_new(
+            Path::new(&abi_path),
+            &serde_json::to_string_pretty(&abi_json)?,
+        )?;
+
+        let mut specs: Vec<ImportedSpec> = Vec::new();
+        for handler in &source.mapping.event_handlers {
+            let signature = event_signature(&handler.event);
+            let event_name = signature.split('(').next().unwrap_or_default();
+            let event = abi::resolve_event(&json_abi, event_name, &signature).context(format!(
+                "Event handler {} of {} doesn't match the ABI",
+                handler.handler, source.name
+            ))?;
+            let mut name = event.name.clone();
+            let mut n = 2;
+            while specs.iter().any(|spec| spec.name == name) {
+                name = format!("{}{}", event.name, n);
+                n += 1;
+            }
+            let mut ir = spec_ir(&source.name, &name, event);
+            ir.contract_address = address.to_string();
+            ir.chain = source.network.clone();
+            ir.start_block = source.source.start_block.unwrap_or(0);
+            ir.abi_hash = Some(abi::abi_hash(&abi_json));
+            ir.description = format!(
+                "{} events, handled by {} in the subgraph",
+                event.name, handler.handler
+            );
+            specs.push(ImportedSpec {
+                name,
+                handler: handler.handler.clone(),
+                ir,
+            });
+        }
+        if specs.is_empty() {
+            tracing::warn!("Data source {} has no event handlers", source.name);
+            continue;
+        }
+
+        let _ = write!(
+            stub,
+            "\n[contracts.{}]\nchain = {}\naddress = {}\nabiPath = {}\n",
+            quote_key(&source.name),
+            quote(&source.network),
+            quote(address),
+            quote(&abi_path)
+        );
+        for spec in &specs {
+            let ir_path = Path::new(SPECS_DIR)
+                .join(&source.name)
+                .join(format!("{}.json", spec.name));
+            write_new(&ir_path, &serde_json::to_string_pretty(&spec.ir)?)?;
+
+            let _ = write!(
+                stub,
+                "\n[[contracts.{}.specs]]\nname = {}\n",
+                quote_key(&source.name),
+                quote(&spec.name)
+            );
+            if let Some(start_block) = source.source.start_block {
+                let _ = writeln!(stub, "startBlock = {}", start_block);
+            }
+            if let Some(end_block) = source.source.end_block {
+                let _ = writeln!(stub, "endBlock = {}", end_block);
+            }
+            let _ = writeln!(
+                stub,
+                "task = {}",
+                quote(&format!(
+                    "Track event {} with every parameter, as handled by {} in the subgraph.",
+                    spec.ir.event_signature, spec.handler
+                ))
+            );
+            for entity in &source.mapping.entities {
+                entity_sources.entry(entity).or_default().push(format!(
+                    "{} (from {} events)",
+                    spec.ir.table_schema.table_name, spec.ir.event_name
+                ));
+            }
+        }
+        summary.contracts += 1;
+        summary.specs += specs.len();
+    }
+
+    for entity in &entities {
+        let fields: Vec<String> = entity
+            .fields
+            .iter()
+            .map(|(name, ty)| format!("{} ({})", name, ty))
+            .collect();
+        let mut task = format!(
+            "Return {} records like the subgraph entity {}, with the fields {}.",
+            entity.name,
+            entity.name,
+            fields.join(", ")
+        );
+        if let Some(tables) = entity_sources.get(entity.name.as_str()) {
+            let _ = write!(task, " Build them from the tables {}.", tables.join(", "));
+        }
+        task.push_str(" Support pagination with limit and offset.");
+
+        let _ = write!(
+            stub,
+            "\n[[endpoints]]\nendpoint = {}\ndescription = {}\ntask = {}\n",
+            quote(&endpoint_path(&entity.name)),
+            quote(&format!(
+                "{} entities, as served by the subgraph",
+                entity.name
+            )),
+            quote(&task)
+        );
+        summary.endpoints += 1;
+    }
+
+    fs::write(out, stub).context(format!("Failed to write {}", out.display()))?;
+    Ok(summary)
+}
+
+/// Entity types of a GraphQL schema, skipping `@derivedFrom` fields
+pub fn parse_schema(schema: &str) -> Vec<Entity> {
+    let schema: String = schema
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut entities = Vec::new();
+    let mut rest = schema.as_str();
+    while let Some(start) = rest.find("type ") {
+        rest = &rest[start + "type ".len()..];
+        let Some(open) = rest.find('{') else {
+            break;
+        };
+        let header = &rest[..open];
+        let body_end = rest[open..].find('}').map_or(rest.len(), |end| open + end);
+        let body = &rest[open + 1..body_end];
+        rest = &rest[body_end..];
+
+        if !header.contains("@entity") {
+            continue;
+        }
+        let name = header
+            .split(|c: char| c.is_whitespace() || c == '@')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let fields = body
+            .lines()
+            .filter(|line| !line.contains("@derivedFrom"))
+            .filter_map(|line| {
+                let (field, ty) = line.split_once(':')?;
+                let ty = ty.split('@').next().unwrap_or_default().trim();
+                Some((field.trim().to_string(), ty.to_string()))
+            })
+            .collect();
+        entities.push(Entity { name, fields });
+    }
+    entities
+}
+
+/// Solidity signature of a subgraph event, e.g. `Transfer(address,address,uint256)`
+/// for `Transfer(indexed address,indexed address,uint256)`
+fn event_signature(subgraph_event: &str) -> String {
+    subgraph_event
+        .replace("indexed ", "")
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect()
+}
+
+/// Spec IR storing every parameter of `event`, in ABI order
+fn spec_ir(contract_name: &str, spec_name: &str, event: &Event) -> IrGenerationResult {
+    let fields: Vec<EventField> = event
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| {
+            let solidity_type = input.selector_type().into_owned();
+            let rust_type = match column_type(&solidity_type).as_str() {
+                "BOOLEAN NOT NULL" => "bool",
+                "JSONB NOT NULL" => "serde_json::Value",
+                _ => "String",
+            };
+            let name = if input.name.is_empty() {
+                format!("arg{}", i)
+            } else {
+                input.name.clone()
+            };
+            EventField {
+                column: Some(snake_case(&name)),
+                name,
+                solidity_type,
+                rust_type: rust_type.to_string(),
+                indexed: input.indexed,
+            }
+        })
+        .collect();
+
+    let mut columns = vec![
+        ColumnDef {
+            name: "id".to_string(),
+            column_type: "BIGSERIAL PRIMARY KEY".to_string(),
+        },
+        ColumnDef {
+            name: "block_number".to_string(),
+            column_type: "BIGINT NOT NULL".to_string(),
+        },
+        ColumnDef {
+            name: "block_timestamp".to_string(),
+            column_type: "BIGINT NOT NULL".to_string(),
+        },
+        ColumnDef {
+            name: "transaction_hash".to_string(),
+            column_type: "VARCHAR(66) NOT NULL".to_string(),
+        },
+        ColumnDef {
+            name: "log_index".to_string(),
+            column_type: "INTEGER NOT NULL".to_string(),
+        },
+    ];
+    columns.extend(fields.iter().map(|field| ColumnDef {
+        name: snake_case(&field.name),
+        column_type: column_type(&field.solidity_type),
+    }));
+
+    let mut table_name = format!("{}_{}", snake_case(contract_name), snake_case(spec_name));
+    table_name.truncate(MAX_TABLE_NAME_LEN);
+
+    IrGenerationResult {
+        event_name: event.name.clone(),
+        event_signature: event.signature(),
+        start_block: 0,
+        contract_address: String::new(),
+        chain: String::new(),
+        indexed_fields: fields,
+        table_schema: TableSchema {
+            table_name,
+            columns,
+            indexes: vec![
+                IndexDef::new("idx_block_number", &["block_number"]),
+                IndexDef::new("idx_timestamp", &["block_timestamp"]),
+            ],
+            computed_columns: Vec::new(),
+        },
+        description: String::new(),
+        abi_hash: None,
+        state_fold: None,
+    }
+}
+
+/// Column type of a Solidity type, as in the README's type mappings
+pub(crate) fn column_type(solidity_type: &str) -> String {
+    let column_type = match solidity_type {
+        t if t.starts_with('(') || t.ends_with(']') => "JSONB",
+        "address" => "VARCHAR(42)",
+        "bool" => "BOOLEAN",
+        "string" | "bytes" => "TEXT",
+        t if t.starts_with("bytes") => "VARCHAR(66)",
+        t if t.starts_with("uint") || t.starts_with("int") => {
+            let bits: u32 = t
+                .trim_start_matches('u')
+                .trim_start_matches("int")
+                .parse()
+                .unwrap_or(256);
+            match bits {
+                0..=8 => "SMALLINT",
+                9..=24 => "INTEGER",
+                25..=64 => "BIGINT",
+                65..=128 => "NUMERIC(39, 0)",
+                _ => "NUMERIC(78, 0)",
+            }
+        }
+        _ => "TEXT",
+    };
+    format!("{} NOT NULL", column_type)
+}
+
+/// `swapFeePercentage` to `swap_fee_percentage`
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && previous_lower {
+            snake.push('_');
+        }
+        previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        snake.push(if c.is_ascii_alphanumeric() {
+            c.to_ascii_lowercase()
+        } else {
+            '_'
+        });
+    }
+    snake
+}
+
+/// Endpoint of an entity, e.g. `/api/liquidity_positions` for `LiquidityPosition`
+fn endpoint_path(entity: &str) -> String {
+    let name = snake_case(entity);
+    if name.ends_with('s') {
+        format!("/api/{}", name)
+    } else {
+        format!("/api/{}s", name)
+    }
+}
+
+/// Write a generated file unless one already exists
+fn write_new(path: &Path, content: &str) -> Result<()> {
+    if path.exists() {
+        tracing::info!("Keeping existing {}", path.display());
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create directory: {}", parent.display()))?;
+    }
+    fs::write(path, content).context(format!("Failed to write {}", path.display()))
+}
+
+fn quote(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+/// Table key, quoted unless it is a bare key
+fn quote_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_schema_entities() {
+        let schema = r#"
+# Accounts holding the token
+type Account @entity {
+  id: ID!
+  balance: BigInt! # raw units
+  transfers: [Transfer!]! @derivedFrom(field: "from")
+}
+
+type Transfer @entity(immutable: true) {
+  id: Bytes!
+  from: Account!
+  amount: BigInt!
+}
+
+type Query {
+  accounts: [Account!]!
+}
+"#;
+        assert_eq!(
+            parse_schema(schema),
+            vec![
+                Entity {
+                    name: "Account".to_string(),
+                    fields: vec![
+                        ("id".to_string(), "ID!".to_string()),
+                        ("balance".to_string(), "BigInt!".to_string()),
+                    ],
+                },
+                Entity {
+                    name: "Transfer".to_string(),
+                    fields: vec![
+                        ("id".to_string(), "Bytes!".to_string()),
+                        ("from".to_string(), "Account!".to_string()),
+                        ("amount".to_string(), "BigInt!".to_string()),
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spec_ir_from_subgraph_event() {
+        let event = Event::parse(
+            "event Swap(address indexed sender, uint256 amount0In, int24 tick, (address,uint8)[] hops)",
+        )
+        .unwrap();
+        assert_eq!(
+            event_signature("Swap(indexed address, uint256,int24,(address,uint8)[])"),
+            event.signature()
+        );
+
+        let ir = spec_ir("UniswapV2Pair", "Swap", &event);
+        assert_eq!(ir.table_schema.table_name, "uniswap_v2_pair_swap");
+        let columns: Vec<(&str, &str)> = ir.table_schema.columns[5..]
+            .iter()
+            .map(|column| (column.name.as_str(), column.column_type.as_str()))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("sender", "VARCHAR(42) NOT NULL"),
+                ("amount0_in", "NUMERIC(78, 0) NOT NULL"),
+                ("tick", "INTEGER NOT NULL"),
+                ("hops", "JSONB NOT NULL"),
+            ]
+        );
+        assert!(ir.indexed_fields[0].indexed);
+        assert_eq!(
+            endpoint_path("LiquidityPosition"),
+            "/api/liquidity_positions"
+        );
+    }
+}
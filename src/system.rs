@@ -0,0 +1,183 @@
+use crate::coverage::COVERAGE_TABLE;
+use crate::health::SPEC_ALERTS_TABLE;
+use crate::pause::PAUSES_TABLE;
+use crate::start_block::START_BLOCKS_TABLE;
+use crate::usage::USAGE_TABLE;
+use crate::warehouse::WAREHOUSE_CURSOR_TABLE;
+use anyhow::{Context, Result};
+use sqlx::{PgConnection, PgPool};
+
+/// Tracks when the indexer last inserted rows into each table
+pub const TABLE_ACTIVITY_TABLE: &str = "smorty_table_activity";
+
+/// Create smorty's own bookkeeping tables if they don't exist
+///
+/// These live alongside the generated event tables but are not part of the
+/// generated migrations, since they don't depend on any IR.
+pub async fn ensure_system_tables(pool: &PgPool) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            table_name TEXT PRIMARY KEY,
+            last_insert_at TIMESTAMPTZ NOT NULL
+        )",
+        TABLE_ACTIVITY_TABLE
+    ))
+    .execute(pool)
+    .await
+    .context("Failed to create table activity table")?;
+
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            spec TEXT NOT NULL,
+            from_block BIGINT NOT NULL,
+            to_block BIGINT NOT NULL,
+            PRIMARY KEY (spec, from_block)
+        )",
+        COVERAGE_TABLE
+    ))
+    .execute(pool)
+    .await
+    .context("Failed to create coverage table")?;
+
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            endpoint TEXT NOT NULL,
+            recorded_at TIMESTAMPTZ NOT NULL,
+            requests BIGINT NOT NULL,
+            client_errors BIGINT NOT NULL,
+            server_errors BIGINT NOT NULL,
+            latency_samples_ms DOUBLE PRECISION[] NOT NULL
+        )",
+        USAGE_TABLE
+    ))
+    .execute(pool)
+    .await
+    .context("Failed to create API usage table")?;
+
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            destination TEXT NOT NULL,
+            table_name TEXT NOT NULL,
+            block_number BIGINT NOT NULL,
+            log_index BIGINT NOT NULL,
+            synced_at TIMESTAMPTZ NOT NULL,
+            PRIMARY KEY (destination, table_name)
+        )",
+        WAREHOUSE_CURSOR_TABLE
+    ))
+    .execute(pool)
+    .await
+    .context("Failed to create warehouse cursor table")?;
+
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            spec TEXT PRIMARY KEY,
+            chain TEXT NOT NULL,
+            cursor BIGINT,
+            head BIGINT NOT NULL,
+            polls INTEGER NOT NULL,
+            last_error TEXT,
+            raised_at TIMESTAMPTZ NOT NULL
+        )",
+        SPEC_ALERTS_TABLE
+    ))
+    .execute(pool)
+    .await
+    .context("Failed to create stuck-spec alerts table")?;
+
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            spec TEXT PRIMARY KEY,
+            start TEXT NOT NULL,
+            block BIGINT NOT NULL,
+            resolved_at TIMESTAMPTZ NOT NULL
+        )",
+        START_BLOCKS_TABLE
+    ))
+    .execute(pool)
+    .await
+    .context("Failed to create start blocks table")?;
+
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            target TEXT PRIMARY KEY,
+            reason TEXT,
+            paused_at TIMESTAMPTZ NOT NULL
+        )",
+        PAUSES_TABLE
+    ))
+    .execute(pool)
+    .await
+    .context("Failed to create pauses table")?;
+
+    sqlx::query(&format!(
+        "CREATE INDEX IF NOT EXISTS {table}_recorded_at ON {table} (recorded_at)",
+        table = USAGE_TABLE
+    ))
+    .execute(pool)
+    .await
+    .context("Failed to create API usage index")?;
+
+    Ok(())
+}
+
+/// Record that rows were just inserted into the given tables
+pub async fn record_table_activity(pool: &PgPool, table_names: &[&str]) -> Result<()> {
+    if table_names.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query(&format!(
+        "INSERT INTO {} (table_name, last_insert_at)
+         SELECT name, NOW() FROM UNNEST($1::TEXT[]) AS name
+         ON CONFLICT (table_name) DO UPDATE SET last_insert_at = EXCLUDED.last_insert_at",
+        TABLE_ACTIVITY_TABLE
+    ))
+    .bind(table_names)
+    .execute(pool)
+    .await
+    .context("Failed to record table activity")?;
+
+    Ok(())
+}
+
+/// Namespace of the advisory locks held by `smorty index`, the key within it is the chain
+const INDEX_LOCK_NAMESPACE: i32 = 0x534d_5459;
+
+/// Advisory locks keeping a second indexer off the same chains
+///
+/// The locks belong to a dedicated connection and are released when it closes,
+/// including when the process exits or crashes.
+pub struct IndexLocks {
+    _connection: PgConnection,
+}
+
+impl IndexLocks {
+    /// Lock every chain, failing if another indexer holds any of them
+    pub async fn acquire(pool: &PgPool, chains: &[&str]) -> Result<Self> {
+        let mut connection = pool
+            .acquire()
+            .await
+            .context("Failed to get a connection for the indexer lock")?
+            .detach();
+
+        for chain in chains {
+            let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1, hashtext($2))")
+                .bind(INDEX_LOCK_NAMESPACE)
+                .bind(chain)
+                .fetch_one(&mut connection)
+                .await
+                .context("Failed to take the indexer lock")?;
+            if !locked {
+                anyhow::bail!(
+                    "Another indexer is running for chain '{}' against this database",
+                    chain
+                );
+            }
+        }
+
+        Ok(Self {
+            _connection: connection,
+        })
+    }
+}
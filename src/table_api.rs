@@ -0,0 +1,329 @@
+//! Endpoints generated from the indexed tables
+//!
+//! With `serve --readonly` (or `server.tableApi`), every table in
+//! migrations/schema.json gets a list endpoint at `/api/tables/<table>`, with an
+//! equality filter per column, and a lookup of the rows a transaction emitted at
+//! `/api/tables/<table>/tx/{tx_hash}`. They need no `gen-endpoint` run, and
+//! endpoint IRs on the same routes take precedence.
+
+use crate::ai::{EndpointIrResult, PathParam, QueryParam, ResponseField, ResponseSchema};
+use crate::schema_state::{ColumnState, SchemaState, TableState};
+use serde_json::{Value as JsonValue, json};
+
+/// Rows a table endpoint returns when the request sets no `limit`
+const DEFAULT_LIMIT: u32 = 50;
+
+/// How a column is returned and filtered on, from its Postgres type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnKind {
+    Integer,
+    Numeric,
+    Bool,
+    /// Addresses and hashes, stored as lowercase hex
+    Hex,
+    Text,
+    Json,
+    /// Returned as text, not filterable
+    Other,
+}
+
+impl ColumnKind {
+    fn of(column: &ColumnState) -> Self {
+        let column_type = column.column_type.trim().to_ascii_uppercase();
+        let starts = |prefixes: &[&str]| prefixes.iter().any(|p| column_type.starts_with(p));
+        if starts(&[
+            "SMALLINT",
+            "INTEGER",
+            "INT2",
+            "INT4",
+            "INT8",
+            "BIGINT",
+            "SERIAL",
+            "BIGSERIAL",
+        ]) {
+            Self::Integer
+        } else if starts(&["NUMERIC", "DECIMAL"]) {
+            Self::Numeric
+        } else if starts(&["BOOL"]) {
+            Self::Bool
+        } else if starts(&["VARCHAR", "CHARACTER VARYING"]) {
+            Self::Hex
+        } else if starts(&["TEXT"]) {
+            Self::Text
+        } else if starts(&["JSON"]) {
+            Self::Json
+        } else {
+            Self::Other
+        }
+    }
+
+    /// Response field type of the column
+    fn field_type(self) -> &'static str {
+        match self {
+            Self::Integer => "i64",
+            Self::Bool => "bool",
+            Self::Json => "serde_json::Value",
+            _ => "String",
+        }
+    }
+
+    /// Select list entry for the column, cast to what its field type decodes
+    fn select(self, name: &str) -> String {
+        match self {
+            Self::Integer => format!("{}::BIGINT AS {}", name, name),
+            Self::Numeric | Self::Other => format!("{}::TEXT AS {}", name, name),
+            _ => name.to_string(),
+        }
+    }
+
+    /// Filter on the column by parameter `$n`, matching every row when it's NULL
+    fn filter(self, name: &str, n: usize) -> Option<(&'static str, String)> {
+        let (param_type, condition) = match self {
+            Self::Integer => ("Option<i64>", format!("{} = ${}", name, n)),
+            Self::Bool => ("Option<bool>", format!("{} = ${}", name, n)),
+            Self::Numeric => ("Option<String>", format!("{} = ${}::NUMERIC", name, n)),
+            Self::Hex => ("Option<String>", format!("{} = LOWER(${})", name, n)),
+            Self::Text => ("Option<String>", format!("{} = ${}", name, n)),
+            Self::Json | Self::Other => return None,
+        };
+        let cast = match self {
+            Self::Integer => "BIGINT",
+            Self::Bool => "BOOLEAN",
+            _ => "TEXT",
+        };
+        Some((
+            param_type,
+            format!("(${}::{} IS NULL OR {})", n, cast, condition),
+        ))
+    }
+}
+
+/// List and transaction lookup endpoints of every table, ordered by table name
+pub fn endpoints(schema: &SchemaState) -> Vec<EndpointIrResult> {
+    let mut tables: Vec<&TableState> = schema.tables.values().collect();
+    tables.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut endpoints = Vec::new();
+    for table in tables {
+        endpoints.push(list_endpoint(table));
+        if has_column(table, "transaction_hash") {
+            endpoints.push(tx_endpoint(table));
+        }
+    }
+    endpoints
+}
+
+fn has_column(table: &TableState, name: &str) -> bool {
+    table.columns.iter().any(|column| column.name == name)
+}
+
+/// Columns and fields every endpoint of the table returns
+fn select_list(table: &TableState) -> (String, ResponseSchema) {
+    let mut selects = Vec::new();
+    let mut fields = Vec::new();
+    for column in &table.columns {
+        let kind = ColumnKind::of(column);
+        selects.push(kind.select(&column.name));
+        fields.push(ResponseField {
+            name: column.name.clone(),
+            field_type: format!("Option<{}>", kind.field_type()),
+            description: column.name.replace('_', " "),
+            scale: None,
+            format: None,
+        });
+    }
+    let schema = ResponseSchema {
+        name: format!("{}Row", table.name),
+        fields,
+    };
+    (selects.join(", "), schema)
+}
+
+/// Newest events first, when the table has the columns to order by
+fn order_by(table: &TableState) -> String {
+    let columns: Vec<String> = ["block_number", "log_index"]
+        .into_iter()
+        .filter(|column| has_column(table, column))
+        .map(|column| format!("{} DESC", column))
+        .collect();
+    if columns.is_empty() {
+        String::new()
+    } else {
+        format!(" ORDER BY {}", columns.join(", "))
+    }
+}
+
+fn limit_param() -> QueryParam {
+    QueryParam {
+        name: "limit".to_string(),
+        param_type: "u32".to_string(),
+        default: Some(json!(DEFAULT_LIMIT)),
+        description: "Maximum number of rows to return".to_string(),
+        allowed_values: vec![],
+    }
+}
+
+fn list_endpoint(table: &TableState) -> EndpointIrResult {
+    let (select, response_schema) = select_list(table);
+
+    let mut query_params = Vec::new();
+    let mut conditions = Vec::new();
+    for column in &table.columns {
+        if matches!(column.name.as_str(), "limit" | "offset") {
+            continue;
+        }
+        let Some((param_type, condition)) =
+            ColumnKind::of(column).filter(&column.name, query_params.len() + 1)
+        else {
+            continue;
+        };
+        query_params.push(QueryParam {
+            name: column.name.clone(),
+            param_type: param_type.to_string(),
+            default: Some(JsonValue::Null),
+            description: format!("Only rows with this {}", column.name),
+            allowed_values: vec![],
+        });
+        conditions.push(condition);
+    }
+
+    let limit = query_params.len() + 1;
+    query_params.push(limit_param());
+    query_params.push(QueryParam {
+        name: "offset".to_string(),
+        param_type: "u64".to_string(),
+        default: Some(json!(0)),
+        description: "Number of rows to skip".to_string(),
+        allowed_values: vec![],
+    });
+
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE {}", conditions.join(" AND "))
+    };
+    let sql_query = format!(
+        "SELECT {} FROM {}{}{} LIMIT ${} OFFSET ${}",
+        select,
+        table.name,
+        where_clause,
+        order_by(table),
+        limit,
+        limit + 1
+    );
+
+    EndpointIrResult {
+        endpoint_path: format!("/api/tables/{}", table.name),
+        description: format!("List rows of {}", table.name),
+        method: "GET".to_string(),
+        path_params: vec![],
+        query_params,
+        response_schema,
+        sql_query,
+        stages: Vec::new(),
+        tables_referenced: vec![table.name.clone()],
+        tags: vec![],
+        scopes: vec![],
+        cache_ttl: None,
+    }
+}
+
+fn tx_endpoint(table: &TableState) -> EndpointIrResult {
+    let (select, response_schema) = select_list(table);
+    let order_by = if has_column(table, "log_index") {
+        " ORDER BY log_index"
+    } else {
+        ""
+    };
+
+    EndpointIrResult {
+        endpoint_path: format!("/api/tables/{}/tx/{{tx_hash}}", table.name),
+        description: format!("Rows of {} emitted by a transaction", table.name),
+        method: "GET".to_string(),
+        path_params: vec![PathParam {
+            name: "tx_hash".to_string(),
+            param_type: "String".to_string(),
+            description: "Transaction hash".to_string(),
+        }],
+        query_params: vec![limit_param()],
+        response_schema,
+        sql_query: format!(
+            "SELECT {} FROM {} WHERE transaction_hash = LOWER($1){} LIMIT $2",
+            select, table.name, order_by
+        ),
+        stages: Vec::new(),
+        tables_referenced: vec![table.name.clone()],
+        tags: vec![],
+        scopes: vec![],
+        cache_ttl: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema_state::TableSource;
+
+    #[test]
+    fn test_generates_list_and_tx_endpoints_per_table() {
+        let column = |name: &str, column_type: &str| ColumnState {
+            name: name.to_string(),
+            column_type: column_type.to_string(),
+        };
+        let mut schema = SchemaState::new();
+        schema.add_table(TableState {
+            name: "swap".to_string(),
+            source: TableSource {
+                contract_name: "Pool".to_string(),
+                spec_name: "Swap".to_string(),
+            },
+            columns: vec![
+                column("block_number", "BIGINT NOT NULL"),
+                column("transaction_hash", "VARCHAR(66) NOT NULL"),
+                column("log_index", "INTEGER NOT NULL"),
+                column("amount", "NUMERIC(78, 0)"),
+                column("path", "JSONB"),
+            ],
+            indexes: vec![],
+        });
+
+        let endpoints = endpoints(&schema);
+        assert_eq!(endpoints.len(), 2);
+
+        let list = &endpoints[0];
+        assert_eq!(list.endpoint_path, "/api/tables/swap");
+        let params: Vec<&str> = list.query_params.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(
+            params,
+            [
+                "block_number",
+                "transaction_hash",
+                "log_index",
+                "amount",
+                "limit",
+                "offset"
+            ]
+        );
+        assert_eq!(
+            list.sql_query,
+            "SELECT block_number::BIGINT AS block_number, transaction_hash, \
+             log_index::BIGINT AS log_index, amount::TEXT AS amount, path FROM swap \
+             WHERE ($1::BIGINT IS NULL OR block_number = $1) \
+             AND ($2::TEXT IS NULL OR transaction_hash = LOWER($2)) \
+             AND ($3::BIGINT IS NULL OR log_index = $3) \
+             AND ($4::TEXT IS NULL OR amount = $4::NUMERIC) \
+             ORDER BY block_number DESC, log_index DESC LIMIT $5 OFFSET $6"
+        );
+        assert_eq!(
+            list.response_schema.fields[4].field_type,
+            "Option<serde_json::Value>"
+        );
+
+        let tx = &endpoints[1];
+        assert_eq!(tx.endpoint_path, "/api/tables/swap/tx/{tx_hash}");
+        assert!(
+            tx.sql_query
+                .ends_with("WHERE transaction_hash = LOWER($1) ORDER BY log_index LIMIT $2")
+        );
+    }
+}
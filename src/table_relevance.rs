@@ -0,0 +1,201 @@
+//! Picks the spec tables most relevant to an endpoint, to keep large projects'
+//! endpoint prompts within the model's context
+//!
+//! Tables are ranked by keyword overlap between the endpoint's path, description and
+//! task and each table's name, event, contract address, columns and description.
+
+use crate::ai::IrGenerationResult;
+use std::collections::HashSet;
+
+/// Words too common in tasks and descriptions to say anything about relevance
+const STOP_WORDS: &[&str] = &[
+    "and", "api", "all", "are", "but", "by", "for", "from", "get", "given", "has", "have", "into",
+    "its", "limit", "not", "of", "on", "or", "per", "return", "the", "that", "this", "to", "was",
+    "when", "where", "which", "with",
+];
+
+/// Matches on a table's name or event count this much more than other matches
+const NAME_WEIGHT: usize = 3;
+
+/// Matches on a table's contract address, e.g. in the endpoint path
+const ADDRESS_WEIGHT: usize = 5;
+
+/// Up to `limit` tables ranked by relevance to `query`, or every table if `limit` is 0
+///
+/// Tables keep their original order when they score the same, and when all of them fit.
+pub fn select_tables<'a>(
+    query: &str,
+    tables: &'a [IrGenerationResult],
+    limit: usize,
+) -> Vec<&'a IrGenerationResult> {
+    if limit == 0 || tables.len() <= limit {
+        return tables.iter().collect();
+    }
+
+    let query_words = keywords(query);
+    let query_lower = query.to_lowercase();
+
+    let mut scored: Vec<(usize, &IrGenerationResult)> = tables
+        .iter()
+        .map(|ir| (score(ir, &query_words, &query_lower), ir))
+        .collect();
+    // Stable, so ties keep config order
+    scored.sort_by(|a, b| b.0.cmp(&a.0));
+
+    scored.into_iter().take(limit).map(|(_, ir)| ir).collect()
+}
+
+/// Relevance of a table to a query's keywords
+fn score(ir: &IrGenerationResult, query_words: &HashSet<String>, query_lower: &str) -> usize {
+    let name_words: HashSet<String> = keywords(&ir.table_schema.table_name)
+        .union(&keywords(&ir.event_name))
+        .cloned()
+        .collect();
+
+    let mut other_text = ir.description.clone();
+    for column in &ir.table_schema.columns {
+        other_text.push(' ');
+        other_text.push_str(&column.name);
+    }
+    for column in &ir.table_schema.computed_columns {
+        other_text.push(' ');
+        other_text.push_str(&column.name);
+    }
+    let other_words = keywords(&other_text);
+
+    let mut score: usize = query_words
+        .iter()
+        .map(|word| {
+            if name_words.contains(word) {
+                NAME_WEIGHT
+            } else if other_words.contains(word) {
+                1
+            } else {
+                0
+            }
+        })
+        .sum();
+
+    if !ir.contract_address.is_empty() && query_lower.contains(&ir.contract_address.to_lowercase())
+    {
+        score += ADDRESS_WEIGHT;
+    }
+
+    score
+}
+
+/// Lowercase words of a text, splitting snake_case, kebab-case, paths and camelCase
+///
+/// Plural "s" endings are dropped so "fees" matches "fee".
+fn keywords(text: &str) -> HashSet<String> {
+    let mut words = HashSet::new();
+    let mut word = String::new();
+    let mut prev_lower = false;
+
+    let mut flush = |word: &mut String| {
+        if word.len() > 3 && word.ends_with('s') && !word.ends_with("ss") {
+            word.pop();
+        }
+        if word.len() > 2 && !STOP_WORDS.contains(&word.as_str()) {
+            words.insert(std::mem::take(word));
+        } else {
+            word.clear();
+        }
+    };
+
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            flush(&mut word);
+            prev_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_lower {
+            flush(&mut word);
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        word.extend(c.to_lowercase());
+    }
+    flush(&mut word);
+
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{ColumnDef, TableSchema};
+
+    fn table(name: &str, event: &str, address: &str, columns: &[&str]) -> IrGenerationResult {
+        IrGenerationResult {
+            event_name: event.to_string(),
+            event_signature: format!("{}()", event),
+            start_block: 0,
+            contract_address: address.to_string(),
+            chain: "mainnet".to_string(),
+            indexed_fields: vec![],
+            table_schema: TableSchema {
+                table_name: name.to_string(),
+                columns: columns
+                    .iter()
+                    .map(|column| ColumnDef {
+                        name: column.to_string(),
+                        column_type: "TEXT".to_string(),
+                    })
+                    .collect(),
+                indexes: vec![],
+                computed_columns: vec![],
+            },
+            description: String::new(),
+            abi_hash: None,
+            state_fold: None,
+        }
+    }
+
+    #[test]
+    fn test_keywords() {
+        let words = keywords("/sonic/feeManager/{pool} fee_updates for the swapFeePercentage");
+        for word in [
+            "sonic",
+            "fee",
+            "manager",
+            "pool",
+            "update",
+            "swap",
+            "percentage",
+        ] {
+            assert!(words.contains(word), "missing {}", word);
+        }
+        assert!(!words.contains("for"));
+        assert!(!words.contains("the"));
+    }
+
+    #[test]
+    fn test_select_tables() {
+        let tables = vec![
+            table(
+                "token_transfers",
+                "Transfer",
+                "0xaaa1",
+                &["from_address", "value"],
+            ),
+            table("pool_swaps", "Swap", "0xbbb2", &["pool", "amount_in"]),
+            table("fee_updates", "FeeUpdated", "0xccc3", &["pool", "swap_fee"]),
+            table("approvals", "Approval", "0xddd4", &["owner", "spender"]),
+        ];
+
+        // Everything fits, or pruning is disabled
+        assert_eq!(select_tables("anything", &tables, 4).len(), 4);
+        assert_eq!(select_tables("anything", &tables, 0).len(), 4);
+
+        let selected = select_tables("/pools/{pool}/fees", &tables, 2);
+        let names: Vec<_> = selected
+            .iter()
+            .map(|ir| ir.table_schema.table_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["fee_updates", "pool_swaps"]);
+
+        // The contract address in the path outweighs keyword matches
+        let selected = select_tables("/0xDDD4/fee-history", &tables, 1);
+        assert_eq!(selected[0].table_schema.table_name, "approvals");
+    }
+}
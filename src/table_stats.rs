@@ -0,0 +1,231 @@
+use crate::schema_state::TableState;
+use crate::system::TABLE_ACTIVITY_TABLE;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+
+/// Statistics for a smorty-managed table
+#[derive(Debug, Clone, Serialize)]
+pub struct TableStats {
+    pub table_name: String,
+    pub contract_name: String,
+    pub spec_name: String,
+    /// Number of rows (planner estimate unless `row_count_exact` is set)
+    pub row_count: i64,
+    pub row_count_exact: bool,
+    pub min_block: Option<i64>,
+    pub max_block: Option<i64>,
+    /// Last time the indexer inserted rows (RFC 3339, UTC)
+    pub last_insert_at: Option<String>,
+    /// Total on-disk size including indexes and TOAST
+    pub disk_size_bytes: i64,
+}
+
+impl TableStats {
+    /// Collect statistics for a table
+    ///
+    /// Returns `None` if the table does not exist in the database. With `exact`,
+    /// rows are counted with `COUNT(*)`; otherwise the planner estimate is used,
+    /// which is cheap enough to call for every table on each dashboard refresh.
+    pub async fn collect(pool: &PgPool, table: &TableState, exact: bool) -> Result<Option<Self>> {
+        let row = sqlx::query(
+            "SELECT GREATEST(c.reltuples, 0)::BIGINT AS estimate,
+                    pg_total_relation_size(c.oid)::BIGINT AS disk_size
+             FROM pg_class c WHERE c.oid = to_regclass($1)",
+        )
+        .bind(&table.name)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to query table size")?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let estimate: i64 = row.try_get("estimate")?;
+        let disk_size_bytes: i64 = row.try_get("disk_size")?;
+
+        // Table names come from migrations/schema.json, not from the request
+        let row = sqlx::query(&format!(
+            "SELECT MIN(block_number)::BIGINT AS min_block, MAX(block_number)::BIGINT AS max_block{} FROM {}",
+            if exact {
+                ", COUNT(*)::BIGINT AS row_count"
+            } else {
+                ""
+            },
+            table.name
+        ))
+        .fetch_one(pool)
+        .await
+        .context(format!("Failed to query block range for {}", table.name))?;
+
+        let row_count = if exact {
+            row.try_get("row_count")?
+        } else {
+            estimate
+        };
+
+        Ok(Some(Self {
+            table_name: table.name.clone(),
+            contract_name: table.source.contract_name.clone(),
+            spec_name: table.source.spec_name.clone(),
+            row_count,
+            row_count_exact: exact,
+            min_block: row.try_get("min_block")?,
+            max_block: row.try_get("max_block")?,
+            last_insert_at: Self::last_insert_at(pool, &table.name).await?,
+            disk_size_bytes,
+        }))
+    }
+
+    /// Collect statistics for a table stored in several databases, see `databases`
+    ///
+    /// Counts and sizes are summed and block ranges widened. Returns `None` if the
+    /// table exists in none of them.
+    pub async fn collect_all(
+        pools: &[PgPool],
+        table: &TableState,
+        exact: bool,
+    ) -> Result<Option<Self>> {
+        let mut merged: Option<Self> = None;
+        for pool in pools {
+            let Some(stats) = Self::collect(pool, table, exact).await? else {
+                continue;
+            };
+            merged = Some(match merged {
+                None => stats,
+                Some(total) => Self {
+                    row_count: total.row_count + stats.row_count,
+                    min_block: total.min_block.into_iter().chain(stats.min_block).min(),
+                    max_block: total.max_block.max(stats.max_block),
+                    // RFC 3339 timestamps in UTC sort as text
+                    last_insert_at: total.last_insert_at.max(stats.last_insert_at),
+                    disk_size_bytes: total.disk_size_bytes + stats.disk_size_bytes,
+                    ..total
+                },
+            });
+        }
+        Ok(merged)
+    }
+
+    /// Read the last insert time recorded by the indexer, if any
+    async fn last_insert_at(pool: &PgPool, table_name: &str) -> Result<Option<String>> {
+        let tracked: bool = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+            .bind(TABLE_ACTIVITY_TABLE)
+            .fetch_one(pool)
+            .await?;
+
+        if !tracked {
+            return Ok(None);
+        }
+
+        let last_insert_at = sqlx::query_scalar(&format!(
+            "SELECT to_char(last_insert_at AT TIME ZONE 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"')
+             FROM {} WHERE table_name = $1",
+            TABLE_ACTIVITY_TABLE
+        ))
+        .bind(table_name)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to query last insert time")?;
+
+        Ok(last_insert_at)
+    }
+}
+
+/// Events indexed on one day
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyEvents {
+    /// UTC date, `YYYY-MM-DD`
+    pub day: String,
+    pub events: i64,
+}
+
+/// Daily event counts of one spec
+#[derive(Debug, Clone, Serialize)]
+pub struct SpecActivity {
+    pub spec_name: String,
+    pub table_name: String,
+    pub total_events: i64,
+    /// Days without events are left out
+    pub days: Vec<DailyEvents>,
+}
+
+/// Indexed activity of a contract, served at `/api/_contracts/{name}/stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct ContractStats {
+    pub contract_name: String,
+    /// Number of days covered, ending today
+    pub period_days: u32,
+    pub total_events: i64,
+    pub specs: Vec<SpecActivity>,
+}
+
+impl ContractStats {
+    /// Count events per day over the last `days` days for each of a contract's tables
+    ///
+    /// Tables that haven't been migrated yet or have no `block_timestamp` column
+    /// are skipped.
+    pub async fn collect(
+        pool: &PgPool,
+        contract_name: &str,
+        tables: &[&TableState],
+        days: u32,
+    ) -> Result<Self> {
+        let since = chrono::Utc::now().timestamp() - i64::from(days) * 86_400;
+
+        let mut specs = Vec::new();
+        for table in tables {
+            if !table
+                .columns
+                .iter()
+                .any(|column| column.name == "block_timestamp")
+            {
+                continue;
+            }
+
+            let exists: bool = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+                .bind(&table.name)
+                .fetch_one(pool)
+                .await?;
+            if !exists {
+                continue;
+            }
+
+            // Table names come from migrations/schema.json, not from the request
+            let rows = sqlx::query(&format!(
+                "SELECT to_char((to_timestamp(block_timestamp) AT TIME ZONE 'UTC')::date, 'YYYY-MM-DD') AS day,
+                        COUNT(*)::BIGINT AS events
+                 FROM {} WHERE block_timestamp >= $1 GROUP BY 1 ORDER BY 1",
+                table.name
+            ))
+            .bind(since)
+            .fetch_all(pool)
+            .await
+            .context(format!("Failed to count events in {}", table.name))?;
+
+            let days = rows
+                .iter()
+                .map(|row| {
+                    Ok(DailyEvents {
+                        day: row.try_get("day")?,
+                        events: row.try_get("events")?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            specs.push(SpecActivity {
+                spec_name: table.source.spec_name.clone(),
+                table_name: table.name.clone(),
+                total_events: days.iter().map(|day| day.events).sum(),
+                days,
+            });
+        }
+        specs.sort_by(|a, b| a.spec_name.cmp(&b.spec_name));
+
+        Ok(Self {
+            contract_name: contract_name.to_string(),
+            period_days: days,
+            total_events: specs.iter().map(|spec| spec.total_events).sum(),
+            specs,
+        })
+    }
+}
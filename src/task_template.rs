@@ -0,0 +1,101 @@
+//! Placeholders in spec tasks
+//!
+//! Tasks can refer to `${address}`, `${chain}`, `${contract}` and `${spec}`, and
+//! to variables set in `vars` on the contract or the spec, e.g.
+//!
+//! ```toml
+//! [contracts.USDC]
+//! vars = { token_symbol = "USDC", decimals = 6 }
+//! ```
+//!
+//! so specs sharing most of their phrasing can share the task text. Spec
+//! variables override the contract's. `$${` writes a literal `${`.
+
+use anyhow::{Result, bail};
+use std::collections::BTreeMap;
+
+/// Variables a task is rendered with, by name
+pub type TaskVars = BTreeMap<String, toml::Value>;
+
+/// Replace the `${name}` placeholders of a task with their values
+pub fn render(template: &str, vars: &TaskVars) -> Result<String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('$') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(escaped) = rest.strip_prefix("$${") {
+            rendered.push_str("${");
+            rest = escaped;
+            continue;
+        }
+        let Some(placeholder) = rest.strip_prefix("${") else {
+            rendered.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+        let Some(end) = placeholder.find('}') else {
+            bail!("Unclosed placeholder in task: {}", rest);
+        };
+        let name = placeholder[..end].trim();
+        let value = vars.get(name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown variable ${{{}}} in task, available: {}",
+                name,
+                vars.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })?;
+        rendered.push_str(&value_text(name, value)?);
+        rest = &placeholder[end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// Text a variable is written as, only scalars can be substituted
+fn value_text(name: &str, value: &toml::Value) -> Result<String> {
+    match value {
+        toml::Value::String(s) => Ok(s.clone()),
+        toml::Value::Integer(i) => Ok(i.to_string()),
+        toml::Value::Float(f) => Ok(f.to_string()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        toml::Value::Datetime(d) => Ok(d.to_string()),
+        toml::Value::Array(_) | toml::Value::Table(_) => {
+            bail!("Variable '{}' must be a string, number or boolean", name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renders_placeholders() {
+        let vars: TaskVars = toml::from_str(
+            r#"
+            address = "0xA0b8"
+            token_symbol = "USDC"
+            decimals = 6
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            render(
+                "Index ${token_symbol} transfers of ${ address }, amounts have ${decimals} decimals",
+                &vars
+            )
+            .unwrap(),
+            "Index USDC transfers of 0xA0b8, amounts have 6 decimals"
+        );
+        assert_eq!(
+            render("Costs $5, keep $${raw}", &vars).unwrap(),
+            "Costs $5, keep ${raw}"
+        );
+        let err = render("Index ${symbol}", &vars).unwrap_err().to_string();
+        assert!(err.contains("${symbol}"), "{}", err);
+        assert!(err.contains("token_symbol"), "{}", err);
+        assert!(render("Index ${symbol", &vars).is_err());
+    }
+}
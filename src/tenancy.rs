@@ -0,0 +1,442 @@
+use crate::ai::EndpointIrResult;
+use crate::config::{ApiKeyConfig, TenancyConfig};
+use crate::query_stages;
+use crate::server::ApiError;
+use crate::sql_guard::{self, Token};
+use anyhow::{Context, Result};
+use sqlx::{PgPool, Row};
+use std::collections::{BTreeMap, HashSet};
+
+/// Row-level tenant filtering for generated endpoints
+///
+/// Every table that has the tenant column is shadowed by a CTE of the same name
+/// that only returns the caller's rows, e.g.
+///
+/// ```sql
+/// WITH swaps AS (SELECT * FROM swaps WHERE pool_address = $3::character varying)
+/// SELECT ... FROM swaps ...
+/// ```
+///
+/// A non-recursive CTE is not in scope inside its own definition, so the inner
+/// `FROM swaps` reads the real table while the rest of the query only sees the
+/// filtered rows. The rewrite happens once at startup; the tenant value from the
+/// API key metadata is bound as the extra parameter on every request.
+#[derive(Debug, Clone)]
+pub struct TenantFilter {
+    metadata_key: String,
+    column: String,
+    /// Tables with the tenant column, and the column's type
+    tenant_tables: BTreeMap<String, String>,
+    /// Routes of endpoints whose SQL was rewritten and expect the tenant parameter
+    scoped_endpoints: HashSet<String>,
+}
+
+impl TenantFilter {
+    /// Find tenant tables in the database and rewrite endpoint SQL to filter them
+    pub async fn prepare(
+        pool: &PgPool,
+        config: &TenancyConfig,
+        endpoints: &mut [EndpointIrResult],
+    ) -> Result<Self> {
+        let rows = sqlx::query(
+            "SELECT table_name::text, data_type::text FROM information_schema.columns \
+             WHERE table_schema = current_schema() AND column_name = $1",
+        )
+        .bind(&config.column)
+        .fetch_all(pool)
+        .await
+        .context("Failed to look up tenant column")?;
+
+        let mut tenant_tables = BTreeMap::new();
+        for row in rows {
+            let table: String = row.try_get(0)?;
+            let data_type: String = row.try_get(1)?;
+            tenant_tables.insert(table, data_type);
+        }
+
+        if tenant_tables.is_empty() {
+            tracing::warn!(
+                "Tenancy is enabled but no table has a '{}' column",
+                config.column
+            );
+        }
+
+        Self::rewrite_endpoints(config, &tenant_tables, endpoints)
+    }
+
+    /// Rewrite endpoint SQL given the tables that hold the tenant column
+    fn rewrite_endpoints(
+        config: &TenancyConfig,
+        tenant_tables: &BTreeMap<String, String>,
+        endpoints: &mut [EndpointIrResult],
+    ) -> Result<Self> {
+        let mut scoped_endpoints = HashSet::new();
+
+        for endpoint in endpoints.iter_mut() {
+            // Don't rely on tables_referenced alone, scan the SQL for any tenant table
+            let tables: Vec<(&str, &str)> = tenant_tables
+                .iter()
+                .filter(|(table, _)| {
+                    endpoint.tables_referenced.contains(table)
+                        || contains_identifier(&endpoint.sql_query, table)
+                        || endpoint
+                            .stages
+                            .iter()
+                            .any(|stage| contains_identifier(&stage.sql_query, table))
+                })
+                .map(|(table, data_type)| (table.as_str(), data_type.as_str()))
+                .collect();
+
+            if tables.is_empty() {
+                continue;
+            }
+
+            // Each statement takes the tenant after the request parameters and the
+            // outputs of the stages before it
+            let request_params = endpoint.path_params.len() + endpoint.query_params.len();
+            for count in 0..endpoint.stages.len() {
+                let param_index =
+                    request_params + query_stages::outputs_before(&endpoint.stages, count) + 1;
+                let stage = &mut endpoint.stages[count];
+                stage.sql_query =
+                    rewrite_sql(&stage.sql_query, &tables, &config.column, param_index).context(
+                        format!(
+                            "Failed to apply tenant filter to stage '{}' of endpoint {}",
+                            stage.name, endpoint.endpoint_path
+                        ),
+                    )?;
+            }
+            let param_index = request_params
+                + query_stages::outputs_before(&endpoint.stages, endpoint.stages.len())
+                + 1;
+            endpoint.sql_query =
+                rewrite_sql(&endpoint.sql_query, &tables, &config.column, param_index).context(
+                    format!(
+                        "Failed to apply tenant filter to endpoint {}",
+                        endpoint.endpoint_path
+                    ),
+                )?;
+
+            tracing::info!(
+                "  Tenant filter on {} ({})",
+                endpoint.endpoint_path,
+                tables
+                    .iter()
+                    .map(|(table, _)| *table)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            scoped_endpoints.insert(endpoint.route());
+        }
+
+        Ok(Self {
+            metadata_key: config.metadata_key.clone(),
+            column: config.column.clone(),
+            tenant_tables: tenant_tables.clone(),
+            scoped_endpoints,
+        })
+    }
+
+    /// Check if an endpoint expects the tenant parameter
+    pub fn is_scoped(&self, endpoint: &EndpointIrResult) -> bool {
+        self.scoped_endpoints.contains(&endpoint.route())
+    }
+
+    /// Tenant column and its type if `table` has one, for queries outside endpoints
+    pub fn table_column(&self, table: &str) -> Option<(&str, &str)> {
+        self.tenant_tables
+            .get(table)
+            .map(|data_type| (self.column.as_str(), data_type.as_str()))
+    }
+
+    /// Get the tenant identifier for an authenticated API key
+    pub fn tenant_for<'a>(&self, api_key: Option<&'a ApiKeyConfig>) -> Result<&'a str, ApiError> {
+        let api_key =
+            api_key.ok_or_else(|| ApiError::Unauthorized("Missing API key".to_string()))?;
+
+        api_key
+            .metadata
+            .get(&self.metadata_key)
+            .map(|tenant| tenant.as_str())
+            .ok_or_else(|| ApiError::Forbidden("API key is not assigned to a tenant".to_string()))
+    }
+}
+
+/// Prepend tenant-filtered CTEs that shadow the given tables
+fn rewrite_sql(
+    sql: &str,
+    tables: &[(&str, &str)],
+    column: &str,
+    param_index: usize,
+) -> Result<String> {
+    let trimmed = sql.trim_start();
+    let lower = trimmed.to_lowercase();
+
+    // A schema-qualified reference would bypass the shadowing CTE, however it's
+    // quoted or spaced
+    let tokens = sql_guard::tokenize(sql)?;
+    for window in tokens.windows(3) {
+        if let [schema, Token::Symbol('.'), name] = window
+            && schema.ident().is_some()
+            && let Some(name) = name.ident()
+            && let Some((table, _)) = tables.iter().find(|(table, _)| *table == name)
+        {
+            anyhow::bail!("Query references schema-qualified table '{}'", table);
+        }
+    }
+
+    let ctes = tables
+        .iter()
+        .map(|(table, data_type)| {
+            format!(
+                "{table} AS (SELECT * FROM {table} WHERE {column} = ${param_index}::{data_type})"
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if !lower.starts_with("with") || lower[4..].starts_with(|c: char| !c.is_whitespace()) {
+        return Ok(format!("WITH {} {}", ctes, trimmed));
+    }
+
+    let rest = trimmed[4..].trim_start();
+    if rest.to_lowercase().starts_with("recursive") {
+        anyhow::bail!("Recursive queries are not supported with tenancy");
+    }
+
+    // An existing CTE with the same name would clash with the shadowing CTE
+    let normalized = lower
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("as(", "as (");
+    for (table, _) in tables {
+        if contains_identifier(&normalized, &format!("{} as (", table)) {
+            anyhow::bail!("Query defines a CTE named after tenant table '{}'", table);
+        }
+    }
+
+    Ok(format!("WITH {}, {}", ctes, rest))
+}
+
+/// Check for a whole-word occurrence of an identifier in SQL (case-insensitive)
+///
+/// Word boundaries are only enforced where the pattern itself starts or ends with
+/// an identifier character, so patterns like `.swaps` or `swaps as (` also work.
+fn contains_identifier(sql: &str, pattern: &str) -> bool {
+    let sql = sql.to_lowercase();
+    let pattern = pattern.to_lowercase();
+    let is_ident_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let check_before = pattern.starts_with(is_ident_char);
+    let check_after = pattern.ends_with(is_ident_char);
+
+    sql.match_indices(&pattern).any(|(start, _)| {
+        let before = sql[..start].chars().next_back();
+        let after = sql[start + pattern.len()..].chars().next();
+        let joined_before = check_before && before.is_some_and(is_ident_char);
+        let joined_after = check_after && after.is_some_and(is_ident_char);
+        !joined_before && !joined_after
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{QueryParam, QueryStage, ResponseSchema};
+    use std::collections::HashMap;
+
+    fn tenancy_config() -> TenancyConfig {
+        TenancyConfig {
+            column: "pool_address".to_string(),
+            metadata_key: "tenant".to_string(),
+        }
+    }
+
+    fn create_endpoint(path: &str, sql: &str) -> EndpointIrResult {
+        EndpointIrResult {
+            endpoint_path: path.to_string(),
+            description: "Test endpoint".to_string(),
+            method: "GET".to_string(),
+            path_params: vec![],
+            query_params: vec![QueryParam {
+                name: "limit".to_string(),
+                param_type: "u32".to_string(),
+                default: Some(serde_json::json!(50)),
+                description: String::new(),
+                allowed_values: vec![],
+            }],
+            response_schema: ResponseSchema {
+                name: "TestResponse".to_string(),
+                fields: vec![],
+            },
+            sql_query: sql.to_string(),
+            stages: Vec::new(),
+            tables_referenced: vec![],
+            tags: vec![],
+            scopes: vec![],
+            cache_ttl: None,
+        }
+    }
+
+    #[test]
+    fn test_rewrite_plain_select() {
+        let sql = rewrite_sql(
+            "SELECT * FROM swaps LIMIT $1",
+            &[("swaps", "character varying")],
+            "pool_address",
+            2,
+        )
+        .unwrap();
+
+        assert_eq!(
+            sql,
+            "WITH swaps AS (SELECT * FROM swaps WHERE pool_address = $2::character varying) SELECT * FROM swaps LIMIT $1"
+        );
+    }
+
+    #[test]
+    fn test_rewrite_merges_existing_with() {
+        let sql = rewrite_sql(
+            "WITH recent AS (SELECT * FROM swaps ORDER BY block_number DESC) SELECT * FROM recent",
+            &[("swaps", "text")],
+            "pool_address",
+            1,
+        )
+        .unwrap();
+
+        assert!(sql.starts_with(
+            "WITH swaps AS (SELECT * FROM swaps WHERE pool_address = $1::text), recent AS ("
+        ));
+    }
+
+    #[test]
+    fn test_rewrite_rejects_unsafe_queries() {
+        let tables = [("swaps", "text")];
+
+        assert!(
+            rewrite_sql(
+                "WITH RECURSIVE t AS (SELECT 1) SELECT * FROM swaps",
+                &tables,
+                "pool_address",
+                1
+            )
+            .is_err()
+        );
+        for sql in [
+            "SELECT * FROM public.swaps",
+            "SELECT * FROM public.\"swaps\"",
+            "SELECT * FROM \"public\".\"swaps\"",
+            "SELECT * FROM \"public\".\"Swaps\"",
+            "SELECT * FROM public . swaps",
+            "SELECT * FROM public\n.\nswaps",
+            "SELECT * FROM public./**/swaps",
+            "SELECT * FROM public. -- comment\n swaps",
+        ] {
+            assert!(
+                rewrite_sql(sql, &tables, "pool_address", 1).is_err(),
+                "{}",
+                sql
+            );
+        }
+        assert!(
+            rewrite_sql(
+                "SELECT 'public.swaps' FROM swaps",
+                &tables,
+                "pool_address",
+                1
+            )
+            .is_ok()
+        );
+        assert!(
+            rewrite_sql(
+                "WITH swaps AS (SELECT 1) SELECT * FROM swaps",
+                &tables,
+                "pool_address",
+                1
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_contains_identifier() {
+        assert!(contains_identifier("SELECT * FROM Swaps s", "swaps"));
+        assert!(!contains_identifier("SELECT * FROM swaps_daily", "swaps"));
+        assert!(!contains_identifier("SELECT total_swaps FROM t", "swaps"));
+        assert!(contains_identifier("select * from public.swaps", ".swaps"));
+        assert!(!contains_identifier(
+            "select s.swaps_count from t s",
+            ".swaps"
+        ));
+    }
+
+    #[test]
+    fn test_rewrite_endpoints_scopes_only_tenant_tables() {
+        let tenant_tables = BTreeMap::from([("swaps".to_string(), "text".to_string())]);
+        let mut endpoints = vec![
+            create_endpoint("/api/swaps", "SELECT * FROM swaps LIMIT $1"),
+            create_endpoint("/api/blocks", "SELECT * FROM blocks LIMIT $1"),
+        ];
+
+        let filter =
+            TenantFilter::rewrite_endpoints(&tenancy_config(), &tenant_tables, &mut endpoints)
+                .unwrap();
+
+        assert!(filter.is_scoped(&endpoints[0]));
+        assert!(!filter.is_scoped(&endpoints[1]));
+        assert_eq!(filter.table_column("swaps"), Some(("pool_address", "text")));
+        assert_eq!(filter.table_column("blocks"), None);
+        assert!(endpoints[0].sql_query.contains("pool_address = $2::text"));
+        assert_eq!(endpoints[1].sql_query, "SELECT * FROM blocks LIMIT $1");
+    }
+
+    #[test]
+    fn test_rewrite_endpoints_numbers_tenant_after_stage_outputs() {
+        let tenant_tables = BTreeMap::from([("swaps".to_string(), "text".to_string())]);
+        let mut endpoint = create_endpoint(
+            "/api/top-pool-swaps",
+            "SELECT * FROM swaps WHERE pool = ANY($2) LIMIT $1",
+        );
+        endpoint.stages = vec![QueryStage {
+            name: "top_pools".to_string(),
+            sql_query: "SELECT pool FROM swaps GROUP BY pool ORDER BY COUNT(*) DESC LIMIT 10"
+                .to_string(),
+            outputs: vec!["pool".to_string()],
+        }];
+        let mut endpoints = vec![endpoint];
+
+        let filter =
+            TenantFilter::rewrite_endpoints(&tenancy_config(), &tenant_tables, &mut endpoints)
+                .unwrap();
+
+        assert!(filter.is_scoped(&endpoints[0]));
+        assert!(
+            endpoints[0].stages[0]
+                .sql_query
+                .contains("pool_address = $2::text")
+        );
+        assert!(endpoints[0].sql_query.contains("pool_address = $3::text"));
+    }
+
+    #[test]
+    fn test_tenant_for() {
+        let filter = TenantFilter {
+            metadata_key: "tenant".to_string(),
+            column: "pool_address".to_string(),
+            tenant_tables: BTreeMap::new(),
+            scoped_endpoints: HashSet::new(),
+        };
+        let api_key = ApiKeyConfig {
+            key: "secret".to_string(),
+            name: None,
+            metadata: HashMap::from([("tenant".to_string(), "0xabc".to_string())]),
+            scopes: vec![],
+        };
+
+        assert_eq!(filter.tenant_for(Some(&api_key)).unwrap(), "0xabc");
+        assert!(matches!(
+            filter.tenant_for(None),
+            Err(ApiError::Unauthorized(_))
+        ));
+    }
+}
@@ -0,0 +1,220 @@
+//! Post-processing of response fields, set on fields of endpoint IRs
+//!
+//! ```json
+//! { "name": "amount", "type": "String", "description": "...", "scale": 1e18 }
+//! { "name": "sender", "type": "String", "description": "...", "format": "checksum_address" }
+//! ```
+//!
+//! `scale` divides a value, e.g. token amounts by `1e18`. NUMERIC columns returned
+//! as strings stay strings and are shifted exactly when the scale is a power of
+//! ten; integer columns become JSON numbers. `format` rewrites a value as an
+//! EIP-55 checksummed address (`checksum_address`), an integer as `0x` hex
+//! (`hex`) or a hex value as a decimal string (`decimal`). Values that don't fit
+//! the transform are returned unchanged.
+
+use crate::ai::ResponseField;
+use alloy::primitives::{Address, U256};
+use anyhow::Result;
+use serde_json::{Value as JsonValue, json};
+use std::str::FromStr;
+
+/// Formats a response field can be rewritten to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldFormat {
+    ChecksumAddress,
+    Hex,
+    Decimal,
+}
+
+/// Check that a field's transforms can be applied
+pub fn check(field: &ResponseField) -> Result<()> {
+    if let Some(scale) = field.scale {
+        if !scale.is_finite() || scale <= 0.0 {
+            anyhow::bail!(
+                "Field '{}' has scale {}, it should be a positive number",
+                field.name,
+                scale
+            );
+        }
+        if matches!(
+            field.format,
+            Some(FieldFormat::ChecksumAddress | FieldFormat::Hex)
+        ) {
+            anyhow::bail!(
+                "Field '{}' can't be both scaled and formatted as {:?}",
+                field.name,
+                field.format
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Whether a field has transforms to apply
+pub fn has_transforms(field: &ResponseField) -> bool {
+    field.scale.is_some() || field.format.is_some()
+}
+
+/// Type of a field's value once transformed, for the OpenAPI document
+pub fn output_type(field: &ResponseField) -> &str {
+    if field.format.is_some() {
+        return "String";
+    }
+    let base_type = field
+        .field_type
+        .strip_prefix("Option<")
+        .and_then(|s| s.strip_suffix('>'))
+        .unwrap_or(&field.field_type);
+    match (field.scale, base_type) {
+        (Some(_), "i64" | "i32" | "u32" | "u64") => "f64",
+        _ => &field.field_type,
+    }
+}
+
+/// Apply a field's transforms to its decoded value
+pub fn apply(field: &ResponseField, value: JsonValue) -> JsonValue {
+    let value = match field.scale {
+        Some(scale) => apply_scale(scale, value),
+        None => value,
+    };
+    match field.format {
+        Some(format) => apply_format(format, value),
+        None => value,
+    }
+}
+
+fn apply_scale(scale: f64, value: JsonValue) -> JsonValue {
+    match value {
+        JsonValue::Number(number) => {
+            let shifted = power_of_ten(scale).and_then(|decimals| {
+                shift_decimal(&number.to_string(), decimals)?
+                    .parse::<f64>()
+                    .ok()
+            });
+            match shifted.or_else(|| number.as_f64().map(|n| n / scale)) {
+                Some(scaled) => json!(scaled),
+                None => JsonValue::Number(number),
+            }
+        }
+        JsonValue::String(s) => {
+            let shifted =
+                power_of_ten(scale).and_then(|decimals| shift_decimal(s.trim(), decimals));
+            match shifted.or_else(|| {
+                s.trim()
+                    .parse::<f64>()
+                    .ok()
+                    .map(|n| (n / scale).to_string())
+            }) {
+                Some(scaled) => JsonValue::String(scaled),
+                None => JsonValue::String(s),
+            }
+        }
+        value => value,
+    }
+}
+
+fn apply_format(format: FieldFormat, value: JsonValue) -> JsonValue {
+    let formatted = match (format, &value) {
+        (FieldFormat::ChecksumAddress, JsonValue::String(s)) => Address::from_str(s.trim())
+            .ok()
+            .map(|address| address.to_checksum(None)),
+        (FieldFormat::Hex, JsonValue::Number(n)) => n.as_u64().map(|n| format!("{:#x}", n)),
+        (FieldFormat::Hex, JsonValue::String(s)) => U256::from_str_radix(s.trim(), 10)
+            .ok()
+            .map(|n| format!("{:#x}", n)),
+        (FieldFormat::Decimal, JsonValue::String(s)) => s
+            .trim()
+            .strip_prefix("0x")
+            .and_then(|hex| U256::from_str_radix(hex, 16).ok())
+            .map(|n| n.to_string()),
+        _ => None,
+    };
+    formatted.map(JsonValue::String).unwrap_or(value)
+}
+
+/// Exponent of a scale that is a power of ten, e.g. 18 for `1e18`
+fn power_of_ten(scale: f64) -> Option<u32> {
+    format!("{:e}", scale)
+        .strip_prefix("1e")?
+        .parse()
+        .ok()
+        .filter(|exponent| *exponent <= 77)
+}
+
+/// Move the decimal point of an integer string `decimals` places to the left
+fn shift_decimal(integer: &str, decimals: u32) -> Option<String> {
+    let (sign, digits) = match integer.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", integer),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let decimals = decimals as usize;
+    let digits = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    let whole = whole.trim_start_matches('0');
+    let whole = if whole.is_empty() { "0" } else { whole };
+    let fraction = fraction.trim_end_matches('0');
+    Some(if fraction.is_empty() {
+        format!("{}{}", sign, whole)
+    } else {
+        format!("{}{}.{}", sign, whole, fraction)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(field_type: &str, scale: Option<f64>, format: Option<FieldFormat>) -> ResponseField {
+        ResponseField {
+            name: "value".to_string(),
+            field_type: field_type.to_string(),
+            description: String::new(),
+            scale,
+            format,
+        }
+    }
+
+    #[test]
+    fn test_scales_and_formats_values() {
+        let amount = field("String", Some(1e18), None);
+        assert_eq!(
+            apply(&amount, json!("1500000000000000000000")),
+            json!("1500")
+        );
+        assert_eq!(
+            apply(&amount, json!("-1234567890123456789")),
+            json!("-1.234567890123456789")
+        );
+        assert_eq!(apply(&amount, json!("n/a")), json!("n/a"));
+        assert_eq!(power_of_ten(1e24), Some(24));
+        assert_eq!(power_of_ten(2e6), None);
+        assert_eq!(
+            apply(&field("i64", Some(1e6), None), json!(2_500_000)),
+            json!(2.5)
+        );
+        assert_eq!(output_type(&field("i64", Some(1e6), None)), "f64");
+
+        let address = field("String", None, Some(FieldFormat::ChecksumAddress));
+        assert_eq!(
+            apply(
+                &address,
+                json!("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2")
+            ),
+            json!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2")
+        );
+        let hex = field("i64", None, Some(FieldFormat::Hex));
+        assert_eq!(apply(&hex, json!(255)), json!("0xff"));
+        assert_eq!(output_type(&hex), "String");
+        let decimal = field("String", None, Some(FieldFormat::Decimal));
+        assert_eq!(apply(&decimal, json!("0xff")), json!("255"));
+
+        assert!(check(&field("String", Some(0.0), None)).is_err());
+        assert!(check(&field("String", Some(1e18), Some(FieldFormat::Hex))).is_err());
+        assert!(check(&amount).is_ok());
+    }
+}
@@ -0,0 +1,268 @@
+//! Per-endpoint request counts, latencies and error rates
+//!
+//! Requests are counted in memory and flushed to `smorty_api_usage` once per
+//! [`USAGE_FLUSH_INTERVAL`], one row per endpoint. Only a sample of each period's
+//! latencies is kept, enough for percentiles without a row per request.
+
+use crate::system;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sqlx::PgPool;
+use sqlx::postgres::PgPoolOptions;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Request counts and latency samples per endpoint and flush period
+pub const USAGE_TABLE: &str = "smorty_api_usage";
+
+/// How often counts are written to the database
+pub const USAGE_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Latencies kept per endpoint and flush period
+const MAX_LATENCY_SAMPLES: usize = 200;
+
+/// Endpoint, requests, client errors, server errors and p50/p95/p99 latencies
+type UsageRow = (String, i64, i64, i64, Option<Vec<f64>>);
+
+/// Counts of a single endpoint since the last flush
+#[derive(Debug, Clone, Default, PartialEq)]
+struct EndpointUsage {
+    requests: u64,
+    client_errors: u64,
+    server_errors: u64,
+    /// Reservoir sample of request latencies in milliseconds
+    latencies_ms: Vec<f64>,
+}
+
+impl EndpointUsage {
+    fn record(&mut self, status: u16, latency: Duration) {
+        self.requests += 1;
+        match status {
+            400..=499 => self.client_errors += 1,
+            500.. => self.server_errors += 1,
+            _ => {}
+        }
+
+        // Every request has the same chance of being in the sample
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        if self.latencies_ms.len() < MAX_LATENCY_SAMPLES {
+            self.latencies_ms.push(latency_ms);
+        } else {
+            let slot = rand::random_range(0..self.requests) as usize;
+            if slot < MAX_LATENCY_SAMPLES {
+                self.latencies_ms[slot] = latency_ms;
+            }
+        }
+    }
+}
+
+/// Collects usage of generated endpoints and writes it to the database in batches
+///
+/// The API's own pool is read-only, so usage is written through a small pool
+/// connected with `database.uri`.
+#[derive(Debug, Clone)]
+pub struct UsageRecorder {
+    pool: PgPool,
+    pending: Arc<Mutex<HashMap<String, EndpointUsage>>>,
+}
+
+impl UsageRecorder {
+    /// Connect with the indexer's role and create the usage table if needed
+    pub async fn connect(uri: &str) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(2)
+            .connect(uri)
+            .await
+            .context("Failed to connect to database for usage analytics")?;
+        system::ensure_system_tables(&pool).await?;
+
+        Ok(Self {
+            pool,
+            pending: Arc::default(),
+        })
+    }
+
+    /// Count a request to the endpoint at `route`, e.g. `GET /api/pools`
+    pub fn record(&self, route: &str, status: u16, latency: Duration) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending
+            .entry(route.to_string())
+            .or_default()
+            .record(status, latency);
+    }
+
+    /// Write pending counts every [`USAGE_FLUSH_INTERVAL`]
+    pub fn spawn_flush(&self) {
+        let recorder = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(USAGE_FLUSH_INTERVAL);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = recorder.flush().await {
+                    tracing::warn!("{:#}", e);
+                }
+            }
+        });
+    }
+
+    /// Write and reset the counts collected since the last flush
+    pub async fn flush(&self) -> Result<()> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()));
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for (endpoint, usage) in pending {
+            sqlx::query(&format!(
+                "INSERT INTO {} (endpoint, recorded_at, requests, client_errors, server_errors, latency_samples_ms)
+                 VALUES ($1, NOW(), $2, $3, $4, $5)",
+                USAGE_TABLE
+            ))
+            .bind(endpoint)
+            .bind(usage.requests as i64)
+            .bind(usage.client_errors as i64)
+            .bind(usage.server_errors as i64)
+            .bind(usage.latencies_ms)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to record API usage")?;
+        }
+        tx.commit().await.context("Failed to record API usage")?;
+        Ok(())
+    }
+
+    /// Usage of each endpoint over the last `hours`, busiest first
+    ///
+    /// Endpoints in `routes` without any request are included, so unused
+    /// endpoints show up with zero requests.
+    pub async fn report(&self, hours: u32, routes: &[String]) -> Result<Vec<EndpointStats>> {
+        let rows: Vec<UsageRow> = sqlx::query_as(&format!(
+            "WITH recent AS (
+                SELECT * FROM {} WHERE recorded_at >= NOW() - make_interval(hours => $1)
+            ),
+            totals AS (
+                SELECT endpoint, SUM(requests)::BIGINT AS requests,
+                       SUM(client_errors)::BIGINT AS client_errors,
+                       SUM(server_errors)::BIGINT AS server_errors
+                FROM recent GROUP BY endpoint
+            ),
+            latencies AS (
+                SELECT endpoint,
+                       percentile_cont(ARRAY[0.5, 0.95, 0.99]::FLOAT8[]) WITHIN GROUP (ORDER BY latency) AS percentiles
+                FROM recent, unnest(latency_samples_ms) AS latency
+                GROUP BY endpoint
+            )
+            SELECT t.endpoint, t.requests, t.client_errors, t.server_errors, l.percentiles
+            FROM totals t LEFT JOIN latencies l USING (endpoint)",
+            USAGE_TABLE
+        ))
+        .bind(hours as i32)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to read API usage")?;
+
+        let mut stats: Vec<EndpointStats> = rows
+            .into_iter()
+            .map(
+                |(endpoint, requests, client_errors, server_errors, percentiles)| {
+                    EndpointStats::new(
+                        endpoint,
+                        requests as u64,
+                        client_errors as u64,
+                        server_errors as u64,
+                        percentiles.unwrap_or_default(),
+                    )
+                },
+            )
+            .collect();
+        for route in routes {
+            if !stats.iter().any(|stat| stat.endpoint == *route) {
+                stats.push(EndpointStats::new(route.clone(), 0, 0, 0, Vec::new()));
+            }
+        }
+        stats.sort_by(|a, b| {
+            b.requests
+                .cmp(&a.requests)
+                .then_with(|| a.endpoint.cmp(&b.endpoint))
+        });
+        Ok(stats)
+    }
+}
+
+/// Usage of one endpoint, returned by `/api/_usage`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EndpointStats {
+    pub endpoint: String,
+    pub requests: u64,
+    pub client_errors: u64,
+    pub server_errors: u64,
+    /// Share of requests that failed with a 5xx
+    pub error_rate: f64,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub p99_ms: Option<f64>,
+}
+
+impl EndpointStats {
+    fn new(
+        endpoint: String,
+        requests: u64,
+        client_errors: u64,
+        server_errors: u64,
+        percentiles: Vec<f64>,
+    ) -> Self {
+        let error_rate = if requests == 0 {
+            0.0
+        } else {
+            server_errors as f64 / requests as f64
+        };
+        Self {
+            endpoint,
+            requests,
+            client_errors,
+            server_errors,
+            error_rate,
+            p50_ms: percentiles.first().copied(),
+            p95_ms: percentiles.get(1).copied(),
+            p99_ms: percentiles.get(2).copied(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_counts_errors_and_caps_samples() {
+        let mut usage = EndpointUsage::default();
+        for i in 0..1000 {
+            let status = match i % 10 {
+                0 => 500,
+                1 => 404,
+                _ => 200,
+            };
+            usage.record(status, Duration::from_millis(i));
+        }
+
+        assert_eq!(usage.requests, 1000);
+        assert_eq!(usage.server_errors, 100);
+        assert_eq!(usage.client_errors, 100);
+        assert_eq!(usage.latencies_ms.len(), MAX_LATENCY_SAMPLES);
+        assert!(
+            usage
+                .latencies_ms
+                .iter()
+                .all(|ms| (0.0..1000.0).contains(ms))
+        );
+
+        let stats =
+            EndpointStats::new("GET /api/pools".to_string(), 1000, 100, 100, vec![1.0, 2.0]);
+        assert_eq!(stats.error_rate, 0.1);
+        assert_eq!(stats.p95_ms, Some(2.0));
+        assert_eq!(stats.p99_ms, None);
+    }
+}
@@ -0,0 +1,204 @@
+//! Checks of spec IRs against the config and contract ABIs, for `smorty validate`
+//!
+//! Spec IRs record a hash of the ABI they were generated from. When a contract is
+//! upgraded and its ABI file replaced, the specs of that contract are reported as
+//! stale, and `gen-spec --changed-only` regenerates just those.
+
+use crate::abi;
+use crate::ai::IrGenerationResult;
+use crate::artifact;
+use crate::config::Config;
+use crate::field_mapping;
+use crate::ir::Ir;
+use anyhow::Result;
+use serde_json::Value;
+use std::fmt;
+use std::path::Path;
+
+/// State of a spec's IR relative to the current contract ABI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecStatus {
+    /// Generated from the current ABI
+    Current,
+    /// No IR file has been generated yet
+    Missing,
+    /// The ABI changed since the IR was generated
+    AbiChanged,
+    /// The IR was generated before ABI hashes were recorded
+    Untracked,
+    /// The IR's event signature isn't in the ABI
+    EventNotInAbi(String),
+    /// The IR's event fields and table columns don't map one to one
+    UnmappedColumns(String),
+}
+
+impl SpecStatus {
+    /// Whether `gen-spec --changed-only` regenerates the spec
+    pub fn needs_regeneration(&self) -> bool {
+        matches!(self, Self::Missing | Self::AbiChanged)
+    }
+
+    /// Whether `validate` fails because of this spec
+    pub fn is_problem(&self) -> bool {
+        !matches!(self, Self::Current | Self::Untracked)
+    }
+}
+
+impl fmt::Display for SpecStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Current => write!(f, "up to date"),
+            Self::Missing => write!(f, "no IR file, run gen-spec"),
+            Self::AbiChanged => write!(
+                f,
+                "ABI changed since the IR was generated, run gen-spec --changed-only"
+            ),
+            Self::Untracked => write!(f, "IR has no ABI hash, regenerate it to track ABI changes"),
+            Self::EventNotInAbi(err) => write!(f, "{}", err),
+            Self::UnmappedColumns(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Status of one spec in the config
+#[derive(Debug, Clone)]
+pub struct SpecCheck {
+    pub contract_name: String,
+    pub spec_name: String,
+    pub status: SpecStatus,
+}
+
+/// Check every spec in the config against its contract's ABI
+pub fn check_specs(config: &Config) -> Result<Vec<SpecCheck>> {
+    let mut checks = Vec::new();
+
+    for (contract_name, contract_config) in &config.contracts {
+        let abi = read_abi(&contract_config.abi_path)?;
+
+        for spec in &contract_config.specs {
+            let ir = Ir::find_ir_spec(contract_name, &spec.name)?;
+            checks.push(SpecCheck {
+                contract_name: contract_name.clone(),
+                spec_name: spec.name.clone(),
+                status: spec_status(ir.as_ref(), &abi),
+            });
+        }
+    }
+
+    checks.sort_by(|a, b| (&a.contract_name, &a.spec_name).cmp(&(&b.contract_name, &b.spec_name)));
+    Ok(checks)
+}
+
+/// Read a contract's ABI file or build artifact as JSON
+pub fn read_abi(abi_path: &str) -> Result<Value> {
+    artifact::read(Path::new(abi_path))
+}
+
+/// Status of a spec from its existing IR, if any, and the current ABI
+pub fn spec_status(ir: Option<&IrGenerationResult>, abi: &Value) -> SpecStatus {
+    let Some(ir) = ir else {
+        return SpecStatus::Missing;
+    };
+
+    if let Some(hash) = &ir.abi_hash
+        && *hash != abi::abi_hash(abi)
+    {
+        return SpecStatus::AbiChanged;
+    }
+
+    // ABIs alloy can't parse are left to the indexer to report
+    if let Ok(json_abi) = abi::from_value(abi)
+        && let Err(e) = abi::resolve_event(&json_abi, &ir.event_name, &ir.event_signature)
+    {
+        return SpecStatus::EventNotInAbi(e.to_string());
+    }
+
+    if let Err(e) = field_mapping::map_columns(&mut ir.clone()) {
+        return SpecStatus::UnmappedColumns(e.to_string());
+    }
+
+    if ir.abi_hash.is_none() {
+        SpecStatus::Untracked
+    } else {
+        SpecStatus::Current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{ColumnDef, TableSchema};
+    use serde_json::json;
+
+    fn transfer_abi(value_type: &str) -> Value {
+        json!([{
+            "type": "event",
+            "name": "Transfer",
+            "anonymous": false,
+            "inputs": [
+                {"name": "from", "type": "address", "indexed": true},
+                {"name": "to", "type": "address", "indexed": true},
+                {"name": "value", "type": value_type, "indexed": false}
+            ]
+        }])
+    }
+
+    fn transfer_ir(abi_hash: Option<String>) -> IrGenerationResult {
+        IrGenerationResult {
+            event_name: "Transfer".to_string(),
+            event_signature: "Transfer(address,address,uint256)".to_string(),
+            start_block: 0,
+            contract_address: "0x0000000000000000000000000000000000000001".to_string(),
+            chain: "mainnet".to_string(),
+            indexed_fields: vec![],
+            table_schema: TableSchema {
+                table_name: "transfers".to_string(),
+                columns: vec![],
+                indexes: vec![],
+                computed_columns: vec![],
+            },
+            description: "Token transfers".to_string(),
+            abi_hash,
+            state_fold: None,
+        }
+    }
+
+    #[test]
+    fn test_spec_status() {
+        let abi = transfer_abi("uint256");
+        let hash = abi::abi_hash(&abi);
+
+        assert_eq!(spec_status(None, &abi), SpecStatus::Missing);
+        assert_eq!(
+            spec_status(Some(&transfer_ir(Some(hash.clone()))), &abi),
+            SpecStatus::Current
+        );
+        assert_eq!(
+            spec_status(Some(&transfer_ir(None)), &abi),
+            SpecStatus::Untracked
+        );
+
+        // Formatting the ABI file differently isn't a change
+        let reformatted: Value =
+            serde_json::from_str(&serde_json::to_string_pretty(&abi).unwrap()).unwrap();
+        assert_eq!(abi::abi_hash(&reformatted), hash);
+
+        let upgraded = transfer_abi("uint128");
+        let status = spec_status(Some(&transfer_ir(Some(hash))), &upgraded);
+        assert_eq!(status, SpecStatus::AbiChanged);
+        assert!(status.needs_regeneration());
+
+        let status = spec_status(Some(&transfer_ir(None)), &upgraded);
+        assert!(matches!(status, SpecStatus::EventNotInAbi(_)));
+        assert!(status.is_problem() && !status.needs_regeneration());
+
+        let mut unmapped = transfer_ir(Some(abi::abi_hash(&abi)));
+        unmapped.table_schema.columns.push(ColumnDef {
+            name: "value".to_string(),
+            column_type: "NUMERIC(78, 0) NOT NULL".to_string(),
+        });
+        let status = spec_status(Some(&unmapped), &abi);
+        assert!(matches!(status, SpecStatus::UnmappedColumns(_)));
+        assert!(status.is_problem());
+    }
+}
@@ -0,0 +1,139 @@
+//! Verification of indexed rows against the chain
+//!
+//! With `[indexer.verify]` set, the indexer daemon regularly picks random rows
+//! of each spec, fetches their logs from the RPC again and decodes them the way
+//! they were inserted. A row whose columns differ from its decoded log, or whose
+//! log the RPC no longer returns, is logged as an error and posted to
+//! `indexer.alertWebhook`, catching rows damaged by missed reorgs, manual edits
+//! or decoding changes in long-running deployments.
+
+use crate::health;
+use crate::ir::SOURCE_COLUMN;
+use serde::Serialize;
+
+/// An indexed row that doesn't match the chain
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Mismatch {
+    pub chain: String,
+    /// Spec as `<contract>/<spec>`
+    pub spec: String,
+    pub block_number: u64,
+    pub transaction_hash: String,
+    pub log_index: u64,
+    pub problem: String,
+}
+
+/// Rows of a shared table belonging to the spec, as the `$n` parameter
+fn source_filter(shared: bool, param: usize) -> String {
+    if shared {
+        format!(" AND {} = ${}", SOURCE_COLUMN, param)
+    } else {
+        String::new()
+    }
+}
+
+/// Query for the lowest and highest block a spec has rows in, bound to its source
+/// when `shared`
+pub fn block_range_sql(table: &str, shared: bool) -> String {
+    format!(
+        "SELECT MIN(block_number), MAX(block_number) FROM {} WHERE TRUE{}",
+        table,
+        source_filter(shared, 1)
+    )
+}
+
+/// Query for the first row at or after block `$1`, which random blocks turn into
+/// a random row without sorting the table
+pub fn sample_sql(table: &str, shared: bool) -> String {
+    format!(
+        "SELECT block_number, transaction_hash, log_index FROM {} \
+         WHERE block_number >= $1{} ORDER BY block_number, log_index LIMIT 1",
+        table,
+        source_filter(shared, 2)
+    )
+}
+
+/// Query for the columns of a row that differ from the values a decoded log
+/// would be inserted with
+///
+/// `values` are the SQL of each column's value, whose `params` placeholders come
+/// first; the row's transaction hash and log index are bound after them.
+pub fn compare_sql(
+    table: &str,
+    columns: &[String],
+    values: &[String],
+    params: usize,
+    shared: bool,
+) -> String {
+    let checks: Vec<String> = columns
+        .iter()
+        .zip(values)
+        .map(|(column, value)| {
+            format!(
+                "CASE WHEN {} IS NOT DISTINCT FROM ({}) THEN NULL ELSE '{}' END",
+                column, value, column
+            )
+        })
+        .collect();
+    format!(
+        "SELECT ARRAY_REMOVE(ARRAY[{}]::TEXT[], NULL) FROM {} \
+         WHERE transaction_hash = ${} AND log_index = ${}{}",
+        checks.join(", "),
+        table,
+        params + 1,
+        params + 2,
+        source_filter(shared, params + 3)
+    )
+}
+
+/// Log a mismatch and post it to the webhook
+pub async fn raise(webhook: Option<&str>, mismatch: &Mismatch) {
+    tracing::error!(
+        "Row of {} on chain '{}' at block {} (tx: {}, log index {}) doesn't match the chain: {}",
+        mismatch.spec,
+        mismatch.chain,
+        mismatch.block_number,
+        mismatch.transaction_hash,
+        mismatch.log_index,
+        mismatch.problem
+    );
+
+    if let Some(url) = webhook
+        && let Err(e) = health::post(
+            url,
+            &serde_json::json!({"event": "row_mismatch", "mismatch": mismatch}),
+        )
+        .await
+    {
+        tracing::warn!("Failed to send row mismatch alert to webhook: {:#}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builds_sample_and_compare_queries() {
+        assert_eq!(
+            sample_sql("transfers", true),
+            "SELECT block_number, transaction_hash, log_index FROM transfers \
+             WHERE block_number >= $1 AND source = $2 ORDER BY block_number, log_index LIMIT 1"
+        );
+        assert_eq!(
+            block_range_sql("swaps", false),
+            "SELECT MIN(block_number), MAX(block_number) FROM swaps WHERE TRUE"
+        );
+
+        let columns = ["block_number", "amount"].map(String::from);
+        let values = ["$1::numeric", "$2::numeric"].map(String::from);
+        assert_eq!(
+            compare_sql("swaps", &columns, &values, 2, true),
+            "SELECT ARRAY_REMOVE(ARRAY[\
+             CASE WHEN block_number IS NOT DISTINCT FROM ($1::numeric) THEN NULL ELSE 'block_number' END, \
+             CASE WHEN amount IS NOT DISTINCT FROM ($2::numeric) THEN NULL ELSE 'amount' END\
+             ]::TEXT[], NULL) FROM swaps WHERE transaction_hash = $3 AND log_index = $4 AND source = $5"
+        );
+    }
+}
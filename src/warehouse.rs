@@ -0,0 +1,621 @@
+//! Incremental copies of indexed tables in BigQuery or Snowflake, see `[warehouse]`
+//!
+//! Each sync reads the rows after a table's cursor in `(block_number, log_index)`
+//! order and loads them in batches through the warehouse's bulk-load API:
+//! BigQuery load jobs, or files uploaded to an S3 external stage and copied with
+//! `COPY INTO` through the Snowflake SQL API. A table's cursor only moves once its
+//! batch has loaded, so a failed sync is retried from the same rows.
+//!
+//! Batches are named after the rows they hold, so a batch that was loaded but
+//! whose cursor wasn't saved is recognised by BigQuery (as an existing job) or by
+//! Snowflake (as an already loaded file) instead of being loaded twice. Rows
+//! removed by a reorg after they were synced stay in the warehouse.
+
+use crate::config::{BigQueryConfig, Config, SnowflakeConfig, WarehouseConfig, WarehouseKind};
+use crate::redaction::ResponseRedactions;
+use crate::schema_state::{SCHEMA_STATE_FILE, SchemaState, TableState};
+use crate::sse::{self, RowCursor};
+use crate::system;
+use anyhow::{Context, Result};
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, PutPayload};
+use reqwest::StatusCode;
+use serde_json::{Value as JsonValue, json};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Position each table has been synced to, by warehouse
+pub const WAREHOUSE_CURSOR_TABLE: &str = "smorty_warehouse_cursors";
+
+/// How often a running load job or statement is checked
+const LOAD_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Rows copied to the warehouse by one sync
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncedTable {
+    pub table: String,
+    pub rows: usize,
+    pub cursor: RowCursor,
+}
+
+/// Copies tables to the configured warehouse
+pub struct WarehouseSync {
+    destination: Destination,
+    tables: Vec<(TableState, PgPool)>,
+    redactions: ResponseRedactions,
+    batch_rows: usize,
+}
+
+impl WarehouseSync {
+    /// Connect to the databases of the tables to sync
+    pub async fn connect(config: &Config, warehouse: &WarehouseConfig) -> Result<Self> {
+        let destination = Destination::new(warehouse)?;
+        let schema = SchemaState::load(std::path::Path::new(SCHEMA_STATE_FILE))
+            .context(format!("Failed to load {}", SCHEMA_STATE_FILE))?;
+
+        for name in &warehouse.tables {
+            if !schema.tables.contains_key(name) {
+                anyhow::bail!(
+                    "warehouse.tables has '{}' which is not in schema.json",
+                    name
+                );
+            }
+        }
+        let mut tables: Vec<&TableState> = schema
+            .tables
+            .values()
+            .filter(|table| warehouse.tables.is_empty() || warehouse.tables.contains(&table.name))
+            .filter(|table| {
+                let positioned = ["block_number", "log_index"]
+                    .iter()
+                    .all(|column| table.get_column(column).is_some());
+                if !positioned {
+                    tracing::warn!(
+                        "Not syncing {}: it has no block_number and log_index columns",
+                        table.name
+                    );
+                }
+                positioned
+            })
+            .collect();
+        tables.sort_by_key(|table| &table.name);
+
+        let mut pools: HashMap<&str, PgPool> = HashMap::new();
+        let mut synced = Vec::with_capacity(tables.len());
+        for table in tables {
+            let Some(contract) = config.contracts.get(&table.source.contract_name) else {
+                tracing::warn!(
+                    "Not syncing {}: contract {} is not in the config",
+                    table.name,
+                    table.source.contract_name
+                );
+                continue;
+            };
+            let uri = config.database.uri_for_chain(&contract.chain);
+            if !pools.contains_key(uri) {
+                let pool = PgPool::connect(uri)
+                    .await
+                    .context("Failed to connect to database")?;
+                system::ensure_system_tables(&pool).await?;
+                pools.insert(uri, pool);
+            }
+            synced.push((table.clone(), pools[uri].clone()));
+        }
+
+        Ok(Self {
+            destination,
+            tables: synced,
+            redactions: ResponseRedactions::new(&config.column_policies, &[]),
+            batch_rows: warehouse.batch_rows,
+        })
+    }
+
+    /// Copy the rows added since the last sync
+    pub async fn sync(&self) -> Result<Vec<SyncedTable>> {
+        let mut synced = Vec::with_capacity(self.tables.len());
+        for (table, pool) in &self.tables {
+            let summary = self
+                .sync_table(table, pool)
+                .await
+                .context(format!("Failed to sync {}", table.name))?;
+            synced.push(summary);
+        }
+        Ok(synced)
+    }
+
+    /// Sync every `interval` until the process stops, logging failures
+    pub async fn run(self, interval: Duration) {
+        loop {
+            match self.sync().await {
+                Ok(synced) => {
+                    let rows: usize = synced.iter().map(|table| table.rows).sum();
+                    if rows > 0 {
+                        tracing::info!("Synced {} rows to {}", rows, self.destination.name());
+                    }
+                }
+                Err(e) => tracing::warn!("Warehouse sync failed: {:#}", e),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    async fn sync_table(&self, table: &TableState, pool: &PgPool) -> Result<SyncedTable> {
+        let destination = self.destination.name();
+        let sql = sse::select_sql(table, None, self.batch_rows);
+        let mut cursor = read_cursor(pool, &destination, &table.name).await?;
+        let mut synced = 0;
+
+        loop {
+            let rows: Vec<(sqlx::types::Json<JsonValue>, i64, i64)> = sqlx::query_as(&sql)
+                .bind(cursor.block_number)
+                .bind(cursor.log_index)
+                .fetch_all(pool)
+                .await
+                .context("Failed to read rows")?;
+            let Some((_, block_number, log_index)) = rows.last() else {
+                break;
+            };
+            let end = RowCursor {
+                block_number: *block_number,
+                log_index: *log_index,
+            };
+
+            let count = rows.len();
+            let mut ndjson = Vec::new();
+            for (sqlx::types::Json(mut row), _, _) in rows {
+                if let JsonValue::Object(fields) = &mut row {
+                    self.redactions.redact_table_row(&table.name, fields);
+                }
+                serde_json::to_writer(&mut ndjson, &row)?;
+                ndjson.push(b'\n');
+            }
+
+            let batch = batch_name(&table.name, cursor, end);
+            self.destination.load(table, &batch, ndjson).await?;
+            write_cursor(pool, &destination, &table.name, end).await?;
+            tracing::debug!("Loaded {} rows of {} as {}", count, table.name, batch);
+
+            cursor = end;
+            synced += count;
+            if count < self.batch_rows {
+                break;
+            }
+        }
+
+        Ok(SyncedTable {
+            table: table.name.clone(),
+            rows: synced,
+            cursor,
+        })
+    }
+}
+
+async fn read_cursor(pool: &PgPool, destination: &str, table: &str) -> Result<RowCursor> {
+    let cursor: Option<(i64, i64)> = sqlx::query_as(&format!(
+        "SELECT block_number, log_index FROM {} WHERE destination = $1 AND table_name = $2",
+        WAREHOUSE_CURSOR_TABLE
+    ))
+    .bind(destination)
+    .bind(table)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to read warehouse cursor")?;
+    let (block_number, log_index) = cursor.unwrap_or((-1, -1));
+    Ok(RowCursor {
+        block_number,
+        log_index,
+    })
+}
+
+async fn write_cursor(
+    pool: &PgPool,
+    destination: &str,
+    table: &str,
+    cursor: RowCursor,
+) -> Result<()> {
+    sqlx::query(&format!(
+        "INSERT INTO {} (destination, table_name, block_number, log_index, synced_at)
+         VALUES ($1, $2, $3, $4, NOW())
+         ON CONFLICT (destination, table_name) DO UPDATE SET
+            block_number = EXCLUDED.block_number,
+            log_index = EXCLUDED.log_index,
+            synced_at = EXCLUDED.synced_at",
+        WAREHOUSE_CURSOR_TABLE
+    ))
+    .bind(destination)
+    .bind(table)
+    .bind(cursor.block_number)
+    .bind(cursor.log_index)
+    .execute(pool)
+    .await
+    .context("Failed to save warehouse cursor")?;
+    Ok(())
+}
+
+/// Name of the batch holding the rows after `start` up to `end`
+fn batch_name(table: &str, start: RowCursor, end: RowCursor) -> String {
+    format!(
+        "{}_{}_{}_{}_{}",
+        table, start.block_number, start.log_index, end.block_number, end.log_index
+    )
+}
+
+enum Destination {
+    BigQuery {
+        config: BigQueryConfig,
+        http: reqwest::Client,
+    },
+    Snowflake {
+        config: SnowflakeConfig,
+        http: reqwest::Client,
+        store: AmazonS3,
+    },
+}
+
+impl Destination {
+    fn new(warehouse: &WarehouseConfig) -> Result<Self> {
+        let http = reqwest::Client::new();
+        match warehouse.kind {
+            WarehouseKind::BigQuery => Ok(Self::BigQuery {
+                config: warehouse
+                    .bigquery
+                    .clone()
+                    .context("[warehouse.bigquery] is required for a bigquery warehouse")?,
+                http,
+            }),
+            WarehouseKind::Snowflake => {
+                let config = warehouse
+                    .snowflake
+                    .clone()
+                    .context("[warehouse.snowflake] is required for a snowflake warehouse")?;
+                let mut builder = AmazonS3Builder::from_env().with_bucket_name(&config.bucket);
+                if let Some(region) = &config.region {
+                    builder = builder.with_region(region);
+                }
+                let store = builder.build().context("Failed to configure S3")?;
+                Ok(Self::Snowflake {
+                    config,
+                    http,
+                    store,
+                })
+            }
+        }
+    }
+
+    /// Identifies the warehouse in the cursor table
+    fn name(&self) -> String {
+        match self {
+            Self::BigQuery { config, .. } => {
+                format!("bigquery:{}.{}", config.project, config.dataset)
+            }
+            Self::Snowflake { config, .. } => format!(
+                "snowflake:{}.{}.{}",
+                config.account, config.database, config.schema
+            ),
+        }
+    }
+
+    /// Bulk-load newline-delimited JSON rows into the table's copy
+    async fn load(&self, table: &TableState, batch: &str, ndjson: Vec<u8>) -> Result<()> {
+        match self {
+            Self::BigQuery { config, http } => {
+                bigquery_load(http, config, table, batch, ndjson).await
+            }
+            Self::Snowflake {
+                config,
+                http,
+                store,
+            } => snowflake_load(http, config, store, table, batch, ndjson).await,
+        }
+    }
+}
+
+async fn bigquery_load(
+    http: &reqwest::Client,
+    config: &BigQueryConfig,
+    table: &TableState,
+    batch: &str,
+    ndjson: Vec<u8>,
+) -> Result<()> {
+    let token = std::env::var(&config.access_token_env)
+        .context(format!("{} is not set", config.access_token_env))?;
+    let job = bigquery_job(config, table, batch);
+    let job_id = &job["jobReference"]["jobId"];
+
+    let boundary = format!("smorty-{}", hex::encode(rand::random::<[u8; 8]>()));
+    let mut body = format!(
+        "--{boundary}\r\nContent-Type: application/json; charset=UTF-8\r\n\r\n{job}\r\n\
+         --{boundary}\r\nContent-Type: application/octet-stream\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend(ndjson);
+    body.extend(format!("\r\n--{boundary}--\r\n").into_bytes());
+
+    let response = http
+        .post(format!(
+            "https://bigquery.googleapis.com/upload/bigquery/v2/projects/{}/jobs?uploadType=multipart",
+            config.project
+        ))
+        .bearer_auth(&token)
+        .header(
+            "Content-Type",
+            format!("multipart/related; boundary={}", boundary),
+        )
+        .body(body)
+        .send()
+        .await
+        .context("Failed to start BigQuery load job")?;
+    // The batch was already loaded by an earlier sync that couldn't save its cursor
+    if response.status() != StatusCode::CONFLICT {
+        error_for_status(response).await?;
+    }
+
+    let mut url = format!(
+        "https://bigquery.googleapis.com/bigquery/v2/projects/{}/jobs/{}",
+        config.project,
+        job_id.as_str().unwrap_or(batch)
+    );
+    if let Some(location) = &config.location {
+        url = format!("{}?location={}", url, location);
+    }
+    loop {
+        let response = http
+            .get(&url)
+            .bearer_auth(&token)
+            .send()
+            .await
+            .context("Failed to check BigQuery load job")?;
+        let job: JsonValue = error_for_status(response).await?.json().await?;
+        if job["status"]["state"] == "DONE" {
+            if let Some(error) = job["status"].get("errorResult") {
+                anyhow::bail!("BigQuery load job {} failed: {}", job_id, error);
+            }
+            return Ok(());
+        }
+        tokio::time::sleep(LOAD_POLL_INTERVAL).await;
+    }
+}
+
+/// Load job appending a batch to the table, creating it or adding new columns as needed
+///
+/// Job ids are unique per project, so they include the dataset.
+fn bigquery_job(config: &BigQueryConfig, table: &TableState, batch: &str) -> JsonValue {
+    let fields: Vec<JsonValue> = table
+        .columns
+        .iter()
+        .map(|column| {
+            json!({
+                "name": column.name,
+                "type": bigquery_type(&column.column_type),
+                "mode": "NULLABLE",
+            })
+        })
+        .collect();
+
+    let mut job_reference = json!({
+        "projectId": config.project,
+        "jobId": format!("smorty_{}_{}", config.dataset, batch),
+    });
+    if let Some(location) = &config.location {
+        job_reference["location"] = json!(location);
+    }
+
+    json!({
+        "jobReference": job_reference,
+        "configuration": {
+            "load": {
+                "destinationTable": {
+                    "projectId": config.project,
+                    "datasetId": config.dataset,
+                    "tableId": table.name,
+                },
+                "sourceFormat": "NEWLINE_DELIMITED_JSON",
+                "writeDisposition": "WRITE_APPEND",
+                "createDisposition": "CREATE_IF_NEEDED",
+                "schemaUpdateOptions": ["ALLOW_FIELD_ADDITION"],
+                "schema": { "fields": fields },
+            }
+        }
+    })
+}
+
+/// BigQuery type of a Postgres column, 256-bit NUMERIC values are kept as strings
+fn bigquery_type(column_type: &str) -> &'static str {
+    let column_type = column_type.to_uppercase();
+    if ["SMALLINT", "INTEGER", "BIGINT"]
+        .iter()
+        .any(|t| column_type.starts_with(t))
+    {
+        "INT64"
+    } else if column_type.starts_with("BOOL") {
+        "BOOL"
+    } else if column_type.starts_with("JSON") {
+        "JSON"
+    } else if column_type.starts_with("TIMESTAMP") {
+        "TIMESTAMP"
+    } else if column_type.starts_with("DOUBLE") || column_type.starts_with("REAL") {
+        "FLOAT64"
+    } else {
+        "STRING"
+    }
+}
+
+async fn snowflake_load(
+    http: &reqwest::Client,
+    config: &SnowflakeConfig,
+    store: &AmazonS3,
+    table: &TableState,
+    batch: &str,
+    ndjson: Vec<u8>,
+) -> Result<()> {
+    let file = format!("{}/{}.json", table.name, batch);
+    let key = match config.prefix.trim_matches('/') {
+        "" => file.clone(),
+        prefix => format!("{}/{}", prefix, file),
+    };
+    store
+        .put(&ObjectPath::from(key.as_str()), PutPayload::from(ndjson))
+        .await
+        .context(format!("Failed to upload {} to S3", key))?;
+
+    let columns = table
+        .columns
+        .iter()
+        .map(|column| {
+            format!(
+                "\"{}\" {}",
+                column.name,
+                snowflake_type(&column.column_type)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    snowflake_statement(
+        http,
+        config,
+        &format!(
+            "CREATE TABLE IF NOT EXISTS \"{}\" ({})",
+            table.name, columns
+        ),
+    )
+    .await?;
+    // Files COPY INTO already loaded are skipped, so a retried batch isn't loaded twice
+    snowflake_statement(
+        http,
+        config,
+        &format!(
+            "COPY INTO \"{}\" FROM @{}/{} FILE_FORMAT = (TYPE = JSON) \
+             MATCH_BY_COLUMN_NAME = CASE_INSENSITIVE",
+            table.name, config.stage, file
+        ),
+    )
+    .await
+}
+
+/// Run a statement through the Snowflake SQL API and wait for it to finish
+async fn snowflake_statement(
+    http: &reqwest::Client,
+    config: &SnowflakeConfig,
+    statement: &str,
+) -> Result<()> {
+    let token =
+        std::env::var(&config.token_env).context(format!("{} is not set", config.token_env))?;
+    let base_url = format!(
+        "https://{}.snowflakecomputing.com/api/v2/statements",
+        config.account
+    );
+    let request = |builder: reqwest::RequestBuilder| {
+        builder
+            .bearer_auth(&token)
+            .header("X-Snowflake-Authorization-Token-Type", &config.token_type)
+            .header("Accept", "application/json")
+    };
+
+    let response = request(http.post(&base_url))
+        .json(&json!({
+            "statement": statement,
+            "timeout": 3600,
+            "database": config.database,
+            "schema": config.schema,
+            "warehouse": config.warehouse,
+            "role": config.role,
+        }))
+        .send()
+        .await
+        .context("Failed to run Snowflake statement")?;
+    let mut response = error_for_status(response).await?;
+
+    // 202 while the statement is still running
+    while response.status() == StatusCode::ACCEPTED {
+        let body: JsonValue = response.json().await?;
+        let handle = body["statementHandle"]
+            .as_str()
+            .context("Snowflake didn't return a statement handle")?
+            .to_string();
+        tokio::time::sleep(LOAD_POLL_INTERVAL).await;
+        let poll = request(http.get(format!("{}/{}", base_url, handle)))
+            .send()
+            .await
+            .context("Failed to check Snowflake statement")?;
+        response = error_for_status(poll).await?;
+    }
+    Ok(())
+}
+
+/// Fail with the response body on an error status
+async fn error_for_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_client_error() || status.is_server_error() {
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("Warehouse returned {}: {}", status, body.trim());
+    }
+    Ok(response)
+}
+
+/// Snowflake type of a Postgres column, 256-bit NUMERIC values are kept as strings
+fn snowflake_type(column_type: &str) -> &'static str {
+    match bigquery_type(column_type) {
+        "INT64" => "NUMBER(38, 0)",
+        "BOOL" => "BOOLEAN",
+        "JSON" => "VARIANT",
+        "TIMESTAMP" => "TIMESTAMP_NTZ",
+        "FLOAT64" => "FLOAT",
+        _ => "VARCHAR",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema_state::ColumnState;
+
+    #[test]
+    fn test_bigquery_job_and_column_types() {
+        let mut table =
+            TableState::new("swaps".to_string(), "Pool".to_string(), "Swap".to_string());
+        for (name, column_type) in [
+            ("block_number", "BIGINT NOT NULL"),
+            ("amount", "NUMERIC(78, 0) NOT NULL"),
+            ("path", "JSONB"),
+        ] {
+            table.add_column(ColumnState::new(name.to_string(), column_type.to_string()));
+        }
+        let config = BigQueryConfig {
+            project: "analytics".to_string(),
+            dataset: "smorty".to_string(),
+            location: Some("US".to_string()),
+            access_token_env: "GOOGLE_OAUTH_ACCESS_TOKEN".to_string(),
+        };
+        let start = RowCursor {
+            block_number: -1,
+            log_index: -1,
+        };
+        let end = RowCursor {
+            block_number: 19_000_000,
+            log_index: 4,
+        };
+
+        let batch = batch_name("swaps", start, end);
+        assert_eq!(batch, "swaps_-1_-1_19000000_4");
+        let job = bigquery_job(&config, &table, &batch);
+        assert_eq!(
+            job["jobReference"]["jobId"],
+            "smorty_smorty_swaps_-1_-1_19000000_4"
+        );
+        assert_eq!(
+            job["configuration"]["load"]["destinationTable"]["tableId"],
+            "swaps"
+        );
+        let types: Vec<&str> = job["configuration"]["load"]["schema"]["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(types, ["INT64", "STRING", "JSON"]);
+        assert_eq!(snowflake_type("NUMERIC(78, 0)"), "VARCHAR");
+        assert_eq!(snowflake_type("INTEGER NOT NULL"), "NUMBER(38, 0)");
+    }
+}
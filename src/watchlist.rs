@@ -0,0 +1,341 @@
+//! Address watchlists
+//!
+//! A `[[watchlists]]` entry indexes every log of its chain's contracts that has
+//! one of the watched addresses in an indexed topic into one table, whatever the
+//! event: transfers to or from a wallet, approvals, swaps it made. Each chunk is
+//! fetched with the addresses as the topic filter of each indexed position, so it
+//! costs three `eth_getLogs` requests however many events the contracts have.
+//!
+//! A log gets a row per watched address it mentions, with the topic position it
+//! appeared at, the contract, the event name from the contract's ABI when known,
+//! and its raw topics and data for events no spec decodes.
+
+use crate::ai::ColumnDef;
+use crate::config::WatchlistConfig;
+use crate::decoded::{DecodedField, DecodedValue};
+use crate::index_def::IndexDef;
+use crate::indexer::{BoundQuery, InsertRow};
+use alloy::primitives::{Address, B256};
+use alloy::rpc::types::{Filter, Log};
+use anyhow::{Context, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+
+/// Contract name recorded as the source of watchlist tables in schema.json
+pub const WATCHLIST_SOURCE: &str = "watchlist";
+
+/// Indexed topics after topic0, the positions an address can appear at
+const TOPIC_POSITIONS: [usize; 3] = [1, 2, 3];
+
+/// A contract whose logs a watchlist searches
+#[derive(Debug, Clone)]
+pub struct WatchedContract {
+    pub name: String,
+    /// Event names by topic0, from the contract's ABI
+    pub events: HashMap<B256, String>,
+}
+
+/// Addresses to index the logs of, on one chain
+#[derive(Debug, Clone)]
+pub struct Watchlist {
+    pub name: String,
+    pub chain: String,
+    pub table: String,
+    pub start_block: Option<u64>,
+    /// Watched addresses as the 32-byte topics they appear as
+    topics: BTreeSet<B256>,
+}
+
+impl Watchlist {
+    pub fn from_config(config: &WatchlistConfig) -> Result<Self> {
+        let topics = config
+            .addresses
+            .iter()
+            .map(|address| {
+                Address::from_str(address.trim())
+                    .map(|address| address.into_word())
+                    .context(format!(
+                        "Invalid address '{}' in watchlist '{}'",
+                        address, config.name
+                    ))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            name: config.name.clone(),
+            chain: config.chain.clone(),
+            table: config.table.clone(),
+            start_block: config.start_block,
+            topics,
+        })
+    }
+
+    /// Name the watchlist's indexed ranges are recorded under
+    pub fn coverage_key(&self) -> String {
+        format!("watchlist/{}", self.name)
+    }
+
+    /// One filter per topic position for the logs of `contracts` mentioning a watched address
+    pub fn filters(&self, contracts: &[Address], from_block: u64, to_block: u64) -> Vec<Filter> {
+        let topics: Vec<B256> = self.topics.iter().copied().collect();
+        TOPIC_POSITIONS
+            .iter()
+            .map(|position| {
+                let filter = Filter::new()
+                    .address(contracts.to_vec())
+                    .from_block(from_block)
+                    .to_block(to_block);
+                match position {
+                    1 => filter.topic1(topics.clone()),
+                    2 => filter.topic2(topics.clone()),
+                    _ => filter.topic3(topics.clone()),
+                }
+            })
+            .collect()
+    }
+
+    /// Watched addresses a log mentions, each with the first topic position it's at
+    pub fn matches(&self, log: &Log) -> Vec<(Address, usize)> {
+        let mut seen = BTreeSet::new();
+        let mut matches = Vec::new();
+        for position in TOPIC_POSITIONS {
+            let Some(topic) = log.topics().get(position) else {
+                break;
+            };
+            if self.topics.contains(topic) && seen.insert(*topic) {
+                matches.push((Address::from_word(*topic), position));
+            }
+        }
+        matches
+    }
+
+    /// INSERT for the rows of a log, `None` when it mentions no watched address
+    pub fn insert_sql(&self, log: &Log, contract: &WatchedContract) -> Result<Option<BoundQuery>> {
+        let matches = self.matches(log);
+        if matches.is_empty() {
+            return Ok(None);
+        }
+        let block_number = log.block_number.context("Log missing block number")?;
+        let block_timestamp = log.block_timestamp.context("Log missing block timestamp")?;
+        let tx_hash = log
+            .transaction_hash
+            .context("Log missing transaction hash")?;
+        let log_index = log.log_index.context("Log missing log index")?;
+        let event_name = log
+            .topic0()
+            .and_then(|topic0| contract.events.get(topic0))
+            .map_or(DecodedValue::Null, |name| {
+                DecodedValue::String(name.clone())
+            });
+        let topics = log.topics().iter().copied().map(DecodedValue::Topic);
+
+        let mut values = Vec::new();
+        let mut rows = Vec::new();
+        for (address, position) in matches {
+            let mut row = InsertRow {
+                values: std::mem::take(&mut values),
+                ..Default::default()
+            };
+            for (column, solidity_type, value) in [
+                (
+                    "block_number",
+                    "int64",
+                    DecodedValue::Int(block_number as i64),
+                ),
+                (
+                    "block_timestamp",
+                    "int64",
+                    DecodedValue::Int(block_timestamp as i64),
+                ),
+                ("transaction_hash", "bytes32", DecodedValue::Topic(tx_hash)),
+                ("log_index", "int64", DecodedValue::Int(log_index as i64)),
+                ("watched_address", "address", DecodedValue::Address(address)),
+                (
+                    "topic_position",
+                    "int64",
+                    DecodedValue::Int(position as i64),
+                ),
+                (
+                    "contract_name",
+                    "string",
+                    DecodedValue::String(contract.name.clone()),
+                ),
+                (
+                    "contract_address",
+                    "address",
+                    DecodedValue::Address(log.address()),
+                ),
+                ("event_name", "string", event_name.clone()),
+                (
+                    "topics",
+                    "bytes32[]",
+                    DecodedValue::Array(topics.clone().collect()),
+                ),
+                (
+                    "data",
+                    "string",
+                    DecodedValue::String(format!("0x{}", hex::encode(log.data().data.as_ref()))),
+                ),
+            ] {
+                row.push(column, DecodedField::new(column, solidity_type, value));
+            }
+            rows.push(format!("({})", row.expressions.join(", ")));
+            values = row.values;
+        }
+        Ok(Some(BoundQuery {
+            sql: format!(
+                "INSERT INTO {} (block_number, block_timestamp, transaction_hash, log_index, \
+                 watched_address, topic_position, contract_name, contract_address, event_name, \
+                 topics, data) VALUES {} \
+                 ON CONFLICT (transaction_hash, log_index, watched_address) DO NOTHING",
+                self.table,
+                rows.join(", ")
+            ),
+            values,
+        }))
+    }
+
+    /// Columns of the watchlist table
+    pub fn columns(&self) -> Vec<ColumnDef> {
+        [
+            ("id", "BIGSERIAL PRIMARY KEY"),
+            ("block_number", "BIGINT NOT NULL"),
+            ("block_timestamp", "BIGINT NOT NULL"),
+            ("transaction_hash", "VARCHAR(66) NOT NULL"),
+            ("log_index", "INTEGER NOT NULL"),
+            ("watched_address", "VARCHAR(42) NOT NULL"),
+            ("topic_position", "SMALLINT NOT NULL"),
+            ("contract_name", "TEXT NOT NULL"),
+            ("contract_address", "VARCHAR(42) NOT NULL"),
+            ("event_name", "TEXT"),
+            ("topics", "JSONB NOT NULL"),
+            ("data", "TEXT NOT NULL"),
+        ]
+        .into_iter()
+        .map(|(name, column_type)| ColumnDef {
+            name: name.to_string(),
+            column_type: column_type.to_string(),
+        })
+        .collect()
+    }
+
+    /// A row per log and address, and indexes for an address's history
+    pub fn indexes(&self) -> Vec<IndexDef> {
+        let mut log_index = IndexDef::new(
+            "idx_watch_log",
+            &["transaction_hash", "log_index", "watched_address"],
+        );
+        log_index.unique = true;
+        vec![
+            log_index,
+            IndexDef::new(
+                "idx_watch_address",
+                &["watched_address", "block_number DESC"],
+            ),
+            IndexDef::new("idx_block_number", &["block_number"]),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{Bytes, LogData, b256, keccak256};
+
+    #[test]
+    fn test_matches_watched_addresses_in_indexed_topics() {
+        let watched = "0x00000000000000000000000000000000000000aa";
+        let watchlist = Watchlist::from_config(&WatchlistConfig {
+            name: "treasury".to_string(),
+            chain: "sonic".to_string(),
+            table: "treasury_activity".to_string(),
+            addresses: vec![watched.to_uppercase().replace("0X", "0x")],
+            start_block: None,
+        })
+        .unwrap();
+
+        let transfer = keccak256("Transfer(address,address,uint256)");
+        let other = Address::repeat_byte(0xbb).into_word();
+        let log = Log {
+            inner: alloy::primitives::Log {
+                address: Address::repeat_byte(0x01),
+                data: LogData::new_unchecked(
+                    vec![
+                        transfer,
+                        other,
+                        Address::from_str(watched).unwrap().into_word(),
+                    ],
+                    Bytes::from(vec![0x01]),
+                ),
+            },
+            block_number: Some(100),
+            block_timestamp: Some(1_700_000_000),
+            transaction_hash: Some(b256!(
+                "0x1f7c1e4b3d6e2f9a8b7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0b9c8d7e6f5a"
+            )),
+            log_index: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            watchlist.matches(&log),
+            [(Address::from_str(watched).unwrap(), 2)]
+        );
+
+        let contract = WatchedContract {
+            name: "Token".to_string(),
+            events: HashMap::from([(transfer, "Transfer".to_string())]),
+        };
+        let insert = watchlist.insert_sql(&log, &contract).unwrap().unwrap();
+        assert!(
+            insert.sql.starts_with("INSERT INTO treasury_activity ("),
+            "{}",
+            insert.sql
+        );
+        assert!(
+            insert.sql.ends_with(
+                "VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) ON CONFLICT (transaction_hash, log_index, watched_address) DO NOTHING"
+            ),
+            "{}",
+            insert.sql
+        );
+        let values: Vec<serde_json::Value> = insert
+            .values
+            .iter()
+            .skip(4)
+            .map(|field| field.value.to_json())
+            .collect();
+        assert_eq!(
+            values,
+            [
+                serde_json::json!(watched),
+                serde_json::json!(2),
+                serde_json::json!("Token"),
+                serde_json::json!("0x0101010101010101010101010101010101010101"),
+                serde_json::json!("Transfer"),
+                serde_json::json!([
+                    format!("{:#x}", transfer),
+                    format!("{:#x}", other),
+                    format!("{:#x}", Address::from_str(watched).unwrap().into_word()),
+                ]),
+                serde_json::json!("0x01"),
+            ]
+        );
+
+        let unrelated = Log {
+            inner: alloy::primitives::Log {
+                address: Address::repeat_byte(0x01),
+                data: LogData::new_unchecked(vec![transfer, other], Bytes::new()),
+            },
+            ..log
+        };
+        assert!(
+            watchlist
+                .insert_sql(&unrelated, &contract)
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(
+            watchlist.filters(&[Address::repeat_byte(0x01)], 1, 2).len(),
+            3
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use crate::ai::EndpointIrResult;
+use axum::http::HeaderMap;
+use axum::http::header::IF_NONE_MATCH;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// How often table high-water marks are refreshed from the database
+///
+/// This bounds how long a client may keep receiving 304 after new rows land.
+pub const WATERMARK_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Per-table high-water marks (`MAX(block_number)`) used to build ETags
+///
+/// Refreshed in the background so conditional requests can be answered without
+/// touching the tables they read.
+#[derive(Debug, Clone, Default)]
+pub struct TableWatermarks {
+    marks: Arc<RwLock<HashMap<String, i64>>>,
+    /// Tables refreshed in the background and the databases holding them,
+    /// replaced when endpoints are reloaded
+    tables: Arc<RwLock<BTreeMap<String, Vec<PgPool>>>>,
+}
+
+impl TableWatermarks {
+    /// Collect the tables referenced by all endpoints
+    pub fn tables_for(endpoints: &[EndpointIrResult]) -> BTreeSet<String> {
+        endpoints
+            .iter()
+            .flat_map(|endpoint| endpoint.tables_referenced.iter().cloned())
+            .filter(|table| {
+                !table.is_empty() && table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+            .collect()
+    }
+
+    /// Replace the tables whose watermarks are refreshed, with the databases holding each
+    pub fn track(&self, tables: BTreeMap<String, Vec<PgPool>>) {
+        if let Ok(mut tracked) = self.tables.write() {
+            *tracked = tables;
+        }
+    }
+
+    /// Refresh watermarks of the tracked tables in a background task for the lifetime of the server
+    pub fn spawn_refresh(&self) {
+        let watermarks = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(WATERMARK_REFRESH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let tables = match watermarks.tables.read() {
+                    Ok(tables) => tables.clone(),
+                    Err(_) => continue,
+                };
+                watermarks.refresh(&tables).await;
+            }
+        });
+    }
+
+    /// Query the current high-water mark of each table
+    ///
+    /// A table in several databases gets the sum of their marks, which changes
+    /// whenever any of them does.
+    async fn refresh(&self, tables: &BTreeMap<String, Vec<PgPool>>) {
+        'tables: for (table, pools) in tables {
+            let mut total = 0;
+            for pool in pools {
+                let result: Result<Option<i64>, sqlx::Error> =
+                    sqlx::query_scalar(&format!("SELECT MAX(block_number)::BIGINT FROM {}", table))
+                        .fetch_one(pool)
+                        .await;
+
+                match result {
+                    Ok(mark) => total += mark.unwrap_or(0),
+                    Err(e) => {
+                        // Tables without block_number never get a watermark, so their endpoints skip ETags
+                        tracing::debug!("Failed to refresh watermark for {}: {}", table, e);
+                        continue 'tables;
+                    }
+                }
+            }
+            self.set(table, total);
+        }
+    }
+
+    /// Current high-water mark of a table, if it has been read yet
+    pub fn mark(&self, table: &str) -> Option<i64> {
+        self.marks.read().ok()?.get(table).copied()
+    }
+
+    pub fn set(&self, table: &str, mark: i64) {
+        if let Ok(mut marks) = self.marks.write() {
+            marks.insert(table.to_string(), mark);
+        }
+    }
+
+    /// Compute a weak ETag for a request
+    ///
+    /// Returns `None` if any referenced table has no known watermark yet, since the
+    /// response freshness can't be determined.
+    pub fn etag(
+        &self,
+        endpoint_ir: &EndpointIrResult,
+        path_params: &HashMap<String, String>,
+        query_params: &HashMap<String, String>,
+        tenant: Option<&str>,
+    ) -> Option<String> {
+        if endpoint_ir.tables_referenced.is_empty() {
+            return None;
+        }
+
+        let marks = self.marks.read().ok()?;
+        let mut table_marks = BTreeMap::new();
+        for table in &endpoint_ir.tables_referenced {
+            table_marks.insert(table.as_str(), *marks.get(table)?);
+        }
+
+        // Sort parameters so equivalent requests hash the same
+        let path_params: BTreeMap<_, _> = path_params.iter().collect();
+        let query_params: BTreeMap<_, _> = query_params.iter().collect();
+
+        let mut hasher = DefaultHasher::new();
+        endpoint_ir.endpoint_path.hash(&mut hasher);
+        endpoint_ir.sql_query.hash(&mut hasher);
+        path_params.hash(&mut hasher);
+        query_params.hash(&mut hasher);
+        tenant.hash(&mut hasher);
+        table_marks.hash(&mut hasher);
+
+        Some(format!("W/\"{:016x}\"", hasher.finish()))
+    }
+}
+
+/// Check if an `If-None-Match` header matches an ETag (weak comparison)
+pub fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let Some(value) = headers.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let expected = opaque(etag);
+
+    value
+        .split(',')
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::ResponseSchema;
+    use axum::http::HeaderValue;
+
+    fn create_endpoint(tables: &[&str]) -> EndpointIrResult {
+        EndpointIrResult {
+            endpoint_path: "/api/swaps".to_string(),
+            description: "Swaps".to_string(),
+            method: "GET".to_string(),
+            path_params: vec![],
+            query_params: vec![],
+            response_schema: ResponseSchema {
+                name: "SwapResponse".to_string(),
+                fields: vec![],
+            },
+            sql_query: "SELECT * FROM swaps".to_string(),
+            stages: Vec::new(),
+            tables_referenced: tables.iter().map(|t| t.to_string()).collect(),
+            tags: vec![],
+            scopes: vec![],
+            cache_ttl: None,
+        }
+    }
+
+    #[test]
+    fn test_etag_requires_watermarks() {
+        let watermarks = TableWatermarks::default();
+        let endpoint = create_endpoint(&["swaps"]);
+        let params = HashMap::new();
+
+        assert!(watermarks.etag(&endpoint, &params, &params, None).is_none());
+
+        watermarks.set("swaps", 100);
+        assert!(watermarks.etag(&endpoint, &params, &params, None).is_some());
+    }
+
+    #[test]
+    fn test_etag_changes_with_inputs() {
+        let watermarks = TableWatermarks::default();
+        watermarks.set("swaps", 100);
+        let endpoint = create_endpoint(&["swaps"]);
+        let empty = HashMap::new();
+        let query = HashMap::from([("limit".to_string(), "10".to_string())]);
+
+        let base = watermarks.etag(&endpoint, &empty, &empty, None).unwrap();
+        assert!(base.starts_with("W/\""));
+        assert_eq!(
+            base,
+            watermarks.etag(&endpoint, &empty, &empty, None).unwrap()
+        );
+        assert_ne!(
+            base,
+            watermarks.etag(&endpoint, &empty, &query, None).unwrap()
+        );
+        assert_ne!(
+            base,
+            watermarks
+                .etag(&endpoint, &empty, &empty, Some("tenant-a"))
+                .unwrap()
+        );
+
+        watermarks.set("swaps", 101);
+        assert_ne!(
+            base,
+            watermarks.etag(&endpoint, &empty, &empty, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let etag = "W/\"00000000000000ff\"";
+        let mut headers = HeaderMap::new();
+        assert!(!if_none_match(&headers, etag));
+
+        headers.insert(
+            IF_NONE_MATCH,
+            HeaderValue::from_static("\"abc\", W/\"00000000000000ff\""),
+        );
+        assert!(if_none_match(&headers, etag));
+
+        headers.insert(
+            IF_NONE_MATCH,
+            HeaderValue::from_static("\"00000000000000ff\""),
+        );
+        assert!(if_none_match(&headers, etag));
+
+        headers.insert(IF_NONE_MATCH, HeaderValue::from_static("\"other\""));
+        assert!(!if_none_match(&headers, etag));
+    }
+
+    #[test]
+    fn test_tables_for_skips_invalid_names() {
+        let endpoints = vec![
+            create_endpoint(&["swaps", "mints"]),
+            create_endpoint(&["bad name"]),
+        ];
+        let tables = TableWatermarks::tables_for(&endpoints);
+        assert_eq!(tables.len(), 2);
+        assert!(tables.contains("swaps"));
+    }
+}
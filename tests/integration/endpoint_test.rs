@@ -4,7 +4,6 @@
 //! the Dune-like feature where users describe queries in plain English.
 
 use anyhow::Result;
-use serial_test::serial;
 use std::path::Path;
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, MockServer, ResponseTemplate};
@@ -52,18 +51,21 @@ fn mock_available_tables() -> Vec<IrGenerationResult> {
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true,
+                    column: None,
                 },
                 EventField {
                     name: "dst".to_string(),
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true,
+                    column: None,
                 },
                 EventField {
                     name: "wad".to_string(),
                     solidity_type: "uint256".to_string(),
                     rust_type: "String".to_string(),
                     indexed: false,
+                    column: None,
                 },
             ],
             table_schema: TableSchema {
@@ -103,8 +105,11 @@ fn mock_available_tables() -> Vec<IrGenerationResult> {
                     },
                 ],
                 indexes: vec![],
+                computed_columns: vec![],
             },
             description: "Tracks all WETH token transfers".to_string(),
+            abi_hash: None,
+            state_fold: None,
         },
         IrGenerationResult {
             event_name: "Transfer".to_string(),
@@ -118,18 +123,21 @@ fn mock_available_tables() -> Vec<IrGenerationResult> {
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true,
+                    column: None,
                 },
                 EventField {
                     name: "to_addr".to_string(),
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true,
+                    column: None,
                 },
                 EventField {
                     name: "value".to_string(),
                     solidity_type: "uint256".to_string(),
                     rust_type: "String".to_string(),
                     indexed: false,
+                    column: None,
                 },
             ],
             table_schema: TableSchema {
@@ -169,8 +177,11 @@ fn mock_available_tables() -> Vec<IrGenerationResult> {
                     },
                 ],
                 indexes: vec![],
+                computed_columns: vec![],
             },
             description: "Tracks all UNI token transfers".to_string(),
+            abi_hash: None,
+            state_fold: None,
         },
         IrGenerationResult {
             event_name: "Swap".to_string(),
@@ -185,12 +196,14 @@ fn mock_available_tables() -> Vec<IrGenerationResult> {
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true,
+                    column: None,
                 },
                 EventField {
                     name: "recipient".to_string(),
                     solidity_type: "address".to_string(),
                     rust_type: "String".to_string(),
                     indexed: true,
+                    column: None,
                 },
             ],
             table_schema: TableSchema {
@@ -246,23 +259,25 @@ fn mock_available_tables() -> Vec<IrGenerationResult> {
                     },
                 ],
                 indexes: vec![],
+                computed_columns: vec![],
             },
             description: "Tracks all swap events on Uniswap V3 USDC/ETH pool".to_string(),
+            abi_hash: None,
+            state_fold: None,
         },
     ]
 }
 
 #[tokio::test]
-#[serial]
 async fn test_endpoint_weth_transfers() -> Result<()> {
     let server = setup_mock_with_cassette("endpoint_weth_transfers").await;
 
-    unsafe {
-        std::env::set_var("OPENAI_BASE_URL", server.uri());
-    }
-
-    let ai_client =
-        smorty::ai::AiClient::new("fake-api-key".to_string(), "gpt-4o".to_string(), 0.7);
+    let ai_client = smorty::ai::AiClient::new_with_base_url(
+        "fake-api-key".to_string(),
+        "gpt-4o".to_string(),
+        0.7,
+        server.uri(),
+    );
 
     let tables = mock_available_tables();
     let result = ai_client
@@ -274,10 +289,6 @@ async fn test_endpoint_weth_transfers() -> Result<()> {
         )
         .await;
 
-    unsafe {
-        std::env::remove_var("OPENAI_BASE_URL");
-    }
-
     let endpoint = result.expect("Endpoint IR generation should succeed");
 
     // Verify endpoint structure
@@ -314,16 +325,15 @@ async fn test_endpoint_weth_transfers() -> Result<()> {
 }
 
 #[tokio::test]
-#[serial]
 async fn test_endpoint_cross_contract_whales() -> Result<()> {
     let server = setup_mock_with_cassette("endpoint_cross_contract_whales").await;
 
-    unsafe {
-        std::env::set_var("OPENAI_BASE_URL", server.uri());
-    }
-
-    let ai_client =
-        smorty::ai::AiClient::new("fake-api-key".to_string(), "gpt-4o".to_string(), 0.7);
+    let ai_client = smorty::ai::AiClient::new_with_base_url(
+        "fake-api-key".to_string(),
+        "gpt-4o".to_string(),
+        0.7,
+        server.uri(),
+    );
 
     let tables = mock_available_tables();
     let result = ai_client
@@ -335,10 +345,6 @@ async fn test_endpoint_cross_contract_whales() -> Result<()> {
         )
         .await;
 
-    unsafe {
-        std::env::remove_var("OPENAI_BASE_URL");
-    }
-
     let endpoint = result.expect("Cross-contract endpoint IR generation should succeed");
 
     // This is the key test - verifies cross-contract JOIN works
@@ -368,16 +374,15 @@ async fn test_endpoint_cross_contract_whales() -> Result<()> {
 }
 
 #[tokio::test]
-#[serial]
 async fn test_endpoint_swap_volume_hourly() -> Result<()> {
     let server = setup_mock_with_cassette("endpoint_swap_volume_hourly").await;
 
-    unsafe {
-        std::env::set_var("OPENAI_BASE_URL", server.uri());
-    }
-
-    let ai_client =
-        smorty::ai::AiClient::new("fake-api-key".to_string(), "gpt-4o".to_string(), 0.7);
+    let ai_client = smorty::ai::AiClient::new_with_base_url(
+        "fake-api-key".to_string(),
+        "gpt-4o".to_string(),
+        0.7,
+        server.uri(),
+    );
 
     let tables = mock_available_tables();
     let result = ai_client
@@ -389,10 +394,6 @@ async fn test_endpoint_swap_volume_hourly() -> Result<()> {
         )
         .await;
 
-    unsafe {
-        std::env::remove_var("OPENAI_BASE_URL");
-    }
-
     let endpoint = result.expect("Aggregation endpoint IR generation should succeed");
 
     // Verify aggregation SQL patterns
@@ -414,16 +415,15 @@ async fn test_endpoint_swap_volume_hourly() -> Result<()> {
 }
 
 #[tokio::test]
-#[serial]
 async fn test_endpoint_v3_swaps_by_pool() -> Result<()> {
     let server = setup_mock_with_cassette("endpoint_v3_swaps_by_pool").await;
 
-    unsafe {
-        std::env::set_var("OPENAI_BASE_URL", server.uri());
-    }
-
-    let ai_client =
-        smorty::ai::AiClient::new("fake-api-key".to_string(), "gpt-4o".to_string(), 0.7);
+    let ai_client = smorty::ai::AiClient::new_with_base_url(
+        "fake-api-key".to_string(),
+        "gpt-4o".to_string(),
+        0.7,
+        server.uri(),
+    );
 
     let tables = mock_available_tables();
     let result = ai_client
@@ -435,10 +435,6 @@ async fn test_endpoint_v3_swaps_by_pool() -> Result<()> {
         )
         .await;
 
-    unsafe {
-        std::env::remove_var("OPENAI_BASE_URL");
-    }
-
     let endpoint = result.expect("Path param endpoint IR generation should succeed");
 
     // Verify path parameter extraction
@@ -4,10 +4,10 @@
 //! making them deterministic, fast, and free to run.
 
 use anyhow::Result;
-use serde_json::Value;
-use serial_test::serial;
+use serde_json::{Value, json};
+use smorty::config::AiConfig;
 use std::path::Path;
-use wiremock::matchers::{method, path};
+use wiremock::matchers::{body_partial_json, method, path};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
 /// Load a cassette file by name
@@ -52,23 +52,20 @@ async fn setup_mock_with_cassette(cassette_name: &str) -> MockServer {
 }
 
 #[tokio::test]
-#[serial]
 async fn test_weth_transfer_ir_generation() -> Result<()> {
     // 1. Start mock server with recorded cassette
     let server = setup_mock_with_cassette("weth_transfer").await;
 
-    // 2. Set the base URL to point at our mock
-    // SAFETY: We're running tests serially, no other threads accessing this env var
-    unsafe {
-        std::env::set_var("OPENAI_BASE_URL", server.uri());
-    }
-
-    // 3. Load the WETH ABI
+    // 2. Load the WETH ABI
     let abi = load_abi("weth");
 
-    // 4. Create AI client and generate IR
-    let ai_client =
-        smorty::ai::AiClient::new("fake-api-key".to_string(), "gpt-4o".to_string(), 0.7);
+    // 3. Create an AI client pointed at the mock and generate IR
+    let ai_client = smorty::ai::AiClient::new_with_base_url(
+        "fake-api-key".to_string(),
+        "gpt-4o".to_string(),
+        0.7,
+        server.uri(),
+    );
 
     let result = ai_client
         .generate_ir(
@@ -82,13 +79,7 @@ async fn test_weth_transfer_ir_generation() -> Result<()> {
         )
         .await;
 
-    // 5. Clean up env var
-    // SAFETY: We're running tests serially, no other threads accessing this env var
-    unsafe {
-        std::env::remove_var("OPENAI_BASE_URL");
-    }
-
-    // 6. Assert on the result
+    // 4. Assert on the result
     let ir = result.expect("IR generation should succeed");
 
     assert_eq!(ir.event_name, "Transfer");
@@ -137,17 +128,16 @@ async fn test_weth_transfer_ir_generation() -> Result<()> {
 }
 
 #[tokio::test]
-#[serial]
 async fn test_weth_deposit_ir_generation() -> Result<()> {
     let server = setup_mock_with_cassette("weth_deposit").await;
 
-    unsafe {
-        std::env::set_var("OPENAI_BASE_URL", server.uri());
-    }
-
     let abi = load_abi("weth");
-    let ai_client =
-        smorty::ai::AiClient::new("fake-api-key".to_string(), "gpt-4o".to_string(), 0.7);
+    let ai_client = smorty::ai::AiClient::new_with_base_url(
+        "fake-api-key".to_string(),
+        "gpt-4o".to_string(),
+        0.7,
+        server.uri(),
+    );
 
     let result = ai_client
         .generate_ir(
@@ -161,10 +151,6 @@ async fn test_weth_deposit_ir_generation() -> Result<()> {
         )
         .await;
 
-    unsafe {
-        std::env::remove_var("OPENAI_BASE_URL");
-    }
-
     let ir = result.expect("IR generation should succeed");
 
     assert_eq!(ir.event_name, "Deposit");
@@ -182,17 +168,16 @@ async fn test_weth_deposit_ir_generation() -> Result<()> {
 }
 
 #[tokio::test]
-#[serial]
 async fn test_uni_transfer_ir_generation() -> Result<()> {
     let server = setup_mock_with_cassette("uni_transfer").await;
 
-    unsafe {
-        std::env::set_var("OPENAI_BASE_URL", server.uri());
-    }
-
     let abi = load_abi("uni");
-    let ai_client =
-        smorty::ai::AiClient::new("fake-api-key".to_string(), "gpt-4o".to_string(), 0.7);
+    let ai_client = smorty::ai::AiClient::new_with_base_url(
+        "fake-api-key".to_string(),
+        "gpt-4o".to_string(),
+        0.7,
+        server.uri(),
+    );
 
     let result = ai_client
         .generate_ir(
@@ -206,10 +191,6 @@ async fn test_uni_transfer_ir_generation() -> Result<()> {
         )
         .await;
 
-    unsafe {
-        std::env::remove_var("OPENAI_BASE_URL");
-    }
-
     let ir = result.expect("IR generation should succeed");
 
     assert_eq!(ir.event_name, "Transfer");
@@ -224,17 +205,16 @@ async fn test_uni_transfer_ir_generation() -> Result<()> {
 }
 
 #[tokio::test]
-#[serial]
 async fn test_uni_delegate_votes_ir_generation() -> Result<()> {
     let server = setup_mock_with_cassette("uni_delegate_votes").await;
 
-    unsafe {
-        std::env::set_var("OPENAI_BASE_URL", server.uri());
-    }
-
     let abi = load_abi("uni");
-    let ai_client =
-        smorty::ai::AiClient::new("fake-api-key".to_string(), "gpt-4o".to_string(), 0.7);
+    let ai_client = smorty::ai::AiClient::new_with_base_url(
+        "fake-api-key".to_string(),
+        "gpt-4o".to_string(),
+        0.7,
+        server.uri(),
+    );
 
     let result = ai_client
         .generate_ir(
@@ -248,10 +228,6 @@ async fn test_uni_delegate_votes_ir_generation() -> Result<()> {
         )
         .await;
 
-    unsafe {
-        std::env::remove_var("OPENAI_BASE_URL");
-    }
-
     let ir = result.expect("IR generation should succeed");
 
     assert_eq!(ir.event_name, "DelegateVotesChanged");
@@ -271,17 +247,16 @@ async fn test_uni_delegate_votes_ir_generation() -> Result<()> {
 }
 
 #[tokio::test]
-#[serial]
 async fn test_v3_pool_swap_ir_generation() -> Result<()> {
     let server = setup_mock_with_cassette("v3_pool_swap").await;
 
-    unsafe {
-        std::env::set_var("OPENAI_BASE_URL", server.uri());
-    }
-
     let abi = load_abi("uniswap_v3_pool");
-    let ai_client =
-        smorty::ai::AiClient::new("fake-api-key".to_string(), "gpt-4o".to_string(), 0.7);
+    let ai_client = smorty::ai::AiClient::new_with_base_url(
+        "fake-api-key".to_string(),
+        "gpt-4o".to_string(),
+        0.7,
+        server.uri(),
+    );
 
     let result = ai_client
         .generate_ir(
@@ -295,10 +270,6 @@ async fn test_v3_pool_swap_ir_generation() -> Result<()> {
         )
         .await;
 
-    unsafe {
-        std::env::remove_var("OPENAI_BASE_URL");
-    }
-
     let ir = result.expect("IR generation should succeed");
 
     assert_eq!(ir.event_name, "Swap");
@@ -329,17 +300,16 @@ async fn test_v3_pool_swap_ir_generation() -> Result<()> {
 }
 
 #[tokio::test]
-#[serial]
 async fn test_v3_pool_mint_ir_generation() -> Result<()> {
     let server = setup_mock_with_cassette("v3_pool_mint").await;
 
-    unsafe {
-        std::env::set_var("OPENAI_BASE_URL", server.uri());
-    }
-
     let abi = load_abi("uniswap_v3_pool");
-    let ai_client =
-        smorty::ai::AiClient::new("fake-api-key".to_string(), "gpt-4o".to_string(), 0.7);
+    let ai_client = smorty::ai::AiClient::new_with_base_url(
+        "fake-api-key".to_string(),
+        "gpt-4o".to_string(),
+        0.7,
+        server.uri(),
+    );
 
     let result = ai_client
         .generate_ir(
@@ -353,10 +323,6 @@ async fn test_v3_pool_mint_ir_generation() -> Result<()> {
         )
         .await;
 
-    unsafe {
-        std::env::remove_var("OPENAI_BASE_URL");
-    }
-
     let ir = result.expect("IR generation should succeed");
 
     assert_eq!(ir.event_name, "Mint");
@@ -384,17 +350,16 @@ async fn test_v3_pool_mint_ir_generation() -> Result<()> {
 }
 
 #[tokio::test]
-#[serial]
 async fn test_v3_factory_pool_created_ir_generation() -> Result<()> {
     let server = setup_mock_with_cassette("v3_factory_pool_created").await;
 
-    unsafe {
-        std::env::set_var("OPENAI_BASE_URL", server.uri());
-    }
-
     let abi = load_abi("uniswap_v3_factory");
-    let ai_client =
-        smorty::ai::AiClient::new("fake-api-key".to_string(), "gpt-4o".to_string(), 0.7);
+    let ai_client = smorty::ai::AiClient::new_with_base_url(
+        "fake-api-key".to_string(),
+        "gpt-4o".to_string(),
+        0.7,
+        server.uri(),
+    );
 
     let result = ai_client
         .generate_ir(
@@ -408,10 +373,6 @@ async fn test_v3_factory_pool_created_ir_generation() -> Result<()> {
         )
         .await;
 
-    unsafe {
-        std::env::remove_var("OPENAI_BASE_URL");
-    }
-
     let ir = result.expect("IR generation should succeed");
 
     assert_eq!(ir.event_name, "PoolCreated");
@@ -438,3 +399,168 @@ async fn test_v3_factory_pool_created_ir_generation() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_spec_generation_uses_ai_spec_overrides() -> Result<()> {
+    let server = MockServer::start().await;
+
+    // Only respond if the request carries the [ai.spec] settings
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({
+            "model": "spec-model",
+            "temperature": 0.0,
+            "max_completion_tokens": 4000,
+            "reasoning_effort": "low"
+        })))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string(load_cassette("weth_transfer"))
+                .insert_header("content-type", "application/json"),
+        )
+        .mount(&server)
+        .await;
+
+    let config: AiConfig = toml::from_str(&format!(
+        r#"
+auditLog = false
+
+[openai]
+model = "gpt-4o"
+apiKey = "fake-api-key"
+temperature = 0.7
+baseUrl = "{}"
+
+[spec]
+model = "spec-model"
+temperature = 0.0
+maxTokens = 4000
+reasoningEffort = "low"
+
+[endpoint]
+model = "endpoint-model"
+"#,
+        server.uri()
+    ))?;
+    let ai_client = smorty::ai::AiClient::from_config(&config);
+
+    let result = ai_client
+        .generate_ir(
+            "WETH",
+            "transfers",
+            Some(0),
+            "0x0000000000000000000000000000000000000001",
+            "mainnet",
+            &load_abi("weth"),
+            "Track all WETH token transfers",
+        )
+        .await;
+
+    let ir = result.expect("IR generation should use the spec overrides");
+    assert_eq!(ir.event_name, "Transfer");
+
+    Ok(())
+}
+
+/// Build a server-sent event stream that delivers `content` in small chunks
+fn streamed_response(content: &str) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let mut body = String::new();
+    for chunk in chars.chunks(16) {
+        let event = json!({
+            "id": "chatcmpl-test",
+            "object": "chat.completion.chunk",
+            "created": 0,
+            "model": "gpt-4o",
+            "choices": [{
+                "index": 0,
+                "delta": { "content": chunk.iter().collect::<String>() },
+                "finish_reason": null
+            }]
+        });
+        body.push_str(&format!("data: {}\n\n", event));
+    }
+    body.push_str("data: [DONE]\n\n");
+    body
+}
+
+async fn setup_streaming_mock(content: &str) -> MockServer {
+    let server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({ "stream": true })))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_raw(streamed_response(content), "text/event-stream"),
+        )
+        .mount(&server)
+        .await;
+
+    server
+}
+
+#[tokio::test]
+async fn test_verbose_ir_generation_streams_response() -> Result<()> {
+    let cassette: Value = serde_json::from_str(&load_cassette("weth_transfer"))?;
+    let content = cassette["choices"][0]["message"]["content"]
+        .as_str()
+        .expect("cassette has content");
+    let server = setup_streaming_mock(content).await;
+
+    let ai_client = smorty::ai::AiClient::new_with_base_url(
+        "fake-api-key".to_string(),
+        "gpt-4o".to_string(),
+        0.7,
+        server.uri(),
+    )
+    .with_verbose(true);
+
+    let result = ai_client
+        .generate_ir(
+            "WETH",
+            "transfers",
+            Some(0),
+            "0x0000000000000000000000000000000000000001",
+            "mainnet",
+            &load_abi("weth"),
+            "Track all WETH token transfers",
+        )
+        .await;
+
+    let ir = result.expect("Streamed IR generation should succeed");
+    assert_eq!(ir.event_name, "Transfer");
+    assert_eq!(ir.event_signature, "Transfer(address,address,uint256)");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_verbose_ir_generation_rejects_malformed_stream() -> Result<()> {
+    let server = setup_streaming_mock("Sure! Here is the IR: {\"event_name\": \"Transfer\"}").await;
+
+    let ai_client = smorty::ai::AiClient::new_with_base_url(
+        "fake-api-key".to_string(),
+        "gpt-4o".to_string(),
+        0.7,
+        server.uri(),
+    )
+    .with_verbose(true);
+
+    let result = ai_client
+        .generate_ir(
+            "WETH",
+            "transfers",
+            Some(0),
+            "0x0000000000000000000000000000000000000001",
+            "mainnet",
+            &load_abi("weth"),
+            "Track all WETH token transfers",
+        )
+        .await;
+
+    let error = result.expect_err("Malformed stream should fail");
+    assert!(format!("{:#}", error).contains("Malformed JSON"));
+
+    Ok(())
+}